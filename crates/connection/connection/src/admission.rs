@@ -0,0 +1,552 @@
+//! Server-side admission control.
+//!
+//! Adding [`AdmissionControl`] on a server entity protects it against abusive peers:
+//! - handshake packets (for example netcode connection requests and challenge responses) are
+//!   rate-limited per source IP address with a token bucket. Packets over the limit are dropped
+//!   without any answer;
+//! - the number of handshakes in flight is capped. A [`LinkOf`] is in flight from the moment it is
+//!   spawned until it becomes [`Connected`], and is despawned if it doesn't connect within
+//!   [`AdmissionControl::handshake_timeout`]. New peers are ignored while the cap is reached;
+//! - peers in the [`BanList`] are refused, by [`PeerId`] or by IP address range. The connection
+//!   layer denies banned peers with [`DeniedReason::Banned`](crate::shared::DeniedReason::Banned)
+//!   when its protocol supports it, and a banned peer that still gets [`Connected`] is
+//!   disconnected right away.
+//!
+//! The IO and connection layers (for example `lightyear_udp` and `lightyear_netcode`) apply the
+//! checks that need the source address of the packets.
+//!
+//! The [`BanList`] implements `Serialize` and `Deserialize` so that it can be stored and loaded
+//! when the server restarts. Ban expiry is expressed in seconds since the UNIX epoch for the
+//! same reason.
+use crate::client::{Connected, Disconnecting};
+use crate::client_of::ClientOf;
+use alloc::string::String;
+use alloc::vec::Vec;
+use bevy_app::{App, Last, Plugin};
+use bevy_ecs::lifecycle::HookContext;
+use bevy_ecs::prelude::*;
+use bevy_ecs::world::DeferredWorld;
+use bevy_platform::collections::HashMap;
+use core::fmt::{Display, Formatter};
+use core::net::IpAddr;
+use core::str::FromStr;
+use core::time::Duration;
+use lightyear_core::id::{PeerId, RemoteId};
+use lightyear_core::time::Instant;
+use lightyear_link::prelude::LinkOf;
+use serde::{Deserialize, Serialize};
+#[allow(unused_imports)]
+use tracing::{debug, info, trace};
+
+/// Token bucket parameters: a source can send `burst` packets at once, and gains one more
+/// packet every `refill_interval`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub refill_interval: Duration,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        // netcode clients send their handshake packets 10 times per second, and several clients
+        // can share the same address behind a NAT
+        Self {
+            burst: 32,
+            refill_interval: Duration::from_millis(50),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: u32,
+    last_refill: Instant,
+}
+
+/// Per-address token buckets.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    limit: RateLimit,
+    buckets: HashMap<IpAddr, Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: HashMap::default(),
+        }
+    }
+
+    /// Consumes one token for `addr`, and returns false if the address is over its limit.
+    pub fn check(&mut self, addr: IpAddr, now: Instant) -> bool {
+        let limit = self.limit;
+        let bucket = self.buckets.entry(addr).or_insert(Bucket {
+            tokens: limit.burst,
+            last_refill: now,
+        });
+        if !limit.refill_interval.is_zero() {
+            let elapsed = now.saturating_duration_since(bucket.last_refill);
+            let refills = (elapsed.as_nanos() / limit.refill_interval.as_nanos()) as u32;
+            if refills > 0 {
+                bucket.tokens = bucket.tokens.saturating_add(refills).min(limit.burst);
+                bucket.last_refill = bucket.last_refill + limit.refill_interval * refills;
+            }
+        }
+        if bucket.tokens == 0 {
+            return false;
+        }
+        bucket.tokens -= 1;
+        true
+    }
+
+    /// Forgets the addresses whose bucket is full again, to bound the memory used.
+    pub fn prune(&mut self, now: Instant) {
+        let limit = self.limit;
+        let full_after = limit.refill_interval * limit.burst;
+        self.buckets
+            .retain(|_, bucket| now.saturating_duration_since(bucket.last_refill) < full_after);
+    }
+
+    /// Number of addresses currently tracked.
+    pub fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+}
+
+/// A range of IP addresses in CIDR notation, for example `10.0.0.0/8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct IpRange {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpRange {
+    /// Returns `None` if `prefix_len` is larger than the number of bits of the address.
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Option<Self> {
+        let max_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        (prefix_len <= max_len).then_some(Self { addr, prefix_len })
+    }
+
+    /// Range containing only `addr`.
+    pub fn single(addr: IpAddr) -> Self {
+        let prefix_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        Self { addr, prefix_len }
+    }
+
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.addr, addr) {
+            (IpAddr::V4(range), IpAddr::V4(addr)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(range) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(range), IpAddr::V6(addr)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(range) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl From<IpAddr> for IpRange {
+    fn from(addr: IpAddr) -> Self {
+        Self::single(addr)
+    }
+}
+
+impl Display for IpRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// Error returned when parsing an invalid [`IpRange`].
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid IP range, expected an address or CIDR notation like `10.0.0.0/8`")]
+pub struct InvalidIpRange;
+
+impl FromStr for IpRange {
+    type Err = InvalidIpRange;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((addr, prefix_len)) = s.split_once('/') else {
+            return s.parse().map(Self::single).map_err(|_| InvalidIpRange);
+        };
+        let addr = addr.parse().map_err(|_| InvalidIpRange)?;
+        let prefix_len = prefix_len.parse().map_err(|_| InvalidIpRange)?;
+        Self::new(addr, prefix_len).ok_or(InvalidIpRange)
+    }
+}
+
+/// What a [`Ban`] applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BanTarget {
+    Peer(PeerId),
+    Address(IpRange),
+}
+
+impl BanTarget {
+    fn matches(&self, peer: Option<PeerId>, addr: Option<IpAddr>) -> bool {
+        match self {
+            BanTarget::Peer(banned) => peer == Some(*banned),
+            BanTarget::Address(range) => addr.is_some_and(|addr| range.contains(addr)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ban {
+    pub target: BanTarget,
+    /// Seconds since the UNIX epoch after which the ban is lifted. `None` for a permanent ban.
+    pub expires_at: Option<u64>,
+    pub reason: Option<String>,
+}
+
+impl Ban {
+    pub fn is_active(&self, now: u64) -> bool {
+        self.expires_at.is_none_or(|expires_at| now < expires_at)
+    }
+}
+
+/// List of banned peers and address ranges.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BanList {
+    bans: Vec<Ban>,
+}
+
+impl BanList {
+    /// Adds a ban, replacing any existing ban with the same target.
+    pub fn ban(&mut self, target: BanTarget, expires_at: Option<u64>, reason: Option<String>) {
+        self.unban(&target);
+        self.bans.push(Ban {
+            target,
+            expires_at,
+            reason,
+        });
+    }
+
+    /// Removes the ban of `target`, and returns true if there was one.
+    pub fn unban(&mut self, target: &BanTarget) -> bool {
+        let len = self.bans.len();
+        self.bans.retain(|ban| ban.target != *target);
+        self.bans.len() != len
+    }
+
+    /// Returns the active ban that applies to a peer or to its address, if any.
+    pub fn find(&self, peer: Option<PeerId>, addr: Option<IpAddr>, now: u64) -> Option<&Ban> {
+        self.bans
+            .iter()
+            .find(|ban| ban.is_active(now) && ban.target.matches(peer, addr))
+    }
+
+    pub fn is_banned(&self, peer: Option<PeerId>, addr: Option<IpAddr>, now: u64) -> bool {
+        self.find(peer, addr, now).is_some()
+    }
+
+    /// Removes the bans that expired before `now`.
+    pub fn remove_expired(&mut self, now: u64) {
+        self.bans.retain(|ban| ban.is_active(now));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Ban> {
+        self.bans.iter()
+    }
+}
+
+/// Returns the number of seconds since the UNIX epoch, used for the expiry of bans.
+///
+/// Without the `std` feature there is no wall clock, and bans never expire.
+pub fn unix_now() -> u64 {
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::SystemTime::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs())
+    }
+    #[cfg(not(all(feature = "std", not(target_arch = "wasm32"))))]
+    {
+        0
+    }
+}
+
+/// Enables admission control on a server entity.
+///
+/// See the [module-level documentation](self).
+#[derive(Component, Debug, Clone)]
+pub struct AdmissionControl {
+    pub ban_list: BanList,
+    /// Maximum number of links of this server that can be handshaking at the same time.
+    pub max_pending_handshakes: Option<usize>,
+    /// Links that are not connected after this duration are despawned.
+    pub handshake_timeout: Duration,
+    limiter: Option<RateLimiter>,
+    pending_handshakes: usize,
+}
+
+impl Default for AdmissionControl {
+    fn default() -> Self {
+        Self {
+            ban_list: BanList::default(),
+            max_pending_handshakes: Some(1024),
+            handshake_timeout: Duration::from_secs(10),
+            limiter: Some(RateLimiter::new(RateLimit::default())),
+            pending_handshakes: 0,
+        }
+    }
+}
+
+impl AdmissionControl {
+    pub fn with_ban_list(mut self, ban_list: BanList) -> Self {
+        self.ban_list = ban_list;
+        self
+    }
+
+    /// Sets the rate limit of handshake packets per source address, or disables rate limiting.
+    pub fn with_rate_limit(mut self, limit: Option<RateLimit>) -> Self {
+        self.limiter = limit.map(RateLimiter::new);
+        self
+    }
+
+    pub fn with_max_pending_handshakes(mut self, max_pending_handshakes: Option<usize>) -> Self {
+        self.max_pending_handshakes = max_pending_handshakes;
+        self
+    }
+
+    pub fn with_handshake_timeout(mut self, handshake_timeout: Duration) -> Self {
+        self.handshake_timeout = handshake_timeout;
+        self
+    }
+
+    /// Returns false if a handshake packet from `addr` exceeds the rate limit and should be dropped.
+    pub fn allow_handshake_packet(&mut self, addr: IpAddr) -> bool {
+        let Some(limiter) = &mut self.limiter else {
+            return true;
+        };
+        let allowed = limiter.check(addr, Instant::now());
+        if !allowed {
+            trace!(?addr, "handshake packet dropped by the rate limiter");
+        }
+        allowed
+    }
+
+    /// Returns true if `additional` more handshakes can start without exceeding
+    /// [`max_pending_handshakes`](Self::max_pending_handshakes).
+    pub fn can_start_handshakes(&self, in_flight: usize, additional: usize) -> bool {
+        self.max_pending_handshakes
+            .is_none_or(|max| in_flight + additional <= max)
+    }
+
+    /// Number of links of this server that are not connected yet.
+    pub fn pending_handshakes(&self) -> usize {
+        self.pending_handshakes
+    }
+}
+
+/// Marks a [`LinkOf`] of a server with [`AdmissionControl`] that is not connected yet.
+#[derive(Component, Debug)]
+#[component(on_remove = PendingAdmission::on_remove)]
+pub struct PendingAdmission {
+    server: Entity,
+    since: Instant,
+}
+
+impl PendingAdmission {
+    fn on_remove(mut world: DeferredWorld, context: HookContext) {
+        let server = world
+            .get::<PendingAdmission>(context.entity)
+            .unwrap()
+            .server;
+        if let Some(mut admission) = world.get_mut::<AdmissionControl>(server) {
+            admission.pending_handshakes = admission.pending_handshakes.saturating_sub(1);
+        }
+    }
+}
+
+pub struct AdmissionPlugin;
+
+impl AdmissionPlugin {
+    fn on_link_of_added(
+        trigger: On<Add, LinkOf>,
+        link_of: Query<&LinkOf, Without<Connected>>,
+        mut server: Query<&mut AdmissionControl>,
+        mut commands: Commands,
+    ) {
+        if let Ok(link_of) = link_of.get(trigger.entity)
+            && let Ok(mut admission) = server.get_mut(link_of.server)
+        {
+            admission.pending_handshakes += 1;
+            commands.entity(trigger.entity).insert(PendingAdmission {
+                server: link_of.server,
+                since: Instant::now(),
+            });
+        }
+    }
+
+    /// A connected peer is not pending anymore, but it is disconnected if it is banned.
+    fn on_connected(
+        trigger: On<Add, Connected>,
+        query: Query<(&LinkOf, &RemoteId, Has<PendingAdmission>), With<ClientOf>>,
+        server: Query<&AdmissionControl>,
+        mut commands: Commands,
+    ) {
+        let Ok((link_of, remote_id, pending)) = query.get(trigger.entity) else {
+            return;
+        };
+        if pending {
+            commands.entity(trigger.entity).remove::<PendingAdmission>();
+        }
+        if let Ok(admission) = server.get(link_of.server)
+            && let Some(ban) = admission.ban_list.find(Some(remote_id.0), None, unix_now())
+        {
+            info!(entity = ?trigger.entity, ?ban, "disconnecting banned peer");
+            commands.entity(trigger.entity).insert(Disconnecting);
+        }
+    }
+
+    /// Despawn the links that didn't connect in time, and forget idle rate limiter buckets.
+    fn expire_pending(
+        pending: Query<(Entity, &PendingAdmission)>,
+        mut servers: Query<&mut AdmissionControl>,
+        mut commands: Commands,
+    ) {
+        let now = Instant::now();
+        for (entity, pending) in pending.iter() {
+            let Ok(admission) = servers.get(pending.server) else {
+                continue;
+            };
+            if now.saturating_duration_since(pending.since) >= admission.handshake_timeout {
+                debug!(?entity, "despawning link that did not connect in time");
+                commands.entity(entity).try_despawn();
+            }
+        }
+        for mut admission in servers.iter_mut() {
+            if let Some(limiter) = admission.limiter.as_mut().filter(|l| !l.is_empty()) {
+                limiter.prune(now);
+            }
+        }
+    }
+}
+
+impl Plugin for AdmissionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(Self::on_link_of_added);
+        app.add_observer(Self::on_connected);
+        app.add_systems(Last, Self::expire_pending);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network_topology::NetworkingMetadata;
+    use core::net::Ipv4Addr;
+    use lightyear_link::prelude::Server;
+
+    #[test]
+    fn rate_limiter_refills_over_time() {
+        let limit = RateLimit {
+            burst: 2,
+            refill_interval: Duration::from_millis(100),
+        };
+        let mut limiter = RateLimiter::new(limit);
+        let addr = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
+        let other = IpAddr::V4(Ipv4Addr::new(5, 6, 7, 8));
+        let now = Instant::now();
+        assert!(limiter.check(addr, now));
+        assert!(limiter.check(addr, now));
+        assert!(!limiter.check(addr, now));
+        // addresses have independent buckets
+        assert!(limiter.check(other, now));
+
+        assert!(limiter.check(addr, now + Duration::from_millis(100)));
+        assert!(!limiter.check(addr, now + Duration::from_millis(150)));
+
+        limiter.prune(now + Duration::from_secs(1));
+        assert!(limiter.is_empty());
+    }
+
+    #[test]
+    fn ban_list_matches_peers_and_ranges() {
+        let mut bans = BanList::default();
+        bans.ban(
+            BanTarget::Address("10.0.0.0/8".parse().unwrap()),
+            None,
+            None,
+        );
+        bans.ban(BanTarget::Peer(PeerId::Netcode(7)), Some(100), None);
+
+        let inside = IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3));
+        let outside = IpAddr::V4(Ipv4Addr::new(11, 0, 0, 1));
+        assert!(bans.is_banned(None, Some(inside), 0));
+        assert!(!bans.is_banned(None, Some(outside), 0));
+        assert!(bans.is_banned(Some(PeerId::Netcode(7)), Some(outside), 99));
+        // the ban of the peer expired
+        assert!(!bans.is_banned(Some(PeerId::Netcode(7)), Some(outside), 100));
+
+        bans.remove_expired(100);
+        assert_eq!(bans.iter().count(), 1);
+        assert!(bans.unban(&BanTarget::Address("10.0.0.0/8".parse().unwrap())));
+        assert!(!bans.is_banned(None, Some(inside), 0));
+    }
+
+    #[test]
+    fn ip_range_parsing() {
+        let range: IpRange = "192.168.1.0/24".parse().unwrap();
+        assert!(range.contains(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 200))));
+        assert!(!range.contains(IpAddr::V4(Ipv4Addr::new(192, 168, 2, 1))));
+        assert!(!range.contains("::1".parse().unwrap()));
+        assert_eq!(range.to_string(), "192.168.1.0/24");
+
+        let everything: IpRange = "0.0.0.0/0".parse().unwrap();
+        assert!(everything.contains(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8))));
+        assert_eq!("10.0.0.0/33".parse::<IpRange>(), Err(InvalidIpRange));
+    }
+
+    #[test]
+    fn pending_links_are_counted_and_expire() {
+        let mut app = App::new();
+        app.init_resource::<NetworkingMetadata>();
+        app.add_plugins(AdmissionPlugin);
+        let server = app
+            .world_mut()
+            .spawn((
+                Server::default(),
+                AdmissionControl::default().with_handshake_timeout(Duration::ZERO),
+            ))
+            .id();
+        let link = app.world_mut().spawn(LinkOf { server }).id();
+        app.world_mut().flush();
+        assert_eq!(
+            app.world()
+                .get::<AdmissionControl>(server)
+                .unwrap()
+                .pending_handshakes(),
+            1
+        );
+
+        app.update();
+        assert!(app.world().get_entity(link).is_err());
+        assert_eq!(
+            app.world()
+                .get::<AdmissionControl>(server)
+                .unwrap()
+                .pending_handshakes(),
+            0
+        );
+    }
+}
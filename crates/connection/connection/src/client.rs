@@ -1,4 +1,6 @@
 use crate::network_topology::NetworkingMetadata;
+use crate::resumption::{SessionResumptionPlugin, Suspended};
+use crate::shared::DeniedReason;
 use alloc::string::String;
 use bevy_app::{App, Plugin};
 use bevy_ecs::lifecycle::HookContext;
@@ -96,6 +98,8 @@ pub enum DisconnectedReason {
     ByPeer(Option<String>),
     /// The connection transport or protocol encountered an error.
     TransportError(String),
+    /// The remote peer denied the connection.
+    Denied(DeniedReason),
 }
 
 impl core::fmt::Display for DisconnectedReason {
@@ -108,6 +112,7 @@ impl core::fmt::Display for DisconnectedReason {
             Self::ByPeer(Some(reason)) => write!(f, "Disconnected by peer: {reason}"),
             Self::ByPeer(None) => f.write_str("Disconnected by peer"),
             Self::TransportError(reason) => write!(f, "Transport error: {reason}"),
+            Self::Denied(reason) => write!(f, "Connection denied: {reason}"),
         }
     }
 }
@@ -130,7 +135,7 @@ impl Disconnected {
         world
             .commands()
             .entity(context.entity)
-            .remove::<(Connecting, Disconnecting, Connected)>();
+            .remove::<(Connecting, Disconnecting, Connected, Suspended)>();
     }
 }
 
@@ -143,7 +148,7 @@ impl Disconnecting {
         world
             .commands()
             .entity(context.entity)
-            .remove::<(Connected, Connecting, Disconnected)>();
+            .remove::<(Connected, Connecting, Disconnected, Suspended)>();
     }
 }
 
@@ -157,6 +162,7 @@ pub struct ClientState {
     pub connecting: Has<Connecting>,
     pub disconnecting: Has<Disconnecting>,
     pub disconnected: Has<Disconnected>,
+    pub suspended: Has<Suspended>,
 }
 
 impl ClientStateItem<'_, '_> {
@@ -172,8 +178,16 @@ impl ClientStateItem<'_, '_> {
         self.disconnecting
     }
 
+    /// Returns true if the connection was interrupted but its session can still be resumed.
+    ///
+    /// A suspended connection can also be [`Connecting`] while it tries to resume.
+    pub fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+
     pub fn is_disconnected(&self) -> bool {
-        self.disconnected || !(self.connected || self.connecting || self.disconnecting)
+        self.disconnected
+            || !(self.connected || self.connecting || self.disconnecting || self.suspended)
     }
 }
 
@@ -210,6 +224,9 @@ impl ConnectionPlugin {
 impl Plugin for ConnectionPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<NetworkingMetadata>();
+        if !app.is_plugin_added::<SessionResumptionPlugin>() {
+            app.add_plugins(SessionResumptionPlugin);
+        }
         app.add_observer(Self::connect);
         app.add_observer(Self::disconnect_if_link_fails);
     }
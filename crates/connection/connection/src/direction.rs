@@ -5,3 +5,14 @@ pub enum NetworkDirection {
     ServerToClient,
     Bidirectional,
 }
+
+impl NetworkDirection {
+    /// Direction in which a reply to a packet sent in this direction is sent
+    pub fn reverse(self) -> Self {
+        match self {
+            Self::ClientToServer => Self::ServerToClient,
+            Self::ServerToClient => Self::ClientToServer,
+            Self::Bidirectional => Self::Bidirectional,
+        }
+    }
+}
@@ -20,10 +20,13 @@ This crates provide concepts that are only useful for a client-server architectu
 
 extern crate alloc;
 extern crate core;
+#[cfg(feature = "std")]
+extern crate std;
 
 use bevy_app::{App, Plugin};
 use bevy_ecs::schedule::SystemSet;
 
+pub mod admission;
 pub mod client;
 
 pub mod server;
@@ -39,6 +42,8 @@ pub mod shared;
 pub mod host;
 pub mod network_topology;
 pub mod p2p;
+pub mod protocol;
+pub mod resumption;
 
 #[deprecated(note = "Use ConnectionSystems instead")]
 pub type ConnectionSet = ConnectionSystems;
@@ -70,7 +75,9 @@ pub mod prelude {
         Client, ClientState, Connect, Connected, Connecting, ConnectionError, Disconnect,
         Disconnected, DisconnectedReason,
     };
-    pub use crate::p2p::P2P;
+    pub use crate::p2p::{P2P, P2PInputFrontier};
+    pub use crate::protocol::AppVersion;
+    pub use crate::resumption::{ResumeToken, SessionResumed, SessionResumption, Suspended};
 
     #[cfg(feature = "client")]
     pub mod client {
@@ -82,6 +89,7 @@ pub mod prelude {
 
     #[cfg(feature = "server")]
     pub mod server {
+        pub use crate::admission::{AdmissionControl, BanList, BanTarget, IpRange, RateLimit};
         pub use crate::client_of::ClientOf;
         pub use crate::identity::{is_headless_server, is_host_server, is_server};
         pub use crate::server::{ConnectionError, Start, Started, Starting, Stop, Stopped};
@@ -1,6 +1,7 @@
 use crate::client::Client;
 use bevy_ecs::prelude::*;
 use bevy_reflect::Reflect;
+use lightyear_core::tick::Tick;
 
 /// Participation state of a direct peer Link in a P2P session.
 ///
@@ -26,3 +27,13 @@ pub enum P2P {
     /// [`NetworkTopology::P2P`](crate::network_topology::NetworkTopology::P2P).
     Joined,
 }
+
+/// Latest tick through which input from the remote peer of a [`P2P`] Link has been received
+/// without gaps.
+///
+/// The P2P session inserts it on the Links that join a session, with the tick before the start
+/// of the session, and input plugins only advance it when a message covers the ticks right after
+/// it. When the remote peer leaves a running session, the P2P session uses it as this peer's
+/// proposal for the last fully confirmed input tick of the departed peer during host migration.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub struct P2PInputFrontier(pub Tick);
@@ -0,0 +1,287 @@
+//! Description of the protocol of a peer, compared during the connection handshake
+//!
+//! The [`ProtocolManifest`] lists the name and network id of every registered message, component
+//! and channel. Together with the [`AppVersion`], it forms the [`Protocol`] of a peer. A client
+//! sends its app version and the [`hash`](Protocol::hash) of its protocol in its connection
+//! request, and the server denies the request before the client is connected if the hash differs
+//! from its own.
+//!
+//! The connection request has no room for the list of registered types, so the denial carries a
+//! [`ProtocolDigest`] of the server's protocol instead. The client compares it with its own
+//! protocol to build the [`ProtocolMismatch`]: the app versions and the types that differ.
+use crate::shared::{ProtocolMismatch, ProtocolRegistry, TypeMismatch};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use bevy_ecs::resource::Resource;
+use core::hash::Hasher;
+use seahash::SeaHasher;
+
+/// Version of the app, compared during the connection handshake.
+///
+/// A client is denied if its version differs from the server's version, even if all their
+/// registered types match.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq)]
+pub struct AppVersion(pub String);
+
+impl AppVersion {
+    pub fn new(version: impl Into<String>) -> Self {
+        Self(version.into())
+    }
+}
+
+/// A type registered in the protocol
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ProtocolEntry {
+    pub net_id: u16,
+    /// Name of the type, as returned by [`core::any::type_name`]
+    pub name: String,
+}
+
+/// Name and network id of every message, component and channel registered in the app.
+///
+/// lightyear inserts it once the app is built.
+#[derive(Resource, Debug, PartialEq, Eq, Clone, Default)]
+pub struct ProtocolManifest {
+    pub messages: Vec<ProtocolEntry>,
+    pub components: Vec<ProtocolEntry>,
+    pub channels: Vec<ProtocolEntry>,
+}
+
+impl ProtocolManifest {
+    /// Create the manifest from the network id and the name of the registered types
+    pub fn new<'a>(
+        messages: impl Iterator<Item = (u16, &'a str)>,
+        components: impl Iterator<Item = (u16, &'a str)>,
+        channels: impl Iterator<Item = (u16, &'a str)>,
+    ) -> Self {
+        fn entries<'a>(iter: impl Iterator<Item = (u16, &'a str)>) -> Vec<ProtocolEntry> {
+            let mut entries: Vec<ProtocolEntry> = iter
+                .map(|(net_id, name)| ProtocolEntry {
+                    net_id,
+                    name: name.to_string(),
+                })
+                .collect();
+            entries.sort_unstable_by_key(|entry| entry.net_id);
+            entries
+        }
+        Self {
+            messages: entries(messages),
+            components: entries(components),
+            channels: entries(channels),
+        }
+    }
+
+    /// Iterate through the registered types, with the registry they belong to
+    pub fn entries(&self) -> impl Iterator<Item = (ProtocolRegistry, &ProtocolEntry)> {
+        [
+            (ProtocolRegistry::Message, &self.messages),
+            (ProtocolRegistry::Component, &self.components),
+            (ProtocolRegistry::Channel, &self.channels),
+        ]
+        .into_iter()
+        .flat_map(|(registry, entries)| entries.iter().map(move |entry| (registry, entry)))
+    }
+}
+
+/// App version and registered types of a peer
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Protocol {
+    pub app_version: String,
+    pub manifest: ProtocolManifest,
+}
+
+impl Protocol {
+    /// The protocol of a peer that has no [`AppVersion`] or no [`ProtocolManifest`] is empty.
+    pub fn new(app_version: Option<&AppVersion>, manifest: Option<&ProtocolManifest>) -> Self {
+        Self {
+            app_version: app_version
+                .map(|version| version.0.clone())
+                .unwrap_or_default(),
+            manifest: manifest.cloned().unwrap_or_default(),
+        }
+    }
+
+    /// Hash of the app version and of the registered types, identical on every platform
+    pub fn hash(&self) -> u64 {
+        let mut hasher = SeaHasher::new();
+        hasher.write(self.app_version.as_bytes());
+        hasher.write_u8(0xff);
+        for (registry, entry) in self.manifest.entries() {
+            hasher.write_u8(registry as u8);
+            hasher.write(&entry.net_id.to_le_bytes());
+            hasher.write(entry.name.as_bytes());
+            hasher.write_u8(0xff);
+        }
+        hasher.finish()
+    }
+
+    /// Describe the protocol compactly, to send it to a client whose protocol differs
+    pub fn digest(&self) -> ProtocolDigest {
+        ProtocolDigest {
+            app_version: self.app_version.clone(),
+            types: self
+                .manifest
+                .entries()
+                .map(|(registry, entry)| TypeDigest {
+                    registry,
+                    net_id: entry.net_id,
+                    name_hash: name_hash(&entry.name),
+                })
+                .collect(),
+            complete: true,
+        }
+    }
+
+    /// Compare the protocol of a client (`self`) with the digest of the server's protocol
+    pub fn mismatch(&self, server: &ProtocolDigest) -> ProtocolMismatch {
+        let app_version = (self.app_version != server.app_version)
+            .then(|| (server.app_version.clone(), self.app_version.clone()));
+        let mut types = Vec::new();
+        for server_type in &server.types {
+            let client_entry = self.manifest.entries().find(|(registry, entry)| {
+                *registry == server_type.registry && name_hash(&entry.name) == server_type.name_hash
+            });
+            match client_entry {
+                Some((_, entry)) if entry.net_id == server_type.net_id => {}
+                Some((registry, entry)) => types.push(TypeMismatch {
+                    registry,
+                    name: entry.name.clone(),
+                    server_net_id: Some(server_type.net_id),
+                    client_net_id: Some(entry.net_id),
+                }),
+                // the client doesn't know the name of a type that only the server registered
+                None => types.push(TypeMismatch {
+                    registry: server_type.registry,
+                    name: format!("<unknown type {:08x}>", server_type.name_hash),
+                    server_net_id: Some(server_type.net_id),
+                    client_net_id: None,
+                }),
+            }
+        }
+        // the types that only the client registered are only known if the digest is complete
+        if server.complete {
+            for (registry, entry) in self.manifest.entries() {
+                let hash = name_hash(&entry.name);
+                if !server
+                    .types
+                    .iter()
+                    .any(|ty| ty.registry == registry && ty.name_hash == hash)
+                {
+                    types.push(TypeMismatch {
+                        registry,
+                        name: entry.name.clone(),
+                        server_net_id: None,
+                        client_net_id: Some(entry.net_id),
+                    });
+                }
+            }
+        }
+        types.sort_unstable_by(|a, b| (a.registry, &a.name).cmp(&(b.registry, &b.name)));
+        ProtocolMismatch { app_version, types }
+    }
+}
+
+/// Compact description of the protocol of the server, sent to a client whose protocol differs.
+///
+/// The names of the types are replaced by a hash so that the digest fits in a single packet.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ProtocolDigest {
+    pub app_version: String,
+    pub types: Vec<TypeDigest>,
+    /// False if some types of the server were left out of [`types`](Self::types) to fit in a
+    /// packet
+    pub complete: bool,
+}
+
+/// A type registered in the protocol of the server, as sent in a [`ProtocolDigest`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TypeDigest {
+    pub registry: ProtocolRegistry,
+    pub net_id: u16,
+    /// Hash of the name of the type
+    pub name_hash: u32,
+}
+
+fn name_hash(name: &str) -> u32 {
+    seahash::hash(name.as_bytes()) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn protocol(app_version: &str, components: &[(u16, &str)]) -> Protocol {
+        Protocol {
+            app_version: app_version.to_string(),
+            manifest: ProtocolManifest::new(
+                [(0, "game::Chat")].into_iter(),
+                components.iter().copied(),
+                [(0, "game::Reliable")].into_iter(),
+            ),
+        }
+    }
+
+    #[test]
+    fn hash_depends_on_the_version_and_the_types() {
+        let server = protocol("1.1.0", &[(0, "game::Health"), (1, "game::Shield")]);
+        assert_eq!(
+            server.hash(),
+            protocol("1.1.0", &[(1, "game::Shield"), (0, "game::Health")]).hash()
+        );
+        assert_ne!(
+            server.hash(),
+            protocol("1.0.0", &[(0, "game::Health"), (1, "game::Shield")]).hash()
+        );
+        assert_ne!(
+            server.hash(),
+            protocol("1.1.0", &[(0, "game::Shield"), (1, "game::Health")]).hash()
+        );
+    }
+
+    #[test]
+    fn mismatch_lists_the_types_that_differ() {
+        let server = protocol("1.1.0", &[(0, "game::Health"), (1, "game::Shield")]);
+        assert!(server.mismatch(&server.digest()).is_empty());
+
+        let client = protocol("1.0.0", &[(0, "game::Shield"), (1, "game::Mana")]);
+        let mismatch = client.mismatch(&server.digest());
+        assert_eq!(
+            mismatch.app_version,
+            Some(("1.1.0".to_string(), "1.0.0".to_string()))
+        );
+        assert_eq!(
+            mismatch.types,
+            vec![
+                TypeMismatch {
+                    registry: ProtocolRegistry::Component,
+                    name: format!("<unknown type {:08x}>", name_hash("game::Health")),
+                    server_net_id: Some(0),
+                    client_net_id: None,
+                },
+                TypeMismatch {
+                    registry: ProtocolRegistry::Component,
+                    name: "game::Mana".to_string(),
+                    server_net_id: None,
+                    client_net_id: Some(1),
+                },
+                TypeMismatch {
+                    registry: ProtocolRegistry::Component,
+                    name: "game::Shield".to_string(),
+                    server_net_id: Some(1),
+                    client_net_id: Some(0),
+                },
+            ]
+        );
+
+        // without the full list of server types, the types that only the client has are unknown
+        let mut digest = server.digest();
+        digest
+            .types
+            .retain(|ty| ty.registry != ProtocolRegistry::Channel);
+        digest.complete = false;
+        let mismatch = client.mismatch(&digest);
+        assert!(mismatch.types.iter().all(|ty| ty.server_net_id.is_some()));
+    }
+}
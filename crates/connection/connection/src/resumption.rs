@@ -0,0 +1,222 @@
+//! Session resumption after a transient disconnect.
+//!
+//! By default, a connection that times out is [`Disconnected`]: its transport, message receivers
+//! and replication state are reset, the server despawns the [`ClientOf`] entity, and a client that
+//! reconnects starts a brand-new session.
+//!
+//! Adding [`SessionResumption`] on a server entity (for its [`ClientOf`] entities) or on a client
+//! entity enables a grace period instead. When the connection layer detects that the remote peer
+//! went silent, the connection entity is marked [`Suspended`] instead of [`Disconnected`]:
+//! - [`Connected`] is removed, so nothing is sent to the remote peer, but none of the disconnection
+//!   cleanup runs. The `RemoteEntityMap`, `ControlledBy` ownership, input buffers and replicated
+//!   entities are kept.
+//! - if the peer connects again before the grace period ends, the same entity becomes
+//!   [`Connected`] again and [`SessionResumed`] is triggered. Replication then resumes from the
+//!   state that was acknowledged before the interruption, instead of starting from scratch.
+//! - otherwise the entity becomes [`Disconnected`] with the original reason, and a [`ClientOf`] is
+//!   despawned.
+//!
+//! Only timeouts suspend a session: explicit disconnections and denied connections still end the
+//! session immediately. Both peers must enable resumption, since a client that reset its state
+//! cannot resume a session that the server kept.
+//!
+//! On the server, a reconnecting peer is matched with its suspended session with the
+//! [`ResumeToken`] that the connection layer inserts on each [`ClientOf`].
+use crate::client::{Connected, Disconnected, DisconnectedReason, Disconnecting};
+use crate::client_of::ClientOf;
+use bevy_app::{App, Last, Plugin};
+use bevy_ecs::lifecycle::HookContext;
+use bevy_ecs::prelude::*;
+use bevy_ecs::world::DeferredWorld;
+use bevy_reflect::Reflect;
+use core::time::Duration;
+use lightyear_core::time::Instant;
+use tracing::{info, trace};
+
+/// Enables session resumption on a server entity or on a client entity.
+///
+/// See the [module-level documentation](self).
+#[derive(Component, Debug, Clone, Copy, PartialEq, Reflect)]
+pub struct SessionResumption {
+    /// How long the state of a [`Suspended`] session is kept after the remote peer went silent.
+    pub grace_period: Duration,
+}
+
+impl Default for SessionResumption {
+    fn default() -> Self {
+        Self {
+            grace_period: Duration::from_secs(10),
+        }
+    }
+}
+
+impl SessionResumption {
+    pub fn new(grace_period: Duration) -> Self {
+        Self { grace_period }
+    }
+
+    /// Returns the [`Suspended`] component to insert on a connection that was interrupted now.
+    pub fn suspend(&self, reason: DisconnectedReason) -> Suspended {
+        Suspended {
+            reason,
+            expires_at: Instant::now() + self.grace_period,
+        }
+    }
+}
+
+/// Identifies the session of a [`ClientOf`], so that a reconnecting peer can resume it.
+///
+/// The connection layer inserts this when the peer connects, from an identity that the remote
+/// peer cannot forge. For example netcode uses the client id of the connect token, which is
+/// authenticated by the token issuer.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub struct ResumeToken(pub u64);
+
+/// Marks a connection that was interrupted, but whose session is kept until `expires_at`.
+///
+/// A suspended entity is neither [`Connected`] nor [`Disconnected`].
+#[derive(Component, Debug, Clone)]
+#[component(on_add = Suspended::on_add)]
+pub struct Suspended {
+    /// Reason used for the [`Disconnected`] component if the session is not resumed in time.
+    pub reason: DisconnectedReason,
+    pub expires_at: Instant,
+}
+
+impl Suspended {
+    fn on_add(mut world: DeferredWorld, context: HookContext) {
+        // The entity stays in the peer map, so that messages sent to the peer are buffered
+        // until the session is resumed
+        world
+            .commands()
+            .entity(context.entity)
+            .remove::<(Connected, Disconnecting)>();
+    }
+}
+
+/// Triggered when a [`Suspended`] session becomes [`Connected`] again.
+#[derive(EntityEvent, Debug)]
+pub struct SessionResumed {
+    pub entity: Entity,
+}
+
+pub struct SessionResumptionPlugin;
+
+impl SessionResumptionPlugin {
+    /// A suspended entity that becomes Connected again resumes its session
+    fn resume(
+        trigger: On<Add, Connected>,
+        query: Query<(), With<Suspended>>,
+        mut commands: Commands,
+    ) {
+        if query.get(trigger.entity).is_ok() {
+            info!(entity = ?trigger.entity, "session resumed");
+            commands.entity(trigger.entity).remove::<Suspended>();
+            commands.trigger(SessionResumed {
+                entity: trigger.entity,
+            });
+        }
+    }
+
+    /// Disconnect the sessions that were not resumed within the grace period.
+    ///
+    /// Like other disconnections, a [`ClientOf`] is despawned after being set to [`Disconnected`].
+    fn expire(query: Query<(Entity, &Suspended, Has<ClientOf>)>, mut commands: Commands) {
+        let now = Instant::now();
+        for (entity, suspended, client_of) in query.iter() {
+            if now < suspended.expires_at {
+                continue;
+            }
+            trace!(?entity, "session was not resumed within the grace period");
+            let mut entity_commands = commands.entity(entity);
+            entity_commands.insert(Disconnected {
+                reason: suspended.reason.clone(),
+            });
+            if client_of {
+                entity_commands.despawn();
+            }
+        }
+    }
+}
+
+impl Plugin for SessionResumptionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_observer(Self::resume);
+        app.add_systems(Last, Self::expire);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network_topology::NetworkingMetadata;
+    use lightyear_core::id::{PeerId, RemoteId};
+
+    #[derive(Resource, Default)]
+    struct Resumed(usize);
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.init_resource::<NetworkingMetadata>();
+        app.init_resource::<Resumed>();
+        app.add_plugins(SessionResumptionPlugin);
+        app.add_observer(|_: On<SessionResumed>, mut resumed: ResMut<Resumed>| {
+            resumed.0 += 1;
+        });
+        app
+    }
+
+    #[test]
+    fn reconnecting_resumes_suspended_session() {
+        let mut app = app();
+        let entity = app
+            .world_mut()
+            .spawn((ClientOf, RemoteId(PeerId::Netcode(1)), Connected))
+            .id();
+
+        app.world_mut()
+            .entity_mut(entity)
+            .insert(SessionResumption::default().suspend(DisconnectedReason::ByPeer(None)));
+        app.update();
+        assert!(!app.world().entity(entity).contains::<Connected>());
+        assert!(!app.world().entity(entity).contains::<Disconnected>());
+
+        app.world_mut().entity_mut(entity).insert(Connected);
+        app.update();
+        assert!(!app.world().entity(entity).contains::<Suspended>());
+        assert_eq!(app.world().resource::<Resumed>().0, 1);
+    }
+
+    #[test]
+    fn expired_session_is_disconnected() {
+        let mut app = app();
+        let client_of = app
+            .world_mut()
+            .spawn((
+                ClientOf,
+                SessionResumption::new(Duration::ZERO).suspend(DisconnectedReason::ByPeer(None)),
+            ))
+            .id();
+        let client = app
+            .world_mut()
+            .spawn(SessionResumption::new(Duration::ZERO).suspend(DisconnectedReason::ByPeer(None)))
+            .id();
+        let kept = app
+            .world_mut()
+            .spawn((
+                ClientOf,
+                SessionResumption::new(Duration::from_secs(3600))
+                    .suspend(DisconnectedReason::ByPeer(None)),
+            ))
+            .id();
+
+        app.update();
+        assert!(app.world().get_entity(client_of).is_err());
+        assert_eq!(
+            app.world().get::<Disconnected>(client).unwrap().reason,
+            DisconnectedReason::ByPeer(None)
+        );
+        assert!(app.world().entity(kept).contains::<Suspended>());
+        assert_eq!(app.world().resource::<Resumed>().0, 0);
+    }
+}
@@ -1,6 +1,8 @@
+use crate::admission::AdmissionPlugin;
 use crate::client::{Disconnected, DisconnectedReason, Disconnecting};
 use crate::client_of::ClientOf;
 use crate::network_topology::NetworkingMetadata;
+use crate::resumption::SessionResumptionPlugin;
 use bevy_app::{App, Last, Plugin};
 use bevy_ecs::lifecycle::HookContext;
 use bevy_ecs::prelude::*;
@@ -183,6 +185,12 @@ pub use crate::identity::is_headless_server;
 
 impl Plugin for ConnectionPlugin {
     fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<SessionResumptionPlugin>() {
+            app.add_plugins(SessionResumptionPlugin);
+        }
+        if !app.is_plugin_added::<AdmissionPlugin>() {
+            app.add_plugins(AdmissionPlugin);
+        }
         app.add_observer(Self::start);
         app.add_observer(Self::stop_if_link_fails);
         app.add_systems(Last, Self::disconnect);
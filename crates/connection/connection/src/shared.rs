@@ -1,10 +1,13 @@
 use alloc::string::String;
-use core::fmt::Debug;
+use alloc::vec::Vec;
+use bevy_reflect::Reflect;
+use core::fmt::{Debug, Display, Formatter};
+use core::net::SocketAddr;
 use lightyear_core::id::PeerId;
 use serde::{Deserialize, Serialize};
 
 /// Reasons for denying a connection request
-#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, Reflect)]
 pub enum DeniedReason {
     ServerFull,
     Banned,
@@ -13,6 +16,88 @@ pub enum DeniedReason {
     TokenAlreadyUsed,
     InvalidToken,
     Custom(String),
+    /// The protocol of the client does not match the protocol of the server.
+    ProtocolMismatch(ProtocolMismatch),
+}
+
+impl Display for DeniedReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::ServerFull => f.write_str("Server full"),
+            Self::Banned => f.write_str("Banned"),
+            Self::InternalError => f.write_str("Internal error"),
+            Self::AlreadyConnected => f.write_str("Already connected"),
+            Self::TokenAlreadyUsed => f.write_str("Token already used"),
+            Self::InvalidToken => f.write_str("Invalid token"),
+            Self::Custom(reason) => f.write_str(reason),
+            Self::ProtocolMismatch(mismatch) => write!(f, "Protocol mismatch: {mismatch}"),
+        }
+    }
+}
+
+/// Registry in which a protocol type is registered.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize, Reflect)]
+pub enum ProtocolRegistry {
+    Message,
+    Component,
+    Channel,
+}
+
+/// A type that is not registered with the same network id on the server and on the client.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, Reflect)]
+pub struct TypeMismatch {
+    pub registry: ProtocolRegistry,
+    /// Name of the type, as returned by [`core::any::type_name`]
+    pub name: String,
+    /// Network id of the type on the server, or `None` if the server did not register it
+    pub server_net_id: Option<u16>,
+    /// Network id of the type on the client, or `None` if the client did not register it
+    pub client_net_id: Option<u16>,
+}
+
+/// Differences between the protocol of the server and the protocol of a client.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize, Reflect)]
+pub struct ProtocolMismatch {
+    /// App versions of the server and of the client, if they differ
+    pub app_version: Option<(String, String)>,
+    /// Types that are registered differently on the server and on the client
+    pub types: Vec<TypeMismatch>,
+}
+
+impl ProtocolMismatch {
+    pub fn is_empty(&self) -> bool {
+        self.app_version.is_none() && self.types.is_empty()
+    }
+}
+
+impl Display for ProtocolMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let mut separator = "";
+        if let Some((server, client)) = &self.app_version {
+            write!(f, "server version {server:?} != client version {client:?}")?;
+            separator = ", ";
+        }
+        for ty in &self.types {
+            write!(
+                f,
+                "{separator}{:?} {} (server: {:?}, client: {:?})",
+                ty.registry, ty.name, ty.server_net_id, ty.client_net_id
+            )?;
+            separator = ", ";
+        }
+        Ok(())
+    }
+}
+
+/// A connection request received by the server, after the identity of the client was validated.
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectionRequest<'a> {
+    pub client_id: PeerId,
+    /// Address the request was received from, if the link has one
+    pub source_addr: Option<SocketAddr>,
+    /// User data provided by the client or by the issuer of its credentials, for example the
+    /// user data of a netcode connect token. Empty if the connection layer has none.
+    pub user_data: &'a [u8],
 }
 
 /// Trait for handling connection requests from clients.
@@ -20,7 +105,7 @@ pub trait ConnectionRequestHandler: Debug + Send + Sync {
     /// Handle a connection request from a client.
     /// Returns None if the connection is accepted,
     /// Returns Some(reason) if the connection is denied.
-    fn handle_request(&self, client_id: PeerId) -> Option<DeniedReason>;
+    fn handle_request(&self, request: &ConnectionRequest) -> Option<DeniedReason>;
 }
 
 /// By default, all connection requests are accepted by the server.
@@ -28,7 +113,7 @@ pub trait ConnectionRequestHandler: Debug + Send + Sync {
 pub struct DefaultConnectionRequestHandler;
 
 impl ConnectionRequestHandler for DefaultConnectionRequestHandler {
-    fn handle_request(&self, _client_id: PeerId) -> Option<DeniedReason> {
+    fn handle_request(&self, _request: &ConnectionRequest) -> Option<DeniedReason> {
         None
     }
 }
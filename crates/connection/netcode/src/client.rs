@@ -6,11 +6,15 @@ use super::{
     ClientId, MAX_PACKET_SIZE, MAX_PKT_BUF_SIZE, PACKET_SEND_RATE_SEC,
     bytes::Bytes,
     error::{Error, Result},
-    packet::{DisconnectPacket, KeepAlivePacket, Packet, RequestPacket, ResponsePacket},
+    packet::{
+        DeniedPacket, DisconnectPacket, KeepAlivePacket, Packet, RequestPacket, ResponsePacket,
+    },
     replay::ReplayProtection,
     token::{ChallengeToken, ConnectToken},
     utils,
 };
+use lightyear_connection::protocol::Protocol;
+use lightyear_connection::shared::DeniedReason;
 use lightyear_link::{LinkReceiver, LinkSender, RecvPayload, SendPayload};
 use lightyear_serde::writer::Writer;
 use tracing::{debug, error, info, trace};
@@ -182,6 +186,10 @@ pub struct Client<Ctx = ()> {
     replay_protection: ReplayProtection,
     should_disconnect: bool,
     should_disconnect_state: ClientState,
+    denied_reason: Option<DeniedReason>,
+    /// Protocol of the client, whose hash is sent in the connection request
+    protocol: Protocol,
+    protocol_hash: u64,
     send_queue: Vec<SendPayload>,
     packet_queue: Vec<RecvPayload>,
     // We use a Writer (wrapper around BytesMut) here because we will keep re-using the
@@ -223,6 +231,9 @@ impl<Ctx> Client<Ctx> {
             replay_protection: ReplayProtection::new(),
             should_disconnect: false,
             should_disconnect_state: ClientState::Disconnected,
+            denied_reason: None,
+            protocol: Protocol::default(),
+            protocol_hash: Protocol::default().hash(),
             send_queue: Vec::new(),
             packet_queue: Vec::new(),
             writer: Writer::with_capacity(MAX_PKT_BUF_SIZE),
@@ -324,6 +335,8 @@ impl<Ctx> Client<Ctx> {
                     self.token.expire_timestamp,
                     self.token.nonce,
                     self.token.private_data,
+                    self.protocol_hash,
+                    &self.protocol.app_version,
                 )
             }
             ClientState::SendingChallengeResponse => {
@@ -411,12 +424,16 @@ impl<Ctx> Client<Ctx> {
                 Packet::Denied(pkt),
                 ClientState::SendingConnectionRequest | ClientState::SendingChallengeResponse,
             ) => {
-                error!(
-                    "client connection denied by server. Reason: {:?}",
-                    pkt.reason
-                );
+                let reason = match pkt {
+                    DeniedPacket::Reason(reason) => reason,
+                    DeniedPacket::ProtocolMismatch(digest) => {
+                        DeniedReason::ProtocolMismatch(self.protocol.mismatch(&digest))
+                    }
+                };
+                error!("client connection denied by server. Reason: {reason}");
                 self.should_disconnect = true;
                 self.should_disconnect_state = ClientState::ConnectionDenied;
+                self.denied_reason = Some(reason);
                 None
             }
             (Packet::Challenge(pkt), ClientState::SendingConnectionRequest) => {
@@ -553,6 +570,7 @@ impl<Ctx> Client<Ctx> {
     /// This function does not perform any IO, it only readies the client to send/receive packets on the next call to [`update`](Client::update).
     pub fn connect(&mut self) {
         self.reset_connection();
+        self.denied_reason = None;
         self.set_state(ClientState::SendingConnectionRequest);
         info!(
             "client connecting to server {} [{}/{}]",
@@ -632,6 +650,18 @@ impl<Ctx> Client<Ctx> {
     pub fn state(&self) -> ClientState {
         self.state
     }
+    /// Gets the reason sent by the server when it denied the connection request.
+    pub fn denied_reason(&self) -> Option<&DeniedReason> {
+        self.denied_reason.as_ref()
+    }
+    /// Sets the protocol of the client, whose hash is sent in the next connection requests.
+    ///
+    /// If the server denies the client because the hashes differ, the protocol is compared with
+    /// the protocol of the server to build the [`DeniedReason::ProtocolMismatch`].
+    pub fn set_protocol(&mut self, protocol: Protocol) {
+        self.protocol_hash = protocol.hash();
+        self.protocol = protocol;
+    }
     /// Returns true if the client is in an error state.
     pub fn is_error(&self) -> bool {
         self.state < ClientState::Disconnected
@@ -1,8 +1,8 @@
 use alloc::format;
 
-use crate::Error;
 use crate::auth::Authentication;
 use crate::client::{ClientConfig, ClientState};
+use crate::{Error, MAX_PACKET_SIZE};
 use aeronet_io::connection::PeerAddr;
 use bevy_app::{App, Plugin, PostUpdate, PreUpdate};
 use bevy_ecs::lifecycle::HookContext;
@@ -15,6 +15,8 @@ use lightyear_connection::client::{
     Connect, Connected, Connecting, ConnectionPlugin, Disconnect, Disconnected, DisconnectedReason,
 };
 use lightyear_connection::host::HostClient;
+use lightyear_connection::protocol::{AppVersion, Protocol, ProtocolManifest};
+use lightyear_connection::resumption::{SessionResumption, Suspended};
 use lightyear_core::id::{LocalId, PeerId, RemoteId};
 use lightyear_link::{Link, LinkSystems, Linked};
 use lightyear_transport::plugin::TransportSystems;
@@ -141,6 +143,9 @@ impl NetcodeClientPlugin {
                 &mut NetcodeClient,
                 Has<Connecting>,
                 Has<Disconnected>,
+                Has<Connected>,
+                Option<&Suspended>,
+                Option<&SessionResumption>,
             ),
             (With<Linked>, Without<HostClient>),
         >,
@@ -148,7 +153,16 @@ impl NetcodeClientPlugin {
     ) {
         let delta = real_time.delta();
         adaptive_for_each_mut!(query).for_each(
-            |(entity, mut link, mut client, connecting, disconnected)| {
+            |(
+                entity,
+                mut link,
+                mut client,
+                connecting,
+                disconnected,
+                connected,
+                suspended,
+                resumption,
+            )| {
                 // #[cfg(feature = "test_utils")]
                 // trace!("CLIENT: length of each packet in receive: {:?}", link.recv.iter().map(|p| p.len()).collect::<Vec<_>>());
 
@@ -179,11 +193,44 @@ impl NetcodeClientPlugin {
                                 | ClientState::SendingChallengeResponse
                         )
                     {
+                        // a session that timed out is kept, and we try to resume it until the
+                        // grace period expires
+                        if let Some(resumption) = resumption
+                            && (connected || suspended.is_some())
+                            && matches!(
+                                state,
+                                ClientState::ConnectionTimedOut
+                                    | ClientState::ConnectionRequestTimedOut
+                                    | ClientState::ChallengeResponseTimedOut
+                            )
+                        {
+                            client.inner.connect();
+                            parallel_commands.command_scope(|mut commands| {
+                                let mut entity_commands = commands.entity(entity);
+                                if suspended.is_none() {
+                                    info!(
+                                        "Client {} timed out. Trying to resume the session for {:?}",
+                                        client.id(),
+                                        resumption.grace_period
+                                    );
+                                    entity_commands.insert(
+                                        resumption.suspend(DisconnectedReason::ByPeer(None)),
+                                    );
+                                }
+                                entity_commands.insert(Connecting);
+                            });
+                            return;
+                        }
                         info!("Client {} disconnected. State: {state:?}", client.id());
                         parallel_commands.command_scope(|mut commands| {
                             commands.entity(entity).insert(Disconnected {
-                                reason: match state {
-                                    ClientState::Disconnected => DisconnectedReason::ByPeer(None),
+                                reason: match (state, client.inner.denied_reason()) {
+                                    (ClientState::Disconnected, _) => {
+                                        DisconnectedReason::ByPeer(None)
+                                    }
+                                    (ClientState::ConnectionDenied, Some(reason)) => {
+                                        DisconnectedReason::Denied(reason.clone())
+                                    }
                                     _ => DisconnectedReason::TransportError(format!("{state:?}")),
                                 },
                             });
@@ -194,18 +241,47 @@ impl NetcodeClientPlugin {
         )
     }
 
+    /// Start the connection, with the hash of the current protocol in the connection requests
     fn connect(
         trigger: On<Connect>,
         mut commands: Commands,
         mut query: Query<&mut NetcodeClient, Without<Connected>>,
+        app_version: Option<Res<AppVersion>>,
+        manifest: Option<Res<ProtocolManifest>>,
     ) {
         if let Ok(mut client) = query.get_mut(trigger.entity) {
             debug!("Starting netcode connection process");
+            client
+                .inner
+                .set_protocol(Protocol::new(app_version.as_deref(), manifest.as_deref()));
             client.inner.connect();
             commands.entity(trigger.entity).insert(Connecting);
         }
     }
 
+    /// Stop any pending connection attempt when the client is disconnected, for example when
+    /// a suspended session was not resumed within its grace period.
+    fn stop_connecting(trigger: On<Add, Disconnected>, mut query: Query<&mut NetcodeClient>) {
+        if let Ok(mut client) = query.get_mut(trigger.entity)
+            && client.inner.is_pending()
+        {
+            client.inner.disconnect().ok();
+        }
+    }
+
+    /// The netcode packets wrap the payloads of the link, which cannot be larger than
+    /// [`MAX_PACKET_SIZE`]
+    fn limit_link_mtu(
+        trigger: On<Insert, (Link, NetcodeClient)>,
+        mut query: Query<&mut Link, With<NetcodeClient>>,
+    ) {
+        if let Ok(mut link) = query.get_mut(trigger.entity) {
+            link.set_max_mtu(MAX_PACKET_SIZE)
+                .inspect_err(|e| error!("Link cannot be used with netcode: {e}"))
+                .ok();
+        }
+    }
+
     fn disconnect(
         trigger: On<Disconnect>,
         mut commands: Commands,
@@ -249,5 +325,7 @@ impl Plugin for NetcodeClientPlugin {
         app.add_systems(PostUpdate, Self::send.in_set(ConnectionSystems::Send));
         app.add_observer(Self::connect);
         app.add_observer(Self::disconnect);
+        app.add_observer(Self::stop_connecting);
+        app.add_observer(Self::limit_link_mtu);
     }
 }
@@ -1,3 +1,4 @@
+use alloc::string::String;
 use bevy_ecs::entity::Entity;
 use core::array::TryFromSliceError;
 use lightyear_core::id::PeerId;
@@ -41,8 +42,20 @@ pub enum Error {
     ServerIsFull(PeerId),
     #[error("client_id {0} handle_connection_request_fn returned false")]
     Denied(PeerId),
+    #[error(
+        "client_id {client_id} was denied: its app version {app_version:?} or its protocol hash {protocol_hash:#x} differs from the server's"
+    )]
+    ProtocolMismatch {
+        client_id: PeerId,
+        app_version: String,
+        protocol_hash: u64,
+    },
     #[error("client_id {0} server ignored non-connection-request packet")]
     Ignored(Entity),
+    #[error("entity {0} sent too many handshake packets")]
+    RateLimited(Entity),
+    #[error("client_id {0} server ignored connection request: too many pending handshakes")]
+    TooManyPendingHandshakes(PeerId),
     #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
     #[error("clock went backwards (did you invent a time machine?): {0}")]
     SystemTime(#[from] std::time::SystemTimeError),
@@ -69,7 +82,7 @@ pub enum Error {
 
 impl Error {
     pub(crate) fn log(&self) {
-        let suppress_error = matches!(&self, Error::Ignored(_));
+        let suppress_error = matches!(&self, Error::Ignored(_) | Error::RateLimited(_));
         if suppress_error {
             debug!("Netcode error: {:?}", self);
         } else {
@@ -0,0 +1,175 @@
+//! Rotation of the private key shared by the token issuer and the game servers.
+//!
+//! Each private key has a [`KeyId`]. [`ConnectTokenBuilder::key_id`](crate::ConnectTokenBuilder::key_id)
+//! writes the id in the first byte of the nonce of the token, which the client sends back unchanged
+//! in its connection request, so the server knows which key to decrypt the token with. The rest of
+//! the nonce stays random, and the token stays compatible with the netcode standard.
+//!
+//! To rotate the key without disconnecting anyone:
+//! 1. add the new key to the servers with [`Keyring::rotate`], keeping the previous key for at
+//!    least the lifetime of the tokens that were already issued;
+//! 2. switch the token issuer to the new key and key id.
+//!
+//! Tokens issued with the previous key are accepted until the previous key is retired, and
+//! connected clients are not affected since they use per-connection keys.
+use crate::{Key, utils};
+use alloc::vec::Vec;
+use core::fmt::{Debug, Formatter};
+use core::time::Duration;
+
+/// Identifier of a private key in a [`Keyring`].
+pub type KeyId = u8;
+
+#[derive(Clone)]
+struct Entry {
+    id: KeyId,
+    key: Key,
+    /// Timestamp (in seconds since the unix epoch) after which the key is removed
+    retire_at: Option<u64>,
+}
+
+/// The private keys accepted by a netcode server.
+///
+/// New tokens should be generated with the current key. The previous keys are only kept so that
+/// the tokens generated with them remain valid until they expire.
+#[derive(Clone)]
+pub struct Keyring {
+    current: KeyId,
+    keys: Vec<Entry>,
+}
+
+impl Debug for Keyring {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Keyring")
+            .field("current", &self.current)
+            .field("ids", &self.ids().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Keyring {
+    pub fn new(id: KeyId, key: Key) -> Self {
+        Self {
+            current: id,
+            keys: alloc::vec![Entry {
+                id,
+                key,
+                retire_at: None,
+            }],
+        }
+    }
+
+    /// Also accept the tokens generated with a previous key, for example when the server restarts
+    /// during a rotation. The key is kept until it is [retired](Self::retire).
+    pub fn with_previous_key(mut self, id: KeyId, key: Key) -> Self {
+        if id != self.current {
+            self.keys.retain(|entry| entry.id != id);
+            self.keys.push(Entry {
+                id,
+                key,
+                retire_at: None,
+            });
+        }
+        self
+    }
+
+    pub fn current_id(&self) -> KeyId {
+        self.current
+    }
+
+    pub fn current_key(&self) -> &Key {
+        self.get(self.current)
+            .expect("the current key is always in the keyring")
+    }
+
+    pub fn get(&self, id: KeyId) -> Option<&Key> {
+        self.keys
+            .iter()
+            .find(|entry| entry.id == id)
+            .map(|entry| &entry.key)
+    }
+
+    pub fn contains(&self, id: KeyId) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Ids of all the accepted keys.
+    pub fn ids(&self) -> impl Iterator<Item = KeyId> + '_ {
+        self.keys.iter().map(|entry| entry.id)
+    }
+
+    /// Makes `key` the current key.
+    ///
+    /// The previous current key is still accepted during `previous_key_lifetime`, which should be
+    /// at least the expiry duration of the tokens, or until it is [retired](Self::retire) if
+    /// `None`. If a previous key used the same id, it is replaced.
+    pub fn rotate(&mut self, id: KeyId, key: Key, previous_key_lifetime: Option<Duration>) {
+        let now = utils::now().unwrap_or_default();
+        let previous = self.current;
+        if let Some(entry) = self.keys.iter_mut().find(|entry| entry.id == previous) {
+            entry.retire_at = previous_key_lifetime.map(|lifetime| now + lifetime.as_secs());
+        }
+        self.keys.retain(|entry| entry.id != id);
+        self.keys.push(Entry {
+            id,
+            key,
+            retire_at: None,
+        });
+        self.current = id;
+    }
+
+    /// Stops accepting the tokens generated with a previous key.
+    ///
+    /// Returns false if the key is not in the keyring or is the current key, which cannot be
+    /// retired.
+    pub fn retire(&mut self, id: KeyId) -> bool {
+        if id == self.current {
+            return false;
+        }
+        let len = self.keys.len();
+        self.keys.retain(|entry| entry.id != id);
+        self.keys.len() != len
+    }
+
+    /// Removes the previous keys whose lifetime ended.
+    pub(crate) fn retire_expired(&mut self, now: u64) {
+        self.keys
+            .retain(|entry| entry.retire_at.is_none_or(|retire_at| retire_at > now));
+    }
+
+    /// Key that should be used to decrypt a token generated with the key `id`.
+    ///
+    /// Tokens from an issuer that doesn't encode the key id have a random first nonce byte, so
+    /// unknown ids fall back to the current key.
+    pub(crate) fn key_for(&self, id: KeyId) -> &Key {
+        self.get(id).unwrap_or_else(|| self.current_key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotate_and_retire() {
+        let mut keyring = Keyring::new(0, [0; 32]).with_previous_key(7, [7; 32]);
+        keyring.rotate(1, [1; 32], Some(Duration::from_secs(30)));
+        assert_eq!(keyring.current_id(), 1);
+        assert_eq!(keyring.current_key(), &[1; 32]);
+        assert_eq!(keyring.key_for(0), &[0; 32]);
+        // unknown ids use the current key
+        assert_eq!(keyring.key_for(3), &[1; 32]);
+
+        let now = utils::now().unwrap();
+        keyring.retire_expired(now);
+        assert!(keyring.contains(0));
+        keyring.retire_expired(now + 30);
+        assert!(!keyring.contains(0));
+
+        // keys added without a lifetime are kept until they are retired
+        assert!(keyring.contains(7));
+        assert!(keyring.retire(7));
+        assert!(!keyring.retire(1));
+        assert_eq!(keyring.ids().collect::<Vec<_>>(), [1]);
+    }
+}
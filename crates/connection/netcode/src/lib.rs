@@ -19,6 +19,7 @@
 
  The protocol does not specify how the web backend should be implemented, but it should probably be a typical HTTPS server
  that provides a means for clients to authenticate and request connection tokens.
+ The `lightyear_token_service` crate provides a reusable implementation of that service.
 
  The sequence of operations for a client to connect to a server is as follows:
 
@@ -66,6 +67,7 @@ extern crate std;
 pub use client_plugin::NetcodeClient;
 pub use crypto::{Key, generate_key, try_generate_key};
 pub use error::{Error, Result};
+pub use keyring::{KeyId, Keyring};
 #[cfg(feature = "server")]
 pub use server::{Callback, ConnectCallback, Server, ServerConfig};
 #[cfg(feature = "server")]
@@ -80,6 +82,7 @@ mod bytes;
 pub mod client;
 mod crypto;
 pub(crate) mod error;
+pub mod keyring;
 mod packet;
 mod replay;
 #[cfg(feature = "server")]
@@ -104,6 +107,7 @@ pub mod prelude {
 
     #[cfg(feature = "server")]
     pub mod server {
+        pub use crate::keyring::{KeyId, Keyring};
         pub use crate::server_plugin::{
             NetcodeConfig, NetcodeServer, NetcodeServerError, NetcodeServerPlugin, TokenUserData,
         };
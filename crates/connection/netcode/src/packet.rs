@@ -1,4 +1,4 @@
-use alloc::{borrow::ToOwned, boxed::Box, string::String, vec};
+use alloc::{borrow::ToOwned, boxed::Box, string::String, vec, vec::Vec};
 use core::mem::size_of;
 #[cfg(not(feature = "std"))]
 use no_std_io2::{
@@ -9,10 +9,11 @@ use no_std_io2::{
 use std::io::{self, Read, Write};
 
 use super::{
-    ClientId, MAC_BYTES, MAX_PKT_BUF_SIZE, NETCODE_VERSION,
+    ClientId, MAC_BYTES, MAX_PACKET_SIZE, MAX_PKT_BUF_SIZE, NETCODE_VERSION,
     bytes::Bytes,
     crypto::{self, Key},
     error::Error as NetcodeError,
+    keyring::KeyId,
     replay::ReplayProtection,
     token::{ChallengeToken, ConnectTokenPrivate},
 };
@@ -23,7 +24,10 @@ use lightyear_serde::writer::WriteInteger;
 use lightyear_serde::{SerializationError, ToBytes};
 use tracing::debug;
 
-use lightyear_connection::shared::DeniedReason;
+use lightyear_connection::protocol::{ProtocolDigest, TypeDigest};
+use lightyear_connection::shared::{
+    DeniedReason, ProtocolMismatch, ProtocolRegistry, TypeMismatch,
+};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -81,14 +85,34 @@ pub struct RequestPacket {
     pub expire_timestamp: u64,
     pub token_nonce: XNonce,
     pub token_data: Box<[u8; ConnectTokenPrivate::SIZE]>,
+    /// [`Protocol::hash`](lightyear_connection::protocol::Protocol::hash) of the client
+    pub protocol_hash: u64,
+    /// App version of the client, truncated to [`RequestPacket::MAX_APP_VERSION_LEN`] bytes.
+    /// It is only used to describe a protocol mismatch: the hash covers the full version.
+    pub app_version: String,
 }
 
 impl RequestPacket {
+    /// Offset of the first byte of the token nonce in a connection request, after the prefix byte,
+    /// the version, the protocol id and the expire timestamp.
+    const KEY_ID_OFFSET: usize = 1 + NETCODE_VERSION.len() + 2 * size_of::<u64>();
+
+    /// Maximum length of the app version sent in a connection request, which leaves the request
+    /// well under [`MAX_PACKET_SIZE`](crate::MAX_PACKET_SIZE).
+    pub const MAX_APP_VERSION_LEN: usize = 64;
+
+    /// Reads the [`KeyId`] of the connect token from a connection request, without decrypting it.
+    pub fn key_id(buf: &[u8]) -> Option<KeyId> {
+        buf.get(Self::KEY_ID_OFFSET).copied()
+    }
+
     pub fn create(
         protocol_id: u64,
         expire_timestamp: u64,
         token_nonce: XNonce,
         token_data: [u8; ConnectTokenPrivate::SIZE],
+        protocol_hash: u64,
+        app_version: &str,
     ) -> Packet {
         Packet::Request(RequestPacket {
             version_info: *NETCODE_VERSION,
@@ -96,6 +120,8 @@ impl RequestPacket {
             expire_timestamp,
             token_nonce,
             token_data: Box::new(token_data),
+            protocol_hash,
+            app_version: truncate(app_version, Self::MAX_APP_VERSION_LEN).to_owned(),
         })
     }
     pub fn validate(&self, protocol_id: u64, current_timestamp: u64) -> Result<(), Error> {
@@ -136,6 +162,11 @@ impl Bytes for RequestPacket {
         writer.write_u64(self.expire_timestamp)?;
         writer.write_all(&self.token_nonce)?;
         writer.write_all(&self.token_data[..])?;
+        writer.write_u64(self.protocol_hash)?;
+        write_short_string(
+            writer,
+            truncate(&self.app_version, Self::MAX_APP_VERSION_LEN),
+        )?;
         Ok(())
     }
 
@@ -150,23 +181,42 @@ impl Bytes for RequestPacket {
         let token_nonce = XNonce::from_slice(&nonce).to_owned();
         let mut token_data = [0; ConnectTokenPrivate::SIZE];
         reader.read_exact(&mut token_data)?;
+        let protocol_hash = reader.read_u64()?;
+        let app_version = read_short_string(reader)?;
         Ok(Self {
             version_info,
             protocol_id,
             expire_timestamp,
             token_nonce,
             token_data: Box::new(token_data),
+            protocol_hash,
+            app_version,
         })
     }
 }
 
-pub struct DeniedPacket {
-    pub reason: DeniedReason,
+/// Maximum size of the body of a denied packet: the packet also holds the prefix byte, up to 8
+/// sequence bytes and the MAC, and cannot exceed [`MAX_PACKET_SIZE`](crate::MAX_PACKET_SIZE).
+const MAX_DENIED_BODY_SIZE: usize = MAX_PACKET_SIZE - 1 - size_of::<u64>() - MAC_BYTES;
+
+/// Size of a [`TypeDigest`] on the wire: registry, net id and name hash
+const TYPE_DIGEST_SIZE: usize = 1 + size_of::<u16>() + size_of::<u32>();
+
+pub enum DeniedPacket {
+    Reason(DeniedReason),
+    /// The protocol of the client does not match the protocol of the server, which is described
+    /// by the digest. The client compares it with its own protocol to build the
+    /// [`ProtocolMismatch`].
+    ProtocolMismatch(ProtocolDigest),
 }
 
 impl DeniedPacket {
     pub fn create(reason: DeniedReason) -> Packet {
-        Packet::Denied(DeniedPacket { reason })
+        Packet::Denied(DeniedPacket::Reason(reason))
+    }
+
+    pub fn protocol_mismatch(digest: ProtocolDigest) -> Packet {
+        Packet::Denied(DeniedPacket::ProtocolMismatch(digest))
     }
 }
 
@@ -196,20 +246,11 @@ impl Bytes for DeniedReason {
             DeniedReason::Custom(reason) => {
                 writer.write_u8(6)?;
                 // the reason cannot exceed u8::MAX in size
-                if reason.len() > u8::MAX as usize {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "custom denied reason too long",
-                    ));
-                }
-                writer.write_u8(reason.len() as u8)?;
-                let num_write = writer.write(reason.as_bytes())?;
-                if num_write != reason.len() {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "invalid denied reason",
-                    ));
-                }
+                write_short_string(writer, reason)?;
+            }
+            DeniedReason::ProtocolMismatch(mismatch) => {
+                writer.write_u8(7)?;
+                write_protocol_mismatch(writer, mismatch)?;
             }
         }
         Ok(())
@@ -217,32 +258,199 @@ impl Bytes for DeniedReason {
 
     fn read_from(reader: &mut impl ReadInteger) -> Result<Self, Self::Error> {
         let variant = reader.read_u8()?;
-        if variant == 0 {
-            Ok(DeniedReason::ServerFull)
-        } else if variant == 1 {
-            Ok(DeniedReason::Banned)
-        } else if variant == 2 {
-            Ok(DeniedReason::InternalError)
-        } else if variant == 3 {
-            Ok(DeniedReason::AlreadyConnected)
-        } else if variant == 4 {
-            Ok(DeniedReason::TokenAlreadyUsed)
-        } else if variant == 5 {
-            Ok(DeniedReason::InvalidToken)
-        } else if variant == 6 {
-            let len = reader.read_u8()? as usize;
-            let mut string_buf = vec![0; len];
-            reader.read_exact(&mut string_buf)?;
-            let reason_str = String::from_utf8(string_buf)
-                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid denied reason"))?;
-            Ok(DeniedReason::Custom(reason_str))
-        } else {
-            Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "invalid denied reason",
-            ))
+        read_denied_reason(variant, reader)
+    }
+}
+
+/// Read the denied reason whose variant tag was already read
+fn read_denied_reason(
+    variant: u8,
+    reader: &mut impl ReadInteger,
+) -> Result<DeniedReason, io::Error> {
+    if variant == 0 {
+        Ok(DeniedReason::ServerFull)
+    } else if variant == 1 {
+        Ok(DeniedReason::Banned)
+    } else if variant == 2 {
+        Ok(DeniedReason::InternalError)
+    } else if variant == 3 {
+        Ok(DeniedReason::AlreadyConnected)
+    } else if variant == 4 {
+        Ok(DeniedReason::TokenAlreadyUsed)
+    } else if variant == 5 {
+        Ok(DeniedReason::InvalidToken)
+    } else if variant == 6 {
+        Ok(DeniedReason::Custom(read_short_string(reader)?))
+    } else if variant == 7 {
+        Ok(DeniedReason::ProtocolMismatch(read_protocol_mismatch(
+            reader,
+        )?))
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid denied reason",
+        ))
+    }
+}
+
+/// Write the mismatch with its names truncated to [`u8::MAX`] bytes. The types that don't fit in
+/// a denied packet are left out.
+fn write_protocol_mismatch(
+    writer: &mut impl WriteInteger,
+    mismatch: &ProtocolMismatch,
+) -> Result<(), io::Error> {
+    // the variant tag was already written
+    let mut budget = MAX_DENIED_BODY_SIZE - 1;
+    match &mismatch.app_version {
+        Some((server, client)) => {
+            let (server, client) = (
+                truncate(server, u8::MAX as usize),
+                truncate(client, u8::MAX as usize),
+            );
+            writer.write_u8(1)?;
+            write_short_string(writer, server)?;
+            write_short_string(writer, client)?;
+            budget -= 3 + server.len() + client.len();
+        }
+        None => {
+            writer.write_u8(0)?;
+            budget -= 1;
         }
     }
+    // registry, name length, presence flags and both net ids
+    let type_size = |name: &str| 3 + 2 * size_of::<u16>() + name.len();
+    budget -= size_of::<u16>();
+    let mut count = 0;
+    for ty in &mismatch.types {
+        let size = type_size(truncate(&ty.name, u8::MAX as usize));
+        if size > budget {
+            break;
+        }
+        budget -= size;
+        count += 1;
+    }
+    writer.write_u16(count as u16)?;
+    for ty in &mismatch.types[..count] {
+        writer.write_u8(ty.registry as u8)?;
+        write_short_string(writer, truncate(&ty.name, u8::MAX as usize))?;
+        writer.write_u8(
+            ty.server_net_id.is_some() as u8 | ((ty.client_net_id.is_some() as u8) << 1),
+        )?;
+        writer.write_u16(ty.server_net_id.unwrap_or_default())?;
+        writer.write_u16(ty.client_net_id.unwrap_or_default())?;
+    }
+    Ok(())
+}
+
+fn read_protocol_mismatch(reader: &mut impl ReadInteger) -> Result<ProtocolMismatch, io::Error> {
+    let app_version = match reader.read_u8()? {
+        0 => None,
+        1 => Some((read_short_string(reader)?, read_short_string(reader)?)),
+        _ => return Err(invalid_protocol()),
+    };
+    let count = reader.read_u16()? as usize;
+    let mut types = Vec::with_capacity(count.min(MAX_DENIED_BODY_SIZE));
+    for _ in 0..count {
+        let registry = read_registry(reader)?;
+        let name = read_short_string(reader)?;
+        let flags = reader.read_u8()?;
+        let server_net_id = reader.read_u16()?;
+        let client_net_id = reader.read_u16()?;
+        types.push(TypeMismatch {
+            registry,
+            name,
+            server_net_id: (flags & 1 != 0).then_some(server_net_id),
+            client_net_id: (flags & 2 != 0).then_some(client_net_id),
+        });
+    }
+    Ok(ProtocolMismatch { app_version, types })
+}
+
+/// Write the digest, leaving out the types that don't fit in a denied packet
+fn write_protocol_digest(
+    writer: &mut impl WriteInteger,
+    digest: &ProtocolDigest,
+) -> Result<(), io::Error> {
+    let app_version = truncate(&digest.app_version, u8::MAX as usize);
+    // variant tag, app version, completeness flag and number of types
+    let header_size = 1 + 1 + app_version.len() + 1 + size_of::<u16>();
+    let count = digest
+        .types
+        .len()
+        .min((MAX_DENIED_BODY_SIZE - header_size) / TYPE_DIGEST_SIZE);
+    write_short_string(writer, app_version)?;
+    writer.write_u8((digest.complete && count == digest.types.len()) as u8)?;
+    writer.write_u16(count as u16)?;
+    for ty in &digest.types[..count] {
+        writer.write_u8(ty.registry as u8)?;
+        writer.write_u16(ty.net_id)?;
+        writer.write_u32(ty.name_hash)?;
+    }
+    Ok(())
+}
+
+fn read_protocol_digest(reader: &mut impl ReadInteger) -> Result<ProtocolDigest, io::Error> {
+    let app_version = read_short_string(reader)?;
+    let complete = reader.read_u8()? != 0;
+    let count = reader.read_u16()? as usize;
+    let mut types = Vec::with_capacity(count.min(MAX_DENIED_BODY_SIZE / TYPE_DIGEST_SIZE));
+    for _ in 0..count {
+        types.push(TypeDigest {
+            registry: read_registry(reader)?,
+            net_id: reader.read_u16()?,
+            name_hash: reader.read_u32()?,
+        });
+    }
+    Ok(ProtocolDigest {
+        app_version,
+        types,
+        complete,
+    })
+}
+
+fn read_registry(reader: &mut impl ReadInteger) -> Result<ProtocolRegistry, io::Error> {
+    match reader.read_u8()? {
+        0 => Ok(ProtocolRegistry::Message),
+        1 => Ok(ProtocolRegistry::Component),
+        2 => Ok(ProtocolRegistry::Channel),
+        _ => Err(invalid_protocol()),
+    }
+}
+
+fn invalid_protocol() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid protocol description")
+}
+
+/// Write a string prefixed by its length, which cannot exceed u8::MAX
+fn write_short_string(writer: &mut impl WriteInteger, value: &str) -> Result<(), io::Error> {
+    let len = u8::try_from(value.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "string too long"))?;
+    writer.write_u8(len)?;
+    let num_write = writer.write(value.as_bytes())?;
+    if num_write != value.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid string"));
+    }
+    Ok(())
+}
+
+fn read_short_string(reader: &mut impl ReadInteger) -> Result<String, io::Error> {
+    let len = reader.read_u8()? as usize;
+    let mut string_buf = vec![0; len];
+    reader.read_exact(&mut string_buf)?;
+    String::from_utf8(string_buf)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid string"))
+}
+
+/// Truncate `value` to at most `max_len` bytes, on a char boundary
+fn truncate(value: &str, max_len: usize) -> &str {
+    if value.len() <= max_len {
+        return value;
+    }
+    let mut end = max_len;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
 }
 
 impl ToBytes for DeniedPacket {
@@ -265,13 +473,25 @@ impl ToBytes for DeniedPacket {
 impl Bytes for DeniedPacket {
     type Error = io::Error;
     fn write_to(&self, writer: &mut impl WriteInteger) -> Result<(), Self::Error> {
-        self.reason.write_to(writer)?;
+        match self {
+            DeniedPacket::Reason(reason) => reason.write_to(writer)?,
+            DeniedPacket::ProtocolMismatch(digest) => {
+                writer.write_u8(8)?;
+                write_protocol_digest(writer, digest)?;
+            }
+        }
         Ok(())
     }
 
     fn read_from(reader: &mut impl ReadInteger) -> Result<Self, io::Error> {
-        let reason = DeniedReason::read_from(reader)?;
-        Ok(Self { reason })
+        // the digest uses the first tag after the ones of the denied reasons
+        let variant = reader.read_u8()?;
+        if variant == 8 {
+            return Ok(DeniedPacket::ProtocolMismatch(read_protocol_digest(
+                reader,
+            )?));
+        }
+        Ok(DeniedPacket::Reason(read_denied_reason(variant, reader)?))
     }
 }
 
@@ -633,13 +853,12 @@ pub fn sequence_len(sequence: u64) -> u8 {
 mod tests {
     use super::*;
 
-    use alloc::vec::Vec;
     use chacha20poly1305::{AeadCore, XChaCha20Poly1305, aead::OsRng};
     use lightyear_link::recv_payload_from_bytes;
     use lightyear_serde::writer::Writer;
     use std::dbg;
 
-    use crate::{MAX_PACKET_SIZE, USER_DATA_BYTES, crypto::generate_key, token::AddressList};
+    use crate::{USER_DATA_BYTES, crypto::generate_key, token::AddressList};
 
     #[test]
     fn sequence_number_bytes_required() {
@@ -694,13 +913,16 @@ mod tests {
             expire_timestamp,
             token_nonce: nonce,
             token_data: Box::new(token_data),
+            protocol_hash: 0xdead_beef,
+            app_version: "1".repeat(RequestPacket::MAX_APP_VERSION_LEN),
         });
 
-        let mut buf = Writer::from([0; MAX_PACKET_SIZE]);
+        let mut buf = Writer::from([0; MAX_PKT_BUF_SIZE]);
         let size = packet
             .write(buf.as_mut(), sequence, &packet_key, protocol_id)
             .unwrap();
         dbg!(size);
+        assert!(size <= MAX_PACKET_SIZE);
 
         let packet = Packet::read(
             recv_payload_from_bytes(buf.split_to(size)),
@@ -720,6 +942,11 @@ mod tests {
         assert_eq!(req_pkt.protocol_id, protocol_id);
         assert_eq!(req_pkt.expire_timestamp, expire_timestamp);
         assert_eq!(req_pkt.token_nonce, nonce);
+        assert_eq!(req_pkt.protocol_hash, 0xdead_beef);
+        assert_eq!(
+            req_pkt.app_version,
+            "1".repeat(RequestPacket::MAX_APP_VERSION_LEN)
+        );
 
         let mut reader = io::Cursor::new(&req_pkt.token_data[..]);
         let connect_token_private = ConnectTokenPrivate::read_from(&mut reader).unwrap();
@@ -735,21 +962,18 @@ mod tests {
         assert_eq!(connect_token_private.user_data, user_data);
     }
 
-    #[test]
-    fn denied_packet_custom_reason() {
+    /// Writes and reads back a denied packet, checking that it fits in a netcode packet
+    fn roundtrip_denied(packet: Packet) -> DeniedPacket {
         let packet_key = generate_key();
         let protocol_id = 0x1234_5678_9abc_def0;
-        let sequence = 0u64;
+        let sequence = u64::MAX;
         let mut replay_protection = ReplayProtection::new();
 
-        let packet = Packet::Denied(DeniedPacket {
-            reason: DeniedReason::Custom(String::from("a")),
-        });
-
         let mut buf = Writer::from([0; MAX_PKT_BUF_SIZE]);
         let size = packet
             .write(buf.as_mut(), sequence, &packet_key, protocol_id)
             .unwrap();
+        assert!(size <= MAX_PACKET_SIZE);
 
         let packet = Packet::read(
             recv_payload_from_bytes(buf.split_to(size)),
@@ -760,11 +984,85 @@ mod tests {
             0xff,
         )
         .unwrap();
-
         let Packet::Denied(denied_pkt) = packet else {
             panic!("wrong packet type");
         };
-        assert_eq!(denied_pkt.reason, DeniedReason::Custom(String::from("a")));
+        denied_pkt
+    }
+
+    #[test]
+    fn denied_packet_custom_reason() {
+        let reason = DeniedReason::Custom(String::from("a"));
+        let DeniedPacket::Reason(received) = roundtrip_denied(DeniedPacket::create(reason.clone()))
+        else {
+            panic!("wrong denied packet");
+        };
+        assert_eq!(received, reason);
+    }
+
+    #[test]
+    fn denied_packet_protocol_mismatch() {
+        let mismatch = |count: u16| ProtocolMismatch {
+            app_version: Some((String::from("1.1.0"), String::from("1.0.0"))),
+            types: (0..count)
+                .map(|i| TypeMismatch {
+                    registry: ProtocolRegistry::Component,
+                    name: alloc::format!("game::Ünicode{i}"),
+                    server_net_id: Some(i),
+                    client_net_id: (i % 2 == 0).then_some(i + 1),
+                })
+                .collect(),
+        };
+        let reason = DeniedReason::ProtocolMismatch(mismatch(3));
+        let DeniedPacket::Reason(received) = roundtrip_denied(DeniedPacket::create(reason.clone()))
+        else {
+            panic!("wrong denied packet");
+        };
+        assert_eq!(received, reason);
+
+        // the types that don't fit in the packet are left out
+        let mismatch = mismatch(200);
+        let DeniedPacket::Reason(DeniedReason::ProtocolMismatch(received)) = roundtrip_denied(
+            DeniedPacket::create(DeniedReason::ProtocolMismatch(mismatch.clone())),
+        ) else {
+            panic!("wrong denied packet");
+        };
+        assert_eq!(received.app_version, mismatch.app_version);
+        assert!(!received.types.is_empty());
+        assert!(received.types.len() < mismatch.types.len());
+        assert!(mismatch.types.starts_with(&received.types));
+    }
+
+    #[test]
+    fn denied_packet_protocol_digest() {
+        let digest = |count: u16| ProtocolDigest {
+            app_version: String::from("1.1.0"),
+            types: (0..count)
+                .map(|i| TypeDigest {
+                    registry: ProtocolRegistry::Message,
+                    net_id: i,
+                    name_hash: u32::from(i) * 0x9e37,
+                })
+                .collect(),
+            complete: true,
+        };
+        let DeniedPacket::ProtocolMismatch(received) =
+            roundtrip_denied(DeniedPacket::protocol_mismatch(digest(3)))
+        else {
+            panic!("wrong denied packet");
+        };
+        assert_eq!(received, digest(3));
+
+        // the types that don't fit in the packet are left out, and the digest is incomplete
+        let digest = digest(500);
+        let DeniedPacket::ProtocolMismatch(received) =
+            roundtrip_denied(DeniedPacket::protocol_mismatch(digest.clone()))
+        else {
+            panic!("wrong denied packet");
+        };
+        assert!(!received.complete);
+        assert!(received.types.len() > 100);
+        assert!(digest.types.starts_with(&received.types));
     }
 
     #[test]
@@ -774,9 +1072,7 @@ mod tests {
         let sequence = 0u64;
         let mut replay_protection = ReplayProtection::new();
 
-        let packet = Packet::Denied(DeniedPacket {
-            reason: DeniedReason::ServerFull,
-        });
+        let packet = DeniedPacket::create(DeniedReason::ServerFull);
 
         let mut buf = Writer::from([0; MAX_PKT_BUF_SIZE]);
         let size = packet
@@ -793,10 +1089,10 @@ mod tests {
         )
         .unwrap();
 
-        let Packet::Denied(denied_pkt) = packet else {
+        let Packet::Denied(DeniedPacket::Reason(reason)) = packet else {
             panic!("wrong packet type");
         };
-        assert_eq!(denied_pkt.reason, DeniedReason::ServerFull);
+        assert_eq!(reason, DeniedReason::ServerFull);
     }
 
     #[test]
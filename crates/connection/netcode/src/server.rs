@@ -10,6 +10,7 @@ use super::{
     bytes::Bytes,
     crypto::{self, Key},
     error::{Error, Result},
+    keyring::{KeyId, Keyring},
     packet::{
         ChallengePacket, DeniedPacket, DisconnectPacket, KeepAlivePacket, Packet, RequestPacket,
         ResponsePacket,
@@ -18,9 +19,11 @@ use super::{
     token::{ChallengeToken, ConnectToken, ConnectTokenBuilder, ConnectTokenPrivate},
 };
 use crate::token::TOKEN_EXPIRE_SEC;
+use lightyear_connection::admission::{AdmissionControl, unix_now};
 use lightyear_connection::prelude::Connecting;
+use lightyear_connection::protocol::Protocol;
 use lightyear_connection::shared::{
-    ConnectionRequestHandler, DefaultConnectionRequestHandler, DeniedReason,
+    ConnectionRequest, ConnectionRequestHandler, DefaultConnectionRequestHandler, DeniedReason,
 };
 use lightyear_core::id;
 use lightyear_link::{Link, LinkReceiver, LinkSender, RecvPayload, SendPayload};
@@ -33,6 +36,10 @@ pub const MAX_CLIENTS: usize = 256;
 
 const CLIENT_TIMEOUT_SECS: i32 = 10;
 
+/// The [`KeyId`] is stored in the most significant byte of the challenge sequence, so that the
+/// challenges sent before a key rotation can still be decrypted.
+const CHALLENGE_KEY_ID_SHIFT: u32 = u64::BITS - KeyId::BITS;
+
 #[derive(Clone, Copy)]
 struct TokenEntry {
     time: f64,
@@ -189,6 +196,27 @@ impl ConnectionCache {
         self.clients.remove(&client_id);
     }
 
+    /// Removes a client whose handshake did not complete.
+    fn remove_pending(&mut self, client_id: ClientId) {
+        if self
+            .clients
+            .get(&client_id)
+            .is_some_and(|conn| !conn.is_connected())
+        {
+            let conn = self.clients.remove(&client_id).unwrap();
+            self.client_id_map.remove(&conn.entity);
+            self.replay_protection.remove(&client_id);
+        }
+    }
+
+    /// Number of clients that were sent a challenge but are not connected yet.
+    fn num_pending(&self) -> usize {
+        self.clients
+            .values()
+            .filter(|conn| !conn.is_connected())
+            .count()
+    }
+
     fn clear(&mut self) {
         self.clients.clear();
         self.client_ids_scratch.clear();
@@ -243,6 +271,12 @@ pub type Callback<Ctx> = Box<dyn FnMut(ClientId, Entity, &mut Ctx) + Send + Sync
 pub type ConnectCallback<Ctx> =
     Box<dyn FnMut(ClientId, Entity, [u8; USER_DATA_BYTES], &mut Ctx) + Send + Sync + 'static>;
 
+/// Remote peer that sent the packets being processed.
+struct PacketSource<'a> {
+    addr: Option<SocketAddr>,
+    admission: Option<&'a mut AdmissionControl>,
+}
+
 fn server_addr_matches(local_addr: SocketAddr, token_addr: SocketAddr) -> bool {
     if local_addr.port() != token_addr.port() || local_addr.is_ipv4() != token_addr.is_ipv4() {
         return false;
@@ -409,11 +443,12 @@ impl<Ctx> ServerConfig<Ctx> {
 /// The server should be run in a loop to process incoming packets, send updates to clients, and maintain stable connections.
 pub struct Server<Ctx = ()> {
     time: f64,
-    private_key: Key,
+    keyring: Keyring,
     sequence: u64,
     token_sequence: u64,
     challenge_sequence: u64,
-    challenge_key: Key,
+    /// Keys used to encrypt the challenge tokens, one for each key of the [`Keyring`]
+    challenge_keys: Vec<(KeyId, Key)>,
     protocol_id: u64,
     conn_cache: ConnectionCache,
     token_entries: TokenEntries,
@@ -428,6 +463,14 @@ pub struct Server<Ctx = ()> {
     // 3. We split the bytes off, to recover the allocation
     writer: Writer,
     client_errors: Vec<Error>,
+    /// Clients that were disconnected because they timed out, since this was last cleared.
+    ///
+    /// This lets the connection layer tell apart a peer that went silent from a peer that
+    /// disconnected explicitly.
+    pub(crate) timed_out: Vec<ClientId>,
+    /// Protocol of the server and its hash. The requests of clients with a different protocol hash
+    /// are denied. No check is done if it is not set.
+    protocol: Option<(u64, Protocol)>,
 }
 
 impl Server {
@@ -438,18 +481,20 @@ impl Server {
     pub fn new(protocol_id: u64, private_key: Key) -> Result<Self> {
         let server: Server<()> = Server {
             time: 0.0,
-            private_key,
+            keyring: Keyring::new(0, private_key),
             protocol_id,
             sequence: 1 << 23,
             token_sequence: 0,
             challenge_sequence: 0,
-            challenge_key: crypto::generate_key(),
+            challenge_keys: Vec::new(),
             conn_cache: ConnectionCache::new(0.0),
             token_entries: TokenEntries::new(),
             cfg: ServerConfig::default(),
             send_queue: HashMap::default(),
             writer: Writer::with_capacity(MAX_PKT_BUF_SIZE),
             client_errors: vec![],
+            timed_out: vec![],
+            protocol: None,
         };
         Ok(server)
     }
@@ -474,18 +519,20 @@ impl<Ctx> Server<Ctx> {
     pub fn with_config(protocol_id: u64, private_key: Key, cfg: ServerConfig<Ctx>) -> Result<Self> {
         let server = Server {
             time: 0.0,
-            private_key,
+            keyring: Keyring::new(0, private_key),
             protocol_id,
             sequence: 1 << 23,
             token_sequence: 0,
             challenge_sequence: 0,
-            challenge_key: crypto::generate_key(),
+            challenge_keys: Vec::new(),
             conn_cache: ConnectionCache::new(0.0),
             token_entries: TokenEntries::new(),
             cfg,
             send_queue: HashMap::default(),
             writer: Writer::with_capacity(MAX_PKT_BUF_SIZE),
             client_errors: vec![],
+            timed_out: vec![],
+            protocol: None,
         };
         // info!("server started on {}", server.addr());
         Ok(server)
@@ -495,21 +542,40 @@ impl<Ctx> Server<Ctx> {
         self.cfg.connection_request_handler = handler;
     }
 
+    /// Set the protocol of the server. The connection requests of clients whose protocol hash
+    /// differs are denied with a [`ProtocolDigest`](lightyear_connection::protocol::ProtocolDigest)
+    /// of this protocol.
+    pub fn set_protocol(&mut self, protocol: Protocol) {
+        self.protocol = Some((protocol.hash(), protocol));
+    }
+
+    /// The private keys accepted by the server. The private key passed to the constructor has the
+    /// [`KeyId`] 0.
+    pub fn keyring(&self) -> &Keyring {
+        &self.keyring
+    }
+
+    /// Gives access to the [`Keyring`], for example to rotate the private key at runtime.
+    pub fn keyring_mut(&mut self) -> &mut Keyring {
+        &mut self.keyring
+    }
+
     /// Clears all connection and pending packet state so this server can be started again.
     ///
     /// Configuration, callback context, elapsed time, and packet sequence counters are preserved.
     /// Keeping the counters monotonic avoids reusing cryptographic nonces with the same keys. The
-    /// challenge key is rotated so responses from before the reset cannot be accepted afterward.
+    /// challenge keys are rotated so responses from before the reset cannot be accepted afterward.
     ///
     /// This does not notify or send disconnect packets to clients. Disconnect active clients before
     /// resetting when a graceful shutdown is required.
     pub fn reset(&mut self) {
-        self.challenge_key = crypto::generate_key();
+        self.challenge_keys.clear();
         self.conn_cache.clear();
         self.token_entries.inner.clear();
         self.send_queue.clear();
         self.writer.reset();
         self.client_errors.clear();
+        self.timed_out.clear();
     }
 }
 
@@ -551,11 +617,12 @@ impl<Ctx> Server<Ctx> {
         packet: Packet,
         entity_mut: &mut EntityCommands,
         server_addr: Option<SocketAddr>,
+        source: &mut PacketSource,
     ) -> Result<Option<RecvPayload>> {
         let entity = entity_mut.id();
         match packet {
             Packet::Request(packet) => {
-                self.process_connection_request(packet, entity_mut, server_addr)?;
+                self.process_connection_request(packet, entity_mut, server_addr, source)?;
                 Ok(None)
             }
             Packet::Response(packet) => {
@@ -693,6 +760,7 @@ impl<Ctx> Server<Ctx> {
         mut packet: RequestPacket,
         entity_mut: &mut EntityCommands,
         server_addr: Option<SocketAddr>,
+        source: &mut PacketSource,
     ) -> Result<()> {
         trace!("Server received connection request packet");
         let mut reader = io::Cursor::new(&mut packet.token_data[..]);
@@ -743,6 +811,47 @@ impl<Ctx> Server<Ctx> {
                 token.client_id,
             )));
         };
+        if let Some(admission) = source.admission.as_deref() {
+            let is_new = self.conn_cache.find_by_id(token.client_id).is_none();
+            // we don't answer, so that a flood of requests cannot be amplified into challenges
+            if is_new && !admission.can_start_handshakes(self.conn_cache.num_pending(), 1) {
+                return Err(Error::TooManyPendingHandshakes(id::PeerId::Netcode(
+                    token.client_id,
+                )));
+            }
+            if let Some(ban) = admission.ban_list.find(
+                Some(id::PeerId::Netcode(token.client_id)),
+                source.addr.map(|addr| addr.ip()),
+                unix_now(),
+            ) {
+                debug!(
+                    ?ban,
+                    "server denied connection request from a banned client"
+                );
+                self.send_netcode_packet(
+                    DeniedPacket::create(DeniedReason::Banned),
+                    token.server_to_client_key,
+                    entity,
+                )?;
+                return Err(Error::Denied(id::PeerId::Netcode(token.client_id)));
+            }
+        }
+        let mismatch_digest = match &self.protocol {
+            Some((hash, protocol)) if *hash != packet.protocol_hash => Some(protocol.digest()),
+            _ => None,
+        };
+        if let Some(digest) = mismatch_digest {
+            self.send_netcode_packet(
+                DeniedPacket::protocol_mismatch(digest),
+                token.server_to_client_key,
+                entity,
+            )?;
+            return Err(Error::ProtocolMismatch {
+                client_id: id::PeerId::Netcode(token.client_id),
+                app_version: packet.app_version,
+                protocol_hash: packet.protocol_hash,
+            });
+        }
         if self.num_connected_clients() >= self.cfg.max_clients {
             self.send_netcode_packet(
                 DeniedPacket::create(DeniedReason::ServerFull),
@@ -751,10 +860,14 @@ impl<Ctx> Server<Ctx> {
             )?;
             return Err(Error::ServerIsFull(id::PeerId::Netcode(token.client_id)));
         };
-        if let Some(denied_reason) = self
-            .cfg
-            .connection_request_handler
-            .handle_request(id::PeerId::Netcode(token.client_id))
+        if let Some(denied_reason) =
+            self.cfg
+                .connection_request_handler
+                .handle_request(&ConnectionRequest {
+                    client_id: id::PeerId::Netcode(token.client_id),
+                    source_addr: source.addr,
+                    user_data: &token.user_data,
+                })
         {
             self.send_netcode_packet(
                 DeniedPacket::create(denied_reason),
@@ -764,18 +877,19 @@ impl<Ctx> Server<Ctx> {
             return Err(Error::Denied(id::PeerId::Netcode(token.client_id)));
         }
 
+        let (challenge_sequence, challenge_key) = self.current_challenge_key();
         let Ok(challenge_token_encrypted) = ChallengeToken {
             client_id: token.client_id,
             user_data: token.user_data,
         }
-        .encrypt(self.challenge_sequence, &self.challenge_key) else {
+        .encrypt(challenge_sequence, &challenge_key) else {
             return Err(Error::ConnectTokenEncryptionFailure(id::PeerId::Netcode(
                 token.client_id,
             )));
         };
 
         self.send_netcode_packet(
-            ChallengePacket::create(self.challenge_sequence, challenge_token_encrypted),
+            ChallengePacket::create(challenge_sequence, challenge_token_encrypted),
             token.server_to_client_key,
             entity,
         )?;
@@ -796,13 +910,36 @@ impl<Ctx> Server<Ctx> {
         Ok(())
     }
 
+    /// Returns the sequence and the key used to encrypt the next challenge token.
+    ///
+    /// Each key of the [`Keyring`] has its own challenge key, which is generated the first time it
+    /// is needed.
+    fn current_challenge_key(&mut self) -> (u64, Key) {
+        let key_id = self.keyring.current_id();
+        let key = match self.challenge_keys.iter().find(|(id, _)| *id == key_id) {
+            Some((_, key)) => *key,
+            None => {
+                let key = crypto::generate_key();
+                self.challenge_keys.push((key_id, key));
+                key
+            }
+        };
+        let counter = self.challenge_sequence & ((1 << CHALLENGE_KEY_ID_SHIFT) - 1);
+        ((u64::from(key_id) << CHALLENGE_KEY_ID_SHIFT) | counter, key)
+    }
+
     fn process_connection_response(
         &mut self,
         mut packet: ResponsePacket,
         entity: Entity,
     ) -> Result<()> {
+        let key_id = (packet.sequence >> CHALLENGE_KEY_ID_SHIFT) as KeyId;
+        let Some((_, challenge_key)) = self.challenge_keys.iter().find(|(id, _)| *id == key_id)
+        else {
+            return Err(Error::ConnectTokenDecryptionFailure);
+        };
         let Ok(challenge_token) =
-            ChallengeToken::decrypt(&mut packet.token, packet.sequence, &self.challenge_key)
+            ChallengeToken::decrypt(&mut packet.token, packet.sequence, challenge_key)
         else {
             return Err(Error::ConnectTokenDecryptionFailure);
         };
@@ -849,6 +986,14 @@ impl<Ctx> Server<Ctx> {
                 continue;
             };
             if !client.is_connected() {
+                // forget the handshakes that were not completed, so that they don't count
+                // towards the limit of pending handshakes forever
+                if client.timeout.is_positive()
+                    && client.last_access_time + (client.timeout as f64) < self.time
+                {
+                    trace!("server forgot pending handshake of client {id}");
+                    self.conn_cache.remove_pending(id);
+                }
                 continue;
             }
             let entity = client.entity;
@@ -856,6 +1001,7 @@ impl<Ctx> Server<Ctx> {
                 && client.last_receive_time + (client.timeout as f64) < self.time
             {
                 debug!("server timed out client {id}");
+                self.timed_out.push(id);
                 self.on_disconnect(id, entity);
                 self.conn_cache.remove(id);
             }
@@ -913,6 +1059,7 @@ impl<Ctx> Server<Ctx> {
         now: u64,
         entity_mut: &mut EntityCommands,
         server_addr: Option<SocketAddr>,
+        source: &mut PacketSource,
     ) -> Result<Option<RecvPayload>> {
         if buf.len() <= 1 {
             // Too small to be a packet
@@ -921,11 +1068,26 @@ impl<Ctx> Server<Ctx> {
         let mut reader = io::Cursor::new(buf);
         let first_byte = reader.read_u8()?;
         let entity = entity_mut.id();
+        // handshake packets are rate-limited before doing any expensive decryption
+        let kind = first_byte & 0xF;
+        if (kind == Packet::REQUEST || kind == Packet::RESPONSE)
+            && let (Some(admission), Some(addr)) = (source.admission.as_deref_mut(), source.addr)
+            && !admission.allow_handshake_packet(addr.ip())
+        {
+            return Err(Error::RateLimited(entity));
+        }
         // reader.rewind()?;
         let (key, replay_protection) = match self.conn_cache.find_by_entity(&entity) {
             // Regardless of whether an entry in the connection cache exists for the client or not,
             // if the packet is a connection request we need to use the server's private key to decrypt it.
-            _ if first_byte == Packet::REQUEST => (self.private_key, None),
+            // The token says which key of the keyring it was generated with.
+            _ if first_byte == Packet::REQUEST => (
+                RequestPacket::key_id(reader.get_ref())
+                    .map_or(*self.keyring.current_key(), |key_id| {
+                        *self.keyring.key_for(key_id)
+                    }),
+                None,
+            ),
             Some(c) => {
                 let client_id = c.client_id;
                 (
@@ -953,7 +1115,7 @@ impl<Ctx> Server<Ctx> {
             Self::ALLOWED_PACKETS,
         )?;
 
-        self.process_packet(packet, entity_mut, server_addr)
+        self.process_packet(packet, entity_mut, server_addr, source)
     }
 
     fn recv_packets(
@@ -961,15 +1123,17 @@ impl<Ctx> Server<Ctx> {
         receiver: &mut LinkReceiver,
         entity_mut: &mut EntityCommands,
         server_addr: Option<SocketAddr>,
+        source: &mut PacketSource,
     ) -> Result<()> {
         let now = super::utils::now()?;
+        self.retire_expired_keys(now);
 
         // we pop every packet that is currently in the receiver, then we process them
         // Processing them might mean that we're re-adding them to the receiver so that
         // the Transport can read them later
         for _ in 0..receiver.len() {
             if let Some(recv_packet) = receiver.pop() {
-                match self.recv_packet(recv_packet, now, entity_mut, server_addr) {
+                match self.recv_packet(recv_packet, now, entity_mut, server_addr, source) {
                     Ok(Some(payload)) => receiver.push_raw(payload),
                     Err(e) => self.handle_client_error(e),
                     _ => {}
@@ -979,6 +1143,13 @@ impl<Ctx> Server<Ctx> {
         Ok(())
     }
 
+    /// Removes the previous private keys whose lifetime ended, and their challenge keys.
+    fn retire_expired_keys(&mut self, now: u64) {
+        self.keyring.retire_expired(now);
+        let keyring = &self.keyring;
+        self.challenge_keys.retain(|(id, _)| keyring.contains(*id));
+    }
+
     /// Updates the server state without receiving packets.
     pub fn update_state(&mut self, delta_ms: f64) {
         self.time += delta_ms;
@@ -997,7 +1168,26 @@ impl<Ctx> Server<Ctx> {
         entity_mut: &mut EntityCommands,
         server_addr: Option<SocketAddr>,
     ) -> Result<Vec<Error>> {
-        self.recv_packets(&mut link.recv, entity_mut, server_addr)?;
+        self.receive_with_admission(link, entity_mut, server_addr, None, None)
+    }
+
+    /// Receive and process packets from a link, applying the [`AdmissionControl`] of the server.
+    ///
+    /// `source_addr` is the address of the remote peer of the link. Without it, the checks that
+    /// depend on the address (rate limiting, bans of address ranges) are skipped.
+    pub fn receive_with_admission(
+        &mut self,
+        link: &mut Link,
+        entity_mut: &mut EntityCommands,
+        server_addr: Option<SocketAddr>,
+        source_addr: Option<SocketAddr>,
+        admission: Option<&mut AdmissionControl>,
+    ) -> Result<Vec<Error>> {
+        let mut source = PacketSource {
+            addr: source_addr,
+            admission,
+        };
+        self.recv_packets(&mut link.recv, entity_mut, server_addr, &mut source)?;
         Ok(core::mem::take(&mut self.client_errors))
     }
 
@@ -1081,8 +1271,13 @@ impl<Ctx> Server<Ctx> {
         client_id: ClientId,
         server_addr: SocketAddr,
     ) -> ConnectTokenBuilder<SocketAddr> {
-        let token_builder =
-            ConnectToken::build(server_addr, self.protocol_id, client_id, self.private_key);
+        let token_builder = ConnectToken::build(
+            server_addr,
+            self.protocol_id,
+            client_id,
+            *self.keyring.current_key(),
+        )
+        .key_id(self.keyring.current_id());
         self.token_sequence += 1;
         token_builder
     }
@@ -1173,12 +1368,29 @@ impl<Ctx> Server<Ctx> {
     pub fn client_entity(&self, client_id: ClientId) -> Option<Entity> {
         self.conn_cache.clients.get(&client_id).map(|c| c.entity)
     }
+
+    /// Moves the connection of a client to another link entity.
+    ///
+    /// This is used when a client resumes a suspended session from a new link: the session entity
+    /// takes over the connection, and the netcode packets queued for the new link.
+    pub(crate) fn migrate_client(&mut self, client_id: ClientId, entity: Entity) {
+        let Some(conn) = self.conn_cache.clients.get_mut(&client_id) else {
+            return;
+        };
+        let previous = core::mem::replace(&mut conn.entity, entity);
+        self.conn_cache.client_id_map.remove(&previous);
+        self.conn_cache.client_id_map.insert(entity, client_id);
+        if let Some(queue) = self.send_queue.remove(&previous) {
+            self.send_queue.entry(entity).or_default().extend(queue);
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::PRIVATE_KEY_BYTES;
+    use alloc::string::String;
     use alloc::sync::Arc;
     use bevy_app::App;
     use bevy_ecs::{
@@ -1186,6 +1398,7 @@ mod tests {
         world::{CommandQueue, World},
     };
     use core::sync::atomic::{AtomicBool, Ordering};
+    use lightyear_connection::admission::BanTarget;
     use lightyear_connection::server::Stopped;
 
     use crate::server_plugin::{NetcodeConfig, NetcodeServer, NetcodeServerPlugin};
@@ -1277,6 +1490,8 @@ mod tests {
             token.expire_timestamp,
             token.nonce,
             token.private_data,
+            Protocol::default().hash(),
+            "",
         ) else {
             unreachable!()
         };
@@ -1317,6 +1532,10 @@ mod tests {
                     connection_request(public_server_addresses, internal_server_addresses),
                     &mut client_commands,
                     server_addr,
+                    &mut PacketSource {
+                        addr: None,
+                        admission: None,
+                    },
                 )
                 .unwrap();
         }
@@ -1324,6 +1543,106 @@ mod tests {
         (server, world, client)
     }
 
+    fn process_request_with_admission(
+        admission: &mut AdmissionControl,
+    ) -> (Server, World, Entity, Result<()>) {
+        let server_addr = SocketAddr::from(([127, 0, 0, 1], 5000));
+        let mut server = Server::new(TEST_PROTOCOL_ID, TEST_PRIVATE_KEY).unwrap();
+        let mut world = World::new();
+        let client = world.spawn_empty().id();
+        let mut command_queue = CommandQueue::default();
+        let result = {
+            let mut commands = Commands::new(&mut command_queue, &world);
+            let mut client_commands = commands.entity(client);
+            server.process_connection_request(
+                connection_request(&[server_addr], None),
+                &mut client_commands,
+                Some(server_addr),
+                &mut PacketSource {
+                    addr: Some(SocketAddr::from(([10, 0, 0, 1], 4000))),
+                    admission: Some(admission),
+                },
+            )
+        };
+        command_queue.apply(&mut world);
+        (server, world, client, result)
+    }
+
+    #[test]
+    fn connection_request_from_banned_address_is_denied() {
+        let mut admission = AdmissionControl::default();
+        admission.ban_list.ban(
+            BanTarget::Address("10.0.0.0/8".parse().unwrap()),
+            None,
+            None,
+        );
+
+        let (server, world, client, result) = process_request_with_admission(&mut admission);
+
+        assert!(matches!(result, Err(Error::Denied(_))));
+        assert!(world.get::<Connecting>(client).is_none());
+        assert!(server.conn_cache.find_by_entity(&client).is_none());
+        // the denied packet is sent to the client
+        assert_eq!(server.send_queue.get(&client).map(Vec::len), Some(1));
+    }
+
+    #[test]
+    fn connection_request_ignored_when_too_many_pending_handshakes() {
+        let mut admission = AdmissionControl::default().with_max_pending_handshakes(Some(0));
+
+        let (server, world, client, result) = process_request_with_admission(&mut admission);
+
+        assert!(matches!(result, Err(Error::TooManyPendingHandshakes(_))));
+        assert!(world.get::<Connecting>(client).is_none());
+        // nothing is sent back
+        assert!(!server.send_queue.contains_key(&client));
+    }
+
+    /// Processes a request from a client without protocol, on a server with the given protocol
+    fn process_request_with_protocol(protocol: Protocol) -> (Server, World, Entity, Result<()>) {
+        let server_addr = SocketAddr::from(([127, 0, 0, 1], 5000));
+        let mut server = Server::new(TEST_PROTOCOL_ID, TEST_PRIVATE_KEY).unwrap();
+        server.set_protocol(protocol);
+        let mut world = World::new();
+        let client = world.spawn_empty().id();
+        let mut command_queue = CommandQueue::default();
+        let result = {
+            let mut commands = Commands::new(&mut command_queue, &world);
+            let mut client_commands = commands.entity(client);
+            server.process_connection_request(
+                connection_request(&[server_addr], None),
+                &mut client_commands,
+                Some(server_addr),
+                &mut PacketSource {
+                    addr: None,
+                    admission: None,
+                },
+            )
+        };
+        command_queue.apply(&mut world);
+        (server, world, client, result)
+    }
+
+    #[test]
+    fn connection_request_with_other_protocol_is_denied() {
+        let (server, world, client, result) = process_request_with_protocol(Protocol {
+            app_version: String::from("1.1.0"),
+            ..Default::default()
+        });
+
+        assert!(matches!(result, Err(Error::ProtocolMismatch { .. })));
+        assert!(world.get::<Connecting>(client).is_none());
+        assert!(server.conn_cache.find_by_entity(&client).is_none());
+        // the denied packet is sent to the client
+        assert_eq!(server.send_queue.get(&client).map(Vec::len), Some(1));
+
+        let (server, world, client, result) = process_request_with_protocol(Protocol::default());
+
+        assert!(result.is_ok());
+        assert!(world.get::<Connecting>(client).is_some());
+        assert!(server.conn_cache.find_by_entity(&client).is_some());
+    }
+
     #[test]
     fn connection_request_requires_server_addr_in_token() {
         let server_addr = SocketAddr::from(([192, 0, 2, 1], 5000));
@@ -1348,6 +1667,118 @@ mod tests {
         assert!(server.send_queue.contains_key(&client));
     }
 
+    /// Serializes a connection request for a token generated with the key `key_id`.
+    fn connection_request_payload(client_id: ClientId, key_id: KeyId, key: Key) -> RecvPayload {
+        let token = ConnectToken::build(
+            SocketAddr::from(([127, 0, 0, 1], 5000)),
+            TEST_PROTOCOL_ID,
+            client_id,
+            key,
+        )
+        .key_id(key_id)
+        .expire_seconds(-1)
+        .generate()
+        .unwrap();
+        let packet = RequestPacket::create(
+            token.protocol_id,
+            token.expire_timestamp,
+            token.nonce,
+            token.private_data,
+            Protocol::default().hash(),
+            "",
+        );
+        let mut buf = [0; MAX_PKT_BUF_SIZE];
+        let size = packet.write(&mut buf, 0, &key, TEST_PROTOCOL_ID).unwrap();
+        RecvPayload::from(&buf[..size])
+    }
+
+    /// Returns true if the server answered the connection request with a challenge.
+    fn receive_connection_request(server: &mut Server, payload: RecvPayload) -> bool {
+        let mut world = World::new();
+        let client = world.spawn_empty().id();
+        let mut command_queue = CommandQueue::default();
+        {
+            let mut commands = Commands::new(&mut command_queue, &world);
+            let mut client_commands = commands.entity(client);
+            let now = crate::utils::now().unwrap();
+            let _ = server.recv_packet(
+                payload,
+                now,
+                &mut client_commands,
+                None,
+                &mut PacketSource {
+                    addr: None,
+                    admission: None,
+                },
+            );
+        }
+        command_queue.apply(&mut world);
+        world.get::<Connecting>(client).is_some()
+    }
+
+    #[test]
+    fn connection_request_accepts_tokens_of_previous_keys() {
+        const NEW_KEY: Key = [0x43; PRIVATE_KEY_BYTES];
+        let mut server = Server::new(TEST_PROTOCOL_ID, TEST_PRIVATE_KEY).unwrap();
+        let old_token = connection_request_payload(1, 0, TEST_PRIVATE_KEY);
+        assert!(receive_connection_request(
+            &mut server,
+            connection_request_payload(2, 0, TEST_PRIVATE_KEY)
+        ));
+
+        server.keyring_mut().rotate(1, NEW_KEY, None);
+        // the token issued before the rotation is still valid
+        assert!(receive_connection_request(&mut server, old_token));
+        assert!(receive_connection_request(
+            &mut server,
+            connection_request_payload(3, 1, NEW_KEY)
+        ));
+        // the challenges are encrypted with the challenge key of the new key, but the challenges
+        // sent before the rotation can still be answered
+        let (sequence, _) = server.current_challenge_key();
+        assert_eq!(sequence >> CHALLENGE_KEY_ID_SHIFT, 1);
+        assert_eq!(
+            server
+                .challenge_keys
+                .iter()
+                .map(|(id, _)| *id)
+                .collect::<Vec<_>>(),
+            [0, 1]
+        );
+
+        assert!(server.keyring_mut().retire(0));
+        server.retire_expired_keys(0);
+        assert!(!receive_connection_request(
+            &mut server,
+            connection_request_payload(4, 0, TEST_PRIVATE_KEY)
+        ));
+        assert_eq!(
+            server
+                .challenge_keys
+                .iter()
+                .map(|(id, _)| *id)
+                .collect::<Vec<_>>(),
+            [1]
+        );
+    }
+
+    #[test]
+    fn migrated_client_moves_to_new_entity() {
+        let server_addr = SocketAddr::from(([127, 0, 0, 1], 5000));
+        let (mut server, mut world, client) =
+            process_request(Some(server_addr), &[server_addr], None);
+        let session = world.spawn_empty().id();
+
+        server.migrate_client(1, session);
+
+        assert!(server.conn_cache.find_by_entity(&client).is_none());
+        assert!(server.conn_cache.find_by_entity(&session).is_some());
+        assert_eq!(server.client_entity(1), Some(session));
+        // the challenge packet queued for the previous link is sent on the new one
+        assert!(!server.send_queue.contains_key(&client));
+        assert!(server.send_queue.contains_key(&session));
+    }
+
     #[test]
     fn connection_request_accepts_additional_expected_address() {
         let local_addr = SocketAddr::from(([0, 0, 0, 0], 5000));
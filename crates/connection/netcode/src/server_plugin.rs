@@ -1,5 +1,8 @@
-use crate::{ClientId, Key, PRIVATE_KEY_BYTES, ServerConfig, USER_DATA_BYTES, server::MAX_CLIENTS};
-use aeronet_io::connection::LocalAddr;
+use crate::{
+    ClientId, ConnectTokenBuilder, Key, KeyId, Keyring, MAX_PACKET_SIZE, PRIVATE_KEY_BYTES,
+    ServerConfig, USER_DATA_BYTES, server::MAX_CLIENTS,
+};
+use aeronet_io::connection::{LocalAddr, PeerAddr};
 use alloc::{sync::Arc, vec::Vec};
 use bevy_app::{App, Plugin, PostUpdate, PreUpdate};
 use bevy_ecs::prelude::*;
@@ -8,14 +11,17 @@ use bevy_ecs::{
 };
 use bevy_time::{Real, Time};
 use core::net::SocketAddr;
+use lightyear_connection::admission::AdmissionControl;
 use lightyear_connection::client::{Connected, Disconnected, DisconnectedReason, Disconnecting};
 use lightyear_connection::client_of::SkipNetcode;
 use lightyear_connection::host::HostClient;
 use lightyear_connection::prelude::{server::*, *};
+use lightyear_connection::protocol::{AppVersion, Protocol, ProtocolManifest};
+use lightyear_connection::resumption::{ResumeToken, SessionResumption, Suspended};
 use lightyear_connection::server::Stopping;
 use lightyear_connection::shared::ConnectionRequestHandler;
 use lightyear_core::id::{LocalId, PeerId, RemoteId};
-use lightyear_link::prelude::{LinkOf, Server};
+use lightyear_link::prelude::{AcceptedBy, LinkOf, ListenerOf, Server};
 use lightyear_link::{Link, LinkSystems, Unlink, UnlinkReason};
 use lightyear_transport::plugin::TransportSystems;
 use lightyear_utils::adaptive_for_each_mut;
@@ -65,10 +71,19 @@ pub struct NetcodeConfig {
     pub client_timeout_secs: i32,
     pub protocol_id: u64,
     pub private_key: Key,
+    /// Id of `private_key` in the server's [`Keyring`], which must match the key id of the tokens.
+    pub key_id: KeyId,
+    /// Keys that were rotated out but whose tokens should still be accepted, for example when the
+    /// server restarts during a key rotation.
+    pub previous_keys: Vec<(KeyId, Key)>,
     /// Whether to validate private connect tokens against the server entity's [`LocalAddr`].
     ///
     /// The default is `true`. Set this to `false` for addressless transports. When enabled, a
     /// [`LocalAddr`] must be present on the server entity.
+    ///
+    /// The links accepted by a [`ListenerOf`] entity are validated against the [`LocalAddr`] of
+    /// their listener instead, so the tokens must list the address of every listener that the
+    /// client may connect to.
     pub server_addr_check: bool,
     /// Additional server addresses accepted during private connect-token validation.
     ///
@@ -88,6 +103,8 @@ impl Default for NetcodeConfig {
             client_timeout_secs: 3,
             protocol_id: 0,
             private_key: [0; PRIVATE_KEY_BYTES],
+            key_id: 0,
+            previous_keys: Vec::new(),
             server_addr_check: true,
             additional_expected_addresses: Vec::new(),
             connection_request_handler: None,
@@ -110,6 +127,17 @@ impl NetcodeConfig {
         self
     }
 
+    pub fn with_key_id(mut self, key_id: KeyId) -> Self {
+        self.key_id = key_id;
+        self
+    }
+
+    /// Also accept the tokens generated with a previous private key.
+    pub fn with_previous_key(mut self, key_id: KeyId, key: Key) -> Self {
+        self.previous_keys.push((key_id, key));
+        self
+    }
+
     pub fn with_client_timeout_secs(mut self, client_timeout_secs: i32) -> Self {
         self.client_timeout_secs = client_timeout_secs;
         self
@@ -154,9 +182,13 @@ impl NetcodeServer {
         if let Some(handler) = config.connection_request_handler {
             cfg = cfg.connection_request_handler(handler);
         }
-        let server =
+        let mut server =
             crate::server::Server::with_config(config.protocol_id, config.private_key, cfg)
                 .expect("Could not create server netcode");
+        *server.keyring_mut() = config.previous_keys.into_iter().fold(
+            Keyring::new(config.key_id, config.private_key),
+            |keyring, (key_id, key)| keyring.with_previous_key(key_id, key),
+        );
         Self {
             inner: server,
             server_addr_check,
@@ -167,6 +199,32 @@ impl NetcodeServer {
         self.inner.set_connection_request_handler(handler);
     }
 
+    /// The private keys accepted by the server.
+    pub fn keyring(&self) -> &Keyring {
+        self.inner.keyring()
+    }
+
+    /// Gives access to the [`Keyring`] to rotate the private key without restarting the server.
+    ///
+    /// Connected clients are not affected, and the tokens generated with the previous key stay
+    /// valid for as long as the previous key is kept in the keyring.
+    pub fn keyring_mut(&mut self) -> &mut Keyring {
+        self.inner.keyring_mut()
+    }
+
+    /// Creates a connect token builder for a client of this server, signed with the current key
+    /// of its [`Keyring`].
+    ///
+    /// `server_addr` is the address that the client connects to. See
+    /// [`Server::token`](crate::server::Server::token).
+    pub fn token(
+        &mut self,
+        client_id: u64,
+        server_addr: SocketAddr,
+    ) -> ConnectTokenBuilder<SocketAddr> {
+        self.inner.token(client_id, server_addr)
+    }
+
     /// Clears the Netcode runtime state while preserving this server's configuration.
     ///
     /// This is invoked automatically when the server enters [`Stopped`].
@@ -280,13 +338,17 @@ impl NetcodeServerPlugin {
                 &mut Server,
                 Has<Stopping>,
                 Option<&LocalAddr>,
+                Option<&SessionResumption>,
+                Option<&mut AdmissionControl>,
             ),
             Without<Stopped>,
         >,
         link_query: Query<
-            (Entity, &mut Link),
+            (Entity, &mut Link, Option<&PeerAddr>, Option<&AcceptedBy>),
             (With<LinkOf>, Without<HostClient>, Without<SkipNetcode>),
         >,
+        listener_query: Query<&LocalAddr, With<ListenerOf>>,
+        suspended_query: Query<Option<&ResumeToken>, With<Suspended>>,
     ) {
         let delta = real_time.delta();
 
@@ -297,24 +359,21 @@ impl NetcodeServerPlugin {
         // receive packets from the link and process them through the server
         let server_query = adaptive_for_each_mut!(server_query);
         server_query.for_each(
-            |(server_entity, mut netcode_server, mut server, stopping, local_addr)| {
+            |(
+                server_entity,
+                mut netcode_server,
+                mut server,
+                stopping,
+                local_addr,
+                resumption,
+                mut admission,
+            )| {
                 parallel_commands.command_scope(|mut c| {
                     // SAFETY: we know that each client is unique to a single server so we won't
                     //  violate aliasing rules
                     let mut link_query = unsafe { link_query.reborrow_unsafe() };
 
-                    let server_addr = if netcode_server.server_addr_check {
-                        local_addr.map(|addr| addr.0)
-                    } else {
-                        None
-                    };
-                    let can_receive = !netcode_server.server_addr_check || server_addr.is_some();
-                    if !can_receive {
-                        error!(
-                            ?server_entity,
-                            "server address checking is enabled but the server has no LocalAddr"
-                        );
-                    }
+                    let server_addr_check = netcode_server.server_addr_check;
                     netcode_server.inner.update_state(delta.as_secs_f64());
 
                     // TODO: try to make this parallel!
@@ -325,18 +384,42 @@ impl NetcodeServerPlugin {
                         unsafe { UniqueEntitySlice::from_slice_unchecked(server.collection()) };
                     link_query
                         .iter_many_unique_mut(unique_slice)
-                        .for_each(|(entity, mut link)| {
+                        .for_each(|(entity, mut link, peer_addr, accepted_by)| {
                             let mut entity_mut = c.entity(entity);
 
+                            // the tokens must contain the address of the listener that accepted
+                            // the link, if any
+                            let server_addr = if server_addr_check {
+                                match accepted_by {
+                                    Some(accepted_by) => listener_query
+                                        .get(accepted_by.listener)
+                                        .ok()
+                                        .map(|addr| addr.0),
+                                    None => local_addr.map(|addr| addr.0),
+                                }
+                            } else {
+                                None
+                            };
+                            let can_receive = !server_addr_check || server_addr.is_some();
+                            if !can_receive {
+                                error!(
+                                    ?server_entity,
+                                    client_entity = ?entity,
+                                    "server address checking is enabled but the server has no LocalAddr"
+                                );
+                            }
+
                             // #[cfg(feature = "test_utils")]
                             // trace!("SERVER: length of each packet in receive: {:?}", link.recv.iter().map(|p| p.len()).collect::<Vec<_>>());
 
                             // TODO: insert Connecting if we receive a ConnectionRequest packet
                             if can_receive {
-                                match netcode_server.inner.receive(
+                                match netcode_server.inner.receive_with_admission(
                                     link.as_mut(),
                                     &mut entity_mut,
                                     server_addr,
+                                    peer_addr.map(|addr| addr.0),
+                                    admission.as_deref_mut(),
                                 ) {
                                     Ok(errors) => {
                                         for error in errors {
@@ -361,43 +444,87 @@ impl NetcodeServerPlugin {
                         });
 
                     // Connections: we know the connection comes from the current entity!
-                    netcode_server
-                        .inner
-                        .cfg
-                        .context
-                        .connections
-                        .drain(..)
-                        .for_each(|(id, entity, user_data)| {
-                            // TODO: mention server id in case we have multiple servers
-                            info!("New connection on netcode from {:?} ({:?})", id, entity);
-                            trace!("Adding Connected/ClientOf with id {:?}", id);
-                            c.entity(entity).insert((
-                                Connected,
-                                LocalId(PeerId::Server),
-                                RemoteId(PeerId::Netcode(id)),
-                                ClientOf,
-                                TokenUserData(user_data),
-                            ));
+                    let mut connections =
+                        core::mem::take(&mut netcode_server.inner.cfg.context.connections);
+                    for (id, entity, user_data) in connections.drain(..) {
+                        let token = ResumeToken(id);
+                        // a peer that reconnects during the grace period resumes its session,
+                        // even if it reconnects through a new link
+                        let session = resumption.and_then(|_| {
+                            server.collection().iter().copied().find(|e| {
+                                suspended_query.get(*e).is_ok_and(|t| t == Some(&token))
+                            })
                         });
-                    netcode_server
-                        .inner
-                        .cfg
-                        .context
-                        .disconnections
-                        .drain(..)
-                        .for_each(|(id, entity)| {
-                            // TODO: mention server id in case we have multiple servers
+                        let target = match session {
+                            Some(session) if session == entity => {
+                                info!("Netcode client {:?} resumed its session ({:?})", id, entity);
+                                entity
+                            }
+                            Some(session) => {
+                                info!(
+                                    "Netcode client {:?} resumed its session ({:?}) from new link {:?}",
+                                    id, session, entity
+                                );
+                                netcode_server.inner.migrate_client(id, session);
+                                c.queue(move |world: &mut World| {
+                                    adopt_link(world, entity, session);
+                                });
+                                session
+                            }
+                            None => {
+                                if suspended_query.contains(entity) {
+                                    // another peer reuses the link of a suspended session: the
+                                    // suspended session cannot be resumed anymore
+                                    c.entity(entity).insert(Disconnected {
+                                        reason: DisconnectedReason::ByPeer(None),
+                                    });
+                                }
+                                // TODO: mention server id in case we have multiple servers
+                                info!("New connection on netcode from {:?} ({:?})", id, entity);
+                                entity
+                            }
+                        };
+                        trace!("Adding Connected/ClientOf with id {:?}", id);
+                        c.entity(target).insert((
+                            Connected,
+                            LocalId(PeerId::Server),
+                            RemoteId(PeerId::Netcode(id)),
+                            ClientOf,
+                            TokenUserData(user_data),
+                            token,
+                        ));
+                    }
+                    netcode_server.inner.cfg.context.connections = connections;
+
+                    let mut disconnections =
+                        core::mem::take(&mut netcode_server.inner.cfg.context.disconnections);
+                    for (id, entity) in disconnections.drain(..) {
+                        // only peers that went silent can resume their session
+                        if let Some(resumption) = resumption
+                            && netcode_server.inner.timed_out.contains(&id)
+                        {
                             info!(
-                                "Disconnection from netcode client {:?}. Despawning entity.",
-                                id
+                                "Netcode client {:?} timed out. Keeping its session for {:?}",
+                                id, resumption.grace_period
                             );
-                            // first disconnect to trigger observers
                             c.entity(entity)
-                                .try_insert(Disconnected {
-                                    reason: DisconnectedReason::ByPeer(None),
-                                })
-                                .despawn();
-                        });
+                                .try_insert(resumption.suspend(DisconnectedReason::ByPeer(None)));
+                            continue;
+                        }
+                        // TODO: mention server id in case we have multiple servers
+                        info!(
+                            "Disconnection from netcode client {:?}. Despawning entity.",
+                            id
+                        );
+                        // first disconnect to trigger observers
+                        c.entity(entity)
+                            .try_insert(Disconnected {
+                                reason: DisconnectedReason::ByPeer(None),
+                            })
+                            .despawn();
+                    }
+                    netcode_server.inner.cfg.context.disconnections = disconnections;
+                    netcode_server.inner.timed_out.clear();
                     if stopping {
                         // after we sent disconnection packets, we can stop the server transport
                         c.trigger(Unlink {
@@ -411,12 +538,38 @@ impl NetcodeServerPlugin {
         )
     }
 
-    fn start(trigger: On<Start>, query: Query<(), With<NetcodeServer>>, mut commands: Commands) {
-        if query.get(trigger.entity).is_ok() {
+    /// Start the server, which denies the clients whose protocol differs from the current one
+    fn start(
+        trigger: On<Start>,
+        mut query: Query<&mut NetcodeServer>,
+        app_version: Option<Res<AppVersion>>,
+        manifest: Option<Res<ProtocolManifest>>,
+        mut commands: Commands,
+    ) {
+        if let Ok(mut server) = query.get_mut(trigger.entity) {
+            server
+                .inner
+                .set_protocol(Protocol::new(app_version.as_deref(), manifest.as_deref()));
             commands.entity(trigger.entity).insert(Started);
         }
     }
 
+    /// The netcode packets wrap the payloads of the links of the server, which cannot be larger
+    /// than [`MAX_PACKET_SIZE`]
+    fn limit_link_mtu(
+        trigger: On<Insert, (Link, LinkOf)>,
+        mut query: Query<(&mut Link, &LinkOf), Without<SkipNetcode>>,
+        servers: Query<(), With<NetcodeServer>>,
+    ) {
+        if let Ok((mut link, link_of)) = query.get_mut(trigger.entity)
+            && servers.contains(link_of.server)
+        {
+            link.set_max_mtu(MAX_PACKET_SIZE)
+                .inspect_err(|e| error!("Link cannot be used with netcode: {e}"))
+                .ok();
+        }
+    }
+
     fn reset_on_stopped(trigger: On<Add, Stopped>, mut query: Query<&mut NetcodeServer>) {
         if let Ok(mut server) = query.get_mut(trigger.entity) {
             server.reset();
@@ -436,6 +589,7 @@ impl NetcodeServerPlugin {
                 Without<SkipNetcode>,
             ),
         >,
+        suspended_query: Query<(), With<Suspended>>,
     ) -> Result {
         if let Ok((server_entity, mut netcode_server, server)) = query.get_mut(trigger.entity) {
             info!("Stopping netcode server");
@@ -458,11 +612,51 @@ impl NetcodeServerPlugin {
                     Ok(())
                 },
             )?;
+
+            // suspended sessions cannot be resumed once the server stops
+            for entity in server.collection() {
+                if suspended_query.contains(*entity) {
+                    commands
+                        .entity(*entity)
+                        .insert(Disconnected {
+                            reason: DisconnectedReason::UserRequested(None),
+                        })
+                        .despawn();
+                }
+            }
         }
         Ok(())
     }
 }
 
+/// Moves the [`Link`] and [`PeerAddr`] of the new `link` entity to the suspended `session`
+/// entity, then despawns `link`.
+///
+/// IO layers that route packets using [`PeerAddr`], like the UDP server, then deliver the packets
+/// of the new link to the session entity.
+fn adopt_link(world: &mut World, link: Entity, session: Entity) {
+    let Ok(mut link_entity) = world.get_entity_mut(link) else {
+        return;
+    };
+    let new_link = link_entity.take::<Link>();
+    let peer_addr = link_entity.take::<PeerAddr>();
+    let accepted_by = link_entity.get::<AcceptedBy>().copied();
+    link_entity.despawn();
+    let Ok(mut session_entity) = world.get_entity_mut(session) else {
+        return;
+    };
+    if let Some(new_link) = new_link {
+        session_entity.insert(new_link);
+    }
+    // the datagrams of the session now arrive through the listener of the new link
+    if let Some(accepted_by) = accepted_by {
+        session_entity.insert(accepted_by);
+    }
+    if let Some(peer_addr) = peer_addr {
+        session_entity.insert(peer_addr);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -510,5 +704,6 @@ impl Plugin for NetcodeServerPlugin {
         app.add_observer(Self::start);
         app.add_observer(Self::stop);
         app.add_observer(Self::reset_on_stopped);
+        app.add_observer(Self::limit_link_mtu);
     }
 }
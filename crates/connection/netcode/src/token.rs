@@ -4,6 +4,7 @@ use super::{
     bytes::Bytes,
     crypto::{self, Key},
     error::Error,
+    keyring::KeyId,
     utils,
 };
 use alloc::borrow::ToOwned;
@@ -322,6 +323,13 @@ impl ConnectToken {
     pub fn expire_timestamp(&self) -> u64 {
         self.expire_timestamp
     }
+
+    /// Id of the private key that the token was generated with.
+    ///
+    /// See [`Keyring`](crate::Keyring).
+    pub fn key_id(&self) -> KeyId {
+        self.nonce[0]
+    }
 }
 
 /// A builder that can be used to generate a connect token.
@@ -330,6 +338,7 @@ pub struct ConnectTokenBuilder<A: utils::ToSocketAddrs> {
     client_id: u64,
     expire_seconds: i32,
     private_key: Key,
+    key_id: KeyId,
     timeout_seconds: i32,
     public_server_addresses: A,
     internal_server_addresses: Option<AddressList>,
@@ -343,6 +352,7 @@ impl<A: utils::ToSocketAddrs> ConnectTokenBuilder<A> {
             client_id,
             expire_seconds: TOKEN_EXPIRE_SEC,
             private_key,
+            key_id: 0,
             timeout_seconds: CONNECTION_TIMEOUT_SEC,
             public_server_addresses: server_addresses,
             internal_server_addresses: None,
@@ -364,6 +374,12 @@ impl<A: utils::ToSocketAddrs> ConnectTokenBuilder<A> {
         self.timeout_seconds = timeout_seconds;
         self
     }
+    /// Sets the id of the private key, so that a server with multiple keys in its
+    /// [`Keyring`](crate::Keyring) knows which one to use. Defaults to 0.
+    pub fn key_id(mut self, key_id: KeyId) -> Self {
+        self.key_id = key_id;
+        self
+    }
     /// Sets the user data that will be added to the token, this can be any data you want.
     pub fn user_data(mut self, user_data: [u8; USER_DATA_BYTES]) -> Self {
         self.user_data = user_data;
@@ -397,7 +413,9 @@ impl<A: utils::ToSocketAddrs> ConnectTokenBuilder<A> {
         };
         let client_to_server_key = crypto::generate_key();
         let server_to_client_key = crypto::generate_key();
-        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        // the key id is sent in clear in the first byte of the nonce, the other 23 bytes are random
+        nonce[0] = self.key_id;
 
         let private_data = ConnectTokenPrivate {
             client_id: self.client_id,
@@ -8,6 +8,10 @@
 //! [`P2PSessionPlugin`] owns the barrier bookkeeping; applications do not need to configure a
 //! [`P2PSession`]. The session does not own peer discovery or Link connection. Stopping it can
 //! either preserve every Link for a lobby/rematch or unlink every currently declared P2P Link.
+//!
+//! Once started, every peer elects the same session coordinator from the roster. If members leave
+//! the running session, the survivors agree on the last confirmed input tick of the departed
+//! peers, re-elect a coordinator if needed and trigger [`P2PHostMigrated`].
 
 #![no_std]
 
@@ -22,6 +26,7 @@ pub use session::*;
 /// Commonly used P2P session types.
 pub mod prelude {
     pub use crate::{
-        P2PSession, P2PSessionPlugin, P2PSessionState, P2PStart, P2PStarted, P2PStop, P2PStopped,
+        P2PHostMigrated, P2PSession, P2PSessionPlugin, P2PSessionState, P2PStart, P2PStarted,
+        P2PStop, P2PStopped,
     };
 }
@@ -7,7 +7,7 @@ use core::time::Duration;
 use lightyear_connection::client::Connected;
 use lightyear_connection::direction::NetworkDirection;
 use lightyear_connection::network_topology::{NetworkTopology, NetworkingMetadata};
-use lightyear_connection::p2p::P2P;
+use lightyear_connection::p2p::{P2P, P2PInputFrontier};
 use lightyear_core::id::{LocalId, PeerId, RemoteId};
 use lightyear_core::prelude::{LocalTimeline, Tick, TimelineSystems};
 use lightyear_link::prelude::{Unlink, UnlinkReason};
@@ -21,6 +21,7 @@ use smallvec::SmallVec;
 
 const DEFAULT_START_DELAY_TICKS: u16 = 120;
 const DEFAULT_START_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_MIGRATION_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Public lifecycle of the deterministic P2P session.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
@@ -61,6 +62,34 @@ impl RemotePeerStart {
     }
 }
 
+/// Host-migration progress after one or more members left a running session.
+#[derive(Debug, Clone)]
+struct Migration {
+    /// Every member that left since the previous completed migration.
+    departed: SmallVec<[PeerId; 4]>,
+    /// Remote members that are expected to take part in this migration.
+    survivors: SmallVec<[PeerId; 4]>,
+    /// Hash of the surviving roster, including the local peer.
+    roster_hash: u64,
+    /// Last confirmed input tick of the departed peers, as known locally.
+    local_confirmed_tick: Tick,
+    /// Whether the local proposal has been queued on every surviving Link.
+    local_proposal_sent: bool,
+    /// Wall-clock timestamp at which the current migration attempt began.
+    started_at: Duration,
+}
+
+/// Latest host-migration proposal received from one remote member.
+///
+/// Proposals are kept even if their roster does not match the local view yet, because a remote
+/// peer can notice a departure before this peer does.
+#[derive(Debug, Clone, Copy)]
+struct MigrationProposal {
+    peer_id: PeerId,
+    roster_hash: u64,
+    confirmed_tick: Tick,
+}
+
 /// Application-global bookkeeping and state for one deterministic P2P session.
 ///
 /// [`P2PSessionPlugin`] initializes this resource. Applications normally only declare [`P2P`]
@@ -95,6 +124,21 @@ pub struct P2PSession {
     remote_peers: SmallVec<[RemotePeerStart; 4]>,
     /// Set when a peer advertises a different roster for the same attempt.
     roster_mismatch: bool,
+    /// Maximum wall-clock time allowed for the surviving peers to agree on a host migration.
+    migration_timeout: Duration,
+    /// Identity of the local peer, captured by [`P2PStart`].
+    local_id: Option<PeerId>,
+    /// Remote members of the running session.
+    ///
+    /// Unlike `remote_peers`, this is retained after the start barrier so that the surviving
+    /// members can be identified when a peer leaves.
+    members: SmallVec<[PeerId; 4]>,
+    /// Member currently acting as the session coordinator.
+    coordinator: Option<PeerId>,
+    /// Host migration in progress, if any member left the running session.
+    migration: Option<Migration>,
+    /// Latest host-migration proposal received from each remote member.
+    migration_proposals: SmallVec<[MigrationProposal; 4]>,
 }
 
 impl Default for P2PSession {
@@ -111,6 +155,12 @@ impl Default for P2PSession {
             roster_hash: None,
             remote_peers: SmallVec::new(),
             roster_mismatch: false,
+            migration_timeout: DEFAULT_MIGRATION_TIMEOUT,
+            local_id: None,
+            members: SmallVec::new(),
+            coordinator: None,
+            migration: None,
+            migration_proposals: SmallVec::new(),
         }
     }
 }
@@ -128,6 +178,13 @@ impl P2PSession {
         self
     }
 
+    /// Set the maximum wall-clock duration allowed for the surviving peers to agree on a host
+    /// migration. The session is stopped if the migration does not complete in time.
+    pub fn with_migration_timeout(mut self, timeout: Duration) -> Self {
+        self.migration_timeout = timeout;
+        self
+    }
+
     /// Current deterministic session lifecycle.
     pub fn state(&self) -> P2PSessionState {
         self.state
@@ -147,14 +204,43 @@ impl P2PSession {
         matches!(self.state, P2PSessionState::Started { .. })
     }
 
+    /// Remote members of the running session.
+    ///
+    /// Members that left the session are removed once the surviving peers agree on a host
+    /// migration.
+    pub fn members(&self) -> &[PeerId] {
+        &self.members
+    }
+
+    /// Member currently acting as the session coordinator.
+    ///
+    /// Every peer elects the same coordinator deterministically from the session roster when the
+    /// session starts, and the surviving peers elect a new one when the coordinator leaves.
+    /// Applications can use it to decide which peer is authoritative for lobby decisions.
+    pub fn coordinator(&self) -> Option<PeerId> {
+        self.coordinator
+    }
+
+    /// Whether the local peer is the session coordinator.
+    pub fn is_coordinator(&self) -> bool {
+        self.local_id.is_some() && self.coordinator == self.local_id
+    }
+
+    /// Whether the surviving peers are currently agreeing on a host migration.
+    pub fn is_migrating(&self) -> bool {
+        self.migration.is_some()
+    }
+
     /// Reset negotiation state and freeze the supplied remote identities into a new start attempt.
     fn begin(
         &mut self,
+        local_id: PeerId,
         remote_peer_ids: SmallVec<[PeerId; 4]>,
         roster_hash: u64,
         started_at: Duration,
     ) {
         self.generation = self.generation.wrapping_add(1);
+        self.local_id = Some(local_id);
         self.start_started_at = Some(started_at);
         self.local_ready_tick = None;
         self.local_ready_sent = false;
@@ -172,6 +258,109 @@ impl P2PSession {
     fn stop(&mut self) {
         self.state = P2PSessionState::Stopped;
         self.clear_barrier_progress();
+        self.local_id = None;
+        self.members.clear();
+        self.coordinator = None;
+        self.migration = None;
+        self.migration_proposals.clear();
+    }
+
+    /// Retain the frozen cohort as the session roster and elect its first coordinator.
+    fn complete_start(&mut self, start_tick: Tick) {
+        self.state = P2PSessionState::Started { start_tick };
+        self.members = self.remote_peers.iter().map(|peer| peer.peer_id).collect();
+        self.coordinator = self
+            .local_id
+            .map(|local_id| elect_coordinator(local_id, &self.members));
+        self.clear_barrier_progress();
+    }
+
+    fn is_member(&self, peer_id: PeerId) -> bool {
+        self.members.contains(&peer_id)
+    }
+
+    /// Start or restart a host migration after `departed` members left the running session.
+    ///
+    /// A departure during an ongoing migration restarts it with the smaller surviving roster.
+    /// The local proposal is the earliest confirmed input tick known for any departed member.
+    fn begin_migration(
+        &mut self,
+        departed: &[PeerId],
+        local_confirmed_tick: Tick,
+        started_at: Duration,
+    ) {
+        let Some(local_id) = self.local_id else {
+            return;
+        };
+        let (mut all_departed, confirmed_tick) = match self.migration.take() {
+            Some(previous) => (
+                previous.departed,
+                previous.local_confirmed_tick.min(local_confirmed_tick),
+            ),
+            None => (SmallVec::new(), local_confirmed_tick),
+        };
+        for peer_id in departed {
+            if !all_departed.contains(peer_id) {
+                all_departed.push(*peer_id);
+            }
+        }
+        let survivors: SmallVec<[PeerId; 4]> = self
+            .members
+            .iter()
+            .copied()
+            .filter(|peer_id| !all_departed.contains(peer_id))
+            .collect();
+        let roster_hash = roster_hash(local_id, &survivors);
+        tracing::info!(
+            departed = ?all_departed,
+            ?survivors,
+            roster_hash,
+            ?confirmed_tick,
+            "starting P2P host migration"
+        );
+        self.migration = Some(Migration {
+            departed: all_departed,
+            survivors,
+            roster_hash,
+            local_confirmed_tick: confirmed_tick,
+            local_proposal_sent: false,
+            started_at,
+        });
+    }
+
+    fn migration_timed_out_at(&self, now: Duration) -> bool {
+        self.migration.as_ref().is_some_and(|migration| {
+            now.saturating_sub(migration.started_at) >= self.migration_timeout
+        })
+    }
+
+    /// Complete the current migration once every survivor proposed a tick for the same roster.
+    ///
+    /// The agreed tick is the minimum of all proposals, so that every survivor only relies on
+    /// departed-peer inputs that all of them have received.
+    fn advance_migration(&mut self) -> Option<P2PHostMigrated> {
+        let migration = self.migration.as_ref()?;
+        let local_id = self.local_id?;
+        let mut confirmed_tick = migration.local_confirmed_tick;
+        for survivor in &migration.survivors {
+            let proposal = self.migration_proposals.iter().find(|proposal| {
+                proposal.peer_id == *survivor && proposal.roster_hash == migration.roster_hash
+            })?;
+            confirmed_tick = confirmed_tick.min(proposal.confirmed_tick);
+        }
+
+        let migration = self.migration.take()?;
+        let roster_hash = migration.roster_hash;
+        self.migration_proposals
+            .retain(|proposal| proposal.roster_hash != roster_hash);
+        self.members = migration.survivors;
+        let coordinator = elect_coordinator(local_id, &self.members);
+        self.coordinator = Some(coordinator);
+        Some(P2PHostMigrated {
+            departed: migration.departed,
+            coordinator,
+            confirmed_tick,
+        })
     }
 
     /// Drop transient barrier bookkeeping while preserving the public Started state.
@@ -203,11 +392,36 @@ impl P2PSession {
         // Reliable packets from a previous start can arrive after a stop/restart cycle.
         let generation = match message {
             P2PSessionMessage::Ready { generation, .. }
-            | P2PSessionMessage::StartAcknowledgement { generation, .. } => generation,
+            | P2PSessionMessage::StartAcknowledgement { generation, .. }
+            | P2PSessionMessage::Migrate { generation, .. } => generation,
         };
         if generation != self.generation {
             return;
         }
+        if let P2PSessionMessage::Migrate {
+            roster_hash,
+            confirmed_tick,
+            ..
+        } = message
+        {
+            if !self.is_member(peer_id) {
+                return;
+            }
+            let proposal = MigrationProposal {
+                peer_id,
+                roster_hash,
+                confirmed_tick,
+            };
+            match self
+                .migration_proposals
+                .iter_mut()
+                .find(|proposal| proposal.peer_id == peer_id)
+            {
+                Some(previous) => *previous = proposal,
+                None => self.migration_proposals.push(proposal),
+            }
+            return;
+        }
         let Some(peer) = self
             .remote_peers
             .iter_mut()
@@ -217,7 +431,8 @@ impl P2PSession {
         };
         let message_roster_hash = match message {
             P2PSessionMessage::Ready { roster_hash, .. }
-            | P2PSessionMessage::StartAcknowledgement { roster_hash, .. } => roster_hash,
+            | P2PSessionMessage::StartAcknowledgement { roster_hash, .. }
+            | P2PSessionMessage::Migrate { roster_hash, .. } => roster_hash,
         };
         if self.roster_hash != Some(message_roster_hash) {
             self.roster_mismatch = true;
@@ -255,6 +470,7 @@ impl P2PSession {
                     Some(_) => {}
                 }
             }
+            P2PSessionMessage::Migrate { .. } => {}
         }
     }
 
@@ -375,6 +591,21 @@ impl P2PSession {
             });
         }
     }
+
+    /// Return the local host-migration proposal once per migration attempt.
+    fn collect_migration_outbound(&mut self) -> Option<P2PSessionMessage> {
+        let generation = self.generation;
+        let migration = self.migration.as_mut()?;
+        if migration.local_proposal_sent {
+            return None;
+        }
+        migration.local_proposal_sent = true;
+        Some(P2PSessionMessage::Migrate {
+            generation,
+            roster_hash: migration.roster_hash,
+            confirmed_tick: migration.local_confirmed_tick,
+        })
+    }
 }
 
 /// Trigger this locally on every peer after declaring the P2P Links that should form the initial
@@ -412,11 +643,29 @@ pub struct P2PStarted {
 
 /// Triggered after [`P2PStop`] returns the deterministic session to its stopped state.
 ///
+/// Also triggered if the surviving peers fail to agree on a host migration before its timeout.
 /// Link connection state is affected only when [`P2PStop::unlink`] is `true`.
 #[derive(Event, Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct P2PStopped;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// Triggered when the surviving peers of a running session agreed on a host migration after one
+/// or more members left.
+///
+/// The session keeps running with the surviving members; Links of the departed peers return to
+/// [`P2P::Inactive`]. Every survivor triggers this event with the same values, so deterministic
+/// games can, for example, stop applying the departed players' inputs after
+/// [`confirmed_tick`](Self::confirmed_tick).
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct P2PHostMigrated {
+    /// Members that left the session.
+    pub departed: SmallVec<[PeerId; 4]>,
+    /// Newly elected session coordinator. This is unchanged if the coordinator did not leave.
+    pub coordinator: PeerId,
+    /// Last tick for which every survivor has received the inputs of every departed member.
+    pub confirmed_tick: Tick,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 enum P2PSessionMessage {
     /// Advertise that one peer is locally ready and its earliest safe start tick.
     Ready {
@@ -436,6 +685,15 @@ enum P2PSessionMessage {
         /// Common start tick calculated by the sender.
         start_tick: Tick,
     },
+    /// Propose the last confirmed input tick of the departed members during host migration.
+    Migrate {
+        /// Start-attempt number of the running session.
+        generation: u32,
+        /// Hash of the surviving roster, including the sender.
+        roster_hash: u64,
+        /// Last tick for which the sender received the inputs of every departed member.
+        confirmed_tick: Tick,
+    },
 }
 
 /// Private reliable delivery queue for P2P lifecycle control messages.
@@ -477,7 +735,12 @@ impl Plugin for P2PSessionPlugin {
 
         app.add_observer(start_session);
         app.add_observer(stop_session);
-        app.add_systems(PreUpdate, drive_session.after(MessageSystems::Receive));
+        app.add_systems(
+            PreUpdate,
+            (drive_session, drive_migration)
+                .chain()
+                .after(MessageSystems::Receive),
+        );
         // PreUpdate runs only once per rendered frame, which may contain several catch-up fixed
         // ticks. FixedFirst observes every boundary, so it cannot skip the transition to the
         // agreed tick. P2PStarted must run before IncrementLocal: a late input for the first
@@ -515,6 +778,25 @@ fn roster_hash(local_id: PeerId, remote_ids: &[PeerId]) -> u64 {
     hasher.finish()
 }
 
+/// Deterministically elect the coordinator of a roster, independently of local/remote ordering.
+///
+/// The coordinator is the member with the smallest serialized [`PeerId`], which matches the order
+/// used by [`roster_hash`].
+fn elect_coordinator(local_id: PeerId, remote_ids: &[PeerId]) -> PeerId {
+    let encode = |peer_id: &PeerId| {
+        let mut encoded = Vec::with_capacity(peer_id.bytes_len());
+        peer_id
+            .to_bytes(&mut encoded)
+            .expect("serializing a PeerId into memory cannot fail");
+        encoded
+    };
+    core::iter::once(&local_id)
+        .chain(remote_ids)
+        .min_by_key(|peer_id| encode(peer_id))
+        .copied()
+        .unwrap_or(local_id)
+}
+
 /// Signal the start of a new deterministic P2P session.
 ///
 /// Every currently declared P2P Link is used as a candidate for the new session.
@@ -609,7 +891,7 @@ fn start_session(
     }
 
     tracing::info!(peer_count, roster_hash, "starting P2P session negotiation");
-    session.begin(remote_ids, roster_hash, real_time.elapsed());
+    session.begin(local_id, remote_ids, roster_hash, real_time.elapsed());
 }
 
 /// Stop the local deterministic session, optionally unlink every P2P Link, and emit
@@ -700,11 +982,16 @@ fn start_session_before_agreed_tick(
     }
 
     joined.sort_unstable_by_key(|entity| entity.index_u32());
-    session.state = P2PSessionState::Started { start_tick };
+    session.complete_start(start_tick);
     transition_candidates(&mut commands, &mut links, P2P::Joined);
+    // no input of the new session has been received yet
+    for entity in &joined {
+        commands
+            .entity(*entity)
+            .insert(P2PInputFrontier(start_tick - 1));
+    }
     metadata.mode = NetworkTopology::P2P(joined);
-    tracing::info!(?start_tick, "P2P session started");
-    session.clear_barrier_progress();
+    tracing::info!(?start_tick, coordinator = ?session.coordinator, "P2P session started");
     commands.trigger(P2PStarted { start_tick });
 }
 
@@ -821,6 +1108,140 @@ fn drive_session(
     }
 }
 
+/// Detect members leaving the running session and drive the host migration between survivors.
+///
+/// A member has left when its joined Link is disconnected or no longer exists. The surviving
+/// peers exchange the last confirmed input tick they know for the departed members, elect a new
+/// coordinator once every survivor has proposed a tick for the same surviving roster, and trigger
+/// [`P2PHostMigrated`]. If they cannot agree before the migration timeout, the session stops.
+fn drive_migration(
+    mut commands: Commands,
+    mut session: ResMut<P2PSession>,
+    timeline: Res<LocalTimeline>,
+    real_time: Res<Time<Real>>,
+    mut links: P2PLinkQuery,
+    frontiers: Query<&P2PInputFrontier>,
+) {
+    if !session.is_started() {
+        return;
+    }
+
+    let mut connected_members = SmallVec::<[PeerId; 4]>::new();
+    let mut departed_links = SmallVec::<[(PeerId, Entity); 4]>::new();
+    for (entity, state, remote_id, connected, _, receiver) in &mut links {
+        if *state != P2P::Joined {
+            continue;
+        }
+        let Some(remote_id) = remote_id.map(|remote_id| remote_id.0) else {
+            continue;
+        };
+        if !session.is_member(remote_id) {
+            continue;
+        }
+        if let Some(mut receiver) = receiver {
+            for message in receiver.receive() {
+                tracing::trace!(?entity, ?remote_id, ?message, tick = ?timeline.tick(), "received P2P session message");
+                session.receive(remote_id, message);
+            }
+        }
+        if connected {
+            connected_members.push(remote_id);
+        } else {
+            departed_links.push((remote_id, entity));
+        }
+    }
+
+    // Members without a connected Link have left, including members whose Link was despawned.
+    let already_departed = |peer_id: &PeerId| {
+        session
+            .migration
+            .as_ref()
+            .is_some_and(|migration| migration.departed.contains(peer_id))
+    };
+    let newly_departed: SmallVec<[PeerId; 4]> = session
+        .members
+        .iter()
+        .filter(|peer_id| !connected_members.contains(peer_id) && !already_departed(peer_id))
+        .copied()
+        .collect();
+    if !newly_departed.is_empty() {
+        // Without an input frontier for a departed member, none of its inputs can be confirmed:
+        // fall back to the tick before the session start, whose state every member agrees on.
+        // The survivors still agree on the minimum of every proposal.
+        let no_input_tick = session
+            .start_tick()
+            .map_or(timeline.tick(), |tick| tick - 1);
+        let confirmed_tick = newly_departed
+            .iter()
+            .map(|peer_id| {
+                departed_links
+                    .iter()
+                    .find(|(departed, _)| departed == peer_id)
+                    .and_then(|(_, entity)| frontiers.get(*entity).ok())
+                    .map_or(no_input_tick, |frontier| frontier.0)
+            })
+            .min()
+            .unwrap_or(no_input_tick);
+        session.begin_migration(&newly_departed, confirmed_tick, real_time.elapsed());
+    }
+    if !session.is_migrating() {
+        return;
+    }
+
+    if let Some(migrated) = session.advance_migration() {
+        for (entity, state, remote_id, ..) in &mut links {
+            if *state == P2P::Joined
+                && remote_id.is_some_and(|remote_id| migrated.departed.contains(&remote_id.0))
+            {
+                commands.entity(entity).insert(P2P::Inactive);
+            }
+        }
+        tracing::info!(
+            departed = ?migrated.departed,
+            coordinator = ?migrated.coordinator,
+            confirmed_tick = ?migrated.confirmed_tick,
+            "P2P host migration complete"
+        );
+        commands.trigger(migrated);
+        return;
+    }
+
+    if session.migration_timed_out_at(real_time.elapsed()) {
+        let timeout = session.migration_timeout;
+        session.stop();
+        for (entity, state, ..) in &mut links {
+            if *state != P2P::Inactive {
+                commands.entity(entity).insert(P2P::Inactive);
+            }
+        }
+        tracing::warn!(?timeout, "P2P host migration timed out");
+        commands.trigger(P2PStopped);
+        return;
+    }
+
+    let Some(message) = session.collect_migration_outbound() else {
+        return;
+    };
+    let survivors = session
+        .migration
+        .as_ref()
+        .map(|migration| migration.survivors.clone())
+        .unwrap_or_default();
+    for (entity, state, remote_id, connected, sender, _) in &mut links {
+        if *state != P2P::Joined
+            || !connected
+            || !remote_id.is_some_and(|remote_id| survivors.contains(&remote_id.0))
+        {
+            continue;
+        }
+        let Some(mut sender) = sender else {
+            continue;
+        };
+        tracing::trace!(?entity, ?message, tick = ?timeline.tick(), "sending P2P session message");
+        sender.send::<P2PSessionChannel>(message);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -900,7 +1321,7 @@ mod tests {
         let second = peer(2);
         let remote_peers = SmallVec::from_slice(&[first, second]);
         let roster_hash = roster_hash(local, &remote_peers);
-        session.begin(remote_peers, roster_hash, Duration::ZERO);
+        session.begin(local, remote_peers, roster_hash, Duration::ZERO);
 
         assert_eq!(session.advance(Tick(5), false), AdvanceResult::Waiting);
         assert_eq!(session.advance(Tick(6), true), AdvanceResult::Waiting);
@@ -954,7 +1375,7 @@ mod tests {
         let remote_peers = SmallVec::from_slice(&[peer(1)]);
         let expected_roster_hash = roster_hash(local, &remote_peers);
         let mut session = P2PSession::default();
-        session.begin(remote_peers, expected_roster_hash, Duration::ZERO);
+        session.begin(local, remote_peers, expected_roster_hash, Duration::ZERO);
 
         session.receive(
             peer(1),
@@ -982,6 +1403,7 @@ mod tests {
         assert_eq!(P2PSession::default().start_timeout, Duration::from_secs(5));
         let mut session = P2PSession::default().with_start_timeout(Duration::from_secs(2));
         session.begin(
+            peer(0),
             remote_peers,
             roster_hash(peer(0), &[peer(1)]),
             Duration::from_secs(5),
@@ -991,6 +1413,117 @@ mod tests {
         assert!(session.timed_out_at(Duration::from_secs(7)));
     }
 
+    /// Build a session that already started with `local` and `remote_peers` as its members.
+    fn started_session(local: PeerId, remote_peers: &[PeerId]) -> P2PSession {
+        let mut session = P2PSession::default();
+        session.begin(
+            local,
+            SmallVec::from_slice(remote_peers),
+            roster_hash(local, remote_peers),
+            Duration::ZERO,
+        );
+        session.complete_start(Tick(10));
+        session
+    }
+
+    #[test]
+    fn the_smallest_peer_is_elected_coordinator() {
+        let session = started_session(peer(2), &[peer(3), peer(1)]);
+        assert_eq!(session.coordinator(), Some(peer(1)));
+        assert!(!session.is_coordinator());
+        assert_eq!(session.members(), &[peer(3), peer(1)]);
+
+        let session = started_session(peer(0), &[peer(1)]);
+        assert!(session.is_coordinator());
+    }
+
+    #[test]
+    fn survivors_agree_on_the_earliest_confirmed_tick() {
+        let mut session = started_session(peer(1), &[peer(0), peer(2), peer(3)]);
+        session.begin_migration(&[peer(0)], Tick(40), Duration::ZERO);
+        let survivor_hash = roster_hash(peer(1), &[peer(2), peer(3)]);
+        assert!(session.is_migrating());
+        assert_eq!(
+            session.collect_migration_outbound(),
+            Some(P2PSessionMessage::Migrate {
+                generation: session.generation,
+                roster_hash: survivor_hash,
+                confirmed_tick: Tick(40),
+            })
+        );
+        assert_eq!(session.collect_migration_outbound(), None);
+
+        session.receive(
+            peer(2),
+            P2PSessionMessage::Migrate {
+                generation: session.generation,
+                roster_hash: survivor_hash,
+                confirmed_tick: Tick(38),
+            },
+        );
+        assert_eq!(session.advance_migration(), None);
+        session.receive(
+            peer(3),
+            P2PSessionMessage::Migrate {
+                generation: session.generation,
+                roster_hash: survivor_hash,
+                confirmed_tick: Tick(41),
+            },
+        );
+
+        assert_eq!(
+            session.advance_migration(),
+            Some(P2PHostMigrated {
+                departed: SmallVec::from_slice(&[peer(0)]),
+                coordinator: peer(1),
+                confirmed_tick: Tick(38),
+            })
+        );
+        assert!(!session.is_migrating());
+        assert!(session.is_coordinator());
+        assert_eq!(session.members(), &[peer(2), peer(3)]);
+    }
+
+    #[test]
+    fn a_second_departure_restarts_the_migration() {
+        let mut session = started_session(peer(2), &[peer(0), peer(1), peer(3)]);
+        session.begin_migration(&[peer(0)], Tick(40), Duration::ZERO);
+        // peer 3 proposed for the roster that still contained peer 1
+        session.receive(
+            peer(3),
+            P2PSessionMessage::Migrate {
+                generation: session.generation,
+                roster_hash: roster_hash(peer(2), &[peer(1), peer(3)]),
+                confirmed_tick: Tick(39),
+            },
+        );
+        session.begin_migration(&[peer(1)], Tick(42), Duration::from_secs(1));
+        assert_eq!(session.advance_migration(), None);
+
+        session.receive(
+            peer(3),
+            P2PSessionMessage::Migrate {
+                generation: session.generation,
+                roster_hash: roster_hash(peer(2), &[peer(3)]),
+                confirmed_tick: Tick(39),
+            },
+        );
+        let migrated = session.advance_migration().unwrap();
+        assert_eq!(migrated.departed.as_slice(), &[peer(0), peer(1)]);
+        assert_eq!(migrated.coordinator, peer(2));
+        assert_eq!(migrated.confirmed_tick, Tick(39));
+    }
+
+    #[test]
+    fn a_migration_times_out() {
+        let mut session = started_session(peer(1), &[peer(0), peer(2)])
+            .with_migration_timeout(Duration::from_secs(2));
+        session.begin_migration(&[peer(0)], Tick(40), Duration::from_secs(5));
+
+        assert!(!session.migration_timed_out_at(Duration::from_secs(6)));
+        assert!(session.migration_timed_out_at(Duration::from_secs(7)));
+    }
+
     #[derive(Resource, Default)]
     struct WasStopped(bool);
 
@@ -1005,6 +1538,7 @@ mod tests {
         let link = app.world_mut().spawn(P2P::Joined).id();
         let mut session = P2PSession::default();
         session.begin(
+            peer(0),
             SmallVec::from_slice(&[peer(1)]),
             roster_hash(peer(0), &[peer(1)]),
             Duration::ZERO,
@@ -1039,6 +1573,7 @@ mod tests {
         let late_link = app.world_mut().spawn(P2P::Inactive).id();
         let mut session = P2PSession::default();
         session.begin(
+            peer(0),
             SmallVec::from_slice(&[peer(1)]),
             roster_hash(peer(0), &[peer(1)]),
             Duration::ZERO,
@@ -1,3 +1,4 @@
+use crate::WithoutHandshake;
 use aeronet_io::connection::LocalAddr;
 use bevy_app::{App, Plugin, PostUpdate, PreUpdate};
 use bevy_ecs::prelude::*;
@@ -22,10 +23,13 @@ pub struct RawConnectionPlugin;
 pub struct RawClient;
 
 impl RawConnectionPlugin {
-    /// For RawClients, Linked implies Connected
+    /// For RawClients, Linked implies Connected, unless the Link first needs to complete an encrypted handshake
     fn on_linked(
         trigger: On<Add, Linked>,
-        query: Query<(&LocalAddr, Option<&LocalId>, Option<&RemoteId>), With<RawClient>>,
+        query: Query<
+            (&LocalAddr, Option<&LocalId>, Option<&RemoteId>),
+            (With<RawClient>, WithoutHandshake),
+        >,
         mut commands: Commands,
     ) {
         if let Ok((local_addr, local_id, remote_id)) = query.get(trigger.entity) {
@@ -71,6 +75,10 @@ impl Plugin for RawConnectionPlugin {
         );
         app.add_observer(Self::on_linked);
         app.add_observer(Self::on_disconnect);
+        #[cfg(feature = "encryption")]
+        if !app.is_plugin_added::<crate::encryption::NoiseEncryptionPlugin>() {
+            app.add_plugins(crate::encryption::NoiseEncryptionPlugin);
+        }
     }
 }
 
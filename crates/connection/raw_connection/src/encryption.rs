@@ -0,0 +1,352 @@
+//! Optional authenticated encryption for raw connections.
+//!
+//! Without encryption, a raw connection sends its payloads in plaintext and identifies the remote
+//! peer by its `SocketAddr`. Adding [`NoiseEncryption`] on a [`RawClient`](crate::client::RawClient)
+//! or on a [`RawServer`](crate::server::RawServer) runs a `Noise_XX_25519_ChaChaPoly_BLAKE2s`
+//! handshake on every new [`Link`] before the connection is established:
+//! - both peers prove that they own the private key of their static [`NoiseKeypair`];
+//! - every payload sent on the link is then encrypted and authenticated with ChaCha20-Poly1305;
+//! - the connection only becomes [`Connected`] once the handshake is complete. On the server the
+//!   [`RemoteId`] of the client is [`PeerId::PublicKey`] with the verified static key of the client,
+//!   which stays the same if the client reconnects from a different address.
+//!
+//! Both peers must enable encryption. The handshake only operates on the payloads of the [`Link`],
+//! so it works with any IO layer (UDP, crossbeam channels, WebSocket, etc.).
+//!
+//! A client usually pins the public key of the server with [`NoiseEncryption::with_remote_key`],
+//! otherwise it accepts any server. A server can restrict which clients are allowed to connect with
+//! [`NoiseEncryption::with_trusted_keys`].
+//!
+//! ```rust,ignore
+//! // on the server
+//! let keypair = NoiseKeypair::from_secret(server_secret);
+//! commands.spawn((RawServer, NoiseEncryption::new(keypair), ServerUdpIo::default()));
+//!
+//! // on the client
+//! commands.spawn((
+//!     RawClient,
+//!     NoiseEncryption::new(NoiseKeypair::generate()).with_remote_key(server_public_key),
+//!     UdpIo::default(),
+//! ));
+//! ```
+use crate::noise::{self, NoiseError, Received, Session};
+use alloc::format;
+use alloc::vec::Vec;
+use bevy_app::{App, Plugin, PostUpdate, PreUpdate};
+use bevy_ecs::prelude::*;
+use core::fmt::{Debug, Formatter};
+use core::time::Duration;
+use lightyear_connection::ConnectionSystems;
+use lightyear_connection::client::Connected;
+use lightyear_connection::client_of::ClientOf;
+use lightyear_core::id::{LocalId, PeerId, RemoteId};
+use lightyear_core::time::Instant;
+use lightyear_link::prelude::{LinkOf, Server};
+use lightyear_link::{Link, LinkSystems, Linked, Unlink, UnlinkReason, Unlinked};
+use lightyear_transport::plugin::TransportSystems;
+#[allow(unused_imports)]
+use tracing::{debug, info, trace, warn};
+use x25519_dalek::StaticSecret;
+
+pub use crate::noise::{DATA_OVERHEAD, PublicKey};
+
+/// Static X25519 keypair that identifies a peer during the handshake.
+#[derive(Clone)]
+pub struct NoiseKeypair {
+    secret: StaticSecret,
+    public: PublicKey,
+}
+
+impl Debug for NoiseKeypair {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("NoiseKeypair")
+            .field("public", &self.public)
+            .finish_non_exhaustive()
+    }
+}
+
+impl NoiseKeypair {
+    /// Generates a new random keypair.
+    pub fn generate() -> Self {
+        Self::from_secret_key(noise::generate_secret())
+    }
+
+    /// Builds the keypair from a 32-byte secret key, for example loaded from disk.
+    pub fn from_secret(secret: [u8; 32]) -> Self {
+        Self::from_secret_key(StaticSecret::from(secret))
+    }
+
+    fn from_secret_key(secret: StaticSecret) -> Self {
+        let public = noise::public_key(&secret);
+        Self { secret, public }
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.public
+    }
+
+    /// Returns the secret key, so that it can be stored and the same identity used later.
+    pub fn secret_bytes(&self) -> [u8; 32] {
+        self.secret.to_bytes()
+    }
+}
+
+/// Enables the encrypted handshake on a [`RawClient`](crate::client::RawClient), or on a
+/// [`RawServer`](crate::server::RawServer) for all of its links.
+///
+/// See the [module-level documentation](self).
+#[derive(Component, Debug, Clone)]
+pub struct NoiseEncryption {
+    pub keypair: NoiseKeypair,
+    /// Data that both peers must agree on for the handshake to succeed, for example the name and
+    /// version of the game. It is authenticated but not sent.
+    pub prologue: Vec<u8>,
+    /// If set, the handshake fails if the remote peer's static key is not in this list.
+    pub trusted_keys: Option<Vec<PublicKey>>,
+    /// Interval at which the client sends its last handshake message again if the server didn't
+    /// answer it.
+    pub resend_interval: Duration,
+    /// The link is closed if the handshake is not complete after this duration.
+    pub handshake_timeout: Duration,
+}
+
+impl NoiseEncryption {
+    pub fn new(keypair: NoiseKeypair) -> Self {
+        Self {
+            keypair,
+            prologue: Vec::new(),
+            trusted_keys: None,
+            resend_interval: Duration::from_millis(250),
+            handshake_timeout: Duration::from_secs(5),
+        }
+    }
+
+    pub fn with_prologue(mut self, prologue: impl Into<Vec<u8>>) -> Self {
+        self.prologue = prologue.into();
+        self
+    }
+
+    pub fn with_trusted_keys(mut self, trusted_keys: impl IntoIterator<Item = PublicKey>) -> Self {
+        self.trusted_keys = Some(trusted_keys.into_iter().collect());
+        self
+    }
+
+    /// Only accept a remote peer with this static key. This is usually used by a client to
+    /// authenticate the server.
+    pub fn with_remote_key(self, remote_key: PublicKey) -> Self {
+        self.with_trusted_keys([remote_key])
+    }
+}
+
+/// Handshake and encryption state of a [`Link`], inserted when the link becomes [`Linked`].
+#[derive(Component)]
+pub struct NoiseSession {
+    session: Session,
+    started_at: Instant,
+    next_resend: Instant,
+    resend_interval: Duration,
+    handshake_timeout: Duration,
+}
+
+impl Debug for NoiseSession {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("NoiseSession")
+            .field("remote_key", &self.remote_key())
+            .field("established", &self.is_established())
+            .finish_non_exhaustive()
+    }
+}
+
+impl NoiseSession {
+    fn new(session: Session, config: &NoiseEncryption) -> Self {
+        let now = Instant::now();
+        Self {
+            session,
+            started_at: now,
+            next_resend: now + config.resend_interval,
+            resend_interval: config.resend_interval,
+            handshake_timeout: config.handshake_timeout,
+        }
+    }
+
+    /// Static key of the remote peer, verified by the handshake.
+    pub fn remote_key(&self) -> Option<PublicKey> {
+        self.session.remote_key()
+    }
+
+    /// Returns true if the handshake is complete and payloads can be exchanged.
+    pub fn is_established(&self) -> bool {
+        self.session.is_established()
+    }
+}
+
+pub struct NoiseEncryptionPlugin;
+
+impl NoiseEncryptionPlugin {
+    /// Start the handshake when a link is established: the client is the initiator, and a
+    /// [`LinkOf`] of a server is the responder.
+    fn start_handshake(
+        trigger: On<Add, Linked>,
+        client: Query<&NoiseEncryption, Without<Server>>,
+        link_of: Query<&LinkOf>,
+        server: Query<&NoiseEncryption, With<Server>>,
+        mut commands: Commands,
+    ) {
+        let entity = trigger.entity;
+        if let Ok(config) = client.get(entity) {
+            trace!(?entity, "starting noise handshake as initiator");
+            let session = Session::initiator(
+                config.keypair.secret.clone(),
+                config.prologue.clone(),
+                config.trusted_keys.clone(),
+            );
+            commands
+                .entity(entity)
+                .insert(NoiseSession::new(session, config));
+        } else if let Ok(link_of) = link_of.get(entity)
+            && let Ok(config) = server.get(link_of.server)
+        {
+            trace!(?entity, "starting noise handshake as responder");
+            let session = Session::responder(
+                config.keypair.secret.clone(),
+                config.prologue.clone(),
+                config.trusted_keys.clone(),
+            );
+            commands
+                .entity(entity)
+                .insert(NoiseSession::new(session, config));
+        }
+    }
+
+    fn end_session(trigger: On<Add, Unlinked>, mut commands: Commands) {
+        if let Ok(mut entity_commands) = commands.get_entity(trigger.entity) {
+            entity_commands.try_remove::<NoiseSession>();
+        }
+    }
+
+    /// Process the handshake packets and decrypt the payloads received on the link, before
+    /// they reach the transport.
+    fn receive(
+        mut query: Query<
+            (
+                Entity,
+                &mut Link,
+                &mut NoiseSession,
+                Option<&NoiseEncryption>,
+                Has<LinkOf>,
+            ),
+            With<Linked>,
+        >,
+        mut commands: Commands,
+    ) {
+        let now = Instant::now();
+        query
+            .iter_mut()
+            .for_each(|(entity, mut link, mut noise, config, is_link_of)| {
+                let packets = link.recv.drain().collect::<Vec<_>>();
+                for packet in packets {
+                    match noise.session.receive(packet) {
+                        Ok(Received::Payload(payload)) => link.recv.push_raw(payload),
+                        Ok(Received::Established(remote_key)) => {
+                            info!(?entity, "noise handshake complete");
+                            if is_link_of {
+                                commands.entity(entity).insert((
+                                    Connected,
+                                    LocalId(PeerId::Server),
+                                    RemoteId(PeerId::PublicKey(remote_key)),
+                                    ClientOf,
+                                ));
+                            } else if let Some(config) = config {
+                                commands.entity(entity).insert((
+                                    Connected,
+                                    LocalId(PeerId::PublicKey(config.keypair.public_key())),
+                                    RemoteId(PeerId::Server),
+                                ));
+                            }
+                        }
+                        Ok(Received::Handshake) => {}
+                        // a peer that could not be authenticated is disconnected
+                        Err(NoiseError::UntrustedKey) if !noise.is_established() => {
+                            warn!(?entity, "noise handshake failed: untrusted remote key");
+                            commands.trigger(Unlink {
+                                entity,
+                                reason: UnlinkReason::TransportError(format!(
+                                    "{}",
+                                    NoiseError::UntrustedKey
+                                )),
+                            });
+                            return;
+                        }
+                        // other invalid packets could be forged by anyone, so they are dropped
+                        Err(e) => {
+                            trace!(?entity, ?e, "dropping invalid packet");
+                        }
+                    }
+                }
+
+                if !noise.is_established()
+                    && now.saturating_duration_since(noise.started_at) > noise.handshake_timeout
+                {
+                    debug!(?entity, "noise handshake timed out");
+                    commands.trigger(Unlink {
+                        entity,
+                        reason: UnlinkReason::TransportError("noise handshake timed out".into()),
+                    });
+                    return;
+                }
+                // The client sends its last handshake message again until the server answers it,
+                // and keeps sending message 3 until the server sends data. The server only
+                // answers the messages it receives.
+                if now >= noise.next_resend {
+                    noise.next_resend = now + noise.resend_interval;
+                    noise.session.retransmit();
+                }
+            });
+    }
+
+    /// Send the handshake packets, and encrypt the payloads written by the transport.
+    ///
+    /// The handshake packets are sent first: the last handshake message must reach the remote peer
+    /// before the first encrypted payload, which it could not decrypt otherwise. Payloads written
+    /// before the handshake is complete are dropped, since the connection is not established yet.
+    fn send(mut query: Query<(&mut Link, &mut NoiseSession), With<Linked>>) {
+        query.iter_mut().for_each(|(mut link, mut noise)| {
+            let payloads = link.send.drain().collect::<Vec<_>>();
+            for packet in noise.session.outgoing.drain(..) {
+                link.send.push(packet);
+            }
+            for payload in payloads {
+                if let Some(packet) = noise.session.encrypt(&payload) {
+                    link.send.push(packet);
+                }
+            }
+        });
+    }
+}
+
+impl Plugin for NoiseEncryptionPlugin {
+    fn build(&self, app: &mut App) {
+        app.configure_sets(
+            PreUpdate,
+            (
+                LinkSystems::Receive,
+                ConnectionSystems::Receive,
+                TransportSystems::Receive,
+            )
+                .chain(),
+        );
+        app.configure_sets(
+            PostUpdate,
+            (
+                TransportSystems::Send,
+                ConnectionSystems::Send,
+                LinkSystems::Send,
+            )
+                .chain(),
+        );
+        app.add_observer(Self::start_handshake);
+        app.add_observer(Self::end_session);
+        app.add_systems(PreUpdate, Self::receive.in_set(ConnectionSystems::Receive));
+        app.add_systems(PostUpdate, Self::send.in_set(ConnectionSystems::Send));
+    }
+}
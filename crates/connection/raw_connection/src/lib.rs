@@ -7,6 +7,10 @@ the peers independently of the underlying Link.
 
 This crates provide a connection implementation where the establishing the Link is equivalent to establishing the Connection.
 The LocalId and RemoteId come from the Link's SocketAddr.
+
+With the `encryption` feature, the Link can first run an authenticated key exchange (see the
+`encryption` module). The payloads are then encrypted, and the RemoteId of a client is the static
+public key that it proved to own.
 */
 #![no_std]
 
@@ -21,14 +25,33 @@ pub mod client;
 #[cfg(feature = "server")]
 pub mod server;
 
+#[cfg(feature = "encryption")]
+pub mod encryption;
+#[cfg(feature = "encryption")]
+mod noise;
+
+/// Links with a [`NoiseEncryption`](encryption::NoiseEncryption) only become connected once
+/// their handshake is complete.
+#[cfg(all(feature = "encryption", any(feature = "client", feature = "server")))]
+type WithoutHandshake = bevy_ecs::query::Without<encryption::NoiseEncryption>;
+#[cfg(all(
+    not(feature = "encryption"),
+    any(feature = "client", feature = "server")
+))]
+type WithoutHandshake = ();
+
 pub mod prelude {
     #[cfg(feature = "client")]
     pub mod client {
         pub use crate::client::RawClient;
+        #[cfg(feature = "encryption")]
+        pub use crate::encryption::{NoiseEncryption, NoiseKeypair, NoiseSession};
     }
 
     #[cfg(feature = "server")]
     pub mod server {
+        #[cfg(feature = "encryption")]
+        pub use crate::encryption::{NoiseEncryption, NoiseKeypair, NoiseSession};
         pub use crate::server::RawServer;
     }
 }
@@ -0,0 +1,911 @@
+//! Implementation of the `Noise_XX_25519_ChaChaPoly_BLAKE2s` handshake from the
+//! [Noise Protocol Framework](https://noiseprotocol.org/noise.html), and of the encrypted
+//! datagrams exchanged once the handshake is complete.
+//!
+//! The XX pattern lets both peers transmit their static public key during the handshake:
+//! ```text
+//! -> e
+//! <- e, ee, s, es
+//! -> s, se
+//! ```
+//! At the end of the handshake each peer has proved that it owns the private key matching the
+//! static public key that it sent.
+//!
+//! Every packet starts with a one-byte [`PacketKind`]. Since links can lose or reorder packets,
+//! data packets carry their nonce explicitly instead of relying on an implicit counter, and the
+//! receiver rejects nonces that were already used.
+use alloc::vec::Vec;
+use blake2::{Blake2s256, Digest};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use chacha20poly1305::aead::{OsRng, rand_core::RngCore};
+use chacha20poly1305::{AeadInPlace, ChaCha20Poly1305, KeyInit, Tag};
+#[allow(unused_imports)]
+use tracing::{debug, trace};
+use x25519_dalek::StaticSecret;
+
+/// Length of a X25519 public key.
+pub const PUBLIC_KEY_BYTES: usize = 32;
+/// Length of the authentication tag appended to every encrypted payload.
+pub const TAG_BYTES: usize = 16;
+/// Bytes added to every payload sent on an established session: the packet kind, the nonce
+/// and the authentication tag.
+pub const DATA_OVERHEAD: usize = 1 + 8 + TAG_BYTES;
+
+const HASH_BYTES: usize = 32;
+const BLOCK_BYTES: usize = 64;
+const PROTOCOL_NAME: &[u8] = b"Noise_XX_25519_ChaChaPoly_BLAKE2s";
+const MESSAGE_2_BYTES: usize = PUBLIC_KEY_BYTES + PUBLIC_KEY_BYTES + TAG_BYTES + TAG_BYTES;
+const MESSAGE_3_BYTES: usize = PUBLIC_KEY_BYTES + TAG_BYTES + TAG_BYTES;
+/// Number of nonces before the most recent one that can still be received.
+const REPLAY_WINDOW: u64 = 64;
+
+/// Static X25519 public key identifying a peer.
+pub type PublicKey = [u8; PUBLIC_KEY_BYTES];
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseError {
+    #[error("the packet is empty or has an unknown kind")]
+    InvalidPacket,
+    #[error("the handshake message has an invalid length")]
+    InvalidLength,
+    #[error("the handshake message was not expected at this stage of the handshake")]
+    UnexpectedMessage,
+    #[error("the packet could not be decrypted")]
+    Decryption,
+    #[error("the packet nonce was already received or is too old")]
+    Replayed,
+    #[error("the remote peer sent a low-order public key")]
+    WeakKey,
+    #[error("the static key of the remote peer is not trusted")]
+    UntrustedKey,
+}
+
+pub type Result<T> = core::result::Result<T, NoiseError>;
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PacketKind {
+    Handshake1 = 0,
+    Handshake2 = 1,
+    Handshake3 = 2,
+    Data = 3,
+}
+
+impl TryFrom<u8> for PacketKind {
+    type Error = NoiseError;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(Self::Handshake1),
+            1 => Ok(Self::Handshake2),
+            2 => Ok(Self::Handshake3),
+            3 => Ok(Self::Data),
+            _ => Err(NoiseError::InvalidPacket),
+        }
+    }
+}
+
+/// Generates a new X25519 secret key from the OS random number generator.
+pub(crate) fn generate_secret() -> StaticSecret {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    StaticSecret::from(bytes)
+}
+
+pub(crate) fn public_key(secret: &StaticSecret) -> PublicKey {
+    x25519_dalek::PublicKey::from(secret).to_bytes()
+}
+
+fn dh(secret: &StaticSecret, public: &PublicKey) -> Result<[u8; 32]> {
+    let shared = secret.diffie_hellman(&x25519_dalek::PublicKey::from(*public));
+    if !shared.was_contributory() {
+        return Err(NoiseError::WeakKey);
+    }
+    Ok(shared.to_bytes())
+}
+
+fn hash(parts: &[&[u8]]) -> [u8; HASH_BYTES] {
+    let mut hasher = Blake2s256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+fn hmac(key: &[u8; HASH_BYTES], parts: &[&[u8]]) -> [u8; HASH_BYTES] {
+    let mut inner_pad = [0x36u8; BLOCK_BYTES];
+    let mut outer_pad = [0x5cu8; BLOCK_BYTES];
+    for (i, byte) in key.iter().enumerate() {
+        inner_pad[i] ^= byte;
+        outer_pad[i] ^= byte;
+    }
+    let mut inner = Blake2s256::new();
+    inner.update(inner_pad);
+    for part in parts {
+        inner.update(part);
+    }
+    let inner: [u8; HASH_BYTES] = inner.finalize().into();
+    hash(&[&outer_pad, &inner])
+}
+
+/// HKDF with two outputs, as defined by the Noise specification.
+fn hkdf(chaining_key: &[u8; HASH_BYTES], input: &[u8]) -> ([u8; HASH_BYTES], [u8; HASH_BYTES]) {
+    let temp_key = hmac(chaining_key, &[input]);
+    let output_1 = hmac(&temp_key, &[&[1]]);
+    let output_2 = hmac(&temp_key, &[&output_1, &[2]]);
+    (output_1, output_2)
+}
+
+/// ChaCha20-Poly1305 with the nonce encoding used by Noise: 4 zero bytes followed by the
+/// little-endian counter.
+#[derive(Clone)]
+pub(crate) struct CipherState {
+    cipher: ChaCha20Poly1305,
+    nonce: u64,
+}
+
+impl CipherState {
+    fn new(key: [u8; 32]) -> Self {
+        Self {
+            cipher: ChaCha20Poly1305::new(&key.into()),
+            nonce: 0,
+        }
+    }
+
+    fn nonce_bytes(nonce: u64) -> [u8; 12] {
+        let mut bytes = [0u8; 12];
+        bytes[4..].copy_from_slice(&nonce.to_le_bytes());
+        bytes
+    }
+
+    fn encrypt_in_place(&self, nonce: u64, associated_data: &[u8], buffer: &mut [u8]) -> Tag {
+        self.cipher
+            .encrypt_in_place_detached(&Self::nonce_bytes(nonce).into(), associated_data, buffer)
+            // encryption only fails if the buffer is larger than what ChaCha20 can handle
+            .expect("payload too large to be encrypted")
+    }
+
+    fn decrypt_in_place(
+        &self,
+        nonce: u64,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+        tag: &[u8],
+    ) -> Result<()> {
+        self.cipher
+            .decrypt_in_place_detached(
+                &Self::nonce_bytes(nonce).into(),
+                associated_data,
+                buffer,
+                Tag::from_slice(tag),
+            )
+            .map_err(|_| NoiseError::Decryption)
+    }
+
+    /// Encrypts `plaintext` with the next nonce, and appends the ciphertext to `out`.
+    fn encrypt(&mut self, associated_data: &[u8], plaintext: &[u8], out: &mut Vec<u8>) {
+        let start = out.len();
+        out.extend_from_slice(plaintext);
+        let tag = self.encrypt_in_place(self.nonce, associated_data, &mut out[start..]);
+        out.extend_from_slice(&tag);
+        self.nonce += 1;
+    }
+
+    fn decrypt(&mut self, associated_data: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let Some(len) = ciphertext.len().checked_sub(TAG_BYTES) else {
+            return Err(NoiseError::InvalidLength);
+        };
+        let mut plaintext = ciphertext[..len].to_vec();
+        self.decrypt_in_place(
+            self.nonce,
+            associated_data,
+            &mut plaintext,
+            &ciphertext[len..],
+        )?;
+        self.nonce += 1;
+        Ok(plaintext)
+    }
+}
+
+#[derive(Clone)]
+struct SymmetricState {
+    chaining_key: [u8; HASH_BYTES],
+    hash: [u8; HASH_BYTES],
+    cipher: Option<CipherState>,
+}
+
+impl SymmetricState {
+    fn new(prologue: &[u8]) -> Self {
+        // the protocol name is longer than HASH_BYTES, so it is hashed instead of padded
+        let hash = hash(&[PROTOCOL_NAME]);
+        let mut state = Self {
+            chaining_key: hash,
+            hash,
+            cipher: None,
+        };
+        state.mix_hash(prologue);
+        state
+    }
+
+    fn mix_hash(&mut self, data: &[u8]) {
+        self.hash = hash(&[&self.hash, data]);
+    }
+
+    fn mix_key(&mut self, input: &[u8]) {
+        let (chaining_key, key) = hkdf(&self.chaining_key, input);
+        self.chaining_key = chaining_key;
+        self.cipher = Some(CipherState::new(key));
+    }
+
+    fn encrypt_and_hash(&mut self, plaintext: &[u8], out: &mut Vec<u8>) {
+        let start = out.len();
+        match &mut self.cipher {
+            Some(cipher) => cipher.encrypt(&self.hash, plaintext, out),
+            None => out.extend_from_slice(plaintext),
+        }
+        let hash = hash(&[&self.hash, &out[start..]]);
+        self.hash = hash;
+    }
+
+    fn decrypt_and_hash(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let plaintext = match &mut self.cipher {
+            Some(cipher) => cipher.decrypt(&self.hash, ciphertext)?,
+            None => ciphertext.to_vec(),
+        };
+        self.mix_hash(ciphertext);
+        Ok(plaintext)
+    }
+
+    fn split(&self) -> (CipherState, CipherState) {
+        let (key_1, key_2) = hkdf(&self.chaining_key, &[]);
+        (CipherState::new(key_1), CipherState::new(key_2))
+    }
+}
+
+/// State of one side of the XX handshake.
+#[derive(Clone)]
+pub(crate) struct HandshakeState {
+    initiator: bool,
+    symmetric: SymmetricState,
+    local_static: StaticSecret,
+    local_ephemeral: StaticSecret,
+    remote_ephemeral: Option<PublicKey>,
+}
+
+impl HandshakeState {
+    pub(crate) fn new(initiator: bool, local_static: StaticSecret, prologue: &[u8]) -> Self {
+        Self {
+            initiator,
+            symmetric: SymmetricState::new(prologue),
+            local_static,
+            local_ephemeral: generate_secret(),
+            remote_ephemeral: None,
+        }
+    }
+
+    /// `-> e`
+    pub(crate) fn write_message_1(&mut self) -> Vec<u8> {
+        let ephemeral = public_key(&self.local_ephemeral);
+        let mut out = ephemeral.to_vec();
+        self.symmetric.mix_hash(&ephemeral);
+        self.symmetric.encrypt_and_hash(&[], &mut out);
+        out
+    }
+
+    /// `-> e`, read by the responder.
+    pub(crate) fn read_message_1(&mut self, message: &[u8]) -> Result<()> {
+        if message.len() != PUBLIC_KEY_BYTES {
+            return Err(NoiseError::InvalidLength);
+        }
+        let remote_ephemeral: PublicKey = message.try_into().unwrap();
+        self.symmetric.mix_hash(&remote_ephemeral);
+        self.symmetric.decrypt_and_hash(&[])?;
+        self.remote_ephemeral = Some(remote_ephemeral);
+        Ok(())
+    }
+
+    /// `<- e, ee, s, es`
+    pub(crate) fn write_message_2(&mut self) -> Result<Vec<u8>> {
+        let remote_ephemeral = self.remote_ephemeral.ok_or(NoiseError::UnexpectedMessage)?;
+        let ephemeral = public_key(&self.local_ephemeral);
+        let mut out = ephemeral.to_vec();
+        self.symmetric.mix_hash(&ephemeral);
+        self.symmetric
+            .mix_key(&dh(&self.local_ephemeral, &remote_ephemeral)?);
+        self.symmetric
+            .encrypt_and_hash(&public_key(&self.local_static), &mut out);
+        self.symmetric
+            .mix_key(&dh(&self.local_static, &remote_ephemeral)?);
+        self.symmetric.encrypt_and_hash(&[], &mut out);
+        Ok(out)
+    }
+
+    /// `<- e, ee, s, es`, read by the initiator. Returns the static key of the responder.
+    pub(crate) fn read_message_2(&mut self, message: &[u8]) -> Result<PublicKey> {
+        if message.len() != MESSAGE_2_BYTES {
+            return Err(NoiseError::InvalidLength);
+        }
+        let (remote_ephemeral, rest) = message.split_at(PUBLIC_KEY_BYTES);
+        let remote_ephemeral: PublicKey = remote_ephemeral.try_into().unwrap();
+        self.symmetric.mix_hash(&remote_ephemeral);
+        self.symmetric
+            .mix_key(&dh(&self.local_ephemeral, &remote_ephemeral)?);
+        let (remote_static, payload) = rest.split_at(PUBLIC_KEY_BYTES + TAG_BYTES);
+        let remote_static: PublicKey = self
+            .symmetric
+            .decrypt_and_hash(remote_static)?
+            .try_into()
+            .map_err(|_| NoiseError::InvalidLength)?;
+        self.symmetric
+            .mix_key(&dh(&self.local_ephemeral, &remote_static)?);
+        self.symmetric.decrypt_and_hash(payload)?;
+        self.remote_ephemeral = Some(remote_ephemeral);
+        Ok(remote_static)
+    }
+
+    /// `-> s, se`
+    pub(crate) fn write_message_3(&mut self) -> Result<Vec<u8>> {
+        let remote_ephemeral = self.remote_ephemeral.ok_or(NoiseError::UnexpectedMessage)?;
+        let mut out = Vec::with_capacity(MESSAGE_3_BYTES);
+        self.symmetric
+            .encrypt_and_hash(&public_key(&self.local_static), &mut out);
+        self.symmetric
+            .mix_key(&dh(&self.local_static, &remote_ephemeral)?);
+        self.symmetric.encrypt_and_hash(&[], &mut out);
+        Ok(out)
+    }
+
+    /// `-> s, se`, read by the responder. Returns the static key of the initiator.
+    pub(crate) fn read_message_3(&mut self, message: &[u8]) -> Result<PublicKey> {
+        if message.len() != MESSAGE_3_BYTES {
+            return Err(NoiseError::InvalidLength);
+        }
+        let (remote_static, payload) = message.split_at(PUBLIC_KEY_BYTES + TAG_BYTES);
+        let remote_static: PublicKey = self
+            .symmetric
+            .decrypt_and_hash(remote_static)?
+            .try_into()
+            .map_err(|_| NoiseError::InvalidLength)?;
+        self.symmetric
+            .mix_key(&dh(&self.local_ephemeral, &remote_static)?);
+        self.symmetric.decrypt_and_hash(payload)?;
+        Ok(remote_static)
+    }
+
+    /// Derives the ciphers used to encrypt the packets once the handshake is complete.
+    pub(crate) fn split(self) -> TransportState {
+        let (initiator_to_responder, responder_to_initiator) = self.symmetric.split();
+        let (send, recv) = if self.initiator {
+            (initiator_to_responder, responder_to_initiator)
+        } else {
+            (responder_to_initiator, initiator_to_responder)
+        };
+        TransportState {
+            send,
+            recv,
+            replay: ReplayWindow::default(),
+        }
+    }
+}
+
+/// Tracks the nonces that were received, to reject replayed packets.
+#[derive(Default)]
+struct ReplayWindow {
+    /// Most recent nonce received, if any.
+    latest: Option<u64>,
+    /// Bit `i` is set if nonce `latest - i` was received.
+    received: u64,
+}
+
+impl ReplayWindow {
+    fn check(&self, nonce: u64) -> Result<()> {
+        let Some(latest) = self.latest else {
+            return Ok(());
+        };
+        if nonce > latest {
+            return Ok(());
+        }
+        let age = latest - nonce;
+        if age >= REPLAY_WINDOW || self.received & (1 << age) != 0 {
+            return Err(NoiseError::Replayed);
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, nonce: u64) {
+        match self.latest {
+            Some(latest) if nonce <= latest => {
+                self.received |= 1 << (latest - nonce);
+            }
+            Some(latest) => {
+                let shift = nonce - latest;
+                self.received = if shift >= REPLAY_WINDOW {
+                    0
+                } else {
+                    self.received << shift
+                } | 1;
+                self.latest = Some(nonce);
+            }
+            None => {
+                self.received = 1;
+                self.latest = Some(nonce);
+            }
+        }
+    }
+}
+
+/// Ciphers of an established session.
+pub(crate) struct TransportState {
+    send: CipherState,
+    recv: CipherState,
+    replay: ReplayWindow,
+}
+
+impl TransportState {
+    /// Builds a data packet containing the encrypted `payload`.
+    pub(crate) fn encrypt(&mut self, payload: &[u8]) -> Bytes {
+        let nonce = self.send.nonce;
+        self.send.nonce += 1;
+        let mut packet = BytesMut::with_capacity(payload.len() + DATA_OVERHEAD);
+        packet.put_u8(PacketKind::Data as u8);
+        packet.put_u64_le(nonce);
+        packet.extend_from_slice(payload);
+        let (header, body) = packet.split_at_mut(9);
+        let tag = self.send.encrypt_in_place(nonce, header, body);
+        packet.extend_from_slice(&tag);
+        packet.freeze()
+    }
+
+    /// Decrypts a data packet in place, and returns the payload.
+    pub(crate) fn decrypt(&mut self, mut packet: BytesMut) -> Result<BytesMut> {
+        if packet.len() < DATA_OVERHEAD {
+            return Err(NoiseError::InvalidLength);
+        }
+        let header: [u8; 9] = packet[..9].try_into().unwrap();
+        let nonce = (&header[1..]).get_u64_le();
+        self.replay.check(nonce)?;
+        packet.advance(9);
+        let tag = packet.split_off(packet.len() - TAG_BYTES);
+        self.recv
+            .decrypt_in_place(nonce, &header, &mut packet, &tag)?;
+        self.replay.insert(nonce);
+        Ok(packet)
+    }
+}
+
+/// Result of processing a packet received on a [`Session`].
+#[derive(Debug)]
+pub(crate) enum Received {
+    /// A decrypted payload for the layers above.
+    Payload(BytesMut),
+    /// The handshake completed and the remote peer owns this static key.
+    Established(PublicKey),
+    /// The packet was a handshake packet that did not complete the handshake.
+    Handshake,
+}
+
+/// Handshake and encryption state for one link.
+///
+/// Handshake packets can be lost, so the initiator retransmits its last handshake message until
+/// the responder answers: message 1 until message 2 is received, then message 3 until the first
+/// data packet of the responder proves that message 3 arrived. The responder only answers the
+/// messages it receives.
+pub(crate) struct Session {
+    initiator: bool,
+    local_static: StaticSecret,
+    prologue: Vec<u8>,
+    trusted_keys: Option<Vec<PublicKey>>,
+    handshake: Option<HandshakeState>,
+    transport: Option<TransportState>,
+    remote_key: Option<PublicKey>,
+    /// Ephemeral key of the last message 1 received by the responder, to detect retransmissions.
+    remote_ephemeral: Option<PublicKey>,
+    /// Last handshake message sent by the initiator, and sent again by [`Session::retransmit`].
+    retransmit: Option<Bytes>,
+    /// Message 2 sent by the responder, sent again only if message 1 is received again before the
+    /// handshake completes.
+    message_2: Option<Bytes>,
+    /// Handshake packets waiting to be sent on the link.
+    pub(crate) outgoing: Vec<Bytes>,
+}
+
+impl Session {
+    pub(crate) fn initiator(
+        local_static: StaticSecret,
+        prologue: Vec<u8>,
+        trusted_keys: Option<Vec<PublicKey>>,
+    ) -> Self {
+        let mut handshake = HandshakeState::new(true, local_static.clone(), &prologue);
+        let message = frame(PacketKind::Handshake1, &handshake.write_message_1());
+        Self {
+            initiator: true,
+            local_static,
+            prologue,
+            trusted_keys,
+            handshake: Some(handshake),
+            transport: None,
+            remote_key: None,
+            remote_ephemeral: None,
+            retransmit: Some(message.clone()),
+            message_2: None,
+            outgoing: alloc::vec![message],
+        }
+    }
+
+    pub(crate) fn responder(
+        local_static: StaticSecret,
+        prologue: Vec<u8>,
+        trusted_keys: Option<Vec<PublicKey>>,
+    ) -> Self {
+        Self {
+            initiator: false,
+            local_static,
+            prologue,
+            trusted_keys,
+            handshake: None,
+            transport: None,
+            remote_key: None,
+            remote_ephemeral: None,
+            retransmit: None,
+            message_2: None,
+            outgoing: Vec::new(),
+        }
+    }
+
+    pub(crate) fn is_established(&self) -> bool {
+        self.transport.is_some()
+    }
+
+    pub(crate) fn remote_key(&self) -> Option<PublicKey> {
+        self.remote_key
+    }
+
+    /// Queues the last handshake message of the initiator again, if the responder did not answer
+    /// it yet. The responder never retransmits on its own.
+    pub(crate) fn retransmit(&mut self) {
+        if let Some(message) = &self.retransmit {
+            self.outgoing.push(message.clone());
+        }
+    }
+
+    /// Encrypts a payload, or returns `None` if the handshake is not complete yet.
+    pub(crate) fn encrypt(&mut self, payload: &[u8]) -> Option<Bytes> {
+        self.transport
+            .as_mut()
+            .map(|transport| transport.encrypt(payload))
+    }
+
+    pub(crate) fn receive(&mut self, mut packet: BytesMut) -> Result<Received> {
+        if packet.is_empty() {
+            return Err(NoiseError::InvalidPacket);
+        }
+        let kind = PacketKind::try_from(packet[0])?;
+        match (kind, self.initiator) {
+            (PacketKind::Data, _) => {
+                let transport = self
+                    .transport
+                    .as_mut()
+                    .ok_or(NoiseError::UnexpectedMessage)?;
+                let payload = transport.decrypt(packet)?;
+                if self.initiator {
+                    // the responder can only send data packets after receiving message 3
+                    self.retransmit = None;
+                }
+                Ok(Received::Payload(payload))
+            }
+            (PacketKind::Handshake1, false) => {
+                packet.advance(1);
+                self.read_message_1(&packet)?;
+                Ok(Received::Handshake)
+            }
+            (PacketKind::Handshake2, true) => {
+                packet.advance(1);
+                self.read_message_2(&packet)
+            }
+            (PacketKind::Handshake3, false) => {
+                packet.advance(1);
+                self.read_message_3(&packet)
+            }
+            _ => Err(NoiseError::UnexpectedMessage),
+        }
+    }
+
+    fn read_message_1(&mut self, message: &[u8]) -> Result<()> {
+        // the initiator retransmits message 1 if our message 2 was lost
+        if self.remote_ephemeral.as_ref().map(|key| key.as_slice()) == Some(message) {
+            if self.handshake.is_some()
+                && let Some(message_2) = &self.message_2
+            {
+                self.outgoing.push(message_2.clone());
+            }
+            return Ok(());
+        }
+        // A new handshake from the same link. If a session is already established, it is kept
+        // until the new handshake completes, so that a spoofed message 1 cannot break it.
+        let mut handshake = HandshakeState::new(false, self.local_static.clone(), &self.prologue);
+        handshake.read_message_1(message)?;
+        let message = frame(PacketKind::Handshake2, &handshake.write_message_2()?);
+        self.outgoing.push(message.clone());
+        self.message_2 = Some(message);
+        self.remote_ephemeral = handshake.remote_ephemeral;
+        self.handshake = Some(handshake);
+        Ok(())
+    }
+
+    fn read_message_2(&mut self, message: &[u8]) -> Result<Received> {
+        let Some(handshake) = &self.handshake else {
+            // message 2 was retransmitted by the responder because our message 3 was lost
+            self.retransmit();
+            return Ok(Received::Handshake);
+        };
+        // the handshake state is only advanced by valid messages, so that a forged packet
+        // cannot interrupt the handshake
+        let mut handshake = handshake.clone();
+        let remote_key = handshake.read_message_2(message)?;
+        self.check_trusted(&remote_key)?;
+        let message = frame(PacketKind::Handshake3, &handshake.write_message_3()?);
+        self.outgoing.push(message.clone());
+        self.retransmit = Some(message);
+        self.handshake = None;
+        self.transport = Some(handshake.split());
+        self.remote_key = Some(remote_key);
+        debug!("noise handshake complete");
+        Ok(Received::Established(remote_key))
+    }
+
+    fn read_message_3(&mut self, message: &[u8]) -> Result<Received> {
+        let Some(handshake) = &self.handshake else {
+            return Err(NoiseError::UnexpectedMessage);
+        };
+        let mut handshake = handshake.clone();
+        let remote_key = handshake.read_message_3(message)?;
+        self.check_trusted(&remote_key)?;
+        // a session that is already established can only be replaced by the same peer
+        if self
+            .remote_key
+            .is_some_and(|established_key| established_key != remote_key)
+        {
+            return Err(NoiseError::UntrustedKey);
+        }
+        self.message_2 = None;
+        self.handshake = None;
+        self.transport = Some(handshake.split());
+        self.remote_key = Some(remote_key);
+        debug!("noise handshake complete");
+        Ok(Received::Established(remote_key))
+    }
+
+    fn check_trusted(&self, remote_key: &PublicKey) -> Result<()> {
+        match &self.trusted_keys {
+            Some(trusted_keys) if !trusted_keys.contains(remote_key) => {
+                Err(NoiseError::UntrustedKey)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn frame(kind: PacketKind, message: &[u8]) -> Bytes {
+    let mut packet = BytesMut::with_capacity(1 + message.len());
+    packet.put_u8(kind as u8);
+    packet.extend_from_slice(message);
+    packet.freeze()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Delivers every packet queued by `from` to `to`, and returns the results.
+    fn deliver(from: &mut Session, to: &mut Session) -> Vec<Result<Received>> {
+        from.outgoing
+            .drain(..)
+            .map(|packet| to.receive(BytesMut::from(packet)))
+            .collect()
+    }
+
+    fn sessions() -> (Session, Session, PublicKey, PublicKey) {
+        let client_key = generate_secret();
+        let server_key = generate_secret();
+        let client_public = public_key(&client_key);
+        let server_public = public_key(&server_key);
+        (
+            Session::initiator(
+                client_key,
+                b"test".to_vec(),
+                Some(alloc::vec![server_public]),
+            ),
+            Session::responder(server_key, b"test".to_vec(), None),
+            client_public,
+            server_public,
+        )
+    }
+
+    #[test]
+    fn handshake_and_data_roundtrip() {
+        let (mut client, mut server, client_public, server_public) = sessions();
+        deliver(&mut client, &mut server);
+        let received = deliver(&mut server, &mut client);
+        assert!(matches!(received[..], [Ok(Received::Established(key))] if key == server_public));
+        let received = deliver(&mut client, &mut server);
+        assert!(matches!(received[..], [Ok(Received::Established(key))] if key == client_public));
+
+        let packet = client.encrypt(b"hello").unwrap();
+        assert_eq!(packet.len(), 5 + DATA_OVERHEAD);
+        assert!(!packet.windows(5).any(|window| window == b"hello"));
+        let Received::Payload(payload) = server.receive(BytesMut::from(packet.clone())).unwrap()
+        else {
+            panic!("expected a payload");
+        };
+        assert_eq!(&payload[..], b"hello");
+        // a replayed packet is rejected
+        assert_eq!(
+            server.receive(BytesMut::from(packet)).unwrap_err(),
+            NoiseError::Replayed
+        );
+
+        let packet = server.encrypt(b"world").unwrap();
+        let Received::Payload(payload) = client.receive(BytesMut::from(packet)).unwrap() else {
+            panic!("expected a payload");
+        };
+        assert_eq!(&payload[..], b"world");
+        // the responder's data packet confirms that message 3 was received
+        client.retransmit();
+        assert!(client.outgoing.is_empty());
+    }
+
+    #[test]
+    fn lost_handshake_messages_are_retransmitted() {
+        let (mut client, mut server, _, _) = sessions();
+        deliver(&mut client, &mut server);
+        // message 2 is lost
+        server.outgoing.clear();
+        client.retransmit();
+        deliver(&mut client, &mut server);
+        deliver(&mut server, &mut client);
+        // message 3 is lost
+        client.outgoing.clear();
+        assert!(server.encrypt(b"data").is_none());
+        client.retransmit();
+        let received = deliver(&mut client, &mut server);
+        assert!(matches!(received[..], [Ok(Received::Established(_))]));
+        assert!(server.is_established());
+    }
+
+    #[test]
+    fn responder_only_answers_duplicate_message_1() {
+        let (mut client, mut server, _, _) = sessions();
+        let message_1 = client.outgoing[0].clone();
+        deliver(&mut client, &mut server);
+        assert_eq!(server.outgoing.len(), 1);
+        let message_2 = server.outgoing.remove(0);
+
+        // the responder does not retransmit on its own
+        server.retransmit();
+        assert!(server.outgoing.is_empty());
+
+        // but it answers a retransmitted message 1 with the same message 2
+        assert!(matches!(
+            server.receive(BytesMut::from(message_1.clone())),
+            Ok(Received::Handshake)
+        ));
+        assert_eq!(server.outgoing, alloc::vec![message_2.clone()]);
+
+        client.receive(BytesMut::from(message_2)).unwrap();
+        deliver(&mut client, &mut server);
+        assert!(server.is_established());
+        server.outgoing.clear();
+
+        // once the handshake is complete, a duplicate message 1 is not answered anymore
+        server.receive(BytesMut::from(message_1)).unwrap();
+        server.retransmit();
+        assert!(server.outgoing.is_empty());
+    }
+
+    const SNOW_PARAMS: &str = "Noise_XX_25519_ChaChaPoly_BLAKE2s";
+
+    /// Our initiator completes a handshake with the `snow` reference implementation, and both
+    /// derive the same transport keys.
+    #[test]
+    fn initiator_interoperates_with_snow() {
+        let client_key = generate_secret();
+        let server_key = generate_secret();
+        let mut buffer = [0u8; 1024];
+        let mut payload = [0u8; 1024];
+        let mut client = HandshakeState::new(true, client_key.clone(), b"test");
+        let mut server = snow::Builder::new(SNOW_PARAMS.parse().unwrap())
+            .local_private_key(&server_key.to_bytes())
+            .prologue(b"test")
+            .build_responder()
+            .unwrap();
+
+        server
+            .read_message(&client.write_message_1(), &mut payload)
+            .unwrap();
+        let len = server.write_message(&[], &mut buffer).unwrap();
+        assert_eq!(
+            client.read_message_2(&buffer[..len]).unwrap(),
+            public_key(&server_key)
+        );
+        server
+            .read_message(&client.write_message_3().unwrap(), &mut payload)
+            .unwrap();
+        assert_eq!(
+            server.get_remote_static(),
+            Some(&public_key(&client_key)[..])
+        );
+
+        // Noise transport messages use the implicit counter as nonce, without associated data
+        let mut client = client.split();
+        let mut server = server.into_transport_mode().unwrap();
+        let mut ciphertext = Vec::new();
+        client.send.encrypt(&[], b"hello", &mut ciphertext);
+        let len = server.read_message(&ciphertext, &mut payload).unwrap();
+        assert_eq!(&payload[..len], b"hello");
+        let len = server.write_message(b"world", &mut buffer).unwrap();
+        assert_eq!(client.recv.decrypt(&[], &buffer[..len]).unwrap(), b"world");
+    }
+
+    /// Our responder completes a handshake with the `snow` reference implementation, and both
+    /// derive the same transport keys.
+    #[test]
+    fn responder_interoperates_with_snow() {
+        let client_key = generate_secret();
+        let server_key = generate_secret();
+        let mut buffer = [0u8; 1024];
+        let mut payload = [0u8; 1024];
+        let mut client = snow::Builder::new(SNOW_PARAMS.parse().unwrap())
+            .local_private_key(&client_key.to_bytes())
+            .prologue(b"test")
+            .build_initiator()
+            .unwrap();
+        let mut server = HandshakeState::new(false, server_key.clone(), b"test");
+
+        let len = client.write_message(&[], &mut buffer).unwrap();
+        server.read_message_1(&buffer[..len]).unwrap();
+        client
+            .read_message(&server.write_message_2().unwrap(), &mut payload)
+            .unwrap();
+        assert_eq!(
+            client.get_remote_static(),
+            Some(&public_key(&server_key)[..])
+        );
+        let len = client.write_message(&[], &mut buffer).unwrap();
+        assert_eq!(
+            server.read_message_3(&buffer[..len]).unwrap(),
+            public_key(&client_key)
+        );
+
+        let mut server = server.split();
+        let mut client = client.into_transport_mode().unwrap();
+        let len = client.write_message(b"hello", &mut buffer).unwrap();
+        assert_eq!(server.recv.decrypt(&[], &buffer[..len]).unwrap(), b"hello");
+        let mut ciphertext = Vec::new();
+        server.send.encrypt(&[], b"world", &mut ciphertext);
+        let len = client.read_message(&ciphertext, &mut payload).unwrap();
+        assert_eq!(&payload[..len], b"world");
+    }
+
+    #[test]
+    fn untrusted_server_key_is_rejected() {
+        let (mut client, _, _, _) = sessions();
+        let mut impostor = Session::responder(generate_secret(), b"test".to_vec(), None);
+        deliver(&mut client, &mut impostor);
+        let received = deliver(&mut impostor, &mut client);
+        assert!(matches!(received[..], [Err(NoiseError::UntrustedKey)]));
+        assert!(!client.is_established());
+    }
+
+    #[test]
+    fn tampered_packet_is_rejected() {
+        let (mut client, mut server, _, _) = sessions();
+        deliver(&mut client, &mut server);
+        deliver(&mut server, &mut client);
+        deliver(&mut client, &mut server);
+
+        let mut packet = BytesMut::from(client.encrypt(b"hello").unwrap());
+        let last = packet.len() - 1;
+        packet[last] ^= 1;
+        assert_eq!(server.receive(packet).unwrap_err(), NoiseError::Decryption);
+    }
+}
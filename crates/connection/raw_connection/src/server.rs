@@ -1,3 +1,4 @@
+use crate::WithoutHandshake;
 use aeronet_io::connection::PeerAddr;
 use bevy_app::{App, Plugin, PostUpdate, PreUpdate};
 use bevy_ecs::entity::UniqueEntitySlice;
@@ -39,11 +40,12 @@ impl RawConnectionPlugin {
         }
     }
 
-    /// For RawServers, when a LinkOf gets Linked, it also becomes Connected
+    /// For RawServers, when a LinkOf gets Linked, it also becomes Connected, unless the Link first
+    /// needs to complete an encrypted handshake
     fn on_link_of_linked(
         trigger: On<Add, Linked>,
         link_of: Query<(&LinkOf, &PeerAddr)>,
-        server: Query<(), With<RawServer>>,
+        server: Query<(), (With<RawServer>, WithoutHandshake)>,
         mut commands: Commands,
     ) {
         if let Ok((link_of, peer_addr)) = link_of.get(trigger.entity)
@@ -78,8 +80,8 @@ impl RawConnectionPlugin {
                 unsafe { UniqueEntitySlice::from_slice_unchecked(server.collection()) };
             link_query.iter_many_unique(unique_slice).try_for_each(
                 |(entity, remote_peer_id)| {
-                    let PeerId::Raw(_) = remote_peer_id.0 else {
-                        error!("Client {:?} is not a Netcode client", remote_peer_id);
+                    let (PeerId::Raw(_) | PeerId::PublicKey(_)) = remote_peer_id.0 else {
+                        error!("Client {:?} is not a Raw client", remote_peer_id);
                         return Err(
                             lightyear_connection::server::ConnectionError::InvalidConnectionType,
                         );
@@ -114,6 +116,10 @@ impl Plugin for RawConnectionPlugin {
         app.add_observer(Self::on_server_linked);
         app.add_observer(Self::on_link_of_linked);
         app.add_observer(Self::on_stop);
+        #[cfg(feature = "encryption")]
+        if !app.is_plugin_added::<crate::encryption::NoiseEncryptionPlugin>() {
+            app.add_plugins(crate::encryption::NoiseEncryptionPlugin);
+        }
     }
 }
 
@@ -14,7 +14,7 @@ use lightyear_connection::client::{Connected, Disconnected, DisconnectedReason};
 use lightyear_connection::client_of::{ClientOf, SkipNetcode};
 use lightyear_connection::server::{Start, Started, Stop};
 use lightyear_core::id::{PeerId, RemoteId};
-use lightyear_link::prelude::LinkOf;
+use lightyear_link::prelude::{LinkOf, ListenerOf};
 use lightyear_link::server::Server;
 use lightyear_link::{Link, LinkStart, Linked, Linking, UnlinkReason};
 use tracing::{info, trace};
@@ -50,8 +50,11 @@ impl Plugin for SteamServerPlugin {
 /// [`SessionRequest`]. Your app **must** observe this, and use
 /// [`SessionRequest::respond`] to set how the server should respond to this
 /// connection attempt.
+///
+/// It can also be inserted on a listener entity with [`ListenerOf`], to accept Steam clients next
+/// to other transports.
 #[derive(Debug, Component)]
-#[require(Server)]
+#[component(on_add = Server::on_add_transport)]
 pub struct SteamServerIo {
     pub target: ListenTarget,
     pub config: SessionConfig,
@@ -81,10 +84,12 @@ impl SteamServerPlugin {
         Ok(())
     }
 
-    /// Steam is both a Link and a Connection, so we add Started when Linked is added
+    /// Steam is both a Link and a Connection, so we add Started when Linked is added.
+    ///
+    /// A listener is not started itself: its server is started by the connection layer.
     fn on_linked(
         trigger: On<Add, Linked>,
-        query: Query<(), With<SteamServerIo>>,
+        query: Query<(), (With<SteamServerIo>, Without<ListenerOf>)>,
         mut commands: Commands,
     ) {
         if query.get(trigger.entity).is_ok() {
@@ -0,0 +1,364 @@
+//! Minimal HTTP/1.1 frontend for the [`TokenIssuer`].
+//!
+//! The service answers a single route:
+//!
+//! ```text
+//! POST /token?region=eu HTTP/1.1
+//! Authorization: Bearer <credentials>
+//! ```
+//!
+//! On success the response has the status `200 OK`, and its body is the serialized
+//! [`ConnectToken`](lightyear_netcode::ConnectToken) (`application/octet-stream`, always
+//! [`CONNECT_TOKEN_BYTES`](lightyear_netcode::CONNECT_TOKEN_BYTES) long). The `X-Client-Id`,
+//! `X-Server` and `X-Expire-Timestamp` headers contain the other fields of the [`IssuedToken`].
+//!
+//! The errors are mapped to the status codes:
+//! - `401 Unauthorized` and `403 Forbidden` if the [`CredentialVerifier`] rejected the request;
+//! - `503 Service Unavailable` if every game server is full or the verifier is unavailable.
+//!
+//! The service does not terminate TLS: the credentials must be protected by running it behind a
+//! reverse proxy that handles HTTPS.
+use crate::issuer::{IssueError, IssuedToken, TokenIssuer};
+use crate::verifier::{CredentialVerifier, TokenRequest, VerifyError};
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::time::Duration;
+use std::io;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Semaphore;
+#[allow(unused_imports)]
+use tracing::{debug, info, trace, warn};
+
+/// Maximum size of the request line and headers.
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+/// Connections that don't send a complete request within this duration are closed.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+/// Delay before accepting connections again after the first failure. It doubles after each
+/// consecutive failure, up to [`MAX_ACCEPT_BACKOFF`].
+const MIN_ACCEPT_BACKOFF: Duration = Duration::from_millis(10);
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+/// Default number of connections that [`serve`] handles at the same time.
+pub const DEFAULT_MAX_CONNECTIONS: usize = 1024;
+
+/// Status code and reason phrase of a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Status(pub u16, pub &'static str);
+
+impl Status {
+    pub const OK: Self = Self(200, "OK");
+    pub const BAD_REQUEST: Self = Self(400, "Bad Request");
+    pub const UNAUTHORIZED: Self = Self(401, "Unauthorized");
+    pub const FORBIDDEN: Self = Self(403, "Forbidden");
+    pub const NOT_FOUND: Self = Self(404, "Not Found");
+    pub const METHOD_NOT_ALLOWED: Self = Self(405, "Method Not Allowed");
+    pub const PAYLOAD_TOO_LARGE: Self = Self(413, "Payload Too Large");
+    pub const INTERNAL_SERVER_ERROR: Self = Self(500, "Internal Server Error");
+    pub const SERVICE_UNAVAILABLE: Self = Self(503, "Service Unavailable");
+}
+
+impl From<&IssueError> for Status {
+    fn from(error: &IssueError) -> Self {
+        match error {
+            IssueError::Verification(VerifyError::InvalidCredentials) => Status::UNAUTHORIZED,
+            IssueError::Verification(VerifyError::Forbidden(_)) => Status::FORBIDDEN,
+            IssueError::Verification(VerifyError::Unavailable(_))
+            | IssueError::NoServerAvailable => Status::SERVICE_UNAVAILABLE,
+            _ => Status::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Accepts connections on `listener` and answers the token requests, handling at most
+/// [`DEFAULT_MAX_CONNECTIONS`] connections at the same time.
+///
+/// See [`serve_with_max_connections`].
+pub async fn serve<V: CredentialVerifier>(
+    listener: TcpListener,
+    issuer: Arc<TokenIssuer<V>>,
+) -> io::Result<()> {
+    serve_with_max_connections(listener, issuer, DEFAULT_MAX_CONNECTIONS).await
+}
+
+/// Accepts connections on `listener` and answers the token requests.
+///
+/// Every connection is handled in its own task, so this must run inside a tokio runtime. The
+/// [`CredentialVerifier`] is called from that task: a verifier that blocks for a long time should
+/// be run on a multi-threaded runtime. Once `max_connections` connections are being handled, the
+/// new connections wait in the backlog of the listener until one of them is closed.
+///
+/// Failing to accept a connection, for example because the process ran out of file descriptors,
+/// doesn't stop the service: it waits a bit longer after each consecutive failure before
+/// accepting connections again. This only returns an error if the address of the listener cannot
+/// be read.
+pub async fn serve_with_max_connections<V: CredentialVerifier>(
+    listener: TcpListener,
+    issuer: Arc<TokenIssuer<V>>,
+    max_connections: usize,
+) -> io::Result<()> {
+    info!(addr = ?listener.local_addr()?, max_connections, "token service listening");
+    let connections = Arc::new(Semaphore::new(max_connections));
+    let mut backoff = MIN_ACCEPT_BACKOFF;
+    loop {
+        let permit = connections
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the semaphore is never closed");
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => {
+                backoff = MIN_ACCEPT_BACKOFF;
+                connection
+            }
+            Err(e) => {
+                warn!(?e, ?backoff, "failed to accept a connection");
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_ACCEPT_BACKOFF);
+                continue;
+            }
+        };
+        let issuer = issuer.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, &issuer).await {
+                debug!(?peer, ?e, "failed to answer token request");
+            }
+            drop(permit);
+        });
+    }
+}
+
+async fn handle_connection<V: CredentialVerifier>(
+    mut stream: TcpStream,
+    issuer: &TokenIssuer<V>,
+) -> io::Result<()> {
+    let request = match tokio::time::timeout(READ_TIMEOUT, read_head(&mut stream)).await {
+        Ok(Ok(Some(head))) => parse_request(&head),
+        Ok(Ok(None)) => Err(Status::PAYLOAD_TOO_LARGE),
+        Ok(Err(e)) => return Err(e),
+        Err(_) => return Err(io::ErrorKind::TimedOut.into()),
+    };
+    let response = match request.map(|request| issuer.issue(&request)) {
+        Ok(Ok(token)) => token_response(&token),
+        Ok(Err(e)) => {
+            debug!(?e, "token request rejected");
+            let status = Status::from(&e);
+            // internal errors are not exposed to the client
+            let body = if status == Status::INTERNAL_SERVER_ERROR {
+                String::from("internal error")
+            } else {
+                e.to_string()
+            };
+            response(status, &[], "text/plain", body.as_bytes())
+        }
+        Err(status) => response(status, &[], "text/plain", status.1.as_bytes()),
+    };
+    stream.write_all(&response).await?;
+    stream.shutdown().await
+}
+
+/// Reads the request line and the headers. Returns `None` if they are too large.
+async fn read_head(stream: &mut TcpStream) -> io::Result<Option<String>> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0; 1024];
+    loop {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.extend_from_slice(&chunk[..read]);
+        // the body is ignored, all the fields of the request are in the head
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            buf.truncate(end);
+            return Ok(Some(String::from_utf8_lossy(&buf).into_owned()));
+        }
+        if buf.len() > MAX_REQUEST_BYTES {
+            return Ok(None);
+        }
+    }
+}
+
+/// Parses the request line and headers of a token request.
+fn parse_request(head: &str) -> Result<TokenRequest, Status> {
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let (Some(method), Some(target), Some(_version)) = (
+        request_line.next(),
+        request_line.next(),
+        request_line.next(),
+    ) else {
+        return Err(Status::BAD_REQUEST);
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    if path != "/token" {
+        return Err(Status::NOT_FOUND);
+    }
+    if method != "POST" {
+        return Err(Status::METHOD_NOT_ALLOWED);
+    }
+
+    let credentials = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("authorization"))
+        .and_then(|(_, value)| value.trim().strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|credentials| !credentials.is_empty())
+        .ok_or(Status::UNAUTHORIZED)?;
+    let region = query
+        .split('&')
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| *name == "region")
+        .map(|(_, region)| String::from(region))
+        .filter(|region| !region.is_empty());
+    Ok(TokenRequest {
+        credentials: credentials.into(),
+        region,
+    })
+}
+
+fn token_response(token: &IssuedToken) -> Vec<u8> {
+    response(
+        Status::OK,
+        &[
+            ("X-Client-Id", token.client_id.to_string()),
+            ("X-Server", token.server.clone()),
+            ("X-Expire-Timestamp", token.expire_timestamp.to_string()),
+        ],
+        "application/octet-stream",
+        &token.bytes,
+    )
+}
+
+fn response(
+    status: Status,
+    headers: &[(&str, String)],
+    content_type: &str,
+    body: &[u8],
+) -> Vec<u8> {
+    let mut response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        status.0,
+        status.1,
+        body.len()
+    );
+    for (name, value) in headers {
+        response.push_str(&format!("{name}: {value}\r\n"));
+    }
+    response.push_str("\r\n");
+    let mut response = response.into_bytes();
+    response.extend_from_slice(body);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::issuer::IssuerConfig;
+    use crate::registry::{GameServer, ServerRegistry};
+    use crate::verifier::{StaticCredentials, VerifiedClient};
+    use lightyear_netcode::{CONNECT_TOKEN_BYTES, ConnectToken, generate_key};
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            parse_request("POST /token?region=eu HTTP/1.1\r\nHost: a\r\nauthorization: Bearer abc"),
+            Ok(TokenRequest::new("abc").with_region("eu"))
+        );
+        assert_eq!(
+            parse_request("POST /token HTTP/1.1\r\nAuthorization: Basic abc"),
+            Err(Status::UNAUTHORIZED)
+        );
+        assert_eq!(
+            parse_request("GET /token HTTP/1.1\r\nAuthorization: Bearer abc"),
+            Err(Status::METHOD_NOT_ALLOWED)
+        );
+        assert_eq!(parse_request("POST /"), Err(Status::BAD_REQUEST));
+    }
+
+    async fn send(addr: core::net::SocketAddr, request: &str) -> Vec<u8> {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        response
+    }
+
+    #[test]
+    fn serve_tokens() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let registry = ServerRegistry::default();
+            registry.register(GameServer::new(
+                "local",
+                ["127.0.0.1:5000".parse().unwrap()],
+            ));
+            let verifier = StaticCredentials::default()
+                .with("secret", VerifiedClient::default().with_client_id(1));
+            let issuer = TokenIssuer::new(IssuerConfig::new(0, generate_key()), verifier, registry);
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(serve(listener, Arc::new(issuer)));
+
+            let response = send(
+                addr,
+                "POST /token HTTP/1.1\r\nAuthorization: Bearer secret\r\nContent-Length: 0\r\n\r\n",
+            )
+            .await;
+            let end = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+            let head = core::str::from_utf8(&response[..end]).unwrap();
+            assert!(head.starts_with("HTTP/1.1 200 OK"));
+            assert!(head.contains("X-Client-Id: 1\r\n"));
+            let body = &response[end + 4..];
+            assert_eq!(body.len(), CONNECT_TOKEN_BYTES);
+            assert!(ConnectToken::try_from_bytes(body).is_ok());
+
+            let response = send(
+                addr,
+                "POST /token HTTP/1.1\r\nAuthorization: Bearer wrong\r\n\r\n",
+            )
+            .await;
+            assert!(response.starts_with(b"HTTP/1.1 401 Unauthorized"));
+        });
+    }
+
+    #[test]
+    fn limit_concurrent_connections() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let verifier = StaticCredentials::default().with("secret", VerifiedClient::default());
+            let issuer = TokenIssuer::new(
+                IssuerConfig::new(0, generate_key()),
+                verifier,
+                ServerRegistry::default(),
+            );
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(serve_with_max_connections(listener, Arc::new(issuer), 1));
+
+            // an idle connection takes the only slot
+            let idle = TcpStream::connect(addr).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let request = "POST /token HTTP/1.1\r\nAuthorization: Bearer wrong\r\n\r\n";
+            let pending = tokio::spawn(send(addr, request));
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            assert!(
+                !pending.is_finished(),
+                "the request should wait until a connection is closed"
+            );
+
+            drop(idle);
+            let response = tokio::time::timeout(Duration::from_secs(1), pending)
+                .await
+                .expect("the request should be answered once the idle connection is closed")
+                .unwrap();
+            assert!(response.starts_with(b"HTTP/1.1 401 Unauthorized"));
+        });
+    }
+}
@@ -0,0 +1,265 @@
+//! Generation of the [`ConnectToken`]s.
+use crate::registry::{GameServer, ServerRegistry};
+use crate::verifier::{CredentialVerifier, TokenRequest, VerifyError};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lightyear_netcode::{CONNECT_TOKEN_BYTES, ConnectToken, Key, KeyId, USER_DATA_BYTES};
+use std::sync::RwLock;
+#[allow(unused_imports)]
+use tracing::{debug, trace};
+
+/// Parameters shared by all the tokens, which must match the configuration of the game servers.
+#[derive(Clone)]
+pub struct IssuerConfig {
+    pub protocol_id: u64,
+    pub private_key: Key,
+    /// Id of `private_key` in the [`Keyring`](lightyear_netcode::Keyring) of the game servers.
+    ///
+    /// To rotate the key, add the new key to the keyring of the servers first, then update the
+    /// issuer with [`TokenIssuer::rotate_key`].
+    pub key_id: KeyId,
+    /// Number of seconds during which the token can be used to connect. Negative values disable
+    /// expiry.
+    pub expire_seconds: i32,
+    /// Number of seconds without packets after which the connection times out. Negative values
+    /// disable timeouts.
+    pub timeout_seconds: i32,
+}
+
+impl core::fmt::Debug for IssuerConfig {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("IssuerConfig")
+            .field("protocol_id", &self.protocol_id)
+            .field("key_id", &self.key_id)
+            .field("expire_seconds", &self.expire_seconds)
+            .field("timeout_seconds", &self.timeout_seconds)
+            .finish_non_exhaustive()
+    }
+}
+
+impl IssuerConfig {
+    pub fn new(protocol_id: u64, private_key: Key) -> Self {
+        Self {
+            protocol_id,
+            private_key,
+            key_id: 0,
+            expire_seconds: 30,
+            timeout_seconds: 15,
+        }
+    }
+
+    pub fn with_key_id(mut self, key_id: KeyId) -> Self {
+        self.key_id = key_id;
+        self
+    }
+
+    pub fn with_expire_seconds(mut self, expire_seconds: i32) -> Self {
+        self.expire_seconds = expire_seconds;
+        self
+    }
+
+    pub fn with_timeout_seconds(mut self, timeout_seconds: i32) -> Self {
+        self.timeout_seconds = timeout_seconds;
+        self
+    }
+}
+
+/// A serialized [`ConnectToken`], ready to be sent to the client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IssuedToken {
+    pub client_id: u64,
+    /// Name of the [`GameServer`] that the token connects to.
+    pub server: String,
+    /// Timestamp after which the token cannot be used, in seconds since the unix epoch.
+    pub expire_timestamp: u64,
+    /// The token, serialized with [`ConnectToken::try_into_bytes`]. It is always
+    /// [`CONNECT_TOKEN_BYTES`] long.
+    pub bytes: Vec<u8>,
+}
+
+impl IssuedToken {
+    /// Deserializes the token, as the client would do.
+    pub fn connect_token(&self) -> Result<ConnectToken, lightyear_netcode::InvalidTokenError> {
+        ConnectToken::try_from_bytes(&self.bytes)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum IssueError {
+    #[error("verification failed: {0}")]
+    Verification(#[from] VerifyError),
+    #[error("no game server is available")]
+    NoServerAvailable,
+    #[error("user data is {0} bytes long, but tokens can only hold {USER_DATA_BYTES} bytes")]
+    UserDataTooLarge(usize),
+    #[error("failed to generate the token: {0}")]
+    Token(#[from] lightyear_netcode::Error),
+    #[error("failed to serialize the token: {0}")]
+    Serialization(String),
+}
+
+/// Authenticates [`TokenRequest`]s and generates the [`ConnectToken`]s.
+///
+/// The issuer only holds shared state, so it can be wrapped in an `Arc` and used from multiple
+/// threads.
+pub struct TokenIssuer<V> {
+    config: RwLock<IssuerConfig>,
+    verifier: V,
+    registry: Arc<ServerRegistry>,
+}
+
+impl<V: CredentialVerifier> TokenIssuer<V> {
+    pub fn new(
+        config: IssuerConfig,
+        verifier: V,
+        registry: impl Into<Arc<ServerRegistry>>,
+    ) -> Self {
+        Self {
+            config: RwLock::new(config),
+            verifier,
+            registry: registry.into(),
+        }
+    }
+
+    pub fn config(&self) -> IssuerConfig {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Generates the next tokens with a new private key.
+    ///
+    /// The game servers must already accept the new key, and should keep the previous key for at
+    /// least [`expire_seconds`](IssuerConfig::expire_seconds) so that the tokens that were just
+    /// issued remain valid.
+    pub fn rotate_key(&self, key_id: KeyId, private_key: Key) {
+        let mut config = self.config.write().unwrap();
+        config.key_id = key_id;
+        config.private_key = private_key;
+    }
+
+    /// Registry of the game servers, which can be updated while the issuer is running.
+    pub fn registry(&self) -> &Arc<ServerRegistry> {
+        &self.registry
+    }
+
+    /// Verifies the request, selects a game server and generates a token for it.
+    pub fn issue(&self, request: &TokenRequest) -> Result<IssuedToken, IssueError> {
+        let client = self.verifier.verify(request)?;
+        if client.user_data.len() > USER_DATA_BYTES {
+            return Err(IssueError::UserDataTooLarge(client.user_data.len()));
+        }
+        let mut user_data = [0; USER_DATA_BYTES];
+        user_data[..client.user_data.len()].copy_from_slice(&client.user_data);
+        let client_id = client.client_id.unwrap_or_else(rand::random);
+
+        let server = self
+            .registry
+            .reserve(request.region.as_deref())
+            .ok_or(IssueError::NoServerAvailable)?;
+        let token = self
+            .generate(&server, client_id, user_data)
+            .inspect_err(|_| self.registry.release(&server.name))?;
+        debug!(?client_id, server = ?server.name, "issued connect token");
+        Ok(IssuedToken {
+            client_id,
+            server: server.name,
+            expire_timestamp: token.expire_timestamp(),
+            bytes: token
+                .try_into_bytes()
+                .map_err(|e| IssueError::Serialization(e.to_string()))?
+                .to_vec(),
+        })
+    }
+
+    fn generate(
+        &self,
+        server: &GameServer,
+        client_id: u64,
+        user_data: [u8; USER_DATA_BYTES],
+    ) -> Result<ConnectToken, IssueError> {
+        let config = self.config();
+        let mut builder = ConnectToken::build(
+            server.public_addresses.as_slice(),
+            config.protocol_id,
+            client_id,
+            config.private_key,
+        )
+        .key_id(config.key_id)
+        .expire_seconds(config.expire_seconds)
+        .timeout_seconds(config.timeout_seconds)
+        .user_data(user_data);
+        if let Some(internal_addresses) = &server.internal_addresses {
+            builder = builder.internal_addresses(internal_addresses.as_slice())?;
+        }
+        Ok(builder.generate()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verifier::{StaticCredentials, VerifiedClient};
+    use core::net::SocketAddr;
+    use lightyear_netcode::generate_key;
+
+    const SERVER_ADDR: &str = "127.0.0.1:5000";
+
+    fn issuer() -> TokenIssuer<StaticCredentials> {
+        let registry = ServerRegistry::default();
+        registry.register(
+            GameServer::new("eu-1", [SERVER_ADDR.parse::<SocketAddr>().unwrap()])
+                .with_region("eu")
+                .with_capacity(1),
+        );
+        let verifier = StaticCredentials::default()
+            .with(
+                "alice",
+                VerifiedClient::default()
+                    .with_client_id(7)
+                    .with_user_data(*b"admin"),
+            )
+            .with("bob", VerifiedClient::default());
+        TokenIssuer::new(IssuerConfig::new(3, generate_key()), verifier, registry)
+    }
+
+    #[test]
+    fn issue_token() {
+        let issuer = issuer();
+        let issued = issuer
+            .issue(&TokenRequest::new("alice").with_region("eu"))
+            .unwrap();
+        assert_eq!(issued.client_id, 7);
+        assert_eq!(issued.server, "eu-1");
+        assert_eq!(issued.bytes.len(), CONNECT_TOKEN_BYTES);
+
+        let token = issued.connect_token().unwrap();
+        assert_eq!(token.expire_timestamp(), issued.expire_timestamp);
+        assert_eq!(token.key_id(), 0);
+
+        // the client never connected to the server
+        issuer.registry().report_load("eu-1", 0);
+        issuer.rotate_key(1, generate_key());
+        let issued = issuer.issue(&TokenRequest::new("alice")).unwrap();
+        assert_eq!(issued.connect_token().unwrap().key_id(), 1);
+        assert_eq!(issuer.registry().load("eu-1"), Some(1));
+    }
+
+    #[test]
+    fn issue_errors() {
+        let issuer = issuer();
+        assert!(matches!(
+            issuer.issue(&TokenRequest::new("mallory")),
+            Err(IssueError::Verification(VerifyError::InvalidCredentials))
+        ));
+        assert!(matches!(
+            issuer.issue(&TokenRequest::new("bob").with_region("us")),
+            Err(IssueError::NoServerAvailable)
+        ));
+        // the failed requests did not reserve the server
+        issuer.issue(&TokenRequest::new("bob")).unwrap();
+        assert!(matches!(
+            issuer.issue(&TokenRequest::new("bob")),
+            Err(IssueError::NoServerAvailable)
+        ));
+    }
+}
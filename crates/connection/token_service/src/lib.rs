@@ -0,0 +1,57 @@
+/*! Service that authenticates clients and issues netcode [`ConnectToken`]s.
+
+The netcode protocol expects a web backend that authenticates the clients and hands them a
+[`ConnectToken`] for one of the game servers. This crate provides that backend:
+- a [`CredentialVerifier`] checks the credentials sent by the client (a session ticket, a JWT, an
+  API key, etc.) and returns the identity of the client;
+- a [`ServerRegistry`] keeps track of the game servers and of their load, so that each client is
+  sent to the least loaded server of its region;
+- a [`TokenIssuer`] combines both to generate the serialized [`ConnectToken`].
+
+The [`TokenIssuer`] can be called directly in-process, which is convenient for tests and for
+single-process deployments. With the `http` feature, [`http::serve`] exposes it over HTTP, and the
+`bin` feature builds a standalone `lightyear_token_service` binary configured from a JSON file.
+
+```rust,no_run
+use lightyear_netcode::generate_key;
+use lightyear_token_service::prelude::*;
+
+let registry = ServerRegistry::default();
+registry.register(GameServer::new("eu-1", ["203.0.113.1:5000".parse().unwrap()]).with_capacity(64));
+
+let verifier = StaticCredentials::default().with("my-api-key", VerifiedClient::default());
+let issuer = TokenIssuer::new(IssuerConfig::new(0, generate_key()), verifier, registry);
+
+let token = issuer.issue(&TokenRequest::new("my-api-key")).unwrap();
+// send `token.bytes` to the client, which can use `ConnectToken::try_from_bytes`
+```
+
+The game servers must use the same `protocol_id` as the [`IssuerConfig`], and have its `private_key`
+in their [`Keyring`](lightyear_netcode::Keyring) under the same `key_id`.
+
+[`ConnectToken`]: lightyear_netcode::ConnectToken
+*/
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+extern crate alloc;
+
+pub mod issuer;
+pub mod registry;
+pub mod verifier;
+
+#[cfg(feature = "http")]
+pub mod http;
+
+pub use issuer::{IssueError, IssuedToken, IssuerConfig, TokenIssuer};
+pub use registry::{GameServer, ServerRegistry};
+pub use verifier::{
+    CredentialVerifier, StaticCredentials, TokenRequest, VerifiedClient, VerifyError,
+};
+
+pub mod prelude {
+    pub use crate::issuer::{IssueError, IssuedToken, IssuerConfig, TokenIssuer};
+    pub use crate::registry::{GameServer, ServerRegistry};
+    pub use crate::verifier::{
+        AllowAll, CredentialVerifier, StaticCredentials, TokenRequest, VerifiedClient, VerifyError,
+    };
+}
@@ -0,0 +1,168 @@
+//! Standalone token service, configured from a JSON file:
+//!
+//! ```json
+//! {
+//!   "listen": "0.0.0.0:8080",
+//!   "protocol_id": 0,
+//!   "private_key": "<64 hex characters>",
+//!   "servers": [
+//!     { "name": "eu-1", "public_addresses": ["203.0.113.1:5000"], "region": "eu", "capacity": 64 }
+//!   ],
+//!   "credentials": {
+//!     "<api key>": { "client_id": 1, "user_data": "admin" }
+//!   }
+//! }
+//! ```
+//!
+//! The clients are authenticated with the API keys listed in `credentials`. Other authentication
+//! methods require a custom [`CredentialVerifier`] and the library API.
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use anyhow::{Context, bail};
+use clap::Parser;
+use core::net::SocketAddr;
+use lightyear_netcode::Key;
+use lightyear_token_service::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(about = "Authenticates clients and issues netcode connect tokens over HTTP")]
+struct Cli {
+    /// Path of the JSON configuration file
+    #[arg(short, long)]
+    config: PathBuf,
+    /// Overrides the address that the service listens on
+    #[arg(short, long)]
+    listen: Option<SocketAddr>,
+    /// Accept every request without checking the credentials. Only use this for local testing!
+    #[arg(long)]
+    allow_all: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    listen: SocketAddr,
+    protocol_id: u64,
+    private_key: String,
+    key_id: Option<u8>,
+    expire_seconds: Option<i32>,
+    timeout_seconds: Option<i32>,
+    max_connections: Option<usize>,
+    servers: Vec<ServerConfig>,
+    #[serde(default)]
+    credentials: HashMap<String, ClientConfig>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ServerConfig {
+    name: String,
+    public_addresses: Vec<SocketAddr>,
+    internal_addresses: Option<Vec<SocketAddr>>,
+    region: Option<String>,
+    capacity: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ClientConfig {
+    client_id: Option<u64>,
+    #[serde(default)]
+    user_data: String,
+}
+
+fn parse_key(hex: &str) -> anyhow::Result<Key> {
+    let mut key = Key::default();
+    if hex.len() != 2 * key.len() {
+        bail!("the private key must be {} hex characters", 2 * key.len());
+    }
+    for (byte, chunk) in key.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let chunk = core::str::from_utf8(chunk)?;
+        *byte = u8::from_str_radix(chunk, 16).context("the private key is not valid hex")?;
+    }
+    Ok(key)
+}
+
+fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()),
+        )
+        .init();
+    let cli = Cli::parse();
+    let config = std::fs::read_to_string(&cli.config)
+        .with_context(|| format!("failed to read {}", cli.config.display()))?;
+    let config: Config = serde_json::from_str(&config).context("invalid configuration")?;
+
+    let mut issuer_config = IssuerConfig::new(config.protocol_id, parse_key(&config.private_key)?);
+    if let Some(key_id) = config.key_id {
+        issuer_config = issuer_config.with_key_id(key_id);
+    }
+    if let Some(expire_seconds) = config.expire_seconds {
+        issuer_config = issuer_config.with_expire_seconds(expire_seconds);
+    }
+    if let Some(timeout_seconds) = config.timeout_seconds {
+        issuer_config = issuer_config.with_timeout_seconds(timeout_seconds);
+    }
+
+    let registry = ServerRegistry::default();
+    for server in config.servers {
+        let mut game_server = GameServer::new(server.name, server.public_addresses);
+        if let Some(internal_addresses) = server.internal_addresses {
+            game_server = game_server.with_internal_addresses(internal_addresses);
+        }
+        if let Some(region) = server.region {
+            game_server = game_server.with_region(region);
+        }
+        if let Some(capacity) = server.capacity {
+            game_server = game_server.with_capacity(capacity);
+        }
+        registry.register(game_server);
+    }
+    if registry.is_empty() {
+        bail!("no game server is configured");
+    }
+
+    let verifier: Box<dyn CredentialVerifier> = if cli.allow_all {
+        tracing::warn!("every request is accepted without checking the credentials");
+        Box::new(AllowAll)
+    } else {
+        let mut credentials = StaticCredentials::default();
+        for (key, client) in config.credentials {
+            let mut verified = VerifiedClient::default().with_user_data(client.user_data);
+            if let Some(client_id) = client.client_id {
+                verified = verified.with_client_id(client_id);
+            }
+            credentials.insert(key, verified);
+        }
+        Box::new(credentials)
+    };
+    let issuer = Arc::new(TokenIssuer::new(issuer_config, verifier, registry));
+
+    let listen = cli.listen.unwrap_or(config.listen);
+    let max_connections = config
+        .max_connections
+        .unwrap_or(lightyear_token_service::http::DEFAULT_MAX_CONNECTIONS);
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(async move {
+            let listener = tokio::net::TcpListener::bind(listen)
+                .await
+                .with_context(|| format!("failed to listen on {listen}"))?;
+            lightyear_token_service::http::serve_with_max_connections(
+                listener,
+                issuer,
+                max_connections,
+            )
+            .await?;
+            Ok(())
+        })
+}
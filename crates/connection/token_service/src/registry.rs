@@ -0,0 +1,202 @@
+//! Registry of the game servers that clients can be sent to.
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::net::SocketAddr;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// A game server that can accept clients.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameServer {
+    /// Unique name of the server in the registry.
+    pub name: String,
+    /// Addresses that the clients use to connect to the server.
+    pub public_addresses: Vec<SocketAddr>,
+    /// Addresses that the server is bound to, if they are different from the public ones (for
+    /// example behind a NAT or a load balancer).
+    pub internal_addresses: Option<Vec<SocketAddr>>,
+    pub region: Option<String>,
+    /// Maximum number of clients that can be sent to the server.
+    pub capacity: u32,
+}
+
+impl GameServer {
+    pub fn new(
+        name: impl Into<String>,
+        public_addresses: impl IntoIterator<Item = SocketAddr>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            public_addresses: public_addresses.into_iter().collect(),
+            internal_addresses: None,
+            region: None,
+            capacity: u32::MAX,
+        }
+    }
+
+    pub fn with_internal_addresses(
+        mut self,
+        internal_addresses: impl IntoIterator<Item = SocketAddr>,
+    ) -> Self {
+        self.internal_addresses = Some(internal_addresses.into_iter().collect());
+        self
+    }
+
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+
+    pub fn with_capacity(mut self, capacity: u32) -> Self {
+        self.capacity = capacity;
+        self
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    server: GameServer,
+    /// Number of clients on the server, as last reported plus the tokens issued since then.
+    load: u32,
+}
+
+/// Thread-safe registry of the [`GameServer`]s, shared between the request handlers.
+///
+/// Every issued token counts as one more client on the selected server, so that concurrent
+/// requests are spread over the servers. The game servers should call [`report_load`] regularly
+/// with their actual number of clients, which also accounts for the tokens that were never used.
+///
+/// [`report_load`]: ServerRegistry::report_load
+#[derive(Debug, Default)]
+pub struct ServerRegistry {
+    servers: RwLock<HashMap<String, Entry>>,
+}
+
+impl ServerRegistry {
+    /// Adds a server to the registry, or replaces the server with the same name.
+    pub fn register(&self, server: GameServer) {
+        let mut servers = self.servers.write().unwrap();
+        let load = servers.get(&server.name).map_or(0, |entry| entry.load);
+        servers.insert(server.name.clone(), Entry { server, load });
+    }
+
+    /// Removes a server, for example when it shuts down. Returns the removed server.
+    pub fn deregister(&self, name: &str) -> Option<GameServer> {
+        self.servers
+            .write()
+            .unwrap()
+            .remove(name)
+            .map(|entry| entry.server)
+    }
+
+    /// Updates the number of clients connected to a server.
+    ///
+    /// Returns false if the server is not registered.
+    pub fn report_load(&self, name: &str, clients: u32) -> bool {
+        match self.servers.write().unwrap().get_mut(name) {
+            Some(entry) => {
+                entry.load = clients;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancels a [`reserve`](Self::reserve), for example if the token could not be delivered.
+    pub fn release(&self, name: &str) {
+        if let Some(entry) = self.servers.write().unwrap().get_mut(name) {
+            entry.load = entry.load.saturating_sub(1);
+        }
+    }
+
+    /// Returns the number of clients on a server, including the tokens issued since the last
+    /// [`report_load`](Self::report_load).
+    pub fn load(&self, name: &str) -> Option<u32> {
+        self.servers
+            .read()
+            .unwrap()
+            .get(name)
+            .map(|entry| entry.load)
+    }
+
+    pub fn servers(&self) -> Vec<GameServer> {
+        self.servers
+            .read()
+            .unwrap()
+            .values()
+            .map(|entry| entry.server.clone())
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.servers.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.servers.read().unwrap().is_empty()
+    }
+
+    /// Selects the server of the region with the lowest load relative to its capacity, and counts
+    /// one more client on it.
+    ///
+    /// If `region` is `None`, servers of every region can be selected. Returns `None` if every
+    /// matching server is full.
+    pub fn reserve(&self, region: Option<&str>) -> Option<GameServer> {
+        let mut servers = self.servers.write().unwrap();
+        let entry = servers
+            .values_mut()
+            .filter(|entry| region.is_none() || entry.server.region.as_deref() == region)
+            .filter(|entry| entry.load < entry.server.capacity)
+            .min_by(|a, b| {
+                let a_ratio = a.load as f64 / a.server.capacity as f64;
+                let b_ratio = b.load as f64 / b.server.capacity as f64;
+                // break ties by name so that the selection is deterministic
+                a_ratio
+                    .total_cmp(&b_ratio)
+                    .then_with(|| a.server.name.cmp(&b.server.name))
+            })?;
+        entry.load += 1;
+        Some(entry.server.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(name: &str, region: &str, capacity: u32) -> GameServer {
+        GameServer::new(name, ["127.0.0.1:5000".parse().unwrap()])
+            .with_region(region)
+            .with_capacity(capacity)
+    }
+
+    #[test]
+    fn reserve_least_loaded_server_of_region() {
+        let registry = ServerRegistry::default();
+        registry.register(server("eu-1", "eu", 2));
+        registry.register(server("eu-2", "eu", 4));
+        registry.register(server("us-1", "us", 10));
+        registry.report_load("eu-2", 1);
+
+        let reserved = (0..5)
+            .map(|_| registry.reserve(Some("eu")).map(|server| server.name))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reserved,
+            [
+                Some("eu-1".into()),
+                Some("eu-2".into()),
+                Some("eu-1".into()),
+                Some("eu-2".into()),
+                Some("eu-2".into()),
+            ]
+        );
+        // every server of the region is full
+        assert_eq!(registry.reserve(Some("eu")), None);
+        assert_eq!(registry.reserve(None).unwrap().name, "us-1");
+
+        // the servers report that some clients never connected
+        registry.report_load("eu-1", 0);
+        assert_eq!(registry.reserve(Some("eu")).unwrap().name, "eu-1");
+    }
+}
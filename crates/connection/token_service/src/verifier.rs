@@ -0,0 +1,128 @@
+//! Authentication of the clients that request a token.
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::collections::HashMap;
+
+/// Request for a [`ConnectToken`](lightyear_netcode::ConnectToken), sent by a client.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TokenRequest {
+    /// Credentials that prove the identity of the client. Over HTTP, this is the bearer token of
+    /// the `Authorization` header.
+    pub credentials: String,
+    /// Region in which the client wants to play. If `None`, a server from any region can be used.
+    pub region: Option<String>,
+}
+
+impl TokenRequest {
+    pub fn new(credentials: impl Into<String>) -> Self {
+        Self {
+            credentials: credentials.into(),
+            region: None,
+        }
+    }
+
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+}
+
+/// Identity of a client, returned by a [`CredentialVerifier`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifiedClient {
+    /// Netcode client id to put in the token.
+    ///
+    /// If `None`, a random id is generated for every token. Setting it (for example to the account
+    /// id of the player) lets the game server recognize a player that reconnects.
+    pub client_id: Option<u64>,
+    /// Data added to the private part of the token, that only the game server can read.
+    ///
+    /// It must not be longer than [`USER_DATA_BYTES`](lightyear_netcode::USER_DATA_BYTES).
+    pub user_data: Vec<u8>,
+}
+
+impl VerifiedClient {
+    pub fn with_client_id(mut self, client_id: u64) -> Self {
+        self.client_id = Some(client_id);
+        self
+    }
+
+    pub fn with_user_data(mut self, user_data: impl Into<Vec<u8>>) -> Self {
+        self.user_data = user_data.into();
+        self
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    #[error("the credentials are invalid")]
+    InvalidCredentials,
+    #[error("the client is not allowed to connect: {0}")]
+    Forbidden(String),
+    /// The verifier could not reach the service that checks the credentials.
+    #[error("the verifier is unavailable: {0}")]
+    Unavailable(String),
+}
+
+/// Checks the credentials of a [`TokenRequest`].
+///
+/// This is where the token service plugs into the authentication of the game: for example by
+/// verifying the signature of a JWT, or by looking up a session ticket. Closures with the
+/// signature `Fn(&TokenRequest) -> Result<VerifiedClient, VerifyError>` also implement this trait.
+pub trait CredentialVerifier: Send + Sync + 'static {
+    fn verify(&self, request: &TokenRequest) -> Result<VerifiedClient, VerifyError>;
+}
+
+impl<F> CredentialVerifier for F
+where
+    F: Fn(&TokenRequest) -> Result<VerifiedClient, VerifyError> + Send + Sync + 'static,
+{
+    fn verify(&self, request: &TokenRequest) -> Result<VerifiedClient, VerifyError> {
+        self(request)
+    }
+}
+
+impl CredentialVerifier for Box<dyn CredentialVerifier> {
+    fn verify(&self, request: &TokenRequest) -> Result<VerifiedClient, VerifyError> {
+        (**self).verify(request)
+    }
+}
+
+/// Accepts every request and gives each client a random id.
+///
+/// This should only be used for local testing.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AllowAll;
+
+impl CredentialVerifier for AllowAll {
+    fn verify(&self, _: &TokenRequest) -> Result<VerifiedClient, VerifyError> {
+        Ok(VerifiedClient::default())
+    }
+}
+
+/// Fixed list of accepted credentials, for example API keys given to trusted clients.
+#[derive(Debug, Clone, Default)]
+pub struct StaticCredentials {
+    clients: HashMap<String, VerifiedClient>,
+}
+
+impl StaticCredentials {
+    pub fn with(mut self, credentials: impl Into<String>, client: VerifiedClient) -> Self {
+        self.insert(credentials, client);
+        self
+    }
+
+    pub fn insert(&mut self, credentials: impl Into<String>, client: VerifiedClient) {
+        self.clients.insert(credentials.into(), client);
+    }
+}
+
+impl CredentialVerifier for StaticCredentials {
+    fn verify(&self, request: &TokenRequest) -> Result<VerifiedClient, VerifyError> {
+        self.clients
+            .get(&request.credentials)
+            .cloned()
+            .ok_or(VerifyError::InvalidCredentials)
+    }
+}
@@ -49,6 +49,9 @@ pub enum PeerId {
     Local(u64),
     /// Refers to the server
     Server,
+    /// A peer identified by the static public key that it proved to own during an authenticated
+    /// key exchange.
+    PublicKey([u8; 32]),
 }
 
 impl ToBytes for PeerId {
@@ -66,6 +69,7 @@ impl ToBytes for PeerId {
             PeerId::Steam(_) => 1 + 8,
             PeerId::Local(_) => 1 + 8,
             PeerId::Server => 1,
+            PeerId::PublicKey(key) => 1 + key.len(),
         }
     }
 
@@ -112,6 +116,12 @@ impl ToBytes for PeerId {
             PeerId::Server => {
                 buffer.write_u8(6)?;
             }
+            PeerId::PublicKey(key) => {
+                buffer.write_u8(7)?;
+                for byte in key.iter() {
+                    buffer.write_u8(*byte)?;
+                }
+            }
         }
         Ok(())
     }
@@ -152,6 +162,13 @@ impl ToBytes for PeerId {
             4 => Ok(PeerId::Steam(buffer.read_u64()?)),
             5 => Ok(PeerId::Local(buffer.read_u64()?)),
             6 => Ok(PeerId::Server),
+            7 => {
+                let mut key = [0u8; 32];
+                for byte in &mut key {
+                    *byte = buffer.read_u8()?;
+                }
+                Ok(PeerId::PublicKey(key))
+            }
             _ => Err(SerializationError::InvalidValue),
         }
     }
@@ -193,6 +210,10 @@ impl PeerId {
             PeerId::Steam(x) => *x,
             PeerId::Local(x) => *x,
             PeerId::Server => 1,
+            // The key is uniformly distributed, so its first bytes are enough to differentiate peers
+            PeerId::PublicKey(key) => u64::from_le_bytes([
+                key[0], key[1], key[2], key[3], key[4], key[5], key[6], key[7],
+            ]),
         }
     }
 
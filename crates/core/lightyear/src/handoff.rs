@@ -0,0 +1,625 @@
+//! Move clients and their entities between the shards of a sharded world.
+//!
+//! A sharded world is split across several servers (shards). When a player crosses the boundary
+//! between two shards, the source shard hands the player off to the destination shard:
+//! 1. the source shard triggers [`StartHandoff`] on the `ClientOf` entity of the player. The
+//!    entities of the player are serialized with [`save_entities`] and sent in a
+//!    [`HandoffRequest`] over a link between the two shards, marked with [`ShardLink`]
+//! 2. the destination shard spawns the entities with [`load_snapshot`] and replies with a connect
+//!    token for the player, generated by its [`NetcodeServer`] (see [`AcceptHandoffs`]). The
+//!    control of the entities is restored when the player connects, with [`PendingControlledBy`]
+//! 3. the source shard forwards the token to the player in a [`HandoffRedirect`] message and
+//!    marks the entities with [`HandedOff`]. They are despawned when the player disconnects
+//! 4. the player disconnects from the source shard and connects to the destination shard. The
+//!    entities of the player are kept alive during the swap and are mapped to the entities of
+//!    the destination shard with [`PremappedEntities`], so that they are not respawned
+//!
+//! The source shard receives [`HandoffFinished`] once the handoff is accepted or failed.
+//! Until then it keeps simulating the entities; afterwards gameplay systems should usually
+//! ignore the entities that have [`HandedOff`].
+//!
+//! ```rust,ignore
+//! // on every shard and every client, at the same point of the protocol registration
+//! app.add_plugins(HandoffPlugin);
+//!
+//! // destination shard
+//! commands.entity(server).insert(AcceptHandoffs::new(public_address));
+//!
+//! // source shard
+//! commands.trigger(StartHandoff {
+//!     entity: client_of,
+//!     shard: shard_link,
+//!     entities: vec![],
+//! });
+//! ```
+//!
+//! [`save_entities`]: lightyear_replication::prelude::save_entities
+//! [`load_snapshot`]: lightyear_replication::prelude::load_snapshot
+//! [`NetcodeServer`]: lightyear_netcode::NetcodeServer
+//! [`PendingControlledBy`]: lightyear_replication::prelude::PendingControlledBy
+//! [`PremappedEntities`]: lightyear_replication::prelude::client::PremappedEntities
+
+use bevy_app::{App, Plugin};
+use bevy_ecs::entity::{EntityMapper, MapEntities};
+use bevy_ecs::prelude::*;
+use core::net::SocketAddr;
+use lightyear_connection::direction::NetworkDirection;
+use lightyear_core::id::PeerId;
+use lightyear_messages::prelude::{AppMessageExt, AppRpcExt, RpcError};
+use lightyear_transport::prelude::{AppChannelExt, ChannelMode, ChannelSettings, ReliableSettings};
+use serde::{Deserialize, Serialize};
+
+/// Reliable channel used to exchange the handoff messages
+pub struct HandoffChannel;
+
+/// Marker component for the links between two shards.
+///
+/// Handoff requests are only accepted on links with this marker, so that game clients cannot
+/// inject entities in a shard. Insert it on the `Client` entity connecting to another shard, and
+/// on the corresponding `ClientOf` entity of the other shard.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ShardLink;
+
+/// Insert this on the [`NetcodeServer`](lightyear_netcode::NetcodeServer) entity of a shard to
+/// accept the clients handed off by other shards.
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct AcceptHandoffs {
+    /// Address that the handed off clients use to connect to the server
+    pub client_address: SocketAddr,
+    /// Duration in seconds after which the connect token sent to the client expires
+    pub token_expire_secs: i32,
+    /// Duration in seconds after which the server disconnects the client if it doesn't hear
+    /// from it
+    pub client_timeout_secs: i32,
+}
+
+impl AcceptHandoffs {
+    pub fn new(client_address: SocketAddr) -> Self {
+        Self {
+            client_address,
+            token_expire_secs: 30,
+            client_timeout_secs: 3,
+        }
+    }
+
+    pub fn with_token_expire_secs(mut self, token_expire_secs: i32) -> Self {
+        self.token_expire_secs = token_expire_secs;
+        self
+    }
+
+    pub fn with_client_timeout_secs(mut self, client_timeout_secs: i32) -> Self {
+        self.client_timeout_secs = client_timeout_secs;
+        self
+    }
+}
+
+/// Request sent by the source shard to the destination shard
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HandoffRequest {
+    /// Id of the client that is handed off
+    pub client: PeerId,
+    /// User data of the connect token that the client used to connect to the source shard
+    pub user_data: Option<Vec<u8>>,
+    /// Entities of the client, serialized with
+    /// [`save_entities`](lightyear_replication::prelude::save_entities)
+    pub entities: Vec<u8>,
+}
+
+/// Reply of the destination shard to a [`HandoffRequest`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum HandoffReply {
+    Accepted {
+        /// Serialized connect token for the destination shard
+        token: Vec<u8>,
+        /// `(source shard entity, destination shard entity)` pairs of the loaded entities
+        entities: Vec<(Entity, Entity)>,
+    },
+    Rejected(String),
+}
+
+/// Message sent by the source shard to tell the client to connect to the destination shard
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HandoffRedirect {
+    /// Serialized connect token for the destination shard
+    pub token: Vec<u8>,
+    /// `(source shard entity, destination shard entity)` pairs of the handed off entities
+    pub entities: Vec<(Entity, Entity)>,
+}
+
+impl MapEntities for HandoffRedirect {
+    /// Only the entities of the source shard can be mapped to the entities of the client; the
+    /// entities of the destination shard are mapped when the client connects to it
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        for (entity, _) in self.entities.iter_mut() {
+            *entity = entity_mapper.get_mapped(*entity);
+        }
+    }
+}
+
+/// Reasons why a handoff failed
+#[derive(thiserror::Error, Debug)]
+pub enum HandoffError {
+    #[error("the destination shard rejected the handoff: {0}")]
+    Rejected(String),
+    #[error("the handoff request failed: {0}")]
+    Rpc(#[from] RpcError),
+    #[cfg(feature = "server")]
+    #[error(transparent)]
+    Snapshot(#[from] lightyear_replication::prelude::SnapshotError),
+    #[error("could not create the connect token: {0}")]
+    Token(#[from] lightyear_netcode::Error),
+    #[error("the client is not connected with netcode")]
+    NotNetcode,
+    #[error("the entity is not a link to another shard")]
+    NotShardLink,
+    #[error("the shard does not accept handoffs")]
+    NoServer,
+}
+
+/// Plugin to hand off clients from one shard to another.
+///
+/// It must be added on every shard and on the clients, after the lightyear plugins and at the
+/// same point of the protocol registration, since it registers messages.
+pub struct HandoffPlugin;
+
+impl Plugin for HandoffPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_channel::<HandoffChannel>(ChannelSettings {
+            mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+            ..Default::default()
+        })
+        .add_direction(NetworkDirection::Bidirectional);
+        app.register_request::<HandoffRequest, HandoffReply>()
+            .add_direction(NetworkDirection::Bidirectional);
+        app.register_message::<HandoffRedirect>()
+            .add_map_entities()
+            .add_direction(NetworkDirection::ServerToClient);
+
+        #[cfg(feature = "server")]
+        server::build(app);
+        #[cfg(feature = "client")]
+        client::build(app);
+    }
+}
+
+#[cfg(feature = "server")]
+pub use server::{HandedOff, HandoffFinished, StartHandoff};
+
+#[cfg(feature = "server")]
+mod server {
+    use super::*;
+    use bevy_ecs::relationship::RelationshipTarget;
+    use lightyear_connection::client::Disconnected;
+    use lightyear_core::id::RemoteId;
+    use lightyear_messages::prelude::{
+        MessageSender, Request, RequestId, RequestSender, Response, ResponseSender,
+    };
+    use lightyear_netcode::{NetcodeServer, TokenUserData, USER_DATA_BYTES};
+    use lightyear_replication::control::ControlledByRemote;
+    use lightyear_replication::hierarchy::ReplicateLikeChildren;
+    use lightyear_replication::prelude::{load_snapshot, save_entities};
+    use lightyear_utils::collections::HashMap;
+    use tracing::{debug, info, warn};
+
+    /// Triggered on the source shard to hand off a client to another shard.
+    #[derive(EntityEvent, Debug, Clone)]
+    pub struct StartHandoff {
+        /// `ClientOf` entity of the client
+        pub entity: Entity,
+        /// Link to the destination shard, with the [`ShardLink`] marker
+        pub shard: Entity,
+        /// Entities handed off with the client.
+        ///
+        /// The entities controlled by the client are always handed off, and so are the
+        /// `ReplicateLike` children of all the handed off entities.
+        pub entities: Vec<Entity>,
+    }
+
+    /// Triggered on the `ClientOf` entity of the source shard when a handoff completes.
+    ///
+    /// If the handoff failed, the client stays connected to the source shard.
+    #[derive(EntityEvent, Debug)]
+    pub struct HandoffFinished {
+        pub entity: Entity,
+        pub result: Result<(), HandoffError>,
+    }
+
+    /// Marker inserted on the source shard on the entities that were handed off to another
+    /// shard. They are despawned when `client` disconnects.
+    #[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct HandedOff {
+        /// `ClientOf` entity of the client that was handed off
+        pub client: Entity,
+    }
+
+    struct PendingHandoff {
+        client: Entity,
+        entities: Vec<Entity>,
+    }
+
+    /// Handoffs waiting for the reply of the destination shard, by shard link
+    #[derive(Resource, Default)]
+    struct PendingHandoffs(HashMap<(Entity, RequestId), PendingHandoff>);
+
+    pub(super) fn build(app: &mut App) {
+        app.init_resource::<PendingHandoffs>();
+        app.add_observer(start_handoff);
+        app.add_observer(receive_reply);
+        app.add_observer(accept_handoff);
+        app.add_observer(despawn_handed_off);
+    }
+
+    /// Returns `entities`, the entities controlled by `client` and all their `ReplicateLike`
+    /// children
+    fn collect_entities(world: &World, client: Entity, entities: &[Entity]) -> Vec<Entity> {
+        let mut stack = entities.to_vec();
+        if let Some(controlled) = world.get::<ControlledByRemote>(client) {
+            stack.extend(controlled.iter());
+        }
+        let mut collected = Vec::new();
+        while let Some(entity) = stack.pop() {
+            if collected.contains(&entity) {
+                continue;
+            }
+            if let Some(children) = world.get::<ReplicateLikeChildren>(entity) {
+                stack.extend(children.iter());
+            }
+            collected.push(entity);
+        }
+        collected
+    }
+
+    fn start_handoff(trigger: On<StartHandoff>, mut commands: Commands) {
+        let event = trigger.event().clone();
+        commands.queue(move |world: &mut World| {
+            if let Err(error) = send_request(world, &event) {
+                warn!(client = ?event.entity, "Could not start the handoff: {error}");
+                world.trigger(HandoffFinished {
+                    entity: event.entity,
+                    result: Err(error),
+                });
+            }
+        });
+    }
+
+    fn send_request(world: &mut World, event: &StartHandoff) -> Result<(), HandoffError> {
+        let Some(&RemoteId(client @ PeerId::Netcode(_))) = world.get::<RemoteId>(event.entity)
+        else {
+            return Err(HandoffError::NotNetcode);
+        };
+        if world.get::<ShardLink>(event.shard).is_none() {
+            return Err(HandoffError::NotShardLink);
+        }
+        let entities = collect_entities(world, event.entity, &event.entities);
+        let request = HandoffRequest {
+            client,
+            user_data: world
+                .get::<TokenUserData>(event.entity)
+                .map(|user_data| user_data.0.to_vec()),
+            entities: save_entities(world, &entities)?,
+        };
+        let mut sender = world
+            .get_mut::<RequestSender<HandoffRequest, HandoffReply>>(event.shard)
+            .ok_or(HandoffError::NotShardLink)?;
+        let id = sender.send::<HandoffChannel>(request);
+        debug!(
+            ?client,
+            ?id,
+            num_entities = entities.len(),
+            "Sent handoff request"
+        );
+        world.resource_mut::<PendingHandoffs>().0.insert(
+            (event.shard, id),
+            PendingHandoff {
+                client: event.entity,
+                entities,
+            },
+        );
+        Ok(())
+    }
+
+    /// On the source shard, redirect the client once the destination shard accepted it
+    fn receive_reply(
+        trigger: On<Response<HandoffRequest, HandoffReply>>,
+        mut pending: ResMut<PendingHandoffs>,
+        mut sender: Query<&mut MessageSender<HandoffRedirect>>,
+        mut commands: Commands,
+    ) {
+        let Some(handoff) = pending.0.remove(&(trigger.entity, trigger.id)) else {
+            return;
+        };
+        let result = match &trigger.result {
+            Ok(HandoffReply::Accepted { token, entities }) => {
+                if let Ok(mut sender) = sender.get_mut(handoff.client) {
+                    sender.send::<HandoffChannel>(HandoffRedirect {
+                        token: token.clone(),
+                        entities: entities.clone(),
+                    });
+                    for entity in handoff.entities {
+                        commands.entity(entity).try_insert(HandedOff {
+                            client: handoff.client,
+                        });
+                    }
+                    Ok(())
+                } else {
+                    // the destination shard keeps the loaded entities, and gives their control
+                    // back to the client if it ever connects to it
+                    warn!(client = ?handoff.client, "Client disconnected during the handoff");
+                    Err(HandoffError::Rpc(RpcError::Disconnected))
+                }
+            }
+            Ok(HandoffReply::Rejected(reason)) => Err(HandoffError::Rejected(reason.clone())),
+            Err(error) => Err(HandoffError::Rpc(error.clone())),
+        };
+        commands.trigger(HandoffFinished {
+            entity: handoff.client,
+            result,
+        });
+    }
+
+    /// On the source shard, despawn the handed off entities once their client left
+    fn despawn_handed_off(
+        trigger: On<Add, Disconnected>,
+        query: Query<(Entity, &HandedOff)>,
+        mut commands: Commands,
+    ) {
+        for (entity, handed_off) in query.iter() {
+            if handed_off.client == trigger.entity {
+                commands.entity(entity).try_despawn();
+            }
+        }
+    }
+
+    /// On the destination shard, load the entities and reply with a connect token
+    fn accept_handoff(trigger: On<Request<HandoffRequest>>, mut commands: Commands) {
+        let link = trigger.entity;
+        let id = trigger.id;
+        let request = trigger.request.clone();
+        commands.queue(move |world: &mut World| {
+            let reply = match load_entities(world, link, &request) {
+                Ok((token, entities)) => {
+                    info!(client = ?request.client, "Accepted handoff");
+                    HandoffReply::Accepted { token, entities }
+                }
+                Err(error) => {
+                    warn!(client = ?request.client, "Rejected handoff: {error}");
+                    HandoffReply::Rejected(error.to_string())
+                }
+            };
+            if let Some(mut sender) =
+                world.get_mut::<ResponseSender<HandoffRequest, HandoffReply>>(link)
+            {
+                sender.respond::<HandoffChannel>(id, reply);
+            }
+        });
+    }
+
+    fn load_entities(
+        world: &mut World,
+        link: Entity,
+        request: &HandoffRequest,
+    ) -> Result<(Vec<u8>, Vec<(Entity, Entity)>), HandoffError> {
+        if world.get::<ShardLink>(link).is_none() {
+            return Err(HandoffError::NotShardLink);
+        }
+        let PeerId::Netcode(client_id) = request.client else {
+            return Err(HandoffError::NotNetcode);
+        };
+        let server = world
+            .query_filtered::<Entity, (With<AcceptHandoffs>, With<NetcodeServer>)>()
+            .iter(world)
+            .next()
+            .ok_or(HandoffError::NoServer)?;
+        let entity_map = load_snapshot(world, &request.entities)?;
+        let entities: Vec<_> = entity_map
+            .iter()
+            .map(|(source, destination)| (*source, *destination))
+            .collect();
+        let token = generate_token(world, server, client_id, request.user_data.as_deref());
+        if token.is_err() {
+            for (_, entity) in entities.iter() {
+                world.try_despawn(*entity).ok();
+            }
+        }
+        Ok((token?, entities))
+    }
+
+    fn generate_token(
+        world: &mut World,
+        server: Entity,
+        client_id: u64,
+        user_data: Option<&[u8]>,
+    ) -> Result<Vec<u8>, HandoffError> {
+        let config = world.get::<AcceptHandoffs>(server).unwrap().clone();
+        let mut netcode = world.get_mut::<NetcodeServer>(server).unwrap();
+        let mut builder = netcode
+            .token(client_id, config.client_address)
+            .expire_seconds(config.token_expire_secs)
+            .timeout_seconds(config.client_timeout_secs);
+        if let Some(user_data) = user_data {
+            let mut data = [0; USER_DATA_BYTES];
+            let len = user_data.len().min(USER_DATA_BYTES);
+            data[..len].copy_from_slice(&user_data[..len]);
+            builder = builder.user_data(data);
+        }
+        let token = builder.generate()?;
+        let bytes = token
+            .try_into_bytes()
+            .map_err(lightyear_netcode::Error::from)?;
+        Ok(bytes.to_vec())
+    }
+}
+
+#[cfg(feature = "client")]
+mod client {
+    use super::*;
+    use bevy_app::{PostUpdate, PreUpdate};
+    use lightyear_connection::ConnectionSystems;
+    use lightyear_connection::client::{Client, Connect, Connected, Disconnect, Disconnected};
+    use lightyear_messages::prelude::{MessageReceiver, MessageSystems};
+    use lightyear_netcode::ConnectToken;
+    use lightyear_netcode::auth::Authentication;
+    use lightyear_netcode::client_plugin::{NetcodeClient, NetcodeConfig};
+    use lightyear_replication::prelude::client::PremappedEntities;
+    use lightyear_replication::prelude::{Persistent, Replicated};
+    use tracing::{error, info};
+
+    /// Handoff in progress on the client
+    #[derive(Component)]
+    struct ClientHandoff {
+        /// Token for the destination shard, taken when the client reconnects
+        token: Option<ConnectToken>,
+        /// `(destination shard entity, local entity)` pairs
+        premapped: Vec<(Entity, Entity)>,
+        /// Local entities on which [`Persistent`] was inserted for the handoff
+        persistent: Vec<Entity>,
+    }
+
+    pub(super) fn build(app: &mut App) {
+        app.add_systems(
+            PreUpdate,
+            (
+                reconnect.before(ConnectionSystems::Receive),
+                receive_redirect.after(MessageSystems::Receive),
+            ),
+        );
+        app.add_systems(PostUpdate, finish_handoff);
+        app.add_observer(handoff_failed);
+    }
+
+    /// Disconnect from the source shard when it redirects the client.
+    ///
+    /// The disconnection packets are flushed at the end of the frame, so the client connects to
+    /// the destination shard in the next frame.
+    fn receive_redirect(
+        mut query: Query<
+            (Entity, &mut MessageReceiver<HandoffRedirect>),
+            (With<Client>, With<Connected>, Without<ShardLink>),
+        >,
+        replicated: Query<Has<Persistent>, With<Replicated>>,
+        mut commands: Commands,
+    ) {
+        for (client, mut receiver) in query.iter_mut() {
+            let Some(redirect) = receiver.receive().last() else {
+                continue;
+            };
+            let token = match ConnectToken::try_from_bytes(&redirect.token) {
+                Ok(token) => token,
+                Err(e) => {
+                    error!("Received an invalid handoff token: {e:?}");
+                    continue;
+                }
+            };
+            let mut premapped = Vec::new();
+            let mut persistent = Vec::new();
+            for (local, destination) in redirect.entities {
+                let Ok(is_persistent) = replicated.get(local) else {
+                    continue;
+                };
+                if !is_persistent {
+                    commands.entity(local).insert(Persistent);
+                    persistent.push(local);
+                }
+                premapped.push((destination, local));
+            }
+            info!(
+                num_entities = premapped.len(),
+                "Handing off the client to another server"
+            );
+            commands.entity(client).insert(ClientHandoff {
+                token: Some(token),
+                premapped,
+                persistent,
+            });
+            commands.trigger(Disconnect { entity: client });
+        }
+    }
+
+    /// Connect to the destination shard once the client is disconnected from the source shard
+    fn reconnect(
+        mut query: Query<(Entity, &mut ClientHandoff), With<Disconnected>>,
+        mut commands: Commands,
+    ) {
+        for (client, mut handoff) in query.iter_mut() {
+            let Some(token) = handoff.token.take() else {
+                continue;
+            };
+            let netcode =
+                match NetcodeClient::new(Authentication::Token(token), NetcodeConfig::default()) {
+                    Ok(netcode) => netcode,
+                    Err(e) => {
+                        error!("Could not connect to the destination server: {e:?}");
+                        for entity in handoff.persistent.iter() {
+                            commands.entity(*entity).try_despawn();
+                        }
+                        commands.entity(client).remove::<ClientHandoff>();
+                        continue;
+                    }
+                };
+            commands
+                .entity(client)
+                .insert((netcode, PremappedEntities(handoff.premapped.clone())));
+            commands.trigger(Connect { entity: client });
+        }
+    }
+
+    /// Stop keeping the entities alive once they are mapped to the destination shard
+    fn finish_handoff(
+        query: Query<(Entity, &ClientHandoff), (With<Connected>, Without<PremappedEntities>)>,
+        mut commands: Commands,
+    ) {
+        for (client, handoff) in query.iter() {
+            if handoff.token.is_some() {
+                continue;
+            }
+            for entity in handoff.persistent.iter() {
+                commands.entity(*entity).try_remove::<Persistent>();
+            }
+            commands.entity(client).remove::<ClientHandoff>();
+        }
+    }
+
+    /// Despawn the entities kept for the handoff if the connection to the destination shard fails
+    fn handoff_failed(
+        trigger: On<Add, Disconnected>,
+        query: Query<&ClientHandoff>,
+        mut commands: Commands,
+    ) {
+        let Ok(handoff) = query.get(trigger.entity) else {
+            return;
+        };
+        if handoff.token.is_some() {
+            // this is the disconnection from the source shard
+            return;
+        }
+        error!("Could not connect to the destination server of the handoff");
+        for entity in handoff.persistent.iter() {
+            commands.entity(*entity).try_despawn();
+        }
+        commands.entity(trigger.entity).remove::<ClientHandoff>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::entity::EntityHashMap;
+
+    #[test]
+    fn redirect_maps_source_entities_only() {
+        let mut world = World::new();
+        let source = world.spawn_empty().id();
+        let local = world.spawn_empty().id();
+        let destination = world.spawn_empty().id();
+
+        let mut map = EntityHashMap::default();
+        map.insert(source, local);
+        map.insert(destination, local);
+        let mut redirect = HandoffRedirect {
+            token: vec![],
+            entities: vec![(source, destination)],
+        };
+        redirect.map_entities(&mut map);
+        assert_eq!(redirect.entities, vec![(local, destination)]);
+    }
+}
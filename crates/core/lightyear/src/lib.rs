@@ -95,6 +95,10 @@ There are several steps:
 
 NOTE: the protocol must currently be added AFTER the Client/Server Plugins, but BEFORE any `Client` or `Server` entity is spawned.
 
+When a netcode client connects, the hash of its protocol and [`AppVersion`](prelude::AppVersion) is compared with the server's.
+A client with a different protocol is denied before it is connected, with [`DisconnectedReason::Denied`](prelude::DisconnectedReason::Denied),
+which lists the types that differ. See the [`protocol`] module.
+
 
 ### Spawn your Link entity
 
@@ -302,8 +306,11 @@ mod server;
 
 mod shared;
 
+#[cfg(all(feature = "replication", feature = "netcode"))]
+pub mod handoff;
+
 #[cfg(feature = "replication")]
-mod protocol;
+pub mod protocol;
 
 pub mod core {
     pub use lightyear_core::*;
@@ -343,6 +350,11 @@ pub mod prediction {
     pub use lightyear_prediction::*;
 }
 
+#[cfg(feature = "replication")]
+pub mod replication {
+    pub use lightyear_replication::*;
+}
+
 #[cfg(feature = "steam")]
 pub mod steam {
     pub use lightyear_steam::*;
@@ -58,7 +58,7 @@ impl PluginGroup for ServerPlugins {
         });
 
         #[cfg(feature = "replication")]
-        let builder = builder.add(LightyearRepliconServerBackend);
+        let builder = builder.add(LightyearRepliconServerBackend::default());
 
         let builder = builder.add(lightyear_connection::host::HostPlugin);
 
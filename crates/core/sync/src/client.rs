@@ -2,6 +2,7 @@
 */
 use crate::plugin::TimelineSyncPlugin;
 use crate::prelude::client::RemoteTimeline;
+use crate::timeline::feedback::receive_input_timing_reports;
 use crate::timeline::input::InputTimelineConfig;
 use crate::timeline::remote;
 use crate::timeline::sync::LocalTimelineSyncPlugin;
@@ -9,6 +10,7 @@ use bevy_app::prelude::*;
 use bevy_ecs::prelude::IntoScheduleConfigs;
 use lightyear_connection::client::Client;
 use lightyear_core::prelude::TimelineSystems;
+use lightyear_messages::plugin::MessageSystems;
 
 // When a Client is created; we want to add a PredictedTimeline? InterpolatedTimeline?
 //  or should we let the user do it?
@@ -58,6 +60,10 @@ impl Plugin for ClientPlugin {
         // while every runtime configuration update, connection, and sync event happens after it.
         app.add_observer(InputTimelineConfig::recompute_input_delay_on_local_timeline_shift);
         app.add_observer(InputTimelineConfig::recompute_input_delay_on_config_update);
+        app.add_systems(
+            PreUpdate,
+            receive_input_timing_reports.after(MessageSystems::Receive),
+        );
 
         // remote timeline
         app.add_observer(RemoteTimeline::handle_connect);
@@ -4,6 +4,7 @@
 //! It handles time synchronization between peers, including:
 //! - Ping and RTT estimation (`ping`).
 //! - Timeline synchronization to align game state across different peers (`timeline`).
+//! - Server feedback on how early the client inputs arrive, to adapt the client's lead (`timeline::feedback`).
 //! - Client and server-specific synchronization logic.
 //!
 //! The core idea is to allow peers to maintain a synchronized understanding of game time,
@@ -37,6 +38,7 @@ pub mod prelude {
     pub use crate::ping::manager::{PingConfig, PingManager};
     pub use crate::ping::message::{Ping, Pong};
     pub use crate::plugin::{SyncSystems, TimelineSyncPlugin};
+    pub use crate::timeline::feedback::{InputFeedbackConfig, InputTimingReport};
     pub use crate::timeline::input::{
         InputTimelineConfig, LocalTimelineSync, PREDICTION_WINDOW_HYSTERESIS_TICKS,
         PredictionWindowWait, SyncedLocalTimeline,
@@ -45,6 +47,7 @@ pub mod prelude {
 
     #[cfg(feature = "client")]
     pub mod client {
+        pub use crate::timeline::feedback::InputFeedbackConfig;
         pub use crate::timeline::input::{
             InputDelayConfig, InputTimelineConfig, LocalTimelineSync,
             PREDICTION_WINDOW_HYSTERESIS_TICKS, PredictionWindowWait, SyncedLocalTimeline,
@@ -54,5 +57,7 @@ pub mod prelude {
     }
 
     #[cfg(feature = "server")]
-    pub mod server {}
+    pub mod server {
+        pub use crate::timeline::feedback::InputArrival;
+    }
 }
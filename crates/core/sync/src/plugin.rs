@@ -1,6 +1,9 @@
 use crate::ping::plugin::PingPlugin;
+use crate::timeline::feedback::InputTimingReport;
 use bevy_app::{App, Plugin, PostUpdate};
 use bevy_ecs::schedule::SystemSet;
+use lightyear_connection::direction::NetworkDirection;
+use lightyear_messages::prelude::AppMessageExt;
 
 #[deprecated(note = "Use SyncSystems instead")]
 pub type SyncSet = SyncSystems;
@@ -18,6 +21,8 @@ impl Plugin for TimelineSyncPlugin {
         if !app.is_plugin_added::<PingPlugin>() {
             app.add_plugins(PingPlugin);
         }
+        app.register_message_to_bytes::<InputTimingReport>()
+            .add_direction(NetworkDirection::ServerToClient);
         app.configure_sets(PostUpdate, SyncSystems::Sync);
     }
 }
@@ -1,6 +1,10 @@
-use bevy_app::{App, Plugin};
+use bevy_app::{App, Plugin, PostUpdate};
+use bevy_ecs::prelude::IntoScheduleConfigs;
+use lightyear_connection::prelude::server::ClientOf;
+use lightyear_messages::plugin::MessageSystems;
 
 use crate::plugin::TimelineSyncPlugin;
+use crate::timeline::feedback::{InputArrival, send_input_timing_reports};
 
 pub struct ServerPlugin;
 
@@ -9,5 +13,12 @@ impl Plugin for ServerPlugin {
         if !app.is_plugin_added::<TimelineSyncPlugin>() {
             app.add_plugins(TimelineSyncPlugin);
         }
+
+        // measure how early the inputs of each client arrive, and report it to the client
+        app.register_required_components::<ClientOf, InputArrival>();
+        app.add_systems(
+            PostUpdate,
+            send_input_timing_reports.before(MessageSystems::Send),
+        );
     }
 }
@@ -0,0 +1,262 @@
+//! Server feedback on the arrival time of the client inputs.
+//!
+//! The [`LocalTimelineSync`](crate::timeline::input::LocalTimelineSync) objective only relies on the RTT and jitter estimated by the client.
+//! The server knows better: when it receives an input message it can measure by how many ticks
+//! the inputs arrived before the server simulated the tick they are for. A negative margin means
+//! that the input arrived too late and the server had to predict it.
+//!
+//! The server records these margins in the [`InputArrival`] component of each client and
+//! periodically sends an [`InputTimingReport`] with their mean to the client. The mean is used
+//! rather than the smallest margin: with jitter, the smallest margin of an interval mostly
+//! measures the worst network delay of the interval, so it is noisy and biased towards late
+//! inputs. The late inputs are counted separately. If
+//! [`InputTimelineConfig::with_input_feedback`](crate::timeline::input::InputTimelineConfig::with_input_feedback)
+//! is set, the client then runs slightly further
+//! ahead or behind the server to keep the margin close to
+//! [`InputFeedbackConfig::target_margin_ticks`].
+#[cfg(feature = "client")]
+use crate::timeline::input::{InputTimelineConfig, LocalTimelineSync};
+use bevy_ecs::prelude::*;
+use bevy_reflect::Reflect;
+use core::time::Duration;
+use lightyear_serde::SerializationError;
+use lightyear_serde::ToBytes;
+use lightyear_serde::reader::{ReadInteger, Reader};
+use lightyear_serde::writer::WriteInteger;
+#[allow(unused_imports)]
+use tracing::trace;
+
+/// Message sent by the server to report how early the inputs of the client arrive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub struct InputTimingReport {
+    /// Mean number of ticks between the arrival of an input message and the simulation of
+    /// its last tick over the report interval, rounded to the nearest tick.
+    pub margin: i16,
+    /// Number of input messages that arrived too late over the report interval.
+    pub late_messages: u16,
+}
+
+impl ToBytes for InputTimingReport {
+    fn bytes_len(&self) -> usize {
+        2 + self.late_messages.bytes_len()
+    }
+
+    fn to_bytes(&self, buffer: &mut impl WriteInteger) -> Result<(), SerializationError> {
+        buffer.write_i16(self.margin)?;
+        self.late_messages.to_bytes(buffer)
+    }
+
+    fn from_bytes(buffer: &mut Reader) -> Result<Self, SerializationError>
+    where
+        Self: Sized,
+    {
+        Ok(Self {
+            margin: buffer.read_i16()?,
+            late_messages: u16::from_bytes(buffer)?,
+        })
+    }
+}
+
+/// Client-side settings of the input timing feedback loop.
+#[derive(Clone, Copy, Debug, Reflect)]
+pub struct InputFeedbackConfig {
+    /// Mean number of ticks of margin with which the inputs should arrive on the server.
+    ///
+    /// This is the size of the jitter buffer on the server: a larger margin makes late inputs less
+    /// likely but increases the amount of prediction done by the client. It should cover the
+    /// jitter of the client's upload path, since the inputs only arrive with this margin on average.
+    pub target_margin_ticks: f32,
+    /// Fraction of the error reported by the server that is corrected on each report.
+    ///
+    /// The reports are only sent a few times per second and are delayed by the network, so
+    /// correcting the full error at once would overshoot.
+    pub gain: f32,
+    /// Maximum number of ticks by which the feedback can move the timeline away from the
+    /// objective computed from the RTT and jitter estimates.
+    pub max_correction_ticks: f32,
+}
+
+impl Default for InputFeedbackConfig {
+    fn default() -> Self {
+        Self {
+            target_margin_ticks: 1.0,
+            gain: 0.5,
+            max_correction_ticks: 4.0,
+        }
+    }
+}
+
+/// Measures, on the server, how early the inputs of a client arrive.
+///
+/// Added automatically on every `ClientOf` entity. The input plugins call
+/// [`InputArrival::record`] for every input message received.
+#[derive(Component, Debug, Clone, Reflect)]
+pub struct InputArrival {
+    report_interval: Duration,
+    /// Sum of the margins recorded since the last report
+    margin_sum: i64,
+    /// Number of margins recorded since the last report
+    margin_count: u32,
+    late_messages: u16,
+    last_report: Option<InputTimingReport>,
+    last_report_time: Duration,
+}
+
+impl Default for InputArrival {
+    fn default() -> Self {
+        Self {
+            report_interval: Duration::from_millis(250),
+            margin_sum: 0,
+            margin_count: 0,
+            late_messages: 0,
+            last_report: None,
+            last_report_time: Duration::ZERO,
+        }
+    }
+}
+
+impl InputArrival {
+    /// Interval between two [`InputTimingReport`]s sent to the client.
+    pub fn with_report_interval(mut self, report_interval: Duration) -> Self {
+        self.report_interval = report_interval;
+        self
+    }
+
+    /// Record that an input message arrived `margin` ticks before the server simulates its
+    /// last tick.
+    pub fn record(&mut self, margin: i32) {
+        self.margin_sum += i64::from(margin);
+        self.margin_count = self.margin_count.saturating_add(1);
+        if margin < 0 {
+            self.late_messages = self.late_messages.saturating_add(1);
+        }
+    }
+
+    /// Last report sent to the client.
+    pub fn last_report(&self) -> Option<InputTimingReport> {
+        self.last_report
+    }
+
+    /// Returns the report to send if the report interval has elapsed and inputs were received.
+    pub(crate) fn take_report(&mut self, now: Duration) -> Option<InputTimingReport> {
+        if now.saturating_sub(self.last_report_time) < self.report_interval {
+            return None;
+        }
+        if self.margin_count == 0 {
+            return None;
+        }
+        let count = i64::from(core::mem::take(&mut self.margin_count));
+        let sum = core::mem::take(&mut self.margin_sum);
+        // mean rounded to the nearest tick
+        let margin = (2 * sum + count).div_euclid(2 * count);
+        let report = InputTimingReport {
+            margin: margin.clamp(i16::MIN as i64, i16::MAX as i64) as i16,
+            late_messages: core::mem::take(&mut self.late_messages),
+        };
+        self.last_report = Some(report);
+        self.last_report_time = now;
+        Some(report)
+    }
+}
+
+#[cfg(feature = "server")]
+pub(crate) fn send_input_timing_reports(
+    real_time: Res<bevy_time::Time<bevy_time::Real>>,
+    mut query: Query<
+        (
+            Entity,
+            &mut InputArrival,
+            &mut lightyear_messages::send::MessageSender<InputTimingReport>,
+        ),
+        (
+            With<lightyear_connection::client::Connected>,
+            Without<lightyear_connection::host::HostClient>,
+        ),
+    >,
+) {
+    let now = real_time.elapsed();
+    query
+        .iter_mut()
+        .for_each(|(entity, mut arrival, mut sender)| {
+            if let Some(report) = arrival.take_report(now) {
+                trace!(?entity, ?report, "sending input timing report");
+                sender.send::<crate::ping::PingChannel>(report);
+            }
+        });
+}
+
+#[cfg(feature = "client")]
+pub(crate) fn receive_input_timing_reports(
+    config: Res<InputTimelineConfig>,
+    mut sync: ResMut<LocalTimelineSync>,
+    mut query: Query<
+        &mut lightyear_messages::receive::MessageReceiver<InputTimingReport>,
+        With<lightyear_connection::client::Client>,
+    >,
+) {
+    query.iter_mut().for_each(|mut receiver| {
+        receiver.receive().for_each(|report| {
+            trace!(?report, "received input timing report");
+            sync.apply_input_timing_report(&report, config.input_feedback.as_ref());
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lightyear_serde::writer::Writer;
+
+    #[test]
+    fn input_timing_report_round_trip() {
+        let report = InputTimingReport {
+            margin: -3,
+            late_messages: 12,
+        };
+        let mut writer = Writer::default();
+        report.to_bytes(&mut writer).unwrap();
+        assert_eq!(writer.len(), report.bytes_len());
+        let mut reader = Reader::from(writer.into_bytes());
+        assert_eq!(InputTimingReport::from_bytes(&mut reader).unwrap(), report);
+    }
+
+    #[test]
+    fn input_arrival_reports_the_mean_margin() {
+        let mut arrival = InputArrival::default().with_report_interval(Duration::from_millis(100));
+        // no inputs received: nothing to report
+        assert_eq!(arrival.take_report(Duration::from_millis(100)), None);
+
+        arrival.record(3);
+        arrival.record(-1);
+        arrival.record(3);
+        assert_eq!(
+            arrival.take_report(Duration::from_millis(150)),
+            Some(InputTimingReport {
+                margin: 2,
+                late_messages: 1,
+            })
+        );
+
+        // the report interval has not elapsed
+        arrival.record(4);
+        assert_eq!(arrival.take_report(Duration::from_millis(200)), None);
+        assert_eq!(
+            arrival.take_report(Duration::from_millis(250)),
+            Some(InputTimingReport {
+                margin: 4,
+                late_messages: 0,
+            })
+        );
+
+        // the mean is rounded to the nearest tick
+        arrival.record(-2);
+        arrival.record(-1);
+        assert_eq!(
+            arrival.take_report(Duration::from_millis(350)),
+            Some(InputTimingReport {
+                margin: -1,
+                late_messages: 2,
+            })
+        );
+    }
+}
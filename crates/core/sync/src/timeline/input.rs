@@ -1,4 +1,5 @@
 use crate::ping::manager::PingManager;
+use crate::timeline::feedback::{InputFeedbackConfig, InputTimingReport};
 use crate::timeline::sync::{SyncConfig, SyncContext, SyncTargetTimeline, TimelineSync};
 
 use bevy_ecs::change_detection::Tick as ChangeTick;
@@ -24,6 +25,7 @@ use tracing::trace;
 pub struct InputTimelineConfig {
     pub(crate) sync: SyncConfig,
     pub(crate) input_delay_config: InputDelayConfig,
+    pub(crate) input_feedback: Option<InputFeedbackConfig>,
 }
 
 impl InputTimelineConfig {
@@ -31,6 +33,7 @@ impl InputTimelineConfig {
         Self {
             sync: sync_config,
             input_delay_config: input_delay,
+            input_feedback: None,
         }
     }
 
@@ -44,6 +47,15 @@ impl InputTimelineConfig {
         self
     }
 
+    /// Use the [`InputTimingReport`]s sent by the server to adjust how far ahead of the server
+    /// the client runs.
+    ///
+    /// Without it, the client relies only on its own RTT and jitter estimates.
+    pub fn with_input_feedback(mut self, input_feedback: InputFeedbackConfig) -> Self {
+        self.input_feedback = Some(input_feedback);
+        self
+    }
+
     /// Returns the true if the timeline is configured for deterministic lockstep mode,
     /// where all the latency is covered by input delay, and no prediction is done.
     #[inline]
@@ -241,6 +253,7 @@ impl Default for InputTimelineConfig {
         Self {
             sync: SyncConfig::default(),
             input_delay_config: InputDelayConfig::no_input_delay(),
+            input_feedback: None,
         }
     }
 }
@@ -254,6 +267,9 @@ pub struct LocalTimelineSync {
     controller: SyncContext,
     /// Current input_delay_ticks that are being applied
     input_delay_ticks: u16,
+    /// Ticks added to the sync objective from the server's input timing reports
+    input_feedback_offset: f32,
+    last_input_timing_report: Option<InputTimingReport>,
     relative_speed: f32,
     is_synced: bool,
 }
@@ -281,6 +297,42 @@ impl LocalTimelineSync {
     pub fn relative_speed(&self) -> f32 {
         self.relative_speed
     }
+
+    /// Return the last [`InputTimingReport`] received from the server.
+    pub fn last_input_timing_report(&self) -> Option<InputTimingReport> {
+        self.last_input_timing_report
+    }
+
+    /// Return the number of ticks that the server feedback adds to the sync objective.
+    pub fn input_feedback_offset(&self) -> f32 {
+        self.input_feedback_offset
+    }
+
+    /// Move the sync objective so that the inputs arrive on the server with the target mean margin.
+    ///
+    /// The offset only changes by a fraction of the reported error, because the report reflects
+    /// the state of the timeline one report interval and one network trip ago.
+    pub(crate) fn apply_input_timing_report(
+        &mut self,
+        report: &InputTimingReport,
+        config: Option<&InputFeedbackConfig>,
+    ) {
+        self.last_input_timing_report = Some(*report);
+        let Some(config) = config else {
+            return;
+        };
+        if !self.is_synced {
+            return;
+        }
+        let error = config.target_margin_ticks - f32::from(report.margin);
+        self.input_feedback_offset = (self.input_feedback_offset + error * config.gain)
+            .clamp(-config.max_correction_ticks, config.max_correction_ticks);
+        trace!(
+            ?report,
+            input_feedback_offset = self.input_feedback_offset,
+            "applied input timing report"
+        );
+    }
 }
 
 impl Default for LocalTimelineSync {
@@ -288,6 +340,8 @@ impl Default for LocalTimelineSync {
         Self {
             controller: SyncContext::default(),
             input_delay_ticks: 0,
+            input_feedback_offset: 0.0,
+            last_input_timing_report: None,
             relative_speed: 1.0,
             is_synced: false,
         }
@@ -624,14 +678,23 @@ impl TimelineSync for LocalTimelineSync {
         // `sync_error_margin` compensates for the sync controller's allowed
         // deadband: the controller may let the local timeline drift behind the
         // objective by this much without correcting.
-        let obj =
-            remote + network_delay + jitter_margin + TickDelta::from_i32(1) + sync_error_margin
-                - input_delay;
+        //
+        // `input_feedback` corrects the estimate with the margins actually
+        // measured by the server (zero unless input feedback is enabled).
+        let input_feedback = TickDelta::from_i32(1) * self.input_feedback_offset;
+        let obj = remote
+            + network_delay
+            + jitter_margin
+            + TickDelta::from_i32(1)
+            + sync_error_margin
+            + input_feedback
+            - input_delay;
         trace!(
             ?remote,
             ?network_delay,
             ?jitter_margin,
             ?sync_error_margin,
+            ?input_feedback,
             ?input_delay,
             "LocalTimeline sync objective: {:?}",
             obj
@@ -662,6 +725,8 @@ impl TimelineSync for LocalTimelineSync {
     fn reset(&mut self) {
         trace!("Resetting LocalTimelineSync");
         self.controller = SyncContext::default();
+        self.input_feedback_offset = 0.0;
+        self.last_input_timing_report = None;
         self.is_synced = false;
         self.relative_speed = 1.0;
     }
@@ -810,6 +875,53 @@ mod tests {
         assert_tick_instant_close(objective, TickInstant::lit("103.75"));
     }
 
+    #[test]
+    fn input_timing_reports_offset_local_timeline_objective() {
+        let tick_duration = Duration::from_millis(10);
+        let mut remote = RemoteTimeline::default();
+        remote.set_now(TickInstant::from(Tick(100)));
+
+        let mut ping_manager = PingManager::default();
+        ping_manager.rtt_estimator_ewma.final_stats.rtt = Duration::from_millis(40);
+        ping_manager.rtt_estimator_ewma.final_stats.jitter = Duration::from_millis(5);
+
+        let mut config = InputTimelineConfig::default()
+            .with_input_delay(InputDelayConfig::fixed_input_delay(2))
+            .with_input_feedback(InputFeedbackConfig::default());
+        config.sync.jitter_multiple = 2;
+        config.sync.jitter_margin = 1.0;
+        config.sync.error_margin = 0.75;
+
+        let mut timeline = LocalTimelineSync::default();
+        timeline.input_delay_ticks = 2;
+        let report = |margin| InputTimingReport {
+            margin,
+            late_messages: 0,
+        };
+
+        // reports received before the initial sync are ignored
+        timeline.apply_input_timing_report(&report(5), config.input_feedback.as_ref());
+        assert_eq!(timeline.input_feedback_offset(), 0.0);
+        assert_eq!(timeline.last_input_timing_report(), Some(report(5)));
+
+        // the inputs arrive 4 ticks earlier than needed: half of it is corrected
+        timeline.set_synced(true);
+        timeline.apply_input_timing_report(&report(5), config.input_feedback.as_ref());
+        assert_eq!(timeline.input_feedback_offset(), -2.0);
+        let objective = timeline.sync_objective(&remote, &config, &ping_manager, tick_duration);
+        assert_tick_instant_close(objective, TickInstant::lit("101.75"));
+
+        // late inputs move the objective forward, up to the maximum correction
+        timeline.apply_input_timing_report(&report(-10), config.input_feedback.as_ref());
+        timeline.apply_input_timing_report(&report(-10), config.input_feedback.as_ref());
+        assert_eq!(timeline.input_feedback_offset(), 4.0);
+        let objective = timeline.sync_objective(&remote, &config, &ping_manager, tick_duration);
+        assert_tick_instant_close(objective, TickInstant::lit("107.75"));
+
+        timeline.reset();
+        assert_eq!(timeline.input_feedback_offset(), 0.0);
+    }
+
     /// The server reads inputs in `FixedPreUpdate`, after receiving packets in
     /// `PreUpdate` and advancing its tick in `FixedFirst`. Inputs sent by the
     /// client must therefore target at least `remote + 1`, even under
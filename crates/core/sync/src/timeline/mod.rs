@@ -1,3 +1,4 @@
+pub mod feedback;
 pub mod input;
 pub mod remote;
 pub mod sync;
@@ -57,6 +57,13 @@ impl<K: TypeKind> TypeMapper<K> {
     pub fn name(&self, kind: &K) -> Option<&'static str> {
         self.kind_map.get(kind).map(|(_, name)| *name)
     }
+
+    /// Iterate through the network id and the type name of every registered type
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &'static str)> + '_ {
+        self.kind_map
+            .values()
+            .map(|(net_id, name)| (*net_id, *name))
+    }
 }
 
 #[derive(Clone)]
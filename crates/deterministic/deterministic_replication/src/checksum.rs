@@ -441,49 +441,10 @@ impl ChecksumReceivePlugin {
         timeline: Res<LocalTimeline>,
         server: Single<&mut ChecksumHistory, With<Started>>,
     ) {
-        let mut checksum = 0u64;
         let tick = timeline.tick();
-        let mut history = server.into_inner();
-
-        world.update_archetypes();
-        // SAFETY: world.update_archetypes() has been called
-        unsafe { world.iter_archetypes() }.for_each(|(archetype, checksum_archetype)| {
-            // TODO: guarantee stable entity iteration order across peers.
-            archetype.entities().iter().for_each(|entity| {
-                checksum_archetype
-                    .components
-                    .iter()
-                    .for_each(|(component_id, storage_type)| {
-                        trace!(
-                            "Adding component {:?} from entity {:?} to checksum for tick {:?}",
-                            component_id,
-                            entity.id(),
-                            tick
-                        );
-                        // SAFETY: the way we constructed the archetypes guarantees that the component exists on the entity and we have unique write access
-                        let component_ptr = unsafe {
-                            lightyear_utils::ecs::get_component_unchecked(
-                                world.world,
-                                entity,
-                                archetype.table_id(),
-                                *storage_type,
-                                *component_id,
-                            )
-                        };
-                        let (hash_fn, _) = world.state.hash_fns.get(component_id).expect(
-                            "Component in checksum archetype must have a hash function registered",
-                        );
-                        let mut hasher = seahash::SeaHasher::default();
-                        hash_fn.hash_component(component_ptr, &mut hasher);
-                        let hash = hasher.finish();
-                        checksum ^= hash; // XOR the hashes together to get an order-independent checksum
-                    });
-            });
-        });
-
+        let checksum = compute_checksum(&mut world, tick);
         debug!("Computed checksum for tick {:?}: {:016x}", tick, checksum);
-
-        history.history.insert(tick, checksum);
+        server.into_inner().history.insert(tick, checksum);
     }
 
     fn receive_checksum_message(
@@ -521,6 +482,49 @@ impl ChecksumReceivePlugin {
     }
 }
 
+/// Compute a checksum over the hashable components of all deterministic entities, using their
+/// current value.
+#[cfg(feature = "server")]
+pub(crate) fn compute_checksum(world: &mut ChecksumWorld<'_, '_, false>, tick: Tick) -> u64 {
+    let mut checksum = 0u64;
+    world.update_archetypes();
+    // SAFETY: world.update_archetypes() has been called
+    unsafe { world.iter_archetypes() }.for_each(|(archetype, checksum_archetype)| {
+        // TODO: guarantee stable entity iteration order across peers.
+        archetype.entities().iter().for_each(|entity| {
+            checksum_archetype
+                .components
+                .iter()
+                .for_each(|(component_id, storage_type)| {
+                    trace!(
+                        "Adding component {:?} from entity {:?} to checksum for tick {:?}",
+                        component_id,
+                        entity.id(),
+                        tick
+                    );
+                    // SAFETY: the way we constructed the archetypes guarantees that the component exists on the entity and we have unique write access
+                    let component_ptr = unsafe {
+                        lightyear_utils::ecs::get_component_unchecked(
+                            world.world,
+                            entity,
+                            archetype.table_id(),
+                            *storage_type,
+                            *component_id,
+                        )
+                    };
+                    let (hash_fn, _) = world.state.hash_fns.get(component_id).expect(
+                        "Component in checksum archetype must have a hash function registered",
+                    );
+                    let mut hasher = seahash::SeaHasher::default();
+                    hash_fn.hash_component(component_ptr, &mut hasher);
+                    let hash = hasher.finish();
+                    checksum ^= hash; // XOR the hashes together to get an order-independent checksum
+                });
+        });
+    });
+    checksum
+}
+
 #[cfg(any(feature = "p2p", feature = "server"))]
 impl Plugin for ChecksumReceivePlugin {
     fn build(&self, app: &mut App) {
@@ -11,18 +11,23 @@
 //!   to the current tick via a forced rollback.
 //! - [`DeterministicReplicationPlugin`] wires up the shared archetype
 //!   index used by both features.
+//! - [`MatchRecorderPlugin`] records the inputs applied by the server and
+//!   the checksum of every tick, and [`ReplayPlugin`] replays them offline
+//!   to find the first tick where the simulation diverges.
 //!
 //! [`ChecksumPlugin`]: crate::prelude::ChecksumPlugin
 //! [`ChecksumSendPlugin`]: crate::prelude::ChecksumSendPlugin
 //! [`ChecksumReceivePlugin`]: crate::prelude::ChecksumReceivePlugin
 //! [`LateJoinCatchUpPlugin`]: crate::prelude::LateJoinCatchUpPlugin
 //! [`DeterministicReplicationPlugin`]: crate::prelude::DeterministicReplicationPlugin
+//! [`MatchRecorderPlugin`]: crate::replay::MatchRecorderPlugin
+//! [`ReplayPlugin`]: crate::replay::ReplayPlugin
 
 #![no_std]
 
 extern crate alloc;
 extern crate core;
-#[cfg(test)]
+#[cfg(any(test, feature = "std"))]
 extern crate std;
 
 use bevy_ecs::component::Component;
@@ -39,6 +44,9 @@ pub mod mode;
 mod plugin;
 #[cfg(feature = "client")]
 mod prediction_window;
+#[cfg(feature = "server")]
+/// Recording of the inputs of a match, and offline replay.
+pub mod replay;
 
 /// Commonly used items from the `lightyear_deterministic_replication` crate.
 pub mod prelude {
@@ -58,6 +66,11 @@ pub mod prelude {
     pub use crate::late_join::{CatchUpClientTimeout, CatchUpManager};
     pub use crate::mode::CatchUpMode;
     pub use crate::plugin::DeterministicReplicationPlugin;
+    #[cfg(feature = "server")]
+    pub use crate::replay::{
+        ChecksumDivergence, MatchRecorder, MatchRecorderPlugin, MatchRecording, MatchReplay,
+        RecordingError, ReplayPlugin,
+    };
     pub use lightyear_prediction::rollback::CatchUpGated;
 }
 
@@ -0,0 +1,652 @@
+//! Record the inputs of a match on the server and replay them offline.
+//!
+//! In a deterministic game, a match can be reproduced from its initial state and from the inputs
+//! that the server applied at each tick. The [`MatchRecorder`] stores, for every tick of the
+//! server's [`LocalTimeline`]:
+//! - the input that [`lightyear_inputs::server`] applied to each entity with an [`InputBuffer`];
+//! - the checksum of the deterministic entities at the end of the tick, computed like the
+//!   [`ChecksumMessage`]s that the clients send.
+//!
+//! The [`ReplayPlugin`] feeds a [`MatchRecording`] back into the [`InputBuffer`]s of a headless
+//! app, tick by tick, and reports the first tick where the checksum diverges from the recorded
+//! one. The app has to run the same simulation as the server and must not add the
+//! `ServerInputPlugin`, since the replay updates the action states itself. Ticks that are missing
+//! from the recording are simulated without inputs and reported by [`MatchReplay::missed_ticks`].
+//!
+//! The initial state is not part of the recording. The replay app has to spawn the same entities
+//! as the server did when the recording started, for example with the replication
+//! [snapshots](lightyear_replication::snapshot); the inputs of the recorded entities are then
+//! applied to the entities given by [`MatchReplay::with_entity_map`].
+//!
+//! ```rust,ignore
+//! // on the server
+//! app.add_plugins(MatchRecorderPlugin::<MyInputs>::default());
+//! app.insert_resource(MatchRecorder::<MyInputs>::create("match.replay")?);
+//!
+//! // offline
+//! let mut app = App::new();
+//! app.add_plugins((
+//!     MinimalPlugins,
+//!     CorePlugins { tick_duration: TICK_DURATION },
+//!     ReplayPlugin::<MyInputs>::default(),
+//! ));
+//! app.insert_resource(TimeUpdateStrategy::ManualDuration(TICK_DURATION));
+//! let recording = MatchRecording::<MyInputs>::load_from_file("match.replay")?;
+//! app.insert_resource(MatchReplay::new(recording));
+//! while !app.world().resource::<MatchReplay<MyInputs>>().is_finished() {
+//!     app.update();
+//! }
+//! if let Some(divergence) = app.world().resource::<MatchReplay<MyInputs>>().first_divergence() {
+//!     println!("the replay diverged at tick {:?}", divergence.tick);
+//! }
+//! ```
+use crate::archetypes::ChecksumWorld;
+use crate::checksum::{ChecksumMessage, compute_checksum};
+use crate::plugin::DeterministicReplicationPlugin;
+use alloc::vec::Vec;
+use bevy_app::{App, FixedFirst, FixedLast, FixedPreUpdate, Plugin};
+use bevy_ecs::entity::{Entity, EntityMapper};
+use bevy_ecs::prelude::*;
+use core::marker::PhantomData;
+use lightyear_core::prelude::LocalTimeline;
+use lightyear_core::tick::{Tick, TickDuration};
+use lightyear_core::timeline::TimelineSystems;
+use lightyear_inputs::input_buffer::InputBuffer;
+use lightyear_inputs::input_message::{ActionStateQueryData, ActionStateSequence};
+use lightyear_inputs::server::InputSystems;
+use lightyear_serde::entity_map::EntityMap;
+use lightyear_serde::reader::{ReadInteger, ReadVarInt, Reader};
+use lightyear_serde::registry::SerializeFns;
+use lightyear_serde::writer::{WriteInteger, Writer};
+use lightyear_serde::{SerializationError, ToBytes};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tracing::{error, info, warn};
+
+/// First bytes of every recording.
+pub const RECORDING_MAGIC: [u8; 4] = *b"LYRP";
+/// Version of the recording format. Recordings with another version are rejected.
+pub const RECORDING_VERSION: u16 = 1;
+
+#[derive(thiserror::Error, Debug)]
+pub enum RecordingError {
+    #[error("the data is not a match recording")]
+    InvalidHeader,
+    #[error("the recording format version {0} is not supported")]
+    UnsupportedVersion(u16),
+    #[error(transparent)]
+    Serialization(#[from] SerializationError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// The inputs applied during one tick, and the checksum of the world at the end of the tick.
+#[derive(Debug, Clone)]
+pub struct RecordedTick<S> {
+    pub tick: Tick,
+    /// The input applied to each entity, sorted by entity. The entities are the ones of the
+    /// server that recorded the match.
+    pub inputs: Vec<(Entity, S)>,
+    pub checksum: u64,
+}
+
+impl<S> RecordedTick<S> {
+    pub fn checksum_message(&self) -> ChecksumMessage {
+        ChecksumMessage {
+            tick: self.tick,
+            checksum: self.checksum,
+        }
+    }
+}
+
+impl<S: Serialize + DeserializeOwned> RecordedTick<S> {
+    fn write(&self, writer: &mut Writer) -> Result<(), SerializationError> {
+        let serialize = SerializeFns::<S>::default().serialize;
+        self.tick.to_bytes(writer)?;
+        writer.write_u64(self.checksum)?;
+        writer.write_varint(self.inputs.len() as u64)?;
+        for (entity, input) in &self.inputs {
+            entity.to_bytes(writer)?;
+            serialize(input, writer)?;
+        }
+        Ok(())
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, SerializationError> {
+        let deserialize = SerializeFns::<S>::default().deserialize;
+        let tick = Tick::from_bytes(reader)?;
+        let checksum = reader.read_u64()?;
+        let mut inputs = Vec::new();
+        for _ in 0..reader.read_varint()? {
+            let entity = Entity::from_bytes(reader)?;
+            inputs.push((entity, deserialize(reader)?));
+        }
+        Ok(Self {
+            tick,
+            inputs,
+            checksum,
+        })
+    }
+}
+
+/// A match recorded by a [`MatchRecorder`].
+#[derive(Debug, Clone)]
+pub struct MatchRecording<S> {
+    ticks: Vec<RecordedTick<S>>,
+}
+
+impl<S: Serialize + DeserializeOwned> MatchRecording<S> {
+    /// Reads a recording. A recording whose last tick is truncated (for example because the
+    /// server crashed while writing it) is read up to its last complete tick.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RecordingError> {
+        let mut reader = Reader::from(bytes.to_vec());
+        if reader.remaining_slice().get(..RECORDING_MAGIC.len()) != Some(&RECORDING_MAGIC[..]) {
+            return Err(RecordingError::InvalidHeader);
+        }
+        reader.set_position(RECORDING_MAGIC.len() as u64);
+        let version = reader
+            .read_u16()
+            .map_err(|_| RecordingError::InvalidHeader)?;
+        if version != RECORDING_VERSION {
+            return Err(RecordingError::UnsupportedVersion(version));
+        }
+        let mut ticks = Vec::new();
+        while reader.has_remaining() {
+            // each tick is prefixed with its length
+            let Ok(frame) = reader
+                .read_varint()
+                .and_then(|len| Ok(reader.split_len(len as usize)?))
+            else {
+                warn!(
+                    num_ticks = ticks.len(),
+                    "The recording ends with an incomplete tick"
+                );
+                break;
+            };
+            ticks.push(RecordedTick::read(&mut Reader::from(frame))?);
+        }
+        Ok(Self { ticks })
+    }
+
+    /// Reads a recording written by [`MatchRecorder::create`].
+    #[cfg(feature = "std")]
+    pub fn load_from_file(path: impl AsRef<std::path::Path>) -> Result<Self, RecordingError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+}
+
+impl<S> MatchRecording<S> {
+    pub fn ticks(&self) -> &[RecordedTick<S>] {
+        &self.ticks
+    }
+}
+
+fn write_header(writer: &mut Writer) -> Result<(), SerializationError> {
+    writer.extend_from_slice(&RECORDING_MAGIC);
+    writer.write_u16(RECORDING_VERSION)?;
+    Ok(())
+}
+
+fn write_tick<S: Serialize + DeserializeOwned>(
+    recorded: &RecordedTick<S>,
+    writer: &mut Writer,
+) -> Result<(), SerializationError> {
+    let mut frame = Writer::default();
+    recorded.write(&mut frame)?;
+    writer.write_varint(frame.len() as u64)?;
+    writer.extend_from_slice(&frame.into_bytes());
+    Ok(())
+}
+
+/// Records the inputs applied by the server for the [`ActionStateSequence`] `S`, and the
+/// checksum of every tick.
+///
+/// The recording starts when the resource is inserted and stops when it is removed. The
+/// [`MatchRecorderPlugin`] must be added for the resource to be updated.
+#[derive(Resource)]
+pub struct MatchRecorder<S> {
+    writer: Writer,
+    /// Inputs of the current tick, written once the checksum is computed at the end of the tick
+    pending: Option<(Tick, Vec<(Entity, S)>)>,
+    #[cfg(feature = "std")]
+    file: Option<std::io::BufWriter<std::fs::File>>,
+}
+
+impl<S> Default for MatchRecorder<S> {
+    /// Records the match in memory. The recording can be retrieved with [`Self::into_bytes`].
+    fn default() -> Self {
+        let mut writer = Writer::default();
+        write_header(&mut writer).expect("writing to a Writer cannot fail");
+        Self {
+            writer,
+            pending: None,
+            #[cfg(feature = "std")]
+            file: None,
+        }
+    }
+}
+
+impl<S> MatchRecorder<S> {
+    /// Streams the recording to the file at `path`, which is created or truncated.
+    ///
+    /// The ticks are written to the file at the end of each frame.
+    #[cfg(feature = "std")]
+    pub fn create(path: impl AsRef<std::path::Path>) -> Result<Self, RecordingError> {
+        let mut recorder = Self::default();
+        recorder.file = Some(std::io::BufWriter::new(std::fs::File::create(path)?));
+        recorder.flush()?;
+        Ok(recorder)
+    }
+
+    /// Writes the recorded ticks to the file, if the recording is streamed to a file.
+    #[cfg(feature = "std")]
+    pub fn flush(&mut self) -> Result<(), RecordingError> {
+        use std::io::Write;
+        if let Some(file) = &mut self.file {
+            file.write_all(&self.writer.take_written())?;
+            file.flush()?;
+        }
+        Ok(())
+    }
+
+    /// The recording, if it is recorded in memory.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.writer.into_bytes().to_vec()
+    }
+}
+
+impl<S: ActionStateSequence> MatchRecorder<S> {
+    /// Store the inputs that the server is about to apply to the action states.
+    fn record_inputs(
+        timeline: Res<LocalTimeline>,
+        recorder: Option<ResMut<Self>>,
+        buffers: Query<(Entity, &InputBuffer<S::Snapshot, S::Action>)>,
+    ) {
+        let Some(mut recorder) = recorder else {
+            return;
+        };
+        let tick = timeline.tick();
+        let mut inputs: Vec<(Entity, S)> = buffers
+            .iter()
+            .filter_map(|(entity, buffer)| {
+                // same snapshot as the one used by the server in `update_action_state`
+                let snapshot = buffer.get_predict(tick)?;
+                let mut applied = InputBuffer::<S::Snapshot, S::Action>::default();
+                applied.set(tick, snapshot.clone());
+                S::build_from_input_buffer(&applied, 1, tick).map(|input| (entity, input))
+            })
+            .collect();
+        inputs.sort_by_key(|(entity, _)| *entity);
+        recorder.pending = Some((tick, inputs));
+    }
+
+    /// Write the tick once its checksum is known.
+    fn record_checksum(
+        mut world: ChecksumWorld<'_, '_, false>,
+        timeline: Res<LocalTimeline>,
+        recorder: Option<ResMut<Self>>,
+    ) {
+        let Some(mut recorder) = recorder else {
+            return;
+        };
+        let tick = timeline.tick();
+        let inputs = match recorder.pending.take() {
+            Some((pending_tick, inputs)) if pending_tick == tick => inputs,
+            _ => Vec::new(),
+        };
+        let recorded = RecordedTick {
+            tick,
+            inputs,
+            checksum: compute_checksum(&mut world, tick),
+        };
+        if let Err(e) = write_tick(&recorded, &mut recorder.writer) {
+            error!(?tick, ?e, "Failed to record the tick");
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn flush_recording(recorder: Option<ResMut<Self>>) {
+        if let Some(mut recorder) = recorder
+            && let Err(e) = recorder.flush()
+        {
+            error!(?e, "Failed to write the match recording");
+        }
+    }
+}
+
+/// Plugin that updates the [`MatchRecorder<S>`] resource, if it exists.
+pub struct MatchRecorderPlugin<S> {
+    marker: PhantomData<S>,
+}
+
+impl<S> Default for MatchRecorderPlugin<S> {
+    fn default() -> Self {
+        Self {
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<S: ActionStateSequence> Plugin for MatchRecorderPlugin<S> {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<DeterministicReplicationPlugin>() {
+            app.add_plugins(DeterministicReplicationPlugin);
+        }
+        let record_inputs =
+            MatchRecorder::<S>::record_inputs.before(InputSystems::UpdateActionState);
+        // in host-server mode, the inputs of the host-client are buffered in FixedPreUpdate
+        #[cfg(feature = "client")]
+        let record_inputs =
+            record_inputs.after(lightyear_inputs::client::InputSystems::BufferClientInputs);
+        app.add_systems(FixedPreUpdate, record_inputs);
+        app.add_systems(FixedLast, MatchRecorder::<S>::record_checksum);
+        #[cfg(feature = "std")]
+        app.add_systems(bevy_app::Last, MatchRecorder::<S>::flush_recording);
+    }
+}
+
+/// Checksum of the replay that doesn't match the recorded checksum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChecksumDivergence {
+    pub tick: Tick,
+    /// Checksum recorded by the server
+    pub expected: u64,
+    /// Checksum computed during the replay
+    pub actual: u64,
+}
+
+/// Replays a [`MatchRecording`] in the app. The [`ReplayPlugin`] must be added.
+#[derive(Resource)]
+pub struct MatchReplay<S> {
+    recording: MatchRecording<S>,
+    entity_map: EntityMap,
+    started: bool,
+    /// Index of the next recorded tick to replay
+    next: usize,
+    first_divergence: Option<ChecksumDivergence>,
+    missed_ticks: Vec<Tick>,
+}
+
+impl<S> MatchReplay<S> {
+    pub fn new(recording: MatchRecording<S>) -> Self {
+        Self {
+            recording,
+            entity_map: EntityMap::default(),
+            started: false,
+            next: 0,
+            first_divergence: None,
+            missed_ticks: Vec::new(),
+        }
+    }
+
+    /// Map the entities of the server that recorded the match to the entities of the replay.
+    ///
+    /// Entities that are not in the map are used as they are, which works when the replay spawns
+    /// the initial entities in the same order as the server.
+    pub fn with_entity_map(mut self, entity_map: EntityMap) -> Self {
+        self.entity_map = entity_map;
+        self
+    }
+
+    /// Returns true once every recorded tick was replayed or skipped.
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.recording.ticks.len()
+    }
+
+    /// Number of recorded ticks that were replayed or skipped.
+    pub fn ticks_replayed(&self) -> usize {
+        self.next
+    }
+
+    /// The first tick where the replay didn't produce the recorded checksum.
+    pub fn first_divergence(&self) -> Option<&ChecksumDivergence> {
+        self.first_divergence.as_ref()
+    }
+
+    /// Ticks that could not be checked against the recording: the ticks of the replay that are
+    /// missing from the recording, and the recorded ticks that the replay went past.
+    pub fn missed_ticks(&self) -> &[Tick] {
+        &self.missed_ticks
+    }
+
+    /// Skip the recorded ticks that are older than `tick`, and flag `tick` if the recording has
+    /// no data for it, so that a gap in the recording doesn't stall the replay.
+    fn seek(&mut self, tick: Tick) {
+        while let Some(recorded) = self.recording.ticks.get(self.next)
+            && recorded.tick < tick
+        {
+            warn!(tick = ?recorded.tick, "The replay went past the recorded tick");
+            self.missed_ticks.push(recorded.tick);
+            self.next += 1;
+        }
+        if !self.is_finished() && self.current(tick).is_none() {
+            warn!(?tick, "The tick is missing from the recording");
+            self.missed_ticks.push(tick);
+        }
+    }
+
+    fn current(&self, tick: Tick) -> Option<&RecordedTick<S>> {
+        self.recording
+            .ticks
+            .get(self.next)
+            .filter(|recorded| recorded.tick == tick)
+    }
+}
+
+impl<S: ActionStateSequence> MatchReplay<S> {
+    /// Move the [`LocalTimeline`] to the first recorded tick.
+    fn start(mut timeline: ResMut<LocalTimeline>, replay: Option<ResMut<Self>>) {
+        let Some(mut replay) = replay else {
+            return;
+        };
+        if replay.started {
+            return;
+        }
+        replay.started = true;
+        if let Some(first) = replay.recording.ticks.first() {
+            let delta = first.tick - timeline.tick();
+            timeline.apply_delta(delta);
+            info!(tick = ?first.tick, num_ticks = replay.recording.ticks.len(), "Starting the replay");
+        }
+    }
+
+    /// Write the recorded inputs in the [`InputBuffer`]s and update the action states, like the
+    /// server did.
+    fn replay_inputs(
+        timeline: Res<LocalTimeline>,
+        tick_duration: Res<TickDuration>,
+        replay: Option<ResMut<Self>>,
+        mut query: Query<(
+            <S::State as ActionStateQueryData>::Mut,
+            &mut InputBuffer<S::Snapshot, S::Action>,
+        )>,
+    ) {
+        let Some(mut replay) = replay else {
+            return;
+        };
+        let tick = timeline.tick();
+        let replay = &mut *replay;
+        replay.seek(tick);
+        let Some(recorded) = replay.current(tick) else {
+            return;
+        };
+        for (entity, input) in &recorded.inputs {
+            let entity = replay.entity_map.get_mapped(*entity);
+            let Ok((action_state, mut input_buffer)) = query.get_mut(entity) else {
+                warn!(?tick, ?entity, "The replayed entity has no InputBuffer");
+                continue;
+            };
+            input
+                .clone()
+                .update_buffer(&mut input_buffer, tick, tick_duration.0);
+            if let Some(snapshot) = input_buffer.get_predict(tick) {
+                S::from_snapshot_transitions(S::State::into_inner(action_state), snapshot);
+            }
+            input_buffer.pop_keeping_last(tick - 1);
+        }
+    }
+
+    /// Compare the checksum at the end of the tick with the recorded one.
+    fn check_checksum(
+        mut world: ChecksumWorld<'_, '_, false>,
+        timeline: Res<LocalTimeline>,
+        replay: Option<ResMut<Self>>,
+    ) {
+        let Some(mut replay) = replay else {
+            return;
+        };
+        let tick = timeline.tick();
+        let Some(expected) = replay.current(tick).map(|recorded| recorded.checksum) else {
+            return;
+        };
+        let actual = compute_checksum(&mut world, tick);
+        if actual != expected && replay.first_divergence.is_none() {
+            error!(
+                ?tick,
+                expected = format_args!("{expected:016x}"),
+                actual = format_args!("{actual:016x}"),
+                "The replay diverged from the recording"
+            );
+            replay.first_divergence = Some(ChecksumDivergence {
+                tick,
+                expected,
+                actual,
+            });
+        }
+        replay.next += 1;
+        if replay.is_finished() {
+            info!(
+                num_ticks = replay.next,
+                diverged = replay.first_divergence.is_some(),
+                "Finished the replay"
+            );
+        }
+    }
+}
+
+/// Plugin that replays the [`MatchReplay<S>`] resource, if it exists.
+pub struct ReplayPlugin<S> {
+    marker: PhantomData<S>,
+}
+
+impl<S> Default for ReplayPlugin<S> {
+    fn default() -> Self {
+        Self {
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<S: ActionStateSequence> Plugin for ReplayPlugin<S> {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<DeterministicReplicationPlugin>() {
+            app.add_plugins(DeterministicReplicationPlugin);
+        }
+        app.add_systems(
+            FixedFirst,
+            MatchReplay::<S>::start.after(TimelineSystems::IncrementLocal),
+        );
+        app.add_systems(
+            FixedPreUpdate,
+            MatchReplay::<S>::replay_inputs.in_set(InputSystems::UpdateActionState),
+        );
+        app.add_systems(FixedLast, MatchReplay::<S>::check_checksum);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_recording() {
+        let mut world = World::new();
+        let a = world.spawn_empty().id();
+        let b = world.spawn_empty().id();
+        let ticks = [
+            RecordedTick {
+                tick: Tick(10),
+                inputs: alloc::vec![(a, 7u32), (b, 0)],
+                checksum: 0xCAFE,
+            },
+            RecordedTick {
+                tick: Tick(11),
+                inputs: alloc::vec![],
+                checksum: 0xBEEF,
+            },
+            RecordedTick {
+                tick: Tick(12),
+                inputs: alloc::vec![(b, 3)],
+                checksum: 0,
+            },
+        ];
+        let mut writer = Writer::default();
+        write_header(&mut writer).unwrap();
+        for tick in &ticks {
+            write_tick(tick, &mut writer).unwrap();
+        }
+        let bytes = writer.into_bytes().to_vec();
+
+        let recording = MatchRecording::<u32>::from_bytes(&bytes).unwrap();
+        assert_eq!(recording.ticks().len(), 3);
+        for (read, written) in recording.ticks().iter().zip(&ticks) {
+            assert_eq!(read.tick, written.tick);
+            assert_eq!(read.inputs, written.inputs);
+            assert_eq!(read.checksum, written.checksum);
+        }
+
+        // a truncated tick is dropped
+        let recording = MatchRecording::<u32>::from_bytes(&bytes[..bytes.len() - 1]).unwrap();
+        assert_eq!(recording.ticks().len(), 2);
+    }
+
+    #[test]
+    fn read_invalid_recording() {
+        assert!(matches!(
+            MatchRecording::<u32>::from_bytes(b"LYSN\x00\x01"),
+            Err(RecordingError::InvalidHeader)
+        ));
+        let mut writer = Writer::default();
+        writer.extend_from_slice(&RECORDING_MAGIC);
+        writer.write_u16(RECORDING_VERSION + 1).unwrap();
+        assert!(matches!(
+            MatchRecording::<u32>::from_bytes(&writer.into_bytes()),
+            Err(RecordingError::UnsupportedVersion(_))
+        ));
+    }
+
+    /// Replays the ticks `ticks` without an app, like the replay systems do.
+    fn replay_ticks(recorded: &[u32], ticks: core::ops::RangeInclusive<u32>) -> MatchReplay<u32> {
+        let mut replay = MatchReplay::new(MatchRecording {
+            ticks: recorded
+                .iter()
+                .map(|tick| RecordedTick {
+                    tick: Tick(*tick),
+                    inputs: Vec::new(),
+                    checksum: 0,
+                })
+                .collect(),
+        });
+        for tick in ticks.map(Tick) {
+            replay.seek(tick);
+            if replay.current(tick).is_some() {
+                replay.next += 1;
+            }
+        }
+        replay
+    }
+
+    #[test]
+    fn replay_skips_missing_ticks() {
+        // a tick is missing from the recording
+        let replay = replay_ticks(&[10, 11, 13], 10..=13);
+        assert!(replay.is_finished());
+        assert_eq!(replay.missed_ticks(), &[Tick(12)]);
+
+        // the replay starts after the first recorded tick
+        let replay = replay_ticks(&[5, 10, 11], 10..=11);
+        assert!(replay.is_finished());
+        assert_eq!(replay.missed_ticks(), &[Tick(5)]);
+    }
+}
@@ -64,6 +64,8 @@ use bevy_time::Fixed;
 use bevy_time::{Real, Time, Timer, TimerMode};
 use bevy_utils::prelude::DebugName;
 use lightyear_connection::network_topology::{NetworkTopology, NetworkingMetadata};
+#[cfg(feature = "prediction")]
+use lightyear_connection::p2p::P2PInputFrontier;
 use lightyear_core::prelude::*;
 use lightyear_core::tick::TickDuration;
 #[cfg(feature = "interpolation")]
@@ -892,6 +894,7 @@ fn receive_remote_player_input_messages<S: ActionStateSequence>(
     last_confirmed_input: Res<LastConfirmedInput>,
     prediction_manager: Option<Res<PredictionManager>>,
     mut receivers: Query<&mut MessageReceiver<InputMessage<S>>>,
+    mut frontiers: Query<&mut P2PInputFrontier>,
     mut predicted_query: Query<
         Option<&mut InputBuffer<S::Snapshot, S::Action>>,
         (Without<S::Marker>, Allow<PredictionDisable>),
@@ -924,6 +927,7 @@ fn receive_remote_player_input_messages<S: ActionStateSequence>(
                     *tick_duration,
                     tick,
                     None,
+                    &mut None,
                     &prediction_manager,
                     &mut predicted_query,
                     &prespawned,
@@ -937,18 +941,34 @@ fn receive_remote_player_input_messages<S: ActionStateSequence>(
                 let Ok(mut receiver) = receivers.get_mut(*link) else {
                     continue;
                 };
+                let mut input_frontier = frontiers.get(*link).ok().map(|frontier| frontier.0);
                 received_relevant_input |= receive_remote_player_input_messages_from_receiver::<S>(
                     &mut receiver,
                     &mut commands,
                     *tick_duration,
                     tick,
                     Some(*link),
+                    &mut input_frontier,
                     &prediction_manager,
                     &mut predicted_query,
                     &prespawned,
                     #[cfg(feature = "metrics")]
                     &mut input_metric_handles,
                 );
+                // Record how far this peer's input stream has been received, so that a P2P
+                // session can agree on its last confirmed input tick if the peer leaves.
+                if let Some(input_frontier) = input_frontier {
+                    match frontiers.get_mut(*link) {
+                        Ok(mut frontier) => {
+                            frontier.0 = input_frontier;
+                        }
+                        Err(_) => {
+                            commands
+                                .entity(*link)
+                                .insert(P2PInputFrontier(input_frontier));
+                        }
+                    }
+                }
             }
         }
     }
@@ -960,6 +980,38 @@ fn receive_remote_player_input_messages<S: ActionStateSequence>(
     }
 }
 
+/// Advance `input_frontier` to the end of `message` if the message covers the ticks right after
+/// the frontier, so that the frontier never skips ticks whose inputs were not received.
+#[cfg(feature = "prediction")]
+fn advance_input_frontier<S: ActionStateSequence>(
+    input_frontier: &mut Option<Tick>,
+    message: &InputMessage<S>,
+) {
+    // a message without targets has no input that could be missing
+    let first_tick = message
+        .inputs
+        .iter()
+        .map(|data| data.states.len().max(1) as u32)
+        .max()
+        .map_or(Tick(0), |len| message.end_tick - (len - 1));
+    advance_frontier(input_frontier, first_tick, message.end_tick);
+}
+
+/// Advance `frontier` to `end_tick` if the ticks from `first_tick` to `end_tick` continue it.
+///
+/// The first message received sets the frontier if there is none yet.
+#[cfg(feature = "prediction")]
+fn advance_frontier(frontier: &mut Option<Tick>, first_tick: Tick, end_tick: Tick) {
+    match frontier {
+        None => *frontier = Some(end_tick),
+        Some(frontier) => {
+            if first_tick <= *frontier + 1 && *frontier < end_tick {
+                *frontier = end_tick;
+            }
+        }
+    }
+}
+
 #[cfg(feature = "prediction")]
 fn receive_remote_player_input_messages_from_receiver<S: ActionStateSequence>(
     receiver: &mut MessageReceiver<InputMessage<S>>,
@@ -967,6 +1019,7 @@ fn receive_remote_player_input_messages_from_receiver<S: ActionStateSequence>(
     tick_duration: TickDuration,
     tick: Tick,
     p2p_link: Option<Entity>,
+    input_frontier: &mut Option<Tick>,
     prediction_manager: &PredictionManager,
     predicted_query: &mut Query<
         Option<&mut InputBuffer<S::Snapshot, S::Action>>,
@@ -978,6 +1031,7 @@ fn receive_remote_player_input_messages_from_receiver<S: ActionStateSequence>(
     let mut received_relevant_input = false;
     receiver.receive().for_each(|message| {
         trace!(?message.end_tick, ?message, "received remote input message for action: {:?}", DebugName::type_name::<S::Action>());
+        advance_input_frontier(input_frontier, &message);
         trace!(
             target: "lightyear_debug::input",
             kind = "remote_input_message_recv",
@@ -1439,6 +1493,28 @@ mod tests {
     use super::*;
     use lightyear_replication::prelude::Lifetime;
 
+    #[cfg(feature = "prediction")]
+    #[test]
+    fn input_frontier_only_advances_without_gaps() {
+        let mut frontier = None;
+        advance_frontier(&mut frontier, Tick(5), Tick(10));
+        assert_eq!(frontier, Some(Tick(10)));
+
+        // the message right after the frontier, and one that overlaps it
+        advance_frontier(&mut frontier, Tick(11), Tick(12));
+        assert_eq!(frontier, Some(Tick(12)));
+        advance_frontier(&mut frontier, Tick(8), Tick(15));
+        assert_eq!(frontier, Some(Tick(15)));
+
+        // the inputs of tick 16 are missing
+        advance_frontier(&mut frontier, Tick(17), Tick(20));
+        assert_eq!(frontier, Some(Tick(15)));
+
+        // an older message doesn't move the frontier back
+        advance_frontier(&mut frontier, Tick(1), Tick(3));
+        assert_eq!(frontier, Some(Tick(15)));
+    }
+
     #[test]
     fn input_route_uses_link_ownership_only_for_client_server() {
         let mut world = World::new();
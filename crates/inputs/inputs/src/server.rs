@@ -17,7 +17,7 @@ use bevy_ecs::relationship::RelationshipTarget;
 use bevy_ecs::{
     entity::{Entity, MapEntities},
     error::Result,
-    query::With,
+    query::{With, Without},
     resource::Resource,
     schedule::{IntoScheduleConfigs, SystemSet},
     system::{Commands, Query, Res, Single},
@@ -38,6 +38,8 @@ use lightyear_messages::prelude::MessageReceiver;
 use lightyear_messages::server::ServerMultiMessageSender;
 use lightyear_replication::control::ControlledByRemote;
 use lightyear_replication::prelude::{PreSpawned, RoomId, Rooms};
+use lightyear_replication::spectator::Spectator;
+use lightyear_sync::timeline::feedback::InputArrival;
 use tracing::{debug, error, trace};
 
 /// Maximum number of ticks ahead of the server's current tick that an
@@ -223,7 +225,8 @@ pub fn authorize_controlled_targets<S: ActionStateSequence>(
 
 /// Component that is used to customize how inputs will be rebroadcasted
 ///
-/// If absent, the inputs received on a given `ClientOf` entity will be rebroadcasted to all other clients
+/// If absent, the inputs received on a given `ClientOf` entity will be rebroadcasted to all other clients.
+/// Inputs are never rebroadcasted to [`Spectator`] links.
 #[derive(Component)]
 pub enum InputRebroadcaster<S> {
     // Rebroadcast to all users in the room
@@ -303,11 +306,11 @@ impl<S: ActionStateSequence + MapEntities> Plugin for ServerInputPlugin<S> {
 fn receive_input_message<S: ActionStateSequence>(
     config: Res<ServerInputConfig<S::Action>>,
     server: Query<&Server>,
-    // make sure to only rebroadcast inputs to connected clients
+    // make sure to only rebroadcast inputs to connected clients, spectators don't predict
     #[cfg_attr(not(feature = "prediction"), allow(unused_mut))]
-    mut sender: ServerMultiMessageSender<With<Connected>>,
+    mut sender: ServerMultiMessageSender<(With<Connected>, Without<Spectator>)>,
     tick_duration: Res<TickDuration>,
-    rooms_query: Query<(Entity, &Rooms), With<Connected>>,
+    rooms_query: Query<(Entity, &Rooms), (With<Connected>, Without<Spectator>)>,
     timeline: Res<LocalTimeline>,
     mut receivers: Query<
         (
@@ -316,6 +319,8 @@ fn receive_input_message<S: ActionStateSequence>(
             &mut MessageReceiver<InputMessage<S>>,
             &RemoteId,
             Option<&InputRebroadcaster<S::Action>>,
+            Option<&mut InputArrival>,
+            Has<Spectator>,
         ),
         // We also receive inputs from the HostClient, in case we want the HostClient's inputs to be
         // rebroadcast to other clients (so that they can do prediction of the HostClient's entity)
@@ -332,7 +337,15 @@ fn receive_input_message<S: ActionStateSequence>(
     mut commands: Commands,
 ) -> Result {
     // TODO: use par_iter_mut
-    receivers.iter_mut().try_for_each(|(client_entity, link_of, mut receiver, client_id, rebroadcaster)| {
+    receivers.iter_mut().try_for_each(|(client_entity, link_of, mut receiver, client_id, rebroadcaster, mut arrival, spectator)| {
+        if spectator {
+            // spectators don't control any entity
+            let dropped = receiver.receive().count();
+            if dropped > 0 {
+                trace!(?client_id, dropped, "Ignoring input messages from spectator");
+            }
+            return Ok(())
+        }
         // TODO: this drains the messages... but the user might want to re-broadcast them?
         //  should we just read instead?
         let server_entity = link_of.server;
@@ -386,6 +399,17 @@ fn receive_input_message<S: ActionStateSequence>(
                 return Ok(())
             }
 
+            // The inputs are read in FixedPreUpdate, after the LocalTimeline advanced to the next tick.
+            // Late inputs will be replaced by the predicted ones: the client is notified with an
+            // `InputTimingReport` so that it can run further ahead.
+            if !client_id.is_local() && let Some(arrival) = arrival.as_mut() {
+                let margin = message.end_tick - (tick + 1);
+                if margin < 0 {
+                    trace!(?tick, ?client_id, end_tick = ?message.end_tick, margin, "Received late input message");
+                }
+                arrival.record(margin);
+            }
+
             // TODO: or should we try to store in a buffer the interpolation delay for the exact tick
             //  that the message was intended for?
             #[cfg(feature = "interpolation")]
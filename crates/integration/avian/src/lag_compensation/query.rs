@@ -1,4 +1,5 @@
 //! Provides a system parameter for performing spatial queries while doing lag compensation.
+use alloc::vec::Vec;
 use core::cell::RefCell;
 
 use super::history::{AabbEnvelopeHolder, LagCompensationHistory};
@@ -11,12 +12,12 @@ use lightyear_link::prelude::Server;
 use tracing::{debug, error, info};
 #[cfg(all(feature = "2d", not(feature = "3d")))]
 use {
-    avian2d::{math::*, prelude::*},
+    avian2d::{collision::collider::contact_query, math::*, prelude::*},
     bevy_math::Dir2 as Dir,
 };
 #[cfg(all(feature = "3d", not(feature = "2d")))]
 use {
-    avian3d::{math::*, prelude::*},
+    avian3d::{collision::collider::contact_query, math::*, prelude::*},
     bevy_math::Dir3 as Dir,
 };
 
@@ -59,6 +60,26 @@ pub struct LagCompensationRayHit {
     pub rotation: Rotation,
 }
 
+/// A lag-compensated shape cast hit together with the historical collider pose
+/// used for the narrow-phase test.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LagCompensationShapeHit {
+    /// The result of casting the shape against the historical collider.
+    pub hit: ShapeHitData,
+    /// The historical pose of the collider that was hit.
+    pub sample: LagCompensationSample,
+}
+
+/// A lag-compensated point projection together with the historical collider
+/// pose that the point was projected on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LagCompensationPointProjection {
+    /// The projection of the point on the historical collider.
+    pub projection: PointProjection,
+    /// The historical pose of the collider that the point was projected on.
+    pub sample: LagCompensationSample,
+}
+
 /// A system parameter for performing [spatial queries](spatial_query) while doing
 /// lag compensation.
 ///
@@ -192,24 +213,9 @@ impl LagCompensationSpatialQuery<'_, '_> {
             filter,
             &|child| {
                 // 2) there is a hit! Check if we hit the collider from the history
-
-                // we cannot rely directly on the CollisionLayers to filter contacts with aabb envelopes
-                // because CollisionLayers only encodes OR conditions, not AND
-                let Ok(parent_component) = self.child_query.get(child) else {
-                    return false;
-                };
-                let parent = parent_component.parent();
-                debug!(?parent, ?filter, "Broadphase hit with {child:?}");
-                let (collider, collision_layers, history) = self
-                    .parent_query
-                    .get(parent)
-                    .expect("the parent must have a history");
-                // the collisions are done with the lag compensation collider; make sure that the parent is not excluded
-                if !filter.test(parent, *collision_layers) {
-                    debug!("Collider entity {parent:?} with layers {collision_layers:?} excluded because of filter");
-                    return false;
-                }
-                let Some(sample) = sample_history(history, interpolation_delay, tick, true) else {
+                let Some((parent, collider, sample)) =
+                    self.narrow_phase_candidate(child, filter, interpolation_delay, tick)
+                else {
                     return false;
                 };
 
@@ -255,6 +261,350 @@ impl LagCompensationSpatialQuery<'_, '_> {
         );
         exact_hit_data.into_inner()
     }
+
+    /// Similar to [`SpatialQuery::cast_shape`], but does lag compensation by
+    /// using the history buffer of the entity.
+    #[allow(clippy::too_many_arguments)]
+    pub fn cast_shape(
+        &self,
+        interpolation_delay: InterpolationDelay,
+        shape: &Collider,
+        origin: Vector,
+        shape_rotation: Rotation,
+        direction: Dir,
+        config: &ShapeCastConfig,
+        filter: &SpatialQueryFilter,
+    ) -> Option<ShapeHitData> {
+        self.cast_shape_predicate_with_sample(
+            interpolation_delay,
+            shape,
+            origin,
+            shape_rotation,
+            direction,
+            config,
+            &|_| true,
+            filter,
+        )
+        .map(|result| result.hit)
+    }
+
+    /// Like [`Self::cast_shape`], but also returns the exact historical collider
+    /// pose used for the successful narrow-phase test.
+    #[allow(clippy::too_many_arguments)]
+    pub fn cast_shape_with_sample(
+        &self,
+        interpolation_delay: InterpolationDelay,
+        shape: &Collider,
+        origin: Vector,
+        shape_rotation: Rotation,
+        direction: Dir,
+        config: &ShapeCastConfig,
+        filter: &SpatialQueryFilter,
+    ) -> Option<LagCompensationShapeHit> {
+        self.cast_shape_predicate_with_sample(
+            interpolation_delay,
+            shape,
+            origin,
+            shape_rotation,
+            direction,
+            config,
+            &|_| true,
+            filter,
+        )
+    }
+
+    /// Similar to [`SpatialQuery::cast_shape_predicate`], but does lag compensation by
+    /// using the history buffer of the entity.
+    #[allow(clippy::too_many_arguments)]
+    pub fn cast_shape_predicate(
+        &self,
+        interpolation_delay: InterpolationDelay,
+        shape: &Collider,
+        origin: Vector,
+        shape_rotation: Rotation,
+        direction: Dir,
+        config: &ShapeCastConfig,
+        predicate: &dyn Fn(Entity) -> bool,
+        filter: &SpatialQueryFilter,
+    ) -> Option<ShapeHitData> {
+        self.cast_shape_predicate_with_sample(
+            interpolation_delay,
+            shape,
+            origin,
+            shape_rotation,
+            direction,
+            config,
+            predicate,
+            filter,
+        )
+        .map(|result| result.hit)
+    }
+
+    /// Like [`Self::cast_shape_predicate`], but also returns the exact historical
+    /// collider pose used for the successful narrow-phase test.
+    ///
+    /// The narrow phase only uses the `max_distance` and `ignore_origin_penetration`
+    /// fields of the [`ShapeCastConfig`]; the other fields only apply to the broad phase.
+    #[allow(clippy::too_many_arguments)]
+    pub fn cast_shape_predicate_with_sample(
+        &self,
+        interpolation_delay: InterpolationDelay,
+        shape: &Collider,
+        origin: Vector,
+        shape_rotation: Rotation,
+        direction: Dir,
+        config: &ShapeCastConfig,
+        predicate: &dyn Fn(Entity) -> bool,
+        filter: &SpatialQueryFilter,
+    ) -> Option<LagCompensationShapeHit> {
+        let tick = self.timeline.tick();
+        #[cfg(all(feature = "2d", not(feature = "3d")))]
+        let dir = direction.as_vec2().adjust_precision();
+        #[cfg(all(feature = "3d", not(feature = "2d")))]
+        let dir = direction.as_vec3().adjust_precision();
+
+        // the broad phase returns the first envelope hit along the cast, which is not necessarily the
+        // closest historical collider, so we keep the closest narrow-phase hit
+        let exact_hit_data: RefCell<Option<LagCompensationShapeHit>> = RefCell::new(None);
+        self.spatial_query.cast_shape_predicate(
+            shape,
+            origin,
+            rotation_value(shape_rotation),
+            direction,
+            config,
+            filter,
+            &|child| {
+                let Some((parent, collider, sample)) =
+                    self.narrow_phase_candidate(child, filter, interpolation_delay, tick)
+                else {
+                    return false;
+                };
+                // the cast shape moves towards the historical collider, which is static
+                let Ok(Some(toi)) = contact_query::time_of_impact(
+                    collider,
+                    sample.position,
+                    sample.rotation,
+                    LinearVelocity::ZERO,
+                    shape,
+                    origin,
+                    shape_rotation,
+                    LinearVelocity(dir),
+                    config.max_distance,
+                ) else {
+                    return false;
+                };
+                if config.ignore_origin_penetration && toi.time_of_impact <= 0.0 {
+                    return false;
+                }
+                if !predicate(parent) {
+                    return false;
+                }
+                if exact_hit_data
+                    .borrow()
+                    .is_some_and(|hit| hit.hit.distance <= toi.time_of_impact)
+                {
+                    return false;
+                }
+                debug!(
+                    ?tick,
+                    interpolation_tick = ?sample.interpolation_tick,
+                    interpolation_overstep = ?sample.interpolation_overstep,
+                    interpolated_position = ?sample.position,
+                    ?parent,
+                    "LagCompensation ShapeHit!"
+                );
+                *exact_hit_data.borrow_mut() = Some(LagCompensationShapeHit {
+                    hit: ShapeHitData {
+                        entity: parent,
+                        distance: toi.time_of_impact,
+                        point1: toi.point1,
+                        point2: toi.point2,
+                        normal1: toi.normal1,
+                        normal2: toi.normal2,
+                    },
+                    sample,
+                });
+                true
+            },
+        );
+        exact_hit_data.into_inner()
+    }
+
+    /// Similar to [`SpatialQuery::shape_intersections`], but does lag compensation by
+    /// using the history buffer of the entity.
+    pub fn shape_intersections(
+        &self,
+        interpolation_delay: InterpolationDelay,
+        shape: &Collider,
+        shape_position: Vector,
+        shape_rotation: Rotation,
+        filter: &SpatialQueryFilter,
+    ) -> Vec<Entity> {
+        self.shape_intersections_with_sample(
+            interpolation_delay,
+            shape,
+            shape_position,
+            shape_rotation,
+            filter,
+        )
+        .into_iter()
+        .map(|(entity, _)| entity)
+        .collect()
+    }
+
+    /// Like [`Self::shape_intersections`], but also returns the exact historical
+    /// pose of each intersecting collider.
+    pub fn shape_intersections_with_sample(
+        &self,
+        interpolation_delay: InterpolationDelay,
+        shape: &Collider,
+        shape_position: Vector,
+        shape_rotation: Rotation,
+        filter: &SpatialQueryFilter,
+    ) -> Vec<(Entity, LagCompensationSample)> {
+        let tick = self.timeline.tick();
+        let mut intersections = Vec::new();
+        self.spatial_query.shape_intersections_callback(
+            shape,
+            shape_position,
+            rotation_value(shape_rotation),
+            filter,
+            |child| {
+                let Some((parent, collider, sample)) =
+                    self.narrow_phase_candidate(child, filter, interpolation_delay, tick)
+                else {
+                    return true;
+                };
+                if contact_query::intersection_test(
+                    collider,
+                    sample.position,
+                    sample.rotation,
+                    shape,
+                    shape_position,
+                    shape_rotation,
+                )
+                .unwrap_or(false)
+                {
+                    debug!(?tick, interpolation_tick = ?sample.interpolation_tick, ?parent, "LagCompensation intersection!");
+                    intersections.push((parent, sample));
+                }
+                true
+            },
+        );
+        intersections
+    }
+
+    /// Similar to [`SpatialQuery::project_point`], but does lag compensation by
+    /// using the history buffer of the entity.
+    ///
+    /// Only colliders whose aabb envelope is within `max_distance` of `point` are
+    /// considered.
+    pub fn project_point(
+        &self,
+        interpolation_delay: InterpolationDelay,
+        point: Vector,
+        solid: bool,
+        max_distance: Scalar,
+        filter: &SpatialQueryFilter,
+    ) -> Option<PointProjection> {
+        self.project_point_with_sample(interpolation_delay, point, solid, max_distance, filter)
+            .map(|result| result.projection)
+    }
+
+    /// Like [`Self::project_point`], but also returns the exact historical
+    /// collider pose used for the projection.
+    pub fn project_point_with_sample(
+        &self,
+        interpolation_delay: InterpolationDelay,
+        point: Vector,
+        solid: bool,
+        max_distance: Scalar,
+        filter: &SpatialQueryFilter,
+    ) -> Option<LagCompensationPointProjection> {
+        let tick = self.timeline.tick();
+        // the closest aabb envelope does not always contain the closest historical collider,
+        // so every envelope within `max_distance` of the point is a candidate
+        #[cfg(all(feature = "2d", not(feature = "3d")))]
+        let search_area = Collider::circle(max_distance);
+        #[cfg(all(feature = "3d", not(feature = "2d")))]
+        let search_area = Collider::sphere(max_distance);
+
+        let mut closest: Option<(Scalar, LagCompensationPointProjection)> = None;
+        self.spatial_query.shape_intersections_callback(
+            &search_area,
+            point,
+            rotation_value(Rotation::default()),
+            filter,
+            |child| {
+                let Some((parent, collider, sample)) =
+                    self.narrow_phase_candidate(child, filter, interpolation_delay, tick)
+                else {
+                    return true;
+                };
+                let (projected_point, is_inside) =
+                    collider.project_point(sample.position, sample.rotation, point, solid);
+                let distance = projected_point.distance(point);
+                if distance > max_distance
+                    || closest
+                        .as_ref()
+                        .is_some_and(|(closest_distance, _)| *closest_distance <= distance)
+                {
+                    return true;
+                }
+                closest = Some((
+                    distance,
+                    LagCompensationPointProjection {
+                        projection: PointProjection {
+                            entity: parent,
+                            point: projected_point,
+                            is_inside,
+                        },
+                        sample,
+                    },
+                ));
+                true
+            },
+        );
+        closest.map(|(_, projection)| projection)
+    }
+
+    /// Resolves a broad-phase hit with an aabb envelope to its parent collider, sampled at the
+    /// historical time of the query.
+    fn narrow_phase_candidate(
+        &self,
+        child: Entity,
+        filter: &SpatialQueryFilter,
+        interpolation_delay: InterpolationDelay,
+        tick: Tick,
+    ) -> Option<(Entity, &Collider, LagCompensationSample)> {
+        // we cannot rely directly on the CollisionLayers to filter contacts with aabb envelopes
+        // because CollisionLayers only encodes OR conditions, not AND
+        let parent = self.child_query.get(child).ok()?.parent();
+        debug!(?parent, ?filter, "Broadphase hit with {child:?}");
+        let (collider, collision_layers, history) = self
+            .parent_query
+            .get(parent)
+            .expect("the parent must have a history");
+        // the collisions are done with the lag compensation collider; make sure that the parent is not excluded
+        if !filter.test(parent, *collision_layers) {
+            debug!(
+                "Collider entity {parent:?} with layers {collision_layers:?} excluded because of filter"
+            );
+            return None;
+        }
+        let sample = sample_history(history, interpolation_delay, tick, true)?;
+        Some((parent, collider, sample))
+    }
+}
+
+#[cfg(all(feature = "2d", not(feature = "3d")))]
+fn rotation_value(rotation: Rotation) -> Scalar {
+    rotation.as_radians()
+}
+
+#[cfg(all(feature = "3d", not(feature = "2d")))]
+fn rotation_value(rotation: Rotation) -> Quaternion {
+    rotation.0
 }
 
 fn sample_history(
@@ -308,3 +658,280 @@ fn sample_history(
         rotation,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lag_compensation::history::LagCompensationPlugin;
+    use bevy_app::App;
+    use bevy_ecs::system::RunSystemOnce;
+    use bevy_time::TimeUpdateStrategy;
+    use core::time::Duration;
+    use lightyear_core::plugin::CorePlugins;
+    use lightyear_core::time::PositiveTickDelta;
+
+    const TICK_DURATION: Duration = Duration::from_millis(10);
+    /// The collider moves by one unit every tick
+    const SPEED: Scalar = 100.0;
+    /// The client sees the collider 10 ticks in the past
+    const DELAY: InterpolationDelay = InterpolationDelay {
+        delay: PositiveTickDelta::lit("10"),
+    };
+
+    #[cfg(all(feature = "2d", not(feature = "3d")))]
+    fn ball(radius: Scalar) -> Collider {
+        Collider::circle(radius)
+    }
+
+    #[cfg(all(feature = "3d", not(feature = "2d")))]
+    fn ball(radius: Scalar) -> Collider {
+        Collider::sphere(radius)
+    }
+
+    fn point(x: Scalar, y: Scalar) -> Vector {
+        Vector::X * x + Vector::Y * y
+    }
+
+    /// Spawns a lag-compensated collider on the server that moves along the x axis, and steps the
+    /// app until its history covers the interpolation delay.
+    fn setup() -> (App, Entity) {
+        let mut app = App::new();
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(TICK_DURATION));
+        app.add_plugins((
+            bevy_app::TaskPoolPlugin::default(),
+            CorePlugins {
+                tick_duration: TICK_DURATION,
+            },
+        ));
+        app.add_plugins(
+            PhysicsPlugins::default()
+                .build()
+                .disable::<PhysicsTransformPlugin>()
+                .disable::<IslandSleepingPlugin>()
+                .disable::<PhysicsInterpolationPlugin>(),
+        );
+        app.add_plugins(LagCompensationPlugin);
+        app.world_mut().spawn(Server::default());
+        let entity = app
+            .world_mut()
+            .spawn((
+                RigidBody::Kinematic,
+                ball(0.5),
+                CollisionLayers::default(),
+                LinearVelocity(Vector::X * SPEED),
+                LagCompensationHistory::default(),
+            ))
+            .id();
+        app.finish();
+        app.cleanup();
+        for _ in 0..20 {
+            app.update();
+        }
+        (app, entity)
+    }
+
+    /// Returns the historical position of the collider seen by the client, and its current position
+    fn poses(app: &mut App, entity: Entity) -> (Vector, Vector) {
+        let sample = app
+            .world_mut()
+            .run_system_once(move |query: LagCompensationSpatialQuery| {
+                query.sample_collider(DELAY, entity)
+            })
+            .unwrap()
+            .expect("the history should cover the interpolation delay");
+        let current = app.world().get::<Position>(entity).unwrap().0;
+        assert!(current.x - sample.position.x >= 5.0);
+        (sample.position.0, current)
+    }
+
+    fn cast_shape(
+        app: &mut App,
+        x: Scalar,
+        predicate: fn(Entity) -> bool,
+    ) -> Option<LagCompensationShapeHit> {
+        app.world_mut()
+            .run_system_once(move |query: LagCompensationSpatialQuery| {
+                query.cast_shape_predicate_with_sample(
+                    DELAY,
+                    &ball(0.25),
+                    point(x, 5.0),
+                    Rotation::default(),
+                    Dir::NEG_Y,
+                    &ShapeCastConfig::from_max_distance(10.0),
+                    &predicate,
+                    &SpatialQueryFilter::default(),
+                )
+            })
+            .unwrap()
+    }
+
+    fn shape_intersections(app: &mut App, x: Scalar) -> Vec<(Entity, LagCompensationSample)> {
+        app.world_mut()
+            .run_system_once(move |query: LagCompensationSpatialQuery| {
+                query.shape_intersections_with_sample(
+                    DELAY,
+                    &ball(0.25),
+                    point(x, 0.0),
+                    Rotation::default(),
+                    &SpatialQueryFilter::default(),
+                )
+            })
+            .unwrap()
+    }
+
+    fn project_point(app: &mut App, x: Scalar) -> Option<LagCompensationPointProjection> {
+        app.world_mut()
+            .run_system_once(move |query: LagCompensationSpatialQuery| {
+                query.project_point_with_sample(
+                    DELAY,
+                    point(x, 1.0),
+                    true,
+                    1.0,
+                    &SpatialQueryFilter::default(),
+                )
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn cast_shape_hits_the_historical_pose() {
+        let (mut app, entity) = setup();
+        let (historical, current) = poses(&mut app, entity);
+
+        let hit = cast_shape(&mut app, historical.x, |_| true)
+            .expect("the cast should hit the historical collider");
+        assert_eq!(hit.hit.entity, entity);
+        assert_eq!(hit.sample.position.0, historical);
+        // the cast starts 5.0 above the center of the collider of radius 0.5
+        assert!((hit.hit.distance - 4.25).abs() < 1e-3);
+
+        // the broad-phase envelope covers the current pose, but the collider was not there yet
+        assert!(cast_shape(&mut app, current.x, |_| true).is_none());
+        assert!(cast_shape(&mut app, historical.x, |_| false).is_none());
+
+        let hit = app
+            .world_mut()
+            .run_system_once(move |query: LagCompensationSpatialQuery| {
+                query.cast_shape(
+                    DELAY,
+                    &ball(0.25),
+                    point(historical.x, 5.0),
+                    Rotation::default(),
+                    Dir::NEG_Y,
+                    &ShapeCastConfig::from_max_distance(10.0),
+                    &SpatialQueryFilter::default(),
+                )
+            })
+            .unwrap();
+        assert_eq!(hit.map(|hit| hit.entity), Some(entity));
+    }
+
+    #[test]
+    fn shape_intersections_use_the_historical_pose() {
+        let (mut app, entity) = setup();
+        let (historical, current) = poses(&mut app, entity);
+
+        let intersections = shape_intersections(&mut app, historical.x);
+        assert_eq!(intersections.len(), 1);
+        assert_eq!(intersections[0].0, entity);
+        assert_eq!(intersections[0].1.position.0, historical);
+
+        assert!(shape_intersections(&mut app, current.x).is_empty());
+
+        let intersections = app
+            .world_mut()
+            .run_system_once(move |query: LagCompensationSpatialQuery| {
+                query.shape_intersections(
+                    DELAY,
+                    &ball(0.25),
+                    point(historical.x, 0.0),
+                    Rotation::default(),
+                    &SpatialQueryFilter::from_excluded_entities([entity]),
+                )
+            })
+            .unwrap();
+        assert!(intersections.is_empty());
+    }
+
+    #[test]
+    fn project_point_uses_the_historical_pose() {
+        let (mut app, entity) = setup();
+        let (historical, current) = poses(&mut app, entity);
+
+        let projection = project_point(&mut app, historical.x)
+            .expect("the point should be projected on the historical collider");
+        assert_eq!(projection.projection.entity, entity);
+        assert!(!projection.projection.is_inside);
+        assert!(
+            projection
+                .projection
+                .point
+                .distance(point(historical.x, 0.5))
+                < 1e-3
+        );
+        assert_eq!(projection.sample.position.0, historical);
+
+        // the historical collider is further than `max_distance` from the current pose
+        assert!(project_point(&mut app, current.x).is_none());
+
+        let projection = app
+            .world_mut()
+            .run_system_once(move |query: LagCompensationSpatialQuery| {
+                query.project_point(
+                    DELAY,
+                    point(historical.x, 1.0),
+                    true,
+                    1.0,
+                    &SpatialQueryFilter::default(),
+                )
+            })
+            .unwrap();
+        assert_eq!(projection.map(|projection| projection.entity), Some(entity));
+    }
+
+    #[test]
+    fn narrow_phase_candidate_samples_the_parent_of_the_envelope() {
+        let (mut app, entity) = setup();
+        let (historical, _) = poses(&mut app, entity);
+        let envelope = app
+            .world_mut()
+            .query_filtered::<Entity, With<AabbEnvelopeHolder>>()
+            .single(app.world())
+            .unwrap();
+
+        app.world_mut()
+            .run_system_once(move |query: LagCompensationSpatialQuery| {
+                let tick = query.timeline.tick();
+                let filter = SpatialQueryFilter::default();
+                let (parent, _, sample) = query
+                    .narrow_phase_candidate(envelope, &filter, DELAY, tick)
+                    .expect("the envelope should resolve to its parent");
+                assert_eq!(parent, entity);
+                assert_eq!(sample.position.0, historical);
+
+                // the filter applies to the parent collider rather than to the envelope
+                let excluded = SpatialQueryFilter::from_excluded_entities([entity]);
+                assert!(
+                    query
+                        .narrow_phase_candidate(envelope, &excluded, DELAY, tick)
+                        .is_none()
+                );
+                // only aabb envelopes are candidates
+                assert!(
+                    query
+                        .narrow_phase_candidate(entity, &filter, DELAY, tick)
+                        .is_none()
+                );
+                // the history does not go that far back
+                let too_old = InterpolationDelay {
+                    delay: PositiveTickDelta::lit("100"),
+                };
+                assert!(
+                    query
+                        .narrow_phase_candidate(envelope, &filter, too_old, tick)
+                        .is_none()
+                );
+            })
+            .unwrap();
+    }
+}
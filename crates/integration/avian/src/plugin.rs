@@ -183,7 +183,8 @@ pub struct LightyearAvianPlugin {
     /// `Position`, `Rotation`, velocities, forces, impulses, collider/joint configuration, or
     /// gameplay side effects. Register or deterministically recreate every such value that can
     /// change during predicted simulation. Collision events can be emitted again during replay, so
-    /// irreversible event consumers must also be rollback-aware.
+    /// irreversible event consumers must also be rollback-aware, for example by emitting their
+    /// effects through [`PredictedEvents`](lightyear_prediction::prelude::PredictedEvents).
     ///
     /// If Avian's `IslandPlugin` is enabled, island rollback state is registered automatically
     /// during `finish()`. If `IslandSleepingPlugin` is also enabled, sleeping state is rolled back too.
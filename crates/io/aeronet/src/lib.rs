@@ -27,8 +27,8 @@ use bevy_ecs::prelude::*;
 use bevy_ecs::relationship::Relationship;
 use bevy_reflect::Reflect;
 use lightyear_link::{
-    Link, LinkPlugin, LinkReceiveSystems, LinkSystems, Linked, Linking, Unlink, UnlinkReason,
-    Unlinked, recv_payload_from_bytes,
+    Link, LinkPlugin, LinkReceiveSystems, LinkSendSystems, LinkSystems, Linked, Linking, Unlink,
+    UnlinkReason, Unlinked, recv_payload_from_bytes,
 };
 use tracing::trace;
 
@@ -236,6 +236,6 @@ impl Plugin for AeronetPlugin {
             PreUpdate,
             Self::receive.in_set(LinkReceiveSystems::BufferToLink),
         );
-        app.add_systems(PostUpdate, Self::send.in_set(LinkSystems::Send));
+        app.add_systems(PostUpdate, Self::send.in_set(LinkSendSystems::LinkToBuffer));
     }
 }
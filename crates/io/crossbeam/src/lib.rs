@@ -51,7 +51,7 @@ use core::net::{Ipv4Addr, SocketAddr};
 use crossbeam_channel::{Receiver, Sender, TryRecvError, TrySendError};
 use lightyear_core::time::Instant;
 use lightyear_link::{
-    Link, LinkPlugin, LinkReceiveSystems, LinkStart, LinkSystems, Linked, Unlink, UnlinkReason,
+    Link, LinkPlugin, LinkReceiveSystems, LinkSendSystems, LinkStart, Linked, Unlink, UnlinkReason,
     recv_payload_from_bytes,
 };
 use tracing::{error, trace};
@@ -109,7 +109,7 @@ impl CrossbeamIo {
 /// - a [`LinkStart`] observer that immediately marks [`CrossbeamIo`] entities as [`Linked`];
 /// - a receive system in [`LinkReceiveSystems::BufferToLink`] that drains channel payloads into
 ///   [`Link::recv`];
-/// - a send system in [`LinkSystems::Send`] that flushes [`Link::send`] into the channel.
+/// - a send system in [`LinkSendSystems::LinkToBuffer`] that flushes [`Link::send`] into the channel.
 ///
 /// It does not implement authentication, handshake state, or `Connected`; pair it with a Lightyear
 /// connection plugin when higher-level connection state is needed.
@@ -226,7 +226,7 @@ impl Plugin for CrossbeamPlugin {
             PreUpdate,
             Self::receive.in_set(LinkReceiveSystems::BufferToLink),
         );
-        app.add_systems(PostUpdate, Self::send.in_set(LinkSystems::Send));
+        app.add_systems(PostUpdate, Self::send.in_set(LinkSendSystems::LinkToBuffer));
     }
 }
 
@@ -498,4 +498,97 @@ mod tests {
         assert!(world.get::<Connected>(mirror_entity).is_some(), "Connected");
         assert!(world.get::<ClientOf>(mirror_entity).is_some(), "ClientOf");
     }
+
+    /// A `RawClient` and a `RawServer` with `NoiseEncryption` only become connected once the
+    /// handshake completes over the crossbeam link, and then exchange encrypted payloads.
+    #[test]
+    fn raw_connection_with_noise_encryption() {
+        use lightyear_connection::prelude::client::Connect;
+        use lightyear_connection::prelude::server::ClientOf;
+        use lightyear_core::id::{PeerId, RemoteId};
+        use lightyear_link::prelude::server::LinkOf;
+        use lightyear_raw_connection::client::{RawClient, RawConnectionPlugin};
+        use lightyear_raw_connection::encryption::{NoiseEncryption, NoiseKeypair, NoiseSession};
+        use lightyear_raw_connection::prelude::server::RawServer;
+
+        let (client_io, server_io) = CrossbeamIo::new_pair();
+        let client_keypair = NoiseKeypair::generate();
+        let server_keypair = NoiseKeypair::generate();
+
+        let mut server_app = App::new();
+        server_app.add_plugins(CrossbeamPlugin);
+        server_app.add_plugins(lightyear_raw_connection::server::RawConnectionPlugin);
+        let server_entity = server_app
+            .world_mut()
+            .spawn((RawServer, NoiseEncryption::new(server_keypair.clone())))
+            .id();
+        let link_of_entity = server_app
+            .world_mut()
+            .spawn((
+                LinkOf {
+                    server: server_entity,
+                },
+                Link::default(),
+                server_io,
+            ))
+            .id();
+        server_app.world_mut().trigger(LinkStart {
+            entity: link_of_entity,
+        });
+
+        let mut client_app = App::new();
+        client_app.add_plugins(CrossbeamPlugin);
+        client_app.add_plugins(RawConnectionPlugin);
+        let client_entity = client_app
+            .world_mut()
+            .spawn((
+                RawClient,
+                NoiseEncryption::new(client_keypair.clone())
+                    .with_remote_key(server_keypair.public_key()),
+                client_io,
+            ))
+            .id();
+        client_app.world_mut().trigger(Connect {
+            entity: client_entity,
+        });
+
+        // the client is linked but only sent message 1
+        client_app.update();
+        assert!(client_app.world().get::<Linked>(client_entity).is_some());
+        assert!(client_app.world().get::<Connected>(client_entity).is_none());
+
+        for _ in 0..3 {
+            server_app.update();
+            client_app.update();
+        }
+        let client = client_app.world().entity(client_entity);
+        assert!(client.contains::<Connected>());
+        assert_eq!(
+            client.get::<NoiseSession>().unwrap().remote_key(),
+            Some(server_keypair.public_key())
+        );
+        let link_of = server_app.world().entity(link_of_entity);
+        assert!(link_of.contains::<Connected>());
+        assert!(link_of.contains::<ClientOf>());
+        assert_eq!(
+            link_of.get::<RemoteId>(),
+            Some(&RemoteId(PeerId::PublicKey(client_keypair.public_key())))
+        );
+
+        // payloads are encrypted by the client and decrypted by the server
+        client_app
+            .world_mut()
+            .get_mut::<Link>(client_entity)
+            .unwrap()
+            .send
+            .push(Bytes::from_static(b"hello"));
+        client_app.update();
+        server_app.update();
+        let mut server_link = server_app
+            .world_mut()
+            .get_mut::<Link>(link_of_entity)
+            .unwrap();
+        assert_eq!(server_link.recv.pop().unwrap().as_ref(), b"hello");
+        assert!(server_link.recv.pop().is_none());
+    }
 }
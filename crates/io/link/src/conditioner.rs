@@ -1,5 +1,11 @@
-//! Receive-side packet conditioning for simulated network latency, jitter, and loss.
-
+//! Packet conditioning for simulated network latency, jitter, loss, duplication, reordering and
+//! bandwidth limits.
+//!
+//! A [`LinkConditioner`] can be installed on the receive side ([`Link::recv`](crate::Link::recv))
+//! or on the send side ([`Link::send`](crate::Link::send)) of a link. Using both simulates
+//! asymmetric links, for example a client whose upload is much slower than its download.
+
+use alloc::vec::Vec;
 use bevy_reflect::Reflect;
 use core::time::Duration;
 use lightyear_core::time::Instant;
@@ -20,12 +26,16 @@ pub enum LinkConditionerState {
     Bad,
 }
 
-/// Configuration for receive-side packet conditioning.
+/// Configuration for packet conditioning.
 ///
-/// The values describe the local inbound path: a payload inserted into a
+/// The values describe one direction of the path: a payload inserted into a
 /// [`LinkConditioner`] can be delayed by [`incoming_latency`](Self::incoming_latency),
 /// randomly shifted by [`incoming_jitter`](Self::incoming_jitter), or dropped
-/// according to the loss probabilities.
+/// according to the loss probabilities. When the conditioner is installed on the send side of a
+/// link, the `incoming_*` values apply to outgoing payloads.
+///
+/// Payloads can also be [duplicated](Self::duplicate), [reordered](Self::reorder), and delayed
+/// or tail-dropped by a [bandwidth bottleneck](Self::bandwidth).
 ///
 /// Build a configuration from [`default`](Default::default) with the
 /// `with_` methods:
@@ -112,11 +122,64 @@ pub struct LinkConditionerConfig {
     /// Probability of going from [`Bad`](LinkConditionerState::Bad) to
     /// [`Good`](LinkConditionerState::Good) after each packet (`0.0..=1.0`).
     pub bad_to_good: f32,
+
+    /// Probability that a delivered packet is delivered a second time (`0.0..=1.0`).
+    ///
+    /// The copy gets its own jitter, so it can arrive before or after the original.
+    pub duplicate: f32,
+
+    /// Probability that a packet is held back by [`reorder_delay`](Self::reorder_delay) so that
+    /// later packets overtake it (`0.0..=1.0`).
+    pub reorder: f32,
+
+    /// Extra delay applied to reordered packets.
+    pub reorder_delay: Duration,
+
+    /// Bottleneck bandwidth in bytes per second. `None` means unlimited.
+    ///
+    /// Packets are serialized through the bottleneck one after the other, so bursts are spread
+    /// over time before the latency is applied.
+    pub bandwidth: Option<u32>,
+
+    /// Maximum number of bytes waiting for the bottleneck.
+    ///
+    /// A packet which would overflow the queue is dropped (tail drop). A packet arriving while the
+    /// bottleneck is idle is always accepted. Only used when [`bandwidth`](Self::bandwidth) is set.
+    pub queue_size: usize,
 }
 
-/// Generic receive-side packet conditioner.
+/// Scripted changes of a [`LinkConditioner`]'s configuration over time.
+///
+/// Offsets are measured from the first time the conditioner is used. For example, a network
+/// outage of 2 seconds starting 10 seconds after the link is established:
 ///
-/// `LinkConditioner` delays and drops payloads according to a [`LinkConditionerConfig`].
+/// ```
+/// # use core::time::Duration;
+/// # use lightyear_link::prelude::{LinkConditionerConfig, LinkConditionerScript};
+/// let normal = LinkConditionerConfig::good_condition();
+/// let script = LinkConditionerScript::default()
+///     .at(Duration::from_secs(10), LinkConditionerConfig::default().with_fixed_loss(1.0))
+///     .at(Duration::from_secs(12), normal.clone());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LinkConditionerScript {
+    steps: Vec<(Duration, LinkConditionerConfig)>,
+}
+
+impl LinkConditionerScript {
+    /// Replaces the conditioner's configuration with `config` once `offset` has elapsed.
+    #[must_use]
+    pub fn at(mut self, offset: Duration, config: LinkConditionerConfig) -> Self {
+        let index = self.steps.partition_point(|(step, _)| *step <= offset);
+        self.steps.insert(index, (offset, config));
+        self
+    }
+}
+
+/// Generic packet conditioner.
+///
+/// `LinkConditioner` delays, drops, duplicates, and reorders payloads according to a
+/// [`LinkConditionerConfig`], optionally changed over time by a [`LinkConditionerScript`].
 #[derive(Debug, Clone)]
 pub struct LinkConditioner<P: Eq> {
     config: LinkConditionerConfig,
@@ -125,6 +188,15 @@ pub struct LinkConditioner<P: Eq> {
     /// per packet by the chain probabilities in [`LinkConditionerConfig`].
     state: LinkConditionerState,
 
+    script: LinkConditionerScript,
+    /// Index of the next script step to apply.
+    next_step: usize,
+    /// Instant of the first use of the conditioner, used as the origin of the script.
+    started_at: Option<Instant>,
+
+    /// Instant at which the bandwidth bottleneck finishes sending the packets queued so far.
+    bottleneck_free_at: Option<Instant>,
+
     /// Payloads waiting for their simulated delivery time.
     ///
     /// The key is the delivery [`Instant`]. Once that instant is less than or equal to the instant
@@ -141,15 +213,71 @@ impl<P: Eq> LinkConditioner<P> {
         LinkConditioner {
             config,
             state: LinkConditionerState::default(),
+            script: LinkConditionerScript::default(),
+            next_step: 0,
+            started_at: None,
+            bottleneck_free_at: None,
             time_queue: ReadyBuffer::new(),
         }
     }
 
-    /// Applies latency, jitter, and loss to `packet` relative to `instant`.
+    /// Changes the configuration over time according to `script`.
+    #[must_use]
+    pub fn with_script(mut self, script: LinkConditionerScript) -> Self {
+        self.script = script;
+        self.next_step = 0;
+        self
+    }
+
+    /// Returns the configuration currently in effect.
+    pub fn config(&self) -> &LinkConditionerConfig {
+        &self.config
+    }
+
+    /// Applies the script steps whose offset has elapsed at `instant`.
+    fn advance_script(&mut self, instant: Instant) {
+        let started_at = *self.started_at.get_or_insert(instant);
+        let elapsed = instant.saturating_duration_since(started_at);
+        while let Some((offset, config)) = self.script.steps.get(self.next_step)
+            && *offset <= elapsed
+        {
+            self.config = config.clone();
+            self.next_step += 1;
+        }
+    }
+
+    /// Returns the instant at which a packet that left the bottleneck at `departure` is delivered.
+    fn delivery_instant(&self, departure: Instant, rng: &mut impl RngExt) -> Instant {
+        let mut latency: i32 = self.config.incoming_latency.as_millis() as i32;
+        if self.config.incoming_jitter > Duration::default() {
+            let jitter: i32 = self.config.incoming_jitter.as_millis() as i32;
+            latency += rng.random_range(-jitter..jitter);
+        }
+        let mut delivery = departure;
+        if latency > 0 {
+            delivery += Duration::from_millis(latency as u64);
+        }
+        if self.config.reorder > 0.0 && rng.random_range(0.0..1.0) < self.config.reorder {
+            delivery += self.config.reorder_delay;
+        }
+        delivery
+    }
+
+    /// Returns the next packet whose delivery instant has elapsed.
+    pub(crate) fn pop_packet(&mut self, instant: Instant) -> Option<P> {
+        self.advance_script(instant);
+        self.time_queue.pop_item(&instant).map(|(_, packet)| packet)
+    }
+}
+
+impl<P: Eq + Clone + AsRef<[u8]>> LinkConditioner<P> {
+    /// Applies loss, bandwidth, latency, jitter, reordering and duplication to `packet` relative
+    /// to `instant`.
     ///
     /// Dropped packets are discarded immediately. Delivered packets are queued by their simulated
     /// delivery instant.
     pub(crate) fn condition_packet(&mut self, packet: P, instant: Instant) {
+        self.advance_script(instant);
         let mut rng = rand::rng();
 
         // Execute the Gilbert–Elliott model to decide packet loss. See
@@ -170,21 +298,38 @@ impl<P: Eq> LinkConditioner<P> {
             return;
         }
 
-        let mut latency: i32 = self.config.incoming_latency.as_millis() as i32;
-        let mut packet_timestamp = instant;
-        if self.config.incoming_jitter > Duration::default() {
-            let jitter: i32 = self.config.incoming_jitter.as_millis() as i32;
-            latency += rng.random_range(-jitter..jitter);
-        }
-        if latency > 0 {
-            packet_timestamp += Duration::from_millis(latency as u64);
+        let Some(departure) = self.enqueue_in_bottleneck(packet.as_ref().len(), instant) else {
+            // Tail drop.
+            return;
+        };
+        if self.config.duplicate > 0.0 && rng.random_range(0.0..1.0) < self.config.duplicate {
+            let duplicate_timestamp = self.delivery_instant(departure, &mut rng);
+            self.time_queue.push(duplicate_timestamp, packet.clone());
         }
+        let packet_timestamp = self.delivery_instant(departure, &mut rng);
         self.time_queue.push(packet_timestamp, packet);
     }
 
-    /// Returns the next packet whose delivery instant has elapsed.
-    pub(crate) fn pop_packet(&mut self, instant: Instant) -> Option<P> {
-        self.time_queue.pop_item(&instant).map(|(_, packet)| packet)
+    /// Queues a packet of `len` bytes in the bandwidth bottleneck.
+    ///
+    /// Returns the instant at which the packet leaves the bottleneck, or `None` if the queue is
+    /// full.
+    fn enqueue_in_bottleneck(&mut self, len: usize, instant: Instant) -> Option<Instant> {
+        let Some(bandwidth) = self.config.bandwidth.filter(|bandwidth| *bandwidth > 0) else {
+            return Some(instant);
+        };
+        let free_at = self
+            .bottleneck_free_at
+            .filter(|free_at| *free_at > instant)
+            .unwrap_or(instant);
+        let queued_bytes =
+            free_at.saturating_duration_since(instant).as_secs_f64() * bandwidth as f64;
+        if queued_bytes > 0.0 && queued_bytes + len as f64 > self.config.queue_size as f64 {
+            return None;
+        }
+        let departure = free_at + Duration::from_secs_f64(len as f64 / bandwidth as f64);
+        self.bottleneck_free_at = Some(departure);
+        Some(departure)
     }
 }
 
@@ -203,6 +348,30 @@ impl LinkConditionerConfig {
         self
     }
 
+    /// Replaces [`duplicate`](Self::duplicate).
+    #[must_use]
+    pub fn with_duplication(mut self, duplicate: f32) -> Self {
+        self.duplicate = duplicate;
+        self
+    }
+
+    /// Replaces [`reorder`](Self::reorder) and [`reorder_delay`](Self::reorder_delay).
+    #[must_use]
+    pub fn with_reordering(mut self, reorder: f32, reorder_delay: Duration) -> Self {
+        self.reorder = reorder;
+        self.reorder_delay = reorder_delay;
+        self
+    }
+
+    /// Limits the link to `bytes_per_second`, dropping packets when more than `queue_size` bytes
+    /// are waiting for the bottleneck.
+    #[must_use]
+    pub fn with_bandwidth(mut self, bytes_per_second: u32, queue_size: usize) -> Self {
+        self.bandwidth = Some(bytes_per_second);
+        self.queue_size = queue_size;
+        self
+    }
+
     /// Replaces the packet-loss probability model with a fixed loss
     /// probability. Every packet is lost independently with the probability
     /// `loss_probability`.
@@ -360,7 +529,8 @@ impl LinkConditionerConfig {
 
     /// Returns an approximate one-way half of this configuration.
     ///
-    /// This divides latency, jitter, and loss probabilities by two.
+    /// This divides latency, jitter, and loss probabilities by two. Duplication, reordering and
+    /// bandwidth limits are kept as is.
     pub fn half(self) -> Self {
         LinkConditionerConfig {
             incoming_latency: self.incoming_latency / 2,
@@ -470,6 +640,87 @@ mod tests {
         }
     }
 
+    fn drain(conditioner: &mut LinkConditioner<Vec<u8>>, instant: Instant) -> Vec<Vec<u8>> {
+        core::iter::from_fn(|| conditioner.pop_packet(instant)).collect()
+    }
+
+    #[test]
+    fn duplication_delivers_packets_twice() {
+        let mut conditioner =
+            LinkConditioner::new(LinkConditionerConfig::default().with_duplication(1.0));
+        let now = Instant::now();
+        conditioner.condition_packet(alloc::vec![1], now);
+        assert_eq!(
+            drain(&mut conditioner, now),
+            [alloc::vec![1], alloc::vec![1]]
+        );
+    }
+
+    #[test]
+    fn reordered_packets_are_overtaken() {
+        let mut conditioner = LinkConditioner::new(
+            LinkConditionerConfig::default().with_reordering(1.0, Duration::from_millis(50)),
+        );
+        let now = Instant::now();
+        conditioner.condition_packet(alloc::vec![1], now);
+        conditioner.config.reorder = 0.0;
+        conditioner.condition_packet(alloc::vec![2], now);
+        assert_eq!(drain(&mut conditioner, now), [alloc::vec![2]]);
+        assert_eq!(
+            drain(&mut conditioner, now + Duration::from_millis(50)),
+            [alloc::vec![1]]
+        );
+    }
+
+    #[test]
+    fn bandwidth_cap_spreads_and_tail_drops_packets() {
+        // 1000 bytes per second with room for 200 bytes in the queue
+        let mut conditioner =
+            LinkConditioner::new(LinkConditionerConfig::default().with_bandwidth(1000, 200));
+        let now = Instant::now();
+        for i in 0..3 {
+            conditioner.condition_packet(alloc::vec![i; 100], now);
+        }
+        // the third packet overflowed the queue
+        assert_eq!(conditioner.time_queue.len(), 2);
+        // each packet takes 100ms to go through the bottleneck
+        assert_eq!(
+            drain(&mut conditioner, now + Duration::from_millis(50)).len(),
+            0
+        );
+        assert_eq!(
+            drain(&mut conditioner, now + Duration::from_millis(150)).len(),
+            1
+        );
+        assert_eq!(
+            drain(&mut conditioner, now + Duration::from_millis(250)).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn script_changes_the_configuration_over_time() {
+        let outage = LinkConditionerConfig::default().with_fixed_loss(1.0);
+        let mut conditioner = LinkConditioner::new(LinkConditionerConfig::default()).with_script(
+            LinkConditionerScript::default()
+                .at(Duration::from_secs(12), LinkConditionerConfig::default())
+                .at(Duration::from_secs(10), outage.clone()),
+        );
+        let start = Instant::now();
+        assert_eq!(conditioner.pop_packet(start), None);
+
+        conditioner.condition_packet(alloc::vec![1], start + Duration::from_secs(9));
+        assert_eq!(conditioner.time_queue.len(), 1);
+        drain(&mut conditioner, start + Duration::from_secs(9));
+
+        conditioner.condition_packet(alloc::vec![2], start + Duration::from_secs(10));
+        assert_eq!(conditioner.config(), &outage);
+        assert!(conditioner.time_queue.is_empty());
+
+        conditioner.condition_packet(alloc::vec![3], start + Duration::from_secs(12));
+        assert_eq!(conditioner.time_queue.len(), 1);
+    }
+
     #[test]
     fn constructor_ladder_reduces_downward() {
         // Each constructor with its extra knob pinned must produce exactly the
@@ -13,7 +13,8 @@
 //!   consume them.
 //! - [`LinkSender`] buffers payloads produced by higher-level systems until a transport flushes
 //!   them.
-//! - [`LinkConditioner`] can delay or drop inbound payloads to simulate imperfect networks.
+//! - [`LinkConditioner`] can delay, drop, duplicate, or reorder inbound and outbound payloads to
+//!   simulate imperfect networks.
 //! - [`Linking`], [`Linked`], and [`Unlinked`] are mutually exclusive ECS marker components that
 //!   keep [`Link::state`] synchronized with the entity lifecycle.
 //!
@@ -44,15 +45,18 @@ use lightyear_core::time::Instant;
 use lightyear_utils::adaptive_for_each_mut;
 
 pub mod prelude {
-    pub use crate::conditioner::{LinkConditionerConfig, LinkConditionerState};
-    pub use crate::server::{LinkOf, Server};
+    pub use crate::conditioner::{
+        LinkConditionerConfig, LinkConditionerScript, LinkConditionerState,
+    };
+    pub use crate::server::{AcceptedBy, LinkOf, ListenerOf, Listeners, Server};
     pub use crate::{
-        DEFAULT_MTU, Link, LinkMtu, LinkStart, LinkStats, LinkSystems, Linked, Linking,
-        MtuTooSmall, RecvLinkConditioner, Unlink, UnlinkReason, Unlinked,
+        DEFAULT_MTU, Link, LinkMtu, LinkSendSystems, LinkStart, LinkStats, LinkSystems, Linked,
+        Linking, MtuTooSmall, RecvLinkConditioner, SendLinkConditioner, Unlink, UnlinkReason,
+        Unlinked,
     };
 
     pub mod server {
-        pub use crate::server::{LinkOf, Server};
+        pub use crate::server::{AcceptedBy, LinkOf, ListenerOf, Listeners, Server};
     }
 }
 
@@ -129,6 +133,12 @@ pub struct Link {
 /// [`prelude::LinkConditionerConfig`] values.
 pub type RecvLinkConditioner = LinkConditioner<RecvPayload>;
 
+/// Packet conditioner used for outbound [`SendPayload`] values.
+///
+/// Combined with a [`RecvLinkConditioner`], this can simulate asymmetric links or send-side
+/// bottlenecks.
+pub type SendLinkConditioner = LinkConditioner<SendPayload>;
+
 impl Link {
     /// Configures the receive-side network conditioner.
     ///
@@ -142,6 +152,18 @@ impl Link {
         self
     }
 
+    /// Configures the send-side network conditioner.
+    ///
+    /// Outgoing payloads are conditioned right before the IO backend flushes them, in
+    /// [`LinkSendSystems::ApplyConditioner`].
+    pub fn with_send_conditioner(
+        mut self,
+        send_conditioner: impl Into<Option<SendLinkConditioner>>,
+    ) -> Self {
+        self.send.conditioner = send_conditioner.into();
+        self
+    }
+
     /// Configures the link's minimum and current MTU characteristics.
     ///
     /// This is intended for constructing a link. Once constructed, only the current MTU can be
@@ -165,6 +187,18 @@ impl Link {
     pub const fn set_mtu(&mut self, mtu: usize) -> Result<(), MtuTooSmall> {
         self.mtu.set_mtu(mtu)
     }
+
+    /// Returns the largest payload accepted by the layers between the link and the IO.
+    pub const fn max_mtu(&self) -> usize {
+        self.mtu.max_mtu()
+    }
+
+    /// Limits the size of the payloads, for connection layers which cannot send larger ones.
+    ///
+    /// See [`LinkMtu::set_max_mtu`].
+    pub const fn set_max_mtu(&mut self, max_mtu: usize) -> Result<(), MtuTooSmall> {
+        self.mtu.set_max_mtu(max_mtu)
+    }
 }
 
 /// Receive-side payload queue for a [`Link`].
@@ -237,8 +271,17 @@ impl LinkReceiver {
 ///
 /// Higher-level systems enqueue payloads here. Transport plugins drain the queue during
 /// [`LinkSystems::Send`] and write each [`SendPayload`] to their concrete IO backend.
+///
+/// If [`conditioner`](Self::conditioner) is present, queued payloads are routed through
+/// [`LinkConditioner`] in [`LinkSendSystems::ApplyConditioner`], before the IO backend flushes
+/// them.
 #[derive(Default)]
-pub struct LinkSender(VecDeque<SendPayload>);
+pub struct LinkSender {
+    buffer: VecDeque<SendPayload>,
+    /// Optional send-side link conditioner for latency, jitter, packet-loss and bandwidth
+    /// simulation.
+    pub conditioner: Option<LinkConditioner<SendPayload>>,
+}
 
 impl LinkSender {
     /// Drains every queued outgoing payload in FIFO order.
@@ -246,7 +289,7 @@ impl LinkSender {
     /// Transport systems typically call this in [`LinkSystems::Send`] once they are ready to flush
     /// all pending packets for the frame or tick.
     pub fn drain(&mut self) -> Drain<'_, SendPayload> {
-        self.0.drain(..)
+        self.buffer.drain(..)
     }
 
     /// Removes and returns the oldest queued outgoing payload.
@@ -254,28 +297,28 @@ impl LinkSender {
     /// This is useful for transports that send one packet at a time or need to requeue a packet
     /// with [`push_front`](Self::push_front) if the backend reports backpressure.
     pub fn pop(&mut self) -> Option<SendPayload> {
-        self.0.pop_front()
+        self.buffer.pop_front()
     }
 
     /// Appends an outgoing payload to the back of the FIFO queue.
     pub fn push(&mut self, value: SendPayload) {
-        self.0.push_back(value)
+        self.buffer.push_back(value)
     }
 
     /// Prepends an outgoing payload to the front of the queue.
     pub fn push_front(&mut self, value: SendPayload) {
-        self.0.push_front(value)
+        self.buffer.push_front(value)
     }
 
     /// Returns the number of outgoing payloads waiting to be flushed.
     pub fn len(&self) -> usize {
-        self.0.len()
+        self.buffer.len()
     }
 
     /// Iterates over queued outgoing payloads without consuming them.
     #[cfg(feature = "test_utils")]
     pub fn iter(&self) -> impl Iterator<Item = &SendPayload> {
-        self.0.iter()
+        self.buffer.iter()
     }
 }
 
@@ -343,6 +386,19 @@ pub enum LinkReceiveSystems {
     ApplyConditioner,
 }
 
+/// System sets that make up [`LinkSystems::Send`].
+///
+/// Transport plugins should put their raw send systems in [`LinkToBuffer`](Self::LinkToBuffer).
+/// [`LinkPlugin`] runs [`ApplyConditioner`](Self::ApplyConditioner) first so that only packets
+/// whose simulated delay has elapsed are flushed.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum LinkSendSystems {
+    /// Move queued packets through the send-side [`LinkConditioner`].
+    ApplyConditioner,
+    /// Flush packets from [`LinkSender`] to IO.
+    LinkToBuffer,
+}
+
 /// Entity event requesting that a transport start establishing a [`Link`].
 ///
 /// `LinkStart` is transport-facing: A transport plugin observes this event for its
@@ -470,10 +526,11 @@ impl Unlinked {
 /// This plugin configures system sets for:
 /// - receiving data into [`Link`] buffers via [`LinkSystems::Receive`];
 /// - applying receive-side link conditioning via [`LinkReceiveSystems::ApplyConditioner`];
+/// - applying send-side link conditioning via [`LinkSendSystems::ApplyConditioner`];
 /// - sending data from [`Link`] buffers via [`LinkSystems::Send`].
 ///
 /// Concrete transport plugins normally add this plugin, then schedule their IO systems inside
-/// [`LinkReceiveSystems::BufferToLink`] and [`LinkSystems::Send`].
+/// [`LinkReceiveSystems::BufferToLink`] and [`LinkSendSystems::LinkToBuffer`].
 pub struct LinkPlugin;
 
 impl LinkPlugin {
@@ -497,6 +554,28 @@ impl LinkPlugin {
         });
     }
 
+    /// Routes queued outgoing packets through each link's send-side conditioner.
+    ///
+    /// Payloads queued in [`LinkSender`] during the frame are handed to the [`LinkConditioner`],
+    /// then the payloads whose simulated delivery time has elapsed are queued again for the IO
+    /// backend. It is installed in [`LinkSendSystems::ApplyConditioner`] by [`LinkPlugin`].
+    pub fn apply_send_link_conditioner(mut query: Query<&mut Link>) {
+        let query = adaptive_for_each_mut!(query);
+        query.for_each(|mut link| {
+            // enable split borrows
+            let send = &mut link.send;
+            if let Some(conditioner) = &mut send.conditioner {
+                let now = Instant::now();
+                for packet in send.buffer.drain(..) {
+                    conditioner.condition_packet(packet, now);
+                }
+                while let Some(packet) = conditioner.pop_packet(now) {
+                    send.buffer.push_back(packet);
+                }
+            }
+        });
+    }
+
     /// Handles [`Unlink`] requests by inserting [`Unlinked`].
     fn unlink(mut unlink: On<Unlink>, mut commands: Commands) {
         if let Ok(mut c) = commands.get_entity(unlink.entity) {
@@ -522,7 +601,19 @@ impl Plugin for LinkPlugin {
                 .in_set(LinkSystems::Receive)
                 .chain(),
         );
-        app.configure_sets(PostUpdate, LinkSystems::Send);
+        app.add_systems(
+            PostUpdate,
+            Self::apply_send_link_conditioner.in_set(LinkSendSystems::ApplyConditioner),
+        );
+        app.configure_sets(
+            PostUpdate,
+            (
+                LinkSendSystems::ApplyConditioner,
+                LinkSendSystems::LinkToBuffer,
+            )
+                .in_set(LinkSystems::Send)
+                .chain(),
+        );
 
         app.add_observer(Self::unlink);
     }
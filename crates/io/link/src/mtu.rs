@@ -12,10 +12,15 @@ pub const DEFAULT_MTU: usize = 1200;
 ///
 /// Both peers must agree on the minimum MTU. The transport derives its fixed fragment payload size
 /// from this value instead of repeating that size in every fragment packet.
+///
+/// The layers between the link and the IO can also limit the size of the payloads they accept,
+/// for example because they add their own header. That limit is the [`max_mtu`](Self::max_mtu),
+/// above which path-MTU discovery never probes.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct LinkMtu {
     min_mtu: usize,
     mtu: usize,
+    max_mtu: usize,
 }
 
 impl LinkMtu {
@@ -24,6 +29,7 @@ impl LinkMtu {
         Self {
             min_mtu,
             mtu: min_mtu,
+            max_mtu: usize::MAX,
         }
     }
 
@@ -37,6 +43,13 @@ impl LinkMtu {
         self.mtu
     }
 
+    /// Returns the largest payload accepted by the layers between the link and the IO.
+    ///
+    /// This is `usize::MAX` unless a connection layer limited it.
+    pub const fn max_mtu(self) -> usize {
+        self.max_mtu
+    }
+
     /// Updates the current MTU without allowing it to fall below the stable minimum.
     pub const fn set_mtu(&mut self, mtu: usize) -> Result<(), MtuTooSmall> {
         if mtu < self.min_mtu {
@@ -48,6 +61,23 @@ impl LinkMtu {
         self.mtu = mtu;
         Ok(())
     }
+
+    /// Limits the size of the payloads, and lowers the current MTU to that limit if needed.
+    ///
+    /// The limit cannot fall below the stable minimum.
+    pub const fn set_max_mtu(&mut self, max_mtu: usize) -> Result<(), MtuTooSmall> {
+        if max_mtu < self.min_mtu {
+            return Err(MtuTooSmall {
+                mtu: max_mtu,
+                min: self.min_mtu,
+            });
+        }
+        self.max_mtu = max_mtu;
+        if self.mtu > max_mtu {
+            self.mtu = max_mtu;
+        }
+        Ok(())
+    }
 }
 
 impl Default for LinkMtu {
@@ -94,4 +124,21 @@ mod tests {
         assert_eq!(mtu.set_mtu(899), Err(MtuTooSmall { mtu: 899, min: 900 }));
         assert_eq!(mtu.mtu(), 1400);
     }
+
+    #[test]
+    fn max_mtu_lowers_current_value() {
+        let mut mtu = LinkMtu::new(900);
+        assert_eq!(mtu.max_mtu(), usize::MAX);
+        mtu.set_mtu(1400).unwrap();
+
+        mtu.set_max_mtu(1200).unwrap();
+        assert_eq!(mtu.max_mtu(), 1200);
+        assert_eq!(mtu.mtu(), 1200);
+
+        assert_eq!(
+            mtu.set_max_mtu(899),
+            Err(MtuTooSmall { mtu: 899, min: 900 })
+        );
+        assert_eq!(mtu.max_mtu(), 1200);
+    }
 }
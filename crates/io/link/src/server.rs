@@ -4,9 +4,26 @@
 //! Bevy's relationship API: [`Server`] is the relationship target, and [`LinkOf`] is inserted on
 //! each child link entity to point back to the server. Transport crates can use this to keep the
 //! server endpoint independent from the concrete links used for each connected peer.
+//!
+//! A server can also accept links through several transports at once, for example UDP for native
+//! clients and WebTransport for browser clients. Each transport is inserted on its own listener
+//! entity with [`ListenerOf`] pointing to the server. The links accepted by a listener belong to
+//! the server, so that the connection and replication layers only deal with a single server, and
+//! keep an [`AcceptedBy`] relationship to their listener:
+//!
+//! ```rust,ignore
+//! let server = commands.spawn((Server::default(), NetcodeServer::new(config))).id();
+//! commands.spawn((ListenerOf { server }, LocalAddr(udp_addr), ServerUdpIo::default()));
+//! commands.spawn((ListenerOf { server }, LocalAddr(wt_addr), WebTransportServerIo { certificate }));
+//! commands.trigger(Start { entity: server });
+//! ```
+//!
+//! Starting the server starts all its listeners, and the server is [`Linked`] once all of them are
+//! linked. If a listener fails, the whole server is unlinked.
 
 use crate::{
-    Link, LinkPlugin, Linked, Linking, RecvLinkConditioner, Unlink, UnlinkReason, Unlinked,
+    Link, LinkPlugin, LinkStart, Linked, Linking, RecvLinkConditioner, SendLinkConditioner, Unlink,
+    UnlinkReason, Unlinked,
 };
 use alloc::{format, vec::Vec};
 use bevy_app::{App, Plugin};
@@ -43,6 +60,9 @@ pub struct Server {
     /// child link receives an independent clone whose runtime state lives in [`Link::recv`].
     #[reflect(ignore)]
     pub conditioner: Option<RecvLinkConditioner>,
+    /// Send conditioner cloned into each new [`LinkOf`] child.
+    #[reflect(ignore)]
+    pub send_conditioner: Option<SendLinkConditioner>,
 }
 
 impl Server {
@@ -51,9 +71,16 @@ impl Server {
         Self {
             links: Vec::new(),
             conditioner,
+            send_conditioner: None,
         }
     }
 
+    /// Sets the send conditioner cloned into each new [`LinkOf`] child.
+    pub fn with_send_conditioner(mut self, send_conditioner: Option<SendLinkConditioner>) -> Self {
+        self.send_conditioner = send_conditioner;
+        self
+    }
+
     fn on_add(mut world: DeferredWorld, context: HookContext) {
         let entity_ref = world.entity(context.entity);
         if !entity_ref.contains::<Unlinked>()
@@ -67,6 +94,21 @@ impl Server {
         };
     }
 
+    /// `on_add` hook for the server transport components, such as `ServerUdpIo`.
+    ///
+    /// It inserts [`Server`] on the transport entity, unless the entity is a listener with
+    /// [`ListenerOf`]: the links accepted by a listener belong to the server of the listener, so
+    /// that there is still a single [`Server`] entity.
+    pub fn on_add_transport(mut world: DeferredWorld, context: HookContext) {
+        let entity_ref = world.entity(context.entity);
+        if !entity_ref.contains::<ListenerOf>() && !entity_ref.contains::<Server>() {
+            world
+                .commands()
+                .entity(context.entity)
+                .insert(Server::default());
+        }
+    }
+
     fn unlinked(
         trigger: On<Add, Unlinked>,
         mut query: Query<(&Server, &Unlinked)>,
@@ -94,6 +136,9 @@ impl Server {
 /// entity.
 /// The custom relationship hooks keep the [`Server`] collection up to date without
 /// despawning the server entity when the last link is removed.
+///
+/// If `server` is a listener entity with [`ListenerOf`], the link is related to the server of the
+/// listener instead, and [`AcceptedBy`] is inserted to remember the listener.
 #[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Reflect)]
 #[component(on_insert = LinkOf::on_insert_hook)]
 #[component(on_discard = LinkOf::on_discard)]
@@ -133,7 +178,20 @@ impl LinkOf {
             RelationshipHookMode::Skip => return,
             RelationshipHookMode::RunIfNotLinked => return,
         }
-        let target_entity = world.entity(entity).get::<Self>().unwrap().get();
+        let mut target_entity = world.entity(entity).get::<Self>().unwrap().get();
+        if let Some(listener_of) = world
+            .get_entity(target_entity)
+            .ok()
+            .and_then(|target| target.get::<ListenerOf>().copied())
+        {
+            let listener = target_entity;
+            target_entity = listener_of.server;
+            world.get_mut::<Self>(entity).unwrap().server = target_entity;
+            world
+                .commands()
+                .entity(entity)
+                .insert(AcceptedBy { listener });
+        }
         if target_entity == entity {
             warn!(
                 "{}The {}({target_entity:?}) relationship on entity {entity:?} points to itself. The invalid {} relationship has been removed.",
@@ -196,11 +254,151 @@ impl LinkOf {
     }
 }
 
-/// Copies a server's receive conditioner into each newly-created client link.
+/// Relationship source inserted on a listener entity that accepts links for another [`Server`].
+///
+/// The listener holds one server transport, for example `ServerUdpIo` and its `LocalAddr`. The
+/// links that it accepts are related to [`server`](Self::server) through [`LinkOf`]. The listener
+/// is not a [`Server`] itself, as long as [`ListenerOf`] is inserted with or before the transport.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Reflect)]
+#[relationship(relationship_target = Listeners)]
+pub struct ListenerOf {
+    /// Server that owns the links accepted by this listener.
+    #[relationship]
+    pub server: Entity,
+}
+
+/// Relationship target listing the listener entities of a [`Server`].
+///
+/// Listeners are despawned with their server.
+#[derive(Component, Debug, Reflect)]
+#[relationship_target(relationship = ListenerOf, linked_spawn)]
+pub struct Listeners(Vec<Entity>);
+
+/// Relationship source inserted on a link accepted by a listener entity with [`ListenerOf`].
+///
+/// Transports use it to find the socket or endpoint through which the link communicates, since
+/// [`LinkOf`] points to the server.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Reflect)]
+#[relationship(relationship_target = AcceptedLinks)]
+pub struct AcceptedBy {
+    /// Listener entity that accepted the link.
+    #[relationship]
+    pub listener: Entity,
+}
+
+/// Relationship target listing the links accepted by a listener entity.
+#[derive(Component, Debug, Reflect)]
+#[relationship_target(relationship = AcceptedBy)]
+pub struct AcceptedLinks(Vec<Entity>);
+
+impl Listeners {
+    /// Starting a server starts its listeners.
+    fn start(
+        trigger: On<LinkStart>,
+        query: Query<&Listeners, (Without<Linking>, Without<Linked>)>,
+        mut commands: Commands,
+    ) {
+        if let Ok(listeners) = query.get(trigger.entity) {
+            trace!("Starting the listeners of server {:?}", trigger.entity);
+            commands.entity(trigger.entity).insert(Linking);
+            for listener in listeners.iter() {
+                commands.trigger(LinkStart { entity: listener });
+            }
+        }
+    }
+
+    /// A listener added to a server that is already started is started as well.
+    fn start_added_listener(
+        trigger: On<Add, ListenerOf>,
+        listener_query: Query<&ListenerOf>,
+        server_query: Query<(), Or<(With<Linking>, With<Linked>)>>,
+        mut commands: Commands,
+    ) {
+        if let Ok(listener_of) = listener_query.get(trigger.entity)
+            && server_query.contains(listener_of.server)
+        {
+            commands.trigger(LinkStart {
+                entity: trigger.entity,
+            });
+        }
+    }
+
+    /// The server is linked once all its listeners are linked.
+    fn listener_linked(
+        trigger: On<Add, Linked>,
+        listener_query: Query<&ListenerOf>,
+        server_query: Query<&Listeners, Without<Linked>>,
+        linked: Query<(), With<Linked>>,
+        mut commands: Commands,
+    ) {
+        if let Ok(listener_of) = listener_query.get(trigger.entity)
+            && let Ok(listeners) = server_query.get(listener_of.server)
+            && listeners
+                .iter()
+                .all(|listener| listener == trigger.entity || linked.contains(listener))
+        {
+            trace!("All listeners are linked. Adding Linked on Server");
+            commands.entity(listener_of.server).insert(Linked);
+        }
+    }
+
+    /// When a listener is unlinked, its links are unlinked and despawned, and the server is
+    /// unlinked.
+    fn listener_unlinked(
+        trigger: On<Add, Unlinked>,
+        listener_query: Query<(&ListenerOf, &Unlinked, Option<&AcceptedLinks>)>,
+        server_query: Query<(), Or<(With<Linking>, With<Linked>)>>,
+        mut commands: Commands,
+    ) {
+        let Ok((listener_of, unlinked, links)) = listener_query.get(trigger.entity) else {
+            return;
+        };
+        if unlinked.reason == UnlinkReason::Initial {
+            return;
+        }
+        for link in links.into_iter().flat_map(|links| links.iter()) {
+            commands.trigger(Unlink {
+                entity: link,
+                reason: unlinked.reason.clone(),
+            });
+            if let Ok(mut c) = commands.get_entity(link) {
+                c.try_despawn();
+            }
+        }
+        if server_query.contains(listener_of.server) {
+            trace!("Unlinking Server because its listener was unlinked");
+            commands.trigger(Unlink {
+                entity: listener_of.server,
+                reason: unlinked.reason.clone(),
+            });
+        }
+    }
+
+    /// Unlinking a server unlinks its listeners.
+    fn unlink(
+        trigger: On<Unlink>,
+        query: Query<&Listeners>,
+        unlinked: Query<(), With<Unlinked>>,
+        mut commands: Commands,
+    ) {
+        if let Ok(listeners) = query.get(trigger.entity) {
+            for listener in listeners.iter() {
+                if !unlinked.contains(listener) {
+                    commands.trigger(Unlink {
+                        entity: listener,
+                        reason: trigger.reason.clone(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Copies a server's receive and send conditioners into each newly-created client link.
 ///
 /// A server entity is only the listening endpoint; packets are received by its [`LinkOf`] child
-/// entities. Keeping the conditioner in [`Link::recv`] lets all IO backends use their existing
-/// receive path unchanged.
+/// entities. Keeping the conditioners in [`Link::recv`] and [`Link::send`] lets all IO backends
+/// use their existing receive and send paths unchanged.
 fn add_server_link_conditioner(
     trigger: On<Add, LinkOf>,
     mut links: Query<(&LinkOf, &mut Link)>,
@@ -212,6 +410,9 @@ fn add_server_link_conditioner(
     let Ok(server) = servers.get(link_of.server) else {
         return;
     };
+    if link.send.conditioner.is_none() {
+        link.send.conditioner = server.send_conditioner.clone();
+    }
     let Some(conditioner) = &server.conditioner else {
         return;
     };
@@ -231,7 +432,8 @@ fn add_server_link_conditioner(
 /// Plugin that installs server/link relationship support.
 ///
 /// The plugin ensures [`LinkPlugin`] is present and adds the observer that reacts to [`Unlinked`]
-/// on server entities by unlinking/despawning their child links. Transport crates that expose a
+/// on server entities by unlinking/despawning their child links, as well as the observers that
+/// keep the state of a server in sync with its [`Listeners`]. Transport crates that expose a
 /// multi-client server endpoint should add this plugin before scheduling their server IO systems.
 pub struct ServerLinkPlugin;
 
@@ -242,6 +444,11 @@ impl Plugin for ServerLinkPlugin {
         }
         app.add_observer(Server::unlinked);
         app.add_observer(add_server_link_conditioner);
+        app.add_observer(Listeners::start);
+        app.add_observer(Listeners::start_added_listener);
+        app.add_observer(Listeners::listener_linked);
+        app.add_observer(Listeners::listener_unlinked);
+        app.add_observer(Listeners::unlink);
     }
 }
 
@@ -310,4 +517,108 @@ mod tests {
                 .is_some()
         );
     }
+
+    #[derive(Resource, Default)]
+    struct StartedLinks(Vec<Entity>);
+
+    fn record_link_start(trigger: On<LinkStart>, mut started: ResMut<StartedLinks>) {
+        started.0.push(trigger.entity);
+    }
+
+    #[test]
+    fn link_accepted_by_listener_belongs_to_server() {
+        let mut app = App::new();
+        app.add_plugins(ServerLinkPlugin);
+        let server = app.world_mut().spawn(Server::default()).id();
+        let listener = app
+            .world_mut()
+            .spawn((Server::default(), ListenerOf { server }))
+            .id();
+
+        let link = app
+            .world_mut()
+            .spawn((LinkOf { server: listener }, Link::default()))
+            .id();
+        app.update();
+
+        assert_eq!(app.world().get::<LinkOf>(link).unwrap().server, server);
+        assert_eq!(
+            app.world().get::<AcceptedBy>(link).unwrap().listener,
+            listener
+        );
+        assert_eq!(
+            app.world().get::<Server>(server).unwrap().collection(),
+            &[link]
+        );
+        assert!(
+            app.world()
+                .get::<Server>(listener)
+                .unwrap()
+                .collection()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn server_is_linked_once_all_listeners_are_linked() {
+        let mut app = App::new();
+        app.add_plugins(ServerLinkPlugin);
+        app.init_resource::<StartedLinks>();
+        app.add_observer(record_link_start);
+        let server = app.world_mut().spawn(Server::default()).id();
+        let udp = app
+            .world_mut()
+            .spawn((Server::default(), ListenerOf { server }))
+            .id();
+        let websocket = app
+            .world_mut()
+            .spawn((Server::default(), ListenerOf { server }))
+            .id();
+        app.update();
+
+        app.world_mut().trigger(LinkStart { entity: server });
+        app.update();
+        assert_eq!(
+            app.world().resource::<StartedLinks>().0,
+            vec![server, udp, websocket]
+        );
+        assert!(app.world().get::<Linking>(server).is_some());
+
+        app.world_mut().entity_mut(udp).insert(Linked);
+        app.update();
+        assert!(app.world().get::<Linked>(server).is_none());
+
+        app.world_mut().entity_mut(websocket).insert(Linked);
+        app.update();
+        assert!(app.world().get::<Linked>(server).is_some());
+    }
+
+    #[test]
+    fn listener_failure_unlinks_server_and_other_listeners() {
+        let mut app = App::new();
+        app.add_plugins(ServerLinkPlugin);
+        let server = app.world_mut().spawn((Server::default(), Linked)).id();
+        let udp = app
+            .world_mut()
+            .spawn((Server::default(), ListenerOf { server }, Linked))
+            .id();
+        let websocket = app
+            .world_mut()
+            .spawn((Server::default(), ListenerOf { server }, Linked))
+            .id();
+        let link = app
+            .world_mut()
+            .spawn((LinkOf { server: udp }, Link::default(), Linked))
+            .id();
+        app.update();
+
+        app.world_mut().entity_mut(udp).insert(Unlinked {
+            reason: UnlinkReason::TransportError("socket closed".into()),
+        });
+        app.update();
+
+        assert!(app.world().get::<Unlinked>(server).is_some());
+        assert!(app.world().get::<Unlinked>(websocket).is_some());
+        assert!(app.world().get_entity(link).is_err());
+    }
 }
@@ -23,7 +23,8 @@ use bytes::BufMut;
 use lightyear_core::buffer_pool::BufferPool;
 use lightyear_core::time::Instant;
 use lightyear_link::{
-    Link, LinkPlugin, LinkReceiveSystems, LinkStart, LinkSystems, Linked, Linking, Unlink, Unlinked,
+    Link, LinkPlugin, LinkReceiveSystems, LinkSendSystems, LinkStart, Linked, Linking, Unlink,
+    Unlinked,
 };
 use lightyear_utils::adaptive_for_each_mut;
 use tracing::{error, info, trace};
@@ -52,6 +53,10 @@ pub mod prelude {
 ///
 /// The value is chosen to avoid common IPv4 fragmentation limits. See
 /// <https://gafferongames.com/post/packet_fragmentation_and_reassembly/>.
+///
+/// This only bounds the receive buffers: packets are built with the [`Link`]'s current MTU, which
+/// path MTU discovery can raise up to this value. Receive buffers must not be smaller than the
+/// largest probe, which is why it matches the transport's default maximum probed MTU.
 pub(crate) const MTU: usize = 1472;
 
 const MAX_RETAINED_RECV_BUFFERS: usize = 64;
@@ -112,7 +117,7 @@ pub enum UdpError {
 /// - an [`Unlink`] observer that closes the socket;
 /// - a receive system in [`LinkReceiveSystems::BufferToLink`] that pushes datagrams into
 ///   [`Link::recv`];
-/// - a send system in [`LinkSystems::Send`] that drains [`Link::send`] to [`PeerAddr`].
+/// - a send system in [`LinkSendSystems::LinkToBuffer`] that drains [`Link::send`] to [`PeerAddr`].
 ///
 /// This is a raw datagram transport. Use Lightyear connection plugins above it when you need
 /// connection state, authentication, or session management.
@@ -224,7 +229,7 @@ impl Plugin for UdpPlugin {
             PreUpdate,
             Self::receive.in_set(LinkReceiveSystems::BufferToLink),
         );
-        app.add_systems(PostUpdate, Self::send.in_set(LinkSystems::Send));
+        app.add_systems(PostUpdate, Self::send.in_set(LinkSendSystems::LinkToBuffer));
     }
 }
 
@@ -7,24 +7,30 @@
 //! [`LinkOf`](lightyear_link::prelude::LinkOf). This keeps server fan-out compatible with the
 //! generic [`Server`](lightyear_link::server::Server) relationship model while preserving UDP's
 //! connectionless socket model.
+//!
+//! If the server entity has an [`AdmissionControl`], datagrams from a new address only spawn a
+//! link if the address is not banned, is within the handshake rate limit, and the server is not
+//! already handling too many pending handshakes. Otherwise they are dropped without an answer.
 
 extern crate alloc;
 
 use bevy_app::{App, Plugin, PostUpdate, PreUpdate};
 use bevy_ecs::prelude::*;
-use bevy_ecs::relationship::RelationshipTarget;
 use bevy_ecs::system::ParallelCommands;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, trace};
 
 use crate::UdpError;
 use aeronet_io::connection::{LocalAddr, PeerAddr};
 use bevy_platform::collections::{HashMap, hash_map::Entry};
 use bytes::BufMut;
 use core::net::SocketAddr;
+use lightyear_connection::admission::{AdmissionControl, unix_now};
 use lightyear_core::buffer_pool::BufferPool;
 use lightyear_core::time::Instant;
-use lightyear_link::prelude::{LinkOf, Server};
-use lightyear_link::{Link, LinkPlugin, LinkStart, LinkSystems, Linked, Linking, Unlink, Unlinked};
+use lightyear_link::prelude::{AcceptedBy, LinkOf, ListenerOf, Server};
+use lightyear_link::{
+    Link, LinkPlugin, LinkSendSystems, LinkStart, LinkSystems, Linked, Linking, Unlink, Unlinked,
+};
 
 /// Maximum UDP payload size used by this transport.
 ///
@@ -41,8 +47,12 @@ pub(crate) const MTU: usize = 1472;
 ///
 /// Each child link receives [`PeerAddr`] for its remote socket address and [`UdpLinkOfIO`] to mark
 /// it as owned by this UDP server transport.
+///
+/// To accept UDP clients next to other transports, insert it on a listener entity with
+/// [`ListenerOf`] instead. The links then belong to the server of the listener, and use the
+/// [`AdmissionControl`] of that server.
 #[derive(Component)]
-#[require(Server)]
+#[component(on_add = Server::on_add_transport)]
 pub struct ServerUdpIo {
     socket: Option<std::net::UdpSocket>,
     recv_buffers: BufferPool,
@@ -68,6 +78,14 @@ enum LinkOfStatus {
     Spawned(Entity),
 }
 
+impl LinkOfStatus {
+    fn entity(&self) -> Entity {
+        match self {
+            LinkOfStatus::Spawning(entity) | LinkOfStatus::Spawned(entity) => *entity,
+        }
+    }
+}
+
 impl Default for ServerUdpIo {
     fn default() -> Self {
         ServerUdpIo {
@@ -125,40 +143,99 @@ impl ServerUdpPlugin {
         }
     }
 
+    /// Route the datagrams of an address to the link that now has this [`PeerAddr`].
+    ///
+    /// This happens when an existing link adopts the address of another link, for example when
+    /// a client resumes its session from a new address.
+    fn update_peer_addr(
+        trigger: On<Insert, PeerAddr>,
+        link_query: Query<(&PeerAddr, &LinkOf, Option<&AcceptedBy>), With<UdpLinkOfIO>>,
+        mut server_query: Query<&mut ServerUdpIo>,
+    ) {
+        let entity = trigger.entity;
+        let Ok((peer_addr, link_of, accepted_by)) = link_query.get(entity) else {
+            return;
+        };
+        let socket_entity = accepted_by.map_or(link_of.server, |a| a.listener);
+        let Ok(mut server_udp_io) = server_query.get_mut(socket_entity) else {
+            return;
+        };
+        let address = peer_addr.0;
+        if server_udp_io
+            .connected_addresses
+            .get(&address)
+            .is_some_and(|status| status.entity() == entity)
+        {
+            return;
+        }
+        debug!(
+            ?entity,
+            "UDP link now receives the datagrams from {address}"
+        );
+        server_udp_io
+            .connected_addresses
+            .retain(|_, status| status.entity() != entity);
+        server_udp_io
+            .connected_addresses
+            .insert(address, LinkOfStatus::Spawned(entity));
+    }
+
     fn send(
-        mut server_query: Query<(&mut ServerUdpIo, &Server), With<Linked>>,
-        mut link_query: Query<(&mut Link, &PeerAddr), With<UdpLinkOfIO>>,
+        mut server_query: Query<&mut ServerUdpIo, With<Linked>>,
+        mut link_query: Query<
+            (Entity, &mut Link, &PeerAddr, &LinkOf, Option<&AcceptedBy>),
+            With<UdpLinkOfIO>,
+        >,
     ) {
         // TODO: parallelize
-        server_query
-            .iter_mut()
-            .for_each(|(mut server_udp_io, server)| {
-                server.collection().iter().for_each(|client_entity| {
-                    let Some((mut link, remote_addr)) = link_query.get_mut(*client_entity).ok()
-                    else {
-                        // Not all server links are Udp Links, so we might not want this to ever print
-                        debug!("Client entity {} not found in link query", client_entity);
-                        return;
-                    };
-
-                    link.send.drain().for_each(|send_payload| {
-                        server_udp_io
-                            .socket
-                            .as_mut()
-                            .unwrap()
-                            .send_to(send_payload.as_ref(), remote_addr.0)
-                            .inspect_err(|e| {
-                                error!("Error sending UDP packet to {}: {}", remote_addr.0, e);
-                            })
-                            .ok();
-                    });
+        link_query.iter_mut().for_each(
+            |(client_entity, mut link, remote_addr, link_of, accepted_by)| {
+                // the link is sent through the socket of the listener that accepted it, if any
+                let socket_entity = accepted_by.map_or(link_of.server, |a| a.listener);
+                let Ok(mut server_udp_io) = server_query.get_mut(socket_entity) else {
+                    debug!(
+                        "UDP server of client entity {} is not linked",
+                        client_entity
+                    );
+                    return;
+                };
+                link.send.drain().for_each(|send_payload| {
+                    server_udp_io
+                        .socket
+                        .as_mut()
+                        .unwrap()
+                        .send_to(send_payload.as_ref(), remote_addr.0)
+                        .inspect_err(|e| {
+                            error!("Error sending UDP packet to {}: {}", remote_addr.0, e);
+                        })
+                        .ok();
                 });
-            });
+            },
+        );
+    }
+
+    /// Returns true if a datagram from the new address `address` can spawn a new link.
+    fn admit(
+        admission: &mut AdmissionControl,
+        address: SocketAddr,
+        new_links: usize,
+        now: u64,
+    ) -> bool {
+        if admission.ban_list.is_banned(None, Some(address.ip()), now) {
+            trace!("dropping UDP packet from banned address {address}");
+            return false;
+        }
+        if !admission.can_start_handshakes(admission.pending_handshakes(), new_links + 1) {
+            trace!("dropping UDP packet from {address}: too many pending handshakes");
+            return false;
+        }
+        admission.allow_handshake_packet(address.ip())
     }
 
     fn receive(
         commands: ParallelCommands,
-        mut server_query: Query<(Entity, &mut ServerUdpIo), With<Linked>>,
+        mut server_query: Query<(Entity, &mut ServerUdpIo, Option<&ListenerOf>), With<Linked>>,
+        mut admission_query: Query<&mut AdmissionControl>,
         // TODO: we want to have With<Linked> here, but that would mean that if a client sends 2 packets in a row
         //  for the first one we spawn them, and for the second one the query will return False.
         //  maybe have a separate Vec for new addresses, and for these we don't require Linked?
@@ -167,13 +244,20 @@ impl ServerUdpPlugin {
         server_query
             // TODO: would par_iter_mut be better here?
             .iter_mut()
-            .for_each(|(server_entity, mut server_udp_io)| {
+            .for_each(|(server_entity, mut server_udp_io, listener_of)| {
+                // the admission control of a listener is the one of its server
+                let mut admission = admission_query
+                    .get_mut(listener_of.map_or(server_entity, |listener_of| listener_of.server))
+                    .ok();
                 // SAFETY: we know that each ServerUdpIo will target different Link entities, so there won't be any aliasing
                 let mut link_query = unsafe { link_query.reborrow_unsafe() };
 
                 // enable split borrows
                 let server_udp_io = &mut *server_udp_io;
                 server_udp_io.recv_buffers.reclaim_pending();
+                // links spawned this frame, which are not counted as pending handshakes yet
+                let mut new_links = 0;
+                let now = if admission.is_some() { unix_now() } else { 0 };
 
                 loop {
                     let mut buffer = server_udp_io.recv_buffers.take();
@@ -241,6 +325,12 @@ impl ServerUdpPlugin {
                                     }
                                 }
                                 Entry::Vacant(vacant) => {
+                                    if let Some(admission) = admission.as_deref_mut()
+                                        && !Self::admit(admission, address, new_links, now)
+                                    {
+                                        continue;
+                                    }
+                                    new_links += 1;
                                     // we are spawning a new entity but the initial packets will be dropped
                                     let mut link = Link::default();
                                     link.recv.push(payload, Instant::now());
@@ -300,8 +390,9 @@ impl Plugin for ServerUdpPlugin {
         }
         app.add_observer(Self::link);
         app.add_observer(Self::unlink);
+        app.add_observer(Self::update_peer_addr);
         app.add_systems(PreUpdate, Self::receive.in_set(LinkSystems::Receive));
-        app.add_systems(PostUpdate, Self::send.in_set(LinkSystems::Send));
+        app.add_systems(PostUpdate, Self::send.in_set(LinkSendSystems::LinkToBuffer));
     }
 }
 
@@ -329,4 +420,69 @@ mod tests {
         assert_ne!(bound_addr.port(), 0);
         assert!(app.world().get::<Linked>(server).is_some());
     }
+
+    #[test]
+    fn link_adopting_peer_addr_receives_its_datagrams() {
+        let mut app = App::new();
+        app.add_plugins(ServerUdpPlugin);
+        let server = app.world_mut().spawn(ServerUdpIo::default()).id();
+        let old_addr = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 1000);
+        let new_addr = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 2000);
+        let session = app
+            .world_mut()
+            .spawn((LinkOf { server }, PeerAddr(old_addr), UdpLinkOfIO))
+            .id();
+        let new_link = app
+            .world_mut()
+            .spawn((LinkOf { server }, PeerAddr(new_addr), UdpLinkOfIO))
+            .id();
+
+        let entity_for = |app: &App, addr| {
+            app.world()
+                .get::<ServerUdpIo>(server)
+                .unwrap()
+                .connected_addresses
+                .get(&addr)
+                .map(LinkOfStatus::entity)
+        };
+        assert_eq!(entity_for(&app, old_addr), Some(session));
+        assert_eq!(entity_for(&app, new_addr), Some(new_link));
+
+        app.world_mut().despawn(new_link);
+        app.world_mut()
+            .entity_mut(session)
+            .insert(PeerAddr(new_addr));
+
+        assert_eq!(entity_for(&app, new_addr), Some(session));
+        assert_eq!(entity_for(&app, old_addr), None);
+    }
+
+    #[test]
+    fn listener_routes_the_datagrams_of_its_links() {
+        let mut app = App::new();
+        app.add_plugins(ServerUdpPlugin);
+        let server = app.world_mut().spawn(Server::default()).id();
+        let listener = app
+            .world_mut()
+            .spawn((ListenerOf { server }, ServerUdpIo::default()))
+            .id();
+        let addr = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 1000);
+        let link = app
+            .world_mut()
+            .spawn((LinkOf { server: listener }, UdpLinkOfIO))
+            .id();
+        app.world_mut().entity_mut(link).insert(PeerAddr(addr));
+
+        assert_eq!(app.world().get::<LinkOf>(link).unwrap().server, server);
+        assert!(app.world().get::<Server>(listener).is_none());
+        assert_eq!(
+            app.world()
+                .get::<ServerUdpIo>(listener)
+                .unwrap()
+                .connected_addresses
+                .get(&addr)
+                .map(LinkOfStatus::entity),
+            Some(link)
+        );
+    }
 }
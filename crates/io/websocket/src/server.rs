@@ -45,13 +45,15 @@ impl Plugin for WebSocketServerPlugin {
 
 /// Lightyear component for a WebSocket server endpoint.
 ///
-/// Insert this on a Lightyear server entity. A [`LocalAddr`] must be present when [`LinkStart`] is
-/// triggered; the plugin opens an Aeronet [`WebSocketServer`] using [`config`](Self::config).
+/// Insert this on a Lightyear server entity, or on a listener entity with
+/// [`ListenerOf`](lightyear_link::prelude::ListenerOf) to accept clients next to other transports.
+/// A [`LocalAddr`] must be present when [`LinkStart`] is triggered; the plugin opens an Aeronet
+/// [`WebSocketServer`] using [`config`](Self::config).
 ///
 /// Accepted clients are represented as child Lightyear link entities related to the server through
 /// [`LinkOf`].
 #[derive(Component)]
-#[require(Server)]
+#[component(on_add = Server::on_add_transport)]
 pub struct WebSocketServerIo {
     /// Aeronet WebSocket server configuration used when opening the server.
     pub config: ServerConfig,
@@ -49,14 +49,15 @@ impl Plugin for WebTransportServerPlugin {
 
 /// Lightyear component for a WebTransport server endpoint.
 ///
-/// Insert this on a Lightyear server entity. A [`LocalAddr`] must be present when [`LinkStart`] is
-/// triggered; the plugin opens an Aeronet [`WebTransportServer`] with
-/// [`certificate`](Self::certificate) as its TLS identity.
+/// Insert this on a Lightyear server entity, or on a listener entity with
+/// [`ListenerOf`](lightyear_link::prelude::ListenerOf) to accept clients next to other transports.
+/// A [`LocalAddr`] must be present when [`LinkStart`] is triggered; the plugin opens an Aeronet
+/// [`WebTransportServer`] with [`certificate`](Self::certificate) as its TLS identity.
 ///
 /// This wrapper currently accepts [`SessionRequest`] events automatically. Accepted clients are
 /// represented as child Lightyear link entities related to the server through [`LinkOf`].
 #[derive(Debug, Component)]
-#[require(Server)]
+#[component(on_add = Server::on_add_transport)]
 pub struct WebTransportServerIo {
     /// TLS identity used by the underlying WebTransport server.
     pub certificate: Identity,
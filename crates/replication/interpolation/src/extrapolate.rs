@@ -0,0 +1,440 @@
+//! Bounded extrapolation past the newest confirmed value.
+//!
+//! Delayed interpolation needs a confirmed value on both sides of the interpolation tick. When
+//! updates stop arriving (for example during a packet-loss burst), the interpolation tick moves
+//! past the newest confirmed value and the component freezes on it.
+//!
+//! A rule with [`InterpolationFns::extrapolate`] (or one of its variants) instead projects the
+//! component forward from the newest confirmed value, for at most
+//! [`ExtrapolationSettings::max_duration`]. The projection uses either the last two confirmed
+//! values, or a velocity component through [`VelocityExtrapolate`]. When the next update arrives,
+//! the live component is blended from the last projected value back to the interpolated value over
+//! [`ExtrapolationSettings::blend_duration`], instead of snapping.
+//!
+//! ```rust,ignore
+//! use lightyear_interpolation::prelude::*;
+//!
+//! app.interpolate_with::<Position>(
+//!     InterpolationFns::interpolate(lerp_position)
+//!         .linear_extrapolate(ExtrapolationSettings::default()),
+//! );
+//!
+//! // or, if the entity replicates its velocity
+//! impl VelocityExtrapolate<LinearVelocity> for Position {
+//!     fn extrapolate(&self, velocity: &LinearVelocity, elapsed_secs: f32) -> Self {
+//!         Position(self.0 + velocity.0 * elapsed_secs)
+//!     }
+//! }
+//! app.interpolate_with::<Position>(
+//!     InterpolationFns::interpolate(lerp_position)
+//!         .extrapolate_with_velocity::<LinearVelocity>(ExtrapolationSettings::default()),
+//! );
+//! ```
+//!
+//! Extrapolation only applies to single-component rules that apply the live component.
+//!
+//! [`InterpolationFns::extrapolate`]: crate::rules::InterpolationFns::extrapolate
+use crate::rules::{InterpolationFn, InterpolationSampleContext};
+use bevy_ecs::component::{Component, ComponentId};
+use bevy_ecs::world::World;
+use bevy_ecs::world::unsafe_world_cell::UnsafeEntityCell;
+use bevy_math::{Curve, curve::Ease};
+use core::fmt;
+use core::time::Duration;
+use lightyear_core::history_buffer::HistoryState;
+use lightyear_core::prelude::{ConfirmedHistory, Tick};
+
+/// Bounds of the extrapolation of a component.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExtrapolationSettings {
+    /// Maximum duration the component is projected past its newest confirmed value. After that,
+    /// the component holds the last projected value until a new update arrives.
+    pub max_duration: Duration,
+    /// Duration over which the component is blended back to the interpolated value once
+    /// updates are received again.
+    pub blend_duration: Duration,
+}
+
+impl Default for ExtrapolationSettings {
+    fn default() -> Self {
+        Self {
+            max_duration: Duration::from_millis(250),
+            blend_duration: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Projects a component forward from its last two confirmed values.
+///
+/// `t` is expressed in units of the interval between `previous` and `latest`: `t = 1.0` returns
+/// `latest` and `t = 2.0` projects one more interval past it.
+pub type ExtrapolateFn<C> = fn(previous: C, latest: C, t: f32) -> C;
+
+/// Projects a component forward using a velocity component `V` present on the same entity.
+pub trait VelocityExtrapolate<V>: Sized {
+    /// Returns the value of the component `elapsed_secs` seconds after `self`.
+    fn extrapolate(&self, velocity: &V, elapsed_secs: f32) -> Self;
+}
+
+/// Projects the component with the velocity component read from the entity.
+///
+/// # Safety
+///
+/// The caller must have read access to the velocity component of `entity`.
+type ProjectWithVelocityFn<C> =
+    unsafe fn(entity: UnsafeEntityCell, latest: &C, elapsed_secs: f32) -> Option<C>;
+
+#[derive(Clone, Copy)]
+pub(crate) enum ExtrapolationMode<C> {
+    Samples(ExtrapolateFn<C>),
+    Velocity {
+        project: ProjectWithVelocityFn<C>,
+        register_velocity: fn(&mut World) -> ComponentId,
+    },
+}
+
+/// Extrapolation configured on an [`InterpolationFns`](crate::rules::InterpolationFns) rule.
+#[derive(Clone, Copy)]
+pub struct Extrapolation<C> {
+    pub(crate) settings: ExtrapolationSettings,
+    pub(crate) mode: ExtrapolationMode<C>,
+}
+
+impl<C> fmt::Debug for Extrapolation<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self.mode {
+            ExtrapolationMode::Samples(_) => "Samples(..)",
+            ExtrapolationMode::Velocity { .. } => "Velocity(..)",
+        };
+        f.debug_struct("Extrapolation")
+            .field("settings", &self.settings)
+            .field("mode", &mode)
+            .finish()
+    }
+}
+
+impl<C> Extrapolation<C> {
+    pub(crate) fn from_samples(
+        settings: ExtrapolationSettings,
+        extrapolate: ExtrapolateFn<C>,
+    ) -> Self {
+        Self {
+            settings,
+            mode: ExtrapolationMode::Samples(extrapolate),
+        }
+    }
+
+    pub(crate) fn from_velocity<V: Component>(settings: ExtrapolationSettings) -> Self
+    where
+        C: VelocityExtrapolate<V>,
+    {
+        Self {
+            settings,
+            mode: ExtrapolationMode::Velocity {
+                project: project_with_velocity::<C, V>,
+                register_velocity: |world| world.register_component::<V>(),
+            },
+        }
+    }
+
+    pub fn settings(&self) -> ExtrapolationSettings {
+        self.settings
+    }
+
+    /// Velocity component read by this extrapolation, registering it if needed.
+    pub(crate) fn register_velocity(&self, world: &mut World) -> Option<ComponentId> {
+        match self.mode {
+            ExtrapolationMode::Samples(_) => None,
+            ExtrapolationMode::Velocity {
+                register_velocity, ..
+            } => Some(register_velocity(world)),
+        }
+    }
+}
+
+/// # Safety
+///
+/// The caller must have read access to `V` on `entity`.
+unsafe fn project_with_velocity<C: VelocityExtrapolate<V>, V: Component>(
+    entity: UnsafeEntityCell,
+    latest: &C,
+    elapsed_secs: f32,
+) -> Option<C> {
+    // SAFETY: upheld by the caller.
+    let velocity = unsafe { entity.get::<V>() }?;
+    Some(latest.extrapolate(velocity, elapsed_secs))
+}
+
+pub(crate) fn linear_extrapolate<C: Ease>(previous: C, latest: C, t: f32) -> C {
+    C::interpolating_curve_unbounded(previous, latest).sample_unchecked(t)
+}
+
+/// Per-entity extrapolation state, used to blend back to the interpolated value.
+///
+/// Inserted automatically on interpolated entities whose rule has an [`Extrapolation`].
+#[derive(Component, Debug)]
+pub struct ExtrapolationState<C: Send + Sync + 'static> {
+    extrapolating: bool,
+    blend: Option<ExtrapolationBlend<C>>,
+}
+
+impl<C: Send + Sync + 'static> Default for ExtrapolationState<C> {
+    fn default() -> Self {
+        Self {
+            extrapolating: false,
+            blend: None,
+        }
+    }
+}
+
+impl<C: Send + Sync + 'static> ExtrapolationState<C> {
+    /// Returns true if the component is currently projected past its newest confirmed value.
+    pub fn is_extrapolating(&self) -> bool {
+        self.extrapolating
+    }
+}
+
+#[derive(Debug)]
+struct ExtrapolationBlend<C> {
+    /// Last projected value.
+    from: C,
+    start_tick: Tick,
+    start_overstep: f32,
+}
+
+/// Interpolation time at which a component is sampled.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SampleTime {
+    pub(crate) tick: Tick,
+    pub(crate) overstep: f32,
+    pub(crate) tick_duration: Duration,
+}
+
+impl SampleTime {
+    /// Ticks elapsed since `tick`, including the overstep.
+    fn ticks_since(&self, tick: Tick, overstep: f32) -> f32 {
+        (self.tick - tick) as f32 + self.overstep - overstep
+    }
+}
+
+/// Projects the newest confirmed value of `history` to the interpolation time.
+///
+/// Returns `None` if the interpolation time is not past the newest confirmed value, or if there
+/// is not enough information to extrapolate.
+///
+/// # Safety
+///
+/// The caller must have read access to the velocity component of `entity`, if any.
+pub(crate) unsafe fn extrapolate_history<C: Component + Clone>(
+    extrapolation: &Extrapolation<C>,
+    entity: UnsafeEntityCell,
+    history: &ConfirmedHistory<C>,
+    time: SampleTime,
+) -> Option<C> {
+    let last_index = history.len().checked_sub(1)?;
+    let (latest_tick, HistoryState::Updated(latest)) = history.get_nth_state(last_index)? else {
+        return None;
+    };
+    let elapsed_ticks = time.ticks_since(latest_tick, 0.0);
+    if elapsed_ticks <= 0.0 {
+        return None;
+    }
+    let tick_secs = time.tick_duration.as_secs_f32();
+    if tick_secs <= 0.0 {
+        return None;
+    }
+    let max_ticks = extrapolation.settings.max_duration.as_secs_f32() / tick_secs;
+    let elapsed_ticks = elapsed_ticks.min(max_ticks);
+    match extrapolation.mode {
+        ExtrapolationMode::Samples(extrapolate) => {
+            let (previous_tick, HistoryState::Updated(previous)) =
+                history.get_nth_state(last_index.checked_sub(1)?)?
+            else {
+                return None;
+            };
+            let interval = (latest_tick - previous_tick) as f32;
+            if interval <= 0.0 {
+                return None;
+            }
+            Some(extrapolate(
+                previous.clone(),
+                latest.clone(),
+                1.0 + elapsed_ticks / interval,
+            ))
+        }
+        // SAFETY: upheld by the caller.
+        ExtrapolationMode::Velocity { project, .. } => unsafe {
+            project(entity, latest, elapsed_ticks * tick_secs)
+        },
+    }
+}
+
+impl<C: Clone + Send + Sync + 'static> ExtrapolationState<C> {
+    /// Returns the value to write on the live component for this frame.
+    ///
+    /// `extrapolated` is the projected value if the interpolation time is past the newest
+    /// confirmed value, `interpolated` the value sampled from the confirmed history, and `current`
+    /// the value currently on the live component.
+    pub(crate) fn resolve(
+        &mut self,
+        settings: &ExtrapolationSettings,
+        interpolation: Option<&InterpolationFn<C>>,
+        extrapolated: Option<C>,
+        interpolated: C,
+        current: Option<&C>,
+        time: SampleTime,
+    ) -> C {
+        if let Some(extrapolated) = extrapolated {
+            self.extrapolating = true;
+            self.blend = None;
+            return extrapolated;
+        }
+        if core::mem::take(&mut self.extrapolating)
+            && let Some(current) = current
+        {
+            self.blend = Some(ExtrapolationBlend {
+                from: current.clone(),
+                start_tick: time.tick,
+                start_overstep: time.overstep,
+            });
+        }
+        let (Some(blend), Some(interpolation)) = (self.blend.as_ref(), interpolation) else {
+            self.blend = None;
+            return interpolated;
+        };
+        let elapsed_secs = time.ticks_since(blend.start_tick, blend.start_overstep)
+            * time.tick_duration.as_secs_f32();
+        let blend_secs = settings.blend_duration.as_secs_f32();
+        if elapsed_secs >= blend_secs {
+            self.blend = None;
+            return interpolated;
+        }
+        interpolation.interpolate(
+            blend.from.clone(),
+            interpolated,
+            InterpolationSampleContext::from_t((elapsed_secs / blend_secs).max(0.0)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Position(f32);
+
+    #[derive(Component)]
+    struct Velocity(f32);
+
+    impl VelocityExtrapolate<Velocity> for Position {
+        fn extrapolate(&self, velocity: &Velocity, elapsed_secs: f32) -> Self {
+            Position(self.0 + velocity.0 * elapsed_secs)
+        }
+    }
+
+    fn extrapolate_position(previous: Position, latest: Position, t: f32) -> Position {
+        Position(previous.0 + (latest.0 - previous.0) * t)
+    }
+
+    fn lerp_position(start: Position, end: Position, t: f32) -> Position {
+        Position(start.0 + (end.0 - start.0) * t)
+    }
+
+    const TICK: Duration = Duration::from_millis(10);
+
+    fn settings() -> ExtrapolationSettings {
+        ExtrapolationSettings {
+            max_duration: Duration::from_millis(50),
+            blend_duration: Duration::from_millis(40),
+        }
+    }
+
+    fn time(tick: u32) -> SampleTime {
+        SampleTime {
+            tick: Tick(tick),
+            overstep: 0.0,
+            tick_duration: TICK,
+        }
+    }
+
+    fn history() -> ConfirmedHistory<Position> {
+        let mut history = ConfirmedHistory::default();
+        history.insert_present(Tick(0), Position(0.0));
+        history.insert_present(Tick(2), Position(2.0));
+        history
+    }
+
+    fn extrapolate(
+        world: &mut World,
+        extrapolation: &Extrapolation<Position>,
+        entity: bevy_ecs::entity::Entity,
+        tick: u32,
+    ) -> Option<Position> {
+        let cell = world.as_unsafe_world_cell().get_entity(entity).unwrap();
+        // SAFETY: the world is not otherwise borrowed.
+        unsafe { extrapolate_history(extrapolation, cell, &history(), time(tick)) }
+    }
+
+    #[test]
+    fn extrapolates_from_last_two_samples_for_a_bounded_time() {
+        let mut world = World::new();
+        let entity = world.spawn_empty().id();
+        let extrapolation = Extrapolation::from_samples(settings(), extrapolate_position);
+
+        assert_eq!(extrapolate(&mut world, &extrapolation, entity, 2), None);
+        assert_eq!(
+            extrapolate(&mut world, &extrapolation, entity, 4),
+            Some(Position(4.0))
+        );
+        // extrapolation stops after 50ms, i.e. 5 ticks past the newest value
+        assert_eq!(
+            extrapolate(&mut world, &extrapolation, entity, 20),
+            Some(Position(7.0))
+        );
+    }
+
+    #[test]
+    fn extrapolates_with_velocity_component() {
+        let mut world = World::new();
+        let entity = world.spawn(Velocity(100.0)).id();
+        let extrapolation = Extrapolation::<Position>::from_velocity::<Velocity>(settings());
+
+        // 3 ticks of 10ms at 100 units/s
+        let projected = extrapolate(&mut world, &extrapolation, entity, 5).unwrap();
+        assert!((projected.0 - 5.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn blends_back_to_interpolated_value() {
+        let mut state = ExtrapolationState::<Position>::default();
+        let interpolation = InterpolationFn::Lerp(lerp_position);
+        let resolve = |state: &mut ExtrapolationState<Position>,
+                       extrapolated: Option<Position>,
+                       current: Position,
+                       tick: u32| {
+            state.resolve(
+                &settings(),
+                Some(&interpolation),
+                extrapolated,
+                Position(0.0),
+                Some(&current),
+                time(tick),
+            )
+        };
+
+        assert_eq!(
+            resolve(&mut state, Some(Position(8.0)), Position(7.0), 10),
+            Position(8.0)
+        );
+        assert!(state.is_extrapolating());
+
+        // a new update arrives: start from the last projected value
+        assert_eq!(resolve(&mut state, None, Position(8.0), 11), Position(8.0));
+        assert!(!state.is_extrapolating());
+        // halfway through the 40ms blend
+        assert_eq!(resolve(&mut state, None, Position(6.0), 13), Position(4.0));
+        assert_eq!(resolve(&mut state, None, Position(4.0), 15), Position(0.0));
+        assert!(state.blend.is_none());
+    }
+}
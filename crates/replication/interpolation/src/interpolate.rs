@@ -1,5 +1,6 @@
 use crate::SyncComponent;
 use crate::archetypes::InterpolationWorld;
+use crate::extrapolate::{ExtrapolationState, SampleTime, extrapolate_history};
 use crate::registry::{InterpolationRegistry, sample_history_with_interpolation};
 use crate::rules::{
     ApplyInterpolationContext, CachedInterpolationComponent, InterpolationRuleId,
@@ -144,10 +145,15 @@ pub(crate) fn update_history_archetype_erased<C: Component + Clone>(
     };
     let present = component.live_component_present();
     let interpolation = interpolation_registry.interpolation_for_rule::<C>(component.rule_id());
+    let needs_extrapolation_state =
+        needs_extrapolation_state::<C>(world, archetype, interpolation_registry, component);
     for entity in archetype.entities() {
         let entity_id = entity.id();
         let row = entity.table_row().index();
         let history = unsafe { &mut *histories.get_unchecked(row).get() };
+        if needs_extrapolation_state {
+            deferred_apply.insert(entity_id, ExtrapolationState::<C>::default());
+        }
         update_history_inner::<C>(history, entity_id, ctx);
         let sample = sample_history_with_interpolation(
             interpolation,
@@ -191,8 +197,13 @@ pub(crate) fn update_history_diff_archetype_erased<C>(
     };
     let present = component.live_component_present();
     let interpolation = interpolation_registry.interpolation_for_rule::<C>(component.rule_id());
+    let needs_extrapolation_state =
+        needs_extrapolation_state::<C>(world, archetype, interpolation_registry, component);
     for entity in archetype.entities() {
         let entity_id = entity.id();
+        if needs_extrapolation_state {
+            deferred_apply.insert(entity_id, ExtrapolationState::<C>::default());
+        }
         let Some(history_diff_receiver) = storage.get_mut::<HistoryDiffReceiver<C>>(entity_id)
         else {
             continue;
@@ -237,6 +248,27 @@ pub(crate) fn update_history_diff_archetype_erased<C>(
     }
 }
 
+/// Returns true if the entities of the archetype are missing the [`ExtrapolationState<C>`] used by
+/// the selected rule.
+fn needs_extrapolation_state<C: Component + Clone>(
+    world: UnsafeWorldCell,
+    archetype: &Archetype,
+    interpolation_registry: &InterpolationRegistry,
+    component: &CachedInterpolationComponent,
+) -> bool {
+    let rule_id = component.rule_id();
+    interpolation_registry
+        .rule(rule_id)
+        .is_some_and(|rule| rule.applies_component())
+        && interpolation_registry
+            .extrapolation_for_rule::<C>(rule_id)
+            .is_some()
+        && !world
+            .components()
+            .component_id::<ExtrapolationState<C>>()
+            .is_some_and(|id| archetype.contains(id))
+}
+
 fn update_history_inner<C: Component + Clone>(
     history: &mut ConfirmedHistory<C>,
     entity: Entity,
@@ -335,11 +367,22 @@ pub(crate) fn apply_interpolation_archetype_erased<C: SyncComponent>(
         return;
     };
     let interpolation = interpolation_registry.interpolation_for_rule::<C>(rule_id);
+    // extrapolation needs the tick duration to bound the projection
+    let extrapolation = interpolation_registry
+        .extrapolation_for_rule::<C>(rule_id)
+        .zip(ctx.tick_duration);
+    let extrapolation_states = extrapolation.and_then(|_| {
+        let state_component_id = world.components().component_id::<ExtrapolationState<C>>()?;
+        if !archetype.contains(state_component_id) {
+            return None;
+        }
+        table_component_slice::<ExtrapolationState<C>>(table, state_component_id)
+    });
 
     for entity in archetype.entities() {
         let row = entity.table_row().index();
         let history = unsafe { &*histories.get_unchecked(row).get() };
-        let Some(HistoryState::Updated(interpolated)) = sample_history_with_interpolation(
+        let Some(HistoryState::Updated(mut interpolated)) = sample_history_with_interpolation(
             interpolation,
             history,
             ctx.interpolation_tick,
@@ -349,6 +392,40 @@ pub(crate) fn apply_interpolation_archetype_erased<C: SyncComponent>(
             continue;
         };
 
+        if let Some((extrapolation, tick_duration)) = extrapolation {
+            let time = SampleTime {
+                tick: ctx.interpolation_tick,
+                overstep: ctx.interpolation_overstep,
+                tick_duration,
+            };
+            // SAFETY: the erased interpolation system declares access to the live component, the
+            // extrapolation state and the velocity component of rules with extrapolation.
+            let Ok(entity_cell) = world.get_entity(entity.id()) else {
+                continue;
+            };
+            let extrapolated =
+                unsafe { extrapolate_history(extrapolation, entity_cell, history, time) };
+            match extrapolation_states {
+                Some(states) => {
+                    let state = unsafe { &mut *states.get_unchecked(row).get() };
+                    let current = unsafe { entity_cell.get::<C>() };
+                    interpolated = state.resolve(
+                        &extrapolation.settings(),
+                        interpolation,
+                        extrapolated,
+                        interpolated,
+                        current,
+                        time,
+                    );
+                }
+                None => {
+                    if let Some(extrapolated) = extrapolated {
+                        interpolated = extrapolated;
+                    }
+                }
+            }
+        }
+
         trace!(
             target: "lightyear_debug::interpolation",
             kind = "interpolation_apply",
@@ -813,6 +890,55 @@ mod tests {
         assert_eq!(app.world().get::<TestComp>(entity), Some(&TestComp(1.0)));
     }
 
+    #[test]
+    fn extrapolation_projects_past_newest_value_and_blends_back() {
+        let mut app = setup_app(Tick(25), 40);
+        app.world_mut()
+            .insert_resource(TickDuration(core::time::Duration::from_millis(10)));
+        insert_rule::<TestComp, ()>(
+            &mut app,
+            InterpolationFns::interpolate(lerp).extrapolate(
+                crate::extrapolate::ExtrapolationSettings {
+                    max_duration: core::time::Duration::from_secs(1),
+                    blend_duration: core::time::Duration::from_millis(100),
+                },
+                lerp,
+            ),
+            InterpolationRuleConfig { priority: 100 },
+        );
+        add_interpolation_test_systems(&mut app);
+
+        let entity = app.world_mut().spawn(TestComp(0.0)).id();
+        insert_confirmed_history(&mut app, entity, two_point_history());
+
+        // 5 ticks past the newest value, projected along the last two values
+        app.update();
+        assert_eq!(app.world().get::<TestComp>(entity), Some(&TestComp(15.0)));
+        assert!(
+            app.world()
+                .get::<ExtrapolationState<TestComp>>(entity)
+                .is_some_and(ExtrapolationState::is_extrapolating)
+        );
+
+        // a newer update arrives: blend from the projected value instead of snapping back
+        app.world_mut()
+            .get_mut::<ConfirmedHistory<TestComp>>(entity)
+            .unwrap()
+            .insert_present(Tick(30), TestComp(20.0));
+        set_interpolation_tick(&mut app, Tick(26));
+        app.update();
+        assert_eq!(app.world().get::<TestComp>(entity), Some(&TestComp(15.0)));
+
+        // the blend is over after 100ms
+        set_interpolation_tick(&mut app, Tick(36));
+        app.world_mut()
+            .get_mut::<ConfirmedHistory<TestComp>>(entity)
+            .unwrap()
+            .insert_present(Tick(40), TestComp(30.0));
+        app.update();
+        assert_eq!(app.world().get::<TestComp>(entity), Some(&TestComp(26.0)));
+    }
+
     #[test]
     fn selected_history_only_rule_suppresses_default_apply() {
         let mut app = setup_app(Tick(15), 40);
@@ -100,6 +100,16 @@
 //! methods such as Hermite curves that need to scale velocities by the interval
 //! between samples.
 //!
+//! # Extrapolation
+//!
+//! By default, the live component holds the newest confirmed value once the
+//! interpolation timeline moves past it, for example during a packet-loss
+//! burst. Rules can opt into a bounded extrapolation with
+//! [`rules::InterpolationFns::extrapolate`],
+//! [`rules::InterpolationFns::linear_extrapolate`] or
+//! [`rules::InterpolationFns::extrapolate_with_velocity`]. See the
+//! [`extrapolate`] module.
+//!
 //! # Custom interpolation systems
 //!
 //! Use a history-only rule when Lightyear should still receive and maintain the
@@ -262,6 +272,7 @@ use bevy_ecs::component::{Component, Mutable};
 pub mod archetypes;
 /// Handles delayed despawns for interpolated entities.
 pub mod despawn;
+pub mod extrapolate;
 /// Contains interpolation logic.
 pub mod interpolate;
 /// Provides the `InterpolationPlugin` and related systems for Bevy integration.
@@ -274,6 +285,9 @@ pub mod timeline;
 /// Commonly used items for client-side interpolation.
 pub mod prelude {
     pub use crate::Interpolated;
+    pub use crate::extrapolate::{
+        ExtrapolateFn, ExtrapolationSettings, ExtrapolationState, VelocityExtrapolate,
+    };
     pub use crate::interpolate::interpolation_fraction;
     pub use crate::plugin::{
         InterpolationDelay, InterpolationMarkerPlugin, InterpolationPlugin, InterpolationSystems,
@@ -1,4 +1,5 @@
 use crate::SyncComponent;
+use crate::extrapolate::{Extrapolation, ExtrapolationState};
 use crate::interpolate::{
     apply_interpolation_archetype_erased, update_history_archetype_erased,
     update_history_diff_archetype_erased,
@@ -95,6 +96,13 @@ impl InterpolationRuleComponentIds {
         {
             write_component_ids.push(live_component_id);
         }
+        if applies_interpolation_component && let Some(extrapolation) = &fns.extrapolation {
+            write_component_ids.push(world.register_component::<ExtrapolationState<C>>());
+            // the velocity is only read, but the interpolation systems only declare write access
+            if let Some(velocity_component_id) = extrapolation.register_velocity(world) {
+                write_component_ids.push(velocity_component_id);
+            }
+        }
 
         let mut frame_write_component_ids = Vec::new();
         if let Some(frame_history_component_id) = frame_history_component_id {
@@ -652,6 +660,18 @@ impl InterpolationRegistry {
         )
     }
 
+    pub(crate) fn extrapolation_for_rule<S: 'static>(
+        &self,
+        rule_id: InterpolationRuleId,
+    ) -> Option<&Extrapolation<S>> {
+        let rule = &self.rules[rule_id.0];
+        debug_assert_eq!(rule.kind, RuleKind::of::<S>());
+        rule.fns
+            .extrapolation
+            .as_ref()
+            .map(|extrapolation| extrapolation.extrapolation::<S>())
+    }
+
     pub(crate) fn interpolation_for_rule<S: 'static>(
         &self,
         rule_id: InterpolationRuleId,
@@ -734,7 +754,8 @@ pub(crate) fn sample_history_with_interpolation<C: Component + Clone>(
 
     // Clamp rather than extrapolate beyond the newest confirmed value. This
     // makes late packets converge to the freshest server state instead of
-    // overshooting when motion changes direction.
+    // overshooting when motion changes direction. Rules can opt into bounded
+    // extrapolation, which is applied on top of this sample.
     let context = InterpolationSampleContext::from_ticks(
         start_tick,
         end_tick,
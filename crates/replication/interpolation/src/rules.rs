@@ -12,11 +12,14 @@ pub use bundle::InterpolationBundle;
 pub(crate) use bundle::TupleInterpolationBundle;
 
 use self::frame_interpolate::{FrameHistoryComponent, FrameInterpolationFns};
+use crate::extrapolate::{
+    ExtrapolateFn, Extrapolation, ExtrapolationSettings, VelocityExtrapolate, linear_extrapolate,
+};
 use crate::registry::InterpolationRegistry;
 use alloc::{boxed::Box, vec::Vec};
 use bevy_ecs::archetype::Archetype;
 use bevy_ecs::component::{ComponentId, Components, StorageType};
-use bevy_ecs::prelude::{Commands, Entity};
+use bevy_ecs::prelude::{Commands, Component, Entity};
 use bevy_ecs::query::QueryFilter;
 use bevy_ecs::world::unsafe_world_cell::UnsafeWorldCell;
 use bevy_math::{
@@ -250,6 +253,7 @@ pub struct InterpolationRuleConfig {
 #[derive(Debug, Clone, Copy)]
 pub struct InterpolationFns<C> {
     pub(crate) interpolation: Option<InterpolationFn<C>>,
+    pub(crate) extrapolation: Option<Extrapolation<C>>,
     pipeline: InterpolationPipeline,
     _marker: PhantomData<fn(C)>,
 }
@@ -287,6 +291,7 @@ impl<C> InterpolationFns<C> {
     pub fn interpolate(interpolation: LerpFn<C>) -> Self {
         Self {
             interpolation: Some(InterpolationFn::Lerp(interpolation)),
+            extrapolation: None,
             pipeline: InterpolationPipeline::Full,
             _marker: PhantomData,
         }
@@ -300,6 +305,7 @@ impl<C> InterpolationFns<C> {
     pub fn interpolate_with_context(interpolation: ContextInterpolationFn<C>) -> Self {
         Self {
             interpolation: Some(InterpolationFn::Contextual(interpolation)),
+            extrapolation: None,
             pipeline: InterpolationPipeline::Full,
             _marker: PhantomData,
         }
@@ -324,6 +330,7 @@ impl<C> InterpolationFns<C> {
     pub fn history_only() -> Self {
         Self {
             interpolation: None,
+            extrapolation: None,
             pipeline: InterpolationPipeline::HistoryOnly,
             _marker: PhantomData,
         }
@@ -354,6 +361,7 @@ impl<C> InterpolationFns<C> {
     pub fn no_history(interpolation: LerpFn<C>) -> Self {
         Self {
             interpolation: Some(InterpolationFn::Lerp(interpolation)),
+            extrapolation: None,
             pipeline: InterpolationPipeline::NoHistory,
             _marker: PhantomData,
         }
@@ -364,6 +372,7 @@ impl<C> InterpolationFns<C> {
     pub fn no_history_with_context(interpolation: ContextInterpolationFn<C>) -> Self {
         Self {
             interpolation: Some(InterpolationFn::Contextual(interpolation)),
+            extrapolation: None,
             pipeline: InterpolationPipeline::NoHistory,
             _marker: PhantomData,
         }
@@ -404,6 +413,7 @@ impl<C> InterpolationFns<C> {
     pub fn disabled() -> Self {
         Self {
             interpolation: None,
+            extrapolation: None,
             pipeline: InterpolationPipeline::Disabled,
             _marker: PhantomData,
         }
@@ -415,11 +425,47 @@ impl<C> InterpolationFns<C> {
     pub(crate) fn frame_history_only() -> Self {
         Self {
             interpolation: None,
+            extrapolation: None,
             pipeline: InterpolationPipeline::FrameHistoryOnly,
             _marker: PhantomData,
         }
     }
 
+    /// Projects the component past its newest confirmed value with `extrapolation`, instead of
+    /// holding the newest value, when no newer update has been received.
+    ///
+    /// See the [`extrapolate`](crate::extrapolate) module for more details.
+    pub fn extrapolate(
+        mut self,
+        settings: ExtrapolationSettings,
+        extrapolation: ExtrapolateFn<C>,
+    ) -> Self {
+        self.extrapolation = Some(Extrapolation::from_samples(settings, extrapolation));
+        self
+    }
+
+    /// Projects the component past its newest confirmed value along the line going through the
+    /// last two confirmed values, using its [`Ease`] curve.
+    pub fn linear_extrapolate(self, settings: ExtrapolationSettings) -> Self
+    where
+        C: Ease,
+    {
+        self.extrapolate(settings, linear_extrapolate::<C>)
+    }
+
+    /// Projects the component past its newest confirmed value using the velocity component `V`
+    /// of the same entity.
+    pub fn extrapolate_with_velocity<V: Component>(
+        mut self,
+        settings: ExtrapolationSettings,
+    ) -> Self
+    where
+        C: VelocityExtrapolate<V>,
+    {
+        self.extrapolation = Some(Extrapolation::from_velocity::<V>(settings));
+        self
+    }
+
     pub(crate) fn owns_interpolation_history(&self) -> bool {
         matches!(
             self.pipeline,
@@ -557,6 +603,18 @@ impl ErasedInterpolationFn {
         }
     }
 
+    fn from_extrapolation<S: 'static>(extrapolation: Extrapolation<S>) -> Self {
+        Self {
+            inner: Box::new(extrapolation),
+        }
+    }
+
+    pub(crate) fn extrapolation<S: 'static>(&self) -> &Extrapolation<S> {
+        self.inner
+            .downcast_ref::<Extrapolation<S>>()
+            .expect("interpolation rule kind and extrapolation type should match")
+    }
+
     pub(crate) fn typed<S: 'static>(&self) -> &InterpolationFn<S> {
         self.inner
             .downcast_ref::<InterpolationFn<S>>()
@@ -682,6 +740,7 @@ impl CachedInterpolationApply {
 #[derive(Debug)]
 pub(crate) struct ErasedInterpolationFns {
     pub(crate) interpolation: Option<ErasedInterpolationFn>,
+    pub(crate) extrapolation: Option<ErasedInterpolationFn>,
     pub(crate) update_history: Option<ErasedUpdateHistoryFn>,
     pub(crate) backfill_confirmed_history: Option<ErasedBackfillConfirmedHistoryFn>,
     pub(crate) apply_interpolation: Option<ErasedApplyInterpolationFn>,
@@ -706,6 +765,9 @@ impl ErasedInterpolationFns {
             interpolation: fns
                 .interpolation
                 .map(ErasedInterpolationFn::from_typed::<S>),
+            extrapolation: fns
+                .extrapolation
+                .map(ErasedInterpolationFn::from_extrapolation::<S>),
             update_history,
             backfill_confirmed_history,
             apply_interpolation,
@@ -80,6 +80,8 @@ impl TimelineConfig for InterpolationConfig {
 #[derive(Default, Debug, Reflect)]
 pub struct InterpolationContext {
     pub(crate) remote_send_interval: Duration,
+    /// How long the remote holds its replication updates before sending them (for spectators)
+    pub(crate) remote_replication_delay: Duration,
     sync: SyncContext,
     relative_speed: f32,
     is_synced: bool,
@@ -190,8 +192,10 @@ impl TimelineSync for InterpolationTimeline {
         ping_manager: &PingManager,
         tick_duration: Duration,
     ) -> TickInstant {
-        let delay =
-            TickDelta::from_duration(config.to_duration(self.remote_send_interval), tick_duration);
+        let delay = TickDelta::from_duration(
+            config.to_duration(self.remote_send_interval) + self.remote_replication_delay,
+            tick_duration,
+        );
         // take extra margin if there is jitter
         let jitter_margin = TickDelta::from_duration(
             config
@@ -255,6 +259,7 @@ impl TimelinePlugin {
         if clients.contains(trigger.entity) {
             timeline.reset();
             timeline.context.remote_send_interval = Duration::default();
+            timeline.context.remote_replication_delay = Duration::default();
         }
     }
 
@@ -278,13 +283,14 @@ impl TimelinePlugin {
         if clients.contains(trigger.entity) {
             timeline.reset();
             timeline.context.remote_send_interval = Duration::default();
+            timeline.context.remote_replication_delay = Duration::default();
         }
     }
 
-    /// Cache the replication interval advertised by the remote replication source.
+    /// Cache the replication interval and delay advertised by the remote replication source.
     ///
-    /// A conventional client has one source, so new metadata replaces the previous value. P2P
-    /// uses the largest observed interval as a conservative delay for its single presentation
+    /// A conventional client has one source, so new metadata replaces the previous values. P2P
+    /// uses the largest observed values as a conservative delay for its single presentation
     /// cursor.
     fn receive_sender_metadata(
         trigger: On<RemoteEvent<SenderMetadata>>,
@@ -294,6 +300,8 @@ impl TimelinePlugin {
     ) {
         let delta = TickDelta::from(trigger.trigger.send_interval);
         let duration = delta.to_duration(tick_duration.0);
+        let replication_delay =
+            TickDelta::from(trigger.trigger.replication_delay).to_duration(tick_duration.0);
         if !metadata.peer_map.contains_key(&trigger.from) {
             return;
         }
@@ -310,6 +318,15 @@ impl TimelinePlugin {
         } else {
             duration
         };
+        // the updates of a delayed sender arrive that much later than its timeline
+        interpolation_timeline.context.remote_replication_delay = if is_p2p {
+            core::cmp::max(
+                interpolation_timeline.context.remote_replication_delay,
+                replication_delay,
+            )
+        } else {
+            replication_delay
+        };
     }
 
     /// Run one interpolation synchronization sample against the selected remote source.
@@ -15,6 +15,7 @@ pub mod despawn;
 pub mod diagnostics;
 pub mod manager;
 pub mod plugin;
+pub mod predicted_event;
 pub mod predicted_history;
 pub mod registry;
 pub mod rollback;
@@ -30,6 +31,9 @@ pub mod prelude {
         LastConfirmedInput, PredictionManager, RollbackMode, RollbackPolicy, StateRollbackMetadata,
     };
     pub use crate::plugin::{PredictionMarkerPlugin, PredictionPlugin, PredictionSystems};
+    pub use crate::predicted_event::{
+        PredictedEvent, PredictedEventAppExt, PredictedEventCancelled, PredictedEvents,
+    };
     pub use crate::predicted_history::PredictionHistory;
     pub use crate::registry::{
         LocalRollbackComponentRegistration, PredictedComponentRegistration,
@@ -0,0 +1,337 @@
+//! Rollback-aware one-shot gameplay events.
+//!
+//! Systems in `FixedMain` run again for every tick that is replayed during a rollback, so a system
+//! that plays a sound or spawns a damage number when a collision happens would do it once for the
+//! original prediction and once more per replay.
+//!
+//! Emitting the event through [`PredictedEvents`] instead records it under a `(tick, source)` key,
+//! where the source is the entity that caused the event:
+//! - the first emission for a key triggers [`PredictedEvent<E>`];
+//! - emitting the same key again while replaying that tick is suppressed;
+//! - if the replay of a tick does not emit an event that was recorded for it, the rollback proved
+//!   that the event never happened, and [`PredictedEventCancelled<E>`] is triggered so that the
+//!   consumer can stop the sound or remove the damage number.
+//!
+//! Events stay in the history for as long as their tick can be rolled back.
+//!
+//! ```rust,ignore
+//! app.add_predicted_event::<Hit>();
+//!
+//! fn detect_hits(mut events: PredictedEvents<Hit>, query: Query<(Entity, &Health), Changed<Health>>) {
+//!     for (entity, health) in &query {
+//!         events.emit(entity, Hit { damage: health.last_damage });
+//!     }
+//! }
+//!
+//! app.add_observer(|trigger: On<PredictedEvent<Hit>>| { /* play the sound */ });
+//! app.add_observer(|trigger: On<PredictedEventCancelled<Hit>>| { /* stop the sound */ });
+//! ```
+use crate::manager::PredictionManager;
+use crate::rollback::{RollbackSystems, end_rollback};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_ecs::system::SystemParam;
+use lightyear_core::prelude::{LocalTimeline, Tick};
+use lightyear_core::timeline::{LocalTimelineShift, Rollback};
+use lightyear_sync::prelude::InputTimelineConfig;
+#[allow(unused_imports)]
+use tracing::{debug, trace};
+
+/// Triggered the first time an event is emitted for a given tick and source.
+#[derive(Event, Debug, Clone)]
+pub struct PredictedEvent<E: Send + Sync + 'static> {
+    /// Tick at which the event was emitted
+    pub tick: Tick,
+    /// Entity that caused the event
+    pub source: Entity,
+    pub event: E,
+}
+
+/// Triggered when a rollback replayed the tick of a [`PredictedEvent`] without emitting it again.
+#[derive(Event, Debug, Clone)]
+pub struct PredictedEventCancelled<E: Send + Sync + 'static> {
+    pub tick: Tick,
+    pub source: Entity,
+    pub event: E,
+}
+
+#[derive(Debug)]
+struct Record<E> {
+    event: E,
+    /// False while a rollback is replaying the tick of the event and has not emitted it yet.
+    confirmed: bool,
+}
+
+/// History of the events emitted through [`PredictedEvents<E>`] that can still be rolled back.
+#[derive(Resource, Debug)]
+pub struct PredictedEventHistory<E> {
+    events: BTreeMap<(Tick, Entity), Record<E>>,
+}
+
+impl<E> Default for PredictedEventHistory<E> {
+    fn default() -> Self {
+        Self {
+            events: BTreeMap::new(),
+        }
+    }
+}
+
+impl<E> PredictedEventHistory<E> {
+    /// Returns true if an event was recorded for this tick and source.
+    pub fn contains(&self, tick: Tick, source: Entity) -> bool {
+        self.events.contains_key(&(tick, source))
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+/// [`SystemParam`] used to emit rollback-aware events from `FixedMain` systems.
+///
+/// See the [module-level documentation](self).
+#[derive(SystemParam)]
+pub struct PredictedEvents<'w, 's, E: Clone + Send + Sync + 'static> {
+    history: ResMut<'w, PredictedEventHistory<E>>,
+    timeline: Res<'w, LocalTimeline>,
+    rollback: Option<Res<'w, Rollback>>,
+    commands: Commands<'w, 's>,
+}
+
+impl<E: Clone + Send + Sync + 'static> PredictedEvents<'_, '_, E> {
+    /// Emits `event` for the current tick, unless `source` already emitted an event for this tick.
+    pub fn emit(&mut self, source: Entity, event: E) {
+        let tick = self.timeline.tick();
+        if let Some(record) = self.history.events.get_mut(&(tick, source)) {
+            if self.rollback.is_some() && !record.confirmed {
+                trace!(?tick, ?source, "predicted event confirmed by the rollback");
+                record.confirmed = true;
+            }
+            return;
+        }
+        self.history.events.insert(
+            (tick, source),
+            Record {
+                event: event.clone(),
+                confirmed: true,
+            },
+        );
+        self.commands.trigger(PredictedEvent {
+            tick,
+            source,
+            event,
+        });
+    }
+}
+
+/// Mark the events of the ticks that will be replayed as unconfirmed.
+fn prepare_predicted_events<E: Send + Sync + 'static>(
+    manager: Res<PredictionManager>,
+    mut history: ResMut<PredictedEventHistory<E>>,
+) {
+    let Some(rollback_tick) = manager.get_rollback_start_tick() else {
+        return;
+    };
+    // the rollback restores the state at the end of `rollback_tick`, and replays the ticks after it
+    history
+        .events
+        .iter_mut()
+        .filter(|((tick, _), _)| *tick > rollback_tick)
+        .for_each(|(_, record)| record.confirmed = false);
+}
+
+/// Cancel the events that were not emitted again during the rollback.
+fn cancel_predicted_events<E: Send + Sync + 'static>(
+    mut history: ResMut<PredictedEventHistory<E>>,
+    mut commands: Commands,
+) {
+    let cancelled = history
+        .events
+        .iter()
+        .filter(|(_, record)| !record.confirmed)
+        .map(|(key, _)| *key)
+        .collect::<Vec<_>>();
+    for (tick, source) in cancelled {
+        let record = history.events.remove(&(tick, source)).unwrap();
+        debug!(?tick, ?source, "predicted event cancelled by the rollback");
+        commands.trigger(PredictedEventCancelled {
+            tick,
+            source,
+            event: record.event,
+        });
+    }
+}
+
+/// Remove the events that are too old to be rolled back.
+fn prune_predicted_events<E: Send + Sync + 'static>(
+    timeline: Res<LocalTimeline>,
+    manager: Option<Res<PredictionManager>>,
+    input_config: Option<Res<InputTimelineConfig>>,
+    mut history: ResMut<PredictedEventHistory<E>>,
+) {
+    let Some(manager) = manager else {
+        // without prediction there are no rollbacks, so the history is only used for deduplication
+        let tick = timeline.tick();
+        history
+            .events
+            .retain(|&(event_tick, _), _| event_tick >= tick);
+        return;
+    };
+    let max_rollback_ticks = input_config.as_deref().map_or(
+        manager.rollback_policy.max_rollback_ticks,
+        |input_config| {
+            manager
+                .rollback_policy
+                .effective_max_rollback_ticks(input_config)
+        },
+    );
+    let oldest_tick = timeline.tick() - u32::from(max_rollback_ticks);
+    history.events.retain(|&(tick, _), _| tick >= oldest_tick);
+}
+
+fn handle_local_timeline_shift_predicted_events<E: Send + Sync + 'static>(
+    trigger: On<LocalTimelineShift>,
+    mut history: ResMut<PredictedEventHistory<E>>,
+) {
+    let events = core::mem::take(&mut history.events);
+    history.events = events
+        .into_iter()
+        .map(|((tick, source), record)| ((tick + trigger.delta, source), record))
+        .collect();
+}
+
+pub trait PredictedEventAppExt {
+    /// Enables emitting `E` through [`PredictedEvents<E>`].
+    fn add_predicted_event<E: Clone + Send + Sync + 'static>(&mut self) -> &mut Self;
+}
+
+impl PredictedEventAppExt for App {
+    fn add_predicted_event<E: Clone + Send + Sync + 'static>(&mut self) -> &mut Self {
+        if self.world().contains_resource::<PredictedEventHistory<E>>() {
+            return self;
+        }
+        self.init_resource::<PredictedEventHistory<E>>();
+        self.add_observer(handle_local_timeline_shift_predicted_events::<E>);
+        self.add_systems(
+            PreUpdate,
+            (
+                prepare_predicted_events::<E>.in_set(RollbackSystems::Prepare),
+                cancel_predicted_events::<E>
+                    .in_set(RollbackSystems::EndRollback)
+                    .before(end_rollback),
+            ),
+        );
+        self.add_systems(Last, prune_predicted_events::<E>);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::system::RunSystemOnce;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Hit(u32);
+
+    #[derive(Resource, Default)]
+    struct Received {
+        emitted: Vec<(Tick, Hit)>,
+        cancelled: Vec<(Tick, Hit)>,
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.init_resource::<LocalTimeline>();
+        app.init_resource::<PredictionManager>();
+        app.init_resource::<Received>();
+        app.add_predicted_event::<Hit>();
+        app.add_observer(
+            |trigger: On<PredictedEvent<Hit>>, mut received: ResMut<Received>| {
+                received.emitted.push((trigger.tick, trigger.event.clone()));
+            },
+        );
+        app.add_observer(
+            |trigger: On<PredictedEventCancelled<Hit>>, mut received: ResMut<Received>| {
+                received
+                    .cancelled
+                    .push((trigger.tick, trigger.event.clone()));
+            },
+        );
+        app.world_mut()
+            .resource_mut::<LocalTimeline>()
+            .apply_delta(5);
+        app
+    }
+
+    fn emit(app: &mut App, source: Entity, hit: Hit) {
+        app.world_mut()
+            .run_system_once(move |mut events: PredictedEvents<Hit>| {
+                events.emit(source, hit.clone());
+            })
+            .unwrap();
+    }
+
+    /// Simulate a rollback from tick 3 that replays tick 5, during which `replay` runs.
+    fn rollback(app: &mut App, replay: impl FnOnce(&mut App)) {
+        app.world()
+            .resource::<PredictionManager>()
+            .set_rollback_tick(Tick(3));
+        app.insert_resource(Rollback::FromState);
+        app.world_mut()
+            .run_system_once(prepare_predicted_events::<Hit>)
+            .unwrap();
+        replay(app);
+        app.world_mut()
+            .run_system_once(cancel_predicted_events::<Hit>)
+            .unwrap();
+        app.world()
+            .resource::<PredictionManager>()
+            .set_non_rollback();
+        app.world_mut().remove_resource::<Rollback>();
+    }
+
+    #[test]
+    fn replayed_event_is_not_emitted_again() {
+        let mut app = app();
+        let source = app.world_mut().spawn_empty().id();
+        emit(&mut app, source, Hit(1));
+        // the same source can only emit once per tick
+        emit(&mut app, source, Hit(2));
+
+        rollback(&mut app, |app| emit(app, source, Hit(1)));
+
+        let received = app.world().resource::<Received>();
+        assert_eq!(received.emitted, [(Tick(5), Hit(1))]);
+        assert!(received.cancelled.is_empty());
+        assert!(
+            app.world()
+                .resource::<PredictedEventHistory<Hit>>()
+                .contains(Tick(5), source)
+        );
+    }
+
+    #[test]
+    fn rollback_cancels_events_that_did_not_happen() {
+        let mut app = app();
+        let source = app.world_mut().spawn_empty().id();
+        let other = app.world_mut().spawn_empty().id();
+        emit(&mut app, source, Hit(1));
+
+        // the replay predicts a different outcome
+        rollback(&mut app, |app| emit(app, other, Hit(2)));
+
+        let received = app.world().resource::<Received>();
+        assert_eq!(received.emitted, [(Tick(5), Hit(1)), (Tick(5), Hit(2))]);
+        assert_eq!(received.cancelled, [(Tick(5), Hit(1))]);
+        let history = app.world().resource::<PredictedEventHistory<Hit>>();
+        assert!(!history.contains(Tick(5), source));
+        assert!(history.contains(Tick(5), other));
+    }
+}
@@ -0,0 +1,610 @@
+//! Transfer of simulation authority over replicated entities between the server and its clients.
+//!
+//! Replication is driven by the server by default: it simulates the entity and replicates it to
+//! the clients. Some entities are better simulated by one client for a while, for example a
+//! physics object that a player picked up. Instead of despawning the entity and respawning a
+//! client-replicated copy, the server can hand the authority over the existing entity to a client
+//! and take it back later.
+//!
+//! - On the server, [`AuthorityPeer`] records which peer currently has authority. Trigger
+//!   [`TransferAuthority`] to grant authority to a client link or to revoke it back to the server.
+//! - Clients trigger [`RequestAuthority`] or [`ReleaseAuthority`]. The server answers requests
+//!   according to the entity's [`AuthorityRequestPolicy`], or triggers [`AuthorityRequested`] so
+//!   that the application can decide.
+//! - A client that has authority over an entity has the [`HasAuthority`] marker on its copy of the
+//!   entity.
+//!
+//! While a client has authority, the server stops sending updates of the entity to that client
+//! but retains the client's copy, so both sides keep their entity mapping. The owning client
+//! becomes the replication sender of the entity: it sends the replicated components that change on
+//! its copy to the server, which applies them and replicates them to the other clients. Every
+//! component registered with one of the `replicate*` methods that use serde or [`BitSerialize`]
+//! is sent; components registered with custom Replicon rule functions are not. The entities
+//! contained in the components are not mapped.
+//!
+//! Authority is opt-in: add the [`AuthorityPlugin`] to the client and the server apps.
+//!
+//! ```rust,ignore
+//! app.add_plugins(AuthorityPlugin);
+//! ```
+//!
+//! Authority returns to the server automatically when the owning client disconnects.
+//!
+//! [`BitSerialize`]: lightyear_serde::bits::BitSerialize
+use crate::registry::replication::{ComponentRegistration, deserialize_as, serialize_as};
+use crate::registry::{ComponentKind, ComponentNetId, ComponentRegistry};
+use alloc::vec::Vec;
+use bevy_app::{App, Plugin};
+use bevy_ecs::entity::{EntityMapper, MapEntities};
+use bevy_ecs::prelude::*;
+use bevy_reflect::Reflect;
+use lightyear_connection::direction::NetworkDirection;
+#[cfg(any(feature = "client", feature = "server"))]
+use lightyear_messages::plugin::MessageSystems;
+use lightyear_messages::prelude::AppMessageExt;
+use lightyear_serde::SerializationError;
+use lightyear_serde::reader::Reader;
+use lightyear_serde::registry::{DeserializeFn, SerializeFn, SerializeFns};
+use lightyear_serde::writer::Writer;
+use lightyear_transport::prelude::{AppChannelExt, ChannelMode, ChannelSettings, ReliableSettings};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+#[allow(unused_imports)]
+use tracing::{debug, trace};
+
+/// Marker component indicating that the client has authority over its copy of this entity.
+///
+/// It is inserted when the server grants authority to the client and removed when the authority
+/// is revoked. The server records the owner of its entities with [`AuthorityPeer`] instead.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Default, Reflect)]
+pub struct HasAuthority;
+
+/// Server-side component that records which peer has authority over a replicated entity.
+///
+/// An entity without this component is owned by the server.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Default, Reflect)]
+#[component(immutable)]
+pub enum AuthorityPeer {
+    /// The server simulates the entity and replicates it to every client.
+    #[default]
+    Server,
+    /// The client behind this link entity has authority over the entity.
+    Client(Entity),
+}
+
+/// Server-side component that decides how [`RequestAuthority`] from clients are answered.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Default, Reflect)]
+pub enum AuthorityRequestPolicy {
+    /// Trigger [`AuthorityRequested`] and let the application answer with [`TransferAuthority`].
+    #[default]
+    Manual,
+    /// Grant authority to the requesting client if the server currently has authority.
+    AcceptIfServerOwned,
+    /// Grant authority to the requesting client if it controls the entity via
+    /// [`ControlledBy`](crate::control::ControlledBy), even if another client has authority.
+    AcceptFromController,
+    /// Ignore every request.
+    Deny,
+}
+
+/// Server-side trigger that transfers the authority over an entity to another peer.
+///
+/// Use [`AuthorityPeer::Client`] to grant authority to a client link and [`AuthorityPeer::Server`]
+/// to revoke it.
+#[derive(EntityEvent, Clone, Copy, Debug)]
+pub struct TransferAuthority {
+    /// The replicated entity, in the server's World.
+    pub entity: Entity,
+    pub to: AuthorityPeer,
+}
+
+/// Triggered on the server when a client requested authority over an entity whose
+/// [`AuthorityRequestPolicy`] is [`Manual`](AuthorityRequestPolicy::Manual).
+#[derive(EntityEvent, Clone, Copy, Debug)]
+pub struct AuthorityRequested {
+    /// The replicated entity, in the server's World.
+    pub entity: Entity,
+    /// Link entity of the requesting client.
+    pub link: Entity,
+}
+
+/// Triggered on the server after the authority over an entity changed.
+#[derive(EntityEvent, Clone, Copy, Debug)]
+pub struct AuthorityChanged {
+    pub entity: Entity,
+    pub previous: AuthorityPeer,
+    pub current: AuthorityPeer,
+}
+
+/// Client-side trigger to ask the server for authority over a replicated entity.
+#[derive(EntityEvent, Clone, Copy, Debug)]
+pub struct RequestAuthority {
+    /// The replicated entity, in the client's World.
+    pub entity: Entity,
+}
+
+/// Client-side trigger to give the authority over an entity back to the server.
+#[derive(EntityEvent, Clone, Copy, Debug)]
+pub struct ReleaseAuthority {
+    /// The replicated entity, in the client's World.
+    pub entity: Entity,
+}
+
+/// Control messages exchanged between the server and clients to transfer authority.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum AuthorityMessage {
+    /// Client to server: ask for authority over the entity.
+    Request(Entity),
+    /// Client to server: give the authority back to the server.
+    Release(Entity),
+    /// Server to client: the client now has authority over the entity.
+    Granted(Entity),
+    /// Server to client: the client no longer has authority over the entity.
+    Revoked(Entity),
+}
+
+impl MapEntities for AuthorityMessage {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        match self {
+            Self::Request(entity)
+            | Self::Release(entity)
+            | Self::Granted(entity)
+            | Self::Revoked(entity) => *entity = entity_mapper.get_mapped(*entity),
+        }
+    }
+}
+
+/// Client to server: the replicated components that changed on an entity that the client has
+/// authority over.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub(crate) struct AuthorityReplication {
+    entity: Entity,
+    /// Network id and serialized value of each component
+    components: Vec<(ComponentNetId, Vec<u8>)>,
+}
+
+impl MapEntities for AuthorityReplication {
+    fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
+        self.entity = entity_mapper.get_mapped(self.entity);
+    }
+}
+
+/// Reliable channel used to transfer authority.
+///
+/// Grants and revocations must be applied in the order they were decided by the server. The
+/// replication from the owner uses the same channel so that it cannot arrive after a release.
+pub struct AuthorityChannel;
+
+type WriteFn = fn(&AuthorityFns, &EntityRef, &mut Writer) -> Result<bool, SerializationError>;
+type ApplyFn =
+    fn(&AuthorityFns, &mut Reader, &mut EntityCommands) -> Result<(), SerializationError>;
+
+/// Type-erased functions that send a component from the client that has authority over an entity
+/// to the server.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct AuthorityFns {
+    // SerializeFn<C> and DeserializeFn<C> converted to fn() to avoid generic parameters
+    serialize: fn(),
+    deserialize: fn(),
+    write_fn: WriteFn,
+    apply_fn: ApplyFn,
+}
+
+impl AuthorityFns {
+    fn new<C: Component>(serialize: SerializeFn<C>, deserialize: DeserializeFn<C>) -> Self {
+        Self {
+            serialize: unsafe { core::mem::transmute::<SerializeFn<C>, fn()>(serialize) },
+            deserialize: unsafe { core::mem::transmute::<DeserializeFn<C>, fn()>(deserialize) },
+            write_fn: write_changed::<C>,
+            apply_fn: apply::<C>,
+        }
+    }
+
+    /// Writes the component of the entity if it changed since the last send. Returns `false` if
+    /// it didn't.
+    #[cfg_attr(not(feature = "client"), allow(dead_code))]
+    fn write(&self, entity: &EntityRef, writer: &mut Writer) -> Result<bool, SerializationError> {
+        (self.write_fn)(self, entity, writer)
+    }
+
+    /// Reads the component and inserts it on the entity.
+    #[cfg_attr(not(feature = "server"), allow(dead_code))]
+    fn apply(
+        &self,
+        reader: &mut Reader,
+        entity: &mut EntityCommands,
+    ) -> Result<(), SerializationError> {
+        (self.apply_fn)(self, reader, entity)
+    }
+}
+
+fn write_changed<C: Component>(
+    fns: &AuthorityFns,
+    entity: &EntityRef,
+    writer: &mut Writer,
+) -> Result<bool, SerializationError> {
+    let Some(component) = entity.get_ref::<C>() else {
+        return Ok(false);
+    };
+    if !component.is_changed() {
+        return Ok(false);
+    }
+    // SAFETY: the AuthorityFns were created for the component C
+    let serialize = unsafe { core::mem::transmute::<fn(), SerializeFn<C>>(fns.serialize) };
+    serialize(component.into_inner(), writer)?;
+    Ok(true)
+}
+
+fn apply<C: Component>(
+    fns: &AuthorityFns,
+    reader: &mut Reader,
+    entity: &mut EntityCommands,
+) -> Result<(), SerializationError> {
+    // SAFETY: the AuthorityFns were created for the component C
+    let deserialize = unsafe { core::mem::transmute::<fn(), DeserializeFn<C>>(fns.deserialize) };
+    entity.insert(deserialize(reader)?);
+    Ok(())
+}
+
+impl<C: Component> ComponentRegistration<'_, C> {
+    /// Let the client that has authority over an entity send this component to the server, using
+    /// its `Serialize` and `Deserialize` implementations.
+    pub(crate) fn add_authority(self) -> Self
+    where
+        C: Serialize + DeserializeOwned,
+    {
+        let fns = SerializeFns::<C>::default();
+        self.add_custom_authority(fns.serialize, fns.deserialize)
+    }
+
+    pub(crate) fn add_authority_as<T>(self) -> Self
+    where
+        C: Clone + Into<T> + From<T>,
+        T: Serialize + DeserializeOwned,
+    {
+        self.add_custom_authority(serialize_as::<C, T>, deserialize_as::<C, T>)
+    }
+
+    pub(crate) fn add_custom_authority(
+        self,
+        serialize: SerializeFn<C>,
+        deserialize: DeserializeFn<C>,
+    ) -> Self {
+        self.app.world_mut().init_resource::<ComponentRegistry>();
+        let mut registry = self.app.world_mut().resource_mut::<ComponentRegistry>();
+        if let Some(replication) = registry
+            .component_metadata_map
+            .get_mut(&ComponentKind::of::<C>())
+            .and_then(|metadata| metadata.replication.as_mut())
+        {
+            replication.authority = Some(AuthorityFns::new(serialize, deserialize));
+        }
+        self
+    }
+}
+
+/// Lets the server transfer the authority over its replicated entities to clients.
+///
+/// This must be added identically on the client and the server because message and channel ids
+/// are assigned in registration order.
+pub struct AuthorityPlugin;
+
+impl Plugin for AuthorityPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_channel::<AuthorityChannel>(ChannelSettings {
+            mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+            priority: 10.0,
+            ..Default::default()
+        })
+        .add_direction(NetworkDirection::Bidirectional);
+        app.register_message::<AuthorityMessage>()
+            .add_map_entities()
+            .add_direction(NetworkDirection::Bidirectional);
+        app.register_message::<AuthorityReplication>()
+            .add_map_entities()
+            .add_direction(NetworkDirection::ClientToServer);
+
+        #[cfg(feature = "server")]
+        {
+            app.add_observer(server::transfer_authority);
+            app.add_observer(server::revoke_on_disconnect);
+            app.add_systems(
+                bevy_app::PreUpdate,
+                // a release is applied before the replication that the client sent after it
+                (
+                    server::receive_authority_messages,
+                    server::receive_authority_replication,
+                )
+                    .chain()
+                    .after(MessageSystems::Receive),
+            );
+        }
+        #[cfg(feature = "client")]
+        {
+            app.add_observer(client::request_authority);
+            app.add_observer(client::release_authority);
+            app.add_systems(
+                bevy_app::PreUpdate,
+                client::receive_authority_messages.after(MessageSystems::Receive),
+            );
+            app.add_systems(
+                bevy_app::PostUpdate,
+                client::send_authority_replication.before(MessageSystems::Send),
+            );
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+mod server {
+    use super::*;
+    use crate::control::ControlledBy;
+    use crate::visibility::immediate::VisibilityExt;
+    use lightyear_connection::client::Disconnected;
+    use lightyear_connection::client_of::ClientOf;
+    use lightyear_messages::prelude::{MessageReceiver, MessageSender};
+
+    pub(super) fn transfer_authority(
+        trigger: On<TransferAuthority>,
+        mut commands: Commands,
+        entities: Query<Option<&AuthorityPeer>>,
+        mut senders: Query<&mut MessageSender<AuthorityMessage>, With<ClientOf>>,
+    ) {
+        let entity = trigger.entity;
+        let Ok(previous) = entities.get(entity) else {
+            return;
+        };
+        let previous = previous.copied().unwrap_or_default();
+        let current = trigger.to;
+        if previous == current {
+            return;
+        }
+
+        if let AuthorityPeer::Client(link) = previous {
+            // resume sending updates so that the former owner converges to the server state
+            commands.gain_visibility(entity, link);
+            if let Ok(mut sender) = senders.get_mut(link) {
+                sender.send::<AuthorityChannel>(AuthorityMessage::Revoked(entity));
+            }
+        }
+        if let AuthorityPeer::Client(link) = current {
+            // the owner's copy is retained so that the entity mapping survives the transfer
+            commands.lose_visibility_retained(entity, link);
+            if let Ok(mut sender) = senders.get_mut(link) {
+                sender.send::<AuthorityChannel>(AuthorityMessage::Granted(entity));
+            }
+        }
+        debug!(?entity, ?previous, ?current, "authority transferred");
+        commands.entity(entity).insert(current);
+        commands.trigger(AuthorityChanged {
+            entity,
+            previous,
+            current,
+        });
+    }
+
+    pub(super) fn revoke_on_disconnect(
+        trigger: On<Add, Disconnected>,
+        mut commands: Commands,
+        entities: Query<(Entity, &AuthorityPeer)>,
+    ) {
+        for (entity, peer) in entities.iter() {
+            if *peer == AuthorityPeer::Client(trigger.entity) {
+                trace!(
+                    ?entity,
+                    "returning authority to the server because its owner disconnected"
+                );
+                commands.trigger(TransferAuthority {
+                    entity,
+                    to: AuthorityPeer::Server,
+                });
+            }
+        }
+    }
+
+    pub(super) fn receive_authority_messages(
+        mut commands: Commands,
+        mut receivers: Query<(Entity, &mut MessageReceiver<AuthorityMessage>), With<ClientOf>>,
+        entities: Query<(
+            Option<&AuthorityPeer>,
+            Option<&AuthorityRequestPolicy>,
+            Option<&ControlledBy>,
+        )>,
+    ) {
+        for (link, mut receiver) in receivers.iter_mut() {
+            for message in receiver.receive() {
+                match message {
+                    AuthorityMessage::Request(entity) => {
+                        let Ok((peer, policy, controlled_by)) = entities.get(entity) else {
+                            debug!(?entity, ?link, "authority requested for an unknown entity");
+                            continue;
+                        };
+                        let peer = peer.copied().unwrap_or_default();
+                        if peer == AuthorityPeer::Client(link) {
+                            continue;
+                        }
+                        let accept = match policy.copied().unwrap_or_default() {
+                            AuthorityRequestPolicy::Manual => {
+                                commands.trigger(AuthorityRequested { entity, link });
+                                false
+                            }
+                            AuthorityRequestPolicy::AcceptIfServerOwned => {
+                                peer == AuthorityPeer::Server
+                            }
+                            AuthorityRequestPolicy::AcceptFromController => controlled_by
+                                .is_some_and(|controlled_by| controlled_by.owner == link),
+                            AuthorityRequestPolicy::Deny => false,
+                        };
+                        if accept {
+                            commands.trigger(TransferAuthority {
+                                entity,
+                                to: AuthorityPeer::Client(link),
+                            });
+                        }
+                    }
+                    AuthorityMessage::Release(entity) => {
+                        // only the current owner can give the authority back
+                        if entities
+                            .get(entity)
+                            .is_ok_and(|(peer, ..)| peer == Some(&AuthorityPeer::Client(link)))
+                        {
+                            commands.trigger(TransferAuthority {
+                                entity,
+                                to: AuthorityPeer::Server,
+                            });
+                        }
+                    }
+                    AuthorityMessage::Granted(_) | AuthorityMessage::Revoked(_) => {
+                        debug!(?link, ?message, "ignoring server-only authority message");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Apply the components replicated by the clients that have authority over the entities.
+    ///
+    /// The server then replicates them to the other clients.
+    pub(super) fn receive_authority_replication(
+        mut commands: Commands,
+        registry: Option<Res<ComponentRegistry>>,
+        mut receivers: Query<(Entity, &mut MessageReceiver<AuthorityReplication>), With<ClientOf>>,
+        peers: Query<&AuthorityPeer>,
+    ) {
+        let Some(registry) = registry else {
+            return;
+        };
+        for (link, mut receiver) in receivers.iter_mut() {
+            for message in receiver.receive() {
+                // the replication can still be in flight after the authority was revoked
+                if !peers
+                    .get(message.entity)
+                    .is_ok_and(|peer| *peer == AuthorityPeer::Client(link))
+                {
+                    trace!(entity = ?message.entity, ?link, "ignoring replication from a client without authority");
+                    continue;
+                }
+                let mut entity = commands.entity(message.entity);
+                for (net_id, bytes) in message.components {
+                    let Some(fns) = registry
+                        .kind_map
+                        .kind(net_id)
+                        .and_then(|kind| registry.component_metadata_map.get(kind))
+                        .and_then(|metadata| metadata.replication.as_ref())
+                        .and_then(|replication| replication.authority)
+                    else {
+                        debug!(
+                            ?net_id,
+                            ?link,
+                            "received a component that cannot be replicated by a client"
+                        );
+                        continue;
+                    };
+                    if let Err(e) = fns.apply(&mut Reader::from(bytes), &mut entity) {
+                        debug!(
+                            ?e,
+                            ?net_id,
+                            ?link,
+                            "could not read a component replicated by a client"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "client")]
+mod client {
+    use super::*;
+    use crate::prelude::Replicated;
+    use lightyear_connection::client::Client;
+    use lightyear_messages::prelude::{MessageReceiver, MessageSender};
+
+    pub(super) fn request_authority(
+        trigger: On<RequestAuthority>,
+        mut senders: Query<&mut MessageSender<AuthorityMessage>, With<Client>>,
+    ) {
+        if let Ok(mut sender) = senders.single_mut() {
+            sender.send::<AuthorityChannel>(AuthorityMessage::Request(trigger.entity));
+        }
+    }
+
+    pub(super) fn release_authority(
+        trigger: On<ReleaseAuthority>,
+        mut senders: Query<&mut MessageSender<AuthorityMessage>, With<Client>>,
+    ) {
+        if let Ok(mut sender) = senders.single_mut() {
+            sender.send::<AuthorityChannel>(AuthorityMessage::Release(trigger.entity));
+        }
+    }
+
+    pub(super) fn receive_authority_messages(
+        mut commands: Commands,
+        mut receivers: Query<(Entity, &mut MessageReceiver<AuthorityMessage>), With<Client>>,
+        entities: Query<(), With<Replicated>>,
+    ) {
+        for (link, mut receiver) in receivers.iter_mut() {
+            for message in receiver.receive() {
+                match message {
+                    AuthorityMessage::Granted(entity) => {
+                        if !entities.contains(entity) {
+                            debug!(?entity, "authority granted for an unknown entity");
+                            continue;
+                        }
+                        commands.entity(entity).insert(HasAuthority);
+                    }
+                    AuthorityMessage::Revoked(entity) => {
+                        if entities.contains(entity) {
+                            commands.entity(entity).remove::<HasAuthority>();
+                        }
+                    }
+                    AuthorityMessage::Request(_) | AuthorityMessage::Release(_) => {
+                        debug!(?link, ?message, "ignoring client-only authority message");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Replicate the components that changed on the entities that the client has authority over
+    /// to the server.
+    pub(super) fn send_authority_replication(
+        registry: Option<Res<ComponentRegistry>>,
+        mut senders: Query<&mut MessageSender<AuthorityReplication>, With<Client>>,
+        entities: Query<EntityRef, (With<HasAuthority>, With<Replicated>, Without<Client>)>,
+    ) {
+        let (Some(registry), Ok(mut sender)) = (registry, senders.single_mut()) else {
+            return;
+        };
+        for entity in entities.iter() {
+            let mut components = Vec::new();
+            for component_id in entity.archetype().components() {
+                let Some(kind) = registry.component_id_to_kind.get(&component_id) else {
+                    continue;
+                };
+                let (Some(net_id), Some(fns)) = (
+                    registry.kind_map.net_id(kind),
+                    registry
+                        .component_metadata_map
+                        .get(kind)
+                        .and_then(|metadata| metadata.replication.as_ref())
+                        .and_then(|replication| replication.authority),
+                ) else {
+                    continue;
+                };
+                let mut writer = Writer::default();
+                match fns.write(&entity, &mut writer) {
+                    Ok(true) => components.push((*net_id, writer.into_bytes().to_vec())),
+                    Ok(false) => {}
+                    Err(e) => debug!(?e, entity = ?entity.id(), "could not write a component"),
+                }
+            }
+            if !components.is_empty() {
+                sender.send::<AuthorityChannel>(AuthorityReplication {
+                    entity: entity.id(),
+                    components,
+                });
+            }
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use alloc::vec::Vec;
 use bevy_app::prelude::*;
 use bevy_ecs::prelude::*;
 use bevy_state::prelude::*;
@@ -8,6 +9,7 @@ use bevy_replicon::prelude::*;
 use bevy_replicon::shared::server_entity_map::ServerEntityMap;
 use lightyear_connection::client::{Client, Connected};
 use lightyear_connection::host::HostClient;
+use lightyear_connection::resumption::Suspended;
 use lightyear_messages::MessageManager;
 use lightyear_transport::plugin::TransportSystems;
 use lightyear_transport::prelude::Transport;
@@ -68,6 +70,13 @@ impl Plugin for RepliconClientPlugin {
                 .after(ServerSystems::Receive),
         );
 
+        app.add_systems(
+            PreUpdate,
+            apply_premapped_entities
+                .before(ClientSystems::Receive)
+                .run_if(in_state(ClientState::Connected)),
+        );
+
         // bevy_replicon's reset only clears the entity map; lightyear must clean up the actual
         // entities when their receiver disconnects or is removed.
         app.add_observer(on_replication_disconnect);
@@ -85,6 +94,40 @@ impl Plugin for RepliconClientPlugin {
     }
 }
 
+/// Maps the entities of the server that the client connects to onto existing local entities,
+/// instead of spawning new ones.
+///
+/// Insert it on the [`Client`] entity with `(server entity, local entity)` pairs before the
+/// connection is established. The mapping is applied before the first replication message of the
+/// server is received, then the component is removed. If the client is currently connected, the
+/// local entities should be [`Persistent`] so that they are not despawned when it disconnects.
+///
+/// This is used to move a client to another server without respawning the entities that the new
+/// server took over.
+#[derive(Component, Debug, Clone, Default, PartialEq)]
+pub struct PremappedEntities(pub Vec<(Entity, Entity)>);
+
+fn apply_premapped_entities(
+    mut entity_map: ResMut<ServerEntityMap>,
+    query: Query<
+        (Entity, &PremappedEntities),
+        (With<Client>, With<Connected>, With<ReplicationReceiver>),
+    >,
+    entities: Query<()>,
+    mut commands: Commands,
+) {
+    for (client, premapped) in query.iter() {
+        for (server_entity, client_entity) in premapped.0.iter().copied() {
+            if entities.get(client_entity).is_err() {
+                debug!(?client_entity, "Premapped entity does not exist anymore");
+                continue;
+            }
+            entity_map.insert(server_entity, client_entity);
+        }
+        commands.entity(client).remove::<PremappedEntities>();
+    }
+}
+
 #[cfg(any(feature = "prediction", feature = "interpolation"))]
 fn sync_checkpoint_last_confirmed_tick(
     server_mutate_ticks: Res<ServerMutateTicks>,
@@ -130,11 +173,14 @@ fn sync_checkpoint_last_confirmed_tick(
 /// Host-clients intentionally keep Replicon's `ClientState` disconnected so the app behaves like
 /// a listen server: replication receive stays disabled and host-local client behavior is emulated
 /// directly in the shared world instead.
+///
+/// A [`Suspended`] client is still considered connected, so that replicon keeps its entity map
+/// while the session can be resumed.
 fn sync_client_state(
     connected: Query<
         (),
         (
-            With<Connected>,
+            Or<(With<Connected>, With<Suspended>)>,
             With<Client>,
             With<ReplicationReceiver>,
             Without<HostClient>,
@@ -190,17 +236,17 @@ fn send_client_packets(
 
 /// Cleans up receiver-side replication state when a receiver disconnects or is removed.
 ///
-/// Watching `Remove` for both [`Connected`] and [`ReplicationReceiver`] handles disconnection,
-/// explicit receiver removal, and receiver entity despawn with a single observer. Lifecycle
-/// `Remove` observers run before the components disappear, so receiver-local [`Persistent`] can
-/// still be read here.
+/// Watching `Remove` for [`Connected`], [`Suspended`] and [`ReplicationReceiver`] handles
+/// disconnection, expired suspended sessions, explicit receiver removal, and receiver entity
+/// despawn with a single observer. Lifecycle `Remove` observers run before the components
+/// disappear, so receiver-local [`Persistent`] can still be read here.
 fn on_replication_disconnect(
-    trigger: On<Remove, (Connected, ReplicationReceiver)>,
+    trigger: On<Remove, (Connected, Suspended, ReplicationReceiver)>,
     mut commands: Commands,
     receivers: Query<
-        Has<Persistent>,
+        (Has<Persistent>, Has<Connected>, Has<Suspended>),
         (
-            With<Connected>,
+            Or<(With<Connected>, With<Suspended>)>,
             With<Client>,
             With<ReplicationReceiver>,
             Without<HostClient>,
@@ -209,12 +255,16 @@ fn on_replication_disconnect(
     replicated: Query<Entity, (With<Replicated>, Without<Replicate>, Without<Persistent>)>,
     mut checkpoints: ResMut<ReplicationCheckpointMap>,
 ) {
-    // The tuple observer runs when either component is removed. Only clean up a connection that
-    // had both components immediately before this removal, and do not clean up twice if its
+    // The tuple observer runs when any component is removed. Only clean up a connection that
+    // had the receiver immediately before this removal, and do not clean up twice if its
     // receiver entity is later despawned.
-    let Ok(receiver_is_persistent) = receivers.get(trigger.entity) else {
+    let Ok((receiver_is_persistent, connected, suspended)) = receivers.get(trigger.entity) else {
         return;
     };
+    // The session is being suspended or resumed: both markers are present during the transition
+    if connected && suspended {
+        return;
+    }
 
     checkpoints.clear();
 
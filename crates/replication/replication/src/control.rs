@@ -14,6 +14,7 @@ use bevy_replicon::shared::replication::registry::ReplicationRegistry;
 use bevy_replicon::shared::replication::registry::ctx::{RemoveCtx, WriteCtx};
 use lightyear_connection::client::Disconnected;
 use lightyear_connection::host::HostClient;
+use lightyear_core::id::{PeerId, RemoteId};
 use serde::{Deserialize, Serialize};
 use tracing::trace;
 
@@ -164,6 +165,48 @@ fn emulate_controlled_on_add(
     }
 }
 
+/// Sender-side component that gives the control of the entity to a peer that is not connected
+/// yet.
+///
+/// When a link with the [`RemoteId`] `owner` connects, this is replaced with a [`ControlledBy`]
+/// component pointing to that link. This is used to restore the control of the entities loaded
+/// from a [snapshot](crate::snapshot), since the links of the clients are not saved.
+///
+/// With netcode, the [`RemoteId`] of a client is the client id of its connect token, which is
+/// random unless the token issuer pins it: the issuer must give a player the same client id when
+/// it reconnects for the control to be restored.
+#[derive(Component, Clone, Copy, PartialEq, Debug, Reflect)]
+#[reflect(Component)]
+pub struct PendingControlledBy {
+    pub owner: PeerId,
+    pub lifetime: Lifetime,
+}
+
+impl PendingControlledBy {
+    fn on_client_visibility_added(
+        trigger: On<Add, ClientVisibility>,
+        mut commands: Commands,
+        remote_id: Query<&RemoteId>,
+        pending: Query<(Entity, &PendingControlledBy)>,
+    ) {
+        let Ok(remote_id) = remote_id.get(trigger.entity) else {
+            return;
+        };
+        for (entity, pending) in pending.iter() {
+            if pending.owner == remote_id.0 {
+                trace!(?entity, owner = ?trigger.entity, "Restoring the control of the entity");
+                commands
+                    .entity(entity)
+                    .remove::<PendingControlledBy>()
+                    .insert(ControlledBy {
+                        owner: trigger.entity,
+                        lifetime: pending.lifetime,
+                    });
+            }
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default, PartialEq, Reflect)]
 pub enum Lifetime {
     #[default]
@@ -204,5 +247,6 @@ impl Plugin for ControlPlugin {
         app.add_observer(ControlledBy::handle_disconnection);
         app.add_observer(emulate_controlled_on_host_client_added);
         app.add_observer(emulate_controlled_on_add);
+        app.add_observer(PendingControlledBy::on_client_visibility_added);
     }
 }
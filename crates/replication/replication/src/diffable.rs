@@ -2,6 +2,60 @@
 //!
 //! Network delta replication is handled separately by Replicon's
 //! `Diffable` trait and `replicate_diff()` registration.
+//!
+//! # Deriving the differences of a struct
+//!
+//! With the `derive` feature, `#[derive(Diffable)]` generates a `{Name}Delta` struct that holds
+//! an `Option` for each field: only the fields that changed are present. The struct implements
+//! [`Diffable<{Name}Delta>`](Diffable) and [`DiffField`], so it can be nested in other derived
+//! structs, and its fields can be any [`DiffField`]: numbers, `bool`, `String`, `Option`,
+//! `bevy_math` vectors, `Vec` and `HashMap`.
+//!
+//! ```rust,ignore
+//! #[derive(Component, Diffable, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+//! #[diffable(replicon)]
+//! struct Player {
+//!     #[diffable(quantize = 0.01)]
+//!     position: Vec3,
+//!     health: u32,
+//!     inventory: Vec<Item>,
+//!     #[diffable(skip)]
+//!     local_cache: u32,
+//! }
+//!
+//! // network delta compression
+//! app.component::<Player>().replicate_diff();
+//! let delta = old_player.diff(&new_player);
+//! commands.entity(entity).apply_diff::<Player>(delta);
+//!
+//! // prediction correction
+//! app.component::<Player>()
+//!     .add_correction_fn::<PlayerDelta>(decay_correction::<Player>);
+//! ```
+//!
+//! The struct attributes are:
+//! - `#[diffable(replicon)]`: also implement Replicon's `Diffable` with the delta struct as
+//!   `Diff`, for components registered with `replicate_diff()`;
+//! - `#[diffable(crate = "path")]`: the path of this crate, if it is not a direct dependency.
+//!
+//! The field attributes are:
+//! - `#[diffable(skip)]`: the field is never part of the delta;
+//! - `#[diffable(replace)]`: the delta is the new value of the field, for types that don't
+//!   implement [`DiffField`], like enums;
+//! - `#[diffable(quantize = 0.01)]`: the field is a [`QuantizedField`] that changes by multiples
+//!   of the step. Smaller changes are not part of the delta, and the delta is an integer number
+//!   of steps, which compresses better than a float.
+use alloc::string::String;
+use alloc::vec::Vec;
+use bevy_math::{Vec2, Vec3};
+use bevy_platform::collections::HashMap;
+use core::fmt::Debug;
+use core::hash::{BuildHasher, Hash};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "derive")]
+pub use lightyear_replication_derive::Diffable;
 
 /// A value whose difference can be computed and applied.
 ///
@@ -17,3 +71,519 @@ pub trait Diffable<Delta = Self>: Clone {
     /// Applies `delta` to `self` (`self + delta`).
     fn apply_diff(&mut self, delta: &Delta);
 }
+
+/// Bounds of the delta types, which are sent over the network and stored in components.
+pub trait DeltaType:
+    Clone + Debug + PartialEq + Serialize + DeserializeOwned + Send + Sync + 'static
+{
+}
+
+impl<T: Clone + Debug + PartialEq + Serialize + DeserializeOwned + Send + Sync + 'static> DeltaType
+    for T
+{
+}
+
+/// A field of a struct that derives [`Diffable`].
+pub trait DiffField: Clone + Debug + PartialEq + Send + Sync + 'static {
+    type Delta: DeltaType;
+
+    /// Computes the difference from `self` to `new`, or `None` if they are equal.
+    fn diff_field(&self, new: &Self) -> Option<Self::Delta>;
+
+    /// Applies `delta` to `self`.
+    fn apply_field(&mut self, delta: &Self::Delta);
+
+    /// Returns the fraction `t` of `delta`, used to decay the prediction correction errors.
+    ///
+    /// Values that cannot be interpolated return `None`: the correction is applied at once.
+    fn scale_delta(delta: &Self::Delta, t: f32) -> Option<Self::Delta> {
+        let _ = (delta, t);
+        None
+    }
+}
+
+/// Correction function that decays the error of a derived [`Diffable`] component linearly.
+///
+/// Register it with `add_correction_fn`.
+pub fn decay_correction<C: DiffField<Delta: Default>>(
+    _start: C::Delta,
+    error: C::Delta,
+    t: f32,
+) -> C::Delta {
+    C::scale_delta(&error, t).unwrap_or_default()
+}
+
+macro_rules! impl_diff_field_integer {
+    ($($ty:ty),*) => {
+        $(
+            impl DiffField for $ty {
+                type Delta = $ty;
+
+                fn diff_field(&self, new: &Self) -> Option<Self::Delta> {
+                    (self != new).then(|| new.wrapping_sub(*self))
+                }
+
+                fn apply_field(&mut self, delta: &Self::Delta) {
+                    *self = self.wrapping_add(*delta);
+                }
+            }
+        )*
+    };
+}
+
+impl_diff_field_integer!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+macro_rules! impl_diff_field_float {
+    ($($ty:ty),*) => {
+        $(
+            impl DiffField for $ty {
+                type Delta = $ty;
+
+                fn diff_field(&self, new: &Self) -> Option<Self::Delta> {
+                    // compare the bits so that NaN doesn't produce a delta on every diff
+                    (self.to_bits() != new.to_bits()).then(|| new - self)
+                }
+
+                fn apply_field(&mut self, delta: &Self::Delta) {
+                    *self += delta;
+                }
+
+                fn scale_delta(delta: &Self::Delta, t: f32) -> Option<Self::Delta> {
+                    let scaled = delta * t as $ty;
+                    (scaled != 0.0).then_some(scaled)
+                }
+            }
+        )*
+    };
+}
+
+impl_diff_field_float!(f32, f64);
+
+macro_rules! impl_diff_field_vec {
+    ($($ty:ty),*) => {
+        $(
+            impl DiffField for $ty {
+                type Delta = $ty;
+
+                fn diff_field(&self, new: &Self) -> Option<Self::Delta> {
+                    (self != new).then(|| *new - *self)
+                }
+
+                fn apply_field(&mut self, delta: &Self::Delta) {
+                    *self += *delta;
+                }
+
+                fn scale_delta(delta: &Self::Delta, t: f32) -> Option<Self::Delta> {
+                    let scaled = *delta * t;
+                    (scaled != <$ty>::ZERO).then_some(scaled)
+                }
+            }
+        )*
+    };
+}
+
+impl_diff_field_vec!(Vec2, Vec3);
+
+macro_rules! impl_diff_field_replace {
+    ($($ty:ty),*) => {
+        $(
+            impl DiffField for $ty {
+                type Delta = $ty;
+
+                fn diff_field(&self, new: &Self) -> Option<Self::Delta> {
+                    (self != new).then(|| new.clone())
+                }
+
+                fn apply_field(&mut self, delta: &Self::Delta) {
+                    self.clone_from(delta);
+                }
+            }
+        )*
+    };
+}
+
+impl_diff_field_replace!(bool, char, String);
+
+/// The delta of an `Option` is its new value.
+impl<T: DeltaType> DiffField for Option<T> {
+    type Delta = Option<T>;
+
+    fn diff_field(&self, new: &Self) -> Option<Self::Delta> {
+        (self != new).then(|| new.clone())
+    }
+
+    fn apply_field(&mut self, delta: &Self::Delta) {
+        self.clone_from(delta);
+    }
+}
+
+/// Difference between two `Vec`s.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "T: Serialize + DeserializeOwned")]
+pub struct VecDelta<T: DiffField> {
+    /// New length, if the vector became shorter
+    pub truncate: Option<usize>,
+    /// Elements that changed, among the ones that exist in both vectors
+    pub changed: Vec<(usize, T::Delta)>,
+    /// Elements added at the end of the vector
+    pub appended: Vec<T>,
+}
+
+impl<T: DiffField + Serialize + DeserializeOwned> DiffField for Vec<T> {
+    type Delta = VecDelta<T>;
+
+    fn diff_field(&self, new: &Self) -> Option<Self::Delta> {
+        let common = self.len().min(new.len());
+        let delta = VecDelta {
+            truncate: (new.len() < self.len()).then_some(new.len()),
+            changed: self[..common]
+                .iter()
+                .zip(&new[..common])
+                .enumerate()
+                .filter_map(|(i, (old, new))| old.diff_field(new).map(|delta| (i, delta)))
+                .collect(),
+            appended: new[common..].to_vec(),
+        };
+        (delta.truncate.is_some() || !delta.changed.is_empty() || !delta.appended.is_empty())
+            .then_some(delta)
+    }
+
+    fn apply_field(&mut self, delta: &Self::Delta) {
+        if let Some(len) = delta.truncate {
+            self.truncate(len);
+        }
+        for (i, element_delta) in &delta.changed {
+            if let Some(element) = self.get_mut(*i) {
+                element.apply_field(element_delta);
+            }
+        }
+        self.extend_from_slice(&delta.appended);
+    }
+
+    /// Only the changes of the existing elements are scaled: the elements that are added or
+    /// removed are corrected at once.
+    fn scale_delta(delta: &Self::Delta, t: f32) -> Option<Self::Delta> {
+        let changed: Vec<_> = delta
+            .changed
+            .iter()
+            .filter_map(|(i, element_delta)| T::scale_delta(element_delta, t).map(|d| (*i, d)))
+            .collect();
+        (!changed.is_empty()).then_some(VecDelta {
+            truncate: None,
+            changed,
+            appended: Vec::new(),
+        })
+    }
+}
+
+/// Difference between two `HashMap`s.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "K: Serialize + DeserializeOwned, V: Serialize + DeserializeOwned")]
+pub struct MapDelta<K, V: DiffField> {
+    pub removed: Vec<K>,
+    pub changed: Vec<(K, V::Delta)>,
+    pub inserted: Vec<(K, V)>,
+}
+
+impl<K, V, S> DiffField for HashMap<K, V, S>
+where
+    K: Eq + Hash + Clone + Debug + Serialize + DeserializeOwned + Send + Sync + 'static,
+    V: DiffField + Serialize + DeserializeOwned,
+    S: BuildHasher + Clone + Send + Sync + 'static,
+{
+    type Delta = MapDelta<K, V>;
+
+    fn diff_field(&self, new: &Self) -> Option<Self::Delta> {
+        let mut delta = MapDelta {
+            removed: self
+                .keys()
+                .filter(|key| !new.contains_key(*key))
+                .cloned()
+                .collect(),
+            changed: Vec::new(),
+            inserted: Vec::new(),
+        };
+        for (key, value) in new.iter() {
+            match self.get(key) {
+                Some(old) => {
+                    if let Some(value_delta) = old.diff_field(value) {
+                        delta.changed.push((key.clone(), value_delta));
+                    }
+                }
+                None => delta.inserted.push((key.clone(), value.clone())),
+            }
+        }
+        (!delta.removed.is_empty() || !delta.changed.is_empty() || !delta.inserted.is_empty())
+            .then_some(delta)
+    }
+
+    fn apply_field(&mut self, delta: &Self::Delta) {
+        for key in &delta.removed {
+            self.remove(key);
+        }
+        for (key, value_delta) in &delta.changed {
+            if let Some(value) = self.get_mut(key) {
+                value.apply_field(value_delta);
+            }
+        }
+        for (key, value) in &delta.inserted {
+            self.insert(key.clone(), value.clone());
+        }
+    }
+
+    /// Only the changes of the existing values are scaled: the entries that are added or
+    /// removed are corrected at once.
+    fn scale_delta(delta: &Self::Delta, t: f32) -> Option<Self::Delta> {
+        let changed: Vec<_> = delta
+            .changed
+            .iter()
+            .filter_map(|(key, value_delta)| {
+                V::scale_delta(value_delta, t).map(|d| (key.clone(), d))
+            })
+            .collect();
+        (!changed.is_empty()).then_some(MapDelta {
+            removed: Vec::new(),
+            changed,
+            inserted: Vec::new(),
+        })
+    }
+}
+
+/// A field whose delta is an integer number of quantization steps.
+///
+/// The values are snapped to a multiple of the step when a delta is applied, so the sender and
+/// the receiver hold exactly the same value after applying the same deltas.
+pub trait QuantizedField: Clone + Debug + PartialEq + Send + Sync + 'static {
+    type Steps: DeltaType;
+
+    /// Number of steps between `self` and `new`, or `None` if they round to the same step.
+    fn diff_steps(&self, new: &Self, step: f32) -> Option<Self::Steps>;
+
+    fn apply_steps(&mut self, steps: &Self::Steps, step: f32);
+
+    /// Returns the fraction `t` of `steps`, rounded towards zero.
+    fn scale_steps(steps: &Self::Steps, t: f32) -> Option<Self::Steps>;
+}
+
+/// Index of the step closest to `value`. `f32::round` is not available in `core`.
+fn to_steps(value: f64, step: f64) -> i64 {
+    let steps = value / step;
+    if steps >= 0.0 {
+        (steps + 0.5) as i64
+    } else {
+        (steps - 0.5) as i64
+    }
+}
+
+impl QuantizedField for f32 {
+    type Steps = i32;
+
+    fn diff_steps(&self, new: &Self, step: f32) -> Option<Self::Steps> {
+        let steps = to_steps(*new as f64, step as f64) - to_steps(*self as f64, step as f64);
+        (steps != 0).then_some(steps as i32)
+    }
+
+    fn apply_steps(&mut self, steps: &Self::Steps, step: f32) {
+        let current = to_steps(*self as f64, step as f64);
+        *self = ((current + *steps as i64) as f64 * step as f64) as f32;
+    }
+
+    fn scale_steps(steps: &Self::Steps, t: f32) -> Option<Self::Steps> {
+        let scaled = (*steps as f32 * t) as i32;
+        (scaled != 0).then_some(scaled)
+    }
+}
+
+impl QuantizedField for f64 {
+    type Steps = i64;
+
+    fn diff_steps(&self, new: &Self, step: f32) -> Option<Self::Steps> {
+        let steps = to_steps(*new, step as f64) - to_steps(*self, step as f64);
+        (steps != 0).then_some(steps)
+    }
+
+    fn apply_steps(&mut self, steps: &Self::Steps, step: f32) {
+        let current = to_steps(*self, step as f64);
+        *self = (current + steps) as f64 * step as f64;
+    }
+
+    fn scale_steps(steps: &Self::Steps, t: f32) -> Option<Self::Steps> {
+        let scaled = (*steps as f64 * t as f64) as i64;
+        (scaled != 0).then_some(scaled)
+    }
+}
+
+macro_rules! impl_quantized_field_vec {
+    ($($ty:ty => $n:literal),*) => {
+        $(
+            impl QuantizedField for $ty {
+                type Steps = [i32; $n];
+
+                fn diff_steps(&self, new: &Self, step: f32) -> Option<Self::Steps> {
+                    let (old, new) = (self.to_array(), new.to_array());
+                    let mut steps = [0; $n];
+                    for i in 0..$n {
+                        steps[i] = old[i].diff_steps(&new[i], step).unwrap_or_default();
+                    }
+                    (steps != [0; $n]).then_some(steps)
+                }
+
+                fn apply_steps(&mut self, steps: &Self::Steps, step: f32) {
+                    let mut values = self.to_array();
+                    for i in 0..$n {
+                        values[i].apply_steps(&steps[i], step);
+                    }
+                    *self = <$ty>::from_array(values);
+                }
+
+                fn scale_steps(steps: &Self::Steps, t: f32) -> Option<Self::Steps> {
+                    let scaled = steps.map(|s| f32::scale_steps(&s, t).unwrap_or_default());
+                    (scaled != [0; $n]).then_some(scaled)
+                }
+            }
+        )*
+    };
+}
+
+impl_quantized_field_vec!(Vec2 => 2, Vec3 => 3);
+
+#[doc(hidden)]
+pub mod __private {
+    pub use bevy_ecs;
+    pub use bevy_replicon;
+    pub use serde;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn vec_delta() {
+        let old = vec![1.0f32, 2.0, 3.0];
+        let new = vec![1.0f32, 5.0];
+        let delta = old.diff_field(&new).unwrap();
+        assert_eq!(delta.truncate, Some(2));
+        assert_eq!(delta.changed, [(1, 3.0)]);
+        let mut value = old.clone();
+        value.apply_field(&delta);
+        assert_eq!(value, new);
+
+        let longer = vec![1.0f32, 5.0, 6.0, 7.0];
+        let delta = new.diff_field(&longer).unwrap();
+        assert!(delta.changed.is_empty());
+        assert_eq!(delta.appended, [6.0, 7.0]);
+        assert!(new.diff_field(&new.clone()).is_none());
+    }
+
+    #[test]
+    fn map_delta() {
+        let old: HashMap<u32, i32> = [(1, 10), (2, 20), (3, 30)].into_iter().collect();
+        let new: HashMap<u32, i32> = [(1, 10), (2, 25), (4, 40)].into_iter().collect();
+        let delta = old.diff_field(&new).unwrap();
+        assert_eq!(delta.removed, [3]);
+        assert_eq!(delta.changed, [(2, 5)]);
+        assert_eq!(delta.inserted, [(4, 40)]);
+        let mut value = old.clone();
+        value.apply_field(&delta);
+        assert_eq!(value, new);
+    }
+
+    #[test]
+    fn quantized_delta() {
+        let step = 0.01;
+        let old = Vec3::new(1.0, 2.0, 3.0);
+        // changes smaller than half a step are ignored
+        assert!(old.diff_steps(&Vec3::new(1.004, 2.0, 3.0), step).is_none());
+
+        let new = Vec3::new(1.5, 2.0, -3.0);
+        let steps = old.diff_steps(&new, step).unwrap();
+        assert_eq!(steps, [50, 0, -600]);
+        let mut value = old;
+        value.apply_steps(&steps, step);
+        assert!(value.abs_diff_eq(new, 1e-5));
+        assert_eq!(f32::scale_steps(&50, 0.5), Some(25));
+        assert_eq!(f32::scale_steps(&1, 0.5), None);
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derive_diffable() {
+        #[derive(Diffable, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+        #[diffable(crate = "crate")]
+        struct Stats {
+            strength: u32,
+            speed: f32,
+        }
+
+        #[derive(Diffable, Clone, Debug, Default, PartialEq)]
+        #[diffable(crate = "crate", replicon)]
+        struct Player {
+            #[diffable(quantize = 0.01)]
+            position: Vec3,
+            health: i32,
+            stats: Stats,
+            inventory: Vec<Stats>,
+            #[diffable(replace)]
+            state: Option<u8>,
+            #[diffable(skip)]
+            local: u32,
+        }
+
+        let old = Player {
+            position: Vec3::new(1.0, 2.0, 3.0),
+            health: 100,
+            stats: Stats {
+                strength: 5,
+                speed: 1.0,
+            },
+            inventory: vec![Stats::default()],
+            state: None,
+            local: 1,
+        };
+        let mut new = old.clone();
+        new.health = 90;
+        new.stats.speed = 1.5;
+        new.local = 2;
+        let delta = old.diff(&new);
+        assert_eq!(
+            delta,
+            PlayerDelta {
+                health: Some(-10),
+                stats: Some(StatsDelta {
+                    speed: Some(0.5),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }
+        );
+        let mut value = old.clone();
+        value.apply_diff(&delta);
+        assert_eq!(value.stats, new.stats);
+        assert_eq!(value.health, new.health);
+        // skipped fields are not part of the delta
+        assert_eq!(value.local, 1);
+        assert_eq!(old.diff(&old.clone()), PlayerDelta::default());
+
+        // Replicon applies the same delta
+        let mut value = old.clone();
+        bevy_replicon::shared::replication::diff::Diffable::apply_diff(&mut value, &delta).unwrap();
+        assert_eq!(value.stats, new.stats);
+        assert_eq!(value.health, new.health);
+        assert_eq!(value.local, 1);
+
+        // only the continuous fields are decayed by the correction
+        let decayed = decay_correction::<Player>(PlayerDelta::default(), delta, 0.5);
+        assert_eq!(
+            decayed,
+            PlayerDelta {
+                stats: Some(StatsDelta {
+                    speed: Some(0.25),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use crate::diffable::Diffable;
+use crate::visibility::spatial::SpatialPosition;
 use avian2d::prelude::{Position, Rotation};
+use bevy_math::Vec3;
 
 impl Diffable<Position> for Position {
     fn base_value() -> Self {
@@ -28,3 +30,9 @@ impl Diffable<Rotation> for Rotation {
         *self = self.add_angle_fast(delta.as_radians());
     }
 }
+
+impl SpatialPosition for Position {
+    fn spatial_position(&self) -> Vec3 {
+        self.0.extend(0.0)
+    }
+}
@@ -1,5 +1,7 @@
 use crate::diffable::Diffable;
+use crate::visibility::spatial::SpatialPosition;
 use avian3d::prelude::{Position, Rotation};
+use bevy_math::Vec3;
 
 impl Diffable<Self> for Position {
     fn base_value() -> Self {
@@ -28,3 +30,9 @@ impl Diffable<Self> for Rotation {
         self.0 *= delta.0;
     }
 }
+
+impl SpatialPosition for Position {
+    fn spatial_position(&self) -> Vec3 {
+        self.0
+    }
+}
@@ -1,4 +1,5 @@
 use crate::diffable::Diffable;
+use crate::visibility::spatial::SpatialPosition;
 use bevy_math::{Isometry2d, Isometry3d, Quat, Rot2, Vec3};
 use bevy_transform::prelude::Transform;
 
@@ -53,3 +54,9 @@ impl Diffable<Isometry3d> for Transform {
         self.rotation *= delta.rotation;
     }
 }
+
+impl SpatialPosition for Transform {
+    fn spatial_position(&self) -> Vec3 {
+        self.translation
+    }
+}
@@ -58,12 +58,37 @@
 //! Visibility changes propagate through [`ReplicateLikeChildren`] so that
 //! hiding a parent also hides its replicated descendants.
 //!
-//! For interest management based on spatial regions, see [`RoomPlugin`].
+//! For interest management based on spatial regions, see [`RoomPlugin`]. To show entities
+//! based on their distance to each client, see [`SpatialGridPlugin`].
+//!
+//! ## Priority
+//!
+//! When a sender can't replicate every entity each tick, [`ReplicationPriorityPlugin`] sends the
+//! most relevant entities first using a per-client priority that accumulates while an entity's
+//! updates are held back.
 //!
 //! ## Control
 //!
 //! [`ControlledBy`] marks which link entity "owns" a replicated entity.
 //!
+//! ## Authority
+//!
+//! With the [`AuthorityPlugin`], the server can transfer the authority over a replicated entity
+//! to a client and take it back later without despawning it. The client with authority has the
+//! [`HasAuthority`] marker and replicates the entity to the server. See the [`authority`] module.
+//!
+//! ## Snapshots
+//!
+//! On the server, [`save_snapshot`] serializes the replicated entities and [`load_snapshot`]
+//! restores them into a fresh server, for example to recover from a crash.
+//!
+//! ## Spectators
+//!
+//! A client link with the [`Spectator`] component watches the game without playing: it doesn't
+//! predict entities, and its replication can be delayed on the server, up to the
+//! `max_spectator_delay` of the [`LightyearRepliconServerBackend`]. It can follow the view of a
+//! player with [`Spectating`]. See the [`spectator`] module.
+//!
 //! ## Pre-spawning
 //!
 //! [`PreSpawned`] allows both client and server to spawn the same entity
@@ -90,8 +115,16 @@
 //! [`VisibilityExt::lose_visibility_retained`]: crate::visibility::immediate::VisibilityExt::lose_visibility_retained
 //! [`VisibilityExt::lose_visibility_always_present`]: crate::visibility::immediate::VisibilityExt::lose_visibility_always_present
 //! [`RoomPlugin`]: crate::visibility::room::RoomPlugin
+//! [`SpatialGridPlugin`]: crate::visibility::spatial::SpatialGridPlugin
+//! [`ReplicationPriorityPlugin`]: crate::priority::ReplicationPriorityPlugin
 //! [`ControlledBy`]: crate::control::ControlledBy
+//! [`AuthorityPlugin`]: crate::authority::AuthorityPlugin
+//! [`HasAuthority`]: crate::authority::HasAuthority
 //! [`PreSpawned`]: crate::prespawn::PreSpawned
+//! [`save_snapshot`]: crate::snapshot::save_snapshot
+//! [`Spectator`]: crate::spectator::Spectator
+//! [`Spectating`]: crate::spectator::Spectating
+//! [`load_snapshot`]: crate::snapshot::load_snapshot
 #![no_std]
 
 extern crate alloc;
@@ -106,6 +139,7 @@ use bevy_ecs::prelude::SystemSet;
 #[cfg(feature = "server")]
 pub mod server;
 
+pub mod authority;
 pub mod channels;
 pub mod checkpoint;
 #[cfg(feature = "client")]
@@ -117,9 +151,14 @@ pub mod diffable;
 pub mod hierarchy;
 pub mod metadata;
 pub mod prespawn;
+pub mod priority;
 pub mod receive;
 pub mod registry;
 pub mod send;
+#[cfg(feature = "server")]
+pub mod snapshot;
+#[cfg(feature = "server")]
+pub mod spectator;
 
 pub mod visibility;
 
@@ -134,8 +173,14 @@ pub mod prelude {
     pub use bevy_replicon::server::{PriorityMap, ReplicatePriority};
 
     pub use crate::ReplicationSystems;
+    pub use crate::authority::{
+        AuthorityChanged, AuthorityPeer, AuthorityPlugin, AuthorityRequestPolicy,
+        AuthorityRequested, HasAuthority, ReleaseAuthority, RequestAuthority, TransferAuthority,
+    };
     pub use crate::checkpoint::ReplicationCheckpointMap;
-    pub use crate::control::{Controlled, ControlledBy, ControlledSend, Lifetime};
+    pub use crate::control::{
+        Controlled, ControlledBy, ControlledSend, Lifetime, PendingControlledBy,
+    };
     pub use crate::deferred_entity::DeferredEntityCommands;
     pub use crate::diff_history::HistoryDiffReceiver;
     pub use crate::hierarchy::{DisableReplicateHierarchy, ReplicateLike};
@@ -143,12 +188,24 @@ pub mod prelude {
     pub use crate::prespawn::PreSpawned;
     pub use crate::receive::{Persistent, ReplicationReceiver};
     pub use crate::send::{Replicate, ReplicatedFrom, Replicating, ReplicationSender};
+    #[cfg(feature = "server")]
+    pub use crate::snapshot::{SnapshotError, load_snapshot, save_entities, save_snapshot};
+    #[cfg(all(feature = "server", feature = "std"))]
+    pub use crate::snapshot::{load_snapshot_from_file, save_snapshot_to_file};
+    #[cfg(feature = "server")]
+    pub use crate::spectator::{Spectating, Spectator};
 
+    pub use crate::priority::{
+        RelevanceFn, ReplicationBudget, ReplicationPriority, ReplicationPriorityPlugin,
+    };
     pub use crate::registry::ComponentRegistry;
     pub use crate::registry::TransformLinearInterpolation;
     pub use crate::registry::replication::{AppComponentExt, ComponentRegistrator};
     pub use crate::visibility::immediate::{NetworkVisibilityPlugin, VisibilityExt};
     pub use crate::visibility::room::{RoomAllocator, RoomId, RoomPlugin, Rooms};
+    pub use crate::visibility::spatial::{
+        SpatialGrid, SpatialGridPlugin, SpatialInterest, SpatialPosition, SpatialViewer,
+    };
 
     pub use crate::diffable::Diffable;
 
@@ -159,6 +216,7 @@ pub mod prelude {
 
     #[cfg(feature = "client")]
     pub mod client {
+        pub use crate::client::PremappedEntities;
         pub use bevy_replicon::prelude::Remote;
         pub use bevy_replicon::prelude::Remote as Replicated;
     }
@@ -242,7 +300,22 @@ impl PluginGroup for LightyearRepliconBackend {
 }
 
 #[cfg(feature = "server")]
-pub struct LightyearRepliconServerBackend;
+#[derive(Default)]
+pub struct LightyearRepliconServerBackend {
+    /// Longest replication delay of a [`Spectator`](spectator::Spectator), zero by default.
+    ///
+    /// Longer delays are reduced to this one. Replicon keeps the unacknowledged mutations this
+    /// much longer before treating them as lost, since the spectators acknowledge them late.
+    pub max_spectator_delay: core::time::Duration,
+}
+
+#[cfg(feature = "server")]
+impl LightyearRepliconServerBackend {
+    pub fn with_max_spectator_delay(mut self, max_spectator_delay: core::time::Duration) -> Self {
+        self.max_spectator_delay = max_spectator_delay;
+        self
+    }
+}
 
 #[cfg(feature = "server")]
 impl Plugin for LightyearRepliconServerBackend {
@@ -253,10 +326,16 @@ impl Plugin for LightyearRepliconServerBackend {
         //   not send any further corrections because nothing changed.
         // - it adds a `ServerMutateTicks` resource on the receiver that keeps track of the ticks
         //   where the receiver received any messages.
+        //
+        // The mutations acknowledged by a delayed `Spectator` come back up to
+        // `max_spectator_delay` late, so they are kept that much longer before being considered
+        // lost.
+        let defaults = bevy_replicon::server::ServerPlugin::default();
         app.add_plugins(bevy_replicon::server::ServerPlugin {
             tick_schedule: None,
             track_mutate_messages: cfg!(any(feature = "prediction", feature = "interpolation")),
-            ..Default::default()
+            mutations_timeout: defaults.mutations_timeout + self.max_spectator_delay,
+            ..defaults
         });
         app.add_plugins(server::RepliconServerPlugin);
         app.add_plugins(send::SendPlugin);
@@ -264,6 +343,9 @@ impl Plugin for LightyearRepliconServerBackend {
         app.add_plugins(hierarchy::HierarchyPlugin);
         app.add_plugins(hierarchy::HierarchySendPlugin::<bevy_ecs::prelude::ChildOf>::default());
         app.add_plugins(visibility::immediate::NetworkVisibilityPlugin);
+        app.add_plugins(spectator::SpectatorPlugin {
+            max_delay: self.max_spectator_delay,
+        });
         app.add_observer(send::handle_new_client_visibility);
     }
 }
@@ -1,4 +1,6 @@
 use crate::prelude::ReplicationSender;
+#[cfg(feature = "server")]
+use crate::spectator::{MaxSpectatorDelay, Spectator};
 use bevy_app::prelude::*;
 use bevy_ecs::prelude::*;
 use bevy_time::{Timer, TimerMode};
@@ -50,17 +52,23 @@ impl Default for ReplicationMetadata {
 pub struct SenderMetadata {
     /// How often the sender emits replication updates.
     pub send_interval: PositiveTickDelta,
+    /// How long the sender holds the replication updates before sending them to this receiver,
+    /// for example because the receiver is a `Spectator`.
+    pub replication_delay: PositiveTickDelta,
     /// The link entity that sent this metadata.
     pub sender_entity: Entity,
 }
 
 impl ToBytes for SenderMetadata {
     fn bytes_len(&self) -> usize {
-        self.send_interval.bytes_len() + self.sender_entity.bytes_len()
+        self.send_interval.bytes_len()
+            + self.replication_delay.bytes_len()
+            + self.sender_entity.bytes_len()
     }
 
     fn to_bytes(&self, buffer: &mut impl WriteInteger) -> Result<(), SerializationError> {
         self.send_interval.to_bytes(buffer)?;
+        self.replication_delay.to_bytes(buffer)?;
         self.sender_entity.to_bytes(buffer)?;
         Ok(())
     }
@@ -70,9 +78,11 @@ impl ToBytes for SenderMetadata {
         Self: Sized,
     {
         let send_interval = PositiveTickDelta::from_bytes(buffer)?;
+        let replication_delay = PositiveTickDelta::from_bytes(buffer)?;
         let sender_entity = Entity::from_bytes(buffer)?;
         Ok(Self {
             send_interval,
+            replication_delay,
             sender_entity,
         })
     }
@@ -81,6 +91,9 @@ impl ToBytes for SenderMetadata {
 /// Default reliable channel to replicate metadata about the Sender or the connection
 pub struct MetadataChannel;
 
+pub(crate) type MetadataSenders<'w, 's> =
+    Query<'w, 's, (Entity, &'static mut EventSender<SenderMetadata>), With<Connected>>;
+
 /// Send a message containing metadata about the sender
 fn send_sender_metadata(
     // NOTE: it's important to trigger on both Add<Connected> and Add<ReplicationSender> because the ClientOf could be
@@ -88,13 +101,42 @@ fn send_sender_metadata(
     trigger: On<Add, (Connected, ReplicationSender)>,
     metadata: Res<ReplicationMetadata>,
     tick_duration: Res<TickDuration>,
-    mut query: Query<(Entity, &mut EventSender<SenderMetadata>), With<Connected>>,
+    #[cfg(feature = "server")] spectators: Query<&Spectator>,
+    #[cfg(feature = "server")] max_spectator_delay: Option<Res<MaxSpectatorDelay>>,
+    query: MetadataSenders<'_, '_>,
+) {
+    #[cfg(feature = "server")]
+    let replication_delay = spectators
+        .get(trigger.entity)
+        .ok()
+        .zip(max_spectator_delay)
+        .map_or(Duration::ZERO, |(spectator, max_delay)| {
+            spectator.effective_delay(max_delay.0)
+        });
+    #[cfg(not(feature = "server"))]
+    let replication_delay = Duration::ZERO;
+    send_metadata(
+        trigger.entity,
+        &metadata,
+        &tick_duration,
+        replication_delay,
+        query,
+    );
+}
+
+pub(crate) fn send_metadata(
+    entity: Entity,
+    metadata: &ReplicationMetadata,
+    tick_duration: &TickDuration,
+    replication_delay: Duration,
+    mut query: MetadataSenders<'_, '_>,
 ) {
     let send_interval = metadata.timer.duration();
     let send_interval_delta = TickDelta::from_duration(send_interval, tick_duration.0);
-    if let Ok((sender_entity, mut trigger_sender)) = query.get_mut(trigger.entity) {
+    if let Ok((sender_entity, mut trigger_sender)) = query.get_mut(entity) {
         let metadata = SenderMetadata {
             send_interval: send_interval_delta.into(),
+            replication_delay: TickDelta::from_duration(replication_delay, tick_duration.0).into(),
             sender_entity,
         };
         trigger_sender.trigger::<MetadataChannel>(metadata);
@@ -0,0 +1,565 @@
+//! Per-client replication priority with accumulation.
+//!
+//! Channel priorities (see [`ChannelSettings::priority`]) and the static priority of
+//! [`replicate_with_priority`] apply to every client in the same way. This module adds a priority
+//! per (entity, client) pair, which lets a sender with a limited budget send the most relevant
+//! entities first.
+//!
+//! Each send tick, every entity with a [`ReplicationPriority`] adds
+//! `base priority * relevance(entity, client)` to its accumulated priority for each sender that has
+//! a [`ReplicationBudget`]. Entities are then picked by decreasing accumulated priority until their
+//! estimated size fills the sender's byte budget for that tick. The selected entities are
+//! replicated and their accumulated priority is reset to zero; the updates of the other entities
+//! are held back until their accumulated priority is high enough. Entities that are far away or
+//! irrelevant are therefore still updated, just less often.
+//!
+//! By default the byte budget follows the bandwidth of the link's [`Transport`]: the rate of its
+//! congestion controller or the bandwidth quota of its [`PriorityManager`], multiplied by the
+//! replication send interval. The size of an entity is estimated from the
+//! [size hints](crate::registry::replication::ComponentRegistration::with_size_hint) of its
+//! replicated components, once per archetype.
+//!
+//! The relevance is computed by the [`RelevanceFn`] provided to the [`ReplicationPriorityPlugin`],
+//! for example from the distance between the entity and the client's player, whether the entity
+//! is in the client's view cone, or whether it recently damaged the client.
+//!
+//! Held-back entities are still spawned on the client, but their updates are paused: once they
+//! are selected again, the client receives their latest state.
+//!
+//! ```rust,ignore
+//! fn distance_relevance(world: &World, entity: Entity, sender: Entity) -> f32 {
+//!     let Some(player) = world.get::<PlayerOf>(sender) else {
+//!         return 1.0;
+//!     };
+//!     let (Some(a), Some(b)) = (world.get::<Transform>(entity), world.get::<Transform>(player.0)) else {
+//!         return 1.0;
+//!     };
+//!     1.0 / (1.0 + a.translation.distance(b.translation))
+//! }
+//!
+//! app.add_plugins(ReplicationPriorityPlugin::new(distance_relevance));
+//! // use at most 80% of the link's bandwidth for prioritized entities
+//! commands.entity(sender_link).insert(ReplicationBudget::new(0.8));
+//! commands.spawn((Replicate::to_clients(NetworkTarget::All), ReplicationPriority::default()));
+//! ```
+//!
+//! [`ChannelSettings::priority`]: lightyear_transport::channel::builder::ChannelSettings::priority
+//! [`Transport`]: lightyear_transport::channel::builder::Transport
+//! [`PriorityManager`]: lightyear_transport::packet::priority_manager::PriorityManager
+//! [`replicate_with_priority`]: crate::registry::replication::ComponentRegistration::replicate_with_priority
+use crate::ReplicationSystems;
+use crate::hierarchy::ReplicateLikeChildren;
+use crate::metadata::ReplicationMetadata;
+use crate::registry::ComponentRegistry;
+use crate::send::Replicating;
+use alloc::vec::Vec;
+use bevy_app::{App, Plugin, PostUpdate};
+use bevy_derive::Deref;
+use bevy_ecs::archetype::{Archetype, ArchetypeId};
+use bevy_ecs::entity::{EntityHashMap, EntityHashSet};
+use bevy_ecs::prelude::*;
+use bevy_platform::collections::HashMap;
+use bevy_reflect::Reflect;
+use bevy_replicon::prelude::ScopeLifetime;
+use bevy_replicon::server::server_tick::ServerTick;
+use bevy_replicon::server::visibility::client_visibility::ClientVisibility;
+use bevy_replicon::server::visibility::filters_mask::FilterBit;
+use bevy_replicon::server::visibility::registry::FilterRegistry;
+use bevy_replicon::shared::replication::registry::ReplicationRegistry;
+use core::time::Duration;
+use lightyear_core::tick::TickDuration;
+use lightyear_transport::prelude::Transport;
+#[allow(unused_imports)]
+use tracing::{debug, trace};
+
+/// Function returning how relevant `entity` is to the client of the `sender` link entity.
+///
+/// The value multiplies the entity's [`ReplicationPriority`] every send tick. Negative values
+/// are treated as zero.
+pub type RelevanceFn = fn(world: &World, entity: Entity, sender: Entity) -> f32;
+
+/// Base priority of a replicated entity, used by the [`ReplicationPriorityPlugin`].
+///
+/// Only entities with this component are held back when a sender's [`ReplicationBudget`] is
+/// exceeded; other entities are always replicated.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct ReplicationPriority(pub f32);
+
+impl Default for ReplicationPriority {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+/// Bytes added to the estimated size of an entity for its header in the replication message.
+const ENTITY_HEADER_BYTES: usize = 4;
+/// Bytes added to the estimated size of an entity for the header of each of its components.
+const COMPONENT_HEADER_BYTES: usize = 1;
+
+/// Component added on a [`ReplicationSender`](crate::send::ReplicationSender) link entity to limit
+/// the number of bytes of prioritized entities replicated to it each send tick.
+#[derive(Component, Debug, Clone)]
+pub struct ReplicationBudget {
+    /// Fixed number of bytes of entities with [`ReplicationPriority`] replicated each send tick.
+    ///
+    /// If `None`, the budget is [`bandwidth_share`](Self::bandwidth_share) of the bandwidth of the
+    /// link's [`Transport`], and prioritized entities are not limited if the transport's bandwidth
+    /// is not limited.
+    pub max_bytes: Option<usize>,
+    /// Fraction of the [`Transport`] bandwidth that prioritized entities can use, so that the
+    /// rest is left for messages, inputs and the other replicated entities.
+    pub bandwidth_share: f32,
+    /// Accumulated priority of each entity for this sender.
+    accumulated: EntityHashMap<f32>,
+}
+
+impl ReplicationBudget {
+    /// Budget that uses `bandwidth_share` of the bandwidth of the link's [`Transport`].
+    pub fn new(bandwidth_share: f32) -> Self {
+        Self {
+            max_bytes: None,
+            bandwidth_share,
+            accumulated: EntityHashMap::default(),
+        }
+    }
+
+    /// Budget of `max_bytes` bytes per send tick, independent of the transport's bandwidth.
+    pub fn fixed(max_bytes: usize) -> Self {
+        Self {
+            max_bytes: Some(max_bytes),
+            ..Self::new(1.0)
+        }
+    }
+
+    /// Priority accumulated by the entity since it was last replicated to this sender.
+    pub fn accumulated_priority(&self, entity: Entity) -> f32 {
+        self.accumulated.get(&entity).copied().unwrap_or_default()
+    }
+
+    /// Number of bytes available this send tick, or `None` if the budget is unlimited.
+    fn bytes_per_send(
+        &self,
+        transport: Option<&Transport>,
+        send_interval: Duration,
+    ) -> Option<usize> {
+        if self.max_bytes.is_some() {
+            return self.max_bytes;
+        }
+        let bandwidth = transport?.bandwidth()?;
+        Some(
+            (bandwidth as f32 * self.bandwidth_share.max(0.0) * send_interval.as_secs_f32())
+                as usize,
+        )
+    }
+
+    /// Accumulate the priority of every `(entity, priority, size)` candidate, and return the
+    /// entities selected for this send tick.
+    ///
+    /// The entity with the highest priority is always selected, even if it is larger than the
+    /// budget, so that large entities are not starved.
+    fn select(
+        &mut self,
+        candidates: &[(Entity, f32, usize)],
+        max_bytes: Option<usize>,
+    ) -> Vec<Entity> {
+        let mut accumulated = EntityHashMap::<f32>::default();
+        let mut sizes = EntityHashMap::<usize>::default();
+        for (entity, priority, size) in candidates {
+            let previous = self.accumulated.get(entity).copied().unwrap_or_default();
+            accumulated.insert(*entity, previous + priority.max(0.0));
+            sizes.insert(*entity, *size);
+        }
+        // entities that are not candidates anymore are dropped
+        self.accumulated = accumulated;
+
+        let mut ordered: Vec<(Entity, f32)> = self
+            .accumulated
+            .iter()
+            .filter(|(_, priority)| **priority > 0.0)
+            .map(|(entity, priority)| (*entity, *priority))
+            .collect();
+        ordered.sort_unstable_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let mut selected = Vec::new();
+        let mut remaining = max_bytes.unwrap_or(usize::MAX);
+        for (entity, _) in ordered {
+            let size = sizes[&entity];
+            if size <= remaining || selected.is_empty() {
+                remaining = remaining.saturating_sub(size);
+                selected.push(entity);
+            }
+        }
+        for entity in &selected {
+            self.accumulated.insert(*entity, 0.0);
+        }
+        selected
+    }
+}
+
+/// Resource holding the [`RelevanceFn`] used by the [`ReplicationPriorityPlugin`].
+#[derive(Resource, Clone, Copy)]
+pub struct PriorityRelevance(pub RelevanceFn);
+
+/// Visibility scope used to pause the updates of the entities that were not selected.
+#[doc(hidden)]
+#[derive(Resource, Deref)]
+pub struct PriorityBit(FilterBit);
+
+impl FromWorld for PriorityBit {
+    fn from_world(world: &mut World) -> Self {
+        let bit = world.resource_scope(|world, mut filter_registry: Mut<FilterRegistry>| {
+            world.resource_scope(|world, mut registry: Mut<ReplicationRegistry>| {
+                filter_registry.register_scope::<Entity>(
+                    world,
+                    &mut registry,
+                    ScopeLifetime::AlwaysPresent,
+                )
+            })
+        });
+        Self(bit)
+    }
+}
+
+/// Plugin that limits the entities replicated to each sender with a [`ReplicationBudget`] based on
+/// their accumulated priority.
+///
+/// See the [module-level documentation](self).
+pub struct ReplicationPriorityPlugin {
+    pub relevance: RelevanceFn,
+}
+
+impl ReplicationPriorityPlugin {
+    pub fn new(relevance: RelevanceFn) -> Self {
+        Self { relevance }
+    }
+}
+
+impl Default for ReplicationPriorityPlugin {
+    fn default() -> Self {
+        Self::new(|_, _, _| 1.0)
+    }
+}
+
+impl Plugin for ReplicationPriorityPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PriorityRelevance(self.relevance));
+        app.init_resource::<PriorityBit>();
+        app.add_observer(clear_priority_on_removal);
+        app.add_observer(clear_priority_on_despawn);
+        app.add_observer(clear_priority_on_budget_removal);
+        app.add_systems(
+            PostUpdate,
+            compute_priority_candidates
+                .pipe(select_prioritized_entities)
+                .run_if(resource_changed::<ServerTick>)
+                .before(ReplicationSystems::Send),
+        );
+    }
+}
+
+/// Prioritized entities and byte budget of a sender for the current send tick.
+struct SenderCandidates {
+    sender: Entity,
+    max_bytes: Option<usize>,
+    /// `(entity, priority, estimated size)` of every prioritized entity
+    candidates: Vec<(Entity, f32, usize)>,
+}
+
+/// Compute the priority of every prioritized entity for each sender.
+///
+/// The [`RelevanceFn`] reads the world, so this only has read access and doesn't block the
+/// systems that read the world in parallel.
+fn compute_priority_candidates(
+    world: &World,
+    relevance: Res<PriorityRelevance>,
+    registry: Option<Res<ComponentRegistry>>,
+    entities: Query<(Entity, &ReplicationPriority), With<Replicating>>,
+    senders: Query<(Entity, &ReplicationBudget, Option<&Transport>), With<ClientVisibility>>,
+    mut archetype_sizes: Local<HashMap<ArchetypeId, usize>>,
+) -> Vec<SenderCandidates> {
+    let entities: Vec<(Entity, f32, usize)> = entities
+        .iter()
+        .map(|(entity, priority)| {
+            let archetype = world.entity(entity).archetype();
+            let size = *archetype_sizes
+                .entry(archetype.id())
+                .or_insert_with(|| estimate_size(registry.as_deref(), archetype));
+            (entity, priority.0, size)
+        })
+        .collect();
+    let send_interval = send_interval(world);
+    senders
+        .iter()
+        .map(|(sender, budget, transport)| SenderCandidates {
+            sender,
+            max_bytes: budget.bytes_per_send(transport, send_interval),
+            candidates: entities
+                .iter()
+                .map(|(entity, base, size)| {
+                    (*entity, base * (relevance.0)(world, *entity, sender), *size)
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Select the entities replicated to each sender, and pause the updates of the other ones.
+fn select_prioritized_entities(
+    In(senders): In<Vec<SenderCandidates>>,
+    bit: Res<PriorityBit>,
+    mut budgets: Query<(&mut ReplicationBudget, &mut ClientVisibility)>,
+    children: Query<&ReplicateLikeChildren>,
+) {
+    for SenderCandidates {
+        sender,
+        max_bytes,
+        candidates,
+    } in senders
+    {
+        let Ok((mut budget, mut visibility)) = budgets.get_mut(sender) else {
+            continue;
+        };
+        let selected = budget.select(&candidates, max_bytes);
+        trace!(
+            ?sender,
+            ?max_bytes,
+            num_candidates = candidates.len(),
+            num_selected = selected.len(),
+            "selected prioritized entities"
+        );
+        let selected: EntityHashSet = selected.into_iter().collect();
+        for (entity, ..) in &candidates {
+            set_hierarchy_priority_visibility(
+                &mut visibility,
+                &children,
+                *entity,
+                **bit,
+                selected.contains(entity),
+            );
+        }
+    }
+}
+
+/// Time between two replication sends.
+fn send_interval(world: &World) -> Duration {
+    let tick_duration = world
+        .get_resource::<TickDuration>()
+        .map(|duration| duration.0)
+        .unwrap_or_default();
+    world
+        .get_resource::<ReplicationMetadata>()
+        .map(|metadata| metadata.timer.duration())
+        .unwrap_or_default()
+        .max(tick_duration)
+}
+
+/// Estimate the number of bytes needed to replicate the entities of an archetype, from the
+/// [size hints](crate::registry::replication::ComponentRegistration::with_size_hint) of their
+/// registered components.
+fn estimate_size(registry: Option<&ComponentRegistry>, archetype: &Archetype) -> usize {
+    let Some(registry) = registry else {
+        return ENTITY_HEADER_BYTES;
+    };
+    ENTITY_HEADER_BYTES
+        + archetype
+            .components()
+            .filter_map(|component_id| registry.component_id_to_kind.get(&component_id))
+            .filter_map(|kind| registry.component_metadata_map.get(kind))
+            .filter_map(|metadata| metadata.replication.as_ref())
+            .map(|replication| COMPONENT_HEADER_BYTES + replication.size_hint)
+            .sum::<usize>()
+}
+
+/// Set the priority visibility in the visibility of one sender, and propagate it through
+/// [`ReplicateLikeChildren`].
+fn set_hierarchy_priority_visibility(
+    visibility: &mut ClientVisibility,
+    children: &Query<&ReplicateLikeChildren>,
+    entity: Entity,
+    bit: FilterBit,
+    visible: bool,
+) {
+    visibility.set(entity, bit, visible);
+    if let Ok(entity_children) = children.get(entity) {
+        for child in entity_children.iter() {
+            set_hierarchy_priority_visibility(visibility, children, child, bit, visible);
+        }
+    }
+}
+
+/// Set the priority visibility and propagate it through [`ReplicateLikeChildren`].
+fn set_priority_visibility(
+    world: &mut World,
+    entity: Entity,
+    sender: Entity,
+    bit: FilterBit,
+    visible: bool,
+) {
+    if let Some(mut visibility) = world.get_mut::<ClientVisibility>(sender) {
+        visibility.set(entity, bit, visible);
+    }
+    let Some(children) = world.get::<ReplicateLikeChildren>(entity) else {
+        return;
+    };
+    let child_entities: smallvec::SmallVec<[Entity; 8]> = children.iter().collect();
+    for child in child_entities {
+        set_priority_visibility(world, child, sender, bit, visible);
+    }
+}
+
+/// Entities that stop being prioritized are replicated normally again.
+fn clear_priority_on_removal(trigger: On<Remove, ReplicationPriority>, mut commands: Commands) {
+    let entity = trigger.entity;
+    commands.queue(move |world: &mut World| {
+        if world.get_entity(entity).is_err() {
+            return;
+        }
+        let bit = **world.resource::<PriorityBit>();
+        let senders: Vec<Entity> = world
+            .query_filtered::<Entity, With<ClientVisibility>>()
+            .iter(world)
+            .collect();
+        for sender in senders {
+            set_priority_visibility(world, entity, sender, bit, true);
+        }
+    });
+}
+
+/// Senders without a budget receive the updates of every entity again.
+fn clear_priority_on_budget_removal(
+    trigger: On<Remove, ReplicationBudget>,
+    mut commands: Commands,
+) {
+    let sender = trigger.entity;
+    commands.queue(move |world: &mut World| {
+        let bit = **world.resource::<PriorityBit>();
+        let entities: Vec<Entity> = world
+            .query_filtered::<Entity, With<ReplicationPriority>>()
+            .iter(world)
+            .collect();
+        for entity in entities {
+            set_priority_visibility(world, entity, sender, bit, true);
+        }
+    });
+}
+
+/// An authoritative despawn must not be suppressed because the entity's updates are paused.
+fn clear_priority_on_despawn(
+    trigger: On<Despawn, Replicating>,
+    bit: Res<PriorityBit>,
+    mut senders: Query<&mut ClientVisibility>,
+) {
+    for mut visibility in &mut senders {
+        visibility.set(trigger.entity, **bit, true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn starved_entities_accumulate_priority() {
+        let near = Entity::from_bits(1);
+        let far = Entity::from_bits(2);
+        let mut budget = ReplicationBudget::fixed(10);
+        let candidates = [(near, 3.0, 10), (far, 1.0, 10)];
+
+        // the near entity is selected until the far entity has accumulated enough priority
+        assert_eq!(budget.select(&candidates, budget.max_bytes), [near]);
+        assert_eq!(budget.accumulated_priority(far), 1.0);
+        assert_eq!(budget.select(&candidates, budget.max_bytes), [near]);
+        assert_eq!(budget.select(&candidates, budget.max_bytes), [near]);
+        assert_eq!(budget.select(&candidates, budget.max_bytes), [far]);
+        assert_eq!(budget.accumulated_priority(far), 0.0);
+        assert_eq!(budget.accumulated_priority(near), 3.0);
+    }
+
+    #[test]
+    fn smaller_entities_fill_the_remaining_bytes() {
+        let large = Entity::from_bits(1);
+        let medium = Entity::from_bits(2);
+        let small = Entity::from_bits(3);
+        let mut budget = ReplicationBudget::fixed(30);
+        let candidates = [(large, 3.0, 20), (medium, 2.0, 15), (small, 1.0, 10)];
+        assert_eq!(budget.select(&candidates, budget.max_bytes), [large, small]);
+        assert_eq!(budget.accumulated_priority(medium), 2.0);
+    }
+
+    #[test]
+    fn entities_larger_than_the_budget_are_not_starved() {
+        let large = Entity::from_bits(1);
+        let small = Entity::from_bits(2);
+        let mut budget = ReplicationBudget::fixed(10);
+        let candidates = [(large, 2.0, 100), (small, 1.0, 5)];
+        assert_eq!(budget.select(&candidates, budget.max_bytes), [large]);
+    }
+
+    #[test]
+    fn irrelevant_entities_are_never_selected() {
+        let relevant = Entity::from_bits(1);
+        let irrelevant = Entity::from_bits(2);
+        let mut budget = ReplicationBudget::new(1.0);
+        let candidates = [(relevant, 1.0, 10), (irrelevant, -1.0, 10)];
+        assert_eq!(budget.select(&candidates, None), [relevant]);
+        assert_eq!(budget.accumulated_priority(irrelevant), 0.0);
+    }
+
+    #[test]
+    fn removed_candidates_are_forgotten() {
+        let entity = Entity::from_bits(1);
+        let mut budget = ReplicationBudget::fixed(0);
+        budget.select(&[(entity, 1.0, 10)], Some(0));
+        budget.select(&[], Some(0));
+        assert!(budget.accumulated.is_empty());
+    }
+
+    #[test]
+    fn size_is_estimated_from_the_registered_components() {
+        #[derive(Component)]
+        struct Health(#[allow(dead_code)] f32);
+        #[derive(Component)]
+        struct NotReplicated;
+
+        let mut world = World::new();
+        let mut registry = ComponentRegistry::default();
+        registry.register_component::<Health>(&mut world);
+        let entity = world.spawn((Health(1.0), NotReplicated)).id();
+        let archetype = world.entity(entity).archetype();
+        assert_eq!(
+            estimate_size(Some(&registry), archetype),
+            ENTITY_HEADER_BYTES + COMPONENT_HEADER_BYTES + 4
+        );
+
+        registry
+            .component_metadata_map
+            .values_mut()
+            .for_each(|metadata| metadata.replication.as_mut().unwrap().set_size_hint(2));
+        assert_eq!(
+            estimate_size(Some(&registry), archetype),
+            ENTITY_HEADER_BYTES + COMPONENT_HEADER_BYTES + 2
+        );
+    }
+
+    #[test]
+    fn budget_follows_the_transport_bandwidth() {
+        use lightyear_transport::prelude::PriorityConfig;
+
+        let transport = Transport::new(PriorityConfig::new(1000));
+        let interval = Duration::from_millis(100);
+        assert_eq!(
+            ReplicationBudget::new(0.5).bytes_per_send(Some(&transport), interval),
+            Some(50)
+        );
+        assert_eq!(
+            ReplicationBudget::fixed(20).bytes_per_send(Some(&transport), interval),
+            Some(20)
+        );
+        let unlimited = Transport::new(PriorityConfig::default());
+        assert_eq!(
+            ReplicationBudget::new(0.5).bytes_per_send(Some(&unlimited), interval),
+            None
+        );
+    }
+}
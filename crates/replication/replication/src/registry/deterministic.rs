@@ -64,6 +64,7 @@ impl<C: Debug> ComponentRegistration<'_, C> {
                         component_id,
                         replication: None,
                         deterministic: None,
+                        snapshot: None,
                     })
                     .deterministic = Some(DeterministicFns::new::<C>(default_inner_hash_fn::<C>));
             });
@@ -89,6 +90,7 @@ impl<C: Debug> ComponentRegistration<'_, C> {
                         component_id,
                         replication: None,
                         deterministic: None,
+                        snapshot: None,
                     })
                     .deterministic = Some(DeterministicFns::new(f));
             });
@@ -2,6 +2,7 @@
 pub mod deterministic;
 
 pub mod replication;
+pub mod snapshot;
 
 use crate::registry::replication::ReplicationMetadata;
 use bevy_ecs::component::ComponentId;
@@ -173,6 +174,7 @@ pub struct ComponentMetadata {
     pub replication: Option<ReplicationMetadata>,
     #[cfg(feature = "deterministic")]
     pub deterministic: Option<deterministic::DeterministicFns>,
+    pub snapshot: Option<snapshot::SnapshotFns>,
 }
 
 impl ComponentRegistry {
@@ -204,9 +206,13 @@ impl ComponentRegistry {
             .entry(component_kind)
             .or_insert(ComponentMetadata {
                 component_id,
-                replication: Some(ReplicationMetadata::default()),
+                replication: Some(ReplicationMetadata {
+                    size_hint: size_of::<C>(),
+                    ..Default::default()
+                }),
                 #[cfg(feature = "deterministic")]
                 deterministic: None,
+                snapshot: None,
             });
     }
 
@@ -1,13 +1,23 @@
-use crate::registry::ComponentRegistry;
+use crate::authority::AuthorityFns;
+use crate::registry::{ComponentKind, ComponentRegistry};
+use alloc::vec::Vec;
 use bevy_app::App;
 use bevy_ecs::change_detection::Mut;
 use bevy_ecs::component::Component;
 use bevy_ecs::resource::Resource;
+use bevy_replicon::bytes::Bytes;
 use bevy_replicon::prelude::{AppRuleExt, ReplicationMode, RuleFns};
 use bevy_replicon::shared::replication::diff::Diffable as RepliconDiffable;
+use bevy_replicon::shared::replication::registry::ctx::{SerializeCtx, WriteCtx};
 use bevy_replicon::shared::replication::registry::receive_fns::MutWrite;
 use bevy_replicon::shared::replication::registry::rule_fns::{DeserializeFn, SerializeFn};
 use bevy_replicon::shared::replication::rules::filter::FilterRules;
+use bytes::Buf;
+use lightyear_serde::SerializationError;
+use lightyear_serde::bits::{BitReader, BitSerialize, BitWriter};
+use lightyear_serde::reader::Reader;
+use lightyear_serde::registry::SerializeFns;
+use lightyear_serde::writer::Writer;
 use serde::{Serialize, de::DeserializeOwned};
 
 /// Add a component to the list of components that can be sent
@@ -198,7 +208,7 @@ impl<C> ComponentRegistration<'_, C> {
         C: Component<Mutability: MutWrite<C>> + Serialize + DeserializeOwned,
     {
         self.app.replicate::<C>();
-        self
+        self.add_authority().add_snapshot()
     }
 
     /// Register this component with Replicon's `Once` replication mode.
@@ -207,7 +217,7 @@ impl<C> ComponentRegistration<'_, C> {
         C: Component<Mutability: MutWrite<C>> + Serialize + DeserializeOwned,
     {
         self.app.replicate_once::<C>();
-        self
+        self.add_authority().add_snapshot()
     }
 
     /// Register this component using Replicon's diff-based replication.
@@ -232,7 +242,7 @@ impl<C> ComponentRegistration<'_, C> {
         C: Component<Mutability: MutWrite<C>> + Serialize + DeserializeOwned,
     {
         self.app.replicate_filtered::<C, F>();
-        self
+        self.add_authority().add_snapshot()
     }
 
     /// Register this component with Replicon's `Once` replication mode and an
@@ -242,7 +252,7 @@ impl<C> ComponentRegistration<'_, C> {
         C: Component<Mutability: MutWrite<C>> + Serialize + DeserializeOwned,
     {
         self.app.replicate_once_filtered::<C, F>();
-        self
+        self.add_authority().add_snapshot()
     }
 
     /// Register this component with Replicon's `replicate_as` conversion API.
@@ -252,7 +262,7 @@ impl<C> ComponentRegistration<'_, C> {
         T: Serialize + DeserializeOwned,
     {
         self.app.replicate_as::<C, T>();
-        self
+        self.add_authority_as::<T>().add_snapshot_as::<T>()
     }
 
     /// Register this component with Replicon's `replicate_once_as` conversion API.
@@ -262,7 +272,7 @@ impl<C> ComponentRegistration<'_, C> {
         T: Serialize + DeserializeOwned,
     {
         self.app.replicate_once_as::<C, T>();
-        self
+        self.add_authority_as::<T>().add_snapshot_as::<T>()
     }
 
     /// Register this component with Replicon's filtered conversion API.
@@ -272,7 +282,7 @@ impl<C> ComponentRegistration<'_, C> {
         T: Serialize + DeserializeOwned,
     {
         self.app.replicate_filtered_as::<C, T, F>();
-        self
+        self.add_authority_as::<T>().add_snapshot_as::<T>()
     }
 
     /// Register this component with Replicon's filtered `Once` conversion API.
@@ -282,7 +292,37 @@ impl<C> ComponentRegistration<'_, C> {
         T: Serialize + DeserializeOwned,
     {
         self.app.replicate_once_filtered_as::<C, T, F>();
-        self
+        self.add_authority_as::<T>().add_snapshot_as::<T>()
+    }
+
+    /// Register this component with bit-packed serialization, using its
+    /// [`BitSerialize`] implementation.
+    ///
+    /// The component is also saved in snapshots with the same representation.
+    pub fn replicate_bits(self) -> Self
+    where
+        C: Component<Mutability: MutWrite<C>> + BitSerialize,
+    {
+        self.app
+            .replicate_with(RuleFns::new(serialize_bits::<C>, deserialize_bits::<C>));
+        let fns = SerializeFns::<C>::with_bits();
+        self.add_custom_authority(fns.serialize, fns.deserialize)
+            .add_custom_snapshot(fns.serialize, fns.deserialize)
+    }
+
+    /// Register this component with bit-packed serialization and
+    /// `ReplicationMode::Once`.
+    pub fn replicate_once_bits(self) -> Self
+    where
+        C: Component<Mutability: MutWrite<C>> + BitSerialize,
+    {
+        self.app.replicate_with((
+            RuleFns::new(serialize_bits::<C>, deserialize_bits::<C>),
+            ReplicationMode::Once,
+        ));
+        let fns = SerializeFns::<C>::with_bits();
+        self.add_custom_authority(fns.serialize, fns.deserialize)
+            .add_custom_snapshot(fns.serialize, fns.deserialize)
     }
 
     /// Register this component with custom Replicon rule functions.
@@ -333,7 +373,7 @@ impl<C> ComponentRegistration<'_, C> {
     {
         self.app
             .replicate_with_priority(priority, RuleFns::<C>::default());
-        self
+        self.add_authority().add_snapshot()
     }
 
     /// Register this component with Replicon's default rule functions, a custom
@@ -344,7 +384,7 @@ impl<C> ComponentRegistration<'_, C> {
     {
         self.app
             .replicate_with_priority_filtered::<_, F>(priority, RuleFns::<C>::default());
-        self
+        self.add_authority().add_snapshot()
     }
 
     /// Register this component with custom Replicon rule functions and a custom
@@ -371,12 +411,70 @@ impl<C> ComponentRegistration<'_, C> {
             .replicate_with_priority_filtered::<_, F>(priority, rule_fns);
         self
     }
+
+    /// Set the number of bytes that the component usually takes once serialized.
+    ///
+    /// The [`ReplicationPriorityPlugin`](crate::priority::ReplicationPriorityPlugin) uses it to
+    /// estimate the size of the prioritized entities. Defaults to the size of the component in
+    /// memory, which is a poor estimate for components that hold collections or that use a
+    /// compact serialization.
+    pub fn with_size_hint(self, size_hint: usize) -> Self
+    where
+        C: Component,
+    {
+        let mut registry = self.app.world_mut().resource_mut::<ComponentRegistry>();
+        if let Some(replication) = registry
+            .component_metadata_map
+            .get_mut(&ComponentKind::of::<C>())
+            .and_then(|metadata| metadata.replication.as_mut())
+        {
+            replication.set_size_hint(size_hint);
+        }
+        self
+    }
+}
+
+pub(crate) fn serialize_as<C: Clone + Into<T>, T: Serialize + DeserializeOwned>(
+    component: &C,
+    writer: &mut Writer,
+) -> Result<(), SerializationError> {
+    (SerializeFns::<T>::default().serialize)(&component.clone().into(), writer)
+}
+
+pub(crate) fn deserialize_as<C: From<T>, T: Serialize + DeserializeOwned>(
+    reader: &mut Reader,
+) -> Result<C, SerializationError> {
+    (SerializeFns::<T>::default().deserialize)(reader).map(C::from)
+}
+
+fn serialize_bits<C: BitSerialize>(
+    _ctx: &mut SerializeCtx,
+    component: &C,
+    message: &mut Vec<u8>,
+) -> bevy_ecs::error::Result<()> {
+    component.encode(&mut BitWriter::new(message))?;
+    Ok(())
+}
+
+fn deserialize_bits<C: BitSerialize>(
+    _ctx: &mut WriteCtx,
+    message: &mut Bytes,
+) -> bevy_ecs::error::Result<C> {
+    let mut reader = BitReader::new(message);
+    let component = C::decode(&mut reader)?;
+    let bytes_read = reader.bytes_read();
+    message.advance(bytes_read);
+    Ok(component)
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct ReplicationMetadata {
     pub(crate) predicted: bool,
     pub(crate) interpolated: bool,
+    /// Number of bytes that the component usually takes once serialized.
+    pub(crate) size_hint: usize,
+    /// Used by the client that has authority over an entity to send the component to the server.
+    pub(crate) authority: Option<AuthorityFns>,
 }
 
 impl ReplicationMetadata {
@@ -391,6 +489,11 @@ impl ReplicationMetadata {
     pub fn set_interpolated(&mut self, interpolated: bool) {
         self.interpolated = interpolated;
     }
+
+    /// Set the number of bytes that the component usually takes once serialized.
+    pub fn set_size_hint(&mut self, size_hint: usize) {
+        self.size_hint = size_hint;
+    }
 }
 
 #[cfg(test)]
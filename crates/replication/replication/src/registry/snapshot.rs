@@ -0,0 +1,252 @@
+//! Functions used to save the replicated components in a [snapshot](crate::snapshot).
+//!
+//! Replicon's rule functions need a replication context, so the snapshot uses its own
+//! serialization functions, registered in the [`ComponentRegistry`].
+use crate::registry::replication::{ComponentRegistration, deserialize_as, serialize_as};
+use crate::registry::{ComponentKind, ComponentMetadata, ComponentRegistry};
+use bevy_ecs::change_detection::Mut;
+use bevy_ecs::component::Component;
+use bevy_ecs::entity::MapEntities;
+use bevy_ecs::reflect::AppTypeRegistry;
+use bevy_ecs::world::{EntityRef, EntityWorldMut, World};
+use core::any::TypeId;
+use lightyear_serde::SerializationError;
+use lightyear_serde::entity_map::EntityMap;
+use lightyear_serde::reader::Reader;
+use lightyear_serde::registry::{DeserializeFn, SerializeFn, SerializeFns};
+use lightyear_serde::writer::Writer;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+type SaveFn = fn(&SnapshotFns, EntityRef, &mut Writer) -> Result<bool, SerializationError>;
+type LoadFn = fn(
+    &SnapshotFns,
+    &mut Reader,
+    &mut EntityWorldMut,
+    &mut EntityMap,
+) -> Result<(), SerializationError>;
+
+/// Type-erased functions that write a component into a snapshot and insert it back.
+#[derive(Debug, Clone, Copy)]
+pub struct SnapshotFns {
+    /// Identifies the component in the snapshot, so that the registration order can change
+    /// between the server that saved the snapshot and the one that loads it.
+    ///
+    /// If it is not set, the [`TypePath`](bevy_reflect::TypePath) of the component in the
+    /// [`AppTypeRegistry`] is used.
+    pub name: Option<&'static str>,
+    /// Only used in error messages: it is not stable across compiler versions.
+    pub type_name: &'static str,
+    type_id: TypeId,
+    // SerializeFn<C> and DeserializeFn<C> converted to fn() to avoid generic parameters
+    serialize: fn(),
+    deserialize: fn(),
+    save_fn: SaveFn,
+    load_fn: LoadFn,
+}
+
+impl SnapshotFns {
+    pub fn new<C: Component>(serialize: SerializeFn<C>, deserialize: DeserializeFn<C>) -> Self {
+        Self {
+            name: None,
+            type_name: core::any::type_name::<C>(),
+            type_id: TypeId::of::<C>(),
+            serialize: unsafe { core::mem::transmute::<SerializeFn<C>, fn()>(serialize) },
+            deserialize: unsafe { core::mem::transmute::<DeserializeFn<C>, fn()>(deserialize) },
+            save_fn: save_component::<C>,
+            load_fn: load_component::<C>,
+        }
+    }
+
+    /// Map the entities contained in the component from the saved world to the restored world.
+    pub fn with_map_entities<C: Component + MapEntities>(mut self) -> Self {
+        self.load_fn = load_mapped_component::<C>;
+        self
+    }
+
+    /// Returns the stable name that identifies the component in the snapshots: the name set with
+    /// [`with_snapshot_name`](ComponentRegistration::with_snapshot_name), or else the type path
+    /// of the component if its type is registered in the [`AppTypeRegistry`].
+    pub fn stable_name(&self, world: &World) -> Option<&'static str> {
+        self.name.or_else(|| {
+            world
+                .get_resource::<AppTypeRegistry>()?
+                .read()
+                .get(self.type_id)
+                .map(|registration| registration.type_info().type_path())
+        })
+    }
+
+    /// Writes the component of the entity, if it has one. Returns `false` if it doesn't.
+    pub fn save(&self, entity: EntityRef, writer: &mut Writer) -> Result<bool, SerializationError> {
+        (self.save_fn)(self, entity, writer)
+    }
+
+    /// Reads the component and inserts it on the entity.
+    pub fn load(
+        &self,
+        reader: &mut Reader,
+        entity: &mut EntityWorldMut,
+        entity_map: &mut EntityMap,
+    ) -> Result<(), SerializationError> {
+        (self.load_fn)(self, reader, entity, entity_map)
+    }
+}
+
+fn save_component<C: Component>(
+    fns: &SnapshotFns,
+    entity: EntityRef,
+    writer: &mut Writer,
+) -> Result<bool, SerializationError> {
+    let Some(component) = entity.get::<C>() else {
+        return Ok(false);
+    };
+    // SAFETY: the SnapshotFns were created for the component C
+    let serialize = unsafe { core::mem::transmute::<fn(), SerializeFn<C>>(fns.serialize) };
+    serialize(component, writer)?;
+    Ok(true)
+}
+
+fn read_component<C: Component>(
+    fns: &SnapshotFns,
+    reader: &mut Reader,
+) -> Result<C, SerializationError> {
+    // SAFETY: the SnapshotFns were created for the component C
+    let deserialize = unsafe { core::mem::transmute::<fn(), DeserializeFn<C>>(fns.deserialize) };
+    deserialize(reader)
+}
+
+fn load_component<C: Component>(
+    fns: &SnapshotFns,
+    reader: &mut Reader,
+    entity: &mut EntityWorldMut,
+    _: &mut EntityMap,
+) -> Result<(), SerializationError> {
+    entity.insert(read_component::<C>(fns, reader)?);
+    Ok(())
+}
+
+fn load_mapped_component<C: Component + MapEntities>(
+    fns: &SnapshotFns,
+    reader: &mut Reader,
+    entity: &mut EntityWorldMut,
+    entity_map: &mut EntityMap,
+) -> Result<(), SerializationError> {
+    let mut component = read_component::<C>(fns, reader)?;
+    component.map_entities(entity_map);
+    entity.insert(component);
+    Ok(())
+}
+
+impl<C: Component> ComponentRegistration<'_, C> {
+    /// Include this component in the [snapshots](crate::snapshot) of the replicated world, using
+    /// its `Serialize` and `Deserialize` implementations.
+    ///
+    /// This is done automatically by the `replicate*` registration methods that don't take custom
+    /// rule functions.
+    pub fn add_snapshot(self) -> Self
+    where
+        C: Serialize + DeserializeOwned,
+    {
+        let fns = SerializeFns::<C>::default();
+        self.set_snapshot_fns(SnapshotFns::new(fns.serialize, fns.deserialize))
+    }
+
+    /// Include this component in the [snapshots](crate::snapshot) of the replicated world, with
+    /// custom serialization functions.
+    ///
+    /// Components registered with custom Replicon rule functions or with diff-based replication
+    /// are only saved in snapshots if this is called.
+    pub fn add_custom_snapshot(
+        self,
+        serialize: SerializeFn<C>,
+        deserialize: DeserializeFn<C>,
+    ) -> Self {
+        self.set_snapshot_fns(SnapshotFns::new(serialize, deserialize))
+    }
+
+    /// Map the entities contained in this component when a snapshot is loaded.
+    ///
+    /// Entities that are not part of the snapshot are kept unchanged.
+    ///
+    /// # Panics
+    ///
+    /// If no snapshot functions were registered for this component.
+    pub fn add_snapshot_map_entities(self) -> Self
+    where
+        C: MapEntities,
+    {
+        let fns = self.snapshot_fns();
+        self.set_snapshot_fns(fns.with_map_entities::<C>())
+    }
+
+    /// Identify this component with `name` in the [snapshots](crate::snapshot).
+    ///
+    /// The name must stay the same between the server that saves a snapshot and the one that
+    /// loads it, and must be unique among the components in the snapshots. Components without a
+    /// name are identified by their [`TypePath`](bevy_reflect::TypePath) if their type is
+    /// registered for reflection, and cannot be saved otherwise.
+    ///
+    /// # Panics
+    ///
+    /// If no snapshot functions were registered for this component.
+    pub fn with_snapshot_name(self, name: &'static str) -> Self {
+        let mut fns = self.snapshot_fns();
+        fns.name = Some(name);
+        self.set_snapshot_fns(fns)
+    }
+
+    pub(crate) fn add_snapshot_as<T>(self) -> Self
+    where
+        C: Clone + Into<T> + From<T>,
+        T: Serialize + DeserializeOwned,
+    {
+        self.set_snapshot_fns(SnapshotFns::new(
+            serialize_as::<C, T>,
+            deserialize_as::<C, T>,
+        ))
+    }
+
+    fn snapshot_fns(&self) -> SnapshotFns {
+        self.app
+            .world()
+            .get_resource::<ComponentRegistry>()
+            .and_then(|registry| {
+                registry
+                    .component_metadata_map
+                    .get(&ComponentKind::of::<C>())
+            })
+            .and_then(|metadata| metadata.snapshot)
+            .unwrap_or_else(|| {
+                panic!(
+                    "Component {} has no snapshot functions",
+                    core::any::type_name::<C>()
+                )
+            })
+    }
+
+    fn set_snapshot_fns(self, fns: SnapshotFns) -> Self {
+        self.app.world_mut().init_resource::<ComponentRegistry>();
+        self.app
+            .world_mut()
+            .resource_scope(|world, mut registry: Mut<ComponentRegistry>| {
+                let component_id = world.register_component::<C>();
+                let metadata = registry
+                    .component_metadata_map
+                    .entry(ComponentKind::of::<C>())
+                    .or_insert_with(|| ComponentMetadata {
+                        component_id,
+                        replication: None,
+                        #[cfg(feature = "deterministic")]
+                        deterministic: None,
+                        snapshot: None,
+                    });
+                // keep the name if the serialization functions are replaced
+                let name = fns
+                    .name
+                    .or_else(|| metadata.snapshot.and_then(|previous| previous.name));
+                metadata.snapshot = Some(SnapshotFns { name, ..fns });
+            });
+        self
+    }
+}
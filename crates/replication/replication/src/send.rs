@@ -132,6 +132,8 @@ mod private {
 pub trait ReplicationTargetT: private::Sealed + Send + Sync + 'static {
     type VisibilityBit: Resource + Deref<Target = FilterBit>;
     type Context: Default + Send;
+    /// Whether the marker is replicated to [`Spectator`](crate::spectator::Spectator) links.
+    const VISIBLE_TO_SPECTATORS: bool = true;
 
     fn post_insert(context: &Self::Context, entity_mut: &mut EntityWorldMut);
     fn update_context(context: &mut Self::Context, sender_entity: Entity, host_client: bool);
@@ -260,6 +262,8 @@ mod prediction {
 
         // Context = the host-sender entity
         type Context = bool;
+        // spectators never predict entities
+        const VISIBLE_TO_SPECTATORS: bool = false;
 
         fn post_insert(context: &Self::Context, entity_mut: &mut EntityWorldMut) {
             if *context {
@@ -434,6 +438,11 @@ impl<T: ReplicationTargetT> ReplicationTarget<T> {
     pub fn manual(senders: Vec<Entity>) -> Self {
         Self::new(ReplicationMode::Manual(senders))
     }
+
+    pub fn mode(&self) -> &ReplicationMode {
+        &self.mode
+    }
+
     fn on_insert(mut world: DeferredWorld, context: HookContext) {
         let entity = context.entity;
         let Some(visibility_bit) = world
@@ -604,6 +613,15 @@ impl<T: ReplicationTargetT> ReplicationTarget<T> {
             }
         }
 
+        #[cfg(feature = "server")]
+        if !T::VISIBLE_TO_SPECTATORS {
+            let mut spectators =
+                world.query_filtered::<&mut ClientVisibility, With<crate::spectator::Spectator>>();
+            for mut visibility in spectators.iter_mut(world) {
+                visibility.set(entity, visibility_bit, false);
+            }
+        }
+
         world.commands().queue(move |world: &mut World| {
             let Ok(mut entity_mut) = world.get_entity_mut(entity) else {
                 return;
@@ -918,6 +936,8 @@ fn emulate_replicate_on_host_client_added(
 /// [`ClientVisibility::default`] treats entities and components as visible. Without this backfill,
 /// a late-joining client would receive pre-existing entities and prediction/interpolation markers
 /// even when their [`NetworkTarget`] excludes that client.
+///
+/// Prediction markers are always hidden from [`Spectator`](crate::spectator::Spectator) links.
 #[cfg(feature = "server")]
 pub(crate) fn handle_new_client_visibility(
     trigger: On<Add, ClientVisibility>,
@@ -926,6 +946,7 @@ pub(crate) fn handle_new_client_visibility(
     replicate_bit: Res<ReplicateBit>,
     #[cfg(feature = "prediction")] prediction_targets: Query<(Entity, &PredictionTarget)>,
     #[cfg(feature = "prediction")] predicted_bit: Res<PredictedBit>,
+    #[cfg(feature = "prediction")] spectators: Query<(), With<crate::spectator::Spectator>>,
     #[cfg(feature = "interpolation")] interpolation_targets: Query<(Entity, &InterpolationTarget)>,
     #[cfg(feature = "interpolation")] interpolated_bit: Res<InterpolatedBit>,
     controlled_entities: Query<(Entity, &crate::control::ControlledBy)>,
@@ -952,11 +973,16 @@ pub(crate) fn handle_new_client_visibility(
     }
 
     #[cfg(feature = "prediction")]
-    for (entity, target) in prediction_targets.iter() {
-        if let ReplicationMode::SingleServer(ref net_target) = target.mode
-            && !net_target.targets(&peer_id)
-        {
-            visibility.set(entity, **predicted_bit, false);
+    {
+        let spectator = spectators.contains(sender_entity);
+        for (entity, target) in prediction_targets.iter() {
+            if spectator {
+                visibility.set(entity, **predicted_bit, false);
+            } else if let ReplicationMode::SingleServer(ref net_target) = target.mode
+                && !net_target.targets(&peer_id)
+            {
+                visibility.set(entity, **predicted_bit, false);
+            }
         }
     }
 
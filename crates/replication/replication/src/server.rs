@@ -1,6 +1,8 @@
 use bevy_app::prelude::*;
 use bevy_ecs::prelude::*;
 use bevy_state::prelude::*;
+use bevy_time::{Real, Time};
+use core::time::Duration;
 
 use bevy_replicon::prelude::*;
 use bevy_replicon::server::visibility::client_visibility::ClientVisibility;
@@ -16,6 +18,7 @@ use lightyear_transport::plugin::TransportSystems;
 use lightyear_transport::prelude::Transport;
 
 use crate::channels::RepliconChannelMap;
+use crate::spectator::{DelayedMessages, MaxSpectatorDelay, Spectator};
 use lightyear_messages::plugin::MessageSystems;
 use tracing::{error, trace};
 
@@ -67,6 +70,9 @@ impl Plugin for RepliconServerPlugin {
 /// When `Connected` is added to a remote client link entity, insert replicon's
 /// `ConnectedClient` and `NetworkId` so replicon's packet path can target it.
 ///
+/// A resumed session already has them: keeping its `ConnectedClient` keeps replicon's
+/// per-client state, so replication continues from what the client acknowledged.
+///
 /// Host-clients intentionally do not become replicon `ConnectedClient`s because they share the
 /// same world as the server and may otherwise collide with a real remote client's `NetworkId`.
 /// They only need `ClientVisibility` for lightyear's same-app visibility hooks.
@@ -74,7 +80,12 @@ fn on_client_connected(
     _trigger: On<Add, Connected>,
     remotes: Query<
         (Entity, &RemoteId, &Link),
-        (Added<Connected>, With<ClientOf>, Without<HostClient>),
+        (
+            Added<Connected>,
+            With<ClientOf>,
+            Without<HostClient>,
+            Without<ConnectedClient>,
+        ),
     >,
     hosts: Query<Entity, (Added<Connected>, With<HostClient>)>,
     mut commands: Commands,
@@ -134,11 +145,26 @@ fn receive_server_packets(
 /// Send `ServerMessages` (replication data) via transport to peers.
 ///
 /// Drains `ServerMessages` and sends on server_channels (Updates, Mutations).
+///
+/// The messages for a [`Spectator`] with a delay are held in its [`DelayedMessages`] until the
+/// delay has elapsed. The spectator acknowledges them late as well, which replicon handles like a
+/// client with a high latency: its mutations timeout is raised by the [`MaxSpectatorDelay`] so
+/// that the late acks are not treated as lost.
 fn send_server_packets(
+    time: Res<Time<Real>>,
     channel_map: Res<RepliconChannelMap>,
+    max_spectator_delay: Res<MaxSpectatorDelay>,
     mut server_messages: ResMut<ServerMessages>,
-    mut transports: Query<&mut Transport, With<ClientOf>>,
+    mut transports: Query<
+        (
+            &mut Transport,
+            Option<&Spectator>,
+            Option<&mut DelayedMessages>,
+        ),
+        With<ClientOf>,
+    >,
 ) {
+    let now = time.elapsed();
     for (client, channel_idx, message) in server_messages.drain_sent() {
         let (channel_kind, _) = channel_map.server_channels[channel_idx];
         trace!(
@@ -147,10 +173,29 @@ fn send_server_packets(
             channel_idx,
             client
         );
-        if let Ok(mut transport) = transports.get_mut(client) {
-            transport.send_mut_erased(channel_kind, message, 1.0).ok();
-        } else {
+        let Ok((mut transport, spectator, delayed)) = transports.get_mut(client) else {
             trace!("send_server_packets: no transport for client {:?}", client);
+            continue;
+        };
+        if let Some(mut delayed) = delayed {
+            // keep queuing after the delay is removed, until the earlier messages are sent
+            let delay = spectator.map_or(Duration::ZERO, |spectator| {
+                spectator.effective_delay(max_spectator_delay.0)
+            });
+            if !delay.is_zero() || !delayed.is_empty() {
+                delayed.push(now + delay, channel_kind, message);
+                continue;
+            }
+        }
+        transport.send_mut_erased(channel_kind, message, 1.0).ok();
+    }
+
+    for (mut transport, _, delayed) in transports.iter_mut() {
+        let Some(mut delayed) = delayed else {
+            continue;
+        };
+        for (channel_kind, message) in delayed.release(now) {
+            transport.send_mut_erased(channel_kind, message, 1.0).ok();
         }
     }
 }
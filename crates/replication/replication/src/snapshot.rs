@@ -0,0 +1,772 @@
+//! Save the replicated world of a server and restore it into another server.
+//!
+//! A snapshot contains every entity with [`Replicate`] and the entities that are replicated like
+//! them through [`ReplicateLike`], with:
+//! - their replication targets ([`Replicate`], and `PredictionTarget`/`InterpolationTarget` when
+//!   those features are enabled);
+//! - every component that has snapshot functions in the [`ComponentRegistry`]. Components
+//!   registered with [`replicate`](crate::registry::replication::ComponentRegistration::replicate)
+//!   and the other `replicate*` methods that use `Serialize` get them automatically, other
+//!   components need [`add_snapshot`](crate::registry::replication::ComponentRegistration::add_snapshot)
+//!   or [`add_custom_snapshot`](crate::registry::replication::ComponentRegistration::add_custom_snapshot);
+//! - their [`Rooms`], their parent ([`ChildOf`]) and their [`ReplicateLike`] root;
+//! - the peer that controls them ([`ControlledBy`]).
+//!
+//! The links of the clients are not saved. The replication targets that list specific links are
+//! saved with the [`PeerId`] of those links, and the control of an entity is restored with a
+//! [`PendingControlledBy`] component that becomes a [`ControlledBy`] when the client reconnects.
+//! Entities whose control is never reclaimed are kept, even with [`Lifetime::SessionBased`].
+//!
+//! [`save_entities`] writes only some of the entities in the same format, for example to move
+//! them to another server.
+//!
+//! Components are identified by a stable name, so the components can be registered in a
+//! different order by the server that loads the snapshot. The name is the one given to
+//! [`with_snapshot_name`](crate::registry::replication::ComponentRegistration::with_snapshot_name),
+//! or else the [`TypePath`](bevy_reflect::TypePath) of the component if its type is registered
+//! for reflection: renaming or moving such a component type makes the previous snapshots
+//! unreadable. A component that has neither cannot be saved. The unstable
+//! [`type_name`](core::any::type_name) is never used, since it can change with the compiler
+//! version.
+//!
+//! The control of an entity is restored by matching the [`RemoteId`] of the reconnecting client.
+//! With netcode, the client id comes from the connect token, so the service that issues the tokens
+//! must give each player the same client id every time, otherwise the control is never restored.
+//!
+//! ```rust,ignore
+//! fn save_world(world: &mut World) {
+//!     if let Err(e) = save_snapshot_to_file(world, "world.snapshot") {
+//!         error!(?e, "failed to save the world");
+//!     }
+//! }
+//!
+//! // on startup, once the protocol is registered
+//! load_snapshot_from_file(app.world_mut(), "world.snapshot")?;
+//! ```
+//!
+//! [`Replicate`]: crate::send::Replicate
+//! [`ReplicateLike`]: crate::hierarchy::ReplicateLike
+//! [`Rooms`]: crate::visibility::room::Rooms
+//! [`ControlledBy`]: crate::control::ControlledBy
+//! [`PendingControlledBy`]: crate::control::PendingControlledBy
+//! [`Lifetime::SessionBased`]: crate::control::Lifetime::SessionBased
+//! [`RemoteId`]: lightyear_core::id::RemoteId
+use crate::control::{ControlledBy, Lifetime, PendingControlledBy};
+use crate::hierarchy::ReplicateLike;
+use crate::registry::ComponentRegistry;
+use crate::registry::snapshot::SnapshotFns;
+use crate::send::{Replicate, ReplicationMode, ReplicationTarget, ReplicationTargetT};
+use crate::visibility::room::{RoomAllocator, RoomId, Rooms};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use bevy_ecs::entity::{Entity, EntityMapper};
+use bevy_ecs::hierarchy::ChildOf;
+use bevy_ecs::prelude::{Or, With, World};
+use bevy_ecs::world::EntityRef;
+use bevy_platform::collections::HashSet;
+use bytes::Bytes;
+use lightyear_connection::network_target::NetworkTarget;
+use lightyear_core::id::{PeerId, RemoteId};
+use lightyear_serde::entity_map::EntityMap;
+use lightyear_serde::reader::{ReadInteger, ReadVarInt, Reader};
+use lightyear_serde::writer::{WriteInteger, Writer};
+use lightyear_serde::{SerializationError, ToBytes};
+#[allow(unused_imports)]
+use tracing::{debug, trace, warn};
+
+/// First bytes of every snapshot.
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"LYSN";
+/// Version of the snapshot format. Snapshots with another version are rejected.
+pub const SNAPSHOT_VERSION: u16 = 1;
+
+#[derive(thiserror::Error, Debug)]
+pub enum SnapshotError {
+    #[error("the data is not a replication snapshot")]
+    InvalidHeader,
+    #[error("the snapshot format version {0} is not supported")]
+    UnsupportedVersion(u16),
+    #[error("the component {0} of the snapshot has no snapshot functions")]
+    UnknownComponent(String),
+    #[error(
+        "the component {0} has no stable snapshot name: register its type for reflection or call `with_snapshot_name`"
+    )]
+    UnnamedComponent(&'static str),
+    #[error("several components use the snapshot name {0}")]
+    DuplicateName(&'static str),
+    #[error(transparent)]
+    Serialization(#[from] SerializationError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Serializable version of a [`ReplicationMode`], without the link entities.
+#[derive(Debug, Clone, PartialEq)]
+enum SavedMode {
+    SingleSender,
+    Clients(NetworkTarget),
+}
+
+impl SavedMode {
+    fn new(world: &World, mode: &ReplicationMode) -> Option<Self> {
+        let peer = |sender: &Entity| world.get::<RemoteId>(*sender).map(|remote_id| remote_id.0);
+        match mode {
+            ReplicationMode::SingleSender => Some(Self::SingleSender),
+            ReplicationMode::SingleServer(target)
+            | ReplicationMode::Server(_, target)
+            | ReplicationMode::Target(target) => Some(Self::Clients(target.clone())),
+            ReplicationMode::Sender(sender) => {
+                peer(sender).map(|peer| Self::Clients(NetworkTarget::Single(peer)))
+            }
+            ReplicationMode::Manual(senders) => Some(Self::Clients(NetworkTarget::Only(
+                senders.iter().filter_map(peer).collect(),
+            ))),
+            #[cfg(feature = "client")]
+            ReplicationMode::SingleClient => None,
+        }
+    }
+
+    fn to_target<T: ReplicationTargetT>(&self) -> ReplicationTarget<T> {
+        match self {
+            Self::SingleSender => ReplicationTarget::new(ReplicationMode::SingleSender),
+            Self::Clients(target) => ReplicationTarget::to_clients(target.clone()),
+        }
+    }
+
+    fn save<T: ReplicationTargetT>(world: &World, entity: EntityRef) -> Option<Self> {
+        let mode = entity.get::<ReplicationTarget<T>>()?.mode();
+        let saved = Self::new(world, mode);
+        if saved.is_none() {
+            warn!(entity = ?entity.id(), ?mode, "Replication mode cannot be saved in a snapshot");
+        }
+        saved
+    }
+
+    fn write(mode: Option<&Self>, writer: &mut Writer) -> Result<(), SerializationError> {
+        match mode {
+            None => writer.write_u8(0)?,
+            Some(Self::SingleSender) => writer.write_u8(1)?,
+            Some(Self::Clients(target)) => {
+                writer.write_u8(2)?;
+                target.to_bytes(writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read(reader: &mut Reader) -> Result<Option<Self>, SerializationError> {
+        match reader.read_u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(Self::SingleSender)),
+            2 => Ok(Some(Self::Clients(NetworkTarget::from_bytes(reader)?))),
+            _ => Err(SerializationError::InvalidValue),
+        }
+    }
+}
+
+/// An entity of the snapshot. The entities are the ones of the world that saved the snapshot.
+#[derive(Debug, Default)]
+struct SavedEntity {
+    entity: u64,
+    replicate: Option<SavedMode>,
+    prediction: Option<SavedMode>,
+    interpolation: Option<SavedMode>,
+    parent: Option<u64>,
+    replicate_like: Option<u64>,
+    control: Option<(PeerId, Lifetime)>,
+    rooms: Vec<u16>,
+    /// Index of the component in the component table, and serialized component
+    components: Vec<(usize, Bytes)>,
+}
+
+impl SavedEntity {
+    fn write(&self, writer: &mut Writer) -> Result<(), SerializationError> {
+        writer.write_u64(self.entity)?;
+        SavedMode::write(self.replicate.as_ref(), writer)?;
+        SavedMode::write(self.prediction.as_ref(), writer)?;
+        SavedMode::write(self.interpolation.as_ref(), writer)?;
+        write_entity(self.parent, writer)?;
+        write_entity(self.replicate_like, writer)?;
+        match &self.control {
+            None => writer.write_u8(0)?,
+            Some((owner, lifetime)) => {
+                writer.write_u8(match lifetime {
+                    Lifetime::SessionBased => 1,
+                    Lifetime::Persistent => 2,
+                })?;
+                owner.to_bytes(writer)?;
+            }
+        }
+        writer.write_varint(self.rooms.len() as u64)?;
+        for room in &self.rooms {
+            writer.write_u16(*room)?;
+        }
+        writer.write_varint(self.components.len() as u64)?;
+        for (index, data) in &self.components {
+            writer.write_varint(*index as u64)?;
+            data.to_bytes(writer)?;
+        }
+        Ok(())
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, SerializationError> {
+        let mut saved = Self {
+            entity: reader.read_u64()?,
+            replicate: SavedMode::read(reader)?,
+            prediction: SavedMode::read(reader)?,
+            interpolation: SavedMode::read(reader)?,
+            parent: read_entity(reader)?,
+            replicate_like: read_entity(reader)?,
+            ..Default::default()
+        };
+        let lifetime = match reader.read_u8()? {
+            0 => None,
+            1 => Some(Lifetime::SessionBased),
+            2 => Some(Lifetime::Persistent),
+            _ => return Err(SerializationError::InvalidValue),
+        };
+        if let Some(lifetime) = lifetime {
+            saved.control = Some((PeerId::from_bytes(reader)?, lifetime));
+        }
+        for _ in 0..reader.read_varint()? {
+            saved.rooms.push(reader.read_u16()?);
+        }
+        for _ in 0..reader.read_varint()? {
+            let index = reader.read_varint()? as usize;
+            saved.components.push((index, Bytes::from_bytes(reader)?));
+        }
+        Ok(saved)
+    }
+}
+
+fn write_entity(entity: Option<u64>, writer: &mut Writer) -> Result<(), SerializationError> {
+    match entity {
+        None => writer.write_u8(0)?,
+        Some(entity) => {
+            writer.write_u8(1)?;
+            writer.write_u64(entity)?;
+        }
+    }
+    Ok(())
+}
+
+/// Maps an entity of the snapshot to the spawned entity.
+fn mapped(entity_map: &EntityMap, entity: u64) -> Result<Entity, SerializationError> {
+    Entity::try_from_bits(entity)
+        .and_then(|entity| entity_map.get(&entity).copied())
+        .ok_or(SerializationError::InvalidValue)
+}
+
+fn read_entity(reader: &mut Reader) -> Result<Option<u64>, SerializationError> {
+    match reader.read_u8()? {
+        0 => Ok(None),
+        1 => Ok(Some(reader.read_u64()?)),
+        _ => Err(SerializationError::InvalidValue),
+    }
+}
+
+/// Serializes the replicated entities of the world.
+pub fn save_snapshot(world: &mut World) -> Result<Vec<u8>, SnapshotError> {
+    let mut entities: Vec<Entity> = world
+        .query_filtered::<Entity, Or<(With<Replicate>, With<ReplicateLike>)>>()
+        .iter(world)
+        .collect();
+    entities.sort();
+    save_entities(world, &entities)
+}
+
+/// Serializes some entities of the world, in the same format as [`save_snapshot`].
+///
+/// This is used to move entities to another server. The relations to entities that are not part
+/// of `entities` (the parent and the [`ReplicateLike`] root) are not saved, so the
+/// [`ReplicateLike`] children of an entity should usually be saved with it.
+///
+/// [`ReplicateLike`]: crate::hierarchy::ReplicateLike
+pub fn save_entities(world: &World, entities: &[Entity]) -> Result<Vec<u8>, SnapshotError> {
+    // the components without a stable name are only an error if an entity has them
+    let mut fns = Vec::new();
+    let mut unnamed = Vec::new();
+    for component_fns in registered_fns(world) {
+        match component_fns.stable_name(world) {
+            Some(name) => fns.push((name, component_fns)),
+            None => unnamed.push(component_fns),
+        }
+    }
+    fns.sort_by_key(|(name, _)| *name);
+    if let Some(window) = fns.windows(2).find(|window| window[0].0 == window[1].0) {
+        return Err(SnapshotError::DuplicateName(window[0].0));
+    }
+
+    let mut saved_entities = HashSet::<Entity>::default();
+    let entities: Vec<Entity> = entities
+        .iter()
+        .copied()
+        .filter(|entity| world.get_entity(*entity).is_ok() && saved_entities.insert(*entity))
+        .collect();
+    let in_snapshot = |entity: Entity| saved_entities.contains(&entity).then_some(entity.to_bits());
+
+    let mut writer = Writer::default();
+    writer.extend_from_slice(&SNAPSHOT_MAGIC);
+    writer
+        .write_u16(SNAPSHOT_VERSION)
+        .map_err(SerializationError::from)?;
+    writer.write_varint(fns.len() as u64)?;
+    for (name, _) in &fns {
+        Bytes::from_static(name.as_bytes()).to_bytes(&mut writer)?;
+    }
+    writer.write_varint(entities.len() as u64)?;
+
+    let mut component_writer = Writer::default();
+    for entity in entities {
+        let entity_ref = world.entity(entity);
+        let mut saved = SavedEntity {
+            entity: entity.to_bits(),
+            replicate: SavedMode::save::<()>(world, entity_ref),
+            parent: entity_ref
+                .get::<ChildOf>()
+                .and_then(|child_of| in_snapshot(child_of.parent())),
+            replicate_like: entity_ref
+                .get::<ReplicateLike>()
+                .and_then(|replicate_like| in_snapshot(replicate_like.root)),
+            rooms: entity_ref
+                .get::<Rooms>()
+                .map(|rooms| rooms.rooms().map(RoomId::to_raw).collect())
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+        #[cfg(feature = "prediction")]
+        {
+            saved.prediction = SavedMode::save::<crate::send::PredictedSend>(world, entity_ref);
+        }
+        #[cfg(feature = "interpolation")]
+        {
+            saved.interpolation =
+                SavedMode::save::<crate::send::InterpolatedSend>(world, entity_ref);
+        }
+        if let Some(controlled_by) = entity_ref.get::<ControlledBy>() {
+            match world.get::<RemoteId>(controlled_by.owner) {
+                Some(remote_id) => saved.control = Some((remote_id.0, controlled_by.lifetime)),
+                None => warn!(
+                    ?entity,
+                    owner = ?controlled_by.owner,
+                    "The owner of the entity has no RemoteId, its control is not saved"
+                ),
+            }
+        } else if let Some(pending) = entity_ref.get::<PendingControlledBy>() {
+            saved.control = Some((pending.owner, pending.lifetime));
+        }
+        for (index, (_, fns)) in fns.iter().enumerate() {
+            if fns.save(entity_ref, &mut component_writer)? {
+                saved
+                    .components
+                    .push((index, component_writer.take_written()));
+            }
+        }
+        for fns in &unnamed {
+            if fns.save(entity_ref, &mut component_writer)? {
+                return Err(SnapshotError::UnnamedComponent(fns.type_name));
+            }
+        }
+        saved.write(&mut writer)?;
+    }
+    debug!(num_bytes = writer.len(), "Saved replication snapshot");
+    Ok(writer.into_bytes().to_vec())
+}
+
+/// Spawns the entities of a snapshot created by [`save_snapshot`].
+///
+/// The entities are spawned with new ids: the returned map goes from the entities of the world
+/// that saved the snapshot to the spawned entities. If the snapshot cannot be loaded, the entities
+/// that were already spawned are despawned.
+pub fn load_snapshot(world: &mut World, bytes: &[u8]) -> Result<EntityMap, SnapshotError> {
+    let mut reader = Reader::from(Bytes::copy_from_slice(bytes));
+    if reader.remaining_slice().get(..SNAPSHOT_MAGIC.len()) != Some(&SNAPSHOT_MAGIC[..]) {
+        return Err(SnapshotError::InvalidHeader);
+    }
+    reader.set_position(SNAPSHOT_MAGIC.len() as u64);
+    let version = reader
+        .read_u16()
+        .map_err(|_| SnapshotError::InvalidHeader)?;
+    if version != SNAPSHOT_VERSION {
+        return Err(SnapshotError::UnsupportedVersion(version));
+    }
+
+    let registered: Vec<(Option<&'static str>, SnapshotFns)> = registered_fns(world)
+        .into_iter()
+        .map(|fns| (fns.stable_name(world), fns))
+        .collect();
+    // the components that are not registered anymore are only an error if an entity has them
+    let mut table = Vec::new();
+    for _ in 0..reader.read_varint()? {
+        let name = Bytes::from_bytes(&mut reader)?;
+        let name = core::str::from_utf8(&name).map_err(|_| SerializationError::InvalidValue)?;
+        let component_fns = registered
+            .iter()
+            .find(|(registered_name, _)| *registered_name == Some(name))
+            .map(|(_, fns)| *fns);
+        table.push((name.to_string(), component_fns));
+    }
+    let mut saved_entities = Vec::new();
+    let mut entities = Vec::new();
+    for _ in 0..reader.read_varint()? {
+        let saved = SavedEntity::read(&mut reader)?;
+        entities.push(Entity::try_from_bits(saved.entity).ok_or(SerializationError::InvalidValue)?);
+        for (index, _) in &saved.components {
+            match table.get(*index) {
+                Some((_, Some(_))) => {}
+                Some((name, None)) => {
+                    return Err(SnapshotError::UnknownComponent(name.clone()));
+                }
+                None => return Err(SerializationError::InvalidValue.into()),
+            }
+        }
+        saved_entities.push(saved);
+    }
+    let fns: Vec<Option<SnapshotFns>> = table.into_iter().map(|(_, fns)| fns).collect();
+
+    let mut entity_map = EntityMap::default();
+    for entity in entities {
+        entity_map.set_mapped(entity, world.spawn_empty().id());
+    }
+    if let Err(e) = restore_entities(world, &fns, &saved_entities, &mut entity_map) {
+        for entity in entity_map.values() {
+            let _ = world.try_despawn(*entity);
+        }
+        return Err(e.into());
+    }
+    debug!(
+        num_entities = saved_entities.len(),
+        "Loaded replication snapshot"
+    );
+    Ok(entity_map)
+}
+
+fn registered_fns(world: &World) -> Vec<SnapshotFns> {
+    world
+        .get_resource::<ComponentRegistry>()
+        .map(|registry| {
+            registry
+                .component_metadata_map
+                .values()
+                .filter_map(|metadata| metadata.snapshot)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn restore_entities(
+    world: &mut World,
+    fns: &[Option<SnapshotFns>],
+    saved_entities: &[SavedEntity],
+    entity_map: &mut EntityMap,
+) -> Result<(), SerializationError> {
+    // the relations are inserted first, so that the hooks of the replication targets see the
+    // whole hierarchy
+    let mut rooms_to_reserve = Vec::new();
+    for saved in saved_entities {
+        let mut entity_mut = world.entity_mut(mapped(entity_map, saved.entity)?);
+        if let Some(parent) = saved.parent {
+            entity_mut.insert(ChildOf(mapped(entity_map, parent)?));
+        }
+        if let Some(root) = saved.replicate_like {
+            entity_mut.insert(ReplicateLike {
+                root: mapped(entity_map, root)?,
+            });
+        }
+        if let Some((owner, lifetime)) = saved.control {
+            entity_mut.insert(PendingControlledBy { owner, lifetime });
+        }
+        if !saved.rooms.is_empty() {
+            let rooms: Vec<RoomId> = saved.rooms.iter().copied().map(RoomId::from_raw).collect();
+            entity_mut.insert(Rooms::from(rooms.iter().copied()));
+            rooms_to_reserve.extend(rooms);
+        }
+    }
+    if let Some(mut allocator) = world.get_resource_mut::<RoomAllocator>() {
+        for room in rooms_to_reserve {
+            allocator.reserve(room);
+        }
+    }
+
+    for saved in saved_entities {
+        let entity = mapped(entity_map, saved.entity)?;
+        for (index, data) in &saved.components {
+            // the components of the entities were checked when reading the snapshot
+            let Some(fns) = fns[*index] else {
+                continue;
+            };
+            let mut reader = Reader::from(data.clone());
+            fns.load(&mut reader, &mut world.entity_mut(entity), entity_map)?;
+        }
+    }
+
+    for saved in saved_entities {
+        let mut entity_mut = world.entity_mut(mapped(entity_map, saved.entity)?);
+        if let Some(mode) = &saved.replicate {
+            entity_mut.insert(mode.to_target::<()>());
+        }
+        #[cfg(feature = "prediction")]
+        if let Some(mode) = &saved.prediction {
+            entity_mut.insert(mode.to_target::<crate::send::PredictedSend>());
+        }
+        #[cfg(feature = "interpolation")]
+        if let Some(mode) = &saved.interpolation {
+            entity_mut.insert(mode.to_target::<crate::send::InterpolatedSend>());
+        }
+        trace!(entity = ?entity_mut.id(), "Restored entity from snapshot");
+    }
+    Ok(())
+}
+
+/// Saves the snapshot in a file.
+///
+/// The snapshot is written to a temporary file that then replaces `path`, so that a crash while
+/// saving doesn't corrupt the previous snapshot.
+#[cfg(feature = "std")]
+pub fn save_snapshot_to_file(
+    world: &mut World,
+    path: impl AsRef<std::path::Path>,
+) -> Result<(), SnapshotError> {
+    let path = path.as_ref();
+    let bytes = save_snapshot(world)?;
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, bytes)?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Loads a snapshot saved with [`save_snapshot_to_file`].
+#[cfg(feature = "std")]
+pub fn load_snapshot_from_file(
+    world: &mut World,
+    path: impl AsRef<std::path::Path>,
+) -> Result<EntityMap, SnapshotError> {
+    let bytes = std::fs::read(path)?;
+    load_snapshot(world, &bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::replication::AppComponentExt;
+    use bevy_app::App;
+    use bevy_ecs::component::Component;
+    use bevy_ecs::entity::MapEntities;
+    use bevy_ecs::reflect::AppTypeRegistry;
+    use bevy_reflect::Reflect;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Component, Serialize, Deserialize, Debug, PartialEq)]
+    struct Health(u32);
+
+    #[derive(Component, Debug, PartialEq, MapEntities)]
+    struct Target(#[entities] Entity);
+
+    #[derive(Component, Serialize, Deserialize, Debug, PartialEq, Reflect)]
+    struct Mana(u32);
+
+    fn register_health(app: &mut App) {
+        app.component::<Health>()
+            .add_snapshot()
+            .with_snapshot_name("health");
+    }
+
+    fn register_target(app: &mut App) {
+        app.component::<Target>()
+            .add_custom_snapshot(
+                |target, writer| Ok(writer.write_u64(target.0.to_bits())?),
+                |reader| Ok(Target(Entity::from_bits(reader.read_u64()?))),
+            )
+            .add_snapshot_map_entities()
+            .with_snapshot_name("target");
+    }
+
+    #[test]
+    fn save_and_load_snapshot() {
+        let mut server = App::new();
+        server.init_resource::<RoomAllocator>();
+        register_health(&mut server);
+        register_target(&mut server);
+        let room = server
+            .world_mut()
+            .resource_mut::<RoomAllocator>()
+            .allocate();
+        let link = server.world_mut().spawn(RemoteId(PeerId::Netcode(7))).id();
+        let root = server
+            .world_mut()
+            .spawn((
+                Replicate::to_clients(NetworkTarget::All),
+                Health(10),
+                Rooms::single(room),
+                ControlledBy {
+                    owner: link,
+                    lifetime: Lifetime::Persistent,
+                },
+            ))
+            .id();
+        let child = server
+            .world_mut()
+            .spawn((ChildOf(root), ReplicateLike { root }, Target(root)))
+            .id();
+        // entities that are not replicated are not saved
+        server.world_mut().spawn(Health(0));
+        let bytes = save_snapshot(server.world_mut()).unwrap();
+
+        // the components can be registered in another order
+        let mut restored = App::new();
+        restored.init_resource::<RoomAllocator>();
+        register_target(&mut restored);
+        register_health(&mut restored);
+        let entity_map = load_snapshot(restored.world_mut(), &bytes).unwrap();
+        assert_eq!(entity_map.len(), 2);
+        let new_root = entity_map[&root];
+        let new_child = entity_map[&child];
+
+        let world = restored.world();
+        assert_eq!(world.get::<Health>(new_root), Some(&Health(10)));
+        assert_eq!(
+            world.get::<Replicate>(new_root).unwrap().mode(),
+            &ReplicationMode::SingleServer(NetworkTarget::All)
+        );
+        assert!(world.get::<Rooms>(new_root).unwrap().contains_room(room));
+        assert_eq!(
+            world.get::<PendingControlledBy>(new_root),
+            Some(&PendingControlledBy {
+                owner: PeerId::Netcode(7),
+                lifetime: Lifetime::Persistent,
+            })
+        );
+        assert_eq!(world.get::<ChildOf>(new_child).unwrap().parent(), new_root);
+        assert_eq!(
+            world.get::<ReplicateLike>(new_child),
+            Some(&ReplicateLike { root: new_root })
+        );
+        assert_eq!(world.get::<Target>(new_child), Some(&Target(new_root)));
+        // restored rooms are not allocated again
+        assert_ne!(
+            restored
+                .world_mut()
+                .resource_mut::<RoomAllocator>()
+                .allocate(),
+            room
+        );
+    }
+
+    #[test]
+    fn save_some_entities() {
+        let mut server = App::new();
+        register_health(&mut server);
+        let root = server
+            .world_mut()
+            .spawn((Replicate::to_clients(NetworkTarget::All), Health(1)))
+            .id();
+        let child = server
+            .world_mut()
+            .spawn((ChildOf(root), ReplicateLike { root }, Health(2)))
+            .id();
+        let other = server
+            .world_mut()
+            .spawn((Replicate::to_clients(NetworkTarget::All), Health(3)))
+            .id();
+        // duplicated and despawned entities are ignored
+        let despawned = server.world_mut().spawn_empty().id();
+        server.world_mut().despawn(despawned);
+        let bytes = save_entities(server.world(), &[child, other, child, despawned]).unwrap();
+
+        let mut restored = App::new();
+        register_health(&mut restored);
+        let entity_map = load_snapshot(restored.world_mut(), &bytes).unwrap();
+        assert_eq!(entity_map.len(), 2);
+        assert!(!entity_map.contains_key(&root));
+        let world = restored.world();
+        let new_child = entity_map[&child];
+        assert_eq!(world.get::<Health>(new_child), Some(&Health(2)));
+        // the root was not saved, so the relations to it are dropped
+        assert!(world.get::<ChildOf>(new_child).is_none());
+        assert!(world.get::<ReplicateLike>(new_child).is_none());
+        assert_eq!(world.get::<Health>(entity_map[&other]), Some(&Health(3)));
+    }
+
+    #[test]
+    fn load_invalid_snapshot() {
+        let mut server = App::new();
+        register_health(&mut server);
+        register_target(&mut server);
+        server
+            .world_mut()
+            .spawn((Replicate::to_clients(NetworkTarget::All), Health(1)));
+        server.world_mut().spawn((
+            Replicate::to_clients(NetworkTarget::All),
+            Target(Entity::PLACEHOLDER),
+        ));
+        let bytes = save_snapshot(server.world_mut()).unwrap();
+
+        let mut restored = App::new();
+        register_health(&mut restored);
+        assert!(matches!(
+            load_snapshot(restored.world_mut(), b"not a snapshot"),
+            Err(SnapshotError::InvalidHeader)
+        ));
+        assert!(matches!(
+            load_snapshot(restored.world_mut(), &bytes),
+            Err(SnapshotError::UnknownComponent(_))
+        ));
+        assert_eq!(
+            restored
+                .world_mut()
+                .query::<&Health>()
+                .iter(restored.world())
+                .count(),
+            0
+        );
+    }
+
+    /// Components are identified by their snapshot name or by their reflected type path, never by
+    /// their unstable type name.
+    #[test]
+    fn components_need_a_stable_name() {
+        let mut server = App::new();
+        server.component::<Mana>().add_snapshot();
+        server
+            .world_mut()
+            .spawn((Replicate::to_clients(NetworkTarget::All), Mana(5)));
+        assert!(matches!(
+            save_snapshot(server.world_mut()),
+            Err(SnapshotError::UnnamedComponent(_))
+        ));
+
+        server.world_mut().init_resource::<AppTypeRegistry>();
+        server
+            .world()
+            .resource::<AppTypeRegistry>()
+            .write()
+            .register::<Mana>();
+        let bytes = save_snapshot(server.world_mut()).unwrap();
+
+        let mut restored = App::new();
+        restored.world_mut().init_resource::<AppTypeRegistry>();
+        restored
+            .world()
+            .resource::<AppTypeRegistry>()
+            .write()
+            .register::<Mana>();
+        restored.component::<Mana>().add_snapshot();
+        load_snapshot(restored.world_mut(), &bytes).unwrap();
+        assert_eq!(
+            restored
+                .world_mut()
+                .query::<&Mana>()
+                .single(restored.world())
+                .unwrap(),
+            &Mana(5)
+        );
+
+        // two components cannot share a name
+        register_health(&mut server);
+        server.component::<Mana>().with_snapshot_name("health");
+        assert!(matches!(
+            save_snapshot(server.world_mut()),
+            Err(SnapshotError::DuplicateName("health"))
+        ));
+    }
+}
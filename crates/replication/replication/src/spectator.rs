@@ -0,0 +1,328 @@
+//! Spectator connections, that watch the game without taking part in it.
+//!
+//! Insert [`Spectator`] on the link entity of a client, usually in the `On<Add, LinkOf>` observer
+//! next to the [`ReplicationSender`](crate::send::ReplicationSender):
+//! - the spectator never predicts entities: [`PredictionTarget`](crate::send::PredictionTarget)s
+//!   are not replicated to it, even if their [`NetworkTarget`](lightyear_connection::network_target::NetworkTarget)
+//!   includes it;
+//! - the server ignores its inputs and doesn't rebroadcast the inputs of the players to it;
+//! - the replication messages can be held on the server for [`Spectator::delay`] before being
+//!   sent, so that a spectator watching a live match cannot relay information to the players.
+//!
+//! Only the replication messages are delayed. The delay is sent to the spectator client in the
+//! [`SenderMetadata`](crate::metadata::SenderMetadata), and the client keeps its interpolation
+//! timeline that much further behind the server, so that the interpolated entities always have
+//! a received state to interpolate towards.
+//!
+//! Delayed spectators are opt-in: the delay is capped at
+//! [`LightyearRepliconServerBackend::max_spectator_delay`], which is zero by default. The spectator
+//! acknowledges the replication messages that much later too, so the server keeps the
+//! unacknowledged mutations for the maximum delay on top of replicon's default timeout before
+//! treating them as lost.
+//!
+//! The visibility of a spectator is managed like for any other client, with [`Rooms`] or a
+//! [`SpatialViewer`] on its link entity. [`Spectating`] makes the spectator follow the view of a
+//! player instead: the [`Rooms`] and the [`SpatialViewer`] of the player's link are copied to the
+//! spectator's link.
+//!
+//! ```rust,ignore
+//! app.add_plugins(
+//!     ServerPlugins::default()
+//!         .set(LightyearRepliconServerBackend::default().with_max_spectator_delay(Duration::from_secs(60))),
+//! );
+//!
+//! fn handle_new_spectator(trigger: On<Add, LinkOf>, mut commands: Commands) {
+//!     commands.entity(trigger.entity).insert((
+//!         ReplicationSender,
+//!         Spectator::default().with_delay(Duration::from_secs(30)),
+//!     ));
+//! }
+//!
+//! // switch the camera of the spectator to another player
+//! commands.entity(spectator_link).insert(Spectating { target: player_link });
+//! ```
+//!
+//! [`LightyearRepliconServerBackend::max_spectator_delay`]: crate::LightyearRepliconServerBackend::max_spectator_delay
+use crate::ReplicationSystems;
+use crate::metadata::{MetadataSenders, ReplicationMetadata, send_metadata};
+use crate::visibility::room::Rooms;
+use crate::visibility::spatial::SpatialViewer;
+use alloc::collections::VecDeque;
+use bevy_app::{App, Plugin, PostUpdate};
+use bevy_ecs::prelude::*;
+use bevy_reflect::Reflect;
+use core::time::Duration;
+use lightyear_core::tick::TickDuration;
+use lightyear_link::SendPayload;
+use lightyear_transport::channel::ChannelKind;
+#[allow(unused_imports)]
+use tracing::{trace, warn};
+
+/// Longest delay of a [`Spectator`]. Longer delays are reduced to this one.
+///
+/// It is set from [`LightyearRepliconServerBackend::max_spectator_delay`](crate::LightyearRepliconServerBackend::max_spectator_delay),
+/// since replicon's mutations timeout is raised by the same duration.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MaxSpectatorDelay(pub Duration);
+
+/// Component added on the link entity of a client that only watches the game.
+#[derive(Component, Debug, Clone, Default, PartialEq, Reflect)]
+#[require(DelayedMessages)]
+pub struct Spectator {
+    /// Duration during which the replication messages are held on the server before being sent
+    /// to the spectator, at most [`MaxSpectatorDelay`].
+    pub delay: Duration,
+}
+
+impl Spectator {
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// The delay that is applied to the replication messages, given the [`MaxSpectatorDelay`].
+    pub fn effective_delay(&self, max_delay: Duration) -> Duration {
+        self.delay.min(max_delay)
+    }
+}
+
+/// Component added on the link entity of a [`Spectator`] to follow the view of a player.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub struct Spectating {
+    /// Link entity of the followed client
+    pub target: Entity,
+}
+
+/// Replication messages waiting for the delay of a [`Spectator`] to elapse.
+#[derive(Component, Debug, Default)]
+pub(crate) struct DelayedMessages {
+    /// Messages with the time at which they can be sent, in the order in which they were produced
+    messages: VecDeque<(Duration, ChannelKind, SendPayload)>,
+}
+
+impl DelayedMessages {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    pub(crate) fn push(&mut self, send_at: Duration, channel: ChannelKind, message: SendPayload) {
+        self.messages.push_back((send_at, channel, message));
+    }
+
+    /// Messages that can be sent at `now`.
+    ///
+    /// A message is never released before the messages produced earlier, even if the delay was
+    /// reduced in between, so that the order of the replication messages is preserved.
+    pub(crate) fn release(
+        &mut self,
+        now: Duration,
+    ) -> impl Iterator<Item = (ChannelKind, SendPayload)> + '_ {
+        core::iter::from_fn(move || {
+            if self.messages.front()?.0 > now {
+                return None;
+            }
+            self.messages
+                .pop_front()
+                .map(|(_, channel, message)| (channel, message))
+        })
+    }
+}
+
+/// Plugin that handles the [`Spectator`] links. It is added by the server replication plugins.
+pub struct SpectatorPlugin {
+    /// Longest delay of a [`Spectator`]
+    pub max_delay: Duration,
+}
+
+impl SpectatorPlugin {
+    /// Stop replicating the prediction markers to a link that becomes a spectator.
+    #[cfg(feature = "prediction")]
+    fn hide_predicted(
+        trigger: On<Add, Spectator>,
+        predicted_bit: Res<crate::send::PredictedBit>,
+        predicted: Query<Entity, With<crate::send::PredictionTarget>>,
+        mut visibilities: Query<
+            &mut bevy_replicon::server::visibility::client_visibility::ClientVisibility,
+        >,
+    ) {
+        let Ok(mut visibility) = visibilities.get_mut(trigger.entity) else {
+            return;
+        };
+        for entity in predicted.iter() {
+            visibility.set(entity, **predicted_bit, false);
+        }
+    }
+
+    /// Send the metadata again when the delay of a connected spectator changes
+    fn resend_metadata(
+        trigger: On<Insert, Spectator>,
+        metadata: Res<ReplicationMetadata>,
+        tick_duration: Res<TickDuration>,
+        max_delay: Res<MaxSpectatorDelay>,
+        spectators: Query<&Spectator>,
+        query: MetadataSenders<'_, '_>,
+    ) {
+        let Ok(spectator) = spectators.get(trigger.entity) else {
+            return;
+        };
+        if spectator.delay > max_delay.0 {
+            warn!(
+                entity = ?trigger.entity,
+                delay = ?spectator.delay,
+                max_delay = ?max_delay.0,
+                "The spectator delay is longer than the maximum spectator delay of the server backend, it is reduced to the maximum"
+            );
+        }
+        send_metadata(
+            trigger.entity,
+            &metadata,
+            &tick_duration,
+            spectator.effective_delay(max_delay.0),
+            query,
+        );
+    }
+
+    /// Copy the interest of the followed players to their spectators.
+    fn follow(
+        mut commands: Commands,
+        mut spectators: Query<
+            (
+                Entity,
+                &Spectating,
+                Option<&Rooms>,
+                Option<&mut SpatialViewer>,
+            ),
+            With<Spectator>,
+        >,
+        targets: Query<(Option<&Rooms>, Option<&SpatialViewer>), Without<Spectator>>,
+    ) {
+        for (spectator, spectating, rooms, viewer) in spectators.iter_mut() {
+            let Ok((target_rooms, target_viewer)) = targets.get(spectating.target) else {
+                trace!(
+                    ?spectator,
+                    target = ?spectating.target,
+                    "The followed client doesn't exist, keeping the current interest"
+                );
+                continue;
+            };
+            match (rooms, target_rooms) {
+                (Some(rooms), Some(target_rooms)) if rooms.rooms().eq(target_rooms.rooms()) => {}
+                (_, Some(target_rooms)) => {
+                    commands
+                        .entity(spectator)
+                        .insert(Rooms::from(target_rooms.rooms()));
+                }
+                (Some(_), None) => {
+                    commands.entity(spectator).remove::<Rooms>();
+                }
+                (None, None) => {}
+            }
+            match (viewer, target_viewer) {
+                (Some(mut viewer), Some(target_viewer)) => {
+                    if viewer.anchor != target_viewer.anchor
+                        || viewer.radius != target_viewer.radius
+                        || viewer.hysteresis != target_viewer.hysteresis
+                    {
+                        viewer.anchor = target_viewer.anchor;
+                        viewer.radius = target_viewer.radius;
+                        viewer.hysteresis = target_viewer.hysteresis;
+                    }
+                }
+                (None, Some(target_viewer)) => {
+                    commands.entity(spectator).insert(
+                        SpatialViewer::new(target_viewer.anchor, target_viewer.radius)
+                            .with_hysteresis(target_viewer.hysteresis),
+                    );
+                }
+                (Some(_), None) => {
+                    commands.entity(spectator).remove::<SpatialViewer>();
+                }
+                (None, None) => {}
+            }
+        }
+    }
+}
+
+impl Plugin for SpectatorPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MaxSpectatorDelay(self.max_delay));
+        app.add_observer(Self::resend_metadata);
+        #[cfg(feature = "prediction")]
+        app.add_observer(Self::hide_predicted);
+        app.add_systems(PostUpdate, Self::follow.before(ReplicationSystems::Send));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::visibility::room::RoomAllocator;
+    use alloc::vec::Vec;
+    use bevy_app::Update;
+    use bytes::Bytes;
+    use test_log::test;
+
+    #[test]
+    fn delayed_messages_keep_their_order() {
+        let mut delayed = DelayedMessages::default();
+        let channel = ChannelKind::of::<()>();
+        delayed.push(Duration::from_secs(2), channel, Bytes::from_static(b"a"));
+        // the delay was reduced: the second message is due before the first one
+        delayed.push(Duration::from_secs(1), channel, Bytes::from_static(b"b"));
+        delayed.push(Duration::from_secs(3), channel, Bytes::from_static(b"c"));
+
+        assert_eq!(delayed.release(Duration::from_secs(1)).count(), 0);
+        let released: Vec<_> = delayed
+            .release(Duration::from_secs(2))
+            .map(|(_, message)| message)
+            .collect();
+        assert_eq!(released, [&b"a"[..], &b"b"[..]]);
+        assert!(!delayed.is_empty());
+        assert_eq!(delayed.release(Duration::from_secs(3)).count(), 1);
+        assert!(delayed.is_empty());
+    }
+
+    #[test]
+    fn spectator_follows_the_interest_of_a_player() {
+        let mut app = App::new();
+        app.add_systems(Update, SpectatorPlugin::follow);
+        let mut allocator = RoomAllocator::default();
+        let (room_a, room_b) = (allocator.allocate(), allocator.allocate());
+
+        let anchor = app.world_mut().spawn_empty().id();
+        let player = app
+            .world_mut()
+            .spawn((Rooms::single(room_a), SpatialViewer::new(anchor, 10.0)))
+            .id();
+        let spectator = app
+            .world_mut()
+            .spawn((Spectator::default(), Rooms::single(room_b)))
+            .id();
+        app.update();
+        // without Spectating, the spectator keeps its own interest
+        assert!(
+            app.world()
+                .get::<Rooms>(spectator)
+                .unwrap()
+                .contains_room(room_b)
+        );
+
+        app.world_mut()
+            .entity_mut(spectator)
+            .insert(Spectating { target: player });
+        app.update();
+        let rooms = app.world().get::<Rooms>(spectator).unwrap();
+        assert!(rooms.contains_room(room_a));
+        assert!(!rooms.contains_room(room_b));
+        let viewer = app.world().get::<SpatialViewer>(spectator).unwrap();
+        assert_eq!(viewer.anchor, anchor);
+        assert_eq!(viewer.radius, 10.0);
+
+        app.world_mut()
+            .entity_mut(player)
+            .remove::<(Rooms, SpatialViewer)>();
+        app.update();
+        assert!(app.world().get::<Rooms>(spectator).is_none());
+        assert!(app.world().get::<SpatialViewer>(spectator).is_none());
+    }
+}
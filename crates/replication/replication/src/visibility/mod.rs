@@ -1,7 +1,8 @@
 //! Replication visibility and interest management.
 //!
-//! Lightyear provides entity-level helpers in [`immediate`] and room-based
-//! interest management in [`room`]. For component-level visibility, register a
+//! Lightyear provides entity-level helpers in [`immediate`], room-based
+//! interest management in [`room`] and distance-based interest management in
+//! [`spatial`]. For component-level visibility, register a
 //! Replicon [`VisibilityFilter`] whose scope contains the components to gate.
 //! Add the filter component to the replicated entity and its
 //! [`VisibilityFilter::ClientComponent`] to each sender link entity. If both
@@ -53,3 +54,4 @@ pub mod immediate;
 
 pub mod error;
 pub mod room;
+pub mod spatial;
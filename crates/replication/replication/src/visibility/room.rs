@@ -63,6 +63,16 @@ impl RoomId {
     pub fn as_usize(&self) -> usize {
         self.0 as usize
     }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn from_raw(id: u16) -> Self {
+        Self(id)
+    }
+
+    #[cfg(feature = "server")]
+    pub(crate) fn to_raw(self) -> u16 {
+        self.0
+    }
 }
 impl From<RoomId> for usize {
     fn from(value: RoomId) -> Self {
@@ -87,6 +97,15 @@ impl RoomAllocator {
         self.next_id = RoomId(self.next_id.0.checked_add(1).expect("RoomId overflow"));
         id
     }
+
+    /// Makes sure that `room` is never allocated again, for example because it was restored from
+    /// a [snapshot](crate::snapshot).
+    #[cfg(feature = "server")]
+    pub(crate) fn reserve(&mut self, room: RoomId) {
+        if room.0 >= self.next_id.0 {
+            self.next_id = RoomId(room.0.checked_add(1).expect("RoomId overflow"));
+        }
+    }
 }
 
 /// A [`Rooms`] is a component that represents the list of rooms that the entity or client belongs to.
@@ -0,0 +1,397 @@
+/*! Spatial interest management, where entities are only replicated to the clients that are close to them
+
+# Spatial grid
+
+[`Rooms`](super::room::Rooms) require you to allocate rooms and move entities between them
+yourself. The [`SpatialGridPlugin`] instead buckets every entity with a [`SpatialInterest`]
+marker into a uniform grid keyed on its position, and computes for each [`SpatialViewer`] which of
+those entities are within its view radius.
+
+The plugin updates the per-client visibility through the same machinery as
+[`VisibilityExt`]: an entity becomes visible to a client when it enters the viewer's radius, and is
+hidden (and despawned on the client) once it is further away than the radius plus the viewer's
+hysteresis margin. The margin prevents entities that move back and forth around the edge of the
+view radius, or around a cell boundary, from being spawned and despawned repeatedly.
+
+Entities with [`SpatialInterest`] are hidden from clients whose link has no [`SpatialViewer`], so
+removing the viewer of a link hides every entity that it was seeing.
+The plugin owns the manual visibility of those entities: don't combine it with manual
+[`VisibilityExt`] calls on the same entities.
+
+## Example
+
+```rust,ignore
+app.add_plugins(SpatialGridPlugin::<Transform>::new(50.0));
+
+// `sender_link` is the entity representing this client connection, and `player` the entity
+// around which the client should see other entities.
+commands
+    .entity(sender_link)
+    .insert(SpatialViewer::new(player, 100.0).with_hysteresis(10.0));
+commands.spawn((Replicate::to_clients(NetworkTarget::All), SpatialInterest, Transform::default()));
+```
+*/
+use crate::ReplicationSystems;
+use crate::visibility::immediate::VisibilityExt;
+use alloc::vec::Vec;
+use bevy_app::{App, Plugin, PostUpdate};
+use bevy_ecs::entity::{EntityHashMap, EntityHashSet};
+use bevy_ecs::prelude::*;
+use bevy_math::{IVec3, Vec3};
+use bevy_platform::collections::HashMap;
+use bevy_replicon::server::visibility::client_visibility::ClientVisibility;
+use bevy_transform::prelude::Transform;
+use core::marker::PhantomData;
+#[allow(unused_imports)]
+use tracing::{info, trace};
+
+/// Component that provides the position used to place an entity in the [`SpatialGridPlugin`].
+///
+/// Implemented for [`Transform`], and for Avian's `Position` when the `avian2d` or `avian3d`
+/// feature is enabled.
+pub trait SpatialPosition: Component {
+    fn spatial_position(&self) -> Vec3;
+}
+
+/// Marker component for replicated entities whose visibility is managed by the
+/// [`SpatialGridPlugin`].
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, bevy_reflect::Reflect)]
+pub struct SpatialInterest;
+
+/// Component added on a [`ReplicationSender`](crate::send::ReplicationSender) link entity to define
+/// the area in which its client sees entities with [`SpatialInterest`].
+#[derive(Component, Debug, Clone)]
+pub struct SpatialViewer {
+    /// Entity whose position is the center of the view area. Usually the client's player entity.
+    pub anchor: Entity,
+    /// Entities closer than this distance to the anchor become visible.
+    pub radius: f32,
+    /// Visible entities only become hidden once they are further than `radius + hysteresis`.
+    pub hysteresis: f32,
+    /// Entities currently visible to this viewer.
+    visible: EntityHashSet,
+}
+
+impl SpatialViewer {
+    pub fn new(anchor: Entity, radius: f32) -> Self {
+        Self {
+            anchor,
+            radius,
+            hysteresis: 0.0,
+            visible: EntityHashSet::default(),
+        }
+    }
+
+    pub fn with_hysteresis(mut self, hysteresis: f32) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    /// Returns true if the entity is currently visible to this viewer.
+    pub fn is_visible(&self, entity: Entity) -> bool {
+        self.visible.contains(&entity)
+    }
+}
+
+/// Uniform grid of the entities with [`SpatialInterest`], keyed on the position component `P`.
+#[derive(Resource, Debug)]
+pub struct SpatialGrid<P> {
+    cell_size: f32,
+    cells: HashMap<IVec3, Vec<Entity>>,
+    /// Cell and position of every entity in the grid.
+    entities: EntityHashMap<(IVec3, Vec3)>,
+    marker: PhantomData<P>,
+}
+
+impl<P> SpatialGrid<P> {
+    pub fn new(cell_size: f32) -> Self {
+        assert!(
+            cell_size > 0.0,
+            "the spatial grid cell size must be positive"
+        );
+        Self {
+            cell_size,
+            cells: HashMap::default(),
+            entities: EntityHashMap::default(),
+            marker: PhantomData,
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Cell that contains the position.
+    pub fn cell(&self, position: Vec3) -> IVec3 {
+        (position / self.cell_size).floor().as_ivec3()
+    }
+
+    /// Insert the entity in the grid, or move it if it was already present.
+    pub fn insert(&mut self, entity: Entity, position: Vec3) {
+        let cell = self.cell(position);
+        if let Some((previous_cell, previous_position)) = self.entities.get_mut(&entity) {
+            *previous_position = position;
+            if *previous_cell == cell {
+                return;
+            }
+            let previous_cell = core::mem::replace(previous_cell, cell);
+            self.remove_from_cell(entity, previous_cell);
+        } else {
+            self.entities.insert(entity, (cell, position));
+        }
+        self.cells.entry(cell).or_default().push(entity);
+    }
+
+    /// Remove the entity from the grid.
+    pub fn remove(&mut self, entity: Entity) {
+        if let Some((cell, _)) = self.entities.remove(&entity) {
+            self.remove_from_cell(entity, cell);
+        }
+    }
+
+    fn remove_from_cell(&mut self, entity: Entity, cell: IVec3) {
+        if let Some(entities) = self.cells.get_mut(&cell) {
+            entities.retain(|e| *e != entity);
+            if entities.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+
+    /// Compute the entities visible from `center`.
+    ///
+    /// An entity is visible if it is within `radius` of `center`, or if it was `previously_visible`
+    /// and is still within `radius + hysteresis`.
+    pub fn visible_from(
+        &self,
+        center: Vec3,
+        radius: f32,
+        hysteresis: f32,
+        previously_visible: &EntityHashSet,
+    ) -> EntityHashSet {
+        let max_radius = radius + hysteresis.max(0.0);
+        let min_cell = self.cell(center - Vec3::splat(max_radius));
+        let max_cell = self.cell(center + Vec3::splat(max_radius));
+        let mut visible = EntityHashSet::default();
+        for x in min_cell.x..=max_cell.x {
+            for y in min_cell.y..=max_cell.y {
+                for z in min_cell.z..=max_cell.z {
+                    let Some(entities) = self.cells.get(&IVec3::new(x, y, z)) else {
+                        continue;
+                    };
+                    for entity in entities {
+                        let (_, position) = self.entities[entity];
+                        let distance_squared = position.distance_squared(center);
+                        let range = if previously_visible.contains(entity) {
+                            max_radius
+                        } else {
+                            radius
+                        };
+                        if distance_squared <= range * range {
+                            visible.insert(*entity);
+                        }
+                    }
+                }
+            }
+        }
+        visible
+    }
+}
+
+/// Plugin that manages the visibility of [`SpatialInterest`] entities with a uniform grid keyed on
+/// the position component `P`.
+pub struct SpatialGridPlugin<P: SpatialPosition = Transform> {
+    /// Size of the grid cells. A cell size close to the typical view radius works well.
+    pub cell_size: f32,
+    marker: PhantomData<P>,
+}
+
+impl<P: SpatialPosition> SpatialGridPlugin<P> {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<P: SpatialPosition> Plugin for SpatialGridPlugin<P> {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SpatialGrid::<P>::new(self.cell_size));
+        app.add_observer(hide_new_interest_entity);
+        app.add_observer(hide_interest_entities_from_new_sender);
+        app.add_observer(hide_entities_of_removed_viewer::<P>);
+        app.add_systems(
+            PostUpdate,
+            (update_spatial_grid::<P>, update_spatial_visibility::<P>)
+                .chain()
+                .before(ReplicationSystems::Send),
+        );
+    }
+}
+
+/// Entities with [`SpatialInterest`] start hidden until a viewer sees them.
+fn hide_new_interest_entity(
+    trigger: On<Add, SpatialInterest>,
+    mut commands: Commands,
+    senders: Query<Entity, With<ClientVisibility>>,
+) {
+    for sender in senders.iter() {
+        commands.lose_visibility(trigger.entity, sender);
+    }
+}
+
+fn hide_interest_entities_from_new_sender(
+    trigger: On<Add, ClientVisibility>,
+    mut commands: Commands,
+    entities: Query<Entity, With<SpatialInterest>>,
+) {
+    for entity in entities.iter() {
+        commands.lose_visibility(entity, trigger.entity);
+    }
+}
+
+/// A link whose [`SpatialViewer`] is removed stops seeing the entities that were in its radius.
+fn hide_entities_of_removed_viewer<P: SpatialPosition>(
+    trigger: On<Remove, SpatialViewer>,
+    mut commands: Commands,
+    grid: Res<SpatialGrid<P>>,
+    viewers: Query<&SpatialViewer>,
+) {
+    let Ok(viewer) = viewers.get(trigger.entity) else {
+        return;
+    };
+    for entity in viewer.visible.iter() {
+        if grid.entities.contains_key(entity) {
+            trace!(?entity, sender = ?trigger.entity, "spatial viewer removed");
+            commands.lose_visibility(*entity, trigger.entity);
+        }
+    }
+}
+
+fn update_spatial_grid<P: SpatialPosition>(
+    mut commands: Commands,
+    mut grid: ResMut<SpatialGrid<P>>,
+    moved: Query<
+        (Entity, &P),
+        (
+            With<SpatialInterest>,
+            Or<(Changed<P>, Added<SpatialInterest>)>,
+        ),
+    >,
+    mut removed: RemovedComponents<SpatialInterest>,
+    exists: Query<(), With<P>>,
+    mut viewers: Query<(Entity, &mut SpatialViewer)>,
+    senders: Query<Entity, With<ClientVisibility>>,
+) {
+    for entity in removed.read() {
+        grid.remove(entity);
+        for (_, mut viewer) in viewers.iter_mut() {
+            viewer.visible.remove(&entity);
+        }
+        // an entity that is no longer managed by the grid is visible to every client again
+        if exists.contains(entity) {
+            for sender in senders.iter() {
+                commands.gain_visibility(entity, sender);
+            }
+        }
+    }
+    for (entity, position) in moved.iter() {
+        grid.insert(entity, position.spatial_position());
+    }
+}
+
+fn update_spatial_visibility<P: SpatialPosition>(
+    mut commands: Commands,
+    grid: Res<SpatialGrid<P>>,
+    anchors: Query<&P>,
+    mut viewers: Query<(Entity, &mut SpatialViewer)>,
+) {
+    for (sender, mut viewer) in viewers.iter_mut() {
+        // without an anchor position, keep the current visibility
+        let Ok(anchor) = anchors.get(viewer.anchor) else {
+            continue;
+        };
+        let visible = grid.visible_from(
+            anchor.spatial_position(),
+            viewer.radius,
+            viewer.hysteresis,
+            &viewer.visible,
+        );
+        for entity in visible.difference(&viewer.visible) {
+            trace!(?entity, ?sender, "entity entered the view radius");
+            commands.gain_visibility(*entity, sender);
+        }
+        for entity in viewer.visible.difference(&visible) {
+            // despawned entities are removed from the grid before this runs
+            if grid.entities.contains_key(entity) {
+                trace!(?entity, ?sender, "entity left the view radius");
+                commands.lose_visibility(*entity, sender);
+            }
+        }
+        viewer.visible = visible;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    fn grid() -> SpatialGrid<Transform> {
+        SpatialGrid::new(10.0)
+    }
+
+    #[test]
+    fn entities_move_between_cells() {
+        let mut grid = grid();
+        let entity = Entity::from_bits(1);
+        grid.insert(entity, Vec3::new(5.0, 5.0, 0.0));
+        assert_eq!(grid.cells[&IVec3::ZERO], [entity]);
+
+        grid.insert(entity, Vec3::new(-5.0, 5.0, 0.0));
+        assert!(!grid.cells.contains_key(&IVec3::ZERO));
+        assert_eq!(grid.cells[&IVec3::new(-1, 0, 0)], [entity]);
+
+        grid.remove(entity);
+        assert!(grid.cells.is_empty());
+        assert!(grid.entities.is_empty());
+    }
+
+    #[test]
+    fn only_entities_within_radius_are_visible() {
+        let mut grid = grid();
+        let near = Entity::from_bits(1);
+        let far = Entity::from_bits(2);
+        grid.insert(near, Vec3::new(14.0, 0.0, 0.0));
+        grid.insert(far, Vec3::new(16.0, 0.0, 0.0));
+
+        let visible = grid.visible_from(Vec3::ZERO, 15.0, 0.0, &EntityHashSet::default());
+        assert!(visible.contains(&near));
+        assert!(!visible.contains(&far));
+    }
+
+    #[test]
+    fn hysteresis_keeps_entities_visible_near_the_edge() {
+        let mut grid = grid();
+        let entity = Entity::from_bits(1);
+        grid.insert(entity, Vec3::new(14.0, 0.0, 0.0));
+        let visible = grid.visible_from(Vec3::ZERO, 15.0, 2.0, &EntityHashSet::default());
+        assert!(visible.contains(&entity));
+
+        // moving slightly past the radius keeps the entity visible
+        grid.insert(entity, Vec3::new(16.0, 0.0, 0.0));
+        let visible = grid.visible_from(Vec3::ZERO, 15.0, 2.0, &visible);
+        assert!(visible.contains(&entity));
+
+        // moving past the hysteresis margin hides it
+        grid.insert(entity, Vec3::new(18.0, 0.0, 0.0));
+        let visible = grid.visible_from(Vec3::ZERO, 15.0, 2.0, &visible);
+        assert!(!visible.contains(&entity));
+
+        // coming back inside the margin doesn't make it visible again until it is within radius
+        grid.insert(entity, Vec3::new(16.0, 0.0, 0.0));
+        let visible = grid.visible_from(Vec3::ZERO, 15.0, 2.0, &visible);
+        assert!(!visible.contains(&entity));
+    }
+}
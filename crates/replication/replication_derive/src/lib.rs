@@ -0,0 +1,308 @@
+//! Derive macros for `lightyear_replication`.
+//!
+//! Use them through the `derive` feature of `lightyear_replication`, which re-exports them.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    Data, DeriveInput, Error, Expr, Fields, GenericParam, Index, LitStr, Member, Path, Result,
+    parse_macro_input, parse_quote,
+};
+
+/// Implements `lightyear_replication::diffable::Diffable` and `DiffField`, with a generated
+/// `{Name}Delta` struct.
+///
+/// See the documentation of the `lightyear_replication::diffable` module for the supported
+/// attributes.
+#[proc_macro_derive(Diffable, attributes(diffable))]
+pub fn derive_diffable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    diffable(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// How the delta of a field is computed
+enum Mode {
+    /// With the `DiffField` implementation of the field
+    Diff,
+    /// With the `QuantizedField` implementation of the field and the given step
+    Quantize(Expr),
+    /// The delta is the new value
+    Replace,
+}
+
+struct Field {
+    /// Access to the field in the original struct
+    member: Member,
+    /// Access to the field in the delta struct
+    delta_member: Member,
+    vis: syn::Visibility,
+    ty: syn::Type,
+    mode: Mode,
+}
+
+struct Options {
+    krate: Path,
+    replicon: bool,
+}
+
+fn diffable(mut input: DeriveInput) -> Result<TokenStream2> {
+    let options = parse_options(&input.attrs)?;
+    let diffable = {
+        let krate = &options.krate;
+        quote!(#krate::diffable)
+    };
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "Diffable can only be derived for structs; use `#[diffable(replace)]` on the fields that contain enums",
+        ));
+    };
+    let tuple = matches!(data.fields, Fields::Unnamed(_));
+    let fields = parse_fields(&data.fields)?;
+
+    let is_generic = input
+        .generics
+        .params
+        .iter()
+        .any(|param| matches!(param, GenericParam::Type(_)));
+    for param in input.generics.params.iter_mut() {
+        if let GenericParam::Type(param) = param {
+            param.bounds.push(parse_quote!(#diffable::DiffField));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let generics = &input.generics;
+    let ident = &input.ident;
+    let vis = &input.vis;
+    let delta = format_ident!("{}Delta", ident);
+    let delta_doc =
+        format!("Fields of [`{ident}`] that changed, generated by `#[derive(Diffable)]`.");
+    let serde_crate = quote!(#diffable::__private::serde).to_string();
+
+    let delta_types = fields.iter().map(|field| {
+        let ty = &field.ty;
+        match &field.mode {
+            Mode::Diff => quote!(<#ty as #diffable::DiffField>::Delta),
+            Mode::Quantize(_) => quote!(<#ty as #diffable::QuantizedField>::Steps),
+            Mode::Replace => quote!(#ty),
+        }
+    });
+    let field_vis = fields.iter().map(|field| &field.vis);
+    let delta_struct = if tuple {
+        quote! {
+            #vis struct #delta #generics (
+                #(#field_vis ::core::option::Option<#delta_types>,)*
+            ) #where_clause;
+        }
+    } else {
+        let delta_members = fields.iter().map(|field| &field.delta_member);
+        quote! {
+            #vis struct #delta #generics #where_clause {
+                #(#field_vis #delta_members: ::core::option::Option<#delta_types>,)*
+            }
+        }
+    };
+
+    let delta_members: Vec<_> = fields.iter().map(|field| &field.delta_member).collect();
+    let diff_fields = fields.iter().map(|field| {
+        let member = &field.member;
+        match &field.mode {
+            Mode::Diff => quote! {
+                #diffable::DiffField::diff_field(&self.#member, &new.#member)
+            },
+            Mode::Quantize(step) => quote! {
+                #diffable::QuantizedField::diff_steps(&self.#member, &new.#member, #step)
+            },
+            Mode::Replace => quote! {
+                (self.#member != new.#member)
+                    .then(|| ::core::clone::Clone::clone(&new.#member))
+            },
+        }
+    });
+    let apply_fields = fields.iter().map(|field| {
+        let member = &field.member;
+        let delta_member = &field.delta_member;
+        let apply = match &field.mode {
+            Mode::Diff => quote! {
+                #diffable::DiffField::apply_field(&mut self.#member, delta)
+            },
+            Mode::Quantize(step) => quote! {
+                #diffable::QuantizedField::apply_steps(&mut self.#member, delta, #step)
+            },
+            Mode::Replace => quote! {
+                ::core::clone::Clone::clone_from(&mut self.#member, delta)
+            },
+        };
+        quote! {
+            if let ::core::option::Option::Some(delta) = &delta.#delta_member {
+                #apply;
+            }
+        }
+    });
+    let scale_fields = fields.iter().map(|field| {
+        let ty = &field.ty;
+        let scale = match &field.mode {
+            Mode::Diff => quote!(<#ty as #diffable::DiffField>::scale_delta(delta, t)),
+            Mode::Quantize(_) => quote!(<#ty as #diffable::QuantizedField>::scale_steps(delta, t)),
+            Mode::Replace => quote!(::core::option::Option::None),
+        };
+        let delta_member = &field.delta_member;
+        quote!(delta.#delta_member.as_ref().and_then(|delta| #scale))
+    });
+    let none = fields.iter().map(|_| quote!(::core::option::Option::None));
+
+    // `Self: Default` cannot be used as a bound on a non-generic impl if it doesn't hold, so
+    // `Default` is only required by the body of `base_value` in that case
+    let base_value_bound = is_generic.then(|| {
+        let predicate = quote!(Self: ::core::default::Default);
+        match where_clause {
+            Some(_) => quote!(, #predicate),
+            None => quote!(where #predicate),
+        }
+    });
+
+    let replicon_impl = options.replicon.then(|| {
+        quote! {
+            impl #impl_generics #diffable::__private::bevy_replicon::shared::replication::diff::Diffable
+                for #ident #ty_generics #where_clause
+            {
+                type Diff = #delta #ty_generics;
+
+                fn apply_diff(
+                    &mut self,
+                    diff: &Self::Diff,
+                ) -> #diffable::__private::bevy_ecs::error::Result<()> {
+                    #diffable::DiffField::apply_field(self, diff);
+                    ::core::result::Result::Ok(())
+                }
+            }
+        }
+    });
+
+    Ok(quote! {
+        #[doc = #delta_doc]
+        #[derive(
+            ::core::clone::Clone,
+            ::core::fmt::Debug,
+            ::core::cmp::PartialEq,
+            #diffable::__private::serde::Serialize,
+            #diffable::__private::serde::Deserialize,
+        )]
+        #[serde(crate = #serde_crate, bound = "")]
+        #delta_struct
+
+        impl #impl_generics ::core::default::Default for #delta #ty_generics #where_clause {
+            fn default() -> Self {
+                Self { #(#delta_members: #none,)* }
+            }
+        }
+
+        impl #impl_generics #diffable::DiffField for #ident #ty_generics #where_clause {
+            type Delta = #delta #ty_generics;
+
+            fn diff_field(&self, new: &Self) -> ::core::option::Option<Self::Delta> {
+                let delta = #delta { #(#delta_members: #diff_fields,)* };
+                (delta != <Self::Delta as ::core::default::Default>::default()).then_some(delta)
+            }
+
+            fn apply_field(&mut self, delta: &Self::Delta) {
+                #(#apply_fields)*
+            }
+
+            fn scale_delta(delta: &Self::Delta, t: f32) -> ::core::option::Option<Self::Delta> {
+                let scaled = #delta { #(#delta_members: #scale_fields,)* };
+                (scaled != <Self::Delta as ::core::default::Default>::default()).then_some(scaled)
+            }
+        }
+
+        impl #impl_generics #diffable::Diffable<#delta #ty_generics> for #ident #ty_generics
+            #where_clause #base_value_bound
+        {
+            fn base_value() -> Self {
+                ::core::default::Default::default()
+            }
+
+            fn diff(&self, new: &Self) -> #delta #ty_generics {
+                #diffable::DiffField::diff_field(self, new).unwrap_or_default()
+            }
+
+            fn apply_diff(&mut self, delta: &#delta #ty_generics) {
+                #diffable::DiffField::apply_field(self, delta);
+            }
+        }
+
+        #replicon_impl
+    })
+}
+
+fn parse_options(attrs: &[syn::Attribute]) -> Result<Options> {
+    let mut options = Options {
+        krate: parse_quote!(::lightyear_replication),
+        replicon: false,
+    };
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("diffable")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("crate") {
+                options.krate = meta.value()?.parse::<LitStr>()?.parse()?;
+                Ok(())
+            } else if meta.path.is_ident("replicon") {
+                options.replicon = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `crate = \"path\"` or `replicon`"))
+            }
+        })?;
+    }
+    Ok(options)
+}
+
+/// Fields that are part of the delta.
+fn parse_fields(fields: &Fields) -> Result<Vec<Field>> {
+    let mut parsed = Vec::new();
+    for (index, field) in fields.iter().enumerate() {
+        let Some(mode) = parse_mode(field)? else {
+            continue;
+        };
+        let (member, delta_member) = match &field.ident {
+            Some(ident) => (Member::Named(ident.clone()), Member::Named(ident.clone())),
+            None => (
+                Member::Unnamed(Index::from(index)),
+                Member::Unnamed(Index::from(parsed.len())),
+            ),
+        };
+        parsed.push(Field {
+            member,
+            delta_member,
+            vis: field.vis.clone(),
+            ty: field.ty.clone(),
+            mode,
+        });
+    }
+    Ok(parsed)
+}
+
+/// Returns `None` if the field is skipped.
+fn parse_mode(field: &syn::Field) -> Result<Option<Mode>> {
+    let mut mode = Some(Mode::Diff);
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("diffable"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                mode = None;
+            } else if meta.path.is_ident("replace") {
+                mode = Some(Mode::Replace);
+            } else if meta.path.is_ident("quantize") {
+                mode = Some(Mode::Quantize(meta.value()?.parse()?));
+            } else {
+                return Err(meta.error("expected `skip`, `replace` or `quantize = step`"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(mode)
+}
@@ -0,0 +1,278 @@
+//! Check authority transfers between the server and a client
+
+use crate::protocol::*;
+use crate::stepper::*;
+use bevy::prelude::*;
+use lightyear_connection::network_target::NetworkTarget;
+use lightyear_messages::MessageManager;
+use lightyear_replication::control::ControlledBy;
+use lightyear_replication::prelude::*;
+use test_log::test;
+
+#[derive(Resource, Default)]
+struct Requests(Vec<Entity>);
+
+fn record_request(trigger: On<AuthorityRequested>, mut requests: ResMut<Requests>) {
+    requests.0.push(trigger.link);
+}
+
+/// Authority is opt-in, so the plugin is added to every app before they connect
+fn authority_stepper(config: StepperConfig) -> ClientServerStepper {
+    let mut stepper = ClientServerStepper::from_config(StepperConfig {
+        init: false,
+        ..config
+    });
+    stepper.server_app.add_plugins(AuthorityPlugin);
+    for app in stepper.client_apps.iter_mut() {
+        app.add_plugins(AuthorityPlugin);
+    }
+    stepper.init();
+    stepper
+}
+
+#[test]
+fn test_request_and_revoke_authority() {
+    let mut stepper = authority_stepper(StepperConfig::single());
+    let client_of = stepper.client_of_entities[0];
+
+    let server_entity = stepper
+        .server_app
+        .world_mut()
+        .spawn((
+            Replicate::to_clients(NetworkTarget::All),
+            ControlledBy {
+                owner: client_of,
+                lifetime: Default::default(),
+            },
+            AuthorityRequestPolicy::AcceptFromController,
+            CompSimple(1.0),
+        ))
+        .id();
+    stepper.frame_step(2);
+    let client_entity = stepper
+        .client(0)
+        .get::<MessageManager>()
+        .unwrap()
+        .entity_mapper
+        .get_local(server_entity)
+        .unwrap();
+    assert!(
+        !stepper
+            .server_app
+            .world()
+            .entity(server_entity)
+            .contains::<HasAuthority>(),
+        "replicated entities should not get the authority marker by default"
+    );
+    assert!(
+        !stepper
+            .client_app()
+            .world()
+            .entity(client_entity)
+            .contains::<HasAuthority>()
+    );
+
+    stepper.client_app().world_mut().trigger(RequestAuthority {
+        entity: client_entity,
+    });
+    stepper.frame_step(3);
+
+    assert_eq!(
+        stepper
+            .server_app
+            .world()
+            .get::<AuthorityPeer>(server_entity),
+        Some(&AuthorityPeer::Client(client_of))
+    );
+    assert!(
+        stepper
+            .client_app()
+            .world()
+            .entity(client_entity)
+            .contains::<HasAuthority>()
+    );
+
+    // the server no longer overwrites the state of the owning client
+    stepper
+        .server_app
+        .world_mut()
+        .entity_mut(server_entity)
+        .insert(CompSimple(2.0));
+    stepper.frame_step(2);
+    assert_eq!(
+        stepper
+            .client_app()
+            .world()
+            .get::<CompSimple>(client_entity),
+        Some(&CompSimple(1.0))
+    );
+
+    stepper.server_app.world_mut().trigger(TransferAuthority {
+        entity: server_entity,
+        to: AuthorityPeer::Server,
+    });
+    stepper.frame_step(3);
+
+    assert_eq!(
+        stepper
+            .server_app
+            .world()
+            .get::<AuthorityPeer>(server_entity),
+        Some(&AuthorityPeer::Server)
+    );
+    assert!(
+        !stepper
+            .client_app()
+            .world()
+            .entity(client_entity)
+            .contains::<HasAuthority>()
+    );
+    assert_eq!(
+        stepper
+            .client(0)
+            .get::<MessageManager>()
+            .unwrap()
+            .entity_mapper
+            .get_local(server_entity),
+        Some(client_entity),
+        "the transfer should preserve the remote entity mapping"
+    );
+    assert_eq!(
+        stepper
+            .client_app()
+            .world()
+            .get::<CompSimple>(client_entity),
+        Some(&CompSimple(2.0)),
+        "the former owner should receive the server state again"
+    );
+}
+
+#[test]
+fn test_manual_authority_request() {
+    let mut stepper = authority_stepper(StepperConfig::single());
+    stepper.server_app.init_resource::<Requests>();
+    stepper.server_app.add_observer(record_request);
+    let client_of = stepper.client_of_entities[0];
+
+    let server_entity = stepper
+        .server_app
+        .world_mut()
+        .spawn((Replicate::to_clients(NetworkTarget::All), CompSimple(1.0)))
+        .id();
+    stepper.frame_step(2);
+    let client_entity = stepper
+        .client(0)
+        .get::<MessageManager>()
+        .unwrap()
+        .entity_mapper
+        .get_local(server_entity)
+        .unwrap();
+
+    stepper.client_app().world_mut().trigger(RequestAuthority {
+        entity: client_entity,
+    });
+    stepper.frame_step(3);
+
+    assert_eq!(
+        stepper.server_app.world().resource::<Requests>().0,
+        vec![client_of]
+    );
+    assert!(
+        stepper
+            .server_app
+            .world()
+            .get::<AuthorityPeer>(server_entity)
+            .is_none(),
+        "a manual request should not transfer authority by itself"
+    );
+}
+
+#[test]
+fn test_owner_replicates_the_entity() {
+    let mut stepper = authority_stepper(StepperConfig::with_netcode_clients(2));
+    let client_of = stepper.client_of_entities[0];
+
+    let server_entity = stepper
+        .server_app
+        .world_mut()
+        .spawn((
+            Replicate::to_clients(NetworkTarget::All),
+            AuthorityRequestPolicy::AcceptIfServerOwned,
+            CompSimple(1.0),
+        ))
+        .id();
+    stepper.frame_step(2);
+    let local_entity = |stepper: &ClientServerStepper, id: usize| {
+        stepper
+            .client(id)
+            .get::<MessageManager>()
+            .unwrap()
+            .entity_mapper
+            .get_local(server_entity)
+            .unwrap()
+    };
+    let owner_entity = local_entity(&stepper, 0);
+    let other_entity = local_entity(&stepper, 1);
+
+    stepper.client_apps[0]
+        .world_mut()
+        .trigger(RequestAuthority {
+            entity: owner_entity,
+        });
+    stepper.frame_step(3);
+    assert_eq!(
+        stepper
+            .server_app
+            .world()
+            .get::<AuthorityPeer>(server_entity),
+        Some(&AuthorityPeer::Client(client_of))
+    );
+
+    stepper.client_apps[0]
+        .world_mut()
+        .entity_mut(owner_entity)
+        .insert((CompSimple(5.0), CompA(3.0)));
+    stepper.frame_step(4);
+
+    assert_eq!(
+        stepper.server_app.world().get::<CompSimple>(server_entity),
+        Some(&CompSimple(5.0)),
+        "the server should apply the mutation of the owning client"
+    );
+    assert_eq!(
+        stepper.client_apps[1]
+            .world()
+            .get::<CompSimple>(other_entity),
+        Some(&CompSimple(5.0)),
+        "the other clients should receive the owner's mutation through the server"
+    );
+    assert_eq!(
+        stepper.client_apps[1].world().get::<CompA>(other_entity),
+        Some(&CompA(3.0)),
+        "every replicated component inserted by the owner should be replicated"
+    );
+
+    // once the authority is released, the former owner's mutations are ignored
+    stepper.client_apps[0]
+        .world_mut()
+        .trigger(ReleaseAuthority {
+            entity: owner_entity,
+        });
+    stepper.frame_step(3);
+    assert_eq!(
+        stepper
+            .server_app
+            .world()
+            .get::<AuthorityPeer>(server_entity),
+        Some(&AuthorityPeer::Server)
+    );
+    stepper.client_apps[0]
+        .world_mut()
+        .entity_mut(owner_entity)
+        .insert(CompSimple(9.0));
+    stepper.frame_step(4);
+    assert_eq!(
+        stepper.server_app.world().get::<CompSimple>(server_entity),
+        Some(&CompSimple(5.0))
+    );
+}
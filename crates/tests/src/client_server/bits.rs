@@ -0,0 +1,93 @@
+//! Check that bit-packed components and messages go through the network
+
+use crate::protocol::*;
+use crate::stepper::*;
+use alloc::vec::Vec;
+use bevy::prelude::*;
+use lightyear::prelude::*;
+use test_log::test;
+
+/// A component registered with `replicate_bits` is replicated and updated with its
+/// `BitSerialize` encoding.
+#[test]
+fn test_replicate_bits_component() {
+    let mut stepper = ClientServerStepper::from_config(StepperConfig::single());
+    let server_entity = stepper
+        .server_app
+        .world_mut()
+        .spawn((
+            Replicate::to_clients(NetworkTarget::All),
+            CompBits {
+                tick: 1000,
+                position: [12.5, -50.0],
+            },
+        ))
+        .id();
+    stepper.frame_step(2);
+    let client_entity = stepper
+        .client(0)
+        .get::<MessageManager>()
+        .unwrap()
+        .entity_mapper
+        .get_local(server_entity)
+        .expect("entity was not replicated to client");
+    let received = stepper.client_apps[0]
+        .world()
+        .get::<CompBits>(client_entity)
+        .unwrap();
+    assert_eq!(received.tick, 1000);
+    // 16 bits over a range of 200 units
+    assert!((received.position[0] - 12.5).abs() < 0.01);
+    assert!((received.position[1] + 50.0).abs() < 0.01);
+
+    stepper
+        .server_app
+        .world_mut()
+        .get_mut::<CompBits>(server_entity)
+        .unwrap()
+        .tick = 3;
+    stepper.frame_step(2);
+    assert_eq!(
+        stepper.client_apps[0]
+            .world()
+            .get::<CompBits>(client_entity)
+            .unwrap()
+            .tick,
+        3
+    );
+}
+
+#[derive(Resource, Default)]
+struct ReceivedBits(Vec<BitsMessage>);
+
+fn receive_bits(
+    mut receivers: Query<&mut MessageReceiver<BitsMessage>>,
+    mut received: ResMut<ReceivedBits>,
+) {
+    for mut receiver in &mut receivers {
+        received.0.extend(receiver.receive());
+    }
+}
+
+/// A message registered with `register_message_bits` is received with the same value.
+#[test]
+fn test_send_bits_message() {
+    let mut stepper = ClientServerStepper::from_config(StepperConfig::single());
+    stepper.server_app.init_resource::<ReceivedBits>();
+    stepper.server_app.add_systems(Update, receive_bits);
+    let message = BitsMessage {
+        id: 1023,
+        score: 990,
+    };
+    stepper
+        .client_mut(0)
+        .get_mut::<MessageSender<BitsMessage>>()
+        .unwrap()
+        .send::<Channel1>(message.clone());
+    stepper.frame_step(1);
+
+    assert_eq!(
+        stepper.server_app.world().resource::<ReceivedBits>().0,
+        [message]
+    );
+}
@@ -1,8 +1,19 @@
 //! Check various replication scenarios between 2 peers only
 
+use crate::protocol::CompA;
 use crate::stepper::*;
-use bevy::prelude::{Entity, With};
+use alloc::string::ToString;
+use bevy::prelude::{Add, Entity, On, ResMut, Resource, With};
+use core::time::Duration;
+use lightyear::prelude::{
+    AppMessageExt, AppVersion, ControlledBy, LinkConditionerConfig, MessageManager, NetworkTarget,
+    RecvLinkConditioner, Replicate, SendLinkConditioner,
+};
+use lightyear_connection::client::{Connected, Disconnected, DisconnectedReason};
 use lightyear_connection::client_of::ClientOf;
+use lightyear_connection::resumption::{SessionResumption, Suspended};
+use lightyear_connection::shared::{DeniedReason, ProtocolMismatch, ProtocolRegistry};
+use serde::{Deserialize, Serialize};
 
 #[test_log::test]
 fn test_disconnection() {
@@ -21,6 +32,179 @@ fn test_disconnection() {
     );
 }
 
+/// Counts the `Connected` components that were added in the app
+#[derive(Resource, Default)]
+struct ConnectedCount(usize);
+
+fn count_connected(_: On<Add, Connected>, mut count: ResMut<ConnectedCount>) {
+    count.0 += 1;
+}
+
+/// Returns the reason of the protocol denial of the client `id`
+fn protocol_mismatch(stepper: &ClientServerStepper, id: usize) -> ProtocolMismatch {
+    let disconnected = stepper
+        .client(id)
+        .get::<Disconnected>()
+        .expect("the client should be disconnected");
+    let DisconnectedReason::Denied(DeniedReason::ProtocolMismatch(mismatch)) = &disconnected.reason
+    else {
+        panic!("unexpected disconnection reason: {:?}", disconnected.reason);
+    };
+    mismatch.clone()
+}
+
+/// The server denies the connection request of a client with a different protocol, so neither
+/// peer ever adds `Connected`.
+#[test_log::test]
+fn test_protocol_mismatch_is_denied() {
+    let mut stepper = ClientServerStepper::from_config(StepperConfig {
+        init: false,
+        ..StepperConfig::single()
+    });
+    stepper.server_app.insert_resource(AppVersion::new("1.1.0"));
+    stepper.client_apps[0].insert_resource(AppVersion::new("1.0.0"));
+    for app in [&mut stepper.server_app, &mut stepper.client_apps[0]] {
+        app.init_resource::<ConnectedCount>()
+            .add_observer(count_connected);
+    }
+    stepper.init();
+    stepper.frame_step(5);
+
+    let mismatch = protocol_mismatch(&stepper, 0);
+    assert_eq!(
+        mismatch.app_version,
+        Some(("1.1.0".to_string(), "1.0.0".to_string()))
+    );
+    // the client and server share the same registered types
+    assert!(mismatch.types.is_empty());
+    assert_eq!(stepper.server_app.world().resource::<ConnectedCount>().0, 0);
+    assert_eq!(
+        stepper.client_apps[0]
+            .world()
+            .resource::<ConnectedCount>()
+            .0,
+        0
+    );
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+struct ClientOnlyMessage(u32);
+
+/// The denial lists the types that only the client registered
+#[test_log::test]
+fn test_protocol_mismatch_lists_client_types() {
+    let mut stepper = ClientServerStepper::from_config(StepperConfig {
+        init: false,
+        ..StepperConfig::with_netcode_clients(0)
+    });
+    stepper.new_client_with_app(ClientType::Netcode, None, |app| {
+        app.register_message::<ClientOnlyMessage>();
+    });
+    stepper.init();
+    stepper.frame_step(5);
+
+    let mismatch = protocol_mismatch(&stepper, 0);
+    assert_eq!(mismatch.app_version, None);
+    assert_eq!(mismatch.types.len(), 1);
+    let ty = &mismatch.types[0];
+    assert_eq!(ty.registry, ProtocolRegistry::Message);
+    assert_eq!(ty.name, core::any::type_name::<ClientOnlyMessage>());
+    assert_eq!(ty.server_net_id, None);
+    assert!(ty.client_net_id.is_some());
+}
+
+#[test_log::test]
+fn test_protocol_match_connects() {
+    let stepper = ClientServerStepper::from_config(StepperConfig::single());
+    assert!(stepper.client(0).contains::<Connected>());
+    assert!(stepper.client_of(0).contains::<Connected>());
+}
+
+/// A client that times out and reconnects within the grace period keeps its session: the server
+/// keeps the same `ClientOf` entity, the entities it controls, and the replicated entities.
+#[test_log::test]
+fn test_session_resumption() {
+    let mut stepper = ClientServerStepper::from_config(StepperConfig::single());
+    let resumption = SessionResumption::new(Duration::from_secs(10));
+    stepper.server_mut().insert(resumption);
+    stepper.client_mut(0).insert(resumption);
+
+    let client_of = stepper.client_of_entities[0];
+    let server_entity = stepper
+        .server_app
+        .world_mut()
+        .spawn((
+            Replicate::to_clients(NetworkTarget::All),
+            ControlledBy {
+                owner: client_of,
+                lifetime: Default::default(),
+            },
+            CompA(1.0),
+        ))
+        .id();
+    stepper.frame_step(2);
+    let client_entity = stepper
+        .client(0)
+        .get::<MessageManager>()
+        .unwrap()
+        .entity_mapper
+        .get_local(server_entity)
+        .unwrap();
+
+    // cut the link for longer than the netcode timeout
+    let outage = LinkConditionerConfig::default().with_fixed_loss(1.0);
+    stepper.condition_client_link(
+        0,
+        Some(RecvLinkConditioner::new(outage.clone())),
+        Some(SendLinkConditioner::new(outage)),
+    );
+    stepper.frame_step(400);
+    assert!(stepper.client(0).contains::<Suspended>());
+    assert!(stepper.client_of(0).contains::<Suspended>());
+    assert!(!stepper.client(0).contains::<Disconnected>());
+    assert!(stepper.server_app.world().get_entity(server_entity).is_ok());
+    assert!(
+        stepper
+            .client_app()
+            .world()
+            .get_entity(client_entity)
+            .is_ok()
+    );
+
+    // the connection comes back before the end of the grace period
+    stepper.condition_client_link(0, None, None);
+    stepper
+        .server_app
+        .world_mut()
+        .get_mut::<CompA>(server_entity)
+        .unwrap()
+        .0 = 2.0;
+    stepper.frame_step(100);
+    assert!(stepper.client(0).contains::<Connected>());
+    assert!(!stepper.client(0).contains::<Suspended>());
+    assert!(stepper.client_of(0).contains::<Connected>());
+    assert!(!stepper.client_of(0).contains::<Suspended>());
+
+    // the replicated entity is kept, and receives the updates made during the outage
+    assert_eq!(
+        stepper
+            .client(0)
+            .get::<MessageManager>()
+            .unwrap()
+            .entity_mapper
+            .get_local(server_entity),
+        Some(client_entity)
+    );
+    assert_eq!(
+        stepper
+            .client_app()
+            .world()
+            .get::<CompA>(client_entity)
+            .unwrap(),
+        &CompA(2.0)
+    );
+}
+
 #[cfg(feature = "std")]
 mod disconnection_log_tests {
     use crate::stepper::*;
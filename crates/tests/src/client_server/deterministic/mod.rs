@@ -12,8 +12,11 @@
 //!   only works when every peer has the same entities at the same spawn
 //!   tick (e.g. entities spawned at startup on all peers, no server-side
 //!   spawning on connect).
+//! - [`replay`]: the server records the inputs of a match, and a headless
+//!   app replays them and compares the checksums of every tick.
 
 mod input_only;
 mod protocol;
+mod replay;
 mod state_based_catchup;
 mod stepper;
@@ -0,0 +1,197 @@
+//! Record the inputs of a match on the server and replay them in a headless app.
+
+use crate::protocol::NativeInput as MyInput;
+use crate::stepper::*;
+use alloc::vec::Vec;
+use bevy::ecs::entity::EntityMapper;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use lightyear::input::native::prelude::{
+    ActionState, InputMarker, NativeBuffer, NativeStateSequence,
+};
+use lightyear_connection::network_target::NetworkTarget;
+use lightyear_core::plugin::CorePlugins;
+use lightyear_core::tick::Tick;
+use lightyear_deterministic_replication::Deterministic;
+use lightyear_deterministic_replication::prelude::{
+    MatchRecorder, MatchRecorderPlugin, MatchRecording, MatchReplay, ReplayPlugin,
+};
+use lightyear_messages::MessageManager;
+use lightyear_replication::prelude::{AppComponentExt, Replicate};
+use lightyear_serde::entity_map::EntityMap;
+use test_log::test;
+
+type Inputs = NativeStateSequence<MyInput>;
+
+/// State of the simulation, which only depends on the inputs.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Hash)]
+struct Score(i64);
+
+fn add_simulation(app: &mut App) {
+    app.component::<Score>().add_default_hash();
+    app.add_systems(FixedUpdate, simulate);
+}
+
+fn simulate(mut query: Query<(&ActionState<MyInput>, &mut Score)>) {
+    for (action_state, mut score) in &mut query {
+        score.0 = score.0 * 3 + action_state.0.0 as i64;
+    }
+}
+
+/// Records the inputs of a client on the server, and returns the recording, the recorded entity
+/// and its final score.
+fn record_match() -> (MatchRecording<Inputs>, Entity, Score) {
+    let mut stepper = ClientServerStepper::from_config(StepperConfig {
+        init: false,
+        ..StepperConfig::single()
+    });
+    add_simulation(&mut stepper.server_app);
+    stepper
+        .server_app
+        .add_plugins(MatchRecorderPlugin::<Inputs>::default());
+    stepper.init();
+
+    let server_entity = stepper
+        .server_app
+        .world_mut()
+        .spawn((
+            Replicate::to_clients(NetworkTarget::All),
+            Score::default(),
+            Deterministic,
+        ))
+        .id();
+    stepper.frame_step(2);
+    let client_entity = stepper
+        .client(0)
+        .get::<MessageManager>()
+        .unwrap()
+        .entity_mapper
+        .get_local(server_entity)
+        .expect("entity was not replicated to client");
+    stepper.client_apps[0]
+        .world_mut()
+        .entity_mut(client_entity)
+        .insert(InputMarker::<MyInput>::default());
+
+    stepper
+        .server_app
+        .insert_resource(MatchRecorder::<Inputs>::default());
+    for i in 0..30 {
+        stepper.client_apps[0]
+            .world_mut()
+            .get_mut::<ActionState<MyInput>>(client_entity)
+            .unwrap()
+            .0 = MyInput(i % 3 - 1);
+        stepper.frame_step(1);
+    }
+    let recorder = stepper
+        .server_app
+        .world_mut()
+        .remove_resource::<MatchRecorder<Inputs>>()
+        .unwrap();
+    let recording = MatchRecording::from_bytes(&recorder.into_bytes()).unwrap();
+    let score = *stepper
+        .server_app
+        .world()
+        .get::<Score>(server_entity)
+        .unwrap();
+    (recording, server_entity, score)
+}
+
+/// Builds a headless app that replays the recording, with the recorded entity in its initial
+/// state.
+fn replay_app(recording: MatchRecording<Inputs>, server_entity: Entity) -> (App, Entity) {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        CorePlugins {
+            tick_duration: TICK_DURATION,
+        },
+        ReplayPlugin::<Inputs>::default(),
+    ));
+    add_simulation(&mut app);
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(TICK_DURATION));
+    let entity = app
+        .world_mut()
+        .spawn((
+            Score::default(),
+            Deterministic,
+            ActionState::<MyInput>::default(),
+            NativeBuffer::<MyInput>::default(),
+        ))
+        .id();
+    let mut entity_map = EntityMap::default();
+    entity_map.set_mapped(server_entity, entity);
+    app.insert_resource(MatchReplay::new(recording).with_entity_map(entity_map));
+    app.finish();
+    app.cleanup();
+    (app, entity)
+}
+
+fn replay(app: &App) -> &MatchReplay<Inputs> {
+    app.world().resource::<MatchReplay<Inputs>>()
+}
+
+/// Replaying a recorded match reproduces the checksums of every tick.
+#[test]
+fn test_replay_matches_recording() {
+    let (recording, server_entity, server_score) = record_match();
+    assert!(
+        recording
+            .ticks()
+            .iter()
+            .any(|recorded| !recorded.inputs.is_empty())
+    );
+    assert_ne!(server_score, Score(0));
+    let num_ticks = recording.ticks().len();
+
+    let (mut app, entity) = replay_app(recording, server_entity);
+    for _ in 0..2 * num_ticks {
+        if replay(&app).is_finished() {
+            break;
+        }
+        app.update();
+    }
+    let replay = replay(&app);
+    assert!(replay.is_finished());
+    assert_eq!(replay.ticks_replayed(), num_ticks);
+    assert!(replay.missed_ticks().is_empty());
+    assert_eq!(replay.first_divergence(), None);
+    assert_eq!(app.world().get::<Score>(entity), Some(&server_score));
+}
+
+/// A replay whose state differs from the recorded match reports the first tick that diverged.
+#[test]
+fn test_replay_detects_divergence() {
+    let (recording, server_entity, _) = record_match();
+    let ticks: Vec<Tick> = recording
+        .ticks()
+        .iter()
+        .map(|recorded| recorded.tick)
+        .collect();
+    let num_ticks = ticks.len();
+
+    let (mut app, entity) = replay_app(recording, server_entity);
+    for _ in 0..2 * num_ticks {
+        if replay(&app).ticks_replayed() >= 5 {
+            break;
+        }
+        app.update();
+    }
+    assert_eq!(replay(&app).first_divergence(), None);
+    let diverged_tick = ticks[replay(&app).ticks_replayed()];
+
+    // the state is modified outside of the simulation
+    app.world_mut().get_mut::<Score>(entity).unwrap().0 += 1000;
+    for _ in 0..2 * num_ticks {
+        if replay(&app).is_finished() {
+            break;
+        }
+        app.update();
+    }
+    let divergence = replay(&app)
+        .first_divergence()
+        .expect("the divergence was not detected");
+    assert_eq!(divergence.tick, diverged_tick);
+    assert_ne!(divergence.expected, divergence.actual);
+}
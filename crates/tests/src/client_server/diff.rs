@@ -1,4 +1,4 @@
-use crate::protocol::CompRepliconDiff;
+use crate::protocol::{CompDerivedDiff, CompDerivedDiffDelta, CompRepliconDiff};
 use crate::stepper::*;
 use bevy::prelude::*;
 use bevy_replicon::bytes::Bytes;
@@ -143,6 +143,64 @@ fn diff_interpolation_records_confirmed_history_from_replicon_diffs() {
     );
 }
 
+/// Verifies that a component deriving `Diffable` with `#[diffable(replicon)]` is replicated
+/// with the generated delta struct: only the changed fields are sent and applied on the client.
+#[test]
+fn derived_diffable_replicates_field_deltas() {
+    let mut stepper = ClientServerStepper::from_config(StepperConfig::single());
+
+    let server_entity = stepper
+        .server_app
+        .world_mut()
+        .spawn((
+            Replicate::to_clients(NetworkTarget::All),
+            CompDerivedDiff::default(),
+        ))
+        .id();
+    stepper
+        .server_app
+        .world_mut()
+        .entity_mut(server_entity)
+        .apply_diff::<CompDerivedDiff>(CompDerivedDiffDelta {
+            position: Some(1.5),
+            health: Some(100),
+        })
+        .unwrap();
+    stepper.frame_step_server_first(1);
+    let client_entity = client_entity(&stepper, server_entity);
+    assert_eq!(
+        stepper
+            .client_app()
+            .world()
+            .get::<CompDerivedDiff>(client_entity),
+        Some(&CompDerivedDiff {
+            position: 1.5,
+            health: 100,
+        })
+    );
+
+    stepper
+        .server_app
+        .world_mut()
+        .entity_mut(server_entity)
+        .apply_diff::<CompDerivedDiff>(CompDerivedDiffDelta {
+            health: Some(-10),
+            ..Default::default()
+        })
+        .unwrap();
+    stepper.frame_step_server_first(1);
+    assert_eq!(
+        stepper
+            .client_app()
+            .world()
+            .get::<CompDerivedDiff>(client_entity),
+        Some(&CompDerivedDiff {
+            position: 1.5,
+            health: 90,
+        })
+    );
+}
+
 #[derive(Serialize)]
 enum TestComponentDelta<'a> {
     Snapshot {
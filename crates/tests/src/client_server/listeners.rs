@@ -0,0 +1,85 @@
+//! Check that one server accepts clients through several transport listeners
+
+use crate::protocol::CompA;
+use crate::stepper::*;
+use alloc::vec::Vec;
+use bevy::prelude::*;
+use lightyear::prelude::{LocalAddr, MessageManager, NetworkTarget, Replicate};
+use lightyear_connection::client::Connected;
+use lightyear_link::Linked;
+use lightyear_link::prelude::{AcceptedBy, LinkOf, ListenerOf, Server};
+use lightyear_udp::server::{ServerUdpIo, ServerUdpPlugin};
+use test_log::test;
+
+/// A UDP client and a crossbeam client connect through two listeners of the same netcode server,
+/// and both receive the replicated entities. The listeners don't become servers themselves.
+#[test]
+fn test_clients_connect_through_several_listeners() {
+    let mut stepper = ClientServerStepper::from_config(StepperConfig {
+        init: false,
+        ..StepperConfig::with_netcode_clients(0)
+    });
+    stepper.server_app.add_plugins(ServerUdpPlugin);
+    let server = stepper.server_entity;
+    let udp_addr = unused_local_udp_addr();
+    let udp_listener = stepper
+        .server_app
+        .world_mut()
+        .spawn((
+            ListenerOf { server },
+            LocalAddr(udp_addr),
+            ServerUdpIo::default(),
+        ))
+        .id();
+    // crossbeam has no listening socket, so its listener is linked from the start
+    let crossbeam_listener = stepper
+        .server_app
+        .world_mut()
+        .spawn((ListenerOf { server }, Linked))
+        .id();
+
+    // the stepper spawns the link of the crossbeam client under the crossbeam listener
+    stepper.server_entity = crossbeam_listener;
+    stepper.new_client(ClientType::Netcode, None);
+    stepper.server_entity = server;
+    // the UDP client sends its datagrams to the UDP listener
+    stepper.io = IoType::Udp;
+    stepper.server_addr = udp_addr;
+    stepper.new_client(ClientType::Netcode, None);
+    stepper.init();
+
+    let world = stepper.server_app.world_mut();
+    assert_eq!(
+        world
+            .query_filtered::<Entity, With<Server>>()
+            .iter(world)
+            .collect::<Vec<_>>(),
+        [server]
+    );
+    for (i, listener) in [crossbeam_listener, udp_listener].into_iter().enumerate() {
+        assert!(stepper.client(i).contains::<Connected>());
+        let client_of = stepper.client_of(i);
+        assert_eq!(client_of.get::<LinkOf>().unwrap().server, server);
+        assert_eq!(client_of.get::<AcceptedBy>().unwrap().listener, listener);
+    }
+
+    let server_entity = stepper
+        .server_app
+        .world_mut()
+        .spawn((Replicate::to_clients(NetworkTarget::All), CompA(1.0)))
+        .id();
+    stepper.frame_step(2);
+    for i in 0..2 {
+        let client_entity = stepper
+            .client(i)
+            .get::<MessageManager>()
+            .unwrap()
+            .entity_mapper
+            .get_local(server_entity)
+            .expect("entity was not replicated to client");
+        assert_eq!(
+            stepper.client_apps[i].world().get::<CompA>(client_entity),
+            Some(&CompA(1.0))
+        );
+    }
+}
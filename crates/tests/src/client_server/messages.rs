@@ -328,3 +328,221 @@ fn test_send_multi_messages_with_target() {
             .is_empty()
     );
 }
+
+#[derive(Resource, Default)]
+struct Responses(Vec<(Entity, RequestId, Result<PongResponse, RpcError>)>);
+
+fn record_responses(
+    trigger: On<Response<PingRequest, PongResponse>>,
+    mut responses: ResMut<Responses>,
+) {
+    responses
+        .0
+        .push((trigger.entity, trigger.id, trigger.result.clone()));
+}
+
+fn respond_to_ping(
+    trigger: On<Request<PingRequest>>,
+    mut sender: Query<&mut ResponseSender<PingRequest, PongResponse>>,
+) {
+    if let Ok(mut sender) = sender.get_mut(trigger.entity) {
+        sender.respond::<Channel1>(trigger.id, PongResponse(trigger.request.0 + 1));
+    }
+}
+
+#[test]
+fn test_request_response() {
+    let mut stepper = ClientServerStepper::from_config(StepperConfig::single());
+    stepper.server_app.add_observer(respond_to_ping);
+    stepper.client_app().init_resource::<Responses>();
+    stepper.client_app().add_observer(record_responses);
+
+    let id = stepper
+        .client_mut(0)
+        .get_mut::<RequestSender<PingRequest, PongResponse>>()
+        .unwrap()
+        .send::<Channel1>(PingRequest(1));
+    stepper.frame_step(3);
+
+    let responses = stepper.client_apps[0].world().resource::<Responses>();
+    assert_eq!(
+        responses.0,
+        vec![(stepper.client_entities[0], id, Ok(PongResponse(2)))]
+    );
+    assert_eq!(
+        stepper
+            .client(0)
+            .get::<RequestSender<PingRequest, PongResponse>>()
+            .unwrap()
+            .num_pending(),
+        0
+    );
+}
+
+#[test]
+fn test_request_timeout() {
+    let mut stepper = ClientServerStepper::from_config(StepperConfig::single());
+    // the server never replies
+    stepper.client_app().init_resource::<Responses>();
+    stepper.client_app().add_observer(record_responses);
+
+    let timeout = stepper.frame_duration * 3;
+    let id = stepper
+        .client_mut(0)
+        .get_mut::<RequestSender<PingRequest, PongResponse>>()
+        .unwrap()
+        .send_with_timeout::<Channel1>(PingRequest(1), timeout);
+    stepper.frame_step(1);
+    assert!(
+        stepper.client_apps[0]
+            .world()
+            .resource::<Responses>()
+            .0
+            .is_empty()
+    );
+
+    stepper.frame_step(3);
+    let responses = stepper.client_apps[0].world().resource::<Responses>();
+    assert_eq!(
+        responses.0,
+        vec![(stepper.client_entities[0], id, Err(RpcError::Timeout))]
+    );
+}
+
+/// A scripted outage on the client's send path drops the client's messages, while the
+/// server's messages still reach the client.
+#[test]
+fn test_send_conditioner_script_outage() {
+    let mut stepper = ClientServerStepper::from_config(StepperConfig::single());
+    stepper.server_app.init_resource::<Buffer<StringMessage>>();
+    stepper
+        .server_app
+        .add_systems(Update, count_messages_observer::<StringMessage>);
+    stepper
+        .client_app()
+        .init_resource::<Buffer<StringMessage>>();
+    stepper
+        .client_app()
+        .add_systems(Update, count_messages_observer::<StringMessage>);
+
+    let outage = LinkConditionerConfig::default().with_fixed_loss(1.0);
+    let script = LinkConditionerScript::default()
+        .at(stepper.frame_duration * 2, outage)
+        .at(
+            stepper.frame_duration * 10,
+            LinkConditionerConfig::default(),
+        );
+    stepper.condition_client_link(
+        0,
+        None,
+        Some(SendLinkConditioner::new(LinkConditionerConfig::default()).with_script(script)),
+    );
+    stepper.frame_step(4);
+
+    let client_message = StringMessage("Lost".to_string());
+    stepper
+        .client_mut(0)
+        .get_mut::<MessageSender<StringMessage>>()
+        .unwrap()
+        .send::<Channel1>(client_message);
+    let server_message = StringMessage("Delivered".to_string());
+    stepper
+        .client_of_mut(0)
+        .get_mut::<MessageSender<StringMessage>>()
+        .unwrap()
+        .send::<Channel1>(server_message.clone());
+    stepper.frame_step(2);
+    assert!(
+        stepper
+            .server_app
+            .world()
+            .resource::<Buffer<StringMessage>>()
+            .0
+            .is_empty()
+    );
+    assert_eq!(
+        stepper.client_apps[0]
+            .world()
+            .resource::<Buffer<StringMessage>>()
+            .0,
+        vec![(stepper.client_entities[0], server_message)]
+    );
+
+    // the outage is over
+    stepper.frame_step(6);
+    let client_message = StringMessage("Hello".to_string());
+    stepper
+        .client_mut(0)
+        .get_mut::<MessageSender<StringMessage>>()
+        .unwrap()
+        .send::<Channel1>(client_message.clone());
+    stepper.frame_step(1);
+    assert_eq!(
+        stepper
+            .server_app
+            .world()
+            .resource::<Buffer<StringMessage>>()
+            .0,
+        vec![(stepper.client_of_entities[0], client_message)]
+    );
+}
+
+#[derive(Resource, Default)]
+struct DoubleResponses(Vec<(Entity, RequestId, Result<PongResponse, RpcError>)>);
+
+fn record_double_responses(
+    trigger: On<Response<DoublePingRequest, PongResponse>>,
+    mut responses: ResMut<DoubleResponses>,
+) {
+    responses
+        .0
+        .push((trigger.entity, trigger.id, trigger.result.clone()));
+}
+
+fn respond_to_double_ping(
+    trigger: On<Request<DoublePingRequest>>,
+    mut sender: Query<&mut ResponseSender<DoublePingRequest, PongResponse>>,
+) {
+    if let Ok(mut sender) = sender.get_mut(trigger.entity) {
+        sender.respond::<Channel1>(trigger.id, PongResponse(trigger.request.0 * 2));
+    }
+}
+
+/// Two request types that share a response type each get the responses to their own requests
+#[test]
+fn test_requests_sharing_a_response_type() {
+    let mut stepper = ClientServerStepper::from_config(StepperConfig::single());
+    stepper.server_app.add_observer(respond_to_ping);
+    stepper.server_app.add_observer(respond_to_double_ping);
+    stepper.client_app().init_resource::<Responses>();
+    stepper.client_app().init_resource::<DoubleResponses>();
+    stepper.client_app().add_observer(record_responses);
+    stepper.client_app().add_observer(record_double_responses);
+
+    let ping = stepper
+        .client_mut(0)
+        .get_mut::<RequestSender<PingRequest, PongResponse>>()
+        .unwrap()
+        .send::<Channel1>(PingRequest(10));
+    let double_ping = stepper
+        .client_mut(0)
+        .get_mut::<RequestSender<DoublePingRequest, PongResponse>>()
+        .unwrap()
+        .send::<Channel1>(DoublePingRequest(10));
+    // each sender numbers its own requests
+    assert_eq!(ping, double_ping);
+    stepper.frame_step(3);
+
+    let client_entity = stepper.client_entities[0];
+    assert_eq!(
+        stepper.client_apps[0].world().resource::<Responses>().0,
+        vec![(client_entity, ping, Ok(PongResponse(11)))]
+    );
+    assert_eq!(
+        stepper.client_apps[0]
+            .world()
+            .resource::<DoubleResponses>()
+            .0,
+        vec![(client_entity, double_ping, Ok(PongResponse(20)))]
+    );
+}
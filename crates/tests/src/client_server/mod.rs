@@ -1,13 +1,21 @@
 mod base;
 
+mod authority;
 mod avian;
+mod bits;
 mod connection;
 mod deterministic;
 mod diff;
 mod hierarchy;
 mod input;
+#[cfg(feature = "std")]
+mod listeners;
 mod messages;
+mod mtu;
 mod prediction;
+mod priority;
 mod replication;
+mod snapshot;
+mod spectator;
 // mod replication_advanced;
 mod visibility;
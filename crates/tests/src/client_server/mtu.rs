@@ -0,0 +1,61 @@
+//! Check path MTU discovery over a netcode connection
+
+use crate::stepper::*;
+use bevy::prelude::*;
+use core::time::Duration;
+use lightyear_connection::client::Connected;
+use lightyear_link::{Link, LinkMtu};
+use lightyear_netcode::MAX_PACKET_SIZE;
+use lightyear_transport::prelude::{MtuDiscovery, MtuDiscoveryConfig};
+use test_log::test;
+
+/// Netcode limits the MTU of its links, so the search converges below the largest payload that
+/// netcode can send instead of probing sizes that the connection layer rejects.
+#[test]
+fn test_mtu_discovery_through_netcode() {
+    let mut stepper = ClientServerStepper::from_config(StepperConfig {
+        init: false,
+        ..StepperConfig::with_netcode_clients(1)
+    });
+    // start below the netcode limit so that there is room to search
+    let client_entity = stepper.client_entities[0];
+    let client_of_entity = stepper.client_of_entities[0];
+    for (world, entity) in [
+        (stepper.client_apps[0].world_mut(), client_entity),
+        (stepper.server_app.world_mut(), client_of_entity),
+    ] {
+        let mut entity = world.entity_mut(entity);
+        let link =
+            core::mem::take(&mut *entity.get_mut::<Link>().unwrap()).with_mtu(LinkMtu::new(1000));
+        entity.insert(link);
+    }
+    stepper.init();
+    assert!(stepper.client(0).contains::<Connected>());
+
+    let discovery = || {
+        MtuDiscovery::new(MtuDiscoveryConfig {
+            probe_interval: Duration::ZERO,
+            ..default()
+        })
+    };
+    stepper.client_apps[0]
+        .world_mut()
+        .entity_mut(client_entity)
+        .insert(discovery());
+    stepper
+        .server_app
+        .world_mut()
+        .entity_mut(client_of_entity)
+        .insert(discovery());
+    stepper.frame_step(100);
+
+    for entity in [stepper.client(0), stepper.client_of(0)] {
+        let link = entity.get::<Link>().unwrap();
+        let discovery = entity.get::<MtuDiscovery>().unwrap();
+        assert_eq!(link.max_mtu(), MAX_PACKET_SIZE);
+        assert!(discovery.is_complete());
+        assert!(link.mtu() <= MAX_PACKET_SIZE);
+        assert!(link.mtu() > MAX_PACKET_SIZE - discovery.config().search_threshold);
+        assert!(entity.contains::<Connected>());
+    }
+}
@@ -1,7 +1,8 @@
 use super::trigger_state_rollback;
 use crate::protocol::{
-    CompCorrectionBundleA, CompCorrectionBundleB, CompMixedCorrectionBundleA,
-    CompMixedCorrectionBundleB, CompPredictionOnly,
+    CompCorrectionBundleA, CompCorrectionBundleB, CompDerivedCorrection,
+    CompDerivedCorrectionDelta, CompMixedCorrectionBundleA, CompMixedCorrectionBundleB,
+    CompPredictionOnly,
 };
 use crate::stepper::{ClientServerStepper, StepperConfig};
 use bevy::prelude::*;
@@ -46,6 +47,15 @@ fn replay_mixed_correction_bundle(
     }
 }
 
+fn replay_derived_correction(mut components: Query<&mut CompDerivedCorrection, With<Predicted>>) {
+    for mut component in &mut components {
+        *component = CompDerivedCorrection {
+            position: 10.0,
+            health: 50,
+        };
+    }
+}
+
 fn history<C: Component + Clone>(tick: Tick, value: C) -> PredictionHistory<C> {
     let mut history = PredictionHistory::default();
     history.add_predicted(tick, Some(value));
@@ -220,3 +230,61 @@ fn post_rollback_bundle_uses_member_without_previous_visual() {
             .is_none()
     );
 }
+
+fn derived_correction_error(
+    stepper: &mut ClientServerStepper,
+    entity: Entity,
+) -> CompDerivedCorrectionDelta {
+    stepper
+        .client_app()
+        .world()
+        .get::<VisualCorrection<CompDerivedCorrectionDelta>>(entity)
+        .expect("the rollback should create a visual correction")
+        .error
+        .clone()
+}
+
+/// A component deriving `Diffable` and registered with `decay_correction` stores the rollback
+/// error as its generated delta struct, and only decays the fields that can be interpolated.
+#[test]
+fn derived_diffable_correction_decays_continuous_fields() {
+    let mut stepper = ClientServerStepper::from_config(StepperConfig::single());
+    set_correction_sampling_time(&mut stepper);
+    stepper
+        .client_app()
+        .add_systems(FixedUpdate, replay_derived_correction);
+
+    let current_tick = stepper.client_tick(0);
+    let rollback_tick = current_tick - 1;
+    let entity = stepper
+        .client_app()
+        .world_mut()
+        .spawn((
+            Predicted,
+            CompDerivedCorrection {
+                position: 1.0,
+                health: 100,
+            },
+            history(rollback_tick, CompDerivedCorrection::default()),
+            FrameInterpolationHistory::<CompDerivedCorrection>::default(),
+        ))
+        .id();
+
+    trigger_state_rollback(&mut stepper, rollback_tick);
+    stepper.client_app().world_mut().run_schedule(PreUpdate);
+
+    // the error goes from the corrected visual value back to the visual value before the rollback
+    let error = derived_correction_error(&mut stepper, entity);
+    assert_eq!(error.health, Some(50));
+    let position_error = error.position.expect("the position was corrected");
+    assert!(position_error < 0.0);
+
+    // the health cannot be interpolated, so its error is dropped at once
+    stepper.frame_step(1);
+    let decayed = derived_correction_error(&mut stepper, entity);
+    assert_eq!(decayed.health, None);
+    let decayed_position = decayed
+        .position
+        .expect("the position error decays gradually");
+    assert!(decayed_position < 0.0 && decayed_position > position_error);
+}
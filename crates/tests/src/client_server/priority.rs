@@ -0,0 +1,91 @@
+//! Check that the replication priority holds back entities that don't fit in a link's byte budget
+
+use crate::protocol::CompA;
+use crate::stepper::*;
+use bevy::prelude::*;
+use lightyear_connection::network_target::NetworkTarget;
+use lightyear_messages::MessageManager;
+use lightyear_replication::prelude::*;
+use test_log::test;
+
+#[test]
+fn test_byte_budget_holds_back_entities() {
+    let mut stepper = ClientServerStepper::from_config(StepperConfig {
+        init: false,
+        ..StepperConfig::single()
+    });
+    stepper
+        .server_app
+        .add_plugins(ReplicationPriorityPlugin::default());
+    stepper.init();
+    // CompA is a f32, so one entity needs about 9 bytes: only one fits in each send tick
+    stepper
+        .client_of_mut(0)
+        .insert(ReplicationBudget::fixed(12));
+
+    let server_entities: Vec<Entity> = (0..3)
+        .map(|_| {
+            stepper
+                .server_app
+                .world_mut()
+                .spawn((
+                    Replicate::to_clients(NetworkTarget::All),
+                    ReplicationPriority::default(),
+                    CompA(0.0),
+                ))
+                .id()
+        })
+        .collect();
+    stepper.frame_step(10);
+    let client_entities: Vec<Entity> = server_entities
+        .iter()
+        .map(|entity| {
+            stepper
+                .client(0)
+                .get::<MessageManager>()
+                .unwrap()
+                .entity_mapper
+                .get_local(*entity)
+                .expect("held back entities should still be spawned")
+        })
+        .collect();
+
+    for entity in &server_entities {
+        stepper
+            .server_app
+            .world_mut()
+            .entity_mut(*entity)
+            .insert(CompA(1.0));
+    }
+    let updated = |stepper: &ClientServerStepper| {
+        client_entities
+            .iter()
+            .filter(|entity| {
+                stepper.client_apps[0].world().get::<CompA>(**entity) == Some(&CompA(1.0))
+            })
+            .count()
+    };
+    stepper.frame_step(2);
+    assert!(
+        updated(&stepper) < 3,
+        "the byte budget should not fit every update in one send tick"
+    );
+
+    // the accumulated priority of the held back entities lets them be sent in the next ticks
+    stepper.frame_step(5);
+    assert_eq!(updated(&stepper), 3);
+
+    // without a budget, every update fits in one send tick
+    stepper.client_of_mut(0).remove::<ReplicationBudget>();
+    for entity in &server_entities {
+        stepper
+            .server_app
+            .world_mut()
+            .entity_mut(*entity)
+            .insert(CompA(2.0));
+    }
+    stepper.frame_step(2);
+    assert!(client_entities.iter().all(|entity| {
+        stepper.client_apps[0].world().get::<CompA>(*entity) == Some(&CompA(2.0))
+    }));
+}
@@ -0,0 +1,68 @@
+//! Restore the replicated world of a server in a fresh server
+
+use crate::protocol::CompA;
+use crate::stepper::*;
+use lightyear::prelude::{MessageManager, NetworkTarget, Replicate};
+use lightyear_replication::control::{Controlled, ControlledBy, Lifetime, PendingControlledBy};
+use lightyear_replication::prelude::{load_snapshot, save_snapshot};
+use test_log::test;
+
+/// A server restarted from a snapshot replicates the saved entities to a reconnecting client, and
+/// gives it back the control of its entities.
+///
+/// The stepper gives the first netcode client the id 0 in both servers, like a token issuer that
+/// pins the client ids.
+#[test]
+fn test_restored_server_replicates_to_reconnecting_client() {
+    let mut stepper = ClientServerStepper::from_config(StepperConfig::single());
+    let client_of = stepper.client_of_entities[0];
+    let server_entity = stepper
+        .server_app
+        .world_mut()
+        .spawn((
+            Replicate::to_clients(NetworkTarget::All),
+            ControlledBy {
+                owner: client_of,
+                lifetime: Lifetime::Persistent,
+            },
+            CompA(1.0),
+        ))
+        .id();
+    stepper.frame_step(2);
+    let bytes = save_snapshot(stepper.server_app.world_mut()).unwrap();
+
+    // the server crashes and a new one starts from the snapshot, before any client is connected
+    let mut restarted = ClientServerStepper::from_config(StepperConfig::with_netcode_clients(0));
+    let restored = load_snapshot(restarted.server_app.world_mut(), &bytes).unwrap()[&server_entity];
+    assert!(
+        restarted
+            .server_app
+            .world()
+            .get::<PendingControlledBy>(restored)
+            .is_some()
+    );
+
+    // the client reconnects with the same client id
+    restarted.new_client(ClientType::Netcode, None);
+    restarted.init();
+    restarted.frame_step(2);
+
+    assert_eq!(
+        restarted
+            .server_app
+            .world()
+            .get::<ControlledBy>(restored)
+            .map(|controlled_by| controlled_by.owner),
+        Some(restarted.client_of_entities[0])
+    );
+    let client_entity = restarted
+        .client(0)
+        .get::<MessageManager>()
+        .unwrap()
+        .entity_mapper
+        .get_local(restored)
+        .expect("the restored entity was not replicated to the client");
+    let client_world = restarted.client_apps[0].world();
+    assert_eq!(client_world.get::<CompA>(client_entity), Some(&CompA(1.0)));
+    assert!(client_world.get::<Controlled>(client_entity).is_some());
+}
@@ -0,0 +1,104 @@
+//! Check that spectators receive a delayed view of the game without taking part in it
+
+use crate::protocol::NativeInput as MyInput;
+use crate::stepper::*;
+use lightyear::input::native::prelude::{ActionState, InputMarker, NativeBuffer};
+use lightyear::prelude::InterpolationTimeline;
+use lightyear_connection::network_target::NetworkTarget;
+use lightyear_core::prediction::Predicted;
+use lightyear_core::timeline::NetworkTimeline;
+use lightyear_messages::MessageManager;
+use lightyear_replication::prelude::{PredictionTarget, Replicate, Spectator};
+use test_log::test;
+
+const DELAY_TICKS: u32 = 20;
+
+/// A spectator receives the replicated entities only after its delay, never predicts them, doesn't
+/// receive the inputs of the players, and keeps its interpolation timeline behind the delay.
+#[test]
+fn test_spectator() {
+    let mut stepper = ClientServerStepper::from_config(
+        StepperConfig::with_netcode_clients(3)
+            .with_max_spectator_delay(TICK_DURATION * DELAY_TICKS),
+    );
+    stepper
+        .client_of_mut(2)
+        .insert(Spectator::default().with_delay(TICK_DURATION * DELAY_TICKS));
+    stepper.frame_step(2);
+
+    let server_entity = stepper
+        .server_app
+        .world_mut()
+        .spawn((
+            Replicate::to_clients(NetworkTarget::All),
+            PredictionTarget::to_clients(NetworkTarget::All),
+            ActionState::<MyInput>::default(),
+        ))
+        .id();
+    let local_entity = |stepper: &ClientServerStepper, i: usize| {
+        stepper
+            .client(i)
+            .get::<MessageManager>()
+            .unwrap()
+            .entity_mapper
+            .get_local(server_entity)
+    };
+    stepper.frame_step(2);
+    let client0_entity = local_entity(&stepper, 0).expect("entity not replicated to client 0");
+    let client1_entity = local_entity(&stepper, 1).expect("entity not replicated to client 1");
+    assert!(local_entity(&stepper, 2).is_none());
+
+    // the replication messages are released once the delay has elapsed
+    stepper.frame_step(DELAY_TICKS as usize + 5);
+    let spectator_entity =
+        local_entity(&stepper, 2).expect("entity not replicated to the spectator");
+    assert!(
+        stepper.client_apps[0]
+            .world()
+            .get::<Predicted>(client0_entity)
+            .is_some()
+    );
+    assert!(
+        stepper.client_apps[2]
+            .world()
+            .get::<Predicted>(spectator_entity)
+            .is_none()
+    );
+
+    // the inputs of client 0 are rebroadcast to client 1, but not to the spectator
+    stepper.client_apps[0]
+        .world_mut()
+        .entity_mut(client0_entity)
+        .insert(InputMarker::<MyInput>::default());
+    stepper.client_apps[0]
+        .world_mut()
+        .get_mut::<ActionState<MyInput>>(client0_entity)
+        .unwrap()
+        .0 = MyInput(1);
+    stepper.frame_step(DELAY_TICKS as usize + 5);
+    assert!(
+        stepper.client_apps[1]
+            .world()
+            .get::<NativeBuffer<MyInput>>(client1_entity)
+            .is_some()
+    );
+    assert!(
+        stepper.client_apps[2]
+            .world()
+            .get::<NativeBuffer<MyInput>>(spectator_entity)
+            .is_none()
+    );
+
+    // the spectator interpolates further in the past, to account for the delay
+    let interpolation_tick = |stepper: &ClientServerStepper, i: usize| {
+        stepper.client_apps[i]
+            .world()
+            .resource::<InterpolationTimeline>()
+            .now()
+            .tick()
+    };
+    assert!(
+        interpolation_tick(&stepper, 0) - interpolation_tick(&stepper, 2)
+            >= (DELAY_TICKS / 2) as i32
+    );
+}
@@ -694,3 +694,53 @@ fn test_visibility_persists_on_replication_target_change() {
         "client 1 should still not see the entity after ReplicationTarget change"
     );
 }
+
+/// Removing the SpatialViewer of a client hides the entities that were in its radius
+#[test]
+fn test_spatial_viewer_removed() {
+    let mut stepper = ClientServerStepper::from_config(StepperConfig {
+        init: false,
+        ..StepperConfig::single()
+    });
+    stepper
+        .server_app
+        .add_plugins(SpatialGridPlugin::<Transform>::new(10.0));
+    stepper.init();
+
+    let server_entity = stepper
+        .server_app
+        .world_mut()
+        .spawn((
+            Replicate::to_clients(NetworkTarget::All),
+            SpatialInterest,
+            Transform::default(),
+        ))
+        .id();
+    let sender = stepper.client_of_entities[0];
+    stepper
+        .server_app
+        .world_mut()
+        .entity_mut(sender)
+        .insert(SpatialViewer::new(server_entity, 5.0));
+    stepper.frame_step(2);
+    let client_entity = stepper
+        .client(0)
+        .get::<MessageManager>()
+        .unwrap()
+        .entity_mapper
+        .get_local(server_entity)
+        .expect("entity in the view radius was not replicated to client");
+
+    stepper
+        .server_app
+        .world_mut()
+        .entity_mut(sender)
+        .remove::<SpatialViewer>();
+    stepper.frame_step(2);
+    assert!(
+        stepper.client_apps[0]
+            .world()
+            .get_entity(client_entity)
+            .is_err()
+    );
+}
@@ -15,6 +15,10 @@ mod client_server;
 pub mod protocol;
 pub mod stepper;
 
+#[cfg(test)]
+#[cfg(feature = "test_utils")]
+mod p2p;
+
 #[cfg(test)]
 #[cfg(feature = "test_utils")]
 mod timeline;
@@ -0,0 +1,250 @@
+//! Hand off a client and its entities from one shard to another
+
+use crate::protocol::CompA;
+use crate::stepper::*;
+use alloc::vec;
+use alloc::vec::Vec;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use core::net::{Ipv4Addr, SocketAddr};
+use lightyear::handoff::{AcceptHandoffs, HandoffFinished, HandoffPlugin, ShardLink, StartHandoff};
+use lightyear::prelude::{server::*, *};
+use lightyear_connection::client_of::SkipNetcode;
+use lightyear_crossbeam::CrossbeamIo;
+use lightyear_raw_connection::client::{RawClient, RawConnectionPlugin};
+use lightyear_replication::control::{Controlled, ControlledBy, Lifetime};
+use lightyear_replication::receive::ReplicationReceiver;
+use test_log::test;
+
+#[derive(Resource, Default)]
+struct FinishedHandoffs(Vec<Result<(), String>>);
+
+fn record_finished(trigger: On<HandoffFinished>, mut finished: ResMut<FinishedHandoffs>) {
+    finished.0.push(
+        trigger
+            .result
+            .as_ref()
+            .copied()
+            .map_err(ToString::to_string),
+    );
+}
+
+/// Builds a shard whose server is not started yet
+fn shard() -> ClientServerStepper {
+    let mut shard = ClientServerStepper::from_config(
+        StepperConfig {
+            init: false,
+            ..StepperConfig::with_netcode_clients(0)
+        }
+        .with_io(IoType::Udp),
+    );
+    shard.server_app.add_plugins(HandoffPlugin);
+    shard
+}
+
+/// Connects the two shards with a crossbeam link: a raw `Client` on the source shard and a
+/// `ClientOf` of the destination server, which doesn't replicate anything.
+fn connect_shards(
+    source: &mut ClientServerStepper,
+    destination: &mut ClientServerStepper,
+) -> Entity {
+    let shard_addr = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 1);
+    let (source_io, destination_io) = CrossbeamIo::new_pair();
+    let shard_link = source
+        .server_app
+        .world_mut()
+        .spawn((
+            RawClient,
+            ShardLink,
+            LocalAddr(shard_addr),
+            Linked,
+            source_io,
+        ))
+        .id();
+
+    let world = destination.server_app.world_mut();
+    let client_of = world
+        .spawn((
+            LinkOf {
+                server: destination.server_entity,
+            },
+            Link::default(),
+            Linked,
+            PeerAddr(shard_addr),
+            SkipNetcode,
+            ShardLink,
+            destination_io,
+        ))
+        .id();
+    world.flush();
+    world
+        .entity_mut(client_of)
+        .remove::<(ReplicationSender, ReplicationReceiver)>()
+        .insert((
+            Connected,
+            ClientOf,
+            LocalId(PeerId::Server),
+            RemoteId(PeerId::Raw(shard_addr)),
+        ));
+    shard_link
+}
+
+/// Advances both shards by one frame, with the destination shard on the clock of the source
+/// shard.
+fn step(source: &mut ClientServerStepper, destination: &mut ClientServerStepper) {
+    source.frame_step(1);
+    destination.current_time = source.current_time;
+    destination
+        .server_app
+        .insert_resource(TimeUpdateStrategy::ManualInstant(destination.current_time));
+    destination.server_app.update();
+}
+
+/// The entities of a handed off client keep their local entity on the client, the destination
+/// shard gives it back their control, and the source shard despawns them once the client left.
+#[test]
+fn test_handoff_keeps_client_entities() {
+    let mut destination = shard();
+    destination
+        .server_app
+        .world_mut()
+        .entity_mut(destination.server_entity)
+        .insert(AcceptHandoffs::new(destination.server_addr));
+    destination.init();
+
+    let mut source = shard();
+    source.server_app.add_plugins(RawConnectionPlugin);
+    source
+        .server_app
+        .init_resource::<FinishedHandoffs>()
+        .add_observer(record_finished);
+    source.new_client_with_app(ClientType::Netcode, None, |app| {
+        app.add_plugins(HandoffPlugin);
+    });
+    source.init();
+    let shard_link = connect_shards(&mut source, &mut destination);
+    // let both shards run a few frames over the shard link before spawning the entities
+    for _ in 0..5 {
+        step(&mut source, &mut destination);
+    }
+
+    let client_of = source.client_of_entities[0];
+    let source_entity = source
+        .server_app
+        .world_mut()
+        .spawn((
+            Replicate::to_clients(NetworkTarget::All),
+            ControlledBy {
+                owner: client_of,
+                lifetime: Lifetime::Persistent,
+            },
+            CompA(1.0),
+        ))
+        .id();
+    for _ in 0..2 {
+        step(&mut source, &mut destination);
+    }
+    let local_entity = source
+        .client(0)
+        .get::<MessageManager>()
+        .unwrap()
+        .entity_mapper
+        .get_local(source_entity)
+        .expect("entity was not replicated to client");
+
+    source.server_app.world_mut().trigger(StartHandoff {
+        entity: client_of,
+        shard: shard_link,
+        entities: vec![],
+    });
+    let mut destination_entity = None;
+    for _ in 0..100 {
+        step(&mut source, &mut destination);
+        // the entity is never despawned or respawned on the client
+        let client_world = source.client_apps[0].world_mut();
+        assert!(client_world.get_entity(local_entity).is_ok());
+        assert_eq!(
+            client_world
+                .query_filtered::<(), With<CompA>>()
+                .iter(client_world)
+                .count(),
+            1
+        );
+
+        destination_entity = destination_entity.or_else(|| {
+            let world = destination.server_app.world_mut();
+            world
+                .query_filtered::<Entity, With<CompA>>()
+                .iter(world)
+                .next()
+        });
+        let mapped = destination_entity.and_then(|entity| {
+            source
+                .client(0)
+                .get::<MessageManager>()
+                .unwrap()
+                .entity_mapper
+                .get_local(entity)
+        });
+        if mapped.is_some()
+            && source.client(0).contains::<Connected>()
+            && source.server_app.world().get_entity(source_entity).is_err()
+        {
+            break;
+        }
+    }
+    assert_eq!(
+        source.server_app.world().resource::<FinishedHandoffs>().0,
+        [Ok(())]
+    );
+    let destination_entity = destination_entity.expect("the entity was not handed off");
+
+    // the client is mapped to the same local entity on the destination shard
+    assert_eq!(
+        source
+            .client(0)
+            .get::<MessageManager>()
+            .unwrap()
+            .entity_mapper
+            .get_local(destination_entity),
+        Some(local_entity)
+    );
+
+    // the control is restored to the new link of the client on the destination shard
+    let destination_world = destination.server_app.world_mut();
+    let player_link = destination_world
+        .query_filtered::<(Entity, &RemoteId), (With<ClientOf>, Without<ShardLink>)>()
+        .iter(destination_world)
+        .find(|(_, remote_id)| remote_id.0 == PeerId::Netcode(0))
+        .map(|(entity, _)| entity)
+        .expect("the client did not connect to the destination shard");
+    assert_eq!(
+        destination_world
+            .get::<ControlledBy>(destination_entity)
+            .map(|controlled_by| controlled_by.owner),
+        Some(player_link)
+    );
+    assert!(
+        source.client_apps[0]
+            .world()
+            .get::<Controlled>(local_entity)
+            .is_some()
+    );
+
+    // the source shard despawned the entity once the client disconnected
+    assert!(source.server_app.world().get_entity(source_entity).is_err());
+
+    // the updates of the destination shard are applied to the same local entity
+    destination
+        .server_app
+        .world_mut()
+        .entity_mut(destination_entity)
+        .insert(CompA(2.0));
+    for _ in 0..2 {
+        step(&mut source, &mut destination);
+    }
+    assert_eq!(
+        source.client_apps[0].world().get::<CompA>(local_entity),
+        Some(&CompA(2.0))
+    );
+}
@@ -1,2 +1,4 @@
+#[cfg(all(feature = "test_utils", feature = "std"))]
+mod handoff;
 #[cfg(feature = "steam")]
 mod steam;
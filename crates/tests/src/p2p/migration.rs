@@ -0,0 +1,62 @@
+//! Tests for host migration in a running P2P session.
+use crate::stepper::*;
+use lightyear::prelude::*;
+use test_log::test;
+
+#[derive(Resource, Default)]
+struct Migrations(Vec<P2PHostMigrated>);
+
+fn record_migration(trigger: On<P2PHostMigrated>, mut migrations: ResMut<Migrations>) {
+    migrations.0.push(trigger.event().clone());
+}
+
+/// The coordinator leaves a 3-peer session: the survivors elect the next peer as coordinator,
+/// agree on the same confirmed tick and keep the session running.
+#[test]
+fn coordinator_departure_migrates_the_session() {
+    let mut stepper = P2PStepper::new(3);
+    for id in 0..3 {
+        let app = stepper.peer_app(id);
+        app.init_resource::<Migrations>();
+        app.add_observer(record_migration);
+    }
+    stepper.init();
+    stepper.start_session();
+    for id in 0..3 {
+        assert_eq!(stepper.session(id).coordinator(), Some(PeerId::Entity(0)));
+    }
+    assert!(stepper.session(0).is_coordinator());
+
+    stepper.drop_peer(0);
+    for _ in 0..100 {
+        if stepper
+            .peers()
+            .all(|(_, app)| !app.world().resource::<Migrations>().0.is_empty())
+        {
+            break;
+        }
+        stepper.tick_step(1);
+    }
+
+    let migrated: Vec<P2PHostMigrated> = stepper
+        .peers()
+        .map(|(_, app)| {
+            let migrations = &app.world().resource::<Migrations>().0;
+            assert_eq!(migrations.len(), 1);
+            migrations[0].clone()
+        })
+        .collect();
+    assert_eq!(migrated[0], migrated[1]);
+    assert_eq!(migrated[0].departed.as_slice(), &[PeerId::Entity(0)]);
+    assert_eq!(migrated[0].coordinator, PeerId::Entity(1));
+
+    for (id, remote) in [(1, 2), (2, 1)] {
+        let session = stepper.session(id);
+        assert!(session.is_started());
+        assert!(!session.is_migrating());
+        assert_eq!(session.coordinator(), Some(PeerId::Entity(1)));
+        assert_eq!(session.members(), &[PeerId::Entity(remote as u64)]);
+        assert_eq!(stepper.link(id, remote).get::<P2P>(), Some(&P2P::Joined));
+    }
+    assert!(stepper.session(1).is_coordinator());
+}
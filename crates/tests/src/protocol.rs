@@ -15,7 +15,8 @@ use lightyear::frame_interpolation::FrameInterpolationPlugin;
 use lightyear::prelude::input::*;
 use lightyear::prelude::*;
 use lightyear_connection::direction::NetworkDirection;
-use lightyear_replication::diffable::Diffable as LightyearDiffable;
+use lightyear_replication::diffable::{Diffable as LightyearDiffable, decay_correction};
+use lightyear_serde::bits::BitSerialize;
 use serde::{Deserialize, Serialize};
 
 // Messages
@@ -25,6 +26,26 @@ pub struct StringMessage(pub String);
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, MapEntities, Reflect)]
 pub struct EntityMessage(#[entities] pub Entity);
 
+/// Message that is bit-packed with [`BitSerialize`]
+#[derive(BitSerialize, Debug, PartialEq, Clone)]
+pub struct BitsMessage {
+    #[bits(uint(bits = 10))]
+    pub id: u16,
+    #[bits(delta(baseline = 1000))]
+    pub score: i32,
+}
+
+// Requests
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Reflect)]
+pub struct PingRequest(pub u32);
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Reflect)]
+pub struct PongResponse(pub u32);
+
+/// Request that shares its response type with [`PingRequest`]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Reflect)]
+pub struct DoublePingRequest(pub u32);
+
 // Triggers
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Reflect, Event)]
 pub struct StringTrigger(pub String);
@@ -50,6 +71,15 @@ pub struct CompA(pub f32);
 #[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, Reflect)]
 pub struct CompS(pub String);
 
+/// Component that is bit-packed with [`BitSerialize`]
+#[derive(Component, BitSerialize, Clone, Debug, PartialEq)]
+pub struct CompBits {
+    #[bits(uint(bits = 10))]
+    pub tick: u16,
+    #[bits(float(min = -100.0, max = 100.0, bits = 16))]
+    pub position: [f32; 2],
+}
+
 #[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, Reflect)]
 pub struct CompDisabled(pub f32);
 
@@ -221,6 +251,36 @@ impl RepliconDiffable for CompRepliconDiff {
     }
 }
 
+/// Component replicated with the delta generated by `#[derive(Diffable)]`
+#[derive(
+    Component, LightyearDiffable, Serialize, Deserialize, Clone, Debug, Default, PartialEq,
+)]
+#[diffable(replicon)]
+pub struct CompDerivedDiff {
+    pub position: f32,
+    pub health: i32,
+}
+
+/// Component corrected with the delta generated by `#[derive(Diffable)]`
+#[derive(
+    Component, LightyearDiffable, Serialize, Deserialize, Clone, Debug, Default, PartialEq,
+)]
+pub struct CompDerivedCorrection {
+    pub position: f32,
+    pub health: i32,
+}
+
+fn derived_correction_lerp(
+    start: CompDerivedCorrection,
+    end: CompDerivedCorrection,
+    t: f32,
+) -> CompDerivedCorrection {
+    CompDerivedCorrection {
+        position: start.position + (end.position - start.position) * t,
+        health: end.health,
+    }
+}
+
 // Native Inputs
 #[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone, Copy, Reflect)]
 pub struct NativeInput(pub i16);
@@ -264,6 +324,13 @@ impl Plugin for ProtocolPlugin {
         app.register_message::<EntityMessage>()
             .add_map_entities()
             .add_direction(NetworkDirection::Bidirectional);
+        app.register_message_bits::<BitsMessage>()
+            .add_direction(NetworkDirection::Bidirectional);
+        // requests
+        app.register_request::<PingRequest, PongResponse>()
+            .add_direction(NetworkDirection::ClientToServer);
+        app.register_request::<DoublePingRequest, PongResponse>()
+            .add_direction(NetworkDirection::ClientToServer);
         // triggers
         app.register_event::<StringTrigger>()
             .add_direction(NetworkDirection::Bidirectional);
@@ -284,6 +351,7 @@ impl Plugin for ProtocolPlugin {
         // components
         app.component::<CompA>().replicate();
         app.component::<CompS>().replicate();
+        app.component::<CompBits>().replicate_bits();
         app.component::<CompReplicateOnce>().replicate_once();
         app.component::<CompCustomReplicateOnce>()
             .replicate_once_with(bevy_replicon::prelude::RuleFns::new(
@@ -349,6 +417,16 @@ impl Plugin for ProtocolPlugin {
             .replicate_diff()
             .predict_diff()
             .add_custom_interpolation_diff();
+        app.component::<CompDerivedDiff>().replicate_diff();
+        app.component::<CompDerivedCorrection>()
+            .replicate()
+            .predict()
+            .add_correction_fn::<CompDerivedCorrectionDelta>(
+                decay_correction::<CompDerivedCorrection>,
+            );
+        app.interpolate_with::<CompDerivedCorrection>(InterpolationFns::interpolate(
+            derived_correction_lerp,
+        ));
         // inputs
         app.add_plugins(native::InputPlugin::<NativeInput> {
             config: InputConfig::<NativeInput> {
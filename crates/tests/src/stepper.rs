@@ -18,6 +18,7 @@ use lightyear_core::test::TestHelper;
 use lightyear_netcode::client_plugin::NetcodeConfig;
 use lightyear_raw_connection::client::RawClient;
 use lightyear_raw_connection::server::RawServer;
+use lightyear_replication::LightyearRepliconServerBackend;
 use lightyear_replication::receive::ReplicationReceiver;
 #[cfg(feature = "std")]
 use lightyear_udp::server::{ServerUdpIo, ServerUdpPlugin};
@@ -55,7 +56,7 @@ fn configure_server_link(
 }
 
 #[cfg(feature = "std")]
-fn unused_local_udp_addr() -> SocketAddr {
+pub fn unused_local_udp_addr() -> SocketAddr {
     let socket = std::net::UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
     socket.local_addr().unwrap()
 }
@@ -166,6 +167,8 @@ pub struct StepperConfig {
     pub avian_mode: AvianReplicationMode,
     /// IO backend, independent from the raw/netcode connection layer.
     pub io: IoType,
+    /// Longest replication delay of a spectator link on the server.
+    pub max_spectator_delay: Duration,
 }
 
 impl StepperConfig {
@@ -181,6 +184,7 @@ impl StepperConfig {
             client_registry: None,
             avian_mode: AvianReplicationMode::default(),
             io: IoType::Crossbeam,
+            max_spectator_delay: Duration::ZERO,
         }
     }
 
@@ -196,6 +200,7 @@ impl StepperConfig {
             client_registry: None,
             avian_mode: AvianReplicationMode::default(),
             io: IoType::Crossbeam,
+            max_spectator_delay: Duration::ZERO,
         }
     }
 
@@ -210,6 +215,7 @@ impl StepperConfig {
             client_registry: None,
             avian_mode: AvianReplicationMode::default(),
             io: IoType::Crossbeam,
+            max_spectator_delay: Duration::ZERO,
         }
     }
 
@@ -225,6 +231,7 @@ impl StepperConfig {
             client_registry: None,
             avian_mode: AvianReplicationMode::default(),
             io: IoType::Crossbeam,
+            max_spectator_delay: Duration::ZERO,
         }
     }
 
@@ -239,6 +246,12 @@ impl StepperConfig {
         self.io = io;
         self
     }
+
+    /// Lets the server delay the replication to spectators by up to `max_spectator_delay`.
+    pub fn with_max_spectator_delay(mut self, max_spectator_delay: Duration) -> Self {
+        self.max_spectator_delay = max_spectator_delay;
+        self
+    }
 }
 
 impl ClientServerStepper {
@@ -250,6 +263,7 @@ impl ClientServerStepper {
             config.avian_mode,
             config.server_registry.clone(),
             config.io,
+            config.max_spectator_delay,
         );
 
         if stepper.io.requires_bound_server_addr() {
@@ -296,6 +310,7 @@ impl ClientServerStepper {
         avian_mode: AvianReplicationMode,
         metrics_registry: Option<MetricsRegistry>,
         io: IoType,
+        max_spectator_delay: Duration,
     ) -> Self {
         let server_addr = match io {
             IoType::Crossbeam => SERVER_ADDR,
@@ -319,7 +334,13 @@ impl ClientServerStepper {
             // the steam resources need to be added before the ServerPlugins
             server_app.add_steam_resources(STEAM_APP_ID);
         }
-        server_app.add_plugins((server::ServerPlugins { tick_duration }, RoomPlugin));
+        server_app.add_plugins((
+            server::ServerPlugins { tick_duration }.set(
+                LightyearRepliconServerBackend::default()
+                    .with_max_spectator_delay(max_spectator_delay),
+            ),
+            RoomPlugin,
+        ));
         #[cfg(feature = "std")]
         if io == IoType::Udp {
             server_app.add_plugins(ServerUdpPlugin);
@@ -387,6 +408,17 @@ impl ClientServerStepper {
         &mut self,
         client_type: ClientType,
         metrics_registry: Option<MetricsRegistry>,
+    ) -> usize {
+        self.new_client_with_app(client_type, metrics_registry, |_| {})
+    }
+
+    /// Adds a client like [`Self::new_client`], calling `configure_app` on its App after the
+    /// protocol is added and before the App is finished.
+    pub fn new_client_with_app(
+        &mut self,
+        client_type: ClientType,
+        metrics_registry: Option<MetricsRegistry>,
+        configure_app: impl FnOnce(&mut App),
     ) -> usize {
         let mut client_app = App::new();
         client_app.add_plugins((
@@ -414,6 +446,7 @@ impl ClientServerStepper {
         client_app.add_plugins(ProtocolPlugin {
             avian_mode: self.avian_mode,
         });
+        configure_app(&mut client_app);
         client_app.finish();
         client_app.cleanup();
         let client_id = self.client_entities.len();
@@ -568,6 +601,7 @@ impl ClientServerStepper {
             AvianReplicationMode::default(),
             None,
             IoType::Crossbeam,
+            Duration::ZERO,
         )
     }
 
@@ -630,6 +664,36 @@ impl ClientServerStepper {
             .entity_mut(self.client_of_entities[id])
     }
 
+    /// Conditions the packets received and sent by the client `id`.
+    ///
+    /// The receive conditioner models the client's download path and the send conditioner its
+    /// upload path, so using different configurations simulates an asymmetric link. Use
+    /// [`LinkConditioner::with_script`](lightyear_link::LinkConditioner::with_script) to change the conditions during the test.
+    pub fn condition_client_link(
+        &mut self,
+        id: usize,
+        recv: Option<RecvLinkConditioner>,
+        send: Option<SendLinkConditioner>,
+    ) {
+        let mut client = self.client_mut(id);
+        let mut link = client.get_mut::<Link>().unwrap();
+        link.recv.conditioner = recv;
+        link.send.conditioner = send;
+    }
+
+    /// Conditions the packets received and sent by the server link of the client `id`.
+    pub fn condition_client_of_link(
+        &mut self,
+        id: usize,
+        recv: Option<RecvLinkConditioner>,
+        send: Option<SendLinkConditioner>,
+    ) {
+        let mut client_of = self.client_of_mut(id);
+        let mut link = client_of.get_mut::<Link>().unwrap();
+        link.recv.conditioner = recv;
+        link.send.conditioner = send;
+    }
+
     pub fn init(&mut self) {
         self.initialize_server();
 
@@ -893,3 +957,180 @@ impl ClientServerStepper {
         }
     }
 }
+
+/// Stepper with n peer Apps connected to each other in a full mesh of P2P Crossbeam Links.
+///
+/// Every peer uses [`PeerId::Entity`] with its index as identity, so peer 0 is elected as the
+/// first session coordinator. Peers can be dropped to simulate a peer leaving: the Crossbeam
+/// channels of its Links are closed, and the remaining peers see those Links disconnect.
+pub struct P2PStepper {
+    pub peer_apps: Vec<Option<App>>,
+    /// `links[i][j]` is the Link entity in the App of peer `i` that connects to peer `j`.
+    pub links: Vec<Vec<Option<Entity>>>,
+    pub tick_duration: Duration,
+    pub current_time: bevy::platform::time::Instant,
+}
+
+impl P2PStepper {
+    pub fn new(peer_count: usize) -> Self {
+        let tick_duration = TICK_DURATION;
+        let mut peer_apps: Vec<App> = (0..peer_count)
+            .map(|_| {
+                let mut app = App::new();
+                app.add_plugins((
+                    MinimalPlugins,
+                    TransformPlugin,
+                    StatesPlugin,
+                    InputPlugin,
+                    LogPlugin::default(),
+                    MetricsPlugin::new(None),
+                ));
+                app.add_plugins(client::ClientPlugins { tick_duration });
+                app.add_plugins(ProtocolPlugin {
+                    avian_mode: AvianReplicationMode::default(),
+                });
+                app.finish();
+                app.cleanup();
+                app
+            })
+            .collect();
+
+        let mut links = vec![vec![None; peer_count]; peer_count];
+        for i in 0..peer_count {
+            for j in (i + 1)..peer_count {
+                let (io_i, io_j) = lightyear_crossbeam::CrossbeamIo::new_pair();
+                links[i][j] = Some(Self::spawn_link(&mut peer_apps[i], i, j, io_i));
+                links[j][i] = Some(Self::spawn_link(&mut peer_apps[j], j, i, io_j));
+            }
+        }
+        Self {
+            peer_apps: peer_apps.into_iter().map(Some).collect(),
+            links,
+            tick_duration,
+            current_time: bevy::platform::time::Instant::now(),
+        }
+    }
+
+    fn spawn_link(
+        app: &mut App,
+        local: usize,
+        remote: usize,
+        io: lightyear_crossbeam::CrossbeamIo,
+    ) -> Entity {
+        app.world_mut()
+            .spawn((
+                P2P::Inactive,
+                RawClient,
+                LocalId(PeerId::Entity(local as u64)),
+                RemoteId(PeerId::Entity(remote as u64)),
+                // Send pings every frame, so that the Acks are sent every frame
+                PingManager::new(PingConfig {
+                    ping_interval: Duration::default(),
+                }),
+                io,
+            ))
+            .id()
+    }
+
+    /// Connect every Link, then frame step until the peers are connected and synced.
+    pub fn init(&mut self) {
+        let now = self.current_time;
+        for (i, app) in self.peer_apps.iter_mut().enumerate() {
+            let app = app.as_mut().unwrap();
+            app.world_mut()
+                .get_resource_mut::<Time<Real>>()
+                .unwrap()
+                .update_with_instant(now);
+            for entity in self.links[i].iter().flatten() {
+                app.world_mut().trigger(Connect { entity: *entity });
+            }
+        }
+        for _ in 0..50 {
+            if self.peers().all(|(i, app)| {
+                input_timeline_is_synced(app.world())
+                    && self.links[i]
+                        .iter()
+                        .flatten()
+                        .all(|entity| app.world().entity(*entity).contains::<Connected>())
+            }) {
+                info!("Peers are all connected and synced");
+                break;
+            }
+            self.tick_step(1);
+        }
+    }
+
+    /// Start the P2P session on every peer and frame step until it has started everywhere.
+    pub fn start_session(&mut self) {
+        self.peer_apps.iter_mut().flatten().for_each(|app| {
+            app.world_mut().trigger(P2PStart);
+        });
+        for _ in 0..100 {
+            if self
+                .peers()
+                .all(|(_, app)| app.world().resource::<P2PSession>().is_started())
+            {
+                info!("P2P session started on every peer");
+                return;
+            }
+            self.tick_step(1);
+        }
+        panic!("P2P session did not start on every peer");
+    }
+
+    /// Iterate over the peers that have not been dropped.
+    pub fn peers(&self) -> impl Iterator<Item = (usize, &App)> {
+        self.peer_apps
+            .iter()
+            .enumerate()
+            .filter_map(|(i, app)| app.as_ref().map(|app| (i, app)))
+    }
+
+    pub fn peer_app(&mut self, id: usize) -> &mut App {
+        self.peer_apps[id].as_mut().unwrap()
+    }
+
+    pub fn session(&self, id: usize) -> &P2PSession {
+        self.peer_apps[id]
+            .as_ref()
+            .unwrap()
+            .world()
+            .resource::<P2PSession>()
+    }
+
+    pub fn link(&self, id: usize, remote: usize) -> EntityRef<'_> {
+        self.peer_apps[id]
+            .as_ref()
+            .unwrap()
+            .world()
+            .entity(self.links[id][remote].unwrap())
+    }
+
+    /// Drop the App of a peer, abruptly closing every Link to it.
+    pub fn drop_peer(&mut self, id: usize) {
+        self.peer_apps[id] = None;
+        self.links.iter_mut().for_each(|links| links[id] = None);
+    }
+
+    pub fn advance_time(&mut self, duration: Duration) {
+        self.current_time += duration;
+        self.peer_apps.iter_mut().flatten().for_each(|app| {
+            app.insert_resource(TimeUpdateStrategy::ManualInstant(self.current_time));
+        });
+        #[cfg(feature = "test_utils")]
+        mock_instant::global::MockClock::advance(duration);
+        #[cfg(all(not(feature = "test_utils"), feature = "std"))]
+        std::thread::sleep(duration);
+    }
+
+    pub fn tick_step(&mut self, n: usize) {
+        for _ in 0..n {
+            self.advance_time(self.tick_duration);
+            self.peer_apps.iter_mut().enumerate().for_each(|(i, app)| {
+                if let Some(app) = app {
+                    error_span!("peer", ?i).in_scope(|| app.update());
+                }
+            });
+        }
+    }
+}
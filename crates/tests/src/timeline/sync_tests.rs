@@ -1,12 +1,19 @@
+use crate::protocol::NativeInput;
 use crate::stepper::{ClientServerStepper, StepperConfig};
+use alloc::vec::Vec;
 use core::time::Duration;
+use lightyear::input::native::prelude::InputMarker;
 use lightyear::interpolation::timeline::InterpolationConfig;
-use lightyear::prelude::InterpolationTimeline;
+use lightyear::prelude::{InterpolationTimeline, MessageManager, NetworkTarget, Replicate};
 use lightyear_core::tick::Tick;
 use lightyear_core::time::TickInstant;
 use lightyear_core::timeline::NetworkTimeline;
+use lightyear_link::prelude::{LinkConditionerConfig, RecvLinkConditioner};
 use lightyear_sync::prelude::client::{InputDelayConfig, RemoteTimeline};
-use lightyear_sync::prelude::{InputTimelineConfig, LocalTimelineSync, PingManager, SyncConfig};
+use lightyear_sync::prelude::server::InputArrival;
+use lightyear_sync::prelude::{
+    InputFeedbackConfig, InputTimelineConfig, LocalTimelineSync, PingManager, SyncConfig,
+};
 use lightyear_sync::timeline::sync::{SyncTargetTimeline, TimelineSync};
 
 const TICK_DURATION: Duration = Duration::from_millis(10);
@@ -153,6 +160,90 @@ fn input_delay_config_update_recomputes_public_delay() {
     assert_eq!(local_sync.input_delay(), 3);
 }
 
+/// With jitter on the upload path of the client, the input timing feedback settles: the mean
+/// margin reported by the server stays around the target instead of following the worst delay
+/// of each report interval.
+#[test_log::test]
+fn input_feedback_converges_under_upload_jitter() {
+    let mut stepper = ClientServerStepper::from_config(StepperConfig::single());
+    let feedback = InputFeedbackConfig {
+        target_margin_ticks: 3.0,
+        ..Default::default()
+    };
+    stepper
+        .client_app()
+        .world_mut()
+        .insert_resource(InputTimelineConfig::default().with_input_feedback(feedback));
+    // the inputs take 4 ± 2 ticks to reach the server
+    stepper.condition_client_of_link(
+        0,
+        Some(RecvLinkConditioner::new(
+            LinkConditionerConfig::default()
+                .with_incoming_latency(TICK_DURATION * 4)
+                .with_incoming_jitter(TICK_DURATION * 2),
+        )),
+        None,
+    );
+
+    // the client sends inputs for a replicated entity on every tick
+    let server_entity = stepper
+        .server_app
+        .world_mut()
+        .spawn(Replicate::to_clients(NetworkTarget::All))
+        .id();
+    stepper.frame_step(2);
+    let client_entity = stepper
+        .client(0)
+        .get::<MessageManager>()
+        .unwrap()
+        .entity_mapper
+        .get_local(server_entity)
+        .expect("entity was not replicated to client");
+    stepper
+        .client_app()
+        .world_mut()
+        .entity_mut(client_entity)
+        .insert(InputMarker::<NativeInput>::default());
+
+    // let the feedback loop settle
+    stepper.frame_step(300);
+
+    // one sample per report interval
+    let margins: Vec<i16> = (0..20)
+        .map(|_| {
+            stepper.frame_step(25);
+            stepper
+                .client_of(0)
+                .get::<InputArrival>()
+                .unwrap()
+                .last_report()
+                .expect("the server sends input timing reports")
+                .margin
+        })
+        .collect();
+    let mean = margins.iter().map(|m| f32::from(*m)).sum::<f32>() / margins.len() as f32;
+    assert!(
+        (mean - feedback.target_margin_ticks).abs() <= 1.0,
+        "reported margins {margins:?} did not converge to {}",
+        feedback.target_margin_ticks
+    );
+    assert!(
+        margins
+            .iter()
+            .all(|m| (f32::from(*m) - feedback.target_margin_ticks).abs() <= 2.0),
+        "reported margins {margins:?} drift away from {}",
+        feedback.target_margin_ticks
+    );
+
+    let local_sync = stepper.client_app().world().resource::<LocalTimelineSync>();
+    assert!(local_sync.last_input_timing_report().is_some());
+    assert!(
+        local_sync.input_feedback_offset().abs() < feedback.max_correction_ticks,
+        "the feedback offset {} is saturated",
+        local_sync.input_feedback_offset()
+    );
+}
+
 #[test_log::test]
 fn interpolation_timeline_objective_lags_remote_by_delay_margin() {
     let remote = remote_timeline_at(100);
@@ -154,6 +154,6 @@ fn peer_numeric_id(peer_id: PeerId) -> Option<u64> {
         PeerId::Entity(id) | PeerId::Netcode(id) | PeerId::Steam(id) | PeerId::Local(id) => {
             Some(id)
         }
-        PeerId::Raw(_) | PeerId::Server => None,
+        PeerId::Raw(_) | PeerId::Server | PeerId::PublicKey(_) => None,
     }
 }
@@ -9,6 +9,13 @@
 //! The crate also provides a [`MessageManager`] component that manages the process of sending and receiving messages for an entity.
 //! It stores a [`RemoteEntityMap`] that holds a mapping between the local and remote entities.
 //!
+//! ## Request/response
+//!
+//! The [`rpc`] module builds correlated requests and responses on top of messages: a
+//! [`RequestSender`](rpc::RequestSender) returns a [`RequestId`](rpc::RequestId) for each request,
+//! and the matching [`Response`](rpc::Response) (or a timeout/disconnection error) is triggered
+//! as an observer event.
+//!
 //! ## Timeline-targeted delivery
 //!
 //! A channel configured with
@@ -45,6 +52,7 @@ pub mod plugin;
 pub mod receive;
 mod receive_event;
 pub mod registry;
+pub mod rpc;
 pub mod send;
 mod send_trigger;
 #[cfg(feature = "server")]
@@ -57,6 +65,9 @@ pub mod prelude {
     pub use crate::receive::MessageReceiver;
     pub use crate::receive_event::RemoteEvent;
     pub use crate::registry::{AppMessageExt, MessageRegistry};
+    pub use crate::rpc::{
+        AppRpcExt, Request, RequestId, RequestSender, Response, ResponseSender, RpcError,
+    };
     pub use crate::send::MessageSender;
     pub use crate::send_trigger::EventSender;
     pub use crate::trigger::AppTriggerExt;
@@ -16,6 +16,7 @@ use core::hash::Hash;
 use lightyear_connection::direction::NetworkDirection;
 use lightyear_core::network::NetId;
 use lightyear_core::prelude::{Tick, TimelineKind};
+use lightyear_serde::bits::BitSerialize;
 use lightyear_serde::entity_map::{ReceiveEntityMap, RemoteEntityMap, SendEntityMap};
 use lightyear_serde::reader::Reader;
 use lightyear_serde::registry::{
@@ -433,6 +434,9 @@ pub trait AppMessageExt {
     #[doc(hidden)]
     /// Register a regular message type `M` that uses `ToBytes` for serialization.
     fn register_message_to_bytes<M: Message + ToBytes>(&mut self) -> MessageRegistration<'_, M>;
+
+    /// Register a regular message type `M` that is bit-packed with [`BitSerialize`].
+    fn register_message_bits<M: Message + BitSerialize>(&mut self) -> MessageRegistration<'_, M>;
 }
 
 impl AppMessageExt for App {
@@ -482,6 +486,10 @@ impl AppMessageExt for App {
     fn register_message_to_bytes<M: Message + ToBytes>(&mut self) -> MessageRegistration<'_, M> {
         self.register_message_custom_serde::<M>(SerializeFns::<M>::with_to_bytes())
     }
+
+    fn register_message_bits<M: Message + BitSerialize>(&mut self) -> MessageRegistration<'_, M> {
+        self.register_message_custom_serde::<M>(SerializeFns::<M>::with_bits())
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,468 @@
+//! Request/response RPCs on top of regular messages.
+//!
+//! Register a request type `Req` along with its response type `Resp` with
+//! [`AppRpcExt::register_request`]. The direction of the registration is the direction of the
+//! request; responses are sent in the reverse direction.
+//!
+//! The sending peer buffers requests in the [`RequestSender<Req, Resp>`] component of its link
+//! entity. Each request gets a [`RequestId`] which is used to correlate it with its response.
+//! The receiving peer observes [`Request<Req>`] and replies with the
+//! [`ResponseSender<Req, Resp>`] component, either immediately or in a later frame.
+//!
+//! The sender observes [`Response<Req, Resp>`], which contains either the response or an
+//! [`RpcError`] if the request timed out or if the connection was closed before the response
+//! arrived. Responses that arrive after the request was cancelled or timed out are dropped.
+//!
+//! Responses are keyed on the request type, so several request types can share a response type.
+//!
+//! ```rust,ignore
+//! app.register_request::<GetInventory, Inventory>()
+//!     .add_direction(NetworkDirection::ClientToServer);
+//!
+//! // client
+//! let id = request_sender.send::<ReliableChannel>(GetInventory);
+//! app.add_observer(|trigger: On<Response<GetInventory, Inventory>>| match &trigger.result {
+//!     Ok(inventory) => info!(?inventory),
+//!     Err(e) => error!("Request {:?} failed: {e}", trigger.id),
+//! });
+//!
+//! // server
+//! app.add_observer(
+//!     |trigger: On<Request<GetInventory>>,
+//!      mut sender: Query<&mut ResponseSender<GetInventory, Inventory>>| {
+//!         if let Ok(mut sender) = sender.get_mut(trigger.entity) {
+//!             sender.respond::<ReliableChannel>(trigger.id, Inventory::default());
+//!         }
+//!     },
+//! );
+//! ```
+//!
+//! Requests and responses are sent on the channel provided by the caller, so use a reliable
+//! channel unless the request can safely time out.
+use crate::Message;
+use crate::plugin::MessageSystems;
+use crate::receive::MessageReceiver;
+use crate::registry::{AppMessageExt, MessageRegistration};
+use crate::send::{MessageSender, Priority};
+use alloc::vec::Vec;
+use bevy_app::{App, PostUpdate, PreUpdate};
+use bevy_ecs::prelude::*;
+use bevy_reflect::Reflect;
+use bevy_time::{Real, Time};
+use core::marker::PhantomData;
+use core::time::Duration;
+use lightyear_connection::client::Disconnected;
+use lightyear_connection::direction::NetworkDirection;
+use lightyear_core::id::{PeerId, RemoteId};
+use lightyear_transport::channel::{Channel, ChannelKind};
+use lightyear_utils::collections::HashMap;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+#[allow(unused_imports)]
+use tracing::{debug, trace};
+
+/// Timeout used by [`RequestSender::send`]
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Identifies a request sent by a [`RequestSender`], unique per link entity
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub struct RequestId(pub u64);
+
+/// Message sent over the network for a request
+#[doc(hidden)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RpcRequest<Req> {
+    id: RequestId,
+    request: Req,
+}
+
+/// Message sent over the network for a response to a request of type `Req`
+#[doc(hidden)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RpcResponse<Req, Resp> {
+    id: RequestId,
+    response: Resp,
+    #[serde(skip)]
+    _marker: PhantomData<fn() -> Req>,
+}
+
+/// Reasons why a request did not get a response
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum RpcError {
+    #[error("the request timed out")]
+    Timeout,
+    #[error("the connection was closed before the response was received")]
+    Disconnected,
+}
+
+/// Triggered on the receiving peer when a request of type `Req` is received.
+///
+/// Reply with the [`ResponseSender`] component of `entity`, using the same `id`.
+#[derive(EntityEvent, Debug)]
+pub struct Request<Req: Message> {
+    /// Link entity on which the request was received
+    pub entity: Entity,
+    pub from: PeerId,
+    pub id: RequestId,
+    pub request: Req,
+}
+
+/// Triggered on the requesting peer when a request of type `Req` completes.
+#[derive(EntityEvent, Debug)]
+pub struct Response<Req: Message, Resp: Message> {
+    /// Link entity from which the request was sent
+    pub entity: Entity,
+    pub id: RequestId,
+    pub result: Result<Resp, RpcError>,
+    _marker: PhantomData<fn() -> Req>,
+}
+
+impl<Req: Message, Resp: Message> Response<Req, Resp> {
+    fn new(entity: Entity, id: RequestId, result: Result<Resp, RpcError>) -> Self {
+        Self {
+            entity,
+            id,
+            result,
+            _marker: PhantomData,
+        }
+    }
+}
+
+struct PendingRequest {
+    timeout: Duration,
+    elapsed: Duration,
+}
+
+/// Component used to send requests of type `Req` and track their responses of type `Resp`.
+#[derive(Component)]
+pub struct RequestSender<Req: Message, Resp: Message> {
+    next_id: u64,
+    send: Vec<(RpcRequest<Req>, ChannelKind, &'static str, Priority)>,
+    pending: HashMap<RequestId, PendingRequest>,
+    _marker: PhantomData<fn() -> Resp>,
+}
+
+impl<Req: Message, Resp: Message> Default for RequestSender<Req, Resp> {
+    fn default() -> Self {
+        Self {
+            next_id: 0,
+            send: Vec::new(),
+            pending: HashMap::default(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<Req: Message, Resp: Message> RequestSender<Req, Resp> {
+    /// Buffers a request to be sent over the channel, with the [`DEFAULT_REQUEST_TIMEOUT`]
+    pub fn send<C: Channel>(&mut self, request: Req) -> RequestId {
+        self.send_with_timeout::<C>(request, DEFAULT_REQUEST_TIMEOUT)
+    }
+
+    /// Buffers a request to be sent over the channel.
+    ///
+    /// If no response is received within `timeout`, a [`Response`] with [`RpcError::Timeout`]
+    /// is triggered.
+    pub fn send_with_timeout<C: Channel>(&mut self, request: Req, timeout: Duration) -> RequestId {
+        let id = RequestId(self.next_id);
+        self.next_id = self.next_id.wrapping_add(1);
+        self.send.push((
+            RpcRequest { id, request },
+            ChannelKind::of::<C>(),
+            core::any::type_name::<C>(),
+            1.0,
+        ));
+        self.pending.insert(
+            id,
+            PendingRequest {
+                timeout,
+                elapsed: Duration::ZERO,
+            },
+        );
+        id
+    }
+
+    /// Stop waiting for the response of a request. No [`Response`] will be triggered for it.
+    ///
+    /// Returns false if the request was not pending.
+    pub fn cancel(&mut self, id: RequestId) -> bool {
+        self.pending.remove(&id).is_some()
+    }
+
+    /// Returns true if the request is still waiting for a response
+    pub fn is_pending(&self, id: RequestId) -> bool {
+        self.pending.contains_key(&id)
+    }
+
+    /// Number of requests waiting for a response
+    pub fn num_pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Advance the time of every pending request, and return the requests that timed out
+    fn expire(&mut self, delta: Duration) -> Vec<RequestId> {
+        let mut expired = Vec::new();
+        self.pending.retain(|id, pending| {
+            pending.elapsed += delta;
+            if pending.elapsed >= pending.timeout {
+                expired.push(*id);
+                return false;
+            }
+            true
+        });
+        expired
+    }
+}
+
+/// Component used to reply to requests of type `Req` with a response of type `Resp`.
+#[derive(Component)]
+pub struct ResponseSender<Req: Message, Resp: Message> {
+    send: Vec<(RpcResponse<Req, Resp>, ChannelKind, &'static str, Priority)>,
+}
+
+impl<Req: Message, Resp: Message> Default for ResponseSender<Req, Resp> {
+    fn default() -> Self {
+        Self { send: Vec::new() }
+    }
+}
+
+impl<Req: Message, Resp: Message> ResponseSender<Req, Resp> {
+    /// Buffers the response to the request `id`, to be sent over the channel
+    pub fn respond<C: Channel>(&mut self, id: RequestId, response: Resp) {
+        self.send.push((
+            RpcResponse {
+                id,
+                response,
+                _marker: PhantomData,
+            },
+            ChannelKind::of::<C>(),
+            core::any::type_name::<C>(),
+            1.0,
+        ));
+    }
+}
+
+pub struct RequestRegistration<'a, Req, Resp> {
+    pub app: &'a mut App,
+    _marker: PhantomData<(Req, Resp)>,
+}
+
+impl<Req, Resp> RequestRegistration<'_, Req, Resp>
+where
+    Req: Message + Serialize + DeserializeOwned,
+    Resp: Message + Serialize + DeserializeOwned,
+{
+    /// Adds the [`RequestSender`] on each side that sends this request, and the
+    /// [`ResponseSender`] on each side that receives it.
+    pub fn add_direction(&mut self, direction: NetworkDirection) -> &mut Self {
+        MessageRegistration::<RpcRequest<Req>> {
+            app: self.app,
+            _marker: PhantomData,
+        }
+        .add_direction(direction);
+        MessageRegistration::<RpcResponse<Req, Resp>> {
+            app: self.app,
+            _marker: PhantomData,
+        }
+        .add_direction(direction.reverse());
+        self
+    }
+}
+
+pub trait AppRpcExt {
+    /// Register a request type `Req`, which is answered with a response of type `Resp`.
+    fn register_request<Req, Resp>(&mut self) -> RequestRegistration<'_, Req, Resp>
+    where
+        Req: Message + Serialize + DeserializeOwned,
+        Resp: Message + Serialize + DeserializeOwned;
+}
+
+impl AppRpcExt for App {
+    fn register_request<Req, Resp>(&mut self) -> RequestRegistration<'_, Req, Resp>
+    where
+        Req: Message + Serialize + DeserializeOwned,
+        Resp: Message + Serialize + DeserializeOwned,
+    {
+        // the rpc components are added with the message senders of the corresponding direction
+        self.try_register_required_components::<MessageSender<RpcRequest<Req>>, RequestSender<Req, Resp>>()
+            .ok();
+        self.try_register_required_components::<
+            MessageSender<RpcResponse<Req, Resp>>,
+            ResponseSender<Req, Resp>,
+        >()
+        .ok();
+        self.register_message::<RpcRequest<Req>>();
+        self.register_message::<RpcResponse<Req, Resp>>();
+
+        self.add_systems(
+            PreUpdate,
+            (
+                receive_requests::<Req>,
+                receive_responses::<Req, Resp>,
+                expire_requests::<Req, Resp>,
+            )
+                .chain()
+                .after(MessageSystems::Receive),
+        );
+        self.add_systems(
+            PostUpdate,
+            (flush_requests::<Req, Resp>, flush_responses::<Req, Resp>)
+                .before(MessageSystems::Send),
+        );
+        self.add_observer(cancel_on_disconnect::<Req, Resp>);
+        RequestRegistration {
+            app: self,
+            _marker: PhantomData,
+        }
+    }
+}
+
+fn flush_requests<Req: Message, Resp: Message>(
+    mut query: Query<(
+        &mut RequestSender<Req, Resp>,
+        &mut MessageSender<RpcRequest<Req>>,
+    )>,
+) {
+    query.iter_mut().for_each(|(mut requests, mut sender)| {
+        requests
+            .send
+            .drain(..)
+            .for_each(|(request, channel_kind, channel_name, priority)| {
+                sender.send_erased(request, channel_kind, channel_name, priority);
+            });
+    });
+}
+
+fn flush_responses<Req: Message, Resp: Message>(
+    mut query: Query<(
+        &mut ResponseSender<Req, Resp>,
+        &mut MessageSender<RpcResponse<Req, Resp>>,
+    )>,
+) {
+    query.iter_mut().for_each(|(mut responses, mut sender)| {
+        responses
+            .send
+            .drain(..)
+            .for_each(|(response, channel_kind, channel_name, priority)| {
+                sender.send_erased(response, channel_kind, channel_name, priority);
+            });
+    });
+}
+
+fn receive_requests<Req: Message>(
+    mut query: Query<(Entity, &RemoteId, &mut MessageReceiver<RpcRequest<Req>>)>,
+    mut commands: Commands,
+) {
+    query
+        .iter_mut()
+        .for_each(|(entity, remote_id, mut receiver)| {
+            receiver.receive().for_each(|RpcRequest { id, request }| {
+                trace!(?entity, ?id, "Received request");
+                commands.trigger(Request {
+                    entity,
+                    from: remote_id.0,
+                    id,
+                    request,
+                });
+            });
+        });
+}
+
+fn receive_responses<Req: Message, Resp: Message>(
+    mut query: Query<(
+        Entity,
+        &mut RequestSender<Req, Resp>,
+        &mut MessageReceiver<RpcResponse<Req, Resp>>,
+    )>,
+    mut commands: Commands,
+) {
+    query
+        .iter_mut()
+        .for_each(|(entity, mut requests, mut receiver)| {
+            receiver
+                .receive()
+                .for_each(|RpcResponse { id, response, .. }| {
+                    if !requests.cancel(id) {
+                        debug!(
+                            ?entity,
+                            ?id,
+                            "Dropping response to a request that is not pending"
+                        );
+                        return;
+                    }
+                    commands.trigger(Response::<Req, Resp>::new(entity, id, Ok(response)));
+                });
+        });
+}
+
+fn expire_requests<Req: Message, Resp: Message>(
+    time: Res<Time<Real>>,
+    mut query: Query<(Entity, &mut RequestSender<Req, Resp>)>,
+    mut commands: Commands,
+) {
+    let delta = time.delta();
+    query.iter_mut().for_each(|(entity, mut requests)| {
+        for id in requests.expire(delta) {
+            debug!(?entity, ?id, "Request timed out");
+            commands.trigger(Response::<Req, Resp>::new(
+                entity,
+                id,
+                Err(RpcError::Timeout),
+            ));
+        }
+    });
+}
+
+/// Pending requests cannot get a response once the connection is closed
+fn cancel_on_disconnect<Req: Message, Resp: Message>(
+    trigger: On<Add, Disconnected>,
+    mut query: Query<&mut RequestSender<Req, Resp>>,
+    mut commands: Commands,
+) {
+    let entity = trigger.entity;
+    let Ok(mut requests) = query.get_mut(entity) else {
+        return;
+    };
+    requests.send.clear();
+    for (id, _) in requests.pending.drain() {
+        commands.trigger(Response::<Req, Resp>::new(
+            entity,
+            id,
+            Err(RpcError::Disconnected),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Channel1;
+
+    #[test]
+    fn request_ids_are_tracked_until_completion() {
+        let mut sender = RequestSender::<u32, u32>::default();
+        let a = sender.send::<Channel1>(1);
+        let b = sender.send_with_timeout::<Channel1>(2, Duration::from_millis(100));
+        assert_ne!(a, b);
+        assert_eq!(sender.num_pending(), 2);
+        assert_eq!(sender.send.len(), 2);
+
+        assert!(sender.cancel(a));
+        assert!(!sender.cancel(a));
+        assert!(sender.is_pending(b));
+    }
+
+    #[test]
+    fn requests_expire_after_their_timeout() {
+        let mut sender = RequestSender::<u32, u32>::default();
+        let short = sender.send_with_timeout::<Channel1>(1, Duration::from_millis(100));
+        let long = sender.send_with_timeout::<Channel1>(2, Duration::from_millis(300));
+
+        assert!(sender.expire(Duration::from_millis(50)).is_empty());
+        assert_eq!(sender.expire(Duration::from_millis(50)), [short]);
+        assert!(sender.is_pending(long));
+        assert_eq!(sender.expire(Duration::from_millis(200)), [long]);
+        assert_eq!(sender.num_pending(), 0);
+    }
+}
@@ -114,6 +114,18 @@ impl<M: Message> MessageSender<M> {
         self.send_with_priority::<C>(message, 1.0);
     }
 
+    /// Buffers a message to be sent over a channel that was resolved ahead of time
+    pub(crate) fn send_erased(
+        &mut self,
+        message: M,
+        channel_kind: ChannelKind,
+        channel_name: &'static str,
+        priority: Priority,
+    ) {
+        self.send
+            .push((message, channel_kind, channel_name, priority));
+    }
+
     /// Take all messages from the [`MessageSender<M>`], serialize them, and buffer them
     /// on the appropriate channel of the [`Transport`].
     ///
@@ -0,0 +1,536 @@
+//! Bit-level serialization.
+//!
+//! [`ToBytes`](crate::ToBytes) and the postcard functions always write whole bytes. Values that
+//! only need a few bits (booleans, small enums, [quantized](crate::quantize) floats) can instead
+//! implement [`BitSerialize`] to be packed together with a [`BitWriter`].
+//!
+//! A bit-packed value always starts on a byte boundary and is padded to the next byte when the
+//! [`BitWriter`] is finished, so it can be mixed with byte-aligned values in the same buffer:
+//!
+//! ```rust
+//! use lightyear_serde::bits::BitSerialize;
+//! use lightyear_serde::reader::Reader;
+//! use lightyear_serde::writer::Writer;
+//!
+//! let mut writer = Writer::default();
+//! let mut bits = writer.bit_writer();
+//! true.encode(&mut bits).unwrap();
+//! bits.write_bits(5, 3);
+//! bits.finish();
+//! // 4 bits were written, padded to 1 byte
+//! assert_eq!(writer.len(), 1);
+//!
+//! let mut reader = Reader::from(writer.into_bytes());
+//! let (flag, value) = reader
+//!     .read_bit_packed(|bits| Ok((bool::decode(bits)?, bits.read_bits(3)?)))
+//!     .unwrap();
+//! assert!(flag);
+//! assert_eq!(value, 5);
+//! ```
+//!
+//! With the `derive` feature, `#[derive(BitSerialize)]` implements the trait for structs and
+//! enums. Each field is encoded with its own [`BitSerialize`] implementation, unless it has one of
+//! these attributes:
+//! - `#[bits(float(min = -100.0, max = 100.0, bits = 16))]`: a [`BoundedFloat`](crate::quantize::BoundedFloat),
+//!   for fields implementing [`BoundedFloats`](crate::quantize::BoundedFloats)
+//! - `#[bits(quat(bits = 10))]`: a [`SmallestThree`](crate::quantize::SmallestThree) rotation,
+//!   for fields implementing [`Quaternion`](crate::quantize::Quaternion)
+//! - `#[bits(delta(baseline = 1000))]`: an integer encoded as the difference with a baseline,
+//!   which can be a constant or a previous field of the struct
+//! - `#[bits(uint(bits = 5))]`: an unsigned integer that fits in the given number of bits;
+//!   encoding a larger value returns [`SerializationError::InvalidValue`]
+//!
+//! The enum variants are encoded with the smallest number of bits that can represent them.
+//!
+//! The derive refers to this crate as `::lightyear_serde`; use `#[bits(crate = "path")]` on the
+//! type if it is re-exported under another path.
+use crate::SerializationError;
+use crate::reader::Reader;
+use crate::writer::Writer;
+use alloc::vec::Vec;
+use bytes::BufMut;
+
+#[cfg(feature = "derive")]
+pub use lightyear_serde_derive::BitSerialize;
+
+/// Writes values bit by bit into a byte buffer.
+///
+/// Bits are written least-significant first. The last partial byte is padded with zeroes when the
+/// writer is [finished](BitWriter::finish) or dropped.
+pub struct BitWriter<'a> {
+    buffer: &'a mut dyn BufMut,
+    /// Bits that don't fill a byte yet
+    scratch: u128,
+    scratch_bits: u32,
+    bits_written: usize,
+}
+
+impl<'a> BitWriter<'a> {
+    pub fn new(buffer: &'a mut dyn BufMut) -> Self {
+        Self {
+            buffer,
+            scratch: 0,
+            scratch_bits: 0,
+            bits_written: 0,
+        }
+    }
+
+    /// Number of bits written so far, without the padding.
+    pub fn bits_written(&self) -> usize {
+        self.bits_written
+    }
+
+    /// Write the `bits` least significant bits of `value`. The higher bits are dropped.
+    ///
+    /// # Panics
+    ///
+    /// If `bits` is greater than 64.
+    pub fn write_bits(&mut self, value: u64, bits: u32) {
+        assert!(bits <= 64, "cannot write more than 64 bits at once");
+        if bits == 0 {
+            return;
+        }
+        let value = if bits == 64 {
+            value
+        } else {
+            value & ((1 << bits) - 1)
+        };
+        self.scratch |= (value as u128) << self.scratch_bits;
+        self.scratch_bits += bits;
+        self.bits_written += bits as usize;
+        while self.scratch_bits >= 8 {
+            self.buffer.put_u8(self.scratch as u8);
+            self.scratch >>= 8;
+            self.scratch_bits -= 8;
+        }
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_bits(value as u64, 1);
+    }
+
+    /// Write an unsigned integer using groups of 4 bits followed by a continuation bit, so that
+    /// small values use few bits (5 bits below 16, 10 bits below 256, etc.)
+    pub fn write_var_u64(&mut self, mut value: u64) {
+        loop {
+            self.write_bits(value & 0b1111, 4);
+            value >>= 4;
+            self.write_bool(value != 0);
+            if value == 0 {
+                return;
+            }
+        }
+    }
+
+    /// Write the difference between `value` and a `baseline` known by the reader.
+    ///
+    /// Values close to the baseline only use a few bits.
+    pub fn write_delta(&mut self, value: i64, baseline: i64) {
+        self.write_var_u64(zigzag(value.wrapping_sub(baseline)));
+    }
+
+    /// Pad the last byte with zeroes and flush it to the buffer.
+    pub fn finish(self) {
+        // the padding is written when the writer is dropped
+    }
+}
+
+impl Drop for BitWriter<'_> {
+    fn drop(&mut self) {
+        if self.scratch_bits > 0 {
+            self.buffer.put_u8(self.scratch as u8);
+            self.scratch = 0;
+            self.scratch_bits = 0;
+        }
+    }
+}
+
+/// Reads values bit by bit from a byte slice written by a [`BitWriter`].
+pub struct BitReader<'a> {
+    bytes: &'a [u8],
+    /// Position in bits
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    /// Number of bits read so far.
+    pub fn bits_read(&self) -> usize {
+        self.position
+    }
+
+    /// Number of bytes that contain the bits read so far, including the padding of the last byte.
+    pub fn bytes_read(&self) -> usize {
+        self.position.div_ceil(8)
+    }
+
+    /// Read a value written on `bits` bits.
+    ///
+    /// # Panics
+    ///
+    /// If `bits` is greater than 64.
+    pub fn read_bits(&mut self, bits: u32) -> Result<u64, SerializationError> {
+        assert!(bits <= 64, "cannot read more than 64 bits at once");
+        if self.position + bits as usize > self.bytes.len() * 8 {
+            return Err(SerializationError::UnexpectedEnd);
+        }
+        let mut value = 0u64;
+        let mut read = 0;
+        while read < bits {
+            let byte = self.bytes[self.position / 8];
+            let offset = (self.position % 8) as u32;
+            let count = (8 - offset).min(bits - read);
+            let chunk = (byte >> offset) as u64 & ((1 << count) - 1);
+            value |= chunk << read;
+            read += count;
+            self.position += count as usize;
+        }
+        Ok(value)
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, SerializationError> {
+        Ok(self.read_bits(1)? == 1)
+    }
+
+    /// Read an unsigned integer written with [`BitWriter::write_var_u64`].
+    pub fn read_var_u64(&mut self) -> Result<u64, SerializationError> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            if shift >= 64 {
+                return Err(SerializationError::InvalidValue);
+            }
+            value |= self.read_bits(4)? << shift;
+            shift += 4;
+            if !self.read_bool()? {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Read a value written with [`BitWriter::write_delta`] with the same `baseline`.
+    pub fn read_delta(&mut self, baseline: i64) -> Result<i64, SerializationError> {
+        Ok(baseline.wrapping_add(unzigzag(self.read_var_u64()?)))
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+impl Reader {
+    /// Read bit-packed values from the current position, then move the position to the end of the
+    /// last byte read.
+    pub fn read_bit_packed<T>(
+        &mut self,
+        f: impl FnOnce(&mut BitReader<'_>) -> Result<T, SerializationError>,
+    ) -> Result<T, SerializationError> {
+        let mut bits = BitReader::new(self.remaining_slice());
+        let value = f(&mut bits)?;
+        let bytes_read = bits.bytes_read();
+        self.set_position(self.position() + bytes_read as u64);
+        Ok(value)
+    }
+}
+
+/// Types that can be packed at the bit level.
+pub trait BitSerialize {
+    /// Write the value, or return an error if it cannot be represented, for example an integer
+    /// that doesn't fit in its declared number of bits.
+    fn encode(&self, writer: &mut BitWriter) -> Result<(), SerializationError>;
+
+    fn decode(reader: &mut BitReader) -> Result<Self, SerializationError>
+    where
+        Self: Sized;
+}
+
+/// Write a bit-packed value into a byte-aligned [`Writer`].
+pub fn to_writer<T: BitSerialize>(
+    value: &T,
+    writer: &mut Writer,
+) -> Result<(), SerializationError> {
+    value.encode(&mut writer.bit_writer())
+}
+
+/// Read a bit-packed value from a byte-aligned [`Reader`].
+pub fn from_reader<T: BitSerialize>(reader: &mut Reader) -> Result<T, SerializationError> {
+    reader.read_bit_packed(T::decode)
+}
+
+impl BitSerialize for bool {
+    fn encode(&self, writer: &mut BitWriter) -> Result<(), SerializationError> {
+        writer.write_bool(*self);
+        Ok(())
+    }
+
+    fn decode(reader: &mut BitReader) -> Result<Self, SerializationError> {
+        reader.read_bool()
+    }
+}
+
+macro_rules! impl_bit_serialize_integer {
+    ($($ty:ty => $unsigned:ty),*) => {
+        $(
+            impl BitSerialize for $ty {
+                fn encode(&self, writer: &mut BitWriter) -> Result<(), SerializationError> {
+                    writer.write_bits(*self as $unsigned as u64, <$ty>::BITS);
+                    Ok(())
+                }
+
+                fn decode(reader: &mut BitReader) -> Result<Self, SerializationError> {
+                    Ok(reader.read_bits(<$ty>::BITS)? as $unsigned as $ty)
+                }
+            }
+        )*
+    };
+}
+
+impl_bit_serialize_integer!(
+    u8 => u8, u16 => u16, u32 => u32, u64 => u64,
+    i8 => u8, i16 => u16, i32 => u32, i64 => u64
+);
+
+impl BitSerialize for f32 {
+    fn encode(&self, writer: &mut BitWriter) -> Result<(), SerializationError> {
+        writer.write_bits(self.to_bits() as u64, 32);
+        Ok(())
+    }
+
+    fn decode(reader: &mut BitReader) -> Result<Self, SerializationError> {
+        Ok(f32::from_bits(reader.read_bits(32)? as u32))
+    }
+}
+
+impl BitSerialize for f64 {
+    fn encode(&self, writer: &mut BitWriter) -> Result<(), SerializationError> {
+        writer.write_bits(self.to_bits(), 64);
+        Ok(())
+    }
+
+    fn decode(reader: &mut BitReader) -> Result<Self, SerializationError> {
+        Ok(f64::from_bits(reader.read_bits(64)?))
+    }
+}
+
+impl<T: BitSerialize> BitSerialize for Option<T> {
+    fn encode(&self, writer: &mut BitWriter) -> Result<(), SerializationError> {
+        writer.write_bool(self.is_some());
+        if let Some(value) = self {
+            value.encode(writer)?;
+        }
+        Ok(())
+    }
+
+    fn decode(reader: &mut BitReader) -> Result<Self, SerializationError> {
+        if reader.read_bool()? {
+            Ok(Some(T::decode(reader)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<T: BitSerialize, const N: usize> BitSerialize for [T; N] {
+    fn encode(&self, writer: &mut BitWriter) -> Result<(), SerializationError> {
+        self.iter().try_for_each(|value| value.encode(writer))
+    }
+
+    fn decode(reader: &mut BitReader) -> Result<Self, SerializationError> {
+        let values = (0..N)
+            .map(|_| T::decode(reader))
+            .collect::<Result<Vec<_>, _>>()?;
+        values
+            .try_into()
+            .map_err(|_| SerializationError::InvalidValue)
+    }
+}
+
+impl<T: BitSerialize> BitSerialize for Vec<T> {
+    fn encode(&self, writer: &mut BitWriter) -> Result<(), SerializationError> {
+        writer.write_var_u64(self.len() as u64);
+        self.iter().try_for_each(|value| value.encode(writer))
+    }
+
+    fn decode(reader: &mut BitReader) -> Result<Self, SerializationError> {
+        let len = reader.read_var_u64()? as usize;
+        // every value uses at least one bit
+        if len > reader.bytes.len() * 8 - reader.position {
+            return Err(SerializationError::UnexpectedEnd);
+        }
+        (0..len).map(|_| T::decode(reader)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn write_and_read_bits() {
+        let mut buffer = Vec::new();
+        let mut writer = BitWriter::new(&mut buffer);
+        writer.write_bits(0b101, 3);
+        writer.write_bits(u64::MAX, 64);
+        writer.write_bool(true);
+        writer.write_bits(0x1234, 13);
+        assert_eq!(writer.bits_written(), 81);
+        writer.finish();
+        assert_eq!(buffer.len(), 11);
+
+        let mut reader = BitReader::new(&buffer);
+        assert_eq!(reader.read_bits(3).unwrap(), 0b101);
+        assert_eq!(reader.read_bits(64).unwrap(), u64::MAX);
+        assert!(reader.read_bool().unwrap());
+        assert_eq!(reader.read_bits(13).unwrap(), 0x1234);
+        assert_eq!(reader.bytes_read(), 11);
+        // only the padding is left
+        assert!(matches!(
+            reader.read_bits(8),
+            Err(SerializationError::UnexpectedEnd)
+        ));
+    }
+
+    #[test]
+    fn var_u64_and_delta() {
+        let mut buffer = Vec::new();
+        let mut writer = BitWriter::new(&mut buffer);
+        writer.write_var_u64(7);
+        assert_eq!(writer.bits_written(), 5);
+        writer.write_var_u64(u64::MAX);
+        writer.write_delta(1003, 1000);
+        writer.write_delta(990, 1000);
+        writer.write_delta(i64::MIN, i64::MAX);
+        writer.finish();
+
+        let mut reader = BitReader::new(&buffer);
+        assert_eq!(reader.read_var_u64().unwrap(), 7);
+        assert_eq!(reader.read_var_u64().unwrap(), u64::MAX);
+        assert_eq!(reader.read_delta(1000).unwrap(), 1003);
+        assert_eq!(reader.read_delta(1000).unwrap(), 990);
+        assert_eq!(reader.read_delta(i64::MAX).unwrap(), i64::MIN);
+    }
+
+    #[test]
+    fn bit_packed_values_in_byte_aligned_buffer() {
+        let mut writer = Writer::default();
+        writer.extend_from_slice(&[42]);
+        let values = (Some(-3i16), vec![true, false, true], [1.5f32, -2.0]);
+        let mut bits = writer.bit_writer();
+        values.0.encode(&mut bits).unwrap();
+        values.1.encode(&mut bits).unwrap();
+        values.2.encode(&mut bits).unwrap();
+        bits.finish();
+        writer.extend_from_slice(&[43]);
+
+        let mut reader = Reader::from(writer.into_bytes());
+        assert_eq!(reader.read_bit_packed(|r| r.read_bits(8)).unwrap(), 42);
+        let decoded = reader
+            .read_bit_packed(|r| {
+                Ok((
+                    Option::<i16>::decode(r)?,
+                    Vec::<bool>::decode(r)?,
+                    <[f32; 2]>::decode(r)?,
+                ))
+            })
+            .unwrap();
+        assert_eq!(decoded, values);
+        assert_eq!(reader.remaining_slice(), &[43]);
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derive_bit_serialize() {
+        #[derive(BitSerialize, Debug, PartialEq)]
+        #[bits(crate = "crate")]
+        struct Snapshot {
+            #[bits(uint(bits = 10))]
+            tick: u16,
+            #[bits(delta(baseline = tick))]
+            input_tick: u16,
+            #[bits(delta(baseline = 1000))]
+            health: i32,
+            #[bits(float(min = -512.0, max = 512.0, bits = 18))]
+            position: [f32; 3],
+            #[bits(quat(bits = 10))]
+            rotation: [f32; 4],
+            state: State,
+        }
+
+        #[derive(BitSerialize, Debug, PartialEq)]
+        #[bits(crate = "crate")]
+        enum State {
+            Idle,
+            Moving(#[bits(float(min = 0.0, max = 10.0, bits = 8))] f32),
+            Dead { respawn_tick: Option<u16> },
+        }
+
+        let snapshot = Snapshot {
+            tick: 1000,
+            input_tick: 1003,
+            health: 990,
+            position: [12.5, -3.0, 500.0],
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            state: State::Dead {
+                respawn_tick: Some(1200),
+            },
+        };
+        let mut writer = Writer::default();
+        to_writer(&snapshot, &mut writer).unwrap();
+        // 10 + 10 + 10 + 3 * 18 + (2 + 3 * 10) + (2 + 1 + 16) bits
+        assert_eq!(writer.len(), 17);
+
+        let decoded: Snapshot = from_reader(&mut Reader::from(writer.into_bytes())).unwrap();
+        assert_eq!(decoded.tick, snapshot.tick);
+        assert_eq!(decoded.input_tick, snapshot.input_tick);
+        assert_eq!(decoded.health, snapshot.health);
+        let quantization = crate::quantize::BoundedFloat::new(-512.0, 512.0, 18);
+        for (decoded, value) in decoded.position.iter().zip(snapshot.position) {
+            assert!((decoded - value).abs() <= quantization.max_error());
+        }
+        let dot: f32 = (0..4)
+            .map(|i| decoded.rotation[i] * snapshot.rotation[i])
+            .sum();
+        assert!(dot > 0.999);
+        assert_eq!(decoded.state, snapshot.state);
+
+        let mut writer = Writer::default();
+        to_writer(&State::Moving(2.5), &mut writer).unwrap();
+        let decoded: State = from_reader(&mut Reader::from(writer.into_bytes())).unwrap();
+        let State::Moving(speed) = decoded else {
+            panic!("expected State::Moving, got {decoded:?}");
+        };
+        assert!((speed - 2.5).abs() < 0.02);
+    }
+
+    /// The check doesn't depend on `debug_assertions`, so this also covers release builds
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derive_uint_out_of_range() {
+        #[derive(BitSerialize, Debug, PartialEq)]
+        #[bits(crate = "crate")]
+        struct Tick(#[bits(uint(bits = 10))] u16);
+
+        assert!(matches!(
+            to_writer(&Tick(1024), &mut Writer::default()),
+            Err(SerializationError::InvalidValue)
+        ));
+        // the error is propagated through the containers
+        assert!(matches!(
+            to_writer(&vec![Tick(0), Tick(u16::MAX)], &mut Writer::default()),
+            Err(SerializationError::InvalidValue)
+        ));
+
+        let mut writer = Writer::default();
+        to_writer(&Tick(1023), &mut writer).unwrap();
+        let decoded: Tick = from_reader(&mut Reader::from(writer.into_bytes())).unwrap();
+        assert_eq!(decoded, Tick(1023));
+    }
+}
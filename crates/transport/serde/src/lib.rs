@@ -7,6 +7,10 @@
 //! It includes implementations for common types and collections, and utilities for
 //! efficient serialization, such as varint encoding.
 //!
+//! For data that dominates the bandwidth, such as positions and rotations replicated every tick,
+//! the [`bits`] module packs values at the bit level and [`quantize`] reduces the precision of
+//! floats and quaternions.
+//!
 //! This crate is fundamental for preparing data to be sent over the network and for
 //! reconstructing data received from remote peers.
 #![no_std]
@@ -24,9 +28,13 @@ use core::hash::{BuildHasher, Hash};
 use no_std_io2::io;
 use smallvec::{Array, SmallVec};
 
+/// Bit-level serialization, for values that don't need whole bytes.
+pub mod bits;
 /// Utilities for mapping entities during serialization and deserialization.
 pub mod entity_map;
 mod postcard_utils;
+/// Lossy compression of floats and rotations for the [`bits`] serialization.
+pub mod quantize;
 /// Provides the [`Reader`] struct and traits for deserializing data from a byte stream.
 pub mod reader;
 /// Defines traits and structures for registering serializable types.
@@ -39,6 +47,7 @@ pub mod writer;
 /// Commonly used items from the `lightyear_serde` crate.
 pub mod prelude {
     pub use crate::SerializationError;
+    pub use crate::bits::BitSerialize;
     pub use crate::entity_map::{ReceiveEntityMap, RemoteEntityMap, SendEntityMap};
     pub use no_std_io2::io::{Seek, SeekFrom};
 }
@@ -59,6 +68,8 @@ pub enum SerializationError {
     InvalidValue,
     #[error("Subtraction overflow")]
     SubtractionOverflow,
+    #[error("Unexpected end of the bit stream")]
+    UnexpectedEnd,
     #[error(transparent)]
     Postcard(#[from] postcard::Error),
 }
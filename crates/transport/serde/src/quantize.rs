@@ -0,0 +1,302 @@
+//! Quantization of floats and rotations.
+//!
+//! Most replicated floats don't need the full `f32` precision: a position inside a 1 km map only
+//! needs to be precise to the millimeter, which fits in 20 bits per axis. These types trade
+//! precision for bandwidth when writing to a [`BitWriter`]:
+//! - [`BoundedFloat`] maps a float in a known range to an integer of `bits` bits;
+//! - [`SmallestThree`] compresses a unit quaternion by dropping its largest component, which can
+//!   be recovered from the other three.
+//!
+//! They are used by the `#[bits(float(..))]` and `#[bits(quat(..))]` attributes of
+//! `#[derive(BitSerialize)]`, but can also be called directly from a manual
+//! [`BitSerialize`](crate::bits::BitSerialize) implementation.
+use crate::SerializationError;
+use crate::bits::{BitReader, BitWriter};
+use core::f32::consts::FRAC_1_SQRT_2;
+
+/// A float in the range `[min, max]`, encoded as an integer on `bits` bits.
+///
+/// Values outside of the range are clamped, and `NaN` is encoded as `min`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundedFloat {
+    pub min: f32,
+    pub max: f32,
+    pub bits: u32,
+}
+
+impl BoundedFloat {
+    /// # Panics
+    ///
+    /// If `bits` is not between 1 and 32, or if `max` is not greater than `min`.
+    pub const fn new(min: f32, max: f32, bits: u32) -> Self {
+        assert!(
+            bits > 0 && bits <= 32,
+            "BoundedFloat uses between 1 and 32 bits"
+        );
+        assert!(max > min, "BoundedFloat requires max > min");
+        Self { min, max, bits }
+    }
+
+    /// Number of intervals between the smallest and the largest encoded value.
+    fn steps(&self) -> f64 {
+        ((1u64 << self.bits) - 1) as f64
+    }
+
+    /// Largest difference between a value in the range and its decoded value.
+    pub fn max_error(&self) -> f32 {
+        ((self.max as f64 - self.min as f64) / self.steps() / 2.0) as f32
+    }
+
+    pub fn encode(&self, value: f32, writer: &mut BitWriter) {
+        let range = self.max as f64 - self.min as f64;
+        let normalized = ((value as f64 - self.min as f64) / range).clamp(0.0, 1.0);
+        // `as` maps NaN to 0
+        let quantized = (normalized * self.steps() + 0.5) as u64;
+        writer.write_bits(quantized, self.bits);
+    }
+
+    pub fn decode(&self, reader: &mut BitReader) -> Result<f32, SerializationError> {
+        let quantized = reader.read_bits(self.bits)?;
+        let range = self.max as f64 - self.min as f64;
+        Ok((self.min as f64 + quantized as f64 / self.steps() * range) as f32)
+    }
+}
+
+/// Types made of floats that can all be quantized with the same [`BoundedFloat`].
+pub trait BoundedFloats: Sized {
+    fn encode_bounded(&self, quantization: &BoundedFloat, writer: &mut BitWriter);
+
+    fn decode_bounded(
+        quantization: &BoundedFloat,
+        reader: &mut BitReader,
+    ) -> Result<Self, SerializationError>;
+}
+
+impl BoundedFloats for f32 {
+    fn encode_bounded(&self, quantization: &BoundedFloat, writer: &mut BitWriter) {
+        quantization.encode(*self, writer);
+    }
+
+    fn decode_bounded(
+        quantization: &BoundedFloat,
+        reader: &mut BitReader,
+    ) -> Result<Self, SerializationError> {
+        quantization.decode(reader)
+    }
+}
+
+impl<const N: usize> BoundedFloats for [f32; N] {
+    fn encode_bounded(&self, quantization: &BoundedFloat, writer: &mut BitWriter) {
+        self.iter()
+            .for_each(|value| quantization.encode(*value, writer));
+    }
+
+    fn decode_bounded(
+        quantization: &BoundedFloat,
+        reader: &mut BitReader,
+    ) -> Result<Self, SerializationError> {
+        let mut values = [0.0; N];
+        for value in values.iter_mut() {
+            *value = quantization.decode(reader)?;
+        }
+        Ok(values)
+    }
+}
+
+/// A unit quaternion encoded with the "smallest three" method.
+///
+/// The component with the largest absolute value is dropped, and its index is written on 2 bits.
+/// The three others are in `[-1/√2, 1/√2]` and are each written on `bits` bits. Since `q` and `-q`
+/// represent the same rotation, the quaternion is negated if needed so that the dropped component
+/// is positive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SmallestThree {
+    pub bits: u32,
+}
+
+impl SmallestThree {
+    /// # Panics
+    ///
+    /// If `bits` is not between 1 and 32.
+    pub const fn new(bits: u32) -> Self {
+        assert!(
+            bits > 0 && bits <= 32,
+            "SmallestThree uses between 1 and 32 bits"
+        );
+        Self { bits }
+    }
+
+    fn component(&self) -> BoundedFloat {
+        BoundedFloat::new(-FRAC_1_SQRT_2, FRAC_1_SQRT_2, self.bits)
+    }
+
+    /// Encode a quaternion given as `[x, y, z, w]`.
+    pub fn encode(&self, quaternion: [f32; 4], writer: &mut BitWriter) {
+        let norm = sqrt(quaternion.iter().map(|c| c * c).sum());
+        let quaternion = if norm > 0.0 {
+            quaternion.map(|c| c / norm)
+        } else {
+            [0.0, 0.0, 0.0, 1.0]
+        };
+        let largest = (0..4)
+            .max_by(|a, b| quaternion[*a].abs().total_cmp(&quaternion[*b].abs()))
+            .unwrap_or(3);
+        let sign = if quaternion[largest] < 0.0 { -1.0 } else { 1.0 };
+        writer.write_bits(largest as u64, 2);
+        let component = self.component();
+        (0..4)
+            .filter(|i| *i != largest)
+            .for_each(|i| component.encode(sign * quaternion[i], writer));
+    }
+
+    /// Decode a quaternion as `[x, y, z, w]`.
+    pub fn decode(&self, reader: &mut BitReader) -> Result<[f32; 4], SerializationError> {
+        let largest = reader.read_bits(2)? as usize;
+        let component = self.component();
+        let mut quaternion = [0.0; 4];
+        let mut sum_squares = 0.0;
+        for i in (0..4).filter(|i| *i != largest) {
+            let value = component.decode(reader)?;
+            sum_squares += value * value;
+            quaternion[i] = value;
+        }
+        quaternion[largest] = sqrt((1.0 - sum_squares).max(0.0));
+        Ok(quaternion)
+    }
+}
+
+/// Square root by Newton's method, since `f32::sqrt` is not available in `core`.
+fn sqrt(value: f32) -> f32 {
+    if value <= 0.0 {
+        return 0.0;
+    }
+    // halving the exponent gives a first approximation within a few percent
+    let mut root = f32::from_bits((value.to_bits() >> 1) + 0x1fbd_1df5);
+    for _ in 0..3 {
+        root = 0.5 * (root + value / root);
+    }
+    root
+}
+
+/// Rotations that can be encoded with [`SmallestThree`].
+pub trait Quaternion: Sized {
+    /// Components of the quaternion, as `[x, y, z, w]`.
+    fn to_xyzw(&self) -> [f32; 4];
+
+    /// Build the quaternion from decoded components, which are normalized up to the
+    /// quantization error.
+    fn from_xyzw(xyzw: [f32; 4]) -> Self;
+}
+
+impl Quaternion for [f32; 4] {
+    fn to_xyzw(&self) -> [f32; 4] {
+        *self
+    }
+
+    fn from_xyzw(xyzw: [f32; 4]) -> Self {
+        xyzw
+    }
+}
+
+#[cfg(feature = "bevy_math")]
+mod bevy_math_impls {
+    use super::*;
+    use bevy_math::{Quat, Vec2, Vec3, Vec4};
+
+    macro_rules! impl_bounded_floats_vec {
+        ($($ty:ty => $n:literal),*) => {
+            $(
+                impl BoundedFloats for $ty {
+                    fn encode_bounded(&self, quantization: &BoundedFloat, writer: &mut BitWriter) {
+                        self.to_array().encode_bounded(quantization, writer);
+                    }
+
+                    fn decode_bounded(
+                        quantization: &BoundedFloat,
+                        reader: &mut BitReader,
+                    ) -> Result<Self, SerializationError> {
+                        Ok(Self::from_array(<[f32; $n]>::decode_bounded(quantization, reader)?))
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_bounded_floats_vec!(Vec2 => 2, Vec3 => 3, Vec4 => 4);
+
+    impl Quaternion for Quat {
+        fn to_xyzw(&self) -> [f32; 4] {
+            self.to_array()
+        }
+
+        fn from_xyzw(xyzw: [f32; 4]) -> Self {
+            Quat::from_array(xyzw)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn bounded_float_error_is_bounded() {
+        let quantization = BoundedFloat::new(-100.0, 100.0, 16);
+        let values = [-100.0, -42.123, 0.0, 0.001, 99.99, 100.0];
+        let mut buffer = Vec::new();
+        let mut writer = BitWriter::new(&mut buffer);
+        values
+            .iter()
+            .for_each(|value| quantization.encode(*value, &mut writer));
+        // out of range values are clamped
+        quantization.encode(250.0, &mut writer);
+        quantization.encode(f32::NAN, &mut writer);
+        writer.finish();
+        assert_eq!(buffer.len(), 16);
+
+        let mut reader = BitReader::new(&buffer);
+        for value in values {
+            let decoded = quantization.decode(&mut reader).unwrap();
+            assert!((decoded - value).abs() <= quantization.max_error() + f32::EPSILON);
+        }
+        assert_eq!(quantization.decode(&mut reader).unwrap(), 100.0);
+        assert_eq!(quantization.decode(&mut reader).unwrap(), -100.0);
+    }
+
+    #[test]
+    fn smallest_three_round_trip() {
+        let quantization = SmallestThree::new(12);
+        let half = 0.5f32;
+        let quaternions = [
+            [0.0, 0.0, 0.0, 1.0],
+            // negative largest component: encoded as the opposite quaternion
+            [0.0, 0.0, 0.0, -1.0],
+            [half, -half, half, half],
+            [0.1825742, 0.3651484, 0.5477226, 0.7302967],
+        ];
+        let mut buffer = Vec::new();
+        let mut writer = BitWriter::new(&mut buffer);
+        quaternions
+            .iter()
+            .for_each(|q| quantization.encode(*q, &mut writer));
+        assert_eq!(writer.bits_written(), 4 * (2 + 3 * 12));
+        writer.finish();
+
+        let mut reader = BitReader::new(&buffer);
+        for q in quaternions {
+            let decoded = quantization.decode(&mut reader).unwrap();
+            // q and -q are the same rotation
+            let dot: f32 = (0..4).map(|i| q[i] * decoded[i]).sum();
+            assert!(dot.abs() > 0.9999, "{q:?} decoded as {decoded:?}");
+        }
+    }
+
+    #[test]
+    fn newton_sqrt() {
+        for value in [0.0f32, 1e-6, 0.25, 0.5, 1.0] {
+            let expected = value.sqrt();
+            assert!((sqrt(value) - expected).abs() < 1e-6);
+        }
+    }
+}
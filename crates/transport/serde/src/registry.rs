@@ -1,3 +1,4 @@
+use crate::bits::{self, BitSerialize};
 use crate::entity_map::{EntityMap, ReceiveEntityMap, SendEntityMap};
 use crate::postcard_utils;
 use crate::reader::Reader;
@@ -121,6 +122,16 @@ impl<M: ToBytes> SerializeFns<M> {
     }
 }
 
+impl<M: BitSerialize> SerializeFns<M> {
+    /// Serialize the type with its bit-packed [`BitSerialize`] representation.
+    pub fn with_bits() -> Self {
+        Self {
+            serialize: bits::to_writer::<M>,
+            deserialize: bits::from_reader::<M>,
+        }
+    }
+}
+
 type ErasedSerializeFn = unsafe fn(
     erased_serialize_fn: &ErasedSerializeFns,
     message: Ptr,
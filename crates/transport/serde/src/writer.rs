@@ -7,6 +7,7 @@
 //! we can split the message of as a separate [`Bytes`], but
 
 use crate::SerializationError;
+use crate::bits::BitWriter;
 use crate::varint::varint_len;
 use bytes::{BufMut, Bytes, BytesMut};
 use core::cmp;
@@ -88,6 +89,11 @@ impl Writer {
         self.0.extend_from_slice(extend)
     }
 
+    /// Start writing bit-packed values at the end of the buffer.
+    pub fn bit_writer(&mut self) -> BitWriter<'_> {
+        BitWriter::new(&mut self.0)
+    }
+
     /// Splits the buffer into two at the given index.
     ///
     /// Afterwards `self` contains elements `[at, len)`, and the returned `BytesMut`
@@ -0,0 +1,368 @@
+//! Derive macros for `lightyear_serde`.
+//!
+//! Use them through the `derive` feature of `lightyear_serde`, which re-exports them.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{
+    Data, DeriveInput, Error, Expr, Fields, GenericParam, Ident, LitStr, Path, Result,
+    parse_macro_input, parse_quote,
+};
+
+/// Implements `lightyear_serde::bits::BitSerialize`.
+///
+/// See the documentation of the `lightyear_serde::bits` module for the supported attributes.
+#[proc_macro_derive(BitSerialize, attributes(bits))]
+pub fn derive_bit_serialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    bit_serialize(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// How a field is written to the bit stream
+enum Encoding {
+    /// With the `BitSerialize` implementation of the field
+    Default,
+    Float {
+        min: Expr,
+        max: Expr,
+        bits: Expr,
+    },
+    Quat {
+        bits: Expr,
+    },
+    Delta {
+        baseline: Expr,
+    },
+    Uint {
+        bits: Expr,
+    },
+}
+
+struct Field {
+    /// Name of the field for named fields
+    ident: Option<Ident>,
+    /// Local variable holding the field in the generated code
+    binding: Ident,
+    ty: syn::Type,
+    encoding: Encoding,
+}
+
+fn bit_serialize(mut input: DeriveInput) -> Result<TokenStream2> {
+    let krate = crate_path(&input.attrs)?;
+    for param in input.generics.params.iter_mut() {
+        if let GenericParam::Type(param) = param {
+            param.bounds.push(parse_quote!(#krate::bits::BitSerialize));
+        }
+    }
+    let (encode, decode) = match &input.data {
+        Data::Struct(data) => {
+            let fields = parse_fields(&data.fields)?;
+            let pattern = pattern(&quote!(Self), &data.fields, &fields);
+            let encode_fields = encode_fields(&krate, &fields);
+            let decode_fields = decode_fields(&krate, &fields);
+            (
+                quote! {
+                    let #pattern = self;
+                    #encode_fields
+                    ::core::result::Result::Ok(())
+                },
+                quote! {
+                    #decode_fields
+                    ::core::result::Result::Ok(#pattern)
+                },
+            )
+        }
+        Data::Enum(data) => {
+            let count = data.variants.len();
+            // smallest number of bits that can represent every variant index
+            let index_bits = usize::BITS - count.saturating_sub(1).leading_zeros();
+            let mut encode_arms = Vec::new();
+            let mut decode_arms = Vec::new();
+            for (index, variant) in data.variants.iter().enumerate() {
+                let fields = parse_fields(&variant.fields)?;
+                let ident = &variant.ident;
+                let pattern = pattern(&quote!(Self::#ident), &variant.fields, &fields);
+                let encode_fields = encode_fields(&krate, &fields);
+                let decode_fields = decode_fields(&krate, &fields);
+                let index = index as u64;
+                encode_arms.push(quote! {
+                    #pattern => {
+                        writer.write_bits(#index, #index_bits);
+                        #encode_fields
+                    }
+                });
+                decode_arms.push(quote! {
+                    #index => {
+                        #decode_fields
+                        ::core::result::Result::Ok(#pattern)
+                    }
+                });
+            }
+            (
+                // an empty enum has no value to encode, but `match self {}` only compiles on
+                // the dereferenced value
+                if count == 0 {
+                    quote!(match *self {})
+                } else {
+                    quote! {
+                        match self {
+                            #(#encode_arms)*
+                        }
+                        ::core::result::Result::Ok(())
+                    }
+                },
+                quote! {
+                    match reader.read_bits(#index_bits)? {
+                        #(#decode_arms)*
+                        _ => ::core::result::Result::Err(#krate::SerializationError::InvalidValue),
+                    }
+                },
+            )
+        }
+        Data::Union(data) => {
+            return Err(Error::new(
+                data.union_token.span(),
+                "BitSerialize cannot be derived for unions",
+            ));
+        }
+    };
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #krate::bits::BitSerialize for #ident #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn encode(
+                &self,
+                writer: &mut #krate::bits::BitWriter,
+            ) -> ::core::result::Result<(), #krate::SerializationError> {
+                #encode
+            }
+
+            #[allow(unused_variables)]
+            fn decode(
+                reader: &mut #krate::bits::BitReader,
+            ) -> ::core::result::Result<Self, #krate::SerializationError> {
+                #decode
+            }
+        }
+    })
+}
+
+/// Path of the `lightyear_serde` crate, overridden with `#[bits(crate = "path")]`.
+fn crate_path(attrs: &[syn::Attribute]) -> Result<Path> {
+    let mut krate = parse_quote!(::lightyear_serde);
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("bits")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("crate") {
+                krate = meta.value()?.parse::<LitStr>()?.parse()?;
+                Ok(())
+            } else {
+                Err(meta.error("expected `crate = \"path\"`"))
+            }
+        })?;
+    }
+    Ok(krate)
+}
+
+fn parse_fields(fields: &Fields) -> Result<Vec<Field>> {
+    let fields: Vec<Field> = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            Ok(Field {
+                ident: field.ident.clone(),
+                binding: format_ident!("__field_{}", index),
+                ty: field.ty.clone(),
+                encoding: parse_encoding(field)?,
+            })
+        })
+        .collect::<Result<_>>()?;
+    // the baseline of a delta must be decoded before the field that uses it
+    for (index, field) in fields.iter().enumerate() {
+        if let Encoding::Delta { baseline } = &field.encoding
+            && let Some(position) = baseline_field(&fields, baseline)
+            && position >= index
+        {
+            return Err(Error::new(
+                baseline.span(),
+                "the baseline field must be declared before the delta-encoded field",
+            ));
+        }
+    }
+    Ok(fields)
+}
+
+fn parse_encoding(field: &syn::Field) -> Result<Encoding> {
+    let mut encoding = Encoding::Default;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("bits"))
+    {
+        attr.parse_nested_meta(|meta| {
+            let mut min = None;
+            let mut max = None;
+            let mut bits = None;
+            let mut baseline = None;
+            let kind = meta
+                .path
+                .get_ident()
+                .map(Ident::to_string)
+                .unwrap_or_default();
+            meta.parse_nested_meta(|inner| {
+                let value = if inner.path.is_ident("min") {
+                    &mut min
+                } else if inner.path.is_ident("max") {
+                    &mut max
+                } else if inner.path.is_ident("bits") {
+                    &mut bits
+                } else if inner.path.is_ident("baseline") {
+                    &mut baseline
+                } else {
+                    return Err(inner.error("unknown argument"));
+                };
+                *value = Some(inner.value()?.parse::<Expr>()?);
+                Ok(())
+            })?;
+            let missing = |name: &str| meta.error(format!("`{kind}` requires `{name} = ..`"));
+            encoding = match kind.as_str() {
+                "float" => Encoding::Float {
+                    min: min.ok_or_else(|| missing("min"))?,
+                    max: max.ok_or_else(|| missing("max"))?,
+                    bits: bits.ok_or_else(|| missing("bits"))?,
+                },
+                "quat" => Encoding::Quat {
+                    bits: bits.ok_or_else(|| missing("bits"))?,
+                },
+                "delta" => Encoding::Delta {
+                    baseline: baseline.ok_or_else(|| missing("baseline"))?,
+                },
+                "uint" => Encoding::Uint {
+                    bits: bits.ok_or_else(|| missing("bits"))?,
+                },
+                _ => {
+                    return Err(meta.error("expected one of `float`, `quat`, `delta` or `uint`"));
+                }
+            };
+            Ok(())
+        })?;
+    }
+    Ok(encoding)
+}
+
+/// Index of the field used as the baseline, if the baseline is the name of a field.
+fn baseline_field(fields: &[Field], baseline: &Expr) -> Option<usize> {
+    let Expr::Path(path) = baseline else {
+        return None;
+    };
+    let ident = path.path.get_ident()?;
+    fields
+        .iter()
+        .position(|field| field.ident.as_ref() == Some(ident))
+}
+
+/// Pattern that binds every field to its local variable, also used to build the value.
+fn pattern(path: &TokenStream2, fields: &Fields, parsed: &[Field]) -> TokenStream2 {
+    let bindings = parsed.iter().map(|field| &field.binding);
+    match fields {
+        Fields::Named(_) => {
+            let idents = parsed.iter().map(|field| &field.ident);
+            quote!(#path { #(#idents: #bindings),* })
+        }
+        Fields::Unnamed(_) => quote!(#path(#(#bindings),*)),
+        Fields::Unit => quote!(#path),
+    }
+}
+
+/// The bindings are references to the fields.
+fn encode_fields(krate: &Path, fields: &[Field]) -> TokenStream2 {
+    let statements = fields.iter().map(|field| {
+        let binding = &field.binding;
+        match &field.encoding {
+            Encoding::Default => quote! {
+                #krate::bits::BitSerialize::encode(#binding, writer)?;
+            },
+            Encoding::Float { min, max, bits } => quote! {
+                #krate::quantize::BoundedFloats::encode_bounded(
+                    #binding,
+                    &#krate::quantize::BoundedFloat::new(#min, #max, #bits),
+                    writer,
+                );
+            },
+            Encoding::Quat { bits } => quote! {
+                #krate::quantize::SmallestThree::new(#bits)
+                    .encode(#krate::quantize::Quaternion::to_xyzw(#binding), writer);
+            },
+            Encoding::Delta { baseline } => {
+                let baseline = match baseline_field(fields, baseline) {
+                    Some(position) => {
+                        let baseline = &fields[position].binding;
+                        quote!(*#baseline)
+                    }
+                    None => quote!(#baseline),
+                };
+                quote! {
+                    writer.write_delta(*#binding as i64, (#baseline) as i64);
+                }
+            }
+            Encoding::Uint { bits } => quote! {
+                // the writer only keeps the low bits, so a larger value would be corrupted
+                if (*#binding as u64)
+                    .checked_shr(#bits)
+                    .is_some_and(|high_bits| high_bits != 0)
+                {
+                    return ::core::result::Result::Err(#krate::SerializationError::InvalidValue);
+                }
+                writer.write_bits(*#binding as u64, #bits);
+            },
+        }
+    });
+    quote!(#(#statements)*)
+}
+
+/// The bindings are the decoded values.
+fn decode_fields(krate: &Path, fields: &[Field]) -> TokenStream2 {
+    let statements = fields.iter().map(|field| {
+        let binding = &field.binding;
+        let ty = &field.ty;
+        let value = match &field.encoding {
+            Encoding::Default => quote! {
+                <#ty as #krate::bits::BitSerialize>::decode(reader)?
+            },
+            Encoding::Float { min, max, bits } => quote! {
+                <#ty as #krate::quantize::BoundedFloats>::decode_bounded(
+                    &#krate::quantize::BoundedFloat::new(#min, #max, #bits),
+                    reader,
+                )?
+            },
+            Encoding::Quat { bits } => quote! {
+                <#ty as #krate::quantize::Quaternion>::from_xyzw(
+                    #krate::quantize::SmallestThree::new(#bits).decode(reader)?,
+                )
+            },
+            Encoding::Delta { baseline } => {
+                let baseline = match baseline_field(fields, baseline) {
+                    Some(position) => {
+                        let baseline = &fields[position].binding;
+                        quote!(#baseline)
+                    }
+                    None => quote!(#baseline),
+                };
+                quote! {
+                    reader.read_delta((#baseline) as i64)? as #ty
+                }
+            }
+            Encoding::Uint { bits } => quote! {
+                reader.read_bits(#bits)? as #ty
+            },
+        };
+        quote! {
+            let #binding: #ty = #value;
+        }
+    });
+    quote!(#(#statements)*)
+}
@@ -281,6 +281,23 @@ impl Transport {
         self.packet_manager.nack_settings()
     }
 
+    /// Current send rate in bytes per second chosen by the congestion controller.
+    ///
+    /// Returns `None` if [`PriorityConfig::congestion_control`] is not enabled.
+    pub fn congestion_rate(&self) -> Option<u32> {
+        self.bandwidth_limiter.congestion_rate()
+    }
+
+    /// Number of bytes per second that this transport can currently send, or `None` if its
+    /// bandwidth is not limited.
+    ///
+    /// This is the congestion controller's rate if it is enabled, and otherwise the bandwidth
+    /// quota of the [`PriorityManager`].
+    pub fn bandwidth(&self) -> Option<u32> {
+        self.congestion_rate()
+            .or_else(|| self.priority_manager.bandwidth_quota())
+    }
+
     /// Number of packet payload buffers allocated because none was ready in the local pool.
     ///
     /// This is test-only instrumentation for exercising the real Transport -> Link -> IO path.
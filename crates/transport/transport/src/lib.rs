@@ -28,6 +28,8 @@ pub mod prelude {
     pub use crate::channel::registry::ChannelRegistry;
     pub use crate::channel::send::ChannelSend;
     pub use crate::packet::compression::{CompressionAlgorithm, CompressionConfig};
+    pub use crate::packet::congestion::CongestionControlConfig;
+    pub use crate::packet::mtu_discovery::{MtuDiscovery, MtuDiscoveryConfig};
     pub use crate::packet::nack::PacketNackSettings;
     pub use crate::packet::priority_manager::{PriorityConfig, PriorityManager};
 }
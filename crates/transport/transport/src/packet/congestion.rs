@@ -0,0 +1,389 @@
+//! Adaptive send-rate control for a single [`Transport`](crate::prelude::Transport).
+//!
+//! The fixed [`PriorityConfig::bandwidth_quota`](crate::prelude::PriorityConfig::bandwidth_quota)
+//! forces applications to pick one byte rate for every peer. When a [`CongestionControlConfig`] is
+//! provided, the transport's bandwidth limiter instead uses a token bucket whose
+//! refill rate is adjusted with an AIMD (additive-increase, multiplicative-decrease) policy:
+//!
+//! - packet losses reported by the packet NACK logic, and a smoothed RTT that rises well above
+//!   the minimum RTT sample of the recent past, are treated as congestion signals and shrink the
+//!   rate;
+//! - control intervals without congestion in which the sender actually used most of its budget
+//!   grow the rate linearly.
+//!
+//! Since the bandwidth limiter admits packets in priority order, lower-priority channels are the
+//! first to be held back when the rate shrinks.
+use core::time::Duration;
+#[allow(unused_imports)]
+use tracing::{debug, trace};
+
+/// Configuration of the adaptive congestion controller.
+///
+/// See the [module-level documentation](self) for the control policy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CongestionControlConfig {
+    /// Send rate in bytes per second used before any congestion signal is received.
+    pub initial_bytes_per_second: u32,
+    /// The send rate never drops below this value, so that critical traffic can always flow.
+    pub min_bytes_per_second: u32,
+    /// The send rate never grows above this value.
+    pub max_bytes_per_second: u32,
+    /// Bytes per second added to the send rate after each control interval without congestion.
+    pub additive_increase: u32,
+    /// Factor applied to the send rate when congestion is detected. Must be in `(0.0, 1.0]`.
+    pub multiplicative_decrease: f32,
+    /// Fraction of packets lost during a control interval above which the link is considered congested.
+    pub loss_threshold: f32,
+    /// The link is considered congested when the smoothed RTT exceeds the minimum RTT sample
+    /// multiplied by this factor.
+    ///
+    /// Set to `f32::INFINITY` to only react to packet loss.
+    pub rtt_inflation_threshold: f32,
+    /// Duration after which an RTT sample stops counting towards the minimum RTT.
+    ///
+    /// The minimum RTT is taken over the last one or two windows, so that the baseline follows a
+    /// permanent change of the base RTT of the path, for example after a route change.
+    pub min_rtt_window: Duration,
+    /// Minimum duration of a control interval.
+    ///
+    /// Each interval lasts at least one RTT so that a loss is only reacted to once per round trip.
+    pub min_interval: Duration,
+    /// Amount of send-rate time that can be accumulated as burst capacity.
+    pub burst_duration: Duration,
+}
+
+impl Default for CongestionControlConfig {
+    fn default() -> Self {
+        Self {
+            // same as the default fixed quota
+            initial_bytes_per_second: 56_000,
+            min_bytes_per_second: 8_000,
+            max_bytes_per_second: 1_000_000,
+            additive_increase: 4_000,
+            multiplicative_decrease: 0.7,
+            loss_threshold: 0.02,
+            rtt_inflation_threshold: 2.0,
+            min_rtt_window: Duration::from_secs(10),
+            min_interval: Duration::from_millis(50),
+            burst_duration: Duration::from_millis(100),
+        }
+    }
+}
+
+impl CongestionControlConfig {
+    /// Sets the send rate used before any congestion signal is received.
+    pub fn with_initial_rate(mut self, bytes_per_second: u32) -> Self {
+        self.initial_bytes_per_second = bytes_per_second;
+        self
+    }
+
+    /// Sets the range in which the send rate is allowed to vary.
+    pub fn with_rate_bounds(
+        mut self,
+        min_bytes_per_second: u32,
+        max_bytes_per_second: u32,
+    ) -> Self {
+        self.min_bytes_per_second = min_bytes_per_second;
+        self.max_bytes_per_second = max_bytes_per_second;
+        self
+    }
+
+    fn clamp_rate(&self, rate: f64) -> f64 {
+        rate.clamp(
+            self.min_bytes_per_second as f64,
+            self.max_bytes_per_second.max(self.min_bytes_per_second) as f64,
+        )
+    }
+}
+
+/// Token bucket whose refill rate follows the AIMD policy of [`CongestionControlConfig`].
+#[derive(Debug)]
+pub(crate) struct CongestionController {
+    config: CongestionControlConfig,
+    /// Current send rate in bytes per second.
+    rate: f64,
+    /// Available bytes in the token bucket.
+    tokens: f64,
+    /// Time at which the bucket was last refilled. `None` until the first refill.
+    last_refill: Option<Duration>,
+    /// Start of the current control interval.
+    interval_start: Option<Duration>,
+    /// Smallest RTT sample of the current min-RTT window.
+    min_rtt: Option<Duration>,
+    /// Smallest RTT sample of the previous min-RTT window.
+    previous_min_rtt: Option<Duration>,
+    /// Start of the current min-RTT window.
+    min_rtt_window_start: Option<Duration>,
+    acked_in_interval: u32,
+    lost_in_interval: u32,
+    bytes_in_interval: u64,
+}
+
+impl CongestionController {
+    pub(crate) fn new(config: CongestionControlConfig) -> Self {
+        let rate = config.clamp_rate(config.initial_bytes_per_second as f64);
+        Self {
+            config,
+            rate,
+            tokens: rate * config.burst_duration.as_secs_f64(),
+            last_refill: None,
+            interval_start: None,
+            min_rtt: None,
+            previous_min_rtt: None,
+            min_rtt_window_start: None,
+            acked_in_interval: 0,
+            lost_in_interval: 0,
+            bytes_in_interval: 0,
+        }
+    }
+
+    /// Current send rate in bytes per second.
+    pub(crate) fn rate(&self) -> u32 {
+        self.rate as u32
+    }
+
+    fn burst_capacity(&self) -> f64 {
+        self.rate * self.config.burst_duration.as_secs_f64()
+    }
+
+    /// Add the tokens accumulated since the last refill.
+    pub(crate) fn refill(&mut self, now: Duration) {
+        if let Some(last_refill) = self.last_refill {
+            let elapsed = now.saturating_sub(last_refill).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * self.rate).min(self.burst_capacity().max(1.0));
+        }
+        self.last_refill = Some(now);
+    }
+
+    /// Try to consume `bytes` from the bucket.
+    pub(crate) fn try_consume(&mut self, bytes: usize) -> bool {
+        let bytes_f = bytes as f64;
+        // allow a single oversized packet to go through once the bucket is full, otherwise
+        // packets bigger than the burst capacity could never be sent.
+        let capacity = self.burst_capacity();
+        if self.tokens < bytes_f && !(bytes_f > capacity && self.tokens >= capacity) {
+            return false;
+        }
+        self.tokens -= bytes_f;
+        self.bytes_in_interval += bytes as u64;
+        true
+    }
+
+    pub(crate) fn on_packet_acked(&mut self, rtt_sample: Duration) {
+        self.acked_in_interval += 1;
+        if !rtt_sample.is_zero() {
+            self.min_rtt = Some(self.min_rtt.map_or(rtt_sample, |min| min.min(rtt_sample)));
+        }
+    }
+
+    pub(crate) fn on_packet_lost(&mut self) {
+        self.lost_in_interval += 1;
+    }
+
+    /// Smallest RTT sample of the last one or two min-RTT windows, used as the uncongested
+    /// baseline.
+    fn windowed_min_rtt(&self) -> Option<Duration> {
+        match (self.min_rtt, self.previous_min_rtt) {
+            (Some(current), Some(previous)) => Some(current.min(previous)),
+            (current, previous) => current.or(previous),
+        }
+    }
+
+    /// Start a new min-RTT window once the current one is over, forgetting the samples of the
+    /// previous window.
+    fn rotate_min_rtt_window(&mut self, now: Duration) {
+        let window_start = *self.min_rtt_window_start.get_or_insert(now);
+        if now.saturating_sub(window_start) >= self.config.min_rtt_window {
+            self.previous_min_rtt = self.min_rtt.take();
+            self.min_rtt_window_start = Some(now);
+        }
+    }
+
+    /// Re-evaluate the send rate once the current control interval is over.
+    pub(crate) fn update(&mut self, now: Duration, smoothed_rtt: Duration) {
+        self.rotate_min_rtt_window(now);
+        let Some(interval_start) = self.interval_start else {
+            self.interval_start = Some(now);
+            return;
+        };
+        let interval = self.config.min_interval.max(smoothed_rtt);
+        let elapsed = now.saturating_sub(interval_start);
+        if elapsed < interval {
+            return;
+        }
+
+        let total = self.acked_in_interval + self.lost_in_interval;
+        let loss_ratio = if total == 0 {
+            0.0
+        } else {
+            self.lost_in_interval as f32 / total as f32
+        };
+        let rtt_inflated = self.windowed_min_rtt().is_some_and(|min_rtt| {
+            !smoothed_rtt.is_zero()
+                && smoothed_rtt.as_secs_f32()
+                    > min_rtt.as_secs_f32() * self.config.rtt_inflation_threshold
+        });
+        let previous_rate = self.rate;
+        if loss_ratio > self.config.loss_threshold || rtt_inflated {
+            self.rate = self
+                .config
+                .clamp_rate(self.rate * self.config.multiplicative_decrease as f64);
+            self.tokens = self.tokens.min(self.burst_capacity());
+            debug!(
+                loss_ratio,
+                rtt_inflated,
+                rate = self.rate,
+                "congestion detected, decreasing send rate"
+            );
+        } else if self.acked_in_interval > 0
+            // only probe for more bandwidth if the budget is actually being used, otherwise an
+            // idle link would grow its rate without ever checking that the path can carry it
+            && self.bytes_in_interval as f64 >= 0.5 * previous_rate * elapsed.as_secs_f64()
+        {
+            self.rate = self
+                .config
+                .clamp_rate(self.rate + self.config.additive_increase as f64);
+            trace!(rate = self.rate, "no congestion, increasing send rate");
+        }
+        self.interval_start = Some(now);
+        self.acked_in_interval = 0;
+        self.lost_in_interval = 0;
+        self.bytes_in_interval = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> CongestionControlConfig {
+        CongestionControlConfig {
+            initial_bytes_per_second: 10_000,
+            min_bytes_per_second: 1_000,
+            max_bytes_per_second: 20_000,
+            additive_increase: 1_000,
+            multiplicative_decrease: 0.5,
+            loss_threshold: 0.1,
+            rtt_inflation_threshold: 2.0,
+            min_rtt_window: Duration::from_secs(1),
+            min_interval: Duration::from_millis(100),
+            burst_duration: Duration::from_millis(100),
+        }
+    }
+
+    /// Run one control interval in which `sent` bytes are sent and the given packets are acked or lost.
+    fn run_interval(
+        controller: &mut CongestionController,
+        now: &mut Duration,
+        sent: usize,
+        acked: u32,
+        lost: u32,
+        rtt_ms: u64,
+    ) {
+        run_interval_with_samples(controller, now, sent, acked, lost, 20, rtt_ms);
+    }
+
+    /// Same as [`run_interval`], with the RTT samples of the acked packets.
+    fn run_interval_with_samples(
+        controller: &mut CongestionController,
+        now: &mut Duration,
+        sent: usize,
+        acked: u32,
+        lost: u32,
+        sample_ms: u64,
+        rtt_ms: u64,
+    ) {
+        controller.refill(*now);
+        assert!(controller.try_consume(sent));
+        (0..acked).for_each(|_| controller.on_packet_acked(Duration::from_millis(sample_ms)));
+        (0..lost).for_each(|_| controller.on_packet_lost());
+        *now += Duration::from_millis(100);
+        controller.update(*now, Duration::from_millis(rtt_ms));
+    }
+
+    #[test]
+    fn loss_decreases_rate_multiplicatively() {
+        let mut controller = CongestionController::new(config());
+        let mut now = Duration::ZERO;
+        controller.update(now, Duration::from_millis(20));
+
+        run_interval(&mut controller, &mut now, 900, 5, 5, 20);
+        assert_eq!(controller.rate(), 5_000);
+
+        // the rate never drops below the configured minimum
+        for _ in 0..10 {
+            run_interval(&mut controller, &mut now, 100, 1, 1, 20);
+        }
+        assert_eq!(controller.rate(), 1_000);
+    }
+
+    #[test]
+    fn used_budget_without_congestion_increases_rate_additively() {
+        let mut controller = CongestionController::new(config());
+        let mut now = Duration::ZERO;
+        controller.update(now, Duration::from_millis(20));
+
+        run_interval(&mut controller, &mut now, 900, 10, 0, 20);
+        assert_eq!(controller.rate(), 11_000);
+    }
+
+    #[test]
+    fn idle_link_does_not_increase_rate() {
+        let mut controller = CongestionController::new(config());
+        let mut now = Duration::ZERO;
+        controller.update(now, Duration::from_millis(20));
+
+        run_interval(&mut controller, &mut now, 10, 1, 0, 20);
+        assert_eq!(controller.rate(), 10_000);
+    }
+
+    #[test]
+    fn rtt_inflation_decreases_rate() {
+        let mut controller = CongestionController::new(config());
+        let mut now = Duration::ZERO;
+        controller.update(now, Duration::from_millis(20));
+
+        // min RTT sample is 20ms, smoothed RTT is 60ms
+        run_interval(&mut controller, &mut now, 900, 10, 0, 60);
+        assert_eq!(controller.rate(), 5_000);
+    }
+
+    #[test]
+    fn min_rtt_follows_a_permanent_rtt_increase() {
+        let mut controller = CongestionController::new(config());
+        let mut now = Duration::ZERO;
+        controller.update(now, Duration::from_millis(20));
+        run_interval_with_samples(&mut controller, &mut now, 900, 10, 0, 20, 20);
+        assert_eq!(controller.rate(), 11_000);
+
+        // the route changes and the base RTT goes from 20ms to 80ms: the inflated RTT is first
+        // treated as congestion
+        run_interval_with_samples(&mut controller, &mut now, 500, 10, 0, 80, 80);
+        assert_eq!(controller.rate(), 5_500);
+
+        // once the 20ms samples leave the min-RTT window, 80ms becomes the baseline and the rate
+        // grows again
+        let mut rate = controller.rate();
+        for _ in 0..30 {
+            // use 75% of the budget of the interval
+            let sent = (controller.rate() * 3 / 40) as usize;
+            run_interval_with_samples(&mut controller, &mut now, sent, 10, 0, 80, 80);
+            rate = rate.min(controller.rate());
+        }
+        assert_eq!(rate, 1_000);
+        assert!(controller.rate() > 1_000 + 10 * 1_000);
+    }
+
+    #[test]
+    fn token_bucket_refills_at_current_rate() {
+        let mut controller = CongestionController::new(config());
+        controller.refill(Duration::ZERO);
+        // burst capacity is 100ms worth of 10KB/s
+        assert!(controller.try_consume(1_000));
+        assert!(!controller.try_consume(1));
+
+        controller.refill(Duration::from_millis(50));
+        assert!(controller.try_consume(500));
+        assert!(!controller.try_consume(1));
+    }
+}
@@ -21,7 +21,9 @@ Packets that are over the maximum packet size will be fragmented into multiple `
 pub(crate) mod header;
 
 pub mod compression;
+pub mod congestion;
 pub mod message;
+pub mod mtu_discovery;
 pub mod nack;
 
 // "module has the same name as its containing module" style nit.
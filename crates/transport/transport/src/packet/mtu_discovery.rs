@@ -0,0 +1,336 @@
+//! Path MTU discovery for a single [`Transport`](crate::prelude::Transport).
+//!
+//! A [`Link`](lightyear_link::Link) starts with a current MTU equal to its stable minimum MTU,
+//! which is small enough to cross most network paths. Many paths can carry larger datagrams, and
+//! a larger MTU lets the packet builder pack more messages in each packet.
+//!
+//! When an [`MtuDiscovery`] component is added next to the `Transport`, the transport periodically
+//! sends `MtuProbe` packets: header-only packets padded to a candidate size. Probes are
+//! acknowledged like any other packet, so:
+//!
+//! - an acknowledged probe proves that the path carries datagrams of that size, and the link's
+//!   current MTU is raised with [`Link::set_mtu`](lightyear_link::Link::set_mtu);
+//! - a probe lost [`MtuDiscoveryConfig::max_probes`] times in a row marks its size as too large.
+//!
+//! Candidate sizes are picked by binary search between the largest confirmed size and the smallest
+//! rejected size, until the two are within [`MtuDiscoveryConfig::search_threshold`] bytes. The
+//! search is restarted after [`MtuDiscoveryConfig::research_interval`] in case the path changed.
+//!
+//! If [`MtuDiscoveryConfig::black_hole_threshold`] consecutive packets are lost while the current
+//! MTU is above the minimum MTU, the path is assumed to have shrunk: the MTU falls back to the
+//! link's minimum MTU and the search starts again below the previous value.
+//!
+//! Fragment sizes are derived from the stable minimum MTU, so changing the current MTU never
+//! invalidates fragments that are already in flight.
+//!
+//! Probes are never larger than the link's [`max_mtu`](lightyear_link::Link::max_mtu): connection
+//! layers which wrap the transport packets, like netcode with its header and MAC, limit it to the
+//! largest payload they can send. With netcode, the search therefore stays below
+//! `lightyear_netcode::MAX_PACKET_SIZE`. [`MtuDiscoveryConfig::max_mtu`] should not be raised
+//! above the MTU of the IO layer.
+use crate::packet::packet::PacketId;
+use bevy_ecs::component::Component;
+use core::time::Duration;
+#[allow(unused_imports)]
+use tracing::{debug, trace};
+
+/// Largest UDP payload that fits in a 1500-byte Ethernet frame with an IPv4 header.
+///
+/// This is only reachable when the transport packets are sent as-is in UDP datagrams. Connection
+/// layers which add their own header lower the limit through the link's
+/// [`max_mtu`](lightyear_link::Link::max_mtu).
+pub const DEFAULT_MAX_MTU: usize = 1472;
+
+/// Configuration of the path MTU discovery.
+///
+/// See the [module-level documentation](self) for the search policy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MtuDiscoveryConfig {
+    /// Largest MTU that will be probed, if the link's
+    /// [`max_mtu`](lightyear_link::Link::max_mtu) is not smaller.
+    pub max_mtu: usize,
+    /// The search stops when the largest confirmed size and the smallest rejected size are
+    /// within this many bytes.
+    pub search_threshold: usize,
+    /// Minimum duration between two probes.
+    pub probe_interval: Duration,
+    /// Number of consecutive lost probes after which a size is considered too large.
+    pub max_probes: u8,
+    /// Duration after which a completed search is started again.
+    pub research_interval: Duration,
+    /// Number of consecutive lost packets after which the MTU falls back to the minimum MTU.
+    pub black_hole_threshold: u32,
+}
+
+impl Default for MtuDiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            max_mtu: DEFAULT_MAX_MTU,
+            search_threshold: 16,
+            probe_interval: Duration::from_millis(500),
+            max_probes: 3,
+            research_interval: Duration::from_secs(600),
+            black_hole_threshold: 8,
+        }
+    }
+}
+
+impl MtuDiscoveryConfig {
+    /// Sets the largest MTU that will be probed.
+    pub fn with_max_mtu(mut self, max_mtu: usize) -> Self {
+        self.max_mtu = max_mtu;
+        self
+    }
+}
+
+/// Probe that has been sent but not acknowledged or lost yet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct InFlightProbe {
+    packet_id: PacketId,
+    size: usize,
+}
+
+/// Component enabling path MTU discovery on a [`Transport`](crate::prelude::Transport) entity.
+///
+/// See the [module-level documentation](self).
+#[derive(Component, Debug)]
+pub struct MtuDiscovery {
+    config: MtuDiscoveryConfig,
+    /// Largest size confirmed by an acknowledged probe. `None` until the search starts.
+    confirmed: Option<usize>,
+    /// Smallest size that is known to be too large.
+    rejected: usize,
+    in_flight: Option<InFlightProbe>,
+    /// Number of consecutive losses of the probe size currently being searched.
+    probe_failures: u8,
+    /// Number of consecutive lost packets, used for black hole detection.
+    consecutive_losses: u32,
+    next_probe: Duration,
+    search_complete_at: Option<Duration>,
+}
+
+impl Default for MtuDiscovery {
+    fn default() -> Self {
+        Self::new(MtuDiscoveryConfig::default())
+    }
+}
+
+impl MtuDiscovery {
+    pub fn new(config: MtuDiscoveryConfig) -> Self {
+        Self {
+            config,
+            confirmed: None,
+            rejected: config.max_mtu + 1,
+            in_flight: None,
+            probe_failures: 0,
+            consecutive_losses: 0,
+            next_probe: Duration::ZERO,
+            search_complete_at: None,
+        }
+    }
+
+    pub fn config(&self) -> &MtuDiscoveryConfig {
+        &self.config
+    }
+
+    /// Returns true if the search has converged and no probes are sent until the next search.
+    pub fn is_complete(&self) -> bool {
+        self.search_complete_at.is_some()
+    }
+
+    /// Restarts the search from the link's current MTU, for example after a disconnection.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.config);
+    }
+
+    /// Returns the size of the probe to send now, if any.
+    ///
+    /// `max_mtu` is the largest payload accepted by the link; larger sizes are never probed.
+    pub(crate) fn next_probe_size(
+        &mut self,
+        now: Duration,
+        current_mtu: usize,
+        max_mtu: usize,
+    ) -> Option<usize> {
+        if self.in_flight.is_some() || now < self.next_probe {
+            return None;
+        }
+        let confirmed = *self.confirmed.get_or_insert(current_mtu);
+        if let Some(complete_at) = self.search_complete_at {
+            if now.saturating_sub(complete_at) < self.config.research_interval {
+                return None;
+            }
+            debug!(confirmed, "restarting path MTU search");
+            self.search_complete_at = None;
+            self.rejected = self.config.max_mtu + 1;
+        }
+        self.rejected = self.rejected.min(max_mtu.saturating_add(1));
+        if self.rejected <= confirmed
+            || self.rejected - confirmed <= self.config.search_threshold.max(1)
+        {
+            debug!(mtu = confirmed, "path MTU search complete");
+            self.search_complete_at = Some(now);
+            return None;
+        }
+        Some(confirmed + (self.rejected - confirmed) / 2)
+    }
+
+    /// Records that a probe of `size` bytes was sent in the packet `packet_id`.
+    pub(crate) fn on_probe_sent(&mut self, packet_id: PacketId, size: usize, now: Duration) {
+        trace!(?packet_id, size, "sent MTU probe");
+        self.in_flight = Some(InFlightProbe { packet_id, size });
+        self.next_probe = now + self.config.probe_interval;
+    }
+
+    /// Returns true if `packet_id` is the probe currently in flight.
+    pub(crate) fn is_probe(&self, packet_id: PacketId) -> bool {
+        self.in_flight
+            .is_some_and(|probe| probe.packet_id == packet_id)
+    }
+
+    /// Handles an acknowledged packet, and returns the new MTU if a probe was acknowledged.
+    pub(crate) fn on_packet_acked(&mut self, packet_id: PacketId) -> Option<usize> {
+        self.consecutive_losses = 0;
+        let probe = self
+            .in_flight
+            .filter(|probe| probe.packet_id == packet_id)?;
+        self.in_flight = None;
+        self.probe_failures = 0;
+        self.confirmed = Some(probe.size);
+        debug!(mtu = probe.size, "MTU probe acknowledged");
+        Some(probe.size)
+    }
+
+    /// Handles a lost packet, and returns the new MTU if a black hole was detected.
+    pub(crate) fn on_packet_lost(&mut self, packet_id: PacketId, min_mtu: usize) -> Option<usize> {
+        if let Some(probe) = self.in_flight.filter(|probe| probe.packet_id == packet_id) {
+            self.in_flight = None;
+            self.probe_failures += 1;
+            if self.probe_failures >= self.config.max_probes {
+                debug!(size = probe.size, "MTU probe size rejected");
+                self.probe_failures = 0;
+                self.rejected = probe.size;
+            }
+            return None;
+        }
+        self.consecutive_losses += 1;
+        let confirmed = self.confirmed?;
+        if self.consecutive_losses < self.config.black_hole_threshold || confirmed <= min_mtu {
+            return None;
+        }
+        debug!(
+            previous_mtu = confirmed,
+            min_mtu, "path MTU black hole detected, falling back to the minimum MTU"
+        );
+        self.consecutive_losses = 0;
+        self.probe_failures = 0;
+        self.confirmed = Some(min_mtu);
+        self.rejected = confirmed;
+        self.search_complete_at = None;
+        Some(min_mtu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> MtuDiscoveryConfig {
+        MtuDiscoveryConfig {
+            max_mtu: 1472,
+            search_threshold: 16,
+            probe_interval: Duration::ZERO,
+            max_probes: 1,
+            research_interval: Duration::from_secs(10),
+            black_hole_threshold: 3,
+        }
+    }
+
+    /// Runs the search against a path which carries datagrams up to `path_mtu` bytes.
+    fn search(discovery: &mut MtuDiscovery, path_mtu: usize, mut mtu: usize) -> usize {
+        let mut packet_id = PacketId(0);
+        while let Some(size) = discovery.next_probe_size(Duration::ZERO, mtu, usize::MAX) {
+            discovery.on_probe_sent(packet_id, size, Duration::ZERO);
+            if size <= path_mtu {
+                mtu = discovery.on_packet_acked(packet_id).unwrap();
+            } else {
+                assert_eq!(discovery.on_packet_lost(packet_id, 1200), None);
+            }
+            packet_id += 1;
+        }
+        mtu
+    }
+
+    #[test]
+    fn search_converges_below_path_mtu() {
+        let mut discovery = MtuDiscovery::new(config());
+        let mtu = search(&mut discovery, 1400, 1200);
+        assert!(discovery.is_complete());
+        assert!(mtu <= 1400);
+        assert!(1400 - mtu <= 16);
+    }
+
+    #[test]
+    fn only_one_probe_in_flight() {
+        let mut discovery = MtuDiscovery::new(config());
+        let size = discovery
+            .next_probe_size(Duration::ZERO, 1200, usize::MAX)
+            .unwrap();
+        assert_eq!(size, 1336);
+        discovery.on_probe_sent(PacketId(0), size, Duration::ZERO);
+        assert!(discovery.is_probe(PacketId(0)));
+        assert_eq!(
+            discovery.next_probe_size(Duration::ZERO, 1200, usize::MAX),
+            None
+        );
+
+        // unrelated acks don't complete the probe
+        assert_eq!(discovery.on_packet_acked(PacketId(1)), None);
+        assert_eq!(discovery.on_packet_acked(PacketId(0)), Some(1336));
+    }
+
+    #[test]
+    fn black_hole_falls_back_to_min_mtu() {
+        let mut discovery = MtuDiscovery::new(config());
+        let mtu = search(&mut discovery, 1472, 1200);
+        assert!(mtu > 1200);
+
+        assert_eq!(discovery.on_packet_lost(PacketId(100), 1200), None);
+        assert_eq!(discovery.on_packet_lost(PacketId(101), 1200), None);
+        assert_eq!(discovery.on_packet_lost(PacketId(102), 1200), Some(1200));
+
+        // the search resumes below the size that stopped working
+        let new_mtu = search(&mut discovery, 1300, 1200);
+        assert!(new_mtu > 1200 && new_mtu <= 1300);
+    }
+
+    #[test]
+    fn search_restarts_after_research_interval() {
+        let mut discovery = MtuDiscovery::new(config());
+        let mtu = search(&mut discovery, 1300, 1200);
+        assert_eq!(
+            discovery.next_probe_size(Duration::from_secs(5), mtu, usize::MAX),
+            None
+        );
+        assert!(
+            discovery
+                .next_probe_size(Duration::from_secs(11), mtu, usize::MAX)
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn probes_stay_below_link_max_mtu() {
+        let mut discovery = MtuDiscovery::new(config());
+        let mut packet_id = PacketId(0);
+        let mut mtu = 1000;
+        while let Some(size) = discovery.next_probe_size(Duration::ZERO, mtu, 1200) {
+            assert!(size <= 1200);
+            discovery.on_probe_sent(packet_id, size, Duration::ZERO);
+            mtu = discovery.on_packet_acked(packet_id).unwrap();
+            packet_id += 1;
+        }
+        assert!(discovery.is_complete());
+        assert!(mtu > 1200 - 16);
+    }
+}
@@ -242,6 +242,25 @@ impl PacketBuilder {
         Ok(self.new_staged_packet(PacketType::AckOnly, current_tick)?)
     }
 
+    /// Build a header-only packet padded with zeroes to exactly `size` bytes, used to probe the
+    /// path MTU.
+    pub(crate) fn build_mtu_probe_packet(
+        &mut self,
+        current_tick: Tick,
+        size: usize,
+    ) -> Result<Packet, PacketError> {
+        if size < HEADER_BYTES {
+            return Err(PacketError::PacketTooLarge {
+                actual: HEADER_BYTES,
+                mtu: size,
+            });
+        }
+        self.buffer_pool.set_payload_capacity(size);
+        let mut packet = self.new_staged_packet(PacketType::MtuProbe, current_tick)?;
+        packet.payload.resize(size, 0);
+        Ok(packet)
+    }
+
     /// Stage the next packet from globally ordered, channel-owned candidates.
     ///
     /// This method only consumes candidate snapshots. It deliberately does not mutate channel
@@ -36,6 +36,12 @@ pub enum PacketType {
     /// `AckOnly` packets are not themselves acknowledgement-eliciting, preventing idle peers from
     /// exchanging an endless stream of acknowledgements.
     AckOnly = 4,
+    /// A header-only packet padded with zeroes, used by path MTU discovery.
+    ///
+    /// The padding is ignored by the receiver. The packet is acknowledgement-eliciting: its
+    /// acknowledgement proves that the path carries datagrams of that size.
+    /// See [`MtuDiscovery`](crate::packet::mtu_discovery::MtuDiscovery).
+    MtuProbe = 5,
 }
 
 impl From<PacketType> for u8 {
@@ -54,6 +60,7 @@ impl TryFrom<u8> for PacketType {
             2 => Ok(PacketType::DataCompressed),
             3 => Ok(PacketType::DataFragmentCompressed),
             4 => Ok(PacketType::AckOnly),
+            5 => Ok(PacketType::MtuProbe),
             _ => Err(lightyear_serde::SerializationError::InvalidPacketType),
         }
     }
@@ -73,7 +80,8 @@ impl PacketType {
             PacketType::DataFragment => Some(PacketType::DataFragmentCompressed),
             PacketType::DataCompressed
             | PacketType::DataFragmentCompressed
-            | PacketType::AckOnly => None,
+            | PacketType::AckOnly
+            | PacketType::MtuProbe => None,
         }
     }
 
@@ -84,6 +92,7 @@ impl PacketType {
                 PacketType::DataFragment
             }
             PacketType::AckOnly => PacketType::AckOnly,
+            PacketType::MtuProbe => PacketType::MtuProbe,
         }
     }
 
@@ -104,6 +113,7 @@ mod tests {
             PacketType::DataCompressed,
             PacketType::DataFragmentCompressed,
             PacketType::AckOnly,
+            PacketType::MtuProbe,
         ] {
             let raw: u8 = packet_type.into();
             assert_eq!(PacketType::try_from(raw).unwrap(), packet_type);
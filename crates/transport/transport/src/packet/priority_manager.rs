@@ -1,7 +1,9 @@
 use crate::channel::registry::ChannelRegistry;
+use crate::packet::congestion::{CongestionControlConfig, CongestionController};
 use crate::packet::message::{MessageData, SendCandidate};
 use alloc::vec::Vec;
 use core::num::NonZeroU32;
+use core::time::Duration;
 use governor::{DefaultDirectRateLimiter, Quota};
 use lightyear_serde::ToBytes;
 use nonzero_ext::*;
@@ -16,6 +18,11 @@ pub struct PriorityConfig {
     pub bandwidth_quota: Quota,
     /// If false, there is no bandwidth cap and all messages are sent as soon as possible
     pub enabled: bool,
+    /// If set, the send rate adapts to the link's loss and RTT signals instead of using the fixed
+    /// [`bandwidth_quota`](Self::bandwidth_quota).
+    ///
+    /// See [`CongestionControlConfig`] for more information.
+    pub congestion_control: Option<CongestionControlConfig>,
 }
 
 // this is mostly for testing
@@ -25,6 +32,7 @@ impl Default for PriorityConfig {
             // 56 KB/s bandwidth cap
             bandwidth_quota: Quota::per_second(nonzero!(56000u32)),
             enabled: false,
+            congestion_control: None,
         }
     }
 }
@@ -40,6 +48,19 @@ impl PriorityConfig {
         Self {
             bandwidth_quota: Quota::per_second(cap).allow_burst(cap),
             enabled: true,
+            congestion_control: None,
+        }
+    }
+
+    /// Enables bandwidth limiting with a send rate that adapts to the link conditions.
+    ///
+    /// The fixed [`bandwidth_quota`](Self::bandwidth_quota) is ignored while congestion control
+    /// is enabled.
+    pub fn adaptive(config: CongestionControlConfig) -> Self {
+        Self {
+            enabled: true,
+            congestion_control: Some(config),
+            ..Default::default()
         }
     }
 
@@ -67,6 +88,8 @@ pub struct PriorityManager {
 pub(crate) struct BandwidthLimiter {
     enabled: bool,
     limiter: DefaultDirectRateLimiter,
+    /// Adaptive rate control; replaces `limiter` when present.
+    congestion: Option<CongestionController>,
 }
 
 impl Default for PriorityManager {
@@ -83,6 +106,17 @@ impl PriorityManager {
         }
     }
 
+    /// Sustained number of bytes per second allowed by the configured
+    /// [`bandwidth_quota`](PriorityConfig::bandwidth_quota), or `None` if the bandwidth is not
+    /// limited.
+    pub fn bandwidth_quota(&self) -> Option<u32> {
+        if !self.config.enabled {
+            return None;
+        }
+        let interval = self.config.bandwidth_quota.replenish_interval().as_nanos();
+        Some((Duration::from_secs(1).as_nanos() / interval.max(1)).min(u32::MAX as u128) as u32)
+    }
+
     pub(crate) fn candidates_mut(&mut self) -> &mut Vec<SendCandidate> {
         &mut self.candidates
     }
@@ -163,6 +197,38 @@ impl BandwidthLimiter {
         Self {
             enabled: config.enabled,
             limiter: DefaultDirectRateLimiter::direct(config.bandwidth_quota),
+            congestion: config.congestion_control.map(CongestionController::new),
+        }
+    }
+
+    /// Current adaptive send rate in bytes per second, if congestion control is enabled.
+    pub(crate) fn congestion_rate(&self) -> Option<u32> {
+        self.congestion.as_ref().map(CongestionController::rate)
+    }
+
+    /// Refill the adaptive budget before the packets of this frame are admitted.
+    pub(crate) fn begin_send(&mut self, now: Duration) {
+        if let Some(congestion) = &mut self.congestion {
+            congestion.refill(now);
+        }
+    }
+
+    pub(crate) fn on_packet_acked(&mut self, rtt_sample: Duration) {
+        if let Some(congestion) = &mut self.congestion {
+            congestion.on_packet_acked(rtt_sample);
+        }
+    }
+
+    pub(crate) fn on_packet_lost(&mut self) {
+        if let Some(congestion) = &mut self.congestion {
+            congestion.on_packet_lost();
+        }
+    }
+
+    /// Update the adaptive send rate from the congestion signals received this frame.
+    pub(crate) fn update(&mut self, now: Duration, smoothed_rtt: Duration) {
+        if let Some(congestion) = &mut self.congestion {
+            congestion.update(now, smoothed_rtt);
         }
     }
 
@@ -174,6 +240,13 @@ impl BandwidthLimiter {
         if !self.enabled {
             return true;
         }
+        if let Some(congestion) = &mut self.congestion {
+            if !congestion.try_consume(packet_bytes) {
+                debug!("Congestion window reached, no more packets can be sent this tick");
+                return false;
+            }
+            return true;
+        }
 
         let Ok(packet_bytes) = u32::try_from(packet_bytes) else {
             error!("packet size exceeds bandwidth limiter range");
@@ -208,6 +281,13 @@ mod tests {
     struct TurnTrafficChannel;
     struct FragmentChannel;
 
+    #[test]
+    fn bandwidth_quota_is_the_sustained_rate() {
+        assert_eq!(PriorityManager::default().bandwidth_quota(), None);
+        let manager = PriorityManager::new(PriorityConfig::new(2000).with_burst_size(100));
+        assert_eq!(manager.bandwidth_quota(), Some(2000));
+    }
+
     #[test]
     fn disabled_priority_uses_fragment_first_size_ascending_packing_order() {
         let registry = ChannelRegistry::default();
@@ -497,4 +577,29 @@ mod tests {
         assert!(limiter.consume_packet_quota(300));
         assert!(!limiter.consume_packet_quota(301));
     }
+
+    #[test]
+    fn adaptive_limiter_shrinks_budget_after_loss() {
+        let config = CongestionControlConfig {
+            initial_bytes_per_second: 10_000,
+            multiplicative_decrease: 0.5,
+            min_interval: Duration::from_millis(100),
+            burst_duration: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let mut limiter = BandwidthLimiter::new(PriorityConfig::adaptive(config));
+        limiter.update(Duration::ZERO, Duration::ZERO);
+        limiter.begin_send(Duration::ZERO);
+        assert!(limiter.consume_packet_quota(1_000));
+        assert!(!limiter.consume_packet_quota(1));
+
+        limiter.on_packet_lost();
+        limiter.update(Duration::from_millis(100), Duration::ZERO);
+        assert_eq!(limiter.congestion_rate(), Some(5_000));
+
+        // the bucket now refills at the decreased rate
+        limiter.begin_send(Duration::from_millis(200));
+        assert!(limiter.consume_packet_quota(500));
+        assert!(!limiter.consume_packet_quota(1));
+    }
 }
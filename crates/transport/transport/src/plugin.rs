@@ -7,6 +7,7 @@ use crate::packet::compression::decompress_payload;
 use crate::packet::error::PacketError;
 use crate::packet::header::PacketHeader;
 use crate::packet::message::{FragmentData, MessageAck, ReceiveMessage, SingleData};
+use crate::packet::mtu_discovery::MtuDiscovery;
 use crate::packet::packet_type::PacketType;
 #[cfg(feature = "test_utils")]
 use crate::prelude::{AppChannelExt, ChannelMode, ChannelSettings};
@@ -81,7 +82,10 @@ impl TransportPlugin {
         #[cfg(feature = "std")] par_commands: ParallelCommands,
         #[cfg(not(feature = "std"))] mut commands: Commands,
         channel_registry: Res<ChannelRegistry>,
-        mut query: Query<(Entity, &mut Link, &mut Transport), (With<Linked>, Without<HostClient>)>,
+        mut query: Query<
+            (Entity, &mut Link, &mut Transport, Option<&mut MtuDiscovery>),
+            (With<Linked>, Without<HostClient>),
+        >,
     ) {
         #[cfg(feature = "metrics")]
         let _timer = timer_gauge!("transport/recv");
@@ -91,7 +95,7 @@ impl TransportPlugin {
         #[cfg(not(feature = "std"))]
         let query = query.iter_mut();
 
-        query.for_each(|(entity, mut link, mut transport)| {
+        query.for_each(|(entity, mut link, mut transport, mut mtu_discovery)| {
             // enable split borrows
             let transport = &mut *transport;
             // update with the latest time
@@ -173,6 +177,10 @@ impl TransportPlugin {
                     }
 
                     let mut packet_type = header.get_packet_type();
+                    // MTU probes only carry padding
+                    if packet_type == PacketType::MtuProbe {
+                        return Ok(());
+                    }
                     if packet_type.is_compressed() {
                         let compressed_payload = cursor.take_remaining();
                         let decompressed_payload =
@@ -293,6 +301,7 @@ impl TransportPlugin {
                 .update(time.elapsed(), &link.stats);
             let packet_message_acks = &mut transport.packet_message_acks;
             let senders = &mut transport.senders;
+            let bandwidth_limiter = &mut transport.bandwidth_limiter;
             transport
                 .packet_manager
                 .header_manager
@@ -301,6 +310,18 @@ impl TransportPlugin {
                 .try_for_each(|lost_packet| {
                     #[cfg(feature = "metrics")]
                     metrics::counter!("transport/packets_lost").increment(1);
+                    if let Some(mtu_discovery) = mtu_discovery.as_deref_mut() {
+                        // a lost probe only means that the probe was too large
+                        let is_probe = mtu_discovery.is_probe(lost_packet);
+                        if let Some(mtu) = mtu_discovery.on_packet_lost(lost_packet, link.min_mtu())
+                        {
+                            link.set_mtu(mtu).ok();
+                        }
+                        if is_probe {
+                            return Ok(());
+                        }
+                    }
+                    bandwidth_limiter.on_packet_lost();
                     trace!(
                         target: "lightyear_debug::transport",
                         kind = "packet_lost",
@@ -352,6 +373,7 @@ impl TransportPlugin {
             // Update the list of messages that have been acked
             let packet_message_acks = &mut transport.packet_message_acks;
             let senders = &mut transport.senders;
+            let bandwidth_limiter = &mut transport.bandwidth_limiter;
             transport
                 .packet_manager
                 .header_manager
@@ -359,6 +381,13 @@ impl TransportPlugin {
                 .drain(..)
                 .try_for_each(|(acked_packet, rtt_sample)| {
                     trace!("Acked packet {:?}", acked_packet);
+                    if let Some(mtu) = mtu_discovery
+                        .as_deref_mut()
+                        .and_then(|mtu_discovery| mtu_discovery.on_packet_acked(acked_packet))
+                    {
+                        link.set_mtu(mtu).ok();
+                    }
+                    bandwidth_limiter.on_packet_acked(rtt_sample);
                     trace!(
                         target: "lightyear_debug::transport",
                         kind = "packet_acked",
@@ -393,6 +422,15 @@ impl TransportPlugin {
                     Ok::<(), TransportError>(())
                 })
                 .ok();
+
+            // Adapt the send rate to the loss and RTT signals received this frame
+            transport
+                .bandwidth_limiter
+                .update(time.elapsed(), link.stats.rtt);
+            #[cfg(feature = "metrics")]
+            if let Some(rate) = transport.bandwidth_limiter.congestion_rate() {
+                metrics::gauge!("transport/congestion_rate").set(rate as f64);
+            }
         });
     }
 
@@ -400,14 +438,22 @@ impl TransportPlugin {
     fn buffer_send(
         real_time: Res<Time<Real>>,
         timeline: Res<LocalTimeline>,
-        mut query: Query<(&mut Link, &mut Transport, Option<&mut HostClient>), With<Linked>>,
+        mut query: Query<
+            (
+                &mut Link,
+                &mut Transport,
+                Option<&mut HostClient>,
+                Option<&mut MtuDiscovery>,
+            ),
+            With<Linked>,
+        >,
         channel_registry: Res<ChannelRegistry>,
     ) {
         #[cfg(feature = "metrics")]
         let _timer = timer_gauge!("transport/send");
         let tick = timeline.tick();
         let query = adaptive_for_each_mut!(query);
-        query.for_each(|(mut link, mut transport, host_client)| {
+        query.for_each(|(mut link, mut transport, host_client, mtu_discovery)| {
             // allow split borrows
             let transport = &mut *transport;
             let mtu = link.mtu();
@@ -457,6 +503,7 @@ impl TransportPlugin {
                 });
             }
             transport.priority_manager.prioritize(&channel_registry);
+            transport.bandwidth_limiter.begin_send(real_time.elapsed());
 
             let mut candidate_cursor = crate::packet::packet_builder::CandidateCursor::default();
             let mut total_bytes_sent = 0;
@@ -596,6 +643,27 @@ impl TransportPlugin {
                 .for_each(|channel| channel.finish_send(flush_outcome));
             transport.priority_manager.clear();
 
+            // MTU probes are control traffic and bypass bandwidth admission, like ACK-only packets.
+            if let Some(mut mtu_discovery) = mtu_discovery
+                && let Some(size) =
+                    mtu_discovery.next_probe_size(real_time.elapsed(), mtu, link.max_mtu())
+            {
+                match transport.packet_manager.build_mtu_probe_packet(tick, size) {
+                    Ok(mut packet) => {
+                        let packet_id = packet.packet_id;
+                        let payload = transport.packet_manager.take_send_payload(&mut packet);
+                        link.send.push(payload);
+                        transport
+                            .packet_manager
+                            .header_manager
+                            .commit_send_packet(packet_id, real_time.elapsed());
+                        mtu_discovery.on_probe_sent(packet_id, size, real_time.elapsed());
+                        total_bytes_sent += size as u32;
+                    }
+                    Err(error) => error!(?error, "failed to stage MTU probe packet"),
+                }
+            }
+
             // Every data packet carries the latest ACK state. When ACK information is pending but
             // no data packet entered the link this frame, send one header-only packet so the remote
             // peer learns about delivered packets without waiting for more application traffic.
@@ -644,11 +712,16 @@ impl TransportPlugin {
     #[cfg(any(feature = "client", feature = "server"))]
     fn handle_disconnection(
         trigger: On<Add, Disconnected>,
-        mut query: Query<&mut Transport>,
+        mut query: Query<(&mut Transport, Option<(&mut MtuDiscovery, &mut Link)>)>,
         registry: Res<ChannelRegistry>,
     ) {
-        if let Ok(mut transport) = query.get_mut(trigger.entity) {
+        if let Ok((mut transport, mtu_discovery)) = query.get_mut(trigger.entity) {
             transport.reset(&registry);
+            if let Some((mut mtu_discovery, mut link)) = mtu_discovery {
+                mtu_discovery.reset();
+                let min_mtu = link.min_mtu();
+                link.set_mtu(min_mtu).ok();
+            }
         }
     }
 }
@@ -913,6 +986,73 @@ mod tests {
         assert!(channel.message_nacks().is_empty());
     }
 
+    #[test]
+    fn acknowledged_mtu_probe_raises_link_mtu() {
+        let mut sender = World::new();
+        sender.insert_resource(ChannelRegistry::default());
+        sender.init_resource::<Time<Real>>();
+        sender.init_resource::<LocalTimeline>();
+        let sender_entity = sender
+            .spawn((
+                Link::default(),
+                Linked,
+                Transport::default(),
+                MtuDiscovery::default(),
+            ))
+            .id();
+
+        let mut receiver = World::new();
+        receiver.insert_resource(ChannelRegistry::default());
+        receiver.init_resource::<Time<Real>>();
+        receiver.init_resource::<LocalTimeline>();
+        let receiver_entity = receiver
+            .spawn((Link::default(), Linked, Transport::default()))
+            .id();
+
+        sender
+            .run_system_once(TransportPlugin::buffer_send)
+            .unwrap();
+        let probe = sender
+            .get_mut::<Link>(sender_entity)
+            .unwrap()
+            .send
+            .pop()
+            .expect("the sender must probe a larger MTU");
+        let probe_header = PacketHeader::from_bytes(&mut Reader::from(probe.clone())).unwrap();
+        assert_eq!(probe_header.get_packet_type(), PacketType::MtuProbe);
+        assert!(probe.len() > lightyear_link::DEFAULT_MTU);
+
+        receiver
+            .get_mut::<Link>(receiver_entity)
+            .unwrap()
+            .recv
+            .push_raw(lightyear_link::recv_payload_from_bytes(probe.clone()));
+        receiver
+            .run_system_once(TransportPlugin::buffer_receive)
+            .unwrap();
+        receiver
+            .run_system_once(TransportPlugin::buffer_send)
+            .unwrap();
+        let ack = receiver
+            .get_mut::<Link>(receiver_entity)
+            .unwrap()
+            .send
+            .pop()
+            .expect("the probe must be acknowledged");
+
+        sender
+            .get_mut::<Link>(sender_entity)
+            .unwrap()
+            .recv
+            .push_raw(lightyear_link::recv_payload_from_bytes(ack));
+        sender
+            .run_system_once(TransportPlugin::buffer_receive)
+            .unwrap();
+        let link = sender.get::<Link>(sender_entity).unwrap();
+        assert_eq!(link.mtu(), probe.len());
+        assert_eq!(link.min_mtu(), lightyear_link::DEFAULT_MTU);
+    }
+
     #[test]
     fn packet_builder_uses_link_mtu_for_fragmentation_and_packet_size() {
         let settings = ChannelSettings::default();
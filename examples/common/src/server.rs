@@ -37,6 +37,77 @@ pub enum ServerTransports {
     Steam {
         local_port: u16,
     },
+    /// Accept clients on several transports at once, for example UDP for native clients and
+    /// WebTransport for browser clients. Each transport gets its own listener entity, but all
+    /// the clients belong to the same server.
+    Multi(Vec<ServerTransports>),
+}
+
+impl ServerTransports {
+    /// Returns true if the clients of this transport connect through netcode
+    fn uses_netcode(&self) -> bool {
+        match self {
+            #[cfg(feature = "steam")]
+            ServerTransports::Steam { .. } => false,
+            ServerTransports::Multi(transports) => transports.iter().any(Self::uses_netcode),
+            _ => true,
+        }
+    }
+
+    /// Inserts the IO of a single transport on `entity_mut`
+    fn insert_io(self, entity_mut: &mut EntityWorldMut) {
+        match self {
+            #[cfg(feature = "udp")]
+            ServerTransports::Udp { local_port } => {
+                let server_addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), local_port);
+                entity_mut.insert((LocalAddr(server_addr), ServerUdpIo::default()));
+            }
+            ServerTransports::WebTransport {
+                local_port,
+                certificate,
+            } => {
+                let server_addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), local_port);
+                entity_mut.insert((
+                    LocalAddr(server_addr),
+                    WebTransportServerIo {
+                        certificate: (&certificate).into(),
+                    },
+                ));
+            }
+            ServerTransports::WebSocket { local_port } => {
+                let server_addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), local_port);
+                let sans = vec![
+                    "localhost".to_string(),
+                    "127.0.0.1".to_string(),
+                    "::1".to_string(),
+                ];
+                let config = ServerConfig::builder()
+                    .with_bind_address(server_addr)
+                    .with_identity(
+                        lightyear::websocket::server::Identity::self_signed(sans).unwrap(),
+                    );
+                entity_mut.insert((LocalAddr(server_addr), WebSocketServerIo { config }));
+            }
+            #[cfg(feature = "steam")]
+            ServerTransports::Steam { local_port } => {
+                let server_addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), local_port);
+                entity_mut.insert(SteamServerIo {
+                    target: ListenTarget::Addr(server_addr),
+                    config: SessionConfig::default(),
+                });
+            }
+            ServerTransports::Multi(transports) => {
+                let server = entity_mut.id();
+                entity_mut.world_scope(|world| {
+                    for transport in transports {
+                        let mut listener =
+                            world.spawn((Name::from("ServerListener"), ListenerOf { server }));
+                        transport.insert_io(&mut listener);
+                    }
+                });
+            }
+        }
+    }
 }
 
 #[derive(Component, Debug)]
@@ -60,7 +131,7 @@ impl ExampleServer {
                 Server::new(settings.conditioner.clone()),
             ));
 
-            let add_netcode = |entity_mut: &mut EntityWorldMut| {
+            if settings.transport.uses_netcode() {
                 // Use private key from environment variable, if set. Otherwise from settings file.
                 let private_key = if let Some(key) = parse_private_key_from_env() {
                     info!("Using private key from LIGHTYEAR_PRIVATE_KEY env var");
@@ -73,51 +144,8 @@ impl ExampleServer {
                     private_key,
                     ..Default::default()
                 }));
-            };
-            match settings.transport {
-                #[cfg(feature = "udp")]
-                ServerTransports::Udp { local_port } => {
-                    add_netcode(&mut entity_mut);
-                    let server_addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), local_port);
-                    entity_mut.insert((LocalAddr(server_addr), ServerUdpIo::default()));
-                }
-                ServerTransports::WebTransport {
-                    local_port,
-                    certificate,
-                } => {
-                    add_netcode(&mut entity_mut);
-                    let server_addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), local_port);
-                    entity_mut.insert((
-                        LocalAddr(server_addr),
-                        WebTransportServerIo {
-                            certificate: (&certificate).into(),
-                        },
-                    ));
-                }
-                ServerTransports::WebSocket { local_port } => {
-                    add_netcode(&mut entity_mut);
-                    let server_addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), local_port);
-                    let sans = vec![
-                        "localhost".to_string(),
-                        "127.0.0.1".to_string(),
-                        "::1".to_string(),
-                    ];
-                    let config = ServerConfig::builder()
-                        .with_bind_address(server_addr)
-                        .with_identity(
-                            lightyear::websocket::server::Identity::self_signed(sans).unwrap(),
-                        );
-                    entity_mut.insert((LocalAddr(server_addr), WebSocketServerIo { config }));
-                }
-                #[cfg(feature = "steam")]
-                ServerTransports::Steam { local_port } => {
-                    let server_addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), local_port);
-                    entity_mut.insert(SteamServerIo {
-                        target: ListenTarget::Addr(server_addr),
-                        config: SessionConfig::default(),
-                    });
-                }
-            };
+            }
+            settings.transport.insert_io(&mut entity_mut);
             Ok(())
         });
     }